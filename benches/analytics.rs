@@ -0,0 +1,198 @@
+//! Benchmarks of the analytics layer over synthetic emissions
+//!
+//! Every benchmark runs over [`ROWS`] generated emissions spread over three
+//! providers, 24 months and a few thousand data points, so most data points
+//! are reported several times, as they are after overlapping backfills.
+//!
+//! After a `cargo bench` run, the mean time of each benchmark is compared to
+//! its budget in [`BUDGETS`] and the run fails when one is over, so a change
+//! slowing the analytics layer down is caught before it accumulates. The
+//! release workflow enforces the budgets before publishing; CI sets
+//! `CARBEM_BENCH_NO_BUDGETS` to only check that the benchmarks run. The
+//! Parquet export is benchmarked with the `parquet` feature.
+//!
+//! ```bash
+//! cargo bench --bench analytics --features parquet
+//! ```
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use carbem::aggregation::{Dimension, EmissionDataset};
+use carbem::{CarbonEmission, EmissionMetadata, FlatEmissionRecord, OutputFormat, TimePeriod};
+use chrono::{Months, TimeZone, Utc};
+use criterion::{Criterion, Throughput, black_box};
+use serde_json::json;
+
+/// Synthetic emissions per benchmark
+const ROWS: usize = 1_000_000;
+
+/// Mean time allowed per benchmark over [`ROWS`] emissions, in milliseconds
+///
+/// Budgets are about three times the mean measured on a 4-core development
+/// machine, leaving room for the shared runners of the release workflow.
+const BUDGETS: [(&str, u64); 9] = [
+    ("aggregation/total", 30),
+    ("aggregation/by_service", 200),
+    ("aggregation/by_month", 200),
+    ("aggregation/by_provider_region_service", 1_000),
+    ("dedup/diff", 4_000),
+    ("flatten/records", 1_000),
+    ("export/csv", 6_500),
+    ("export/json", 4_000),
+    ("export/parquet", 4_500),
+];
+
+const PROVIDERS: [&str; 3] = ["azure", "gcp", "ibm"];
+const REGIONS: usize = 24;
+const SERVICES: usize = 40;
+const MONTHS: usize = 24;
+
+// Deterministic emissions; every 8th one carries provider data, as a few
+// provider rows do
+fn synthetic_emissions(rows: usize) -> Vec<CarbonEmission> {
+    let first_month = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+    (0..rows)
+        .map(|i| {
+            let provider = PROVIDERS[i % PROVIDERS.len()];
+            let month = (i / 7) % MONTHS;
+            let start = first_month + Months::new(month as u32);
+            let end = start + Months::new(1);
+            CarbonEmission {
+                provider: provider.to_string(),
+                region: format!("region-{}", (i / 3) % REGIONS),
+                service: (i % 97 != 0).then(|| format!("service-{}", (i / 11) % SERVICES)),
+                service_category: None,
+                lineage: None,
+                emissions_kg_co2eq: ((i * 7919) % 100_000) as f64 / 100.0,
+                time_period: TimePeriod { start, end },
+                metadata: Some(EmissionMetadata {
+                    energy_kwh: Some(((i * 104_729) % 500_000) as f64 / 100.0),
+                    grid_carbon_intensity: Some(200.0 + (i % 300) as f64),
+                    renewable_percentage: None,
+                    date_alignment: None,
+                    provider_data: (i % 8 == 0).then(|| json!({"row": i, "scope": "scope2"})),
+                    tags: Default::default(),
+                    estimated: None,
+                }),
+            }
+        })
+        .collect()
+}
+
+fn benchmarks(c: &mut Criterion, dataset: &EmissionDataset) {
+    let mut group = c.benchmark_group("aggregation");
+    group.throughput(Throughput::Elements(ROWS as u64));
+    group.bench_function("total", |b| b.iter(|| dataset.total_kg_co2eq()));
+    group.bench_function("by_service", |b| {
+        b.iter(|| dataset.group_by_dimension(Dimension::Service))
+    });
+    group.bench_function("by_month", |b| b.iter(|| dataset.group_by_month()));
+    group.bench_function("by_provider_region_service", |b| {
+        b.iter(|| {
+            dataset.group_by(|e| {
+                (
+                    e.provider.clone(),
+                    e.region.clone(),
+                    e.service.clone().unwrap_or_default(),
+                )
+            })
+        })
+    });
+    group.finish();
+
+    // Duplicate data points are summed into one on both sides of a diff
+    let mut group = c.benchmark_group("dedup");
+    group.throughput(Throughput::Elements(2 * ROWS as u64));
+    let restated = dataset.filter(|e| e.region != "region-0");
+    group.bench_function("diff", |b| b.iter(|| dataset.diff(&restated)));
+    group.finish();
+
+    let mut group = c.benchmark_group("flatten");
+    group.throughput(Throughput::Elements(ROWS as u64));
+    group.bench_function("records", |b| {
+        b.iter(|| {
+            dataset
+                .emissions()
+                .iter()
+                .map(FlatEmissionRecord::from)
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("export");
+    group.throughput(Throughput::Elements(ROWS as u64));
+    for (name, format) in [("csv", OutputFormat::Csv), ("json", OutputFormat::Json)] {
+        group.bench_function(name, |b| {
+            b.iter(|| format.render(black_box(dataset.emissions())).unwrap())
+        });
+    }
+    // Uncompressed, like the CSV and JSON exports
+    #[cfg(feature = "parquet")]
+    group.bench_function("parquet", |b| {
+        b.iter(|| {
+            let records: Vec<FlatEmissionRecord> = black_box(dataset.emissions())
+                .iter()
+                .map(Into::into)
+                .collect();
+            carbem::sinks::parquet::encode(&records, carbem::sinks::Compression::None).unwrap()
+        })
+    });
+    group.finish();
+}
+
+// Directory criterion writes its estimates to
+fn criterion_dir() -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"))
+        .join("criterion")
+}
+
+// Benchmarks of the run started at `started` over their budget, with their
+// mean and budget; benchmarks that did not run (filtered out, or run as
+// tests) have no estimate written since and are skipped
+fn over_budget(started: SystemTime) -> Vec<(&'static str, Duration, Duration)> {
+    let dir = criterion_dir();
+    BUDGETS
+        .iter()
+        .filter_map(|&(id, budget_ms)| {
+            let path = dir.join(id).join("new").join("estimates.json");
+            if std::fs::metadata(&path).ok()?.modified().ok()? < started {
+                return None;
+            }
+            let estimates: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+            let mean_ns = estimates["mean"]["point_estimate"].as_f64()?;
+            let mean = Duration::from_nanos(mean_ns as u64);
+            let budget = Duration::from_millis(budget_ms);
+            (mean > budget).then_some((id, mean, budget))
+        })
+        .collect()
+}
+
+fn main() {
+    let started = SystemTime::now();
+    let mut criterion = Criterion::default()
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10))
+        .configure_from_args();
+    let dataset = EmissionDataset::new(synthetic_emissions(ROWS));
+    benchmarks(&mut criterion, &dataset);
+    criterion.final_summary();
+
+    if std::env::var_os("CARBEM_BENCH_NO_BUDGETS").is_some() {
+        return;
+    }
+    let over = over_budget(started);
+    for (id, mean, budget) in &over {
+        eprintln!(
+            "{} took {:?} on average, over its budget of {:?}",
+            id, mean, budget
+        );
+    }
+    if !over.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,113 @@
+//! Checks last month's emissions against a carbon budget
+//!
+//! Meant for a scheduled CI job: prints the summary of the last complete
+//! month, posts it to Slack when `SLACK_WEBHOOK_URL` is set, and exits with
+//! [`ExitStatus::BudgetBreach`] (7) when emissions exceeded the budget, or
+//! with the class of the error when the query failed.
+//!
+//! ```bash
+//! # Budget of 5 t CO2e a month for Azure
+//! CARBEM_CONFIG=carbem.json cargo run --example budget_checker -- 5000 azure
+//! ```
+
+mod common;
+
+use std::process::ExitCode;
+
+use carbem::aggregation::EmissionDataset;
+use carbem::{
+    CarbemClient, CarbemError, EmissionQuery, ExitStatus, Result, SlackNotifier, Summary,
+    SummaryPeriod,
+};
+use chrono::{DateTime, Datelike, Months, Utc};
+
+use crate::common::client;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(budget) = args.next().and_then(|budget| budget.parse::<f64>().ok()) else {
+        eprintln!("usage: budget_checker <budget in kg CO2e> [provider]");
+        return ExitStatus::Config.into();
+    };
+    let provider = args.next();
+
+    let summary = match client() {
+        Ok(client) => check(&client, provider.as_deref(), budget, Utc::now()).await,
+        Err(e) => Err(e),
+    };
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("Budget check failed: {}", e);
+            return ExitStatus::from(&e).into();
+        }
+    };
+
+    println!("{}", summary.to_slack_text());
+    if let Ok(webhook_url) = std::env::var("SLACK_WEBHOOK_URL")
+        && let Err(e) = SlackNotifier::new(webhook_url).notify(&summary).await
+    {
+        eprintln!("Failed to post to Slack: {}", e);
+    }
+    ExitStatus::for_summary(&summary).into()
+}
+
+// Summary of the last complete month before `as_of` against `budget_kg_co2eq`
+async fn check(
+    client: &CarbemClient,
+    provider: Option<&str>,
+    budget_kg_co2eq: f64,
+    as_of: DateTime<Utc>,
+) -> Result<Summary> {
+    let providers: Vec<&str> = match provider {
+        Some(provider) if client.has_provider(provider) => vec![provider],
+        Some(provider) => return Err(CarbemError::UnsupportedProvider(provider.to_string())),
+        None => client.available_providers(),
+    };
+
+    // The month before is queried too, for the change reported in the summary
+    let today = as_of.date_naive();
+    let this_month = today.with_day(1).unwrap_or(today);
+    let mut dataset = EmissionDataset::default();
+    for provider in providers {
+        let query = EmissionQuery::from_query_string(&format!(
+            "provider={}&start={}&end={}",
+            provider,
+            this_month - Months::new(2),
+            this_month.pred_opt().unwrap_or(this_month)
+        ))?;
+        dataset.extend(client.query_emissions(&query).await?);
+    }
+    Ok(Summary::compute(&dataset, SummaryPeriod::Monthly, as_of)
+        .with_budget_kg_co2eq(budget_kg_co2eq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_budget_breach_sets_exit_status() {
+        let client = CarbemClient::demo();
+        let as_of = Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap();
+
+        let summary = check(&client, Some("azure"), 1_000_000.0, as_of)
+            .await
+            .unwrap();
+        assert!(summary.total_kg_co2eq > 0.0);
+        assert!(summary.previous_kg_co2eq > 0.0);
+        assert_eq!(ExitStatus::for_summary(&summary), ExitStatus::Success);
+
+        let total = summary.total_kg_co2eq;
+        let summary = check(&client, Some("azure"), total / 2.0, as_of)
+            .await
+            .unwrap();
+        assert_eq!(summary.budget.unwrap().used_percent.round(), 200.0);
+        assert_eq!(ExitStatus::for_summary(&summary), ExitStatus::BudgetBreach);
+
+        let error = check(&client, Some("gcp"), 1.0, as_of).await.unwrap_err();
+        assert_eq!(ExitStatus::from(&error), ExitStatus::Config);
+    }
+}
@@ -0,0 +1,19 @@
+//! Client shared by the examples
+
+use carbem::{CarbemClient, CarbemError, ClientConfig, Result};
+
+/// Client of the accounts in the file named by `CARBEM_CONFIG`, else of the
+/// demo dataset
+pub fn client() -> Result<CarbemClient> {
+    match std::env::var("CARBEM_CONFIG") {
+        Ok(path) => {
+            let json = std::fs::read_to_string(&path)
+                .map_err(|e| CarbemError::Config(format!("Failed to read {}: {}", path, e)))?;
+            CarbemClient::from_config(&ClientConfig::from_json(&json)?)
+        }
+        Err(_) => {
+            eprintln!("CARBEM_CONFIG is not set, using the demo dataset");
+            Ok(CarbemClient::demo())
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Feeds a dashboard with the emissions of every configured provider
+//!
+//! Queries the last complete months of each provider of the client at once
+//! and writes one JSON document with the totals a dashboard plots: by month
+//! and provider, by region, and the services emitting the most.
+//!
+//! ```bash
+//! # Accounts of a client configuration file, or the demo dataset without one
+//! CARBEM_CONFIG=carbem.json cargo run --example dashboard_feeder -- feed.json 12
+//! ```
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use carbem::aggregation::{Dimension, EmissionDataset};
+use carbem::{CarbemClient, CarbemError, EmissionQuery, Result};
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use crate::common::client;
+
+/// Services listed in the feed
+const TOP_SERVICES: usize = 5;
+
+/// Document read by the dashboard
+#[derive(Debug, Serialize)]
+struct DashboardFeed {
+    generated_at: DateTime<Utc>,
+    start: NaiveDate,
+    end: NaiveDate,
+    total_kg_co2eq: f64,
+    by_month: BTreeMap<String, BTreeMap<String, f64>>,
+    by_region: BTreeMap<String, f64>,
+    top_services: Vec<(String, f64)>,
+    failed_providers: BTreeMap<String, String>,
+}
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let output = args.next();
+    let months = args.next().map(|months| months.parse()).transpose()?;
+
+    let feed = feed(&client()?, Utc::now(), months.unwrap_or(12)).await?;
+    let json = serde_json::to_string_pretty(&feed)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, json)?;
+            println!(
+                "Wrote {:.1} kg CO2e from {} to {} to {}",
+                feed.total_kg_co2eq, feed.start, feed.end, path
+            );
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+// First and last day of the `months` complete months before `as_of`
+fn last_months(as_of: DateTime<Utc>, months: u32) -> (NaiveDate, NaiveDate) {
+    let today = as_of.date_naive();
+    let this_month = today.with_day(1).unwrap_or(today);
+    let start = this_month - Months::new(months.max(1));
+    (start, this_month.pred_opt().unwrap_or(this_month))
+}
+
+// Feed of every provider of `client`, queried concurrently
+async fn feed(client: &CarbemClient, as_of: DateTime<Utc>, months: u32) -> Result<DashboardFeed> {
+    let (start, end) = last_months(as_of, months);
+    let mut queries = JoinSet::new();
+    for provider in client.available_providers() {
+        let query = EmissionQuery::from_query_string(&format!(
+            "provider={}&start={}&end={}",
+            provider, start, end
+        ))?;
+        let client = client.clone();
+        let provider = provider.to_string();
+        queries.spawn(async move { (provider, client.query_emissions(&query).await) });
+    }
+
+    // A failing provider leaves a gap in the dashboard rather than no dashboard
+    let mut dataset = EmissionDataset::default();
+    let mut failed_providers = BTreeMap::new();
+    while let Some(joined) = queries.join_next().await {
+        let (provider, result) =
+            joined.map_err(|e| CarbemError::Other(format!("Query task failed: {}", e)))?;
+        match result {
+            Ok(emissions) => dataset.extend(emissions),
+            Err(e) => {
+                eprintln!("{} query failed: {}", provider, e);
+                failed_providers.insert(provider, e.to_string());
+            }
+        }
+    }
+
+    let mut by_month: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+    for ((month, provider), kg) in dataset.group_by(|emission| {
+        (
+            emission.time_period.start.format("%Y-%m").to_string(),
+            emission.provider.clone(),
+        )
+    }) {
+        by_month.entry(month).or_default().insert(provider, kg);
+    }
+    let mut top_services: Vec<(String, f64)> = dataset
+        .group_by_dimension(Dimension::Service)
+        .into_iter()
+        .collect();
+    top_services.sort_by(|a, b| b.1.total_cmp(&a.1));
+    top_services.truncate(TOP_SERVICES);
+
+    Ok(DashboardFeed {
+        generated_at: as_of,
+        start,
+        end,
+        total_kg_co2eq: dataset.total_kg_co2eq(),
+        by_month,
+        by_region: dataset.group_by_dimension(Dimension::Region),
+        top_services,
+        failed_providers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_demo_feed_covers_every_provider_and_month() {
+        let as_of = Utc.with_ymd_and_hms(2024, 7, 15, 8, 0, 0).unwrap();
+        let feed = feed(&CarbemClient::demo(), as_of, 6).await.unwrap();
+
+        assert_eq!(feed.start, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(feed.end, NaiveDate::from_ymd_opt(2024, 6, 30).unwrap());
+        assert!(feed.failed_providers.is_empty());
+        assert_eq!(feed.by_month.len(), 6);
+        for providers in feed.by_month.values() {
+            assert_eq!(providers.keys().collect::<Vec<_>>(), ["azure", "ibm"]);
+        }
+        let monthly: f64 = feed.by_month.values().flat_map(|p| p.values()).sum();
+        assert!((monthly - feed.total_kg_co2eq).abs() < 1e-6);
+        assert_eq!(feed.top_services.len(), TOP_SERVICES);
+        assert!(feed.top_services[0].1 >= feed.top_services[1].1);
+    }
+}
@@ -0,0 +1,104 @@
+//! Generates the emissions report of the last complete month
+//!
+//! Queries every configured provider for the last month, then writes a
+//! Markdown report by provider, region and service, with each one's change
+//! from the month before. With an `.html` output path, the report is written
+//! as a standalone HTML page instead.
+//!
+//! ```bash
+//! # Accounts of a client configuration file, or the demo dataset without one
+//! CARBEM_CONFIG=carbem.json cargo run --example monthly_report -- report.html
+//! ```
+
+mod common;
+
+use carbem::aggregation::{Dimension, EmissionDataset};
+use carbem::{CarbemClient, EmissionQuery, Report, ReportSection, Result, Summary, SummaryPeriod};
+use chrono::{DateTime, Datelike, Months, Utc};
+
+use crate::common::client;
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let output = std::env::args().nth(1);
+    let report = monthly_report(&client()?, Utc::now()).await?;
+    match output {
+        Some(path) if path.ends_with(".html") => std::fs::write(&path, report.to_html())?,
+        Some(path) => std::fs::write(&path, report.to_markdown())?,
+        None => print!("{}", report.to_markdown()),
+    }
+    Ok(())
+}
+
+// Report of the last complete month before `as_of`, compared to the month before
+async fn monthly_report(client: &CarbemClient, as_of: DateTime<Utc>) -> Result<Report> {
+    let today = as_of.date_naive();
+    let this_month = today.with_day(1).unwrap_or(today);
+    let start = this_month - Months::new(2);
+    let end = this_month.pred_opt().unwrap_or(this_month);
+
+    // Both months are queried, the earlier one for the comparison only
+    let mut dataset = EmissionDataset::default();
+    for provider in client.available_providers() {
+        let query = EmissionQuery::from_query_string(&format!(
+            "provider={}&start={}&end={}",
+            provider, start, end
+        ))?;
+        dataset.extend(client.query_emissions(&query).await?);
+    }
+    let summary = Summary::compute(&dataset, SummaryPeriod::Monthly, as_of);
+    let month = dataset.filter(|emission| {
+        emission.time_period.start >= summary.start && emission.time_period.start < summary.end
+    });
+
+    let title = format!("Cloud emissions, {}", summary.start.format("%B %Y"));
+    let mut report = Report::by_dimensions(
+        title,
+        &month,
+        &[Dimension::Provider, Dimension::Region, Dimension::Service],
+    );
+    report.sections.push(ReportSection {
+        heading: "Largest changes from the month before".to_string(),
+        columns: vec![
+            "Service".to_string(),
+            "kg CO2e".to_string(),
+            "Change (kg CO2e)".to_string(),
+        ],
+        rows: summary
+            .top_movers
+            .iter()
+            .map(|mover| {
+                vec![
+                    mover.service.clone(),
+                    format!("{:.2}", mover.current_kg_co2eq),
+                    format!("{:+.2}", mover.change_kg_co2eq()),
+                ]
+            })
+            .collect(),
+    });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_demo_report_covers_last_month() {
+        let as_of = Utc.with_ymd_and_hms(2024, 4, 2, 6, 0, 0).unwrap();
+        let report = monthly_report(&CarbemClient::demo(), as_of).await.unwrap();
+
+        assert_eq!(report.title, "Cloud emissions, March 2024");
+        let (start, end) = report.period.unwrap();
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap());
+        assert!(end < Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap());
+        assert_eq!(report.sections.len(), 5);
+        assert_eq!(report.sections[4].rows.len(), 3);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("westeurope"));
+        assert!(markdown.contains("Largest changes from the month before"));
+        assert!(report.to_html().contains("<table"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Exports emissions as Prometheus metrics
+//!
+//! Serves `/metrics` in the Prometheus text format, with the emissions of
+//! the last complete month by provider, region and service, refreshed in the
+//! background so that scrapes never wait for a provider. `/healthz` and
+//! `/readyz` are served for the orchestrator, readiness probing the
+//! providers.
+//!
+//! ```bash
+//! # Refresh every hour, serve on port 9464
+//! CARBEM_CONFIG=carbem.json cargo run --example prometheus_exporter --features axum -- 0.0.0.0:9464 3600
+//! ```
+
+mod common;
+
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use carbem::aggregation::EmissionDataset;
+use carbem::web::{DEFAULT_PROBE_MAX_AGE, HealthChecks};
+use carbem::{CarbemClient, EmissionQuery, Result};
+use chrono::{DateTime, Datelike, Months, Utc};
+use tokio::sync::RwLock;
+
+use crate::common::client;
+
+/// Content type of the Prometheus text format
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Last rendered metrics and the refreshes that failed so far
+#[derive(Debug, Default)]
+struct Metrics {
+    emissions: String,
+    refreshed_at: Option<DateTime<Utc>>,
+    failures: u64,
+}
+
+type SharedMetrics = Arc<RwLock<Metrics>>;
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:9464".to_string());
+    let refresh = Duration::from_secs(args.next().map(|s| s.parse()).transpose()?.unwrap_or(3600));
+
+    let client = client()?;
+    let metrics = SharedMetrics::default();
+    tokio::spawn(refresh_periodically(
+        client.clone(),
+        metrics.clone(),
+        refresh,
+    ));
+
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    println!("Serving metrics on http://{}/metrics", address);
+    axum::serve(listener, router(client, metrics)).await?;
+    Ok(())
+}
+
+fn router(client: CarbemClient, metrics: SharedMetrics) -> Router {
+    Router::new()
+        .route("/metrics", get(serve_metrics))
+        .with_state(metrics)
+        .merge(
+            HealthChecks::new(client)
+                .with_provider_probes(DEFAULT_PROBE_MAX_AGE)
+                .router(),
+        )
+}
+
+async fn serve_metrics(State(metrics): State<SharedMetrics>) -> impl IntoResponse {
+    let metrics = metrics.read().await;
+    let mut text = metrics.emissions.clone();
+    text.push_str("# HELP carbem_refresh_failures_total Refreshes of the emissions that failed\n");
+    text.push_str("# TYPE carbem_refresh_failures_total counter\n");
+    let _ = writeln!(text, "carbem_refresh_failures_total {}", metrics.failures);
+    if let Some(refreshed_at) = metrics.refreshed_at {
+        text.push_str(
+            "# HELP carbem_refresh_timestamp_seconds Last successful refresh of the emissions\n",
+        );
+        text.push_str("# TYPE carbem_refresh_timestamp_seconds gauge\n");
+        let _ = writeln!(
+            text,
+            "carbem_refresh_timestamp_seconds {}",
+            refreshed_at.timestamp()
+        );
+    }
+    ([(CONTENT_TYPE, TEXT_FORMAT)], text)
+}
+
+// Refresh `metrics` now, then every `interval`
+async fn refresh_periodically(client: CarbemClient, metrics: SharedMetrics, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        refresh(&client, &metrics, Utc::now()).await;
+    }
+}
+
+// Query the last complete month before `now` and render it; a failed
+// refresh keeps the previous values, which Prometheus would rather scrape
+// than none
+async fn refresh(client: &CarbemClient, metrics: &SharedMetrics, now: DateTime<Utc>) {
+    match last_month(client, now).await {
+        Ok(dataset) => {
+            let emissions = render(&dataset);
+            let mut metrics = metrics.write().await;
+            metrics.emissions = emissions;
+            metrics.refreshed_at = Some(now);
+        }
+        Err(e) => {
+            eprintln!("Failed to refresh emissions: {}", e);
+            metrics.write().await.failures += 1;
+        }
+    }
+}
+
+async fn last_month(client: &CarbemClient, now: DateTime<Utc>) -> Result<EmissionDataset> {
+    let today = now.date_naive();
+    let this_month = today.with_day(1).unwrap_or(today);
+    let mut dataset = EmissionDataset::default();
+    for provider in client.available_providers() {
+        let query = EmissionQuery::from_query_string(&format!(
+            "provider={}&start={}&end={}",
+            provider,
+            this_month - Months::new(1),
+            this_month.pred_opt().unwrap_or(this_month)
+        ))?;
+        dataset.extend(client.query_emissions(&query).await?);
+    }
+    Ok(dataset)
+}
+
+// Emissions by provider, region and service in the Prometheus text format
+fn render(dataset: &EmissionDataset) -> String {
+    let mut text = String::new();
+    text.push_str(
+        "# HELP carbem_emissions_kg_co2eq Emissions of the last complete month in kg CO2e\n",
+    );
+    text.push_str("# TYPE carbem_emissions_kg_co2eq gauge\n");
+    let series = dataset.group_by(|emission| {
+        (
+            emission.provider.clone(),
+            emission.region.clone(),
+            emission.service.clone().unwrap_or_default(),
+        )
+    });
+    for ((provider, region, service), kg) in series {
+        let _ = writeln!(
+            text,
+            "carbem_emissions_kg_co2eq{{provider=\"{}\",region=\"{}\",service=\"{}\"}} {}",
+            escape(&provider),
+            escape(&region),
+            escape(&service),
+            kg
+        );
+    }
+    text
+}
+
+// Label value with backslashes, quotes and line feeds escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode};
+    use chrono::TimeZone;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_metrics_are_served_after_refresh() {
+        let client = CarbemClient::demo();
+        let metrics = SharedMetrics::default();
+        let now = Utc.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        refresh(&client, &metrics, now).await;
+
+        let response = router(client, metrics)
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], TEXT_FORMAT);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(text.contains("# TYPE carbem_emissions_kg_co2eq gauge"));
+        assert!(text.contains(
+            "carbem_emissions_kg_co2eq{provider=\"azure\",region=\"westeurope\",service=\"Storage\"} "
+        ));
+        assert!(text.contains("carbem_refresh_failures_total 0"));
+        assert!(text.contains(&format!(
+            "carbem_refresh_timestamp_seconds {}",
+            now.timestamp()
+        )));
+        assert_eq!(escape("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -1,5 +1,5 @@
 use carbem::CarbemClient;
-use carbem::models::{EmissionQuery, TimePeriod};
+use carbem::models::{DateAlignment, EmissionQuery, QueryTimezone, RawResponseMode, TimePeriod};
 use carbem::{AzureCarbonScope, AzureQueryConfig, AzureReportType, ProviderQueryConfig};
 use chrono::{TimeZone, Utc};
 
@@ -26,8 +26,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         resources: None,
         // Type-safe configuration for Azure (required)
         provider_config: Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::MonthlySummaryReport,
-            subscription_list: vec!["your-subscription-id".to_string()], // Replace with your subscription ID
+            report_type: Some(AzureReportType::MonthlySummaryReport),
+            subscription_list: Some(vec!["your-subscription-id".to_string()]), // Replace with your subscription ID
+            all_subscriptions: None,
             carbon_scope_list: Some(vec![AzureCarbonScope::Scope1, AzureCarbonScope::Scope3]),
             category_type: None,
             order_by: None,
@@ -38,6 +39,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             resource_type_list: None,
             skip_token: None,
         })),
+        raw_response: RawResponseMode::None,
+        date_alignment: DateAlignment::ExpandToFullMonths,
+        timezone: QueryTimezone::Utc,
+        dry_run: false,
+        route: Vec::new(),
+        tag_filters: Vec::new(),
+        strict: false,
     };
 
     println!("Querying Azure carbon emissions...");
@@ -0,0 +1,21 @@
+//! IBM carbon emission responses, parsed and converted
+
+#![no_main]
+
+use std::sync::Arc;
+
+use carbem::providers::CarbonProvider;
+use carbem::{IbmConfig, IbmProvider};
+use carbem_fuzz::{Answering, block_on, expected_error, query};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|body: &[u8]| {
+    let provider = IbmProvider::new(IbmConfig {
+        api_key: "key".to_string(),
+    })
+    .unwrap()
+    .with_transport(Arc::new(Answering(body.to_vec())));
+    if let Err(e) = block_on(provider.get_emissions(&query("ibm"))) {
+        assert!(expected_error(&e), "unexpected error: {}", e);
+    }
+});
@@ -0,0 +1,27 @@
+//! Queries from URL query strings, converted to Azure bodies and IBM URLs
+
+#![no_main]
+
+use carbem::providers::CarbonProvider;
+use carbem::{AzureConfig, AzureProvider, EmissionQuery, IbmConfig, IbmProvider};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let Ok(query) = EmissionQuery::from_query_string(input) else {
+        return;
+    };
+    let azure = AzureProvider::new(AzureConfig {
+        access_token: "token".to_string(),
+    })
+    .unwrap();
+    let ibm = IbmProvider::new(IbmConfig {
+        api_key: "key".to_string(),
+    })
+    .unwrap();
+    // Invalid queries are rejected; valid ones plan well-formed requests
+    for requests in [azure.plan_requests(&query), ibm.plan_requests(&query)] {
+        for request in requests.into_iter().flatten() {
+            assert!(request.url.starts_with("https://"), "{}", request.url);
+        }
+    }
+});
@@ -0,0 +1,65 @@
+//! Helpers shared by the fuzz targets
+
+use std::sync::LazyLock;
+
+use async_trait::async_trait;
+use carbem::transport::{HttpRequest, HttpResponse, Transport};
+use carbem::{
+    AzureQueryConfig, CarbemError, EmissionQuery, IbmGroupBy, IbmQueryConfig, ProviderQueryConfig,
+    Result,
+};
+use reqwest::StatusCode;
+
+static RUNTIME: LazyLock<tokio::runtime::Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime starts")
+});
+
+/// Transport answering every request with `body`
+#[derive(Debug)]
+pub struct Answering(pub Vec<u8>);
+
+#[async_trait]
+impl Transport for Answering {
+    async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+        Ok(HttpResponse::new(StatusCode::OK, self.0.clone()))
+    }
+}
+
+/// Run `future` to completion
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME.block_on(future)
+}
+
+/// A monthly summary query of `provider`, valid for both providers
+pub fn query(provider: &str) -> EmissionQuery {
+    let mut query = EmissionQuery::from_query_string(&format!(
+        "provider={}&regions=dallas&start=2024-01-01&end=2024-03-31",
+        provider
+    ))
+    .expect("query is valid");
+    query.provider_config = Some(match provider {
+        "azure" => ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            ..Default::default()
+        }),
+        _ => ProviderQueryConfig::Ibm(IbmQueryConfig {
+            enterprise_id: "enterprise".to_string(),
+            group_by: Some(IbmGroupBy::Month),
+            enterprise_account_id: None,
+            account_group_id: None,
+            limit: None,
+            offset: None,
+        }),
+    });
+    query
+}
+
+/// Whether `error` is one a malformed response may produce
+pub fn expected_error(error: &CarbemError) -> bool {
+    matches!(
+        error.root(),
+        CarbemError::Api(_) | CarbemError::Auth(_) | CarbemError::LossyConversion(_)
+    )
+}
@@ -0,0 +1,283 @@
+//! What-if scenarios supporting cloud-migration business cases
+//!
+//! Scenarios recompute emissions with the grid carbon intensity and power
+//! usage effectiveness (PUE) of another region. The built-in
+//! [`RegionProfiles`] hold indicative annual averages; supply your own figures
+//! for a business case that will be published.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::EmissionDataset;
+use crate::error::{CarbemError, Result};
+
+/// Carbon characteristics of a cloud region
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RegionProfile {
+    /// Grid carbon intensity in gCO2e/kWh
+    pub grid_intensity_g_per_kwh: f64,
+
+    /// Power usage effectiveness of the provider's data centers
+    pub pue: f64,
+}
+
+impl RegionProfile {
+    /// Emissions per kWh of IT energy, accounting for data center overhead
+    pub fn effective_intensity(&self) -> f64 {
+        self.grid_intensity_g_per_kwh * self.pue
+    }
+}
+
+// Indicative figures: yearly grid averages of the region's country or
+// balancing authority, and the PUE published by each provider
+const AZURE_PUE: f64 = 1.18;
+const IBM_PUE: f64 = 1.4;
+const BUILTIN_PROFILES: &[(&str, f64, f64)] = &[
+    // Azure regions
+    ("eastus", 380.0, AZURE_PUE),
+    ("eastus2", 380.0, AZURE_PUE),
+    ("westus", 220.0, AZURE_PUE),
+    ("westus2", 90.0, AZURE_PUE),
+    ("centralus", 450.0, AZURE_PUE),
+    ("canadacentral", 30.0, AZURE_PUE),
+    ("northeurope", 290.0, AZURE_PUE),
+    ("westeurope", 330.0, AZURE_PUE),
+    ("francecentral", 56.0, AZURE_PUE),
+    ("germanywestcentral", 380.0, AZURE_PUE),
+    ("uksouth", 230.0, AZURE_PUE),
+    ("swedencentral", 13.0, AZURE_PUE),
+    ("norwayeast", 8.0, AZURE_PUE),
+    ("japaneast", 460.0, AZURE_PUE),
+    ("australiaeast", 650.0, AZURE_PUE),
+    // IBM Cloud locations
+    ("dallas", 400.0, IBM_PUE),
+    ("washingtondc", 380.0, IBM_PUE),
+    ("toronto", 30.0, IBM_PUE),
+    ("saopaulo", 100.0, IBM_PUE),
+    ("london", 230.0, IBM_PUE),
+    ("frankfurt", 380.0, IBM_PUE),
+    ("madrid", 150.0, IBM_PUE),
+    ("tokyo", 460.0, IBM_PUE),
+    ("osaka", 460.0, IBM_PUE),
+    ("sydney", 650.0, IBM_PUE),
+];
+
+/// Region profiles by region name
+///
+/// Names are matched ignoring case, spaces, dashes and underscores, so
+/// `"East US"`, `"east-us"` and `"eastus"` are the same region.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionProfiles {
+    profiles: BTreeMap<String, RegionProfile>,
+}
+
+impl RegionProfiles {
+    /// Profiles without any region
+    pub fn empty() -> Self {
+        Self {
+            profiles: BTreeMap::new(),
+        }
+    }
+
+    /// Add or replace the profile of `region`
+    pub fn with_region(mut self, region: &str, profile: RegionProfile) -> Self {
+        self.profiles.insert(normalize_region(region), profile);
+        self
+    }
+
+    /// Profile of `region`, if known
+    pub fn get(&self, region: &str) -> Option<&RegionProfile> {
+        self.profiles.get(&normalize_region(region))
+    }
+
+    fn require(&self, region: &str) -> Result<&RegionProfile> {
+        self.get(region).ok_or_else(|| {
+            CarbemError::Config(format!("no carbon profile known for region '{}'", region))
+        })
+    }
+}
+
+impl Default for RegionProfiles {
+    /// Built-in indicative profiles of common Azure and IBM Cloud regions
+    fn default() -> Self {
+        BUILTIN_PROFILES.iter().fold(
+            Self::empty(),
+            |profiles, (region, grid_intensity_g_per_kwh, pue)| {
+                profiles.with_region(
+                    region,
+                    RegionProfile {
+                        grid_intensity_g_per_kwh: *grid_intensity_g_per_kwh,
+                        pue: *pue,
+                    },
+                )
+            },
+        )
+    }
+}
+
+fn normalize_region(region: &str) -> String {
+    region
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Projected effect of migrating a region's workloads to another region
+#[derive(Debug, Clone)]
+pub struct MigrationScenario {
+    /// Region the workloads move away from
+    pub from_region: String,
+
+    /// Region the workloads move to
+    pub to_region: String,
+
+    /// Current emissions of the migrated workloads in kg CO2e
+    pub current_kg_co2eq: f64,
+
+    /// Projected emissions of the migrated workloads in kg CO2e
+    pub projected_kg_co2eq: f64,
+
+    /// Projected dataset, with the migrated emissions recomputed and moved to `to_region`
+    pub projected: EmissionDataset,
+}
+
+impl MigrationScenario {
+    /// Projected savings in kg CO2e (negative when emissions increase)
+    pub fn savings_kg_co2eq(&self) -> f64 {
+        self.current_kg_co2eq - self.projected_kg_co2eq
+    }
+
+    /// Projected savings in percent of the current emissions of the migrated workloads
+    pub fn savings_percent(&self) -> Option<f64> {
+        (self.current_kg_co2eq != 0.0)
+            .then(|| self.savings_kg_co2eq() / self.current_kg_co2eq * 100.0)
+    }
+}
+
+/// Simulate moving every workload of `from_region` to `to_region`, using built-in region profiles
+pub fn simulate_migration(
+    dataset: &EmissionDataset,
+    from_region: &str,
+    to_region: &str,
+) -> Result<MigrationScenario> {
+    simulate_migration_with(dataset, from_region, to_region, &RegionProfiles::default())
+}
+
+/// Simulate moving every workload of `from_region` to `to_region` with custom profiles
+///
+/// Emissions of `from_region` are scaled by the ratio of the effective carbon
+/// intensities of both regions, assuming the same IT energy consumption.
+pub fn simulate_migration_with(
+    dataset: &EmissionDataset,
+    from_region: &str,
+    to_region: &str,
+    profiles: &RegionProfiles,
+) -> Result<MigrationScenario> {
+    let source = profiles.require(from_region)?;
+    let target = profiles.require(to_region)?;
+    if source.effective_intensity() <= 0.0 {
+        return Err(CarbemError::Config(format!(
+            "carbon profile of region '{}' must have a positive intensity",
+            from_region
+        )));
+    }
+    let factor = target.effective_intensity() / source.effective_intensity();
+
+    let from_key = normalize_region(from_region);
+    let mut current_kg_co2eq = 0.0;
+    let mut projected_kg_co2eq = 0.0;
+    let projected = dataset
+        .emissions()
+        .iter()
+        .cloned()
+        .map(|mut emission| {
+            if normalize_region(&emission.region) == from_key {
+                current_kg_co2eq += emission.emissions_kg_co2eq;
+                emission.emissions_kg_co2eq *= factor;
+                emission.region = to_region.to_string();
+                projected_kg_co2eq += emission.emissions_kg_co2eq;
+            }
+            emission
+        })
+        .collect();
+
+    Ok(MigrationScenario {
+        from_region: from_region.to_string(),
+        to_region: to_region.to_string(),
+        current_kg_co2eq,
+        projected_kg_co2eq,
+        projected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(region: &str, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_simulate_migration_with_custom_profiles() {
+        let profiles = RegionProfiles::empty()
+            .with_region(
+                "west europe",
+                RegionProfile {
+                    grid_intensity_g_per_kwh: 300.0,
+                    pue: 1.2,
+                },
+            )
+            .with_region(
+                "swedencentral",
+                RegionProfile {
+                    grid_intensity_g_per_kwh: 30.0,
+                    pue: 1.2,
+                },
+            );
+        let dataset = EmissionDataset::new(vec![
+            emission("West Europe", 100.0),
+            emission("westeurope", 50.0),
+            emission("eastus", 40.0),
+        ]);
+
+        let scenario =
+            simulate_migration_with(&dataset, "westeurope", "swedencentral", &profiles).unwrap();
+
+        assert_eq!(scenario.current_kg_co2eq, 150.0);
+        assert!((scenario.projected_kg_co2eq - 15.0).abs() < 1e-9);
+        assert!((scenario.savings_percent().unwrap() - 90.0).abs() < 1e-9);
+        assert!((scenario.projected.total_kg_co2eq() - 55.0).abs() < 1e-9);
+        assert_eq!(
+            scenario.projected.emissions()[0].region,
+            "swedencentral".to_string()
+        );
+    }
+
+    #[test]
+    fn test_simulate_migration_builtin_profiles() {
+        let dataset = EmissionDataset::new(vec![emission("Dallas", 10.0)]);
+
+        let scenario = simulate_migration(&dataset, "dallas", "toronto").unwrap();
+        assert!(scenario.savings_kg_co2eq() > 0.0);
+
+        assert!(simulate_migration(&dataset, "dallas", "atlantis").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+//! Anonymization of datasets shared outside the organization
+//!
+//! Emissions identify the infrastructure they were measured on: IBM account
+//! IDs, Azure subscription IDs and, in top items reports, the names of
+//! subscriptions, resource groups and resources. An [`Anonymizer`] replaces
+//! these with pseudonyms, or strips them, before a dataset is exported, e.g.
+//! for external consultants. Locations, services and values are kept.
+//!
+//! Pseudonyms are derived from the value and a salt with SHA-256: the same
+//! value always gets the same pseudonym under one salt, so totals per account
+//! or resource can still be compared across exports, while the original
+//! value cannot be recovered without the salt.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::EmissionDataset;
+use crate::models::CarbonEmission;
+use crate::redact::REDACTED;
+
+/// Provider data fields holding account or subscription IDs
+const ID_FIELDS: &[(&str, &str)] = &[
+    ("account_id", "account"),
+    ("subscription_id", "subscription"),
+    ("subscriptionId", "subscription"),
+];
+
+/// Hex digits of the hash kept in pseudonyms
+const PSEUDONYM_LEN: usize = 16;
+
+/// How identifying values are anonymized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnonymizationMode {
+    /// Replace values with stable pseudonyms, e.g. `account-9f86d081884c7d65`
+    #[default]
+    Pseudonymize,
+
+    /// Remove provider data fields and replace other values with `[REDACTED]`
+    Strip,
+}
+
+/// Strips or pseudonymizes identifying values from emissions
+#[derive(Clone)]
+pub struct Anonymizer {
+    salt: String,
+    mode: AnonymizationMode,
+    fields: Vec<(String, String)>,
+}
+
+impl Anonymizer {
+    /// Anonymizer deriving pseudonyms with `salt`
+    ///
+    /// Keep the salt secret: anyone holding it can check guesses of the
+    /// original values against the pseudonyms.
+    pub fn new(salt: impl Into<String>) -> Self {
+        Self {
+            salt: salt.into(),
+            mode: AnonymizationMode::default(),
+            fields: ID_FIELDS
+                .iter()
+                .map(|(field, kind)| (field.to_string(), kind.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Set how values are anonymized
+    pub fn with_mode(mut self, mode: AnonymizationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Also anonymize the provider data field `field`, naming its pseudonyms `kind-…`
+    pub fn with_field(mut self, field: impl Into<String>, kind: impl Into<String>) -> Self {
+        self.fields.push((field.into(), kind.into()));
+        self
+    }
+
+    /// Stable pseudonym of `value`, e.g. `resource-2c26b46b68ffc68f`
+    pub fn pseudonym(&self, kind: &str, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(value.as_bytes());
+        let hash: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("{}-{}", kind, &hash[..PSEUDONYM_LEN])
+    }
+
+    /// Anonymize the identifying values of `emission` in place
+    pub fn anonymize(&self, emission: &mut CarbonEmission) {
+        let Some(Value::Object(data)) = emission
+            .metadata
+            .as_mut()
+            .and_then(|metadata| metadata.provider_data.as_mut())
+        else {
+            return;
+        };
+
+        // Azure top items reports name the item in the region, and fall back
+        // to the subscription ID when there is no item
+        if emission.provider == "azure" {
+            let kind = match data.get("categoryType").and_then(Value::as_str) {
+                Some("Resource") => Some("resource"),
+                Some("ResourceGroup") => Some("resource-group"),
+                Some("Subscription") => Some("subscription"),
+                Some(_) => None,
+                None if data.contains_key("itemName") => None,
+                None => Some("subscription"),
+            };
+            if let Some(kind) = kind {
+                emission.region = self.replace(kind, &emission.region);
+                self.anonymize_field(data, "itemName", kind);
+            }
+        }
+        if data.get("group_by_type").and_then(Value::as_str) == Some("account") {
+            self.anonymize_field(data, "group_by_value", "account");
+        }
+        for (field, kind) in &self.fields {
+            self.anonymize_field(data, field, kind);
+        }
+    }
+
+    fn replace(&self, kind: &str, value: &str) -> String {
+        match self.mode {
+            AnonymizationMode::Pseudonymize => self.pseudonym(kind, value),
+            AnonymizationMode::Strip => REDACTED.to_string(),
+        }
+    }
+
+    fn anonymize_field(&self, data: &mut serde_json::Map<String, Value>, field: &str, kind: &str) {
+        match self.mode {
+            AnonymizationMode::Pseudonymize => {
+                if let Some(Value::String(value)) = data.get_mut(field) {
+                    *value = self.pseudonym(kind, value);
+                }
+            }
+            AnonymizationMode::Strip => {
+                data.remove(field);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Anonymizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Anonymizer")
+            .field("salt", &REDACTED)
+            .field("mode", &self.mode)
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+impl EmissionDataset {
+    /// Copy of the dataset with identifying values anonymized by `anonymizer`
+    pub fn anonymize(&self, anonymizer: &Anonymizer) -> Self {
+        let mut dataset = self.clone();
+        for emission in &mut dataset.emissions {
+            anonymizer.anonymize(emission);
+        }
+        dataset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EmissionMetadata, TimePeriod};
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    fn emission(provider: &str, region: &str, provider_data: Value) -> CarbonEmission {
+        CarbonEmission {
+            provider: provider.to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.5,
+            time_period: TimePeriod {
+                start: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+                end: Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap(),
+            },
+            metadata: Some(EmissionMetadata {
+                energy_kwh: None,
+                grid_carbon_intensity: None,
+                renewable_percentage: None,
+                date_alignment: None,
+                provider_data: Some(provider_data),
+                tags: Default::default(),
+                estimated: None,
+            }),
+        }
+    }
+
+    fn provider_data(emission: &CarbonEmission) -> &Value {
+        emission
+            .metadata
+            .as_ref()
+            .unwrap()
+            .provider_data
+            .as_ref()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_anonymize_identifiers() {
+        let dataset = EmissionDataset::new(vec![
+            emission(
+                "ibm",
+                "Dallas",
+                json!({"account_id": "acct-1", "group_by_type": "account", "group_by_value": "acct-1"}),
+            ),
+            emission(
+                "azure",
+                "vm-payments-01",
+                json!({"categoryType": "Resource", "itemName": "vm-payments-01"}),
+            ),
+            emission(
+                "azure",
+                "westeurope",
+                json!({"categoryType": "Location", "itemName": "westeurope"}),
+            ),
+            emission(
+                "azure",
+                "00000000-sub",
+                json!({"dataType": "OverallSummaryData"}),
+            ),
+        ]);
+
+        let anonymizer = Anonymizer::new("s3cret");
+        let anonymized = dataset.anonymize(&anonymizer);
+        let emissions = anonymized.emissions();
+        let account = anonymizer.pseudonym("account", "acct-1");
+        assert!(account.starts_with("account-") && account.len() == 24);
+        assert_eq!(provider_data(&emissions[0])["account_id"], account);
+        assert_eq!(provider_data(&emissions[0])["group_by_value"], account);
+        assert_eq!(emissions[0].region, "Dallas");
+        assert_eq!(
+            emissions[1].region,
+            anonymizer.pseudonym("resource", "vm-payments-01")
+        );
+        assert_eq!(
+            provider_data(&emissions[1])["itemName"],
+            emissions[1].region
+        );
+        assert_eq!(emissions[2].region, "westeurope");
+        assert_eq!(
+            emissions[3].region,
+            anonymizer.pseudonym("subscription", "00000000-sub")
+        );
+        assert_eq!(anonymized.total_kg_co2eq(), dataset.total_kg_co2eq());
+
+        // Pseudonyms depend on the salt
+        assert_ne!(
+            Anonymizer::new("other").pseudonym("account", "acct-1"),
+            account
+        );
+
+        let stripped = dataset.anonymize(&anonymizer.with_mode(AnonymizationMode::Strip));
+        assert!(
+            provider_data(&stripped.emissions()[0])
+                .get("account_id")
+                .is_none()
+        );
+        assert_eq!(stripped.emissions()[1].region, REDACTED);
+    }
+}
@@ -0,0 +1,292 @@
+//! Snapshots of emission datasets and the differences between them
+//!
+//! A data point is the emissions of a provider, region and service over a
+//! period. [`EmissionDataset::diff`] lists the data points added, removed or
+//! changed between two datasets, e.g. two [`DatasetSnapshot`]s of the same
+//! query taken a month apart to audit provider restatements.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{EmissionDataset, stable_sum};
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, sort_emissions};
+use crate::runtime::fs;
+use crate::sinks::jsonl::io_error;
+
+// Identifies a data point: provider, region, service and period
+pub(crate) type DataPointKey = (String, String, Option<String>, DateTime<Utc>, DateTime<Utc>);
+
+pub(crate) fn data_point_key(emission: &CarbonEmission) -> DataPointKey {
+    (
+        emission.provider.clone(),
+        emission.region.clone(),
+        emission.service.clone(),
+        emission.time_period.start,
+        emission.time_period.end,
+    )
+}
+
+/// A dataset frozen at a point in time, e.g. saved for a later audit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetSnapshot {
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+
+    /// [`content_hash`](EmissionDataset::content_hash) of the emissions
+    pub content_hash: String,
+
+    /// Emissions, in canonical order
+    pub emissions: Vec<CarbonEmission>,
+}
+
+impl DatasetSnapshot {
+    /// The emissions of the snapshot as a dataset
+    pub fn dataset(&self) -> EmissionDataset {
+        EmissionDataset::new(self.emissions.clone())
+    }
+
+    /// Save the snapshot to `path` as JSON
+    pub async fn write_to(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        fs::write(path, json).await.map_err(io_error)
+    }
+
+    /// Load a snapshot saved with [`write_to`](Self::write_to)
+    ///
+    /// Fails if its emissions no longer match its content hash.
+    pub async fn read_from(path: &Path) -> Result<Self> {
+        let json = fs::read(path).await.map_err(io_error)?;
+        let snapshot: Self = serde_json::from_slice(&json)?;
+        if snapshot.dataset().content_hash() != snapshot.content_hash {
+            return Err(CarbemError::Other(format!(
+                "Snapshot {} does not match its content hash",
+                path.display()
+            )));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// A data point that differs between two datasets
+// Changes are short-lived: keep both emissions inline for matching
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum DataPointChange {
+    /// A data point only in the newer dataset
+    Added {
+        /// The data point
+        current: CarbonEmission,
+    },
+
+    /// A data point only in the older dataset
+    Removed {
+        /// The data point
+        previous: CarbonEmission,
+    },
+
+    /// A data point whose emissions changed
+    Changed {
+        /// The data point in the older dataset
+        previous: CarbonEmission,
+
+        /// The data point in the newer dataset
+        current: CarbonEmission,
+    },
+}
+
+/// Data points added, removed or changed between two datasets
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatasetDiff {
+    /// Changes, in the canonical order of their data points
+    pub changes: Vec<DataPointChange>,
+}
+
+impl DatasetDiff {
+    /// Whether both datasets hold the same data points
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Change of the total emissions in kg CO2e, from the older dataset to the newer
+    pub fn net_change_kg_co2eq(&self) -> f64 {
+        stable_sum(self.changes.iter().map(|change| match change {
+            DataPointChange::Added { current } => current.emissions_kg_co2eq,
+            DataPointChange::Removed { previous } => -previous.emissions_kg_co2eq,
+            DataPointChange::Changed { previous, current } => {
+                current.emissions_kg_co2eq - previous.emissions_kg_co2eq
+            }
+        }))
+    }
+}
+
+impl fmt::Display for DatasetDiff {
+    /// One line per change: `+` added, `-` removed, `~` changed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let point = |emission: &CarbonEmission| {
+            format!(
+                "{} {} {} {}..{}",
+                emission.provider,
+                emission.region,
+                emission.service.as_deref().unwrap_or("-"),
+                emission.time_period.start.format("%Y-%m-%d"),
+                emission.time_period.end.format("%Y-%m-%d"),
+            )
+        };
+        for change in &self.changes {
+            match change {
+                DataPointChange::Added { current } => {
+                    writeln!(f, "+ {}: {} kg", point(current), current.emissions_kg_co2eq)?
+                }
+                DataPointChange::Removed { previous } => writeln!(
+                    f,
+                    "- {}: {} kg",
+                    point(previous),
+                    previous.emissions_kg_co2eq
+                )?,
+                DataPointChange::Changed { previous, current } => writeln!(
+                    f,
+                    "~ {}: {} -> {} kg",
+                    point(current),
+                    previous.emissions_kg_co2eq,
+                    current.emissions_kg_co2eq
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EmissionDataset {
+    /// The dataset as of now, in canonical order
+    pub fn snapshot(&self) -> DatasetSnapshot {
+        let mut emissions = self.emissions.clone();
+        sort_emissions(&mut emissions);
+        DatasetSnapshot {
+            taken_at: Utc::now(),
+            content_hash: self.content_hash(),
+            emissions,
+        }
+    }
+
+    /// Data points added, removed or changed from this dataset to `other`
+    ///
+    /// Emissions of the same data point are summed. A data point changed when
+    /// its emissions did; metadata is not compared.
+    pub fn diff(&self, other: &EmissionDataset) -> DatasetDiff {
+        let previous = data_points(&self.emissions);
+        let mut current = data_points(&other.emissions);
+        let mut changes = Vec::new();
+        for (key, previous) in previous {
+            match current.remove(&key) {
+                None => changes.push((key, DataPointChange::Removed { previous })),
+                Some(current) if current.emissions_kg_co2eq != previous.emissions_kg_co2eq => {
+                    changes.push((key, DataPointChange::Changed { previous, current }));
+                }
+                Some(_) => {}
+            }
+        }
+        changes.extend(
+            current
+                .into_iter()
+                .map(|(key, current)| (key, DataPointChange::Added { current })),
+        );
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        DatasetDiff {
+            changes: changes.into_iter().map(|(_, change)| change).collect(),
+        }
+    }
+}
+
+// Emissions per data point, those sharing one summed without metadata
+fn data_points(emissions: &[CarbonEmission]) -> BTreeMap<DataPointKey, CarbonEmission> {
+    let mut grouped: BTreeMap<DataPointKey, Vec<&CarbonEmission>> = BTreeMap::new();
+    for emission in emissions {
+        grouped
+            .entry(data_point_key(emission))
+            .or_default()
+            .push(emission);
+    }
+    grouped
+        .into_iter()
+        .map(|(key, emissions)| {
+            let mut point = emissions[0].clone();
+            if emissions.len() > 1 {
+                point.emissions_kg_co2eq =
+                    stable_sum(emissions.iter().map(|e| e.emissions_kg_co2eq));
+                point.metadata = None;
+            }
+            (key, point)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::TimeZone;
+
+    fn emission(region: &str, month: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: Some("Storage".to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: Utc.with_ymd_and_hms(2024, month + 1, 1, 0, 0, 0).unwrap(),
+            },
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_diff() {
+        let before = EmissionDataset::new(vec![
+            emission("westeurope", 1, 10.0),
+            emission("westeurope", 2, 20.0),
+            emission("northeurope", 1, 5.0),
+        ]);
+        let after = EmissionDataset::new(vec![
+            emission("westeurope", 2, 12.0),
+            emission("westeurope", 2, 10.0),
+            emission("westeurope", 1, 10.0),
+            emission("westeurope", 3, 7.0),
+        ]);
+
+        let path =
+            std::env::temp_dir().join(format!("carbem-snapshot-{}.json", std::process::id()));
+        before.snapshot().write_to(&path).await.unwrap();
+        let saved = DatasetSnapshot::read_from(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.content_hash, before.content_hash());
+
+        let diff = saved.dataset().diff(&after);
+        assert_eq!(diff.changes.len(), 3);
+        assert!(matches!(
+            &diff.changes[0],
+            DataPointChange::Removed { previous } if previous.region == "northeurope"
+        ));
+        assert!(matches!(
+            &diff.changes[1],
+            DataPointChange::Changed { previous, current }
+                if previous.emissions_kg_co2eq == 20.0 && current.emissions_kg_co2eq == 22.0
+        ));
+        assert!(matches!(&diff.changes[2], DataPointChange::Added { .. }));
+        assert_eq!(diff.net_change_kg_co2eq(), 4.0);
+        assert_eq!(
+            diff.to_string().lines().nth(1),
+            Some("~ azure westeurope Storage 2024-02-01..2024-03-01: 20 -> 22 kg")
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+}
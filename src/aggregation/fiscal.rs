@@ -0,0 +1,146 @@
+//! Fiscal calendars for reporting cycles that don't follow calendar years
+
+use std::fmt;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CarbemError, Result};
+
+/// A fiscal calendar defined by the month its fiscal year starts in
+///
+/// Fiscal years are named after the calendar year in which they end: with a
+/// fiscal year starting in April, April 2024 – March 2025 is `FY2025`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FiscalCalendar {
+    start_month: u32,
+}
+
+impl FiscalCalendar {
+    /// Create a fiscal calendar whose year starts in `start_month` (1 = January)
+    pub fn new(start_month: u32) -> Result<Self> {
+        if !(1..=12).contains(&start_month) {
+            return Err(CarbemError::Config(format!(
+                "fiscal year start month must be between 1 and 12, got {}",
+                start_month
+            )));
+        }
+        Ok(Self { start_month })
+    }
+
+    /// The calendar year, starting in January
+    pub fn calendar_year() -> Self {
+        Self { start_month: 1 }
+    }
+
+    /// Month the fiscal year starts in (1 = January)
+    pub fn start_month(&self) -> u32 {
+        self.start_month
+    }
+
+    /// Fiscal year containing `date`
+    pub fn fiscal_year(&self, date: NaiveDate) -> i32 {
+        if self.start_month == 1 || date.month() < self.start_month {
+            date.year()
+        } else {
+            date.year() + 1
+        }
+    }
+
+    /// Fiscal quarter containing `date`
+    pub fn fiscal_quarter(&self, date: NaiveDate) -> FiscalQuarter {
+        let months_into_year = (date.month() + 12 - self.start_month) % 12;
+        FiscalQuarter {
+            year: self.fiscal_year(date),
+            quarter: months_into_year / 3 + 1,
+        }
+    }
+
+    /// First day of `fiscal_year`
+    pub fn year_start(&self, fiscal_year: i32) -> NaiveDate {
+        let calendar_year = if self.start_month == 1 {
+            fiscal_year
+        } else {
+            fiscal_year - 1
+        };
+        NaiveDate::from_ymd_opt(calendar_year, self.start_month, 1)
+            .expect("first day of an existing month is valid")
+    }
+
+    /// First day of the fiscal year containing `instant`
+    pub fn year_start_of(&self, instant: DateTime<Utc>) -> NaiveDate {
+        self.year_start(self.fiscal_year(instant.date_naive()))
+    }
+}
+
+impl Default for FiscalCalendar {
+    fn default() -> Self {
+        Self::calendar_year()
+    }
+}
+
+/// A quarter of a fiscal year, ordered chronologically
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FiscalQuarter {
+    /// Fiscal year, named after the calendar year in which it ends
+    pub year: i32,
+
+    /// Quarter within the fiscal year, from 1 to 4
+    pub quarter: u32,
+}
+
+impl fmt::Display for FiscalQuarter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FY{}-Q{}", self.year, self.quarter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_calendar_year() {
+        let calendar = FiscalCalendar::default();
+        assert_eq!(calendar.fiscal_year(date(2024, 12, 31)), 2024);
+        assert_eq!(
+            calendar.fiscal_quarter(date(2024, 5, 10)),
+            FiscalQuarter {
+                year: 2024,
+                quarter: 2
+            }
+        );
+        assert_eq!(calendar.year_start(2024), date(2024, 1, 1));
+    }
+
+    #[test]
+    fn test_april_fiscal_year() {
+        let calendar = FiscalCalendar::new(4).unwrap();
+
+        assert_eq!(calendar.fiscal_year(date(2024, 3, 31)), 2024);
+        assert_eq!(calendar.fiscal_year(date(2024, 4, 1)), 2025);
+        assert_eq!(
+            calendar.fiscal_quarter(date(2024, 4, 1)).to_string(),
+            "FY2025-Q1"
+        );
+        assert_eq!(
+            calendar.fiscal_quarter(date(2024, 12, 1)).to_string(),
+            "FY2025-Q3"
+        );
+        assert_eq!(
+            calendar.fiscal_quarter(date(2025, 2, 28)).to_string(),
+            "FY2025-Q4"
+        );
+        assert_eq!(calendar.year_start(2025), date(2024, 4, 1));
+    }
+
+    #[test]
+    fn test_invalid_start_month() {
+        assert!(FiscalCalendar::new(0).is_err());
+        assert!(FiscalCalendar::new(13).is_err());
+    }
+}
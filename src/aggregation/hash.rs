@@ -0,0 +1,132 @@
+//! Canonical content hash of an emission dataset
+//!
+//! The hash covers every field of every emission, metadata included, and does
+//! not depend on the order of emissions or of keys in provider data. Two
+//! datasets with the same hash hold the same data. Lineage is left out: the
+//! same data retrieved again has the same hash.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::EmissionDataset;
+use crate::models::{FlatEmissionRecord, sort_emissions};
+
+impl EmissionDataset {
+    /// SHA-256 of the canonical form of the dataset, as lowercase hex
+    ///
+    /// The canonical form is one JSON-encoded [`FlatEmissionRecord`] per line,
+    /// in the order of [`sort_emissions`], with provider data keys sorted and
+    /// without the lineage columns.
+    pub fn content_hash(&self) -> String {
+        let mut emissions = self.emissions.clone();
+        sort_emissions(&mut emissions);
+
+        let mut hasher = Sha256::new();
+        for emission in &emissions {
+            let mut record = FlatEmissionRecord::from(emission);
+            record.provider_data = emission
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.provider_data.as_ref())
+                .map(canonical_json);
+            record.lineage_endpoint = None;
+            record.lineage_request_hash = None;
+            record.lineage_retrieved_at = None;
+            record.lineage_carbem_version = None;
+            let line = serde_json::to_string(&record).expect("flat records always serialize");
+            hasher.update(line.as_bytes());
+            hasher.update(b"\n");
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+// JSON with object keys sorted, whatever the map implementation of serde_json
+fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<(&String, &Value)> = object.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let fields: Vec<String> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}:{}", Value::from(key.as_str()), canonical_json(value))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, EmissionMetadata, TimePeriod};
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    fn emission(region: &str, kg: f64, provider_data: Value) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: Some(EmissionMetadata {
+                energy_kwh: None,
+                grid_carbon_intensity: None,
+                renewable_percentage: None,
+                date_alignment: None,
+                provider_data: Some(provider_data),
+                tags: Default::default(),
+                estimated: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_content_hash_is_order_independent() {
+        let a = emission("westeurope", 1.0, json!({"a": 1, "b": [{"y": 2, "x": 1}]}));
+        let b = emission("eastus", 2.0, json!({}));
+
+        let hash = EmissionDataset::new(vec![a.clone(), b.clone()]).content_hash();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(
+            hash,
+            EmissionDataset::new(vec![b.clone(), a]).content_hash()
+        );
+
+        let changed = emission(
+            "westeurope",
+            1.0001,
+            json!({"a": 1, "b": [{"y": 2, "x": 1}]}),
+        );
+        assert_ne!(hash, EmissionDataset::new(vec![changed, b]).content_hash());
+    }
+
+    #[test]
+    fn test_canonical_json_sorts_keys() {
+        let mut object = serde_json::Map::new();
+        object.insert("z".to_string(), json!(1));
+        object.insert("a".to_string(), json!({"d": null, "c": "text"}));
+        assert_eq!(
+            canonical_json(&Value::Object(object)),
+            r#"{"a":{"c":"text","d":null},"z":1}"#
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Intensity metrics: emissions per unit of a business metric
+//!
+//! A [`BusinessMetric`] is a monthly series supplied by the user (requests
+//! served, revenue, active users). Joining it with an [`EmissionDataset`]
+//! gives emissions in grams CO2e per unit of the metric over time.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use super::{EmissionDataset, stable_sum};
+
+/// A monthly series of a business metric
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusinessMetric {
+    /// Name of the metric, e.g. "requests"
+    pub name: String,
+
+    /// Unit of the metric, e.g. "request" or "EUR"
+    pub unit: String,
+
+    /// Metric values keyed by the first day of their month
+    pub values: BTreeMap<NaiveDate, f64>,
+}
+
+impl BusinessMetric {
+    /// Create an empty metric series
+    pub fn new(name: impl Into<String>, unit: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            unit: unit.into(),
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Add the value for the month containing `date`, summing with any existing value
+    pub fn with_value(mut self, date: NaiveDate, value: f64) -> Self {
+        let month = NaiveDate::from_ymd_opt(date.year(), date.month(), 1)
+            .expect("first day of an existing month is valid");
+        *self.values.entry(month).or_insert(0.0) += value;
+        self
+    }
+}
+
+/// Emissions intensity for one month
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IntensityPoint {
+    /// First day of the month
+    pub month: NaiveDate,
+
+    /// Emissions of the month in kg CO2e
+    pub emissions_kg_co2eq: f64,
+
+    /// Metric value of the month
+    pub metric_value: f64,
+
+    /// Grams CO2e per unit of the metric, `None` when the metric value is zero
+    pub g_co2eq_per_unit: Option<f64>,
+}
+
+impl EmissionDataset {
+    /// Monthly emissions intensity for every month present in both the dataset and `metric`
+    pub fn intensity(&self, metric: &BusinessMetric) -> Vec<IntensityPoint> {
+        let emissions = self.group_by_month();
+        metric
+            .values
+            .iter()
+            .filter_map(|(month, value)| {
+                let kg = emissions.get(month)?;
+                Some(IntensityPoint {
+                    month: *month,
+                    emissions_kg_co2eq: *kg,
+                    metric_value: *value,
+                    g_co2eq_per_unit: grams_per_unit(*kg, *value),
+                })
+            })
+            .collect()
+    }
+
+    /// Intensity over all months present in both the dataset and `metric`
+    pub fn overall_intensity(&self, metric: &BusinessMetric) -> Option<f64> {
+        let points = self.intensity(metric);
+        let kg = stable_sum(points.iter().map(|p| p.emissions_kg_co2eq));
+        let value = stable_sum(points.iter().map(|p| p.metric_value));
+        grams_per_unit(kg, value)
+    }
+}
+
+fn grams_per_unit(kg: f64, value: f64) -> Option<f64> {
+    (value != 0.0).then(|| kg * 1000.0 / value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(month: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(28),
+            },
+            metadata: None,
+        }
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_intensity_joins_months() {
+        let dataset =
+            EmissionDataset::new(vec![emission(1, 2.0), emission(2, 3.0), emission(3, 1.0)]);
+        let requests = BusinessMetric::new("requests", "request")
+            .with_value(date(1, 10), 500.0)
+            .with_value(date(1, 20), 500.0)
+            .with_value(date(2, 1), 0.0)
+            .with_value(date(4, 1), 1000.0);
+
+        let points = dataset.intensity(&requests);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].month, date(1, 1));
+        assert_eq!(points[0].metric_value, 1000.0);
+        assert_eq!(points[0].g_co2eq_per_unit, Some(2.0));
+        assert_eq!(points[1].g_co2eq_per_unit, None);
+
+        assert_eq!(dataset.overall_intensity(&requests), Some(5.0));
+    }
+}
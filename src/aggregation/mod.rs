@@ -0,0 +1,324 @@
+//! Aggregation of carbon emissions for reporting
+//!
+//! [`EmissionDataset`] wraps the emissions returned by one or more queries and
+//! groups them by period or dimension. Emissions are attributed to the period
+//! containing the start of their `time_period`. Totals are computed with
+//! compensated summation, see [`StableSum`] for the accuracy guarantee.
+
+pub mod anonymize;
+mod diff;
+pub mod fiscal;
+mod hash;
+pub mod intensity;
+mod quality;
+mod stats;
+mod sum;
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{CarbonEmission, EmissionResult};
+use crate::precision::PrecisionPolicy;
+
+pub use anonymize::{AnonymizationMode, Anonymizer};
+pub use diff::{DataPointChange, DatasetDiff, DatasetSnapshot};
+pub use fiscal::{FiscalCalendar, FiscalQuarter};
+pub use intensity::{BusinessMetric, IntensityPoint};
+pub use quality::DataQuality;
+pub use sum::StableSum;
+
+pub(crate) use diff::{DataPointKey, data_point_key};
+pub(crate) use sum::stable_sum;
+
+/// Key used by emissions without a service when grouping by service
+pub const UNSPECIFIED_SERVICE: &str = "unspecified";
+
+/// Dimension of a carbon emission to group by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dimension {
+    Provider,
+    Region,
+    Service,
+    ServiceCategory,
+}
+
+impl Dimension {
+    /// Value of this dimension for `emission`
+    pub fn key(&self, emission: &CarbonEmission) -> String {
+        match self {
+            Dimension::Provider => emission.provider.clone(),
+            Dimension::Region => emission.region.clone(),
+            Dimension::Service => emission
+                .service
+                .clone()
+                .unwrap_or_else(|| UNSPECIFIED_SERVICE.to_string()),
+            Dimension::ServiceCategory => emission
+                .service_category
+                .map_or_else(|| UNSPECIFIED_SERVICE.to_string(), |c| c.to_string()),
+        }
+    }
+}
+
+/// A collection of carbon emissions to aggregate
+///
+/// Totals are rounded according to the dataset's [`PrecisionPolicy`], if any.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmissionDataset {
+    emissions: Vec<CarbonEmission>,
+    #[serde(skip)]
+    precision: Option<PrecisionPolicy>,
+}
+
+impl EmissionDataset {
+    /// Create a dataset from emissions
+    pub fn new(emissions: Vec<CarbonEmission>) -> Self {
+        Self {
+            emissions,
+            precision: None,
+        }
+    }
+
+    /// Round totals according to `policy`, including those of filtered datasets
+    pub fn with_precision(mut self, policy: PrecisionPolicy) -> Self {
+        self.precision = Some(policy);
+        self
+    }
+
+    /// Precision policy of the totals, `None` when they are not rounded
+    pub fn precision(&self) -> Option<&PrecisionPolicy> {
+        self.precision.as_ref()
+    }
+
+    /// Emissions in the dataset
+    pub fn emissions(&self) -> &[CarbonEmission] {
+        &self.emissions
+    }
+
+    /// Consume the dataset, returning its emissions
+    pub fn into_emissions(self) -> Vec<CarbonEmission> {
+        self.emissions
+    }
+
+    /// Number of emissions in the dataset
+    pub fn len(&self) -> usize {
+        self.emissions.len()
+    }
+
+    /// Whether the dataset contains no emissions
+    pub fn is_empty(&self) -> bool {
+        self.emissions.is_empty()
+    }
+
+    /// Add emissions to the dataset, e.g. from another provider
+    pub fn extend(&mut self, emissions: impl IntoIterator<Item = CarbonEmission>) {
+        self.emissions.extend(emissions);
+    }
+
+    /// Emissions matching `predicate`, as a new dataset
+    pub fn filter(&self, predicate: impl Fn(&CarbonEmission) -> bool) -> Self {
+        Self {
+            emissions: self
+                .emissions
+                .iter()
+                .filter(|e| predicate(e))
+                .cloned()
+                .collect(),
+            precision: self.precision,
+        }
+    }
+
+    /// Emissions whose `dimension` equals `value`, ignoring case
+    pub fn filter_dimension(&self, dimension: Dimension, value: &str) -> Self {
+        self.filter(|e| dimension.key(e).eq_ignore_ascii_case(value))
+    }
+
+    /// Total emissions in kg CO2e
+    pub fn total_kg_co2eq(&self) -> f64 {
+        self.sum(self.emissions.iter().map(|e| e.emissions_kg_co2eq))
+    }
+
+    /// Total emissions in kg CO2e per key returned by `key`
+    pub fn group_by<K: Ord>(&self, key: impl Fn(&CarbonEmission) -> K) -> BTreeMap<K, f64> {
+        let mut groups: BTreeMap<K, Vec<f64>> = BTreeMap::new();
+        for emission in &self.emissions {
+            groups
+                .entry(key(emission))
+                .or_default()
+                .push(emission.emissions_kg_co2eq);
+        }
+        groups
+            .into_iter()
+            .map(|(key, values)| (key, self.sum(values)))
+            .collect()
+    }
+
+    /// Total emissions in kg CO2e per value of `dimension`
+    pub fn group_by_dimension(&self, dimension: Dimension) -> BTreeMap<String, f64> {
+        self.group_by(|e| dimension.key(e))
+    }
+
+    /// Total emissions in kg CO2e per month, keyed by the first day of the month
+    pub fn group_by_month(&self) -> BTreeMap<NaiveDate, f64> {
+        self.group_by(|e| {
+            let start = e.time_period.start;
+            NaiveDate::from_ymd_opt(start.year(), start.month(), 1)
+                .expect("first day of an existing month is valid")
+        })
+    }
+
+    /// Total emissions in kg CO2e per fiscal year
+    pub fn group_by_fiscal_year(&self, calendar: &FiscalCalendar) -> BTreeMap<i32, f64> {
+        self.group_by(|e| calendar.fiscal_year(e.time_period.start.date_naive()))
+    }
+
+    /// Total emissions in kg CO2e per fiscal quarter
+    pub fn group_by_fiscal_quarter(
+        &self,
+        calendar: &FiscalCalendar,
+    ) -> BTreeMap<FiscalQuarter, f64> {
+        self.group_by(|e| calendar.fiscal_quarter(e.time_period.start.date_naive()))
+    }
+
+    /// Fiscal year-to-date emissions in kg CO2e as of `as_of`
+    ///
+    /// Includes emissions starting between the beginning of the fiscal year
+    /// containing `as_of` and `as_of` itself.
+    pub fn fiscal_year_to_date(&self, calendar: &FiscalCalendar, as_of: DateTime<Utc>) -> f64 {
+        let year_start = calendar.year_start_of(as_of);
+        let emissions = self
+            .emissions
+            .iter()
+            .filter(|e| {
+                e.time_period.start.date_naive() >= year_start && e.time_period.start <= as_of
+            })
+            .map(|e| e.emissions_kg_co2eq);
+        self.sum(emissions)
+    }
+
+    // Sum of emission values, rounded by the precision policy
+    fn sum(&self, values: impl IntoIterator<Item = f64>) -> f64 {
+        match &self.precision {
+            Some(policy) => policy.sum(values),
+            None => stable_sum(values),
+        }
+    }
+}
+
+impl From<Vec<CarbonEmission>> for EmissionDataset {
+    fn from(emissions: Vec<CarbonEmission>) -> Self {
+        Self::new(emissions)
+    }
+}
+
+impl From<EmissionResult> for EmissionDataset {
+    fn from(result: EmissionResult) -> Self {
+        Self::new(result.emissions)
+    }
+}
+
+impl FromIterator<CarbonEmission> for EmissionDataset {
+    fn from_iter<I: IntoIterator<Item = CarbonEmission>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::TimeZone;
+
+    fn emission(year: i32, month: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(28),
+            },
+            metadata: None,
+        }
+    }
+
+    fn dataset() -> EmissionDataset {
+        vec![
+            emission(2024, 2, 1.0),
+            emission(2024, 4, 2.0),
+            emission(2024, 6, 4.0),
+            emission(2024, 7, 8.0),
+            emission(2025, 1, 16.0),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn test_total_and_group_by() {
+        let dataset = dataset();
+        assert_eq!(dataset.len(), 5);
+        assert_eq!(dataset.total_kg_co2eq(), 31.0);
+
+        let by_region = dataset.group_by(|e| e.region.clone());
+        assert_eq!(by_region["westeurope"], 31.0);
+    }
+
+    #[test]
+    fn test_precision_policy() {
+        let dataset: EmissionDataset = vec![
+            emission(2024, 1, 0.004),
+            emission(2024, 1, 0.004),
+            emission(2024, 2, 0.007),
+        ]
+        .into();
+        assert!((dataset.total_kg_co2eq() - 0.015).abs() < 1e-12);
+
+        let rounded = dataset.with_precision(PrecisionPolicy::new(2));
+        assert_eq!(rounded.total_kg_co2eq(), 0.02);
+        assert_eq!(rounded.group_by(|e| e.region.clone())["westeurope"], 0.02);
+
+        let january = rounded.filter(|e| e.time_period.start.month() == 1);
+        assert_eq!(january.precision(), Some(&PrecisionPolicy::new(2)));
+        assert_eq!(january.total_kg_co2eq(), 0.01);
+    }
+
+    #[test]
+    fn test_group_by_fiscal_quarter() {
+        let calendar = FiscalCalendar::new(4).unwrap();
+        let quarters = dataset().group_by_fiscal_quarter(&calendar);
+
+        let totals: Vec<(String, f64)> = quarters
+            .iter()
+            .map(|(quarter, kg)| (quarter.to_string(), *kg))
+            .collect();
+        assert_eq!(
+            totals,
+            vec![
+                ("FY2024-Q4".to_string(), 1.0),
+                ("FY2025-Q1".to_string(), 6.0),
+                ("FY2025-Q2".to_string(), 8.0),
+                ("FY2025-Q4".to_string(), 16.0),
+            ]
+        );
+
+        let years = dataset().group_by_fiscal_year(&calendar);
+        assert_eq!(years[&2025], 30.0);
+    }
+
+    #[test]
+    fn test_fiscal_year_to_date() {
+        let calendar = FiscalCalendar::new(4).unwrap();
+        let as_of = Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap();
+        assert_eq!(dataset().fiscal_year_to_date(&calendar, as_of), 6.0);
+        assert_eq!(
+            dataset().fiscal_year_to_date(&FiscalCalendar::calendar_year(), as_of),
+            7.0
+        );
+    }
+}
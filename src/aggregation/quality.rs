@@ -0,0 +1,232 @@
+//! Data quality diagnostics of an emission dataset
+//!
+//! [`EmissionDataset::quality`] checks how complete a dataset is for the
+//! period that was requested: months without any emission, emissions that
+//! are estimates rather than provider-reported values, and emissions whose
+//! region or service is unknown. The result, a [`DataQuality`], tells report
+//! consumers how much to trust the totals.
+
+use std::fmt;
+
+use chrono::{Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use super::{EmissionDataset, UNSPECIFIED_SERVICE, stable_sum};
+use crate::error::Result;
+use crate::models::{CarbonEmission, DateAlignment, QueryTimezone, TimePeriod};
+
+// Weights of the score components, summing to 100
+const COVERAGE_WEIGHT: f64 = 40.0;
+const REPORTED_WEIGHT: f64 = 20.0;
+const KNOWN_REGION_WEIGHT: f64 = 20.0;
+const KNOWN_SERVICE_WEIGHT: f64 = 20.0;
+
+/// Completeness of a dataset for a requested period
+///
+/// Shares are fractions of the total emissions, between 0 and 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataQuality {
+    /// Months of the requested period, keyed by their first day
+    pub requested_months: Vec<NaiveDate>,
+
+    /// Requested months without any emission
+    pub missing_months: Vec<NaiveDate>,
+
+    /// Share of emissions flagged as [`estimated`](crate::EmissionMetadata::estimated)
+    pub estimated_share: f64,
+
+    /// Share of emissions with no or an `unknown` region
+    pub unknown_region_share: f64,
+
+    /// Share of emissions with no or an `unknown` service
+    pub unknown_service_share: f64,
+
+    /// Overall score from 0 (unusable) to 100 (complete)
+    ///
+    /// Weighs the covered share of the requested months (40), and the shares
+    /// of provider-reported values (20), known regions (20) and known
+    /// services (20). An empty dataset scores 0.
+    pub score: f64,
+}
+
+impl DataQuality {
+    /// Share of the requested months with at least one emission
+    pub fn period_coverage(&self) -> f64 {
+        if self.requested_months.is_empty() {
+            return 0.0;
+        }
+        1.0 - self.missing_months.len() as f64 / self.requested_months.len() as f64
+    }
+
+    /// Findings lowering the score, in plain words
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if !self.missing_months.is_empty() {
+            let months: Vec<String> = self
+                .missing_months
+                .iter()
+                .map(|month| month.format("%Y-%m").to_string())
+                .collect();
+            issues.push(format!("no emissions for {}", months.join(", ")));
+        }
+        for (share, what) in [
+            (self.estimated_share, "estimated"),
+            (self.unknown_region_share, "in an unknown region"),
+            (self.unknown_service_share, "of an unknown service"),
+        ] {
+            if share > 0.0 {
+                issues.push(format!("{:.1}% of emissions are {}", share * 100.0, what));
+            }
+        }
+        issues
+    }
+}
+
+impl fmt::Display for DataQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Data quality {:.0}/100: {} of {} months covered",
+            self.score,
+            self.requested_months.len() - self.missing_months.len(),
+            self.requested_months.len()
+        )?;
+        for issue in self.issues() {
+            write!(f, "\n- {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl EmissionDataset {
+    /// Score the completeness of the dataset for the `requested` period
+    ///
+    /// The period is expanded to whole UTC months, like provider queries.
+    pub fn quality(&self, requested: &TimePeriod) -> Result<DataQuality> {
+        let range =
+            requested.month_range_in(DateAlignment::ExpandToFullMonths, &QueryTimezone::Utc)?;
+        let covered = self.group_by_month();
+        let mut requested_months = Vec::new();
+        let mut month = range.start.date_naive();
+        while month < range.end.date_naive() {
+            requested_months.push(month);
+            month = month
+                .checked_add_months(Months::new(1))
+                .expect("month after a valid month is valid");
+        }
+        let missing_months = requested_months
+            .iter()
+            .filter(|month| !covered.contains_key(month))
+            .copied()
+            .collect();
+
+        let total = self.total_kg_co2eq();
+        let share = |predicate: fn(&CarbonEmission) -> bool| {
+            if total == 0.0 {
+                return 0.0;
+            }
+            stable_sum(
+                self.emissions
+                    .iter()
+                    .filter(|e| predicate(e))
+                    .map(|e| e.emissions_kg_co2eq),
+            ) / total
+        };
+        let mut quality = DataQuality {
+            requested_months,
+            missing_months,
+            estimated_share: share(|e| {
+                e.metadata
+                    .as_ref()
+                    .is_some_and(|metadata| metadata.estimated == Some(true))
+            }),
+            unknown_region_share: share(|e| is_unknown(&e.region)),
+            unknown_service_share: share(|e| e.service.as_deref().is_none_or(is_unknown)),
+            score: 0.0,
+        };
+        if !self.is_empty() {
+            quality.score = COVERAGE_WEIGHT * quality.period_coverage()
+                + REPORTED_WEIGHT * (1.0 - quality.estimated_share)
+                + KNOWN_REGION_WEIGHT * (1.0 - quality.unknown_region_share)
+                + KNOWN_SERVICE_WEIGHT * (1.0 - quality.unknown_service_share);
+        }
+        Ok(quality)
+    }
+}
+
+fn is_unknown(value: &str) -> bool {
+    value.is_empty() || value == "unknown" || value == UNSPECIFIED_SERVICE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EmissionMetadata;
+    use chrono::{TimeZone, Utc};
+
+    fn emission(
+        month: u32,
+        region: &str,
+        service: Option<&str>,
+        estimated: bool,
+    ) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "ibm".to_string(),
+            region: region.to_string(),
+            service: service.map(str::to_string),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 10.0,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(27),
+            },
+            metadata: Some(EmissionMetadata {
+                energy_kwh: None,
+                grid_carbon_intensity: None,
+                renewable_percentage: None,
+                date_alignment: None,
+                provider_data: None,
+                tags: Default::default(),
+                estimated: Some(estimated),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_quality_of_incomplete_dataset() {
+        let dataset = EmissionDataset::new(vec![
+            emission(1, "us-south", Some("Code Engine"), false),
+            emission(1, "unknown", Some("Code Engine"), false),
+            emission(3, "us-south", None, true),
+            emission(3, "eu-de", Some("Cloudant"), false),
+        ]);
+        let requested = TimePeriod {
+            start: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 31, 23, 59, 59).unwrap(),
+        };
+
+        let quality = dataset.quality(&requested).unwrap();
+        assert_eq!(quality.requested_months.len(), 3);
+        assert_eq!(
+            quality.missing_months,
+            vec![NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()]
+        );
+        assert_eq!(quality.estimated_share, 0.25);
+        assert_eq!(quality.unknown_region_share, 0.25);
+        assert_eq!(quality.unknown_service_share, 0.25);
+        let expected = 40.0 * 2.0 / 3.0 + 3.0 * 20.0 * 0.75;
+        assert!((quality.score - expected).abs() < 1e-9);
+        assert_eq!(quality.issues().len(), 4);
+        assert!(
+            quality.to_string().starts_with(
+                "Data quality 72/100: 2 of 3 months covered\n- no emissions for 2024-02"
+            )
+        );
+
+        let empty = EmissionDataset::default().quality(&requested).unwrap();
+        assert_eq!(empty.score, 0.0);
+        assert_eq!(empty.missing_months.len(), 3);
+    }
+}
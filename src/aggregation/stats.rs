@@ -0,0 +1,241 @@
+//! Trend and share statistics over an emission dataset
+//!
+//! Growth rates and shares are expressed in percent. Monthly statistics work
+//! on a continuous series: months without emissions between the first and the
+//! last month of the dataset count as zero.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Months, NaiveDate};
+
+use super::{Dimension, EmissionDataset, stable_sum};
+
+impl EmissionDataset {
+    /// Continuous monthly totals in kg CO2e, keyed by the first day of each month
+    pub fn monthly_series(&self) -> Vec<(NaiveDate, f64)> {
+        let totals = self.group_by_month();
+        let (Some(first), Some(last)) = (totals.keys().next(), totals.keys().next_back()) else {
+            return Vec::new();
+        };
+
+        let mut series = Vec::new();
+        let mut month = *first;
+        while month <= *last {
+            series.push((month, totals.get(&month).copied().unwrap_or(0.0)));
+            month = month + Months::new(1);
+        }
+        series
+    }
+
+    /// Trailing average of monthly totals over `window` months
+    ///
+    /// The first `window - 1` months have no complete window and are skipped.
+    pub fn rolling_average(&self, window: usize) -> Vec<(NaiveDate, f64)> {
+        if window == 0 {
+            return Vec::new();
+        }
+        self.monthly_series()
+            .windows(window)
+            .map(|months| {
+                let sum = stable_sum(months.iter().map(|(_, kg)| *kg));
+                (months[window - 1].0, sum / window as f64)
+            })
+            .collect()
+    }
+
+    /// Growth of each month's total compared to the previous month
+    ///
+    /// `None` when the previous month has no emissions.
+    pub fn month_over_month_growth(&self) -> Vec<(NaiveDate, Option<f64>)> {
+        self.monthly_series()
+            .windows(2)
+            .map(|months| (months[1].0, growth(months[0].1, months[1].1)))
+            .collect()
+    }
+
+    /// Growth of each month's total compared to the same month of the previous year
+    ///
+    /// Only months with a month twelve months earlier in the series are returned;
+    /// `None` when that month has no emissions.
+    pub fn year_over_year_growth(&self) -> Vec<(NaiveDate, Option<f64>)> {
+        let totals: BTreeMap<NaiveDate, f64> = self.monthly_series().into_iter().collect();
+        totals
+            .iter()
+            .filter_map(|(month, kg)| {
+                let previous = totals.get(&(*month - Months::new(12)))?;
+                Some((*month, growth(*previous, *kg)))
+            })
+            .collect()
+    }
+
+    /// Compound annual growth rate between the first and last calendar years
+    ///
+    /// `None` with less than two years of data or no emissions in the first year.
+    /// Partial years are compared as-is, so prefer datasets covering full years.
+    pub fn cagr(&self) -> Option<f64> {
+        let years = self.group_by(|e| e.time_period.start.year());
+        let (first_year, first) = years.first_key_value()?;
+        let (last_year, last) = years.last_key_value()?;
+        if last_year == first_year || *first <= 0.0 {
+            return None;
+        }
+        let periods = f64::from(last_year - first_year);
+        Some(((last / first).powf(1.0 / periods) - 1.0) * 100.0)
+    }
+
+    /// Monthly totals as a one-line Unicode sparkline, e.g. `▁▃█▅`
+    ///
+    /// One character per month of [`monthly_series`](Self::monthly_series),
+    /// scaled between zero and the largest month.
+    pub fn monthly_sparkline(&self) -> String {
+        const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+        let series = self.monthly_series();
+        let max = series.iter().map(|(_, kg)| *kg).fold(0.0, f64::max);
+        series
+            .iter()
+            .map(|(_, kg)| {
+                if max <= 0.0 {
+                    return BARS[0];
+                }
+                let level = (kg.max(0.0) / max * (BARS.len() - 1) as f64).round() as usize;
+                BARS[level.min(BARS.len() - 1)]
+            })
+            .collect()
+    }
+
+    /// Share of the total emissions of each value of `dimension`, in percent
+    ///
+    /// Empty when the dataset has no emissions.
+    pub fn share_of_total(&self, dimension: Dimension) -> BTreeMap<String, f64> {
+        let total = self.total_kg_co2eq();
+        if total == 0.0 {
+            return BTreeMap::new();
+        }
+        self.group_by_dimension(dimension)
+            .into_iter()
+            .map(|(key, kg)| (key, kg / total * 100.0))
+            .collect()
+    }
+}
+
+// Growth from `previous` to `current` in percent
+fn growth(previous: f64, current: f64) -> Option<f64> {
+    (previous != 0.0).then(|| (current - previous) / previous * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(year: i32, month: u32, region: &str, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "ibm".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(28),
+            },
+            metadata: None,
+        }
+    }
+
+    fn month(year: i32, month: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, 1).unwrap()
+    }
+
+    #[test]
+    fn test_monthly_series_fills_gaps() {
+        let dataset = EmissionDataset::new(vec![
+            emission(2024, 1, "dallas", 10.0),
+            emission(2024, 1, "frankfurt", 5.0),
+            emission(2024, 3, "dallas", 30.0),
+        ]);
+
+        assert_eq!(
+            dataset.monthly_series(),
+            vec![
+                (month(2024, 1), 15.0),
+                (month(2024, 2), 0.0),
+                (month(2024, 3), 30.0)
+            ]
+        );
+        assert_eq!(dataset.rolling_average(3), vec![(month(2024, 3), 15.0)]);
+        assert!(dataset.rolling_average(4).is_empty());
+    }
+
+    #[test]
+    fn test_month_over_month_growth() {
+        let dataset = EmissionDataset::new(vec![
+            emission(2024, 1, "dallas", 0.0),
+            emission(2024, 2, "dallas", 10.0),
+            emission(2024, 3, "dallas", 15.0),
+        ]);
+
+        assert_eq!(
+            dataset.month_over_month_growth(),
+            vec![(month(2024, 2), None), (month(2024, 3), Some(50.0))]
+        );
+    }
+
+    #[test]
+    fn test_year_over_year_growth_and_cagr() {
+        let dataset = EmissionDataset::new(vec![
+            emission(2022, 6, "dallas", 100.0),
+            emission(2023, 6, "dallas", 80.0),
+            emission(2024, 6, "dallas", 64.0),
+        ]);
+
+        // Every month from June 2023 has a month twelve months earlier
+        let yoy = dataset.year_over_year_growth();
+        assert_eq!(yoy.len(), 13);
+        assert_eq!(yoy[0], (month(2023, 6), Some(-20.0)));
+        assert_eq!(yoy[1], (month(2023, 7), None));
+        assert_eq!(yoy[12], (month(2024, 6), Some(-20.0)));
+
+        let cagr = dataset.cagr().unwrap();
+        assert!((cagr + 20.0).abs() < 1e-9);
+        assert_eq!(EmissionDataset::default().cagr(), None);
+    }
+
+    #[test]
+    fn test_monthly_sparkline_and_filter() {
+        let dataset = EmissionDataset::new(vec![
+            emission(2024, 1, "dallas", 0.0),
+            emission(2024, 2, "dallas", 35.0),
+            emission(2024, 4, "Frankfurt", 70.0),
+        ]);
+
+        assert_eq!(dataset.monthly_sparkline(), "▁▅▁█");
+        assert_eq!(EmissionDataset::default().monthly_sparkline(), "");
+
+        let frankfurt = dataset.filter_dimension(Dimension::Region, "frankfurt");
+        assert_eq!(frankfurt.len(), 1);
+        assert_eq!(frankfurt.monthly_sparkline(), "█");
+    }
+
+    #[test]
+    fn test_share_of_total() {
+        let dataset = EmissionDataset::new(vec![
+            emission(2024, 1, "dallas", 30.0),
+            emission(2024, 2, "dallas", 45.0),
+            emission(2024, 1, "frankfurt", 25.0),
+        ]);
+
+        let shares = dataset.share_of_total(Dimension::Region);
+        assert_eq!(shares["dallas"], 75.0);
+        assert_eq!(shares["frankfurt"], 25.0);
+        assert!(
+            EmissionDataset::default()
+                .share_of_total(Dimension::Provider)
+                .is_empty()
+        );
+    }
+}
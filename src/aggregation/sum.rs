@@ -0,0 +1,111 @@
+//! Numerically stable summation of emission values
+//!
+//! Adding millions of item-level rows one after the other in `f64` loses the
+//! low digits of every small value added to a large running total, and the
+//! lost digits depend on the order and chunking of the rows. [`StableSum`]
+//! carries the lost digits in a compensation term (Neumaier's variant of
+//! Kahan summation), which every total of an [`EmissionDataset`](super::EmissionDataset)
+//! uses.
+//!
+//! # Guarantee
+//!
+//! For values of the same sign, such as emissions, the result is within two
+//! units in the last place of the exact sum, however many values are added
+//! (up to about 10^15) and in whichever order or chunks they are added and
+//! [merged](StableSum::merge). Totals of the same rows therefore agree run to
+//! run up to the last bits, and exactly once rounded by a
+//! [`PrecisionPolicy`](crate::PrecisionPolicy), unless the exact total lies on
+//! a rounding boundary.
+
+use std::iter::Sum;
+
+/// Compensated sum of `f64` values
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StableSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl StableSum {
+    /// An empty sum
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `value` to the sum
+    pub fn add(&mut self, value: f64) {
+        let sum = self.sum + value;
+        // Recover the low-order digits lost by the addition
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - sum) + value;
+        } else {
+            self.compensation += (value - sum) + self.sum;
+        }
+        self.sum = sum;
+    }
+
+    /// Add the values of `other`, e.g. the sum of another chunk of rows
+    pub fn merge(&mut self, other: StableSum) {
+        self.add(other.sum);
+        self.compensation += other.compensation;
+    }
+
+    /// The compensated total
+    pub fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+impl Extend<f64> for StableSum {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, values: I) {
+        for value in values {
+            self.add(value);
+        }
+    }
+}
+
+impl FromIterator<f64> for StableSum {
+    fn from_iter<I: IntoIterator<Item = f64>>(values: I) -> Self {
+        let mut sum = Self::new();
+        sum.extend(values);
+        sum
+    }
+}
+
+impl Sum<f64> for StableSum {
+    fn sum<I: Iterator<Item = f64>>(values: I) -> Self {
+        values.collect()
+    }
+}
+
+impl<'a> Sum<&'a f64> for StableSum {
+    fn sum<I: Iterator<Item = &'a f64>>(values: I) -> Self {
+        values.copied().collect()
+    }
+}
+
+/// Compensated sum of `values`
+pub(crate) fn stable_sum(values: impl IntoIterator<Item = f64>) -> f64 {
+    values.into_iter().collect::<StableSum>().value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sum_is_independent_of_chunking() {
+        let values = vec![0.1; 1_000_000];
+        assert_ne!(values.iter().sum::<f64>(), 100_000.0);
+        assert_eq!(stable_sum(values.iter().copied()), 100_000.0);
+
+        let mut chunked = StableSum::new();
+        for chunk in values.chunks(4096).rev() {
+            chunked.merge(chunk.iter().sum());
+        }
+        assert_eq!(chunked.value(), 100_000.0);
+
+        assert_eq!(stable_sum([1.0, 1e100, 1.0, -1e100]), 2.0);
+        assert_eq!(stable_sum([]), 0.0);
+    }
+}
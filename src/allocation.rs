@@ -0,0 +1,280 @@
+//! Allocation of emissions to teams for internal chargeback
+//!
+//! An [`Allocator`] attributes each emission of a dataset to teams:
+//! - emissions of resources owned by a single team are assigned to it,
+//! - emissions of shared resources (shared clusters, shared subscriptions) are
+//!   split across teams proportionally to an [`AllocationKey`],
+//! - everything else is reported as unallocated.
+//!
+//! Rules are evaluated in the order they were added; the first matching rule wins.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::error::{CarbemError, Result};
+use crate::models::CarbonEmission;
+
+/// Selects the emissions a rule applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selector {
+    /// Emissions whose `dimension` equals `value`
+    Dimension { dimension: Dimension, value: String },
+
+    /// Emissions whose provider-specific data has `field` equal to `value`,
+    /// e.g. an IBM `account_id` or an Azure `itemName`
+    ProviderData { field: String, value: String },
+}
+
+impl Selector {
+    /// Whether `emission` is selected
+    pub fn matches(&self, emission: &CarbonEmission) -> bool {
+        match self {
+            Selector::Dimension { dimension, value } => dimension.key(emission) == *value,
+            Selector::ProviderData { field, value } => emission
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.provider_data.as_ref())
+                .and_then(|data| data.get(field))
+                .and_then(|field| field.as_str())
+                .is_some_and(|field| field == value),
+        }
+    }
+}
+
+/// How the emissions of a shared resource are split across teams
+///
+/// Every variant holds a value per team; each team receives the fraction of
+/// the emissions its value represents in the sum of all values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationKey {
+    /// Agreed weights, e.g. headcount or a negotiated split
+    Weights(BTreeMap<String, f64>),
+
+    /// Cost of the shared resource attributed to each team
+    Cost(BTreeMap<String, f64>),
+
+    /// Usage of the shared resource by each team (CPU hours, requests, ...)
+    Usage(BTreeMap<String, f64>),
+}
+
+impl AllocationKey {
+    fn values(&self) -> &BTreeMap<String, f64> {
+        match self {
+            AllocationKey::Weights(values)
+            | AllocationKey::Cost(values)
+            | AllocationKey::Usage(values) => values,
+        }
+    }
+
+    // Fraction of the emissions allocated to each team
+    fn fractions(&self) -> Result<Vec<(String, f64)>> {
+        let values = self.values();
+        if values
+            .values()
+            .any(|value| *value < 0.0 || !value.is_finite())
+        {
+            return Err(CarbemError::Config(
+                "allocation key values must be finite and non-negative".to_string(),
+            ));
+        }
+        let total: f64 = values.values().sum();
+        if total <= 0.0 {
+            return Err(CarbemError::Config(
+                "allocation key values must not all be zero".to_string(),
+            ));
+        }
+        Ok(values
+            .iter()
+            .map(|(team, value)| (team.clone(), value / total))
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Assign(Selector, String),
+    Share(Selector, AllocationKey),
+}
+
+/// Emissions allocated to teams
+#[derive(Debug, Clone, Default)]
+pub struct Allocation {
+    /// Dataset of each team, with shared emissions scaled to the team's share
+    pub teams: BTreeMap<String, EmissionDataset>,
+
+    /// Emissions no rule applies to
+    pub unallocated: EmissionDataset,
+}
+
+impl Allocation {
+    /// Total emissions in kg CO2e per team
+    pub fn totals(&self) -> BTreeMap<String, f64> {
+        self.teams
+            .iter()
+            .map(|(team, dataset)| (team.clone(), dataset.total_kg_co2eq()))
+            .collect()
+    }
+}
+
+/// Allocates emissions to teams according to ownership and sharing rules
+#[derive(Debug, Clone, Default)]
+pub struct Allocator {
+    rules: Vec<Rule>,
+}
+
+impl Allocator {
+    /// Create an allocator without rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assign all selected emissions to `team`
+    pub fn assign(mut self, selector: Selector, team: impl Into<String>) -> Self {
+        self.rules.push(Rule::Assign(selector, team.into()));
+        self
+    }
+
+    /// Split selected emissions across teams according to `key`
+    pub fn share(mut self, selector: Selector, key: AllocationKey) -> Self {
+        self.rules.push(Rule::Share(selector, key));
+        self
+    }
+
+    /// Allocate the emissions of `dataset`
+    ///
+    /// The total of the team datasets and the unallocated dataset equals the
+    /// total of `dataset`.
+    pub fn allocate(&self, dataset: &EmissionDataset) -> Result<Allocation> {
+        // Validate keys once, before touching any emission
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| match rule {
+                Rule::Assign(selector, team) => Ok((selector, vec![(team.clone(), 1.0)])),
+                Rule::Share(selector, key) => Ok((selector, key.fractions()?)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut allocation = Allocation::default();
+        for emission in dataset.emissions() {
+            let Some((_, fractions)) = rules
+                .iter()
+                .find(|(selector, _)| selector.matches(emission))
+            else {
+                allocation.unallocated.extend([emission.clone()]);
+                continue;
+            };
+
+            for (team, fraction) in fractions {
+                let mut share = emission.clone();
+                share.emissions_kg_co2eq *= fraction;
+                allocation
+                    .teams
+                    .entry(team.clone())
+                    .or_default()
+                    .extend([share]);
+            }
+        }
+        Ok(allocation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EmissionMetadata, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(region: &str, account_id: &str, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "ibm".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: Some(EmissionMetadata {
+                energy_kwh: None,
+                grid_carbon_intensity: None,
+                renewable_percentage: None,
+                date_alignment: None,
+                provider_data: Some(serde_json::json!({ "account_id": account_id })),
+                tags: Default::default(),
+                estimated: None,
+            }),
+        }
+    }
+
+    fn region(value: &str) -> Selector {
+        Selector::Dimension {
+            dimension: Dimension::Region,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_allocate_assigned_and_shared() {
+        let dataset = EmissionDataset::new(vec![
+            emission("dallas", "acc-search", 10.0),
+            emission("frankfurt", "acc-shared", 100.0),
+            emission("tokyo", "acc-other", 7.0),
+        ]);
+
+        let allocator = Allocator::new()
+            .assign(
+                Selector::ProviderData {
+                    field: "account_id".to_string(),
+                    value: "acc-search".to_string(),
+                },
+                "search",
+            )
+            .share(
+                region("frankfurt"),
+                AllocationKey::Usage(BTreeMap::from([
+                    ("search".to_string(), 3.0),
+                    ("ads".to_string(), 1.0),
+                ])),
+            );
+
+        let allocation = allocator.allocate(&dataset).unwrap();
+        let totals = allocation.totals();
+        assert_eq!(totals["search"], 85.0);
+        assert_eq!(totals["ads"], 25.0);
+        assert_eq!(allocation.unallocated.total_kg_co2eq(), 7.0);
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let dataset = EmissionDataset::new(vec![emission("dallas", "acc", 4.0)]);
+        let allocation = Allocator::new()
+            .assign(region("dallas"), "platform")
+            .assign(region("dallas"), "search")
+            .allocate(&dataset)
+            .unwrap();
+
+        assert_eq!(allocation.totals().len(), 1);
+        assert_eq!(allocation.totals()["platform"], 4.0);
+    }
+
+    #[test]
+    fn test_invalid_allocation_key() {
+        let dataset = EmissionDataset::new(vec![emission("dallas", "acc", 4.0)]);
+        let result = Allocator::new()
+            .share(
+                region("dallas"),
+                AllocationKey::Cost(BTreeMap::from([("search".to_string(), 0.0)])),
+            )
+            .allocate(&dataset);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Append-only audit log of outbound provider requests
+//!
+//! Compliance teams may need evidence of which external APIs were called. An
+//! [`AuditLog`] appends one JSON line per provider request to a file, with
+//! when it was sent, the provider, the endpoint, the outcome, its duration
+//! and a hash of the query that initiated it. Existing lines are never
+//! rewritten. Enable it with
+//! [`CarbemClientBuilder::with_audit_log`](crate::CarbemClientBuilder::with_audit_log)
+//! or the `audit_log` path of a [`ClientConfig`](crate::ClientConfig).
+//!
+//! Endpoints are logged without their query string, and request and response
+//! bodies are never logged.
+
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::correlation;
+use crate::error::{CarbemError, Result};
+use crate::models::EmissionQuery;
+use crate::runtime::spawn_blocking;
+use crate::transport::{HttpRequest, HttpResponse, Transport, request_hash};
+
+tokio::task_local! {
+    // Hash of the query being run by the current task
+    static QUERY_HASH: String;
+}
+
+/// One outbound provider request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the request was sent
+    pub timestamp: DateTime<Utc>,
+
+    /// Provider that sent the request, e.g. `azure`
+    pub provider: String,
+
+    /// HTTP method
+    pub method: String,
+
+    /// URL without its query string
+    pub endpoint: String,
+
+    /// Optional: HTTP status of the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+
+    /// Optional: error when no response was received
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Time until the complete response was received, in milliseconds
+    pub duration_ms: u64,
+
+    /// Optional: [`request_hash`] of the request, recorded in the
+    /// [`Lineage`](crate::Lineage) of the emissions it returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_hash: Option<String>,
+
+    /// Optional: [`query_hash`] of the query that initiated the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_hash: Option<String>,
+
+    /// Optional: [correlation ID](crate::correlation) of the query that initiated the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+/// SHA-256 of the JSON form of `query`, as lowercase hex
+pub fn query_hash(query: &EmissionQuery) -> String {
+    let json = serde_json::to_vec(query).expect("queries always serialize");
+    Sha256::digest(&json)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Run `future` with requests attributed to `query` in the audit log
+pub(crate) async fn for_query<F: Future>(query: &EmissionQuery, future: F) -> F::Output {
+    QUERY_HASH.scope(query_hash(query), future).await
+}
+
+/// A JSON Lines file that audit records are appended to
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl AuditLog {
+    /// Open `path` for appending, creating it if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| io_error(&path, e))?;
+        Ok(Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `record` as one line, flushed before returning
+    pub async fn append(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let file = self.file.clone();
+        spawn_blocking(move || {
+            let mut file = file.lock().expect("audit log lock poisoned");
+            file.write_all(&line)?;
+            file.flush()
+        })
+        .await?
+        .map_err(|e| io_error(&self.path, e))
+    }
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> CarbemError {
+    CarbemError::Other(format!(
+        "Failed to write audit log {}: {}",
+        path.display(),
+        error
+    ))
+}
+
+/// [`Transport`] recording every request of a provider in an [`AuditLog`]
+///
+/// A request fails when its record cannot be written, so that no response
+/// is used without evidence of the call.
+#[derive(Debug)]
+pub struct AuditTransport {
+    inner: Arc<dyn Transport>,
+    provider: String,
+    log: Arc<AuditLog>,
+}
+
+impl AuditTransport {
+    /// Record the requests of `provider` sent through `inner` in `log`
+    pub fn new(inner: Arc<dyn Transport>, provider: impl Into<String>, log: Arc<AuditLog>) -> Self {
+        Self {
+            inner,
+            provider: provider.into(),
+            log,
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for AuditTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let mut record = AuditRecord {
+            timestamp: Utc::now(),
+            provider: self.provider.clone(),
+            method: request.method.to_string(),
+            endpoint: request
+                .url
+                .split(['?', '#'])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            status: None,
+            error: None,
+            duration_ms: 0,
+            request_hash: Some(request_hash(&request)),
+            query_hash: QUERY_HASH.try_with(Clone::clone).ok(),
+            correlation_id: correlation::current(),
+        };
+        let started = Instant::now();
+        let result = self.inner.send(request).await;
+        record.duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(response) => record.status = Some(response.status.as_u16()),
+            Err(e) => record.error = Some(e.to_string()),
+        }
+        self.log.append(&record).await?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use reqwest::header::HeaderMap;
+    use reqwest::{Method, StatusCode};
+
+    #[derive(Debug)]
+    struct Ok200;
+
+    #[async_trait]
+    impl Transport for Ok200 {
+        async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+            Ok(HttpResponse::new(StatusCode::OK, "{}"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_appended() {
+        let path = std::env::temp_dir().join(format!("carbem-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        std::fs::write(&path, "{\"earlier\":true}\n").unwrap();
+        let log = Arc::new(AuditLog::open(&path).unwrap());
+        let transport = AuditTransport::new(Arc::new(Ok200), "ibm", log);
+        let request = || {
+            HttpRequest::new(
+                Method::GET,
+                "https://api.example/v1/emissions?enterprise_id=secret",
+                HeaderMap::new(),
+            )
+        };
+        let query = EmissionQuery {
+            provider: "ibm".to_string(),
+            regions: Vec::new(),
+            time_period: TimePeriod {
+                start: Utc::now(),
+                end: Utc::now(),
+            },
+            services: None,
+            resources: None,
+            provider_config: None,
+            raw_response: Default::default(),
+            date_alignment: Default::default(),
+            timezone: Default::default(),
+            dry_run: false,
+            route: Vec::new(),
+            tag_filters: Vec::new(),
+            strict: false,
+        };
+
+        transport.send(request()).await.unwrap();
+        for_query(&query, transport.send(request())).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], "{\"earlier\":true}");
+        let first: AuditRecord = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(first.provider, "ibm");
+        assert_eq!(first.method, "GET");
+        assert_eq!(first.endpoint, "https://api.example/v1/emissions");
+        assert_eq!(first.status, Some(200));
+        assert_eq!(first.query_hash, None);
+        assert_eq!(first.request_hash, Some(request_hash(&request())));
+        let second: AuditRecord = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(second.query_hash, Some(query_hash(&query)));
+        assert_eq!(second.query_hash.unwrap().len(), 64);
+    }
+}
@@ -0,0 +1,259 @@
+//! Short-lived access tokens shared by concurrent queries
+//!
+//! A [`TokenCache`] hands out the current access token and refreshes it with
+//! its [`TokenRefresher`] shortly before it expires. Refreshes are
+//! single-flight: when many queries find the token expired at once, one of
+//! them refreshes it and the others wait for its result, so the identity
+//! provider sees a single request instead of one per query.
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use tokio::sync::watch;
+
+use crate::error::{CarbemError, Result};
+
+/// Tokens expiring within this margin are refreshed before use
+pub const DEFAULT_REFRESH_MARGIN: Duration = Duration::minutes(5);
+
+/// An access token and its expiry
+#[derive(Clone, PartialEq, Eq)]
+pub struct AccessToken {
+    /// Bearer token sent to the provider
+    pub secret: String,
+
+    /// When the token expires
+    pub expires_at: DateTime<Utc>,
+}
+
+// Never expose the token in debug output
+impl std::fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("secret", &crate::redact::REDACTED)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Obtains new access tokens, e.g. from a refresh token or client credentials
+#[async_trait]
+pub trait TokenRefresher: Send + Sync {
+    /// Request a new access token from the identity provider
+    async fn refresh(&self) -> Result<AccessToken>;
+}
+
+// Result of a refresh, shared with the queries waiting for it
+type Outcome = Option<std::result::Result<AccessToken, String>>;
+
+#[derive(Default)]
+struct State {
+    token: Option<AccessToken>,
+    in_flight: Option<watch::Receiver<Outcome>>,
+}
+
+/// Current access token, refreshed once for all concurrent callers
+pub struct TokenCache {
+    refresher: Arc<dyn TokenRefresher>,
+    margin: Duration,
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for TokenCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenCache")
+            .field("margin", &self.margin)
+            .finish_non_exhaustive()
+    }
+}
+
+// Clears the in-flight refresh if its leader is cancelled, so waiters retry
+struct InFlightGuard<'a> {
+    state: &'a Mutex<State>,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .in_flight = None;
+    }
+}
+
+impl TokenCache {
+    /// Cache refreshing tokens with `refresher`, [`DEFAULT_REFRESH_MARGIN`] before expiry
+    pub fn new(refresher: Arc<dyn TokenRefresher>) -> Self {
+        Self {
+            refresher,
+            margin: DEFAULT_REFRESH_MARGIN,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Start with a token obtained elsewhere, e.g. from an interactive sign-in
+    pub fn with_token(self, token: AccessToken) -> Self {
+        self.lock().token = Some(token);
+        self
+    }
+
+    /// Refresh tokens expiring within `margin` instead of the default
+    pub fn with_margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Current access token, refreshed first when it is missing or about to expire
+    pub async fn token(&self) -> Result<String> {
+        loop {
+            let (sender, mut receiver) = {
+                let mut state = self.lock();
+                if let Some(token) = &state.token
+                    && token.expires_at - self.margin > Utc::now()
+                {
+                    return Ok(token.secret.clone());
+                }
+                match &state.in_flight {
+                    Some(receiver) => (None, receiver.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        state.in_flight = Some(receiver.clone());
+                        (Some(sender), receiver)
+                    }
+                }
+            };
+
+            let Some(sender) = sender else {
+                // Another caller is refreshing: wait for its outcome
+                match receiver.wait_for(Option::is_some).await {
+                    Ok(outcome) => return shared_result(outcome.as_ref()),
+                    // The refresh was cancelled before completing: try again
+                    Err(_) => continue,
+                }
+            };
+
+            let guard = InFlightGuard { state: &self.state };
+            let result = self.refresher.refresh().await;
+            if let Ok(token) = &result {
+                self.lock().token = Some(token.clone());
+            }
+            drop(guard);
+            let result = result.map_err(|e| match e {
+                CarbemError::Auth(message) => message,
+                other => other.to_string(),
+            });
+            sender.send_replace(Some(result.clone()));
+            return shared_result(Some(&result));
+        }
+    }
+
+    /// Drop the current token, e.g. after the provider rejected it
+    pub fn invalidate(&self) {
+        self.lock().token = None;
+    }
+}
+
+// Refresh outcome as returned to every caller, the leader included
+fn shared_result(outcome: Option<&std::result::Result<AccessToken, String>>) -> Result<String> {
+    match outcome {
+        Some(Ok(token)) => Ok(token.secret.clone()),
+        Some(Err(message)) => Err(CarbemError::Auth(message.clone())),
+        None => unreachable!("waited for a completed refresh"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingRefresher {
+        calls: AtomicUsize,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl TokenRefresher for CountingRefresher {
+        async fn refresh(&self) -> Result<AccessToken> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            if self.fail {
+                return Err(CarbemError::Auth("invalid_grant".to_string()));
+            }
+            Ok(AccessToken {
+                secret: format!("token-{}", call),
+                expires_at: Utc::now() + Duration::hours(1),
+            })
+        }
+    }
+
+    fn cache(fail: bool) -> (Arc<CountingRefresher>, Arc<TokenCache>) {
+        let refresher = Arc::new(CountingRefresher {
+            calls: AtomicUsize::new(0),
+            fail,
+        });
+        let cache = TokenCache::new(refresher.clone()).with_token(AccessToken {
+            secret: "expired".to_string(),
+            expires_at: Utc::now() - Duration::minutes(1),
+        });
+        (refresher, Arc::new(cache))
+    }
+
+    async fn concurrent_tokens(cache: &Arc<TokenCache>) -> Vec<Result<String>> {
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let cache = cache.clone();
+                tokio::spawn(async move { cache.token().await })
+            })
+            .collect();
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_callers_share_one_refresh() {
+        let (refresher, cache) = cache(false);
+
+        let results = concurrent_tokens(&cache).await;
+        assert!(results.iter().all(|r| r.as_deref().unwrap() == "token-1"));
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 1);
+
+        // The fresh token is served without refreshing again
+        assert_eq!(cache.token().await.unwrap(), "token-1");
+        cache.invalidate();
+        assert_eq!(cache.token().await.unwrap(), "token-2");
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_shared() {
+        let (refresher, cache) = cache(true);
+
+        let results = concurrent_tokens(&cache).await;
+        assert!(
+            results
+                .iter()
+                .all(|r| matches!(r, Err(CarbemError::Auth(m)) if m.contains("invalid_grant")))
+        );
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_refresh_is_retried() {
+        let (refresher, cache) = cache(false);
+
+        let leader = tokio::time::timeout(std::time::Duration::from_millis(1), cache.token());
+        assert!(leader.await.is_err());
+        assert_eq!(cache.token().await.unwrap(), "token-2");
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 2);
+    }
+}
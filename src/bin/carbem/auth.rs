@@ -0,0 +1,131 @@
+//! `carbem auth`: credentials of a profile in the OS keyring
+//!
+//! `carbem auth login <provider>` prompts for a credential without echoing it
+//! and stores it under `<provider>:<profile>`, where commands read credentials
+//! left empty in the configuration file. For Azure, it signs in with the device
+//! code flow instead and also stores the refresh token, which commands use to
+//! get a fresh access token. Built with the `keyring` feature.
+
+use carbem::credentials::{delete_credential, store_credential};
+use carbem::providers::azure::DeviceCodeFlow;
+use carbem::{CarbemError, Result};
+use clap::{Args, Subcommand, ValueEnum};
+
+use crate::init::secret;
+use crate::messages;
+use crate::session::SessionOptions;
+
+/// Options of `carbem auth`
+#[derive(Debug, Args)]
+pub struct AuthArgs {
+    #[command(subcommand)]
+    command: AuthCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum AuthCommand {
+    /// Store the credential of a provider for the profile
+    Login {
+        /// Provider of the credential
+        provider: Provider,
+
+        /// Azure tenant to sign in to, by default the tenant of the account
+        #[arg(long, conflicts_with = "token")]
+        tenant: Option<String>,
+
+        /// Paste an Azure access token instead of signing in in a browser
+        #[arg(long)]
+        token: bool,
+    },
+
+    /// Remove the stored credentials of a provider for the profile
+    Logout {
+        /// Provider of the credential
+        provider: Provider,
+    },
+}
+
+/// Provider whose credential is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Provider {
+    Azure,
+    Ibm,
+}
+
+impl Provider {
+    // Provider name of the keyring entry
+    fn name(self) -> &'static str {
+        match self {
+            Provider::Azure => "azure",
+            Provider::Ibm => "ibm",
+        }
+    }
+
+    // Message id of the credential prompt
+    fn prompt(self) -> &'static str {
+        match self {
+            Provider::Azure => "cli-azure-token",
+            Provider::Ibm => "cli-ibm-api-key",
+        }
+    }
+}
+
+/// Run the command, returning a line for the user
+pub async fn run(options: &SessionOptions, args: &AuthArgs) -> Result<String> {
+    let profile = &options.profile;
+    match &args.command {
+        AuthCommand::Login {
+            provider: Provider::Azure,
+            tenant,
+            token: false,
+        } => {
+            let flow = tenant
+                .as_ref()
+                .map_or_else(DeviceCodeFlow::default, DeviceCodeFlow::new);
+            let challenge = flow.start().await?;
+            // Shown even with --quiet, the sign-in cannot complete without it
+            eprintln!("{}", challenge.message);
+            let token = flow.poll(&challenge).await?;
+            store_credential("azure", profile, &token.access_token)?;
+            DeviceCodeFlow::save_to_keyring(&token, profile)?;
+            Ok(messages::format(
+                "cli-auth-signed-in",
+                &[("profile", profile)],
+            ))
+        }
+        &AuthCommand::Login { provider, .. } => {
+            let secret = secret(&messages::message(provider.prompt()))?;
+            store_credential(provider.name(), profile, &secret)?;
+            Ok(messages::format(
+                "cli-auth-stored",
+                &[("provider", provider.name()), ("profile", profile)],
+            ))
+        }
+        &AuthCommand::Logout { provider } => {
+            delete_credential(provider.name(), profile)?;
+            if provider == Provider::Azure {
+                match DeviceCodeFlow::delete_from_keyring(profile) {
+                    // Not signed in with the device code flow
+                    Ok(()) | Err(CarbemError::Config(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(messages::format(
+                "cli-auth-removed",
+                &[("provider", provider.name()), ("profile", profile)],
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyring_entries() {
+        assert_eq!(Provider::from_str("ibm", true).unwrap().name(), "ibm");
+        assert_eq!(Provider::from_str("azure", true).unwrap().name(), "azure");
+        assert!(Provider::from_str("aws", true).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! `carbem config`: checks of the selected profile
+//!
+//! `carbem config validate` reports missing fields and malformed IDs without
+//! calling providers. `--probe` also runs a small live query with every
+//! account and `--preflight` checks the roles they hold. The report is
+//! printed one line per finding, or as JSON with `--output json`.
+
+use carbem::config::validate::{IssueSeverity, ValidationReport};
+use carbem::{CarbemClient, CarbemError, ExitStatus, OutputFormat, Result};
+use clap::{Args, Subcommand};
+
+use crate::messages::{self, message};
+use crate::session::SessionOptions;
+
+/// Options of `carbem config`
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Check the profile, optionally against the providers
+    Validate(ValidateArgs),
+}
+
+#[derive(Debug, Args)]
+struct ValidateArgs {
+    /// Verify the credentials with a one-month query per account
+    #[arg(long)]
+    probe: bool,
+
+    /// Check that every account holds the roles carbem needs
+    #[arg(long)]
+    preflight: bool,
+}
+
+/// Run the command, printing its report in `format`
+pub async fn run(
+    options: &SessionOptions,
+    args: &ConfigArgs,
+    format: OutputFormat,
+) -> Result<ExitStatus> {
+    let ConfigCommand::Validate(args) = &args.command;
+    let config = options.load_config().await?;
+    let report = match (args.probe, args.preflight) {
+        (false, false) => CarbemClient::validate_config(&config),
+        (true, false) => CarbemClient::probe_config(&config).await,
+        (false, true) => CarbemClient::preflight_config(&config).await,
+        (true, true) => {
+            let probed = CarbemClient::probe_config(&config).await;
+            ValidationReport {
+                probes: probed.probes,
+                ..CarbemClient::preflight_config(&config).await
+            }
+        }
+    };
+    println!("{}", render(&report, format)?);
+    Ok(status(&report))
+}
+
+/// One line per issue, probe and permission check of `report`
+pub fn describe(report: &ValidationReport) -> Vec<String> {
+    let mut lines: Vec<String> = report
+        .issues
+        .iter()
+        .map(|issue| {
+            let severity = message(match issue.severity {
+                IssueSeverity::Error => "cli-severity-error",
+                IssueSeverity::Warning => "cli-severity-warning",
+            });
+            if issue.field.is_empty() {
+                format!("{}: {}", severity, issue.message)
+            } else {
+                format!("{}: {}: {}", severity, issue.field, issue.message)
+            }
+        })
+        .collect();
+    lines.extend(report.probes.iter().map(|probe| match &probe.error {
+        None => messages::format("cli-validate-queried", &[("account", &probe.account)]),
+        Some(error) => messages::format(
+            "cli-validate-query-failed",
+            &[("account", &probe.account), ("error", error)],
+        ),
+    }));
+    lines.extend(report.permissions.iter().map(ToString::to_string));
+    if lines.is_empty() {
+        lines.push(message("cli-validate-no-issue"));
+    }
+    lines
+}
+
+fn render(report: &ValidationReport, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(report)?),
+        OutputFormat::Table => Ok(describe(report).join("\n")),
+        OutputFormat::Csv => Err(CarbemError::Config(message("cli-csv-unsupported"))),
+    }
+}
+
+// Invalid settings first, then rejected credentials or missing roles
+fn status(report: &ValidationReport) -> ExitStatus {
+    if report.errors().next().is_some() {
+        ExitStatus::Config
+    } else if !report.is_valid() {
+        ExitStatus::Auth
+    } else {
+        ExitStatus::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use carbem::config::validate::{AuthProbe, ConfigIssue};
+
+    #[test]
+    fn test_report_output_and_status() {
+        let mut report = ValidationReport {
+            issues: vec![ConfigIssue {
+                severity: IssueSeverity::Warning,
+                field: "azure[0].regions".to_string(),
+                message: "no location".to_string(),
+            }],
+            probes: vec![AuthProbe {
+                account: "ibm[0]".to_string(),
+                error: Some("401 Unauthorized".to_string()),
+            }],
+            permissions: Vec::new(),
+        };
+        assert_eq!(
+            describe(&report),
+            [
+                "warning: azure[0].regions: no location",
+                "error: ibm[0] query failed: 401 Unauthorized"
+            ]
+        );
+        assert_eq!(status(&report), ExitStatus::Auth);
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&report, OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["probes"][0]["account"], "ibm[0]");
+        assert!(render(&report, OutputFormat::Csv).is_err());
+
+        report.issues[0].severity = IssueSeverity::Error;
+        assert_eq!(status(&report), ExitStatus::Config);
+
+        let valid = ValidationReport::default();
+        assert_eq!(describe(&valid), ["ok: no issue found"]);
+        assert_eq!(status(&valid), ExitStatus::Success);
+    }
+}
@@ -0,0 +1,185 @@
+//! `carbem init`: interactive setup of a profile
+//!
+//! Asks for the providers to query, their credentials and identifiers, checks
+//! access with a test query and saves the profile to the configuration file.
+//! With the `keyring` feature, the credentials can be kept in the OS
+//! credential store instead, leaving them empty in the file.
+
+use carbem::config::profiles::ConfigFile;
+use carbem::config::{AzureAccountConfig, ClientConfig, IbmAccountConfig};
+use carbem::{AzureConfig, CarbemClient, CarbemError, IbmConfig, Result};
+use dialoguer::{Confirm, Input, MultiSelect, Password};
+
+use crate::config::describe;
+use crate::messages::{self, message};
+use crate::session::SessionOptions;
+
+/// Run the wizard, saving the profile selected by `options`
+pub async fn run(options: &SessionOptions) -> Result<()> {
+    let path = options.config_path()?;
+    let profile = &options.profile;
+    let mut file = ConfigFile::load(&path)?;
+    if file.profiles.contains_key(profile)
+        && !confirm(
+            &messages::format(
+                "cli-init-replace",
+                &[("profile", profile), ("path", &path.display().to_string())],
+            ),
+            false,
+        )?
+    {
+        return Ok(());
+    }
+
+    let providers = MultiSelect::new()
+        .with_prompt(message("cli-init-providers"))
+        .items(["Azure", "IBM Cloud"])
+        .interact()
+        .map_err(prompt_error)?;
+    if providers.is_empty() {
+        return Err(CarbemError::Config(message("cli-init-no-provider")));
+    }
+
+    let mut config = ClientConfig::default();
+    if providers.contains(&0) {
+        config.azure.push(AzureAccountConfig {
+            name: None,
+            auth: AzureConfig {
+                access_token: secret(&message("cli-azure-token"))?,
+            },
+            subscriptions: list(&message("cli-init-subscriptions"))?,
+            regions: list(&message("cli-init-locations"))?,
+        });
+    }
+    if providers.contains(&1) {
+        let enterprise_id = text(&message("cli-init-enterprise"))?;
+        config.ibm.push(IbmAccountConfig {
+            name: None,
+            auth: IbmConfig {
+                api_key: secret(&message("cli-ibm-api-key"))?,
+            },
+            enterprise_id: (!enterprise_id.is_empty()).then_some(enterprise_id),
+            regions: list(&message("cli-init-regions"))?,
+        });
+    }
+
+    if confirm(&message("cli-init-verify"), true)? {
+        eprintln!("{}", message("cli-init-querying"));
+        let report = CarbemClient::probe_config(&config).await;
+        for line in describe(&report) {
+            eprintln!("  {}", line);
+        }
+        if !report.is_valid() && !confirm(&message("cli-init-save-anyway"), false)? {
+            return Ok(());
+        }
+    }
+
+    #[cfg(feature = "keyring")]
+    if confirm(&message("cli-init-keyring"), true)? {
+        for (provider, secret) in detach_secrets(&mut config) {
+            carbem::credentials::store_credential(provider, profile, &secret)?;
+        }
+    }
+
+    file.set_profile(profile, config);
+    file.save(&path)?;
+    eprintln!(
+        "{}",
+        messages::format(
+            "cli-init-saved",
+            &[("profile", profile), ("path", &path.display().to_string())],
+        )
+    );
+    Ok(())
+}
+
+// Move the credentials of `config` out, by provider, leaving them empty
+#[cfg(feature = "keyring")]
+fn detach_secrets(config: &mut ClientConfig) -> Vec<(&'static str, String)> {
+    let azure = config
+        .azure
+        .iter_mut()
+        .map(|account| ("azure", std::mem::take(&mut account.auth.access_token)));
+    let ibm = config
+        .ibm
+        .iter_mut()
+        .map(|account| ("ibm", std::mem::take(&mut account.auth.api_key)));
+    azure.chain(ibm).collect()
+}
+
+fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()
+        .map_err(prompt_error)
+}
+
+/// Prompt for a secret without echoing it
+pub fn secret(prompt: &str) -> Result<String> {
+    Password::new()
+        .with_prompt(prompt)
+        .interact()
+        .map_err(prompt_error)
+}
+
+fn text(prompt: &str) -> Result<String> {
+    Input::<String>::new()
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .interact_text()
+        .map(|value| value.trim().to_string())
+        .map_err(prompt_error)
+}
+
+fn list(prompt: &str) -> Result<Vec<String>> {
+    text(prompt).map(|value| split_list(&value))
+}
+
+// Non-empty, trimmed items of a comma-separated list
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn prompt_error(error: dialoguer::Error) -> CarbemError {
+    CarbemError::Other(messages::format(
+        "cli-prompt-failed",
+        &[("error", &error.to_string())],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_answers() {
+        assert_eq!(
+            split_list(" westeurope, ,francecentral,"),
+            ["westeurope", "francecentral"]
+        );
+        assert!(split_list("").is_empty());
+    }
+
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn test_secrets_are_detached() {
+        let mut config = ClientConfig::from_json(
+            r#"{"azure": [{"access_token": "token", "subscriptions": ["sub"]}],
+                "ibm": [{"api_key": "key", "enterprise_id": "ent"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            detach_secrets(&mut config),
+            [("azure", "token".to_string()), ("ibm", "key".to_string())]
+        );
+        assert!(config.azure[0].auth.access_token.is_empty());
+        assert!(config.ibm[0].auth.api_key.is_empty());
+        assert_eq!(config.azure[0].subscriptions, ["sub"]);
+    }
+}
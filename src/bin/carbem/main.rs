@@ -0,0 +1,279 @@
+//! The `carbem` command-line tool, built with the `cli` feature
+//!
+//! Commands run against a profile of the configuration file (see
+//! [`ConfigFile`](carbem::config::profiles::ConfigFile)), or against the demo
+//! client with `--demo`.
+//!
+//! The exit status tells failures apart, see [`ExitStatus`]. With `--quiet`,
+//! nothing but the machine-readable output is printed.
+
+#[cfg(feature = "keyring")]
+mod auth;
+mod config;
+mod init;
+mod messages;
+mod providers;
+mod query;
+mod report;
+mod schema;
+mod session;
+mod snapshot;
+mod support;
+mod tui;
+
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use carbem::config::profiles::DEFAULT_PROFILE;
+use carbem::{ExitStatus, Locale, OutputFormat, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+
+use crate::messages::message;
+use crate::session::{Session, SessionOptions};
+
+/// Carbon emissions of your cloud accounts
+#[derive(Debug, Parser)]
+#[command(name = "carbem", version)]
+struct Cli {
+    /// Configuration file, defaults to `CARBEM_CONFIG` or `carbem/config.json`
+    /// in the user configuration directory
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Profile of the configuration file
+    #[arg(long, global = true, default_value = DEFAULT_PROFILE)]
+    profile: String,
+
+    /// Use the bundled demo data instead of a profile
+    #[arg(long, global = true)]
+    demo: bool,
+
+    /// Format of machine-readable output: json, table or csv
+    #[arg(long, global = true, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    /// Print only machine-readable output, no messages; the exit status tells
+    /// what failed
+    #[arg(long, short, global = true)]
+    quiet: bool,
+
+    /// Language of messages: en, fr or de, defaults to the system locale
+    #[arg(long, global = true, env = "CARBEM_LOCALE")]
+    locale: Option<Locale>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Create or replace a profile interactively
+    Init,
+
+    /// Check the profile
+    Config(config::ConfigArgs),
+
+    /// Manage the credentials of the profile in the OS keyring
+    #[cfg(feature = "keyring")]
+    Auth(auth::AuthArgs),
+
+    /// List the providers carbem can query
+    Providers(providers::ProvidersArgs),
+
+    /// Terminal dashboard of totals, breakdowns and trends
+    Tui(tui::TuiArgs),
+
+    /// Print the emissions of a period
+    Query(query::QueryArgs),
+
+    /// Run or list the saved report definitions of the profile
+    Report(report::ReportArgs),
+
+    /// Compare two snapshots saved with `query --snapshot`
+    DiffSnapshots(snapshot::DiffArgs),
+
+    /// Write a bundle of redacted diagnostics to attach to an issue
+    SupportBundle(support::SupportArgs),
+
+    /// Print the JSON Schema of the emission records of `query`, or its
+    /// columns and types with `--output table` or `csv`
+    Schema,
+
+    /// Print the shell completion script
+    Completions {
+        /// Shell to complete for
+        shell: Shell,
+    },
+}
+
+impl Cli {
+    fn session_options(&self) -> SessionOptions {
+        SessionOptions {
+            config: self.config.clone(),
+            profile: self.profile.clone(),
+            demo: self.demo,
+        }
+    }
+}
+
+// Print machine-readable output to stdout, ending with a newline
+fn emit(output: &str) {
+    if output.ends_with('\n') {
+        print!("{}", output);
+    } else {
+        println!("{}", output);
+    }
+}
+
+// Rows under a header row, as CSV or as a table aligned on the widest cells;
+// cells are written as is, so they must not need CSV quoting
+fn render_rows<const N: usize>(
+    columns: &[&str; N],
+    rows: &[[String; N]],
+    format: OutputFormat,
+) -> String {
+    let header = columns.map(str::to_string);
+    let lines = std::iter::once(&header).chain(rows);
+    if format == OutputFormat::Csv {
+        return lines
+            .map(|row| row.join(","))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    let mut widths = columns.map(|column| column.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    lines
+        .map(|row| {
+            row.iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Run the command, its exit status telling how it went
+async fn run(cli: &Cli) -> Result<ExitStatus> {
+    let options = cli.session_options();
+    match &cli.command {
+        Command::Init => init::run(&options).await?,
+        Command::Config(args) => return config::run(&options, args, cli.output).await,
+        #[cfg(feature = "keyring")]
+        Command::Auth(args) => {
+            let message = auth::run(&options, args).await?;
+            if !cli.quiet {
+                eprintln!("{}", message);
+            }
+        }
+        Command::Providers(args) => emit(&providers::run(args, cli.output)?),
+        Command::Tui(args) => tui::run(&Session::open(&options).await?, args).await?,
+        Command::Query(args) if args.dry_run => {
+            let planned = query::plan(&Session::open(&options).await?, args).await?;
+            emit(&query::render_plan(&planned, cli.output)?);
+        }
+        Command::Query(args) => {
+            let emissions = query::run(&Session::open(&options).await?, args).await?;
+            emit(&cli.output.render(&emissions)?);
+            let status = args.status(&emissions);
+            if !cli.quiet {
+                match status {
+                    ExitStatus::NoData => eprintln!("{}", message("cli-warning-no-data")),
+                    ExitStatus::BudgetBreach => eprintln!("{}", message("cli-warning-budget")),
+                    _ => {}
+                }
+            }
+            return Ok(status);
+        }
+        Command::Report(args) => {
+            emit(&report::run(&Session::open(&options).await?, args, cli.output).await?)
+        }
+        Command::DiffSnapshots(args) => emit(&snapshot::run(args, cli.output).await?),
+        Command::SupportBundle(args) => {
+            for note in support::run(&options, args).await? {
+                if !cli.quiet {
+                    eprintln!("{}", note);
+                }
+            }
+        }
+        Command::Schema => emit(&schema::render(cli.output)?),
+        Command::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "carbem", &mut io::stdout())
+        }
+    }
+    Ok(ExitStatus::Success)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    messages::set_locale(cli.locale);
+    match run(&cli).await {
+        Ok(status) => status.into(),
+        Err(e) => {
+            if !cli.quiet {
+                let error = e.to_string();
+                eprintln!("{}", messages::format("cli-error", &[("message", &error)]));
+            }
+            ExitStatus::from(&e).into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from(["carbem", "tui", "--demo", "--months", "3"]);
+        assert!(cli.demo);
+        assert_eq!(cli.profile, DEFAULT_PROFILE);
+        assert!(matches!(cli.command, Command::Tui(_)));
+
+        let cli = Cli::parse_from(["carbem", "query", "--output", "csv", "--months", "2"]);
+        assert_eq!(cli.output, OutputFormat::Csv);
+        assert!(!cli.quiet);
+        let cli = Cli::parse_from(["carbem", "schema", "--locale", "fr"]);
+        assert_eq!(cli.locale, Some(Locale::Fr));
+        assert!(Cli::try_parse_from(["carbem", "schema", "--locale", "es"]).is_err());
+        assert!(Cli::parse_from(["carbem", "query", "-q"]).quiet);
+        assert!(matches!(
+            Cli::parse_from(["carbem", "config", "validate", "--probe"]).command,
+            Command::Config(_)
+        ));
+        assert!(matches!(
+            Cli::parse_from(["carbem", "support-bundle", "bundle.json.zst"]).command,
+            Command::SupportBundle(_)
+        ));
+        #[cfg(feature = "keyring")]
+        assert!(matches!(
+            Cli::parse_from(["carbem", "auth", "login", "ibm", "--profile", "ci"]).command,
+            Command::Auth(_)
+        ));
+        #[cfg(feature = "keyring")]
+        assert!(
+            Cli::try_parse_from([
+                "carbem", "auth", "login", "azure", "--tenant", "t", "--token"
+            ])
+            .is_err()
+        );
+        assert!(Cli::try_parse_from(["carbem", "query", "--output", "xml"]).is_err());
+        assert!(Cli::try_parse_from(["carbem", "query", "--start", "2024-01-01"]).is_err());
+
+        let mut script = Vec::new();
+        clap_complete::generate(Shell::Bash, &mut Cli::command(), "carbem", &mut script);
+        assert!(String::from_utf8(script).unwrap().contains("completions"));
+    }
+}
@@ -0,0 +1,62 @@
+//! Localized messages of the command-line tool
+//!
+//! Messages are the `cli-*` Fluent messages of [`carbem::i18n`], in the
+//! language selected with `--locale`, `CARBEM_LOCALE` or the system locale
+//! settings. Machine-readable output is never localized.
+
+use std::sync::OnceLock;
+
+use carbem::Locale;
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Use `locale` for every message, the system locale settings when `None`
+pub fn set_locale(locale: Option<Locale>) {
+    let _ = LOCALE.set(locale.unwrap_or_else(system_locale));
+}
+
+/// Language of the messages, English until set
+pub fn locale() -> Locale {
+    LOCALE.get().copied().unwrap_or_default()
+}
+
+/// The message `id`
+pub fn message(id: &str) -> String {
+    locale().message(id)
+}
+
+/// The message `id` with its `{ $name }` placeables replaced by `args`
+pub fn format(id: &str, args: &[(&str, &str)]) -> String {
+    locale().format(id, args)
+}
+
+// Language of the first of `LC_ALL`, `LC_MESSAGES` and `LANG` that is set
+fn system_locale() -> Locale {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| parse_posix(&value))
+        .unwrap_or_default()
+}
+
+// Locale of a POSIX setting, e.g. `fr_FR.UTF-8`; `C` and `POSIX` are not languages
+fn parse_posix(value: &str) -> Option<Locale> {
+    value.split(['.', '@']).next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_posix_locales() {
+        assert_eq!(parse_posix("fr_FR.UTF-8"), Some(Locale::Fr));
+        assert_eq!(parse_posix("de_DE@euro"), Some(Locale::De));
+        assert_eq!(parse_posix("C"), None);
+        assert_eq!(
+            Locale::Fr.format("cli-support-written", &[("path", "bundle.json")]),
+            "bundle.json écrit"
+        );
+    }
+}
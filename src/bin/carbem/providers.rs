@@ -0,0 +1,83 @@
+//! `carbem providers list`: providers built into carbem
+//!
+//! Lists what [`ProviderRegistry::available`] describes: the name of every
+//! provider, how it authenticates and the fields required in its
+//! configuration and in the query `provider_config`.
+
+use carbem::providers::registry::{AuthStyle, ProviderInfo, ProviderRegistry};
+use carbem::{OutputFormat, Result};
+use clap::{Args, Subcommand};
+
+use crate::render_rows;
+
+/// Options of `carbem providers`
+#[derive(Debug, Args)]
+pub struct ProvidersArgs {
+    #[command(subcommand)]
+    command: ProvidersCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProvidersCommand {
+    /// List the providers and their required settings
+    List,
+}
+
+const COLUMNS: [&str; 4] = ["provider", "auth", "config_fields", "query_fields"];
+
+/// Run the command, rendering its output in `format`
+pub fn run(args: &ProvidersArgs, format: OutputFormat) -> Result<String> {
+    let ProvidersCommand::List = args.command;
+    render(&ProviderRegistry::new().available(), format)
+}
+
+fn render(providers: &[ProviderInfo], format: OutputFormat) -> Result<String> {
+    if format == OutputFormat::Json {
+        return Ok(serde_json::to_string_pretty(providers)?);
+    }
+    // Lists are space-separated, so CSV cells need no quoting
+    let rows: Vec<[String; 4]> = providers
+        .iter()
+        .map(|info| {
+            [
+                info.name.clone(),
+                auth_name(info.auth_style).to_string(),
+                info.required_config_fields.join(" "),
+                info.required_query_fields.join(" "),
+            ]
+        })
+        .collect();
+    Ok(render_rows(&COLUMNS, &rows, format))
+}
+
+// Name of `style` in the JSON output
+fn auth_name(style: AuthStyle) -> &'static str {
+    match style {
+        AuthStyle::BearerToken => "bearer_token",
+        AuthStyle::ApiKey => "api_key",
+        AuthStyle::Unspecified => "unspecified",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_providers_are_listed() {
+        let args = ProvidersArgs {
+            command: ProvidersCommand::List,
+        };
+        assert_eq!(
+            run(&args, OutputFormat::Csv).unwrap(),
+            "provider,auth,config_fields,query_fields\n\
+             azure,bearer_token,access_token,\n\
+             ibm,api_key,api_key,enterprise_id"
+        );
+        let table = run(&args, OutputFormat::Table).unwrap();
+        assert!(table.starts_with("provider  auth          config_fields  query_fields\n"));
+        let json: serde_json::Value =
+            serde_json::from_str(&run(&args, OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(json[1]["required_query_fields"][0], "enterprise_id");
+    }
+}
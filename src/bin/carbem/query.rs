@@ -0,0 +1,280 @@
+//! `carbem query`: emissions of a period, for scripts and pipelines
+//!
+//! Emissions are printed in the `--output` format. JSON and CSV use the
+//! columns of the flat emission records, whose JSON Schema `carbem schema`
+//! prints. The exit status is `6` when no emission was found and `7` when
+//! they exceed `--budget`. With `--dry-run`, the provider requests are
+//! printed instead of sent. `--snapshot` also saves the emissions for
+//! `carbem diff-snapshots`, and `--chart` draws them as an SVG chart.
+
+use std::path::PathBuf;
+
+use carbem::plot::{self, ChartOptions};
+use carbem::{
+    CarbemError, CarbonEmission, EmissionDataset, EmissionQuery, ExitStatus, OutputFormat,
+    PlannedRequest, Result, TimePeriod,
+};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use clap::{Args, ValueEnum};
+
+use crate::messages::{self, message};
+use crate::session::Session;
+
+/// Options of `carbem query`
+#[derive(Debug, Default, Args)]
+pub struct QueryArgs {
+    /// Number of complete months to query, ending with the last one
+    #[arg(long, default_value_t = 1, conflicts_with = "start")]
+    months: u32,
+
+    /// First day of the period, e.g. 2024-01-01
+    #[arg(long, requires = "end")]
+    start: Option<NaiveDate>,
+
+    /// Last day of the period, e.g. 2024-03-31
+    #[arg(long, requires = "start")]
+    end: Option<NaiveDate>,
+
+    /// Only query the accounts of this provider, e.g. azure
+    #[arg(long)]
+    provider: Option<String>,
+
+    /// Budget of the period in kg CO2eq, exceeding it exits with status 7
+    #[arg(long, value_name = "KG")]
+    budget: Option<f64>,
+
+    /// Print the provider requests, credentials redacted, without sending them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Also save the emissions as a snapshot, to compare with `diff-snapshots`
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    snapshot: Option<PathBuf>,
+
+    /// Also draw the emissions as an SVG chart
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    chart: Option<PathBuf>,
+
+    /// What the chart shows
+    #[arg(long, value_enum, default_value_t, requires = "chart")]
+    chart_by: ChartKind,
+}
+
+/// Chart drawn by `--chart`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ChartKind {
+    /// Monthly emissions stacked by service
+    #[default]
+    Service,
+
+    /// Total emissions by region
+    Region,
+}
+
+impl QueryArgs {
+    /// Period to query
+    pub fn period(&self) -> Result<TimePeriod> {
+        match (self.start, self.end) {
+            // Periods end on the last second of their last day
+            (Some(start), Some(end)) => Ok(TimePeriod {
+                start: start.and_time(NaiveTime::MIN).and_utc(),
+                end: (end + Duration::days(1)).and_time(NaiveTime::MIN).and_utc()
+                    - Duration::seconds(1),
+            }),
+            _ => carbem::emissions().last_months(self.months).period(),
+        }
+    }
+
+    /// Status of a run that found `emissions`
+    pub fn status(&self, emissions: &[CarbonEmission]) -> ExitStatus {
+        let total: f64 = emissions
+            .iter()
+            .map(|emission| emission.emissions_kg_co2eq)
+            .sum();
+        if emissions.is_empty() {
+            ExitStatus::NoData
+        } else if self.budget.is_some_and(|budget| total > budget) {
+            ExitStatus::BudgetBreach
+        } else {
+            ExitStatus::Success
+        }
+    }
+
+    // Queries of the accounts selected by `--provider`
+    fn select(&self, session: &Session) -> Result<Vec<EmissionQuery>> {
+        let period = self.period()?;
+        Ok(session
+            .queries(&period)
+            .into_iter()
+            .filter(|query| {
+                self.provider
+                    .as_ref()
+                    .is_none_or(|provider| provider.eq_ignore_ascii_case(&query.provider))
+            })
+            .collect())
+    }
+}
+
+/// Emissions of every account of the session
+pub async fn run(session: &Session, args: &QueryArgs) -> Result<Vec<CarbonEmission>> {
+    let mut emissions = Vec::new();
+    for query in args.select(session)? {
+        emissions.extend(session.client().query_emissions(&query).await?);
+    }
+    if let Some(path) = &args.snapshot {
+        EmissionDataset::new(emissions.clone())
+            .snapshot()
+            .write_to(path)
+            .await?;
+    }
+    if let Some(path) = &args.chart {
+        let svg = chart(&emissions, args.chart_by)?;
+        tokio::fs::write(path, svg).await.map_err(|e| {
+            CarbemError::Other(messages::format(
+                "cli-write-failed",
+                &[
+                    ("path", &path.display().to_string()),
+                    ("error", &e.to_string()),
+                ],
+            ))
+        })?;
+    }
+    Ok(emissions)
+}
+
+/// SVG chart of `emissions`
+pub fn chart(emissions: &[CarbonEmission], kind: ChartKind) -> Result<String> {
+    let dataset = EmissionDataset::new(emissions.to_vec());
+    let title = |id| format!("{} ({})", message(id), message("unit-kg-co2e"));
+    match kind {
+        ChartKind::Service => plot::stacked_area_by_service(
+            &dataset,
+            &ChartOptions {
+                title: Some(title("chart-monthly-by-service")),
+                ..Default::default()
+            },
+        ),
+        ChartKind::Region => plot::bar_by_region(
+            &dataset,
+            &ChartOptions {
+                title: Some(title("chart-by-region")),
+                ..Default::default()
+            },
+        ),
+    }
+}
+
+/// Requests [`run`] would send, in order
+pub async fn plan(session: &Session, args: &QueryArgs) -> Result<Vec<PlannedRequest>> {
+    let mut planned = Vec::new();
+    for mut query in args.select(session)? {
+        query.dry_run = true;
+        let result = session.client().query_emissions_with_raw(&query).await?;
+        planned.extend(result.planned_requests);
+    }
+    Ok(planned)
+}
+
+/// Render `planned` requests in `format`, one per line in a table
+pub fn render_plan(planned: &[PlannedRequest], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(planned)?),
+        OutputFormat::Table => Ok(planned
+            .iter()
+            .map(|request| match &request.body {
+                Some(body) => format!(
+                    "{} {} {} {}",
+                    request.provider, request.method, request.url, body
+                ),
+                None => format!("{} {} {}", request.provider, request.method, request.url),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")),
+        OutputFormat::Csv => Err(CarbemError::Config(message("cli-csv-unsupported"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionOptions;
+    use carbem::OutputFormat;
+    use chrono::{TimeZone, Utc};
+
+    #[tokio::test]
+    async fn test_query_output() {
+        let session = Session::open(&SessionOptions {
+            config: None,
+            profile: "default".to_string(),
+            demo: true,
+        })
+        .await
+        .unwrap();
+        let args = QueryArgs {
+            months: 1,
+            start: NaiveDate::from_ymd_opt(2024, 1, 1),
+            end: NaiveDate::from_ymd_opt(2024, 3, 31),
+            provider: Some("ibm".to_string()),
+            ..Default::default()
+        };
+        let period = args.period().unwrap();
+        assert_eq!(
+            period.end,
+            Utc.with_ymd_and_hms(2024, 3, 31, 23, 59, 59).unwrap()
+        );
+
+        let emissions = run(&session, &args).await.unwrap();
+        assert!(!emissions.is_empty());
+        assert!(emissions.iter().all(|emission| emission.provider == "ibm"));
+        let csv = OutputFormat::Csv.render(&emissions).unwrap();
+        assert_eq!(csv.lines().count(), emissions.len() + 1);
+
+        assert_eq!(args.status(&emissions), ExitStatus::Success);
+        assert_eq!(args.status(&[]), ExitStatus::NoData);
+        let args = QueryArgs {
+            budget: Some(0.001),
+            ..args
+        };
+        assert_eq!(args.status(&emissions), ExitStatus::BudgetBreach);
+
+        for kind in [ChartKind::Service, ChartKind::Region] {
+            assert!(chart(&emissions, kind).unwrap().starts_with("<svg"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_plans_requests() {
+        let path = std::env::temp_dir().join(format!("carbem-cli-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"profiles": {"default": {"azure": [{
+                "access_token": "secret-token",
+                "subscriptions": ["00000000-0000-0000-0000-000000000000"],
+                "regions": ["westeurope"]}]}}}"#,
+        )
+        .unwrap();
+        let session = Session::open(&SessionOptions {
+            config: Some(path.clone()),
+            profile: "default".to_string(),
+            demo: false,
+        })
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let args = QueryArgs {
+            months: 2,
+            dry_run: true,
+            ..Default::default()
+        };
+        let planned = plan(&session, &args).await.unwrap();
+        assert!(!planned.is_empty());
+        assert!(planned.iter().all(|request| request.provider == "azure"));
+
+        let json = render_plan(&planned, OutputFormat::Json).unwrap();
+        assert!(!json.contains("secret-token"));
+        let table = render_plan(&planned, OutputFormat::Table).unwrap();
+        assert_eq!(table.lines().count(), planned.len());
+        assert!(render_plan(&planned, OutputFormat::Csv).is_err());
+    }
+}
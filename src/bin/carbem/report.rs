@@ -0,0 +1,108 @@
+//! `carbem report`: saved report definitions of the profile
+//!
+//! `carbem report run <NAME>` runs a definition of the profile's `reports`
+//! with [`CarbemClient::run_report`](carbem::CarbemClient::run_report),
+//! printing it in its own format and delivering it to its destination.
+//! `carbem report list` lists the definitions.
+
+use carbem::report_definition::ReportDefinition;
+use carbem::{CarbemError, OutputFormat, Result};
+use clap::{Args, Subcommand};
+
+use crate::messages::message;
+use crate::session::Session;
+
+/// Options of `carbem report`
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    #[command(subcommand)]
+    command: ReportCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ReportCommand {
+    /// Run a report definition by name
+    Run {
+        /// Name of the definition, e.g. monthly-eu
+        name: String,
+    },
+
+    /// List the report definitions
+    List,
+}
+
+/// Run the command, returning its output
+pub async fn run(session: &Session, args: &ReportArgs, format: OutputFormat) -> Result<String> {
+    match &args.command {
+        ReportCommand::Run { name } => session.client().run_report(name).await,
+        ReportCommand::List => {
+            let definitions: Vec<&ReportDefinition> = session.client().reports().collect();
+            render_list(&definitions, format)
+        }
+    }
+}
+
+fn render_list(definitions: &[&ReportDefinition], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(definitions)?),
+        OutputFormat::Table => Ok(definitions
+            .iter()
+            .map(|definition| match &definition.title {
+                Some(title) => format!("{}  {}", definition.name, title),
+                None => definition.name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")),
+        OutputFormat::Csv => Err(CarbemError::Config(message("cli-csv-unsupported"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionOptions;
+
+    #[tokio::test]
+    async fn test_list_and_run_by_name() {
+        let path =
+            std::env::temp_dir().join(format!("carbem-cli-report-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"profiles": {"default": {
+                "ibm": [{"api_key": "key", "enterprise_id": "ent"}],
+                "reports": [{
+                    "name": "monthly-eu",
+                    "title": "Monthly EU",
+                    "query": {"provider": "ibm", "regions": ["eu-de"],
+                        "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"}}
+                }]}}}"#,
+        )
+        .unwrap();
+        let session = Session::open(&SessionOptions {
+            config: Some(path.clone()),
+            profile: "default".to_string(),
+            demo: false,
+        })
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let list = ReportArgs {
+            command: ReportCommand::List,
+        };
+        assert_eq!(
+            run(&session, &list, OutputFormat::Table).await.unwrap(),
+            "monthly-eu  Monthly EU"
+        );
+        let json = run(&session, &list, OutputFormat::Json).await.unwrap();
+        assert!(json.contains("\"eu-de\""));
+        assert!(run(&session, &list, OutputFormat::Csv).await.is_err());
+
+        let unknown = ReportArgs {
+            command: ReportCommand::Run {
+                name: "weekly".to_string(),
+            },
+        };
+        assert!(run(&session, &unknown, OutputFormat::Table).await.is_err());
+    }
+}
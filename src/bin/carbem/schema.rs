@@ -0,0 +1,76 @@
+//! `carbem schema`: schema of the emission records printed by `query`
+//!
+//! With `--output json`, prints the JSON Schema of
+//! [`emission_record_schema`]. The table and CSV outputs list the same
+//! columns, in output order, with their types.
+
+use carbem::output::emission_record_schema;
+use carbem::{OutputFormat, Result};
+use serde_json::Value;
+
+use crate::render_rows;
+
+const COLUMNS: [&str; 2] = ["column", "type"];
+
+/// The schema rendered in `format`
+pub fn render(format: OutputFormat) -> Result<String> {
+    let schema = emission_record_schema();
+    if format == OutputFormat::Json {
+        return Ok(serde_json::to_string_pretty(&schema)?);
+    }
+    let rows: Vec<[String; 2]> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|column| [column.to_string(), type_name(&schema["properties"][column])])
+        .collect();
+    Ok(render_rows(&COLUMNS, &rows, format))
+}
+
+// Types of a property, space-separated so CSV cells need no quoting, e.g.
+// `string null`, `string (date-time)` or `enum: strict truncate null`
+fn type_name(property: &Value) -> String {
+    if let Some(values) = property.get("enum") {
+        return format!("enum: {}", words(values).join(" "));
+    }
+    let types = words(&property["type"]).join(" ");
+    match property["format"].as_str() {
+        Some(format) => format!("{} ({})", types, format),
+        None => types,
+    }
+}
+
+// A value or each value of an array, strings unquoted
+fn words(value: &Value) -> Vec<String> {
+    let word = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    match value {
+        Value::Array(values) => values.iter().map(word).collect(),
+        value => vec![word(value)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_outputs() {
+        let json: Value = serde_json::from_str(&render(OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(json, emission_record_schema());
+
+        let csv = render(OutputFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "column,type");
+        assert_eq!(lines[1], "provider,string");
+        assert!(lines.contains(&"service,string null"));
+        assert!(lines.contains(&"period_start,string (date-time)"));
+        assert!(lines.contains(&"date_alignment,enum: expand_to_full_months truncate strict null"));
+
+        let table = render(OutputFormat::Table).unwrap();
+        assert!(table.starts_with("column                  type\n"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Client and queries of the selected profile
+
+use std::path::PathBuf;
+
+use carbem::config::ClientConfig;
+use carbem::config::profiles::ConfigFile;
+#[cfg(feature = "keyring")]
+use carbem::providers::azure::DeviceCodeFlow;
+use carbem::{
+    AzureQueryConfig, CarbemClient, CarbemError, EmissionDataset, EmissionQuery, IbmQueryConfig,
+    ProviderQueryConfig, Result, TimePeriod,
+};
+
+/// Where the client of a command comes from
+#[derive(Debug, Clone)]
+pub struct SessionOptions {
+    /// Configuration file, [`ConfigFile::default_path`] when `None`
+    pub config: Option<PathBuf>,
+
+    /// Profile of the configuration file
+    pub profile: String,
+
+    /// Use the demo client instead of a profile
+    pub demo: bool,
+}
+
+impl SessionOptions {
+    /// Path of the configuration file
+    pub fn config_path(&self) -> Result<PathBuf> {
+        self.config
+            .clone()
+            .or_else(ConfigFile::default_path)
+            .ok_or_else(|| {
+                CarbemError::Config(
+                    "no configuration directory, pass --config or set CARBEM_CONFIG".to_string(),
+                )
+            })
+    }
+
+    /// Configuration of the selected profile
+    ///
+    /// With the `keyring` feature, credentials left empty in the file are
+    /// read from the OS keyring. After `carbem auth login azure`, the stored
+    /// refresh token gives a fresh Azure access token.
+    pub async fn load_config(&self) -> Result<ClientConfig> {
+        let path = self.config_path()?;
+        #[cfg_attr(not(feature = "keyring"), allow(unused_mut))]
+        let mut config = ConfigFile::load(&path)?
+            .profiles
+            .remove(&self.profile)
+            .ok_or_else(|| {
+                CarbemError::Config(format!(
+                    "unknown profile '{}' in {}, create it with `carbem init`",
+                    self.profile,
+                    path.display()
+                ))
+            })?;
+        #[cfg(feature = "keyring")]
+        credentials_from_keyring(&mut config, &self.profile).await?;
+        Ok(config)
+    }
+}
+
+#[cfg(feature = "keyring")]
+async fn credentials_from_keyring(config: &mut ClientConfig, profile: &str) -> Result<()> {
+    use carbem::credentials::load_credential;
+
+    if config
+        .azure
+        .iter()
+        .any(|account| account.auth.access_token.is_empty())
+    {
+        let access_token = azure_token_from_keyring(profile).await?;
+        for account in &mut config.azure {
+            if account.auth.access_token.is_empty() {
+                account.auth.access_token = access_token.clone();
+            }
+        }
+    }
+    for account in &mut config.ibm {
+        if account.auth.api_key.is_empty() {
+            account.auth.api_key = load_credential("ibm", profile)?;
+        }
+    }
+    Ok(())
+}
+
+// A fresh token when a refresh token is stored, else the stored access token
+#[cfg(feature = "keyring")]
+async fn azure_token_from_keyring(profile: &str) -> Result<String> {
+    match DeviceCodeFlow::default()
+        .refresh_from_keyring(profile)
+        .await
+    {
+        Ok(token) => Ok(token.access_token),
+        // No refresh token in the keyring
+        Err(CarbemError::Config(_)) => carbem::credentials::load_credential("azure", profile),
+        Err(e) => Err(e),
+    }
+}
+
+// What one query asks for, its period set when run
+#[derive(Debug, Clone)]
+struct AccountQuery {
+    provider: &'static str,
+    route: Vec<String>,
+    regions: Vec<String>,
+    provider_config: Option<ProviderQueryConfig>,
+}
+
+/// A client with one query per account of its profile
+pub struct Session {
+    client: CarbemClient,
+    config: Option<ClientConfig>,
+    accounts: Vec<AccountQuery>,
+}
+
+impl Session {
+    /// Client and accounts of the profile selected by `options`
+    pub async fn open(options: &SessionOptions) -> Result<Self> {
+        if options.demo {
+            return Ok(Self {
+                client: CarbemClient::demo(),
+                config: None,
+                accounts: ["azure", "ibm"]
+                    .into_iter()
+                    .map(|provider| AccountQuery {
+                        provider,
+                        route: Vec::new(),
+                        regions: Vec::new(),
+                        provider_config: None,
+                    })
+                    .collect(),
+            });
+        }
+
+        let config = options.load_config().await?;
+        let route = |provider: &str, name: &Option<String>| {
+            name.iter()
+                .map(|name| format!("{}:{}", provider, name))
+                .collect()
+        };
+        let mut accounts = Vec::new();
+        for account in &config.azure {
+            accounts.push(AccountQuery {
+                provider: "azure",
+                route: route("azure", &account.name),
+                regions: account.regions.clone(),
+                provider_config: (!account.subscriptions.is_empty()).then(|| {
+                    ProviderQueryConfig::Azure(AzureQueryConfig {
+                        subscription_list: Some(account.subscriptions.clone()),
+                        ..Default::default()
+                    })
+                }),
+            });
+        }
+        for account in &config.ibm {
+            accounts.push(AccountQuery {
+                provider: "ibm",
+                route: route("ibm", &account.name),
+                regions: account.regions.clone(),
+                provider_config: account.enterprise_id.as_ref().map(|enterprise_id| {
+                    ProviderQueryConfig::Ibm(IbmQueryConfig {
+                        enterprise_id: Some(enterprise_id.clone()),
+                        ..Default::default()
+                    })
+                }),
+            });
+        }
+        Ok(Self {
+            client: CarbemClient::from_config(&config)?,
+            config: Some(config),
+            accounts,
+        })
+    }
+
+    /// The client of the profile
+    pub fn client(&self) -> &CarbemClient {
+        &self.client
+    }
+
+    /// Configuration of the profile, `None` for the demo client
+    pub fn config(&self) -> Option<&ClientConfig> {
+        self.config.as_ref()
+    }
+
+    /// One query per account, covering `period`
+    pub fn queries(&self, period: &TimePeriod) -> Vec<EmissionQuery> {
+        self.accounts
+            .iter()
+            .map(|account| EmissionQuery {
+                provider: account.provider.to_string(),
+                regions: account.regions.clone(),
+                time_period: period.clone(),
+                services: None,
+                resources: None,
+                provider_config: account.provider_config.clone(),
+                raw_response: Default::default(),
+                date_alignment: Default::default(),
+                timezone: Default::default(),
+                dry_run: false,
+                route: account.route.clone(),
+                tag_filters: Vec::new(),
+                strict: false,
+            })
+            .collect()
+    }
+
+    /// Emissions of every account over `period`
+    pub async fn fetch(&self, period: &TimePeriod) -> Result<EmissionDataset> {
+        let mut dataset = EmissionDataset::default();
+        for query in self.queries(period) {
+            dataset.extend(self.client.query_emissions(&query).await?);
+        }
+        Ok(dataset)
+    }
+}
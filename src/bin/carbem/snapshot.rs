@@ -0,0 +1,102 @@
+//! `carbem diff-snapshots`: audit of provider restatements
+//!
+//! Compares two [`DatasetSnapshot`]s, e.g. saved a month apart with
+//! `carbem query --snapshot`, and prints the data points added, removed or
+//! changed: one `+`, `-` or `~` line per change and the net change, or the
+//! [`DatasetDiff`](carbem::DatasetDiff) as JSON with `--output json`.
+
+use std::path::PathBuf;
+
+use carbem::{CarbemError, DatasetSnapshot, OutputFormat, Result};
+use clap::Args;
+
+use crate::messages::{self, message};
+
+/// Options of `carbem diff-snapshots`
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// Older snapshot
+    previous: PathBuf,
+
+    /// Newer snapshot
+    current: PathBuf,
+}
+
+/// Differences from the older snapshot to the newer, rendered in `format`
+pub async fn run(args: &DiffArgs, format: OutputFormat) -> Result<String> {
+    let previous = DatasetSnapshot::read_from(&args.previous).await?;
+    let current = DatasetSnapshot::read_from(&args.current).await?;
+    let diff = previous.dataset().diff(&current.dataset());
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(&diff)?),
+        OutputFormat::Table if diff.is_empty() => Ok(message("cli-diff-no-change")),
+        OutputFormat::Table => Ok(format!(
+            "{}{}",
+            diff,
+            messages::format(
+                "cli-diff-net-change",
+                &[("value", &format!("{:+}", diff.net_change_kg_co2eq()))],
+            )
+        )),
+        OutputFormat::Csv => Err(CarbemError::Config(message("cli-csv-unsupported"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use carbem::{CarbonEmission, EmissionDataset, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(region: &str, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: Utc.with_ymd_and_hms(2024, 1, 31, 23, 59, 59).unwrap(),
+            },
+            metadata: None,
+            lineage: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_diff_of_saved_snapshots() {
+        let dir = std::env::temp_dir();
+        let path =
+            |name: &str| dir.join(format!("carbem-cli-{}-{}.json", name, std::process::id()));
+        let args = DiffArgs {
+            previous: path("previous"),
+            current: path("current"),
+        };
+        EmissionDataset::new(vec![emission("westeurope", 10.0)])
+            .snapshot()
+            .write_to(&args.previous)
+            .await
+            .unwrap();
+        EmissionDataset::new(vec![emission("westeurope", 12.5), emission("eastus", 1.0)])
+            .snapshot()
+            .write_to(&args.current)
+            .await
+            .unwrap();
+
+        let table = run(&args, OutputFormat::Table).await.unwrap();
+        let json = run(&args, OutputFormat::Json).await.unwrap();
+        std::fs::remove_file(&args.previous).unwrap();
+        std::fs::remove_file(&args.current).unwrap();
+
+        assert_eq!(
+            table,
+            "+ azure eastus - 2024-01-01..2024-01-31: 1 kg\n\
+             ~ azure westeurope - 2024-01-01..2024-01-31: 10 -> 12.5 kg\n\
+             net change: +3.5 kg"
+        );
+        let diff: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(diff["changes"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,93 @@
+//! `carbem support-bundle`: diagnostics to attach to an issue
+//!
+//! Writes a [`SupportBundle`] of the selected profile, its configuration and
+//! recent provider exchanges redacted. With `--query`, the last full month is
+//! queried first so the bundle holds the exchanges, failed ones included.
+
+use std::path::PathBuf;
+
+use carbem::Result;
+use carbem::support::SupportBundle;
+use clap::Args;
+
+use crate::messages;
+use crate::session::{Session, SessionOptions};
+
+/// Options of `carbem support-bundle`
+#[derive(Debug, Args)]
+pub struct SupportArgs {
+    /// File to write, compressed when ending with `.gz` or `.zst`
+    #[arg(default_value = "carbem-support.json.gz")]
+    path: PathBuf,
+
+    /// Query the last full month first, to capture the provider exchanges
+    #[arg(long)]
+    query: bool,
+}
+
+/// Collect the bundle and write it, returning a line per note for the user
+pub async fn run(options: &SessionOptions, args: &SupportArgs) -> Result<Vec<String>> {
+    let mut notes = Vec::new();
+    // A broken profile is what bundles are for, so collect what is available
+    let session = match Session::open(options).await {
+        Ok(session) => Some(session),
+        Err(e) => {
+            notes.push(messages::format(
+                "cli-support-no-profile",
+                &[("error", &e.to_string())],
+            ));
+            None
+        }
+    };
+    if let Some(session) = &session
+        && args.query
+    {
+        let period = carbem::emissions().last_months(1).period()?;
+        for query in session.queries(&period) {
+            if let Err(e) = session.client().query_emissions(&query).await {
+                notes.push(messages::format(
+                    "cli-support-query-failed",
+                    &[("provider", &query.provider), ("error", &e.to_string())],
+                ));
+            }
+        }
+    }
+
+    let bundle = SupportBundle::collect(
+        session.as_ref().map(Session::client),
+        session.as_ref().and_then(Session::config),
+    )?;
+    bundle.write_to(&args.path).await?;
+    notes.push(messages::format(
+        "cli-support-written",
+        &[("path", &args.path.display().to_string())],
+    ));
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bundle_is_written() {
+        let path =
+            std::env::temp_dir().join(format!("carbem-cli-support-{}.json", std::process::id()));
+        let args = SupportArgs {
+            path: path.clone(),
+            query: true,
+        };
+        let options = SessionOptions {
+            config: None,
+            profile: "default".to_string(),
+            demo: true,
+        };
+        let notes = run(&options, &args).await.unwrap();
+        assert_eq!(notes, [format!("Wrote {}", path.display())]);
+
+        let bundle: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(bundle["carbem_version"], env!("CARGO_PKG_VERSION"));
+    }
+}
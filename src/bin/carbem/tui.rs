@@ -0,0 +1,425 @@
+//! `carbem tui`: terminal dashboard of the emissions of a profile
+//!
+//! Shows the total and monthly trend of the last months, broken down by one
+//! dimension at a time. Selecting a row filters the dashboard on its value,
+//! so operators can drill down from a provider to its regions and services.
+//! The data is fetched again periodically and on demand.
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use carbem::{CarbemError, Dimension, EmissionDataset, Result, TimePeriod};
+use clap::Args;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::messages::{self, message};
+use crate::session::Session;
+
+// Dimensions of the breakdown in tab order, with the message id of their name
+const DIMENSIONS: [(Dimension, &str); 4] = [
+    (Dimension::Provider, "dimension-provider"),
+    (Dimension::Region, "dimension-region"),
+    (Dimension::Service, "dimension-service"),
+    (Dimension::ServiceCategory, "dimension-service-category"),
+];
+
+// How long to wait for a key before checking whether to refresh
+const TICK: Duration = Duration::from_millis(250);
+
+/// Options of `carbem tui`
+#[derive(Debug, Args)]
+pub struct TuiArgs {
+    /// Number of complete months shown
+    #[arg(long, default_value_t = 12)]
+    months: u32,
+
+    /// Seconds between refreshes of the data, 0 to refresh only with `r`
+    #[arg(long, default_value_t = 300)]
+    refresh: u64,
+}
+
+/// Run the dashboard until the user quits
+pub async fn run(session: &Session, args: &TuiArgs) -> Result<()> {
+    let period = carbem::emissions().last_months(args.months).period()?;
+    let dataset = session.fetch(&period).await?;
+    let mut dashboard = Dashboard::new(dataset, period);
+
+    let mut terminal = ratatui::try_init().map_err(terminal_error)?;
+    let result = event_loop(&mut terminal, session, &mut dashboard, args).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    session: &Session,
+    dashboard: &mut Dashboard,
+    args: &TuiArgs,
+) -> Result<()> {
+    let refresh_every = (args.refresh > 0).then(|| Duration::from_secs(args.refresh));
+    let mut refreshed_at = Instant::now();
+    loop {
+        terminal
+            .draw(|frame| dashboard.draw(frame))
+            .map_err(terminal_error)?;
+
+        let key = tokio::task::block_in_place(|| -> io::Result<Option<KeyCode>> {
+            if !event::poll(TICK)? {
+                return Ok(None);
+            }
+            Ok(match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => Some(key.code),
+                _ => None,
+            })
+        })
+        .map_err(terminal_error)?;
+
+        let action = key.map_or(Action::None, |key| dashboard.handle_key(key));
+        if action == Action::Quit {
+            return Ok(());
+        }
+        let due = refresh_every.is_some_and(|every| refreshed_at.elapsed() >= every);
+        if action == Action::Refresh || due {
+            dashboard.status = Some(message("cli-tui-refreshing"));
+            terminal
+                .draw(|frame| dashboard.draw(frame))
+                .map_err(terminal_error)?;
+            // The window moves forward when a month ends
+            let fetched = match carbem::emissions().last_months(args.months).period() {
+                Ok(period) => session
+                    .fetch(&period)
+                    .await
+                    .map(|dataset| (dataset, period)),
+                Err(e) => Err(e),
+            };
+            match fetched {
+                Ok((dataset, period)) => dashboard.replace(dataset, period),
+                Err(e) => {
+                    dashboard.status = Some(messages::format(
+                        "cli-tui-refresh-failed",
+                        &[("error", &e.to_string())],
+                    ))
+                }
+            }
+            refreshed_at = Instant::now();
+        }
+    }
+}
+
+fn terminal_error(error: io::Error) -> CarbemError {
+    CarbemError::Other(messages::format(
+        "cli-terminal-error",
+        &[("error", &error.to_string())],
+    ))
+}
+
+// What the event loop does after a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    None,
+    Refresh,
+    Quit,
+}
+
+// One value of the breakdown dimension
+#[derive(Debug, Clone, PartialEq)]
+struct BreakdownRow {
+    value: String,
+    kg_co2eq: f64,
+    share: f64,
+    sparkline: String,
+}
+
+// State of the dashboard, independent of the terminal
+#[derive(Debug)]
+struct Dashboard {
+    dataset: EmissionDataset,
+    period: TimePeriod,
+    filters: Vec<(Dimension, String)>,
+    dimension: usize,
+    view: EmissionDataset,
+    rows: Vec<BreakdownRow>,
+    table: TableState,
+    status: Option<String>,
+}
+
+impl Dashboard {
+    fn new(dataset: EmissionDataset, period: TimePeriod) -> Self {
+        let mut dashboard = Self {
+            view: dataset.clone(),
+            dataset,
+            period,
+            filters: Vec::new(),
+            dimension: 0,
+            rows: Vec::new(),
+            table: TableState::default(),
+            status: None,
+        };
+        dashboard.update();
+        dashboard
+    }
+
+    // Replace the data, keeping the filters and the selected value
+    fn replace(&mut self, dataset: EmissionDataset, period: TimePeriod) {
+        self.dataset = dataset;
+        self.period = period;
+        self.status = None;
+        self.update();
+    }
+
+    // Recompute the filtered view and its breakdown
+    fn update(&mut self) {
+        let selected = self.selected().map(|row| row.value.clone());
+        self.view = self.dataset.clone();
+        for (dimension, value) in &self.filters {
+            self.view = self.view.filter_dimension(*dimension, value);
+        }
+
+        let dimension = DIMENSIONS[self.dimension].0;
+        let total = self.view.total_kg_co2eq();
+        self.rows = self
+            .view
+            .group_by_dimension(dimension)
+            .into_iter()
+            .map(|(value, kg_co2eq)| BreakdownRow {
+                sparkline: self
+                    .view
+                    .filter_dimension(dimension, &value)
+                    .monthly_sparkline(),
+                share: if total == 0.0 {
+                    0.0
+                } else {
+                    kg_co2eq / total * 100.0
+                },
+                value,
+                kg_co2eq,
+            })
+            .collect();
+        self.rows.sort_by(|a, b| b.kg_co2eq.total_cmp(&a.kg_co2eq));
+
+        let index = selected
+            .and_then(|value| self.rows.iter().position(|row| row.value == value))
+            .or((!self.rows.is_empty()).then_some(0));
+        self.table.select(index);
+    }
+
+    fn selected(&self) -> Option<&BreakdownRow> {
+        self.table.selected().and_then(|index| self.rows.get(index))
+    }
+
+    fn handle_key(&mut self, key: KeyCode) -> Action {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('r') => return Action::Refresh,
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.show_dimension((self.dimension + 1) % DIMENSIONS.len())
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.show_dimension((self.dimension + DIMENSIONS.len() - 1) % DIMENSIONS.len())
+            }
+            KeyCode::Enter => self.drill_down(),
+            KeyCode::Backspace => {
+                if let Some((dimension, _)) = self.filters.pop() {
+                    self.show_dimension(dimension_index(dimension));
+                }
+            }
+            KeyCode::Char('c') => {
+                self.filters.clear();
+                self.update();
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn show_dimension(&mut self, dimension: usize) {
+        self.dimension = dimension;
+        self.table.select(None);
+        self.update();
+    }
+
+    // Filter on the selected value, then break down by the next unfiltered dimension
+    fn drill_down(&mut self) {
+        let Some(value) = self.selected().map(|row| row.value.clone()) else {
+            return;
+        };
+        let dimension = DIMENSIONS[self.dimension].0;
+        self.filters.retain(|(filtered, _)| *filtered != dimension);
+        self.filters.push((dimension, value));
+        let next = (1..DIMENSIONS.len())
+            .map(|step| (self.dimension + step) % DIMENSIONS.len())
+            .find(|&index| {
+                !self
+                    .filters
+                    .iter()
+                    .any(|(filtered, _)| *filtered == DIMENSIONS[index].0)
+            })
+            .unwrap_or(self.dimension);
+        self.show_dimension(next);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [summary, tabs, table, help] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let filters = if self.filters.is_empty() {
+            message("cli-tui-no-filter")
+        } else {
+            self.filters
+                .iter()
+                .map(|(dimension, value)| {
+                    format!(
+                        "{}={}",
+                        message(DIMENSIONS[dimension_index(*dimension)].1),
+                        value
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" › ")
+        };
+        let title = format!(
+            " carbem · {} ",
+            messages::format(
+                "report-period-range",
+                &[
+                    ("start", &self.period.start.format("%Y-%m").to_string()),
+                    ("end", &self.period.end.format("%Y-%m").to_string()),
+                ],
+            )
+        );
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    "{} {:.3} {}   {}",
+                    message("cli-tui-total"),
+                    self.view.total_kg_co2eq(),
+                    message("unit-kg-co2e"),
+                    self.view.monthly_sparkline()
+                )),
+                Line::from(format!("{}: {}", message("cli-tui-filters"), filters)),
+            ])
+            .block(Block::bordered().title(title)),
+            summary,
+        );
+
+        frame.render_widget(
+            Tabs::new(DIMENSIONS.iter().map(|(_, name)| message(name)))
+                .select(self.dimension)
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            tabs,
+        );
+
+        let rows = self.rows.iter().map(|row| {
+            Row::new(vec![
+                row.value.clone(),
+                format!("{:.3}", row.kg_co2eq),
+                format!("{:.1}%", row.share),
+                row.sparkline.clone(),
+            ])
+        });
+        let widths = [
+            Constraint::Fill(1),
+            Constraint::Length(14),
+            Constraint::Length(7),
+            Constraint::Length(24),
+        ];
+        frame.render_stateful_widget(
+            Table::new(rows, widths)
+                .header(
+                    Row::new([
+                        message("cli-tui-value"),
+                        message("unit-kg-co2e"),
+                        message("report-share"),
+                        message("cli-tui-trend"),
+                    ])
+                    .style(Style::new().add_modifier(Modifier::BOLD)),
+                )
+                .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+                .block(Block::bordered()),
+            table,
+            &mut self.table,
+        );
+
+        let status = self
+            .status
+            .clone()
+            .unwrap_or_else(|| message("cli-tui-help"));
+        frame.render_widget(Paragraph::new(status), help);
+    }
+}
+
+fn dimension_index(dimension: Dimension) -> usize {
+    DIMENSIONS
+        .iter()
+        .position(|(candidate, _)| *candidate == dimension)
+        .expect("every dimension has a tab")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionOptions;
+    use ratatui::backend::TestBackend;
+
+    #[tokio::test]
+    async fn test_drill_down_and_back() {
+        let session = Session::open(&SessionOptions {
+            config: None,
+            profile: "default".to_string(),
+            demo: true,
+        })
+        .await
+        .unwrap();
+        let period = carbem::emissions().last_months(6).period().unwrap();
+        let dataset = session.fetch(&period).await.unwrap();
+        let total = dataset.total_kg_co2eq();
+        let mut dashboard = Dashboard::new(dataset, period);
+
+        // Providers, largest first, each with one sparkline character per month
+        assert_eq!(dashboard.rows.len(), 2);
+        assert!(dashboard.rows[0].kg_co2eq >= dashboard.rows[1].kg_co2eq);
+        assert_eq!(dashboard.rows[0].sparkline.chars().count(), 6);
+        let shares: f64 = dashboard.rows.iter().map(|row| row.share).sum();
+        assert!((shares - 100.0).abs() < 1e-6);
+
+        // Enter filters on the second provider and shows its regions
+        dashboard.handle_key(KeyCode::Down);
+        let provider = dashboard.selected().unwrap().clone();
+        dashboard.handle_key(KeyCode::Enter);
+        assert_eq!(
+            dashboard.filters,
+            vec![(Dimension::Provider, provider.value.clone())]
+        );
+        assert_eq!(DIMENSIONS[dashboard.dimension].0, Dimension::Region);
+        assert!((dashboard.view.total_kg_co2eq() - provider.kg_co2eq).abs() < 1e-6);
+        let regions: f64 = dashboard.rows.iter().map(|row| row.kg_co2eq).sum();
+        assert!((regions - provider.kg_co2eq).abs() < 1e-6);
+
+        // Backspace removes the filter and goes back to the providers
+        dashboard.handle_key(KeyCode::Backspace);
+        assert!(dashboard.filters.is_empty());
+        assert_eq!(DIMENSIONS[dashboard.dimension].0, Dimension::Provider);
+        assert!((dashboard.view.total_kg_co2eq() - total).abs() < 1e-6);
+
+        let mut terminal = ratatui::Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("Filters: none"));
+        assert!(screen.contains(&provider.value));
+
+        assert_eq!(dashboard.handle_key(KeyCode::Char('r')), Action::Refresh);
+        assert_eq!(dashboard.handle_key(KeyCode::Char('q')), Action::Quit);
+    }
+}
@@ -0,0 +1,230 @@
+//! Signed report bundles (requires the `signing` feature)
+//!
+//! A [`ReportBundle`] packages emissions with the metadata needed to interpret
+//! them and their [content hash](EmissionDataset::content_hash). Signing it
+//! with an Ed25519 key lets recipients check that the numbers are the ones
+//! carbem retrieved and that nothing was changed since.
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, Verifier};
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::EmissionDataset;
+use crate::error::{CarbemError, Result};
+use crate::models::CarbonEmission;
+
+/// Methodology statement used unless another one is given
+pub const DEFAULT_METHODOLOGY: &str = "Emissions as reported by the cloud providers' carbon \
+    footprint APIs (Azure Carbon Optimization, IBM Cloud Carbon Calculator), retrieved by carbem \
+    without modification. Scope and allocation rules are those of each provider.";
+
+/// Context of the emissions in a bundle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleMetadata {
+    /// Title of the report
+    pub title: String,
+
+    /// When the bundle was produced
+    pub generated_at: DateTime<Utc>,
+
+    /// Version of carbem that produced the bundle
+    pub carbem_version: String,
+
+    /// How the emissions were obtained and should be interpreted
+    pub methodology: String,
+}
+
+/// Emissions with their metadata and content hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportBundle {
+    /// Context of the emissions
+    pub metadata: BundleMetadata,
+
+    /// Content hash of `emissions`
+    pub content_hash: String,
+
+    /// The emissions
+    pub emissions: Vec<CarbonEmission>,
+}
+
+// What the signature covers: the data through its hash, and the metadata
+#[derive(Serialize)]
+struct SignedContent<'a> {
+    domain: &'static str,
+    metadata: &'a BundleMetadata,
+    content_hash: &'a str,
+}
+
+impl ReportBundle {
+    /// Bundle the emissions of `dataset` with the default methodology
+    pub fn new(title: impl Into<String>, dataset: &EmissionDataset) -> Self {
+        Self {
+            metadata: BundleMetadata {
+                title: title.into(),
+                generated_at: Utc::now(),
+                carbem_version: env!("CARGO_PKG_VERSION").to_string(),
+                methodology: DEFAULT_METHODOLOGY.to_string(),
+            },
+            content_hash: dataset.content_hash(),
+            emissions: dataset.emissions().to_vec(),
+        }
+    }
+
+    /// Replace the methodology statement
+    pub fn with_methodology(mut self, methodology: impl Into<String>) -> Self {
+        self.metadata.methodology = methodology.into();
+        self
+    }
+
+    /// Sign the bundle with `key`
+    pub fn sign(self, key: &SigningKey) -> SignedBundle {
+        let signature = key.sign(&self.signed_content());
+        SignedBundle {
+            public_key: to_hex(key.verifying_key().as_bytes()),
+            signature: to_hex(&signature.to_bytes()),
+            bundle: self,
+        }
+    }
+
+    fn signed_content(&self) -> Vec<u8> {
+        serde_json::to_vec(&SignedContent {
+            domain: "carbem-report-bundle-v1",
+            metadata: &self.metadata,
+            content_hash: &self.content_hash,
+        })
+        .expect("bundle metadata always serializes")
+    }
+}
+
+/// A report bundle with an Ed25519 signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedBundle {
+    /// The signed bundle
+    #[serde(flatten)]
+    pub bundle: ReportBundle,
+
+    /// Public key of the signer, hex-encoded
+    pub public_key: String,
+
+    /// Signature of the bundle, hex-encoded
+    pub signature: String,
+}
+
+impl SignedBundle {
+    /// Check the bundle against the public key of a trusted signer
+    ///
+    /// Fails when the emissions do not match the content hash, when the bundle
+    /// was signed by another key or when anything was changed after signing.
+    pub fn verify(&self, trusted_key: &VerifyingKey) -> Result<()> {
+        if to_hex(trusted_key.as_bytes()) != self.public_key {
+            return Err(CarbemError::Auth(
+                "bundle was signed by an untrusted key".to_string(),
+            ));
+        }
+        self.verify_integrity()
+    }
+
+    /// Check the bundle against the public key it embeds
+    ///
+    /// This only proves the bundle was not changed since it was signed, not
+    /// who signed it; prefer [`verify`](Self::verify) with a trusted key.
+    pub fn verify_integrity(&self) -> Result<()> {
+        let content_hash = EmissionDataset::new(self.bundle.emissions.clone()).content_hash();
+        if content_hash != self.bundle.content_hash {
+            return Err(CarbemError::Auth(
+                "bundle emissions do not match their content hash".to_string(),
+            ));
+        }
+
+        let public_key: [u8; 32] = from_hex(&self.public_key)?;
+        let signature: [u8; 64] = from_hex(&self.signature)?;
+        let key = VerifyingKey::from_bytes(&public_key)
+            .map_err(|e| CarbemError::Auth(format!("invalid bundle public key: {}", e)))?;
+        key.verify(
+            &self.bundle.signed_content(),
+            &Signature::from_bytes(&signature),
+        )
+        .map_err(|_| CarbemError::Auth("invalid bundle signature".to_string()))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Result<[u8; N]> {
+    let invalid = || CarbemError::Auth(format!("invalid hex value of {} bytes", N));
+    if hex.len() != N * 2 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; N];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::TimeZone;
+
+    fn dataset() -> EmissionDataset {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        EmissionDataset::new(vec![CarbonEmission {
+            provider: "ibm".to_string(),
+            region: "dallas".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 12.5,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }])
+    }
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signed = ReportBundle::new("FY2024", &dataset()).sign(&key(1));
+        assert!(signed.verify(&key(1).verifying_key()).is_ok());
+        assert!(signed.verify(&key(2).verifying_key()).is_err());
+
+        // The bundle survives a JSON round trip
+        let json = serde_json::to_string(&signed).unwrap();
+        let parsed: SignedBundle = serde_json::from_str(&json).unwrap();
+        assert!(parsed.verify(&key(1).verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let signed = ReportBundle::new("FY2024", &dataset())
+            .with_methodology("Location-based")
+            .sign(&key(1));
+
+        let mut changed_data = signed.clone();
+        changed_data.bundle.emissions[0].emissions_kg_co2eq = 1.0;
+        assert!(changed_data.verify_integrity().is_err());
+
+        let mut changed_hash = changed_data.clone();
+        changed_hash.bundle.content_hash =
+            EmissionDataset::new(changed_hash.bundle.emissions.clone()).content_hash();
+        assert!(changed_hash.verify_integrity().is_err());
+
+        let mut changed_metadata = signed.clone();
+        changed_metadata.bundle.metadata.methodology = "Market-based".to_string();
+        assert!(changed_metadata.verify_integrity().is_err());
+
+        let mut invalid_signature = signed;
+        invalid_signature.signature = "zz".to_string();
+        assert!(invalid_signature.verify_integrity().is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Capture of recent provider request/response exchanges
+//!
+//! Providers keep their last exchanges in memory, redacted, so a support
+//! bundle can show exactly what was sent and received when a query failed.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::PlannedRequest;
+
+/// Number of exchanges kept per provider
+const CAPACITY: usize = 20;
+
+/// Response bodies are truncated to this many bytes
+const MAX_RESPONSE_LEN: usize = 64 * 1024;
+
+/// A provider request and its outcome, with credentials redacted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedExchange {
+    /// When the request was sent
+    pub sent_at: DateTime<Utc>,
+
+    /// The request as sent
+    pub request: PlannedRequest,
+
+    /// Optional: HTTP status of the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+
+    /// Optional: response body, truncated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+
+    /// Optional: transport error when no response was received
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CapturedExchange {
+    /// Exchange for `request` sent now, without outcome yet
+    pub(crate) fn new(request: PlannedRequest) -> Self {
+        Self {
+            sent_at: Utc::now(),
+            request,
+            status: None,
+            response: None,
+            error: None,
+        }
+    }
+
+    /// Record the response, already redacted
+    pub(crate) fn with_response(mut self, status: u16, body: &str) -> Self {
+        self.status = Some(status);
+        self.response = Some(truncate(body));
+        self
+    }
+
+    /// Record a transport error, already redacted
+    pub(crate) fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+}
+
+fn truncate(body: &str) -> String {
+    if body.len() <= MAX_RESPONSE_LEN {
+        return body.to_string();
+    }
+    let mut end = MAX_RESPONSE_LEN;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &body[..end])
+}
+
+/// Last exchanges of a provider, shared by its clones
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExchangeLog {
+    exchanges: Arc<Mutex<VecDeque<CapturedExchange>>>,
+}
+
+impl ExchangeLog {
+    /// Record an exchange, dropping the oldest one when full
+    pub(crate) fn record(&self, exchange: CapturedExchange) {
+        let mut exchanges = self
+            .exchanges
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if exchanges.len() == CAPACITY {
+            exchanges.pop_front();
+        }
+        exchanges.push_back(exchange);
+    }
+
+    /// Recorded exchanges, oldest first
+    pub(crate) fn snapshot(&self) -> Vec<CapturedExchange> {
+        self.exchanges
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn request(url: &str) -> PlannedRequest {
+        PlannedRequest {
+            provider: "ibm".to_string(),
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: BTreeMap::new(),
+            body: None,
+        }
+    }
+
+    #[test]
+    fn test_log_keeps_last_exchanges() {
+        let log = ExchangeLog::default();
+        for i in 0..CAPACITY + 2 {
+            log.clone().record(CapturedExchange::new(request(&format!(
+                "https://api/{}",
+                i
+            ))));
+        }
+
+        let exchanges = log.snapshot();
+        assert_eq!(exchanges.len(), CAPACITY);
+        assert_eq!(exchanges[0].request.url, "https://api/2");
+    }
+
+    #[test]
+    fn test_truncate_response() {
+        let body = "é".repeat(MAX_RESPONSE_LEN);
+        let exchange = CapturedExchange::new(request("https://api")).with_response(500, &body);
+        let response = exchange.response.unwrap();
+        assert!(response.len() <= MAX_RESPONSE_LEN + 3);
+        assert!(response.ends_with("..."));
+        assert_eq!(exchange.status, Some(500));
+    }
+}
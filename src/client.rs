@@ -1,21 +1,87 @@
 //! Type-safe builder pattern for CarbemClient
 
+use crate::audit::{self, AuditLog, AuditTransport};
+use crate::auth::TokenCache;
+use crate::capture::CapturedExchange;
+use crate::coalesce::Coalescer;
+use crate::config::ClientConfig;
+#[cfg(feature = "keyring")]
+use crate::config::profiles::DEFAULT_PROFILE;
+use crate::config::validate::{self, ValidationReport};
+use crate::correlation;
 use crate::error::{CarbemError, Result};
-use crate::models::{CarbonEmission, EmissionQuery};
-use crate::providers::CarbonProvider;
-use crate::providers::azure::AzureConfig;
+use crate::estimate::QueryEstimate;
+use crate::hedge::{HedgePolicy, HedgedTransport, Hedger};
+use crate::logging::{debug, warn};
+use crate::models::{CarbonEmission, EmissionQuery, EmissionResult, sort_emissions};
+use crate::providers::DynCarbonProvider;
+use crate::providers::azure::{AzureConfig, AzureProvider};
+use crate::providers::config::ProviderQueryConfig;
+use crate::providers::demo::DemoProvider;
 use crate::providers::ibm::IbmConfig;
 use crate::providers::registry::ProviderRegistry;
+use crate::quota::{Quota, QuotaStatus, QuotaTracker, QuotaTransport};
+use crate::report_definition::ReportDefinition;
+use crate::sandbox::{SANDBOX_CREDENTIAL, SANDBOX_ENV, SandboxTransport, sandbox_enabled};
+use crate::schema::SchemaWarning;
+use crate::spill::{self, SpillOptions, SpilledEmissions};
+use crate::subscription::EmissionEvents;
+use crate::taxonomy::categorize_emissions;
+use crate::transport::{
+    self, HeaderTransport, LimitedTransport, ProxyConfig, ReqwestTransport, Transport,
+};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, USER_AGENT};
 use serde_json::json;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Anything answering emission queries: live providers or stored data
+///
+/// Code written against this trait can switch between a [`CarbemClient`] and
+/// a [`StoredClient`](crate::store::StoredClient) without other changes.
+#[async_trait]
+pub trait EmissionSource: Send + Sync {
+    /// Query emissions, in the canonical order of [`sort_emissions`]
+    async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>>;
+}
 
 /// Type-safe builder for CarbemClient
 pub struct CarbemClientBuilder<State> {
     registry: ProviderRegistry,
-    providers: Vec<Box<dyn CarbonProvider + Send + Sync>>,
+    providers: Vec<Box<dyn DynCarbonProvider>>,
+    instances: HashMap<String, usize>,
+    lenient_parsing: bool,
+    coalesce_queries: bool,
+    transport: Option<Arc<dyn Transport>>,
+    limits: ConcurrencyLimits,
+    audit_log: Option<Arc<AuditLog>>,
+    headers: RequestHeaders,
+    query_defaults: HashMap<String, ProviderQueryConfig>,
     _state: PhantomData<State>,
 }
 
+// Headers added to provider requests when building
+#[derive(Default)]
+struct RequestHeaders {
+    // Application named in the User-Agent
+    app: Option<String>,
+    per_provider: HashMap<String, HeaderMap>,
+}
+
+// Request limits applied to provider transports when building
+#[derive(Default)]
+struct ConcurrencyLimits {
+    max_in_flight_requests: Option<usize>,
+    per_provider: HashMap<String, usize>,
+    quotas: HashMap<String, Quota>,
+    hedging: HashMap<String, HedgePolicy>,
+}
+
 /// Builder state: No providers configured
 pub struct Empty;
 
@@ -29,6 +95,14 @@ impl CarbemClientBuilder<Empty> {
         Self {
             registry: ProviderRegistry::new(),
             providers: Vec::new(),
+            instances: HashMap::new(),
+            lenient_parsing: false,
+            coalesce_queries: false,
+            transport: None,
+            limits: ConcurrencyLimits::default(),
+            audit_log: None,
+            headers: RequestHeaders::default(),
+            query_defaults: HashMap::new(),
             _state: PhantomData,
         }
     }
@@ -41,14 +115,48 @@ impl CarbemClientBuilder<Empty> {
         Ok(CarbemClientBuilder {
             registry: self.registry,
             providers: self.providers,
+            instances: self.instances,
+            lenient_parsing: self.lenient_parsing,
+            coalesce_queries: self.coalesce_queries,
+            transport: self.transport,
+            limits: self.limits,
+            audit_log: self.audit_log,
+            headers: self.headers,
+            query_defaults: self.query_defaults,
+            _state: PhantomData,
+        })
+    }
+
+    /// Add Azure provider taking access tokens from `cache`, refreshed once for concurrent queries
+    pub fn with_azure_token_cache(
+        mut self,
+        cache: Arc<TokenCache>,
+    ) -> Result<CarbemClientBuilder<Configured>> {
+        self.providers.push(azure_with_token_cache(cache)?);
+
+        Ok(CarbemClientBuilder {
+            registry: self.registry,
+            providers: self.providers,
+            instances: self.instances,
+            lenient_parsing: self.lenient_parsing,
+            coalesce_queries: self.coalesce_queries,
+            transport: self.transport,
+            limits: self.limits,
+            audit_log: self.audit_log,
+            headers: self.headers,
+            query_defaults: self.query_defaults,
             _state: PhantomData,
         })
     }
 
     /// Add Azure provider from environment
+    ///
+    /// In [sandbox mode](crate::sandbox), a missing token falls back to
+    /// [`SANDBOX_CREDENTIAL`].
     pub fn with_azure_from_env(self) -> Result<CarbemClientBuilder<Configured>> {
         let access_token = std::env::var("AZURE_TOKEN")
             .or_else(|_| std::env::var("CARBEM_AZURE_ACCESS_TOKEN"))
+            .or_else(|e| sandbox_credential().ok_or(e))
             .map_err(|_| {
                 CarbemError::Config(
                     "AZURE_TOKEN or CARBEM_AZURE_ACCESS_TOKEN environment variable not set"
@@ -68,14 +176,26 @@ impl CarbemClientBuilder<Empty> {
         Ok(CarbemClientBuilder {
             registry: self.registry,
             providers: self.providers,
+            instances: self.instances,
+            lenient_parsing: self.lenient_parsing,
+            coalesce_queries: self.coalesce_queries,
+            transport: self.transport,
+            limits: self.limits,
+            audit_log: self.audit_log,
+            headers: self.headers,
+            query_defaults: self.query_defaults,
             _state: PhantomData,
         })
     }
 
     /// Add IBM provider from environment
+    ///
+    /// In [sandbox mode](crate::sandbox), a missing API key falls back to
+    /// [`SANDBOX_CREDENTIAL`].
     pub fn with_ibm_from_env(self) -> Result<CarbemClientBuilder<Configured>> {
         let api_key = std::env::var("IBM_API_KEY")
             .or_else(|_| std::env::var("CARBEM_IBM_API_KEY"))
+            .or_else(|e| sandbox_credential().ok_or(e))
             .map_err(|_| {
                 CarbemError::Config(
                     "IBM_API_KEY or CARBEM_IBM_API_KEY environment variable not set".to_string(),
@@ -85,6 +205,134 @@ impl CarbemClientBuilder<Empty> {
         let config = IbmConfig { api_key };
         self.with_ibm(config)
     }
+
+    /// Add Azure provider with the access token stored in the OS keyring for the default profile
+    #[cfg(feature = "keyring")]
+    pub fn with_azure_from_keyring(self) -> Result<CarbemClientBuilder<Configured>> {
+        let access_token = crate::credentials::load_credential("azure", DEFAULT_PROFILE)?;
+        self.with_azure(AzureConfig { access_token })
+    }
+
+    /// Add IBM provider with the API key stored in the OS keyring for the default profile
+    #[cfg(feature = "keyring")]
+    pub fn with_ibm_from_keyring(self) -> Result<CarbemClientBuilder<Configured>> {
+        let api_key = crate::credentials::load_credential("ibm", DEFAULT_PROFILE)?;
+        self.with_ibm(IbmConfig { api_key })
+    }
+}
+
+impl<State> CarbemClientBuilder<State> {
+    /// Fill missing fields in provider responses with defaults instead of failing
+    ///
+    /// Differences from the expected schema are reported by
+    /// [`CarbemClient::schema_warnings`] whether or not this is enabled.
+    pub fn with_lenient_parsing(mut self) -> Self {
+        self.lenient_parsing = true;
+        self
+    }
+
+    /// Share the result of a running query with identical concurrent queries
+    ///
+    /// A query identical to one already running, i.e. with the same
+    /// [`query_hash`](crate::audit::query_hash), waits for it and gets a copy
+    /// of its result instead of calling the providers again. Only
+    /// [`CarbemClient::query_emissions`] is coalesced; clones of the client
+    /// share running queries.
+    pub fn with_query_coalescing(mut self) -> Self {
+        self.coalesce_queries = true;
+        self
+    }
+
+    /// Send provider requests through `transport` instead of the default reqwest client
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Send provider requests through `proxy`, e.g. one requiring authentication
+    ///
+    /// Without it, the `HTTPS_PROXY` and `NO_PROXY` environment variables apply.
+    pub fn with_proxy(self, proxy: &ProxyConfig) -> Result<Self> {
+        Ok(self.with_transport(Arc::new(ReqwestTransport::with_proxy(proxy)?)))
+    }
+
+    /// Append a record of every provider request to `log`
+    pub fn with_audit_log(mut self, log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(log);
+        self
+    }
+
+    /// Name the application in the `User-Agent` of provider requests
+    ///
+    /// `app` is a product token such as `emissions-dashboard/2.1`, optionally
+    /// followed by a contact, e.g. `emissions-dashboard/2.1 (ops@example.com)`.
+    /// Requests are sent with `<app> carbem/<version>` instead of
+    /// [`DEFAULT_USER_AGENT`](transport::DEFAULT_USER_AGENT) alone.
+    pub fn with_app_identifier(mut self, app: &str) -> Result<Self> {
+        transport::header(USER_AGENT.as_str(), &transport::user_agent(Some(app)))?;
+        self.headers.app = Some(app.to_string());
+        Ok(self)
+    }
+
+    /// Send the header `name` with `value` on every request to `provider`, e.g. `azure`
+    ///
+    /// For headers some provider APIs require or use for support triage, such
+    /// as a client request ID. Headers set by the provider itself are kept.
+    pub fn with_provider_header(mut self, provider: &str, name: &str, value: &str) -> Result<Self> {
+        let (name, value) = transport::header(name, value)?;
+        self.headers
+            .per_provider
+            .entry(provider.to_string())
+            .or_default()
+            .insert(name, value);
+        Ok(self)
+    }
+
+    /// Complete the provider configuration of queries to its provider with `defaults`
+    ///
+    /// E.g. the IBM `enterprise_id` is set once here and queries only give
+    /// what changes, such as `group_by`; a query without provider
+    /// configuration uses `defaults` as is. Fields set by a query replace the
+    /// defaults, unset ones keep them, see [`ProviderQueryConfig::merged_over`].
+    /// Setting defaults again for the same provider replaces them.
+    pub fn with_query_defaults(mut self, defaults: ProviderQueryConfig) -> Self {
+        self.query_defaults
+            .insert(defaults.provider().to_string(), defaults);
+        self
+    }
+
+    /// Allow at most `limit` provider requests in flight at once, across all providers
+    pub fn with_max_in_flight_requests(mut self, limit: usize) -> Self {
+        self.limits.max_in_flight_requests = Some(limit.max(1));
+        self
+    }
+
+    /// Allow at most `limit` requests in flight at once to `provider`, e.g. `azure`
+    ///
+    /// Accounts of the same provider share the limit.
+    pub fn with_provider_concurrency(mut self, provider: &str, limit: usize) -> Self {
+        self.limits
+            .per_provider
+            .insert(provider.to_string(), limit.max(1));
+        self
+    }
+
+    /// Limit the calls to `provider`, e.g. `azure`, per hour and per day
+    ///
+    /// Accounts of the same provider share the quota. See [`quota`](crate::quota).
+    pub fn with_provider_quota(mut self, provider: &str, quota: Quota) -> Self {
+        self.limits.quotas.insert(provider.to_string(), quota);
+        self
+    }
+
+    /// Send a second attempt of slow requests to `provider`, e.g. `ibm`
+    ///
+    /// Accounts of the same provider share the latencies and caps of `policy`.
+    /// See [`hedge`](crate::hedge).
+    pub fn with_provider_hedging(mut self, provider: &str, policy: HedgePolicy) -> Self {
+        self.limits.hedging.insert(provider.to_string(), policy);
+        self
+    }
 }
 
 impl CarbemClientBuilder<Configured> {
@@ -95,6 +343,12 @@ impl CarbemClientBuilder<Configured> {
         Ok(self)
     }
 
+    /// Add another Azure provider taking access tokens from `cache`
+    pub fn with_azure_token_cache(mut self, cache: Arc<TokenCache>) -> Result<Self> {
+        self.providers.push(azure_with_token_cache(cache)?);
+        Ok(self)
+    }
+
     /// Add another IBM provider
     pub fn with_ibm(mut self, config: IbmConfig) -> Result<Self> {
         let provider = self.registry.create_provider("ibm", json!(config))?;
@@ -116,23 +370,173 @@ impl CarbemClientBuilder<Configured> {
         Ok(self)
     }
 
+    /// Name the provider added last, so queries can route to it as `provider:name`
+    ///
+    /// E.g. two Azure providers reading the same tenant with different
+    /// credentials, named `prod` and `dr`, are addressed by a query route of
+    /// `["azure:prod", "azure:dr"]`.
+    pub fn with_instance_name(mut self, name: &str) -> Result<Self> {
+        if name.is_empty() || name.contains(':') {
+            return Err(CarbemError::Config(format!(
+                "invalid instance name '{}': must be non-empty and contain no ':'",
+                name
+            )));
+        }
+        let index = self.providers.len() - 1;
+        let key = format!("{}:{}", self.providers[index].name(), name);
+        if self.instances.contains_key(&key) {
+            return Err(CarbemError::Config(format!(
+                "provider instance '{}' is already defined",
+                key
+            )));
+        }
+        self.instances.insert(key, index);
+        Ok(self)
+    }
+
     /// Build the final client (only available when configured)
-    pub fn build(self) -> CarbemClient {
+    pub fn build(mut self) -> CarbemClient {
+        for provider in &mut self.providers {
+            provider.set_lenient_parsing(self.lenient_parsing);
+            if let Some(transport) = &self.transport {
+                provider.set_transport(transport.clone());
+            }
+        }
+        let quotas = self.apply_transports();
         CarbemClient {
             providers: self.providers,
+            instances: self.instances,
+            reports: BTreeMap::new(),
+            quotas,
+            coalescer: self
+                .coalesce_queries
+                .then(|| Arc::new(Coalescer::default())),
+            query_defaults: self.query_defaults,
         }
     }
+
+    // Route every provider through a transport hedging slow requests,
+    // counting calls against its quota, waiting for the limits, recording its requests in the audit log,
+    // then adding the configured headers. Returns the quota trackers.
+    fn apply_transports(&mut self) -> Vec<Arc<QuotaTracker>> {
+        let limits = &self.limits;
+        let limited = limits.max_in_flight_requests.is_some() || !limits.per_provider.is_empty();
+        let global = limits
+            .max_in_flight_requests
+            .map(|limit| Arc::new(Semaphore::new(limit)));
+        let per_provider: HashMap<&str, Arc<Semaphore>> = limits
+            .per_provider
+            .iter()
+            .map(|(name, limit)| (name.as_str(), Arc::new(Semaphore::new(*limit))))
+            .collect();
+        let quotas: BTreeMap<&str, Arc<QuotaTracker>> = limits
+            .quotas
+            .iter()
+            .map(|(name, quota)| (name.as_str(), Arc::new(QuotaTracker::new(name, *quota))))
+            .collect();
+        let hedgers: HashMap<&str, Arc<Hedger>> = limits
+            .hedging
+            .iter()
+            .map(|(name, policy)| (name.as_str(), Arc::new(Hedger::new(*policy))))
+            .collect();
+        // Sandbox mode replaces the network, configured transports included
+        let base = if sandbox_enabled() {
+            debug!(
+                "{} is set, providers answer from the sandbox fixtures",
+                SANDBOX_ENV
+            );
+            Arc::new(SandboxTransport::new())
+        } else {
+            self.transport
+                .clone()
+                .unwrap_or_else(|| Arc::new(ReqwestTransport::default()))
+        };
+
+        for provider in &mut self.providers {
+            let mut headers = HeaderTransport::new(base.clone())
+                .with_correlation_header(correlation::header_name(provider.name()));
+            if let Some(app) = &self.headers.app {
+                let (name, value) =
+                    transport::header(USER_AGENT.as_str(), &transport::user_agent(Some(app)))
+                        .expect("application identifier was validated");
+                headers = headers.with_header(name, value);
+            }
+            for (name, value) in self
+                .headers
+                .per_provider
+                .get(provider.name())
+                .into_iter()
+                .flatten()
+            {
+                headers = headers.with_header(name.clone(), value.clone());
+            }
+            let mut transport: Arc<dyn Transport> = Arc::new(headers);
+            if let Some(log) = &self.audit_log {
+                transport = Arc::new(AuditTransport::new(transport, provider.name(), log.clone()));
+            }
+            if limited {
+                let mut limited = LimitedTransport::new(transport);
+                if let Some(semaphore) = per_provider.get(provider.name()) {
+                    limited = limited.with_limit(semaphore.clone());
+                }
+                if let Some(semaphore) = &global {
+                    limited = limited.with_limit(semaphore.clone());
+                }
+                transport = Arc::new(limited);
+            }
+            if let Some(tracker) = quotas.get(provider.name()) {
+                transport = Arc::new(QuotaTransport::new(transport, tracker.clone()));
+            }
+            if let Some(hedger) = hedgers.get(provider.name()) {
+                let mut hedged = HedgedTransport::new(transport, hedger.clone());
+                if let Some(semaphore) = per_provider.get(provider.name()) {
+                    hedged = hedged.with_limit(semaphore.clone());
+                }
+                if let Some(semaphore) = &global {
+                    hedged = hedged.with_limit(semaphore.clone());
+                }
+                if let Some(tracker) = quotas.get(provider.name()) {
+                    hedged = hedged.with_quota(tracker.clone());
+                }
+                transport = Arc::new(hedged);
+            }
+            provider.set_transport(transport);
+        }
+        quotas.into_values().collect()
+    }
+}
+
+// Credential of providers configured from the environment in sandbox mode
+fn sandbox_credential() -> Option<String> {
+    sandbox_enabled().then(|| SANDBOX_CREDENTIAL.to_string())
+}
+
+fn azure_with_token_cache(cache: Arc<TokenCache>) -> Result<Box<dyn DynCarbonProvider>> {
+    let provider = AzureProvider::new(AzureConfig {
+        access_token: String::new(),
+    })?;
+    Ok(Box::new(provider.with_token_cache(cache)))
 }
 
 /// Main client with type-safe guarantee of having providers
 pub struct CarbemClient {
-    providers: Vec<Box<dyn CarbonProvider + Send + Sync>>,
+    providers: Vec<Box<dyn DynCarbonProvider>>,
+    instances: HashMap<String, usize>,
+    reports: BTreeMap<String, ReportDefinition>,
+    quotas: Vec<Arc<QuotaTracker>>,
+    coalescer: Option<Arc<Coalescer>>,
+    query_defaults: HashMap<String, ProviderQueryConfig>,
 }
 
 impl Clone for CarbemClient {
     fn clone(&self) -> Self {
         Self {
             providers: self.providers.iter().map(|p| p.clone_provider()).collect(),
+            instances: self.instances.clone(),
+            reports: self.reports.clone(),
+            quotas: self.quotas.clone(),
+            coalescer: self.coalescer.clone(),
+            query_defaults: self.query_defaults.clone(),
         }
     }
 }
@@ -143,14 +547,328 @@ impl CarbemClient {
         CarbemClientBuilder::new()
     }
 
+    /// Client answering `azure` and `ibm` queries from a bundled sample dataset
+    ///
+    /// Needs no credentials and sends no request, e.g. to try the query,
+    /// aggregation and export pipeline before having cloud access. See
+    /// [`DemoProvider`].
+    pub fn demo() -> Self {
+        Self {
+            providers: DemoProvider::provider_names()
+                .into_iter()
+                .map(|name| Box::new(DemoProvider::new(name)) as Box<dyn DynCarbonProvider>)
+                .collect(),
+            instances: HashMap::new(),
+            reports: BTreeMap::new(),
+            quotas: Vec::new(),
+            coalescer: None,
+            query_defaults: HashMap::new(),
+        }
+    }
+
+    /// Build a client with every provider account of `config`
+    pub fn from_config(config: &ClientConfig) -> Result<Self> {
+        if config.is_empty() {
+            return Err(CarbemError::Config(
+                "client config has no provider account".to_string(),
+            ));
+        }
+
+        let mut builder = CarbemClientBuilder::<Configured> {
+            registry: ProviderRegistry::new(),
+            providers: Vec::new(),
+            instances: HashMap::new(),
+            lenient_parsing: config.lenient_parsing,
+            coalesce_queries: false,
+            transport: config.transport()?,
+            limits: ConcurrencyLimits {
+                quotas: config.quotas.clone().into_iter().collect(),
+                ..ConcurrencyLimits::default()
+            },
+            audit_log: config
+                .audit_log
+                .as_ref()
+                .map(|path| AuditLog::open(path).map(Arc::new))
+                .transpose()?,
+            headers: RequestHeaders::default(),
+            query_defaults: config
+                .query_defaults
+                .iter()
+                .map(|defaults| (defaults.provider().to_string(), defaults.clone()))
+                .collect(),
+            _state: PhantomData,
+        };
+        if let Some(app) = &config.app_identifier {
+            builder = builder.with_app_identifier(app)?;
+        }
+        let accounts = config
+            .azure
+            .iter()
+            .map(|account| ("azure", json!(account.auth), &account.name))
+            .chain(
+                config
+                    .ibm
+                    .iter()
+                    .map(|account| ("ibm", json!(account.auth), &account.name)),
+            );
+        for (provider_name, auth, name) in accounts {
+            let provider = builder.registry.create_provider(provider_name, auth)?;
+            builder.providers.push(provider);
+            if let Some(name) = name {
+                builder = builder.with_instance_name(name)?;
+            }
+        }
+        config
+            .reports
+            .iter()
+            .cloned()
+            .try_fold(builder.build(), CarbemClient::with_report)
+    }
+
+    /// Add a report definition, run by its name with [`run_report`](Self::run_report)
+    pub fn with_report(mut self, definition: ReportDefinition) -> Result<Self> {
+        if self.reports.contains_key(&definition.name) {
+            return Err(CarbemError::Config(format!(
+                "report '{}' is already defined",
+                definition.name
+            )));
+        }
+        self.reports.insert(definition.name.clone(), definition);
+        Ok(self)
+    }
+
+    /// Report definitions, ordered by name
+    pub fn reports(&self) -> impl Iterator<Item = &ReportDefinition> {
+        self.reports.values()
+    }
+
+    /// Run the report named `name` and return it rendered
+    ///
+    /// The report is also delivered to its destination, if it has one.
+    pub async fn run_report(&self, name: &str) -> Result<String> {
+        let definition = self
+            .reports
+            .get(name)
+            .ok_or_else(|| CarbemError::Config(format!("Unknown report: {}", name)))?;
+        definition.run(self).await
+    }
+
+    /// Check `config` for missing fields and malformed IDs without calling providers
+    pub fn validate_config(config: &ClientConfig) -> ValidationReport {
+        validate::validate(config)
+    }
+
+    /// Validate `config`, then verify access with a small live query per valid account
+    pub async fn probe_config(config: &ClientConfig) -> ValidationReport {
+        validate::probe(config).await
+    }
+
+    /// Validate `config`, then check every valid account holds the roles carbem needs
+    pub async fn preflight_config(config: &ClientConfig) -> ValidationReport {
+        validate::preflight(config).await
+    }
+
     /// Query emissions from all configured providers
+    ///
+    /// Emissions are returned in the canonical order of [`sort_emissions`],
+    /// with their [`service_category`](CarbonEmission::service_category) set.
+    /// With a [`route`](EmissionQuery::route), each instance is tried in turn
+    /// until one succeeds; the error of the last one is returned if all fail.
+    /// The query runs under a [correlation ID](crate::correlation), which
+    /// errors name. With [query coalescing](CarbemClientBuilder::with_query_coalescing),
+    /// identical concurrent queries share one run. The provider configuration
+    /// of the query is completed with the [query defaults](CarbemClientBuilder::with_query_defaults)
+    /// of its provider.
     pub async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
-        for provider in &self.providers {
-            if provider.name() == query.provider {
-                return provider.get_emissions(query).await;
+        let query = &*self.with_query_defaults(query)?;
+        let emissions = || async { Ok(self.run_query(query).await?.emissions) };
+        match &self.coalescer {
+            Some(coalescer) => coalescer.run(audit::query_hash(query), emissions).await,
+            None => emissions().await,
+        }
+    }
+
+    // Query the providers of the route of `query` in failover order
+    async fn run_query(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        let route = self.route(query)?;
+        correlation::in_scope(async {
+            let mut last_error = None;
+            for (entry, provider) in route {
+                match audit::for_query(query, provider.get_emissions_with_raw(query)).await {
+                    Ok(mut result) => {
+                        if !provider.filters_tags() {
+                            result
+                                .emissions
+                                .retain(|emission| query.matches_tags(emission));
+                        }
+                        categorize_emissions(&mut result.emissions);
+                        sort_emissions(&mut result.emissions);
+                        return Ok(result);
+                    }
+                    Err(e) => {
+                        warn!(
+                            "{} query failed, trying the next route entry (correlation ID {}): {}",
+                            entry,
+                            correlation::current().unwrap_or_default(),
+                            e
+                        );
+                        last_error = Some(e);
+                    }
+                }
             }
+            Err(last_error.expect("routes have at least one entry"))
+        })
+        .await
+    }
+
+    /// Query emissions window by window, spilling each page to disk
+    ///
+    /// Meant for backfills too large for memory: the months selected by the
+    /// query's date alignment are split into windows of
+    /// `options.window_months` months, each queried as by
+    /// [`query_emissions`](Self::query_emissions) and written to a temporary
+    /// file before the next one, so at most one page is held in memory. See
+    /// [`spill`](crate::spill).
+    pub async fn query_emissions_spilled(
+        &self,
+        query: &EmissionQuery,
+        options: &SpillOptions,
+    ) -> Result<SpilledEmissions> {
+        let mut spilled = SpilledEmissions::create(options).await?;
+        for window in options.windows(query)? {
+            let page = self
+                .query_emissions(&spill::page_query(query, &window))
+                .await?;
+            spilled.push_page(window, page).await?;
         }
-        Err(CarbemError::UnsupportedProvider(query.provider.clone()))
+        Ok(spilled)
+    }
+
+    /// Query emissions, also returning raw provider responses
+    ///
+    /// Set `query.raw_response` to `RawResponseMode::Alongside` to get both the
+    /// parsed emissions and the provider payloads, or to `RawResponseMode::Only`
+    /// to skip mapping entirely when it fails or is incomplete.
+    pub async fn query_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        let query = &*self.with_query_defaults(query)?;
+        self.run_query(query).await
+    }
+
+    // Providers answering `query` in failover order, with their route entry
+    fn route<'a>(
+        &'a self,
+        query: &'a EmissionQuery,
+    ) -> Result<Vec<(&'a str, &'a dyn DynCarbonProvider)>> {
+        if query.route.is_empty() {
+            return self
+                .default_instance(&query.provider)
+                .map(|provider| vec![(query.provider.as_str(), provider)]);
+        }
+
+        let mut providers = Vec::new();
+        for entry in &query.route {
+            let provider_name = entry.split_once(':').map_or(entry.as_str(), |(p, _)| p);
+            if provider_name != query.provider {
+                return Err(CarbemError::Config(format!(
+                    "route entry '{}' is not a {} instance",
+                    entry, query.provider
+                )));
+            }
+            let provider = if entry.contains(':') {
+                self.instances
+                    .get(entry)
+                    .map(|&index| self.providers[index].as_ref())
+                    .ok_or_else(|| {
+                        CarbemError::Config(format!("unknown provider instance '{}'", entry))
+                    })?
+            } else {
+                self.default_instance(entry)?
+            };
+            providers.push((entry.as_str(), provider));
+        }
+        Ok(providers)
+    }
+
+    fn default_instance(&self, provider_name: &str) -> Result<&dyn DynCarbonProvider> {
+        self.providers
+            .iter()
+            .find(|p| p.name() == provider_name)
+            .map(|p| p.as_ref())
+            .ok_or_else(|| CarbemError::UnsupportedProvider(provider_name.to_string()))
+    }
+
+    /// Expected requests, result pages and duration of `query`, without sending it
+    ///
+    /// See [`QueryEstimate`]. Fails like a dry run when the query is invalid.
+    pub fn estimate_query(&self, query: &EmissionQuery) -> Result<QueryEstimate> {
+        let query = &*self.with_query_defaults(query)?;
+        let (_, provider) = self.route(query)?.remove(0);
+        QueryEstimate::new(provider, query)
+    }
+
+    // `query` with its provider configuration completed by the query defaults of its provider
+    fn with_query_defaults<'a>(&self, query: &'a EmissionQuery) -> Result<Cow<'a, EmissionQuery>> {
+        let Some(defaults) = self.query_defaults.get(&query.provider) else {
+            return Ok(Cow::Borrowed(query));
+        };
+        let provider_config = match &query.provider_config {
+            Some(config) => config.merged_over(defaults)?,
+            None => defaults.clone(),
+        };
+        Ok(Cow::Owned(EmissionQuery {
+            provider_config: Some(provider_config),
+            ..query.clone()
+        }))
+    }
+
+    /// Run `query` every `interval` and stream what changed between results
+    ///
+    /// See [`EmissionEvents`]. Polling runs on a background task of the
+    /// [`Runtime`](crate::runtime::Runtime), Tokio by default, with a clone of
+    /// this client.
+    pub fn subscribe(&self, query: EmissionQuery, interval: Duration) -> EmissionEvents {
+        EmissionEvents::spawn(self.clone(), query, interval)
+    }
+
+    /// Authenticate every provider and open its connections ahead of the first query
+    ///
+    /// Call it while a serverless function initializes, and keep the client
+    /// for the following invocations, so they skip token refreshes and TLS
+    /// handshakes. Providers are warmed up one after the other.
+    pub async fn prewarm(&self) -> Result<()> {
+        for provider in &self.providers {
+            provider.prewarm().await?;
+        }
+        Ok(())
+    }
+
+    /// Check every provider instance can authenticate and connect
+    ///
+    /// Each instance is [prewarmed](Self::prewarm), without querying
+    /// emissions. Results are labelled with the instance name, e.g.
+    /// `azure:prod`, or with the provider name for unnamed instances.
+    pub async fn probe_providers(&self) -> Vec<(String, Result<()>)> {
+        let mut results = Vec::with_capacity(self.providers.len());
+        for (index, provider) in self.providers.iter().enumerate() {
+            let label = self
+                .instances
+                .iter()
+                .find(|(_, i)| **i == index)
+                .map_or_else(|| provider.name().to_string(), |(name, _)| name.clone());
+            results.push((label, provider.prewarm().await));
+        }
+        results
+    }
+
+    /// Calls made to each provider with a quota in the current hour and day
+    ///
+    /// See [`CarbemClientBuilder::with_provider_quota`].
+    pub fn quota_status(&self) -> Vec<QuotaStatus> {
+        let now = chrono::Utc::now();
+        self.quotas
+            .iter()
+            .map(|tracker| tracker.status(now))
+            .collect()
     }
 
     /// Get all available providers
@@ -162,11 +880,233 @@ impl CarbemClient {
     pub fn has_provider(&self, name: &str) -> bool {
         self.providers.iter().any(|p| p.name() == name)
     }
+
+    /// Differences between provider responses and the schema carbem expects
+    ///
+    /// A non-empty list means a provider changed its payload; queries may
+    /// still succeed, but the mapping should be reviewed.
+    pub fn schema_warnings(&self) -> Vec<SchemaWarning> {
+        self.providers
+            .iter()
+            .flat_map(|p| p.schema_warnings())
+            .collect()
+    }
+
+    /// Last requests sent by every provider and their responses, redacted
+    pub fn recent_exchanges(&self) -> Vec<CapturedExchange> {
+        let mut exchanges: Vec<CapturedExchange> = self
+            .providers
+            .iter()
+            .flat_map(|p| p.recent_exchanges())
+            .collect();
+        exchanges.sort_by_key(|exchange| exchange.sent_at);
+        exchanges
+    }
+}
+
+#[async_trait]
+impl EmissionSource for CarbemClient {
+    async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        CarbemClient::query_emissions(self, query).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::transport::{HttpRequest, HttpResponse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_provider_concurrency_limit() {
+        #[derive(Debug, Default)]
+        struct Slow {
+            in_flight: AtomicUsize,
+            peak: AtomicUsize,
+        }
+
+        #[async_trait]
+        impl Transport for Slow {
+            async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+                let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(HttpResponse::new(
+                    reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                    "",
+                ))
+            }
+        }
+
+        let transport = Arc::new(Slow::default());
+        let client = Arc::new(
+            CarbemClient::builder()
+                .with_ibm(IbmConfig {
+                    api_key: "key".to_string(),
+                })
+                .unwrap()
+                .with_transport(transport.clone())
+                .with_provider_concurrency("ibm", 1)
+                .with_max_in_flight_requests(4)
+                .build(),
+        );
+        let query: EmissionQuery = serde_json::from_value(json!({
+            "provider": "ibm",
+            "regions": [],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"},
+            "provider_config": {
+                "provider": "ibm",
+                "config": {"enterprise_id": "x2x261x8x5x84xxxx49x4891xx077xx9"}
+            }
+        }))
+        .unwrap();
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let client = client.clone();
+                let query = query.clone();
+                tokio::spawn(async move { client.query_emissions(&query).await })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap().is_err());
+        }
+        assert_eq!(transport.peak.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_prewarm_connects_every_provider() {
+        #[derive(Debug, Default)]
+        struct Recording {
+            urls: std::sync::Mutex<Vec<String>>,
+        }
+
+        #[async_trait]
+        impl Transport for Recording {
+            async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+                assert_eq!(request.method, reqwest::Method::HEAD);
+                self.urls.lock().unwrap().push(request.url);
+                Ok(HttpResponse::new(reqwest::StatusCode::NOT_FOUND, ""))
+            }
+        }
+
+        let transport = Arc::new(Recording::default());
+        let client = CarbemClient::builder()
+            .with_azure(AzureConfig {
+                access_token: "token".to_string(),
+            })
+            .unwrap()
+            .with_ibm(IbmConfig {
+                api_key: "key".to_string(),
+            })
+            .unwrap()
+            .with_transport(transport.clone())
+            .build();
+
+        client.prewarm().await.unwrap();
+        assert_eq!(
+            *transport.urls.lock().unwrap(),
+            vec![
+                "https://management.azure.com",
+                "https://api.carbon-calculator.cloud.ibm.com"
+            ]
+        );
+        CarbemClient::demo().prewarm().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_route_fails_over_to_next_instance() {
+        #[derive(Debug, Default)]
+        struct RotatedCredentials {
+            requests: AtomicUsize,
+        }
+
+        #[async_trait]
+        impl Transport for RotatedCredentials {
+            async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+                self.requests.fetch_add(1, Ordering::SeqCst);
+                if request.headers["authorization"] == "Bearer revoked" {
+                    return Ok(HttpResponse::new(reqwest::StatusCode::UNAUTHORIZED, ""));
+                }
+                Ok(HttpResponse::new(
+                    reqwest::StatusCode::OK,
+                    r#"{"value": [{
+                        "dataType": "MonthlySummaryData",
+                        "latestMonthEmissions": 12.5,
+                        "previousMonthEmissions": 10.0,
+                        "monthOverMonthEmissionsChangeRatio": 0.25,
+                        "monthlyEmissionsChangeValue": 2.5,
+                        "date": "2024-01-01"
+                    }]}"#,
+                ))
+            }
+        }
+
+        let transport = Arc::new(RotatedCredentials::default());
+        let client = CarbemClient::builder()
+            .with_azure(AzureConfig {
+                access_token: "revoked".to_string(),
+            })
+            .unwrap()
+            .with_instance_name("prod")
+            .unwrap()
+            .with_azure(AzureConfig {
+                access_token: "valid".to_string(),
+            })
+            .unwrap()
+            .with_instance_name("dr")
+            .unwrap()
+            .with_transport(transport.clone())
+            .build();
+        let mut query: EmissionQuery = serde_json::from_value(json!({
+            "provider": "azure",
+            "regions": ["westeurope"],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"},
+            "provider_config": {
+                "provider": "azure",
+                "config": {
+                    "report_type": "MonthlySummaryReport",
+                    "subscription_list": ["00000000-0000-0000-0000-000000000000"]
+                }
+            },
+            "route": ["azure:prod", "azure:dr"]
+        }))
+        .unwrap();
+
+        let emissions = client.query_emissions(&query).await.unwrap();
+        assert_eq!(emissions[0].emissions_kg_co2eq, 12.5);
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 2);
+
+        // Without a fallback the error of the failing instance is returned
+        query.route = vec!["azure:prod".to_string()];
+        assert!(client.query_emissions(&query).await.is_err());
+
+        query.route = vec!["azure:staging".to_string()];
+        assert!(matches!(
+            client.query_emissions(&query).await,
+            Err(CarbemError::Config(m)) if m.contains("azure:staging")
+        ));
+        query.route = vec!["ibm".to_string()];
+        assert!(matches!(
+            client.query_emissions(&query).await,
+            Err(CarbemError::Config(_))
+        ));
+
+        let duplicate = CarbemClient::builder()
+            .with_azure(AzureConfig {
+                access_token: "token".to_string(),
+            })
+            .unwrap()
+            .with_instance_name("prod")
+            .unwrap()
+            .with_azure(AzureConfig {
+                access_token: "token".to_string(),
+            })
+            .unwrap()
+            .with_instance_name("prod");
+        assert!(duplicate.is_err());
+    }
 
     #[test]
     fn test_type_safe_builder() {
@@ -201,4 +1141,52 @@ mod tests {
 
         assert_eq!(client.available_providers().len(), 2);
     }
+
+    #[test]
+    fn test_from_config() {
+        let config = ClientConfig::from_json(
+            r#"{"azure": [{"access_token": "token"}], "ibm": [{"name": "prod", "api_key": "key"}]}"#,
+        )
+        .unwrap();
+
+        let client = CarbemClient::from_config(&config).unwrap();
+        assert_eq!(client.available_providers(), vec!["azure", "ibm"]);
+        assert_eq!(client.instances["ibm:prod"], 1);
+
+        assert!(CarbemClient::from_config(&ClientConfig::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_defaults_complete_partial_configs() {
+        use crate::providers::ibm::IbmQueryConfig;
+
+        let client = CarbemClient::builder()
+            .with_transport(Arc::new(SandboxTransport::new()))
+            .with_query_defaults(ProviderQueryConfig::Ibm(IbmQueryConfig {
+                enterprise_id: Some("sandbox-enterprise".to_string()),
+                limit: Some(100),
+                ..Default::default()
+            }))
+            .with_ibm(IbmConfig {
+                api_key: SANDBOX_CREDENTIAL.to_string(),
+            })
+            .unwrap()
+            .build();
+
+        // Only the grouping is given by the query
+        let query = EmissionQuery::from_query_string(
+            "provider=ibm&regions=us-south&start=2024-01-01&end=2024-01-31&config.group_by=service",
+        )
+        .unwrap();
+        let emissions = client.query_emissions(&query).await.unwrap();
+        assert_eq!(emissions.len(), 3);
+        assert!(emissions.iter().all(|e| e.service.is_some()));
+
+        // Without provider configuration, the defaults are used as is
+        let query = EmissionQuery {
+            provider_config: None,
+            ..query
+        };
+        assert_eq!(client.query_emissions(&query).await.unwrap().len(), 1);
+    }
 }
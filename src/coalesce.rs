@@ -0,0 +1,150 @@
+//! Coalescing of identical concurrent queries
+//!
+//! Behind a dashboard, several users often load the same view at once. With
+//! [`with_query_coalescing`](crate::CarbemClientBuilder::with_query_coalescing),
+//! a query identical to one already running, same [`query_hash`], waits for
+//! that query instead of calling the provider again, and gets a copy of its
+//! result. If the running query is cancelled, a waiting one runs itself.
+//!
+//! [`query_hash`]: crate::audit::query_hash
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::watch;
+
+use crate::error::Result;
+use crate::logging::debug;
+use crate::models::CarbonEmission;
+
+type SharedResult = Arc<Result<Vec<CarbonEmission>>>;
+
+/// Queries running under each query hash, with the channel their result is sent on
+#[derive(Debug, Default)]
+pub(crate) struct Coalescer {
+    running: Mutex<HashMap<String, watch::Receiver<Option<SharedResult>>>>,
+}
+
+impl Coalescer {
+    /// Result of `run`, or of the identical query `key` already running
+    pub(crate) async fn run<F>(
+        &self,
+        key: String,
+        run: impl FnOnce() -> F,
+    ) -> Result<Vec<CarbonEmission>>
+    where
+        F: Future<Output = Result<Vec<CarbonEmission>>>,
+    {
+        let sender = loop {
+            let mut receiver = {
+                let mut running = self.running.lock().expect("coalescer lock poisoned");
+                match running.get(&key) {
+                    Some(receiver) => receiver.clone(),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        running.insert(key.clone(), receiver);
+                        break sender;
+                    }
+                }
+            };
+            debug!("Waiting for identical query {}", key);
+            if let Ok(result) = receiver.wait_for(Option::is_some).await {
+                return copy(result.as_ref().expect("waited for a result"));
+            }
+            // The running query was cancelled before sending its result
+        };
+
+        let _running = Running {
+            coalescer: self,
+            key: &key,
+        };
+        let result = Arc::new(run().await);
+        sender.send_replace(Some(result.clone()));
+        drop(_running);
+        Arc::try_unwrap(result).unwrap_or_else(|result| copy(&result))
+    }
+}
+
+// Removes a query from the running ones when it completes or is cancelled
+struct Running<'a> {
+    coalescer: &'a Coalescer,
+    key: &'a str,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.coalescer
+            .running
+            .lock()
+            .expect("coalescer lock poisoned")
+            .remove(self.key);
+    }
+}
+
+fn copy(result: &Result<Vec<CarbonEmission>>) -> Result<Vec<CarbonEmission>> {
+    match result {
+        Ok(emissions) => Ok(emissions.clone()),
+        Err(e) => Err(e.duplicate()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::error::CarbemError;
+    use crate::models::EmissionQuery;
+    use crate::transport::{HttpRequest, HttpResponse, Transport};
+    use crate::{AzureConfig, CarbemClient};
+
+    // Fails every request after a while
+    #[derive(Debug, Default)]
+    struct Slow {
+        requests: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Transport for Slow {
+        async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Err(CarbemError::Other("connection reset".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identical_queries_share_one_run() {
+        let transport = Arc::new(Slow::default());
+        let client = CarbemClient::builder()
+            .with_azure(AzureConfig {
+                access_token: "token".to_string(),
+            })
+            .unwrap()
+            .with_transport(transport.clone())
+            .with_query_coalescing()
+            .build();
+        let query = EmissionQuery::from_query_string(
+            "provider=azure&regions=westeurope&start=2024-01-01&end=2024-01-31\
+             &config.report_type=MonthlySummaryReport&config.subscription_list=sub-1",
+        )
+        .unwrap();
+
+        assert!(client.query_emissions(&query).await.is_err());
+        let single = transport.requests.swap(0, Ordering::SeqCst);
+        assert!(single > 0);
+
+        let clone = client.clone();
+        let (first, second) = tokio::join!(
+            client.query_emissions(&query),
+            clone.query_emissions(&query)
+        );
+        assert!(first.unwrap_err().to_string().contains("connection reset"));
+        assert!(second.unwrap_err().to_string().contains("connection reset"));
+        assert_eq!(transport.requests.load(Ordering::SeqCst), single);
+    }
+}
@@ -0,0 +1,27 @@
+//! Locks electing one collector among replicas
+//!
+//! When a collector runs in several replicas, e.g. for availability, each of
+//! them would call provider APIs and write the same rows. Giving them a
+//! shared [`CollectionLock`] makes the replica holding it the only one that
+//! collects; the others check again on each run and take over once it is
+//! released or its holder stops renewing it.
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+
+/// Exclusive lock shared by the replicas of a collector
+#[async_trait]
+pub trait CollectionLock: Send + Sync {
+    /// Get the lock backend name
+    fn name(&self) -> &'static str;
+
+    /// Acquire the lock, or confirm it is still held
+    ///
+    /// Returns whether this instance holds the lock. Once acquired, it is
+    /// held until [`release`](Self::release) or until the instance stops.
+    async fn try_acquire(&self) -> Result<bool>;
+
+    /// Release the lock if this instance holds it
+    async fn release(&self) -> Result<()>;
+}
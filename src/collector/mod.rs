@@ -0,0 +1,670 @@
+//! Scheduled collection of emissions into sinks
+//!
+//! A [`Collector`] runs [`CollectionJob`]s on an interval, e.g. the previous
+//! month of every account each night, and writes what they return to its
+//! sinks. The time each job last ran can be saved to a state file, so that a
+//! restarted collector does not collect again what is still fresh. Failed
+//! collections are recorded in a [`JobQueue`] and retried with backoff on
+//! later runs. Replicas sharing a [`CollectionLock`] collect one at a time.
+//!
+//! [`Collector::shutdown`] stops it gracefully: the job in flight finishes,
+//! sinks are flushed and closed and the state is saved before it returns.
+//!
+//! ```ignore
+//! let collector = Arc::new(
+//!     Collector::new(Arc::new(client))
+//!         .with_job(CollectionJob::new("monthly", query).with_period(ReportPeriod::PreviousMonths(1)))
+//!         .with_sink(Box::new(sink))
+//!         .with_state_file("collector.json")?,
+//! );
+//! let running = tokio::spawn({
+//!     let collector = collector.clone();
+//!     async move { collector.run().await }
+//! });
+//! tokio::signal::ctrl_c().await?;
+//! collector.shutdown().await;
+//! running.await??;
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub mod lock;
+pub mod queue;
+#[cfg(feature = "redis")]
+pub mod redis_lock;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::client::EmissionSource;
+use crate::error::{CarbemError, Result};
+use crate::logging::{debug, warn};
+use crate::models::{EmissionQuery, TimePeriod};
+use crate::report_definition::ReportPeriod;
+use crate::runtime::{fs, runtime};
+use crate::sinks::EmissionSink;
+use crate::sinks::jsonl::io_error;
+
+pub use lock::CollectionLock;
+pub use queue::{JobQueue, MemoryJobQueue, PendingCollection};
+#[cfg(feature = "redis")]
+pub use redis_lock::RedisLock;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteJobQueue;
+
+/// Interval between collections when none is set
+pub const DEFAULT_COLLECTION_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A query collected on every run of a [`Collector`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionJob {
+    /// Name the job's progress is saved under, e.g. `azure-monthly`
+    pub name: String,
+
+    /// Query of the job
+    pub query: EmissionQuery,
+
+    /// Period relative to the run, replacing the query's time period when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<ReportPeriod>,
+}
+
+impl CollectionJob {
+    /// Job collecting `query` as is
+    pub fn new(name: impl Into<String>, query: EmissionQuery) -> Self {
+        Self {
+            name: name.into(),
+            query,
+            period: None,
+        }
+    }
+
+    /// Collect `period`, resolved at each run, instead of the query's time period
+    pub fn with_period(mut self, period: ReportPeriod) -> Self {
+        self.period = Some(period);
+        self
+    }
+
+    /// The query run at `now`, with the relative period resolved
+    pub fn query_at(&self, now: DateTime<Utc>) -> EmissionQuery {
+        let mut query = self.query.clone();
+        if let Some(period) = &self.period {
+            query.time_period = period.resolve(now);
+        }
+        query
+    }
+}
+
+// Progress saved to the state file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CollectorState {
+    // When each job last ran, by name
+    last_run: BTreeMap<String, DateTime<Utc>>,
+}
+
+/// Runs collection jobs on an interval until shut down
+pub struct Collector {
+    source: Arc<dyn EmissionSource>,
+    jobs: Vec<CollectionJob>,
+    sinks: tokio::sync::Mutex<Vec<Box<dyn EmissionSink>>>,
+    interval: Duration,
+    state_file: Option<PathBuf>,
+    state: Mutex<CollectorState>,
+    queue: Arc<dyn JobQueue>,
+    retry_delay: Duration,
+    lock: Option<Arc<dyn CollectionLock>>,
+    shutdown: watch::Sender<bool>,
+    phase: watch::Sender<Phase>,
+}
+
+// Lifecycle of a collector: it runs once, then finishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    // Claimed by `run`, or by `shutdown` finishing a collector that never ran
+    Running,
+    // Sinks closed and state saved
+    Finished,
+}
+
+impl Collector {
+    /// Collector querying `source`, without jobs or sinks yet
+    pub fn new(source: Arc<dyn EmissionSource>) -> Self {
+        Self {
+            source,
+            jobs: Vec::new(),
+            sinks: tokio::sync::Mutex::new(Vec::new()),
+            interval: DEFAULT_COLLECTION_INTERVAL,
+            state_file: None,
+            state: Mutex::new(CollectorState::default()),
+            queue: Arc::new(MemoryJobQueue::new()),
+            retry_delay: queue::DEFAULT_RETRY_DELAY,
+            lock: None,
+            shutdown: watch::Sender::new(false),
+            phase: watch::Sender::new(Phase::Idle),
+        }
+    }
+
+    /// Add a job, collected on every run
+    pub fn with_job(mut self, job: CollectionJob) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// Write collected emissions to `sink`, in addition to other sinks
+    pub fn with_sink(mut self, sink: Box<dyn EmissionSink>) -> Self {
+        self.sinks.get_mut().push(sink);
+        self
+    }
+
+    /// Collect every `interval` (defaults to [`DEFAULT_COLLECTION_INTERVAL`])
+    ///
+    /// A job is not run again until `interval` has passed since it last ran,
+    /// even across restarts with a state file.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Save progress to `path`, loading the progress already saved there
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        match std::fs::read(&path) {
+            Ok(json) => {
+                *self.state.get_mut().expect("state lock poisoned") = serde_json::from_slice(&json)?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(io_error(e)),
+        }
+        self.state_file = Some(path);
+        Ok(self)
+    }
+
+    /// Record failed collections in `queue` (defaults to a [`MemoryJobQueue`])
+    pub fn with_job_queue(mut self, queue: Arc<dyn JobQueue>) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    /// Retry a failed collection after `delay`, doubled after each further failure
+    ///
+    /// Defaults to [`DEFAULT_RETRY_DELAY`](queue::DEFAULT_RETRY_DELAY), at
+    /// most [`MAX_RETRY_DELAY`](queue::MAX_RETRY_DELAY).
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Only collect while holding `lock`, shared with the other replicas
+    ///
+    /// Runs of replicas not holding the lock collect nothing. The holder
+    /// keeps it until shutdown.
+    pub fn with_lock(mut self, lock: Arc<dyn CollectionLock>) -> Self {
+        self.lock = Some(lock);
+        self
+    }
+
+    /// The queue of failed collections
+    pub fn job_queue(&self) -> &Arc<dyn JobQueue> {
+        &self.queue
+    }
+
+    /// When `job` last ran
+    pub fn last_run(&self, job: &str) -> Option<DateTime<Utc>> {
+        self.state
+            .lock()
+            .expect("state lock poisoned")
+            .last_run
+            .get(job)
+            .copied()
+    }
+
+    /// Retry the failed collections and run the jobs due now, once
+    ///
+    /// Collections are run one at a time; none starts after a shutdown was
+    /// requested, or while another replica holds the lock. Failed
+    /// collections are recorded in the job queue. Returns the number of
+    /// collections that succeeded, or the first failure once every due
+    /// collection was tried.
+    pub async fn collect_once(&self) -> Result<usize> {
+        if let Some(lock) = &self.lock
+            && !lock.try_acquire().await?
+        {
+            debug!(
+                "Collection lock ({}) is held by another instance, skipping this run",
+                lock.name()
+            );
+            return Ok(0);
+        }
+        let mut collected = 0;
+        let mut failure = None;
+
+        // Retries first: they cover periods older than those of this run
+        for pending in self.queue.due(Utc::now()).await? {
+            if *self.shutdown.borrow() {
+                break;
+            }
+            let Some(job) = self.jobs.iter().find(|job| job.name == pending.job) else {
+                debug!("Dropping retry of removed collection job {}", pending.job);
+                self.queue.complete(&pending.job, &pending.period).await?;
+                continue;
+            };
+            let mut query = job.query.clone();
+            query.time_period = pending.period;
+            match self.collect(&job.name, &query).await {
+                Ok(()) => {
+                    self.queue.complete(&job.name, &query.time_period).await?;
+                    collected += 1;
+                }
+                Err(e) => {
+                    self.retry_later(&job.name, &query.time_period, pending.attempts, &e)
+                        .await?;
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+
+        for job in &self.jobs {
+            if *self.shutdown.borrow() {
+                break;
+            }
+            let now = Utc::now();
+            if !self.is_due(job, now) {
+                continue;
+            }
+            let query = job.query_at(now);
+            let result = self.collect(&job.name, &query).await;
+            self.state
+                .lock()
+                .expect("state lock poisoned")
+                .last_run
+                .insert(job.name.clone(), now);
+            self.save_state().await?;
+            match result {
+                Ok(()) => {
+                    self.queue.complete(&job.name, &query.time_period).await?;
+                    collected += 1;
+                }
+                Err(e) => {
+                    let attempts = self
+                        .queue
+                        .pending()
+                        .await?
+                        .iter()
+                        .find(|pending| pending.is_for(&job.name, &query.time_period))
+                        .map_or(0, |pending| pending.attempts);
+                    self.retry_later(&job.name, &query.time_period, attempts, &e)
+                        .await?;
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(collected),
+        }
+    }
+
+    /// Collect due jobs every interval until [`shutdown`](Self::shutdown)
+    ///
+    /// Failed collections are logged and retried from the job queue. On
+    /// shutdown, sinks are closed: a collector runs once.
+    pub async fn run(&self) -> Result<()> {
+        if !self.claim() {
+            // Already running, or shut down before it started
+            self.finished().await;
+            return Ok(());
+        }
+        let mut shutdown = self.shutdown.subscribe();
+        while !*shutdown.borrow_and_update() {
+            if let Err(e) = self.collect_once().await {
+                debug!("Collection run failed, retrying on the next one: {}", e);
+            }
+            tokio::select! {
+                _ = shutdown.wait_for(|stop| *stop) => {}
+                _ = runtime().sleep(self.interval) => {}
+            }
+        }
+        let result = self.finish().await;
+        self.phase.send_replace(Phase::Finished);
+        result
+    }
+
+    /// Stop [`run`](Self::run) and wait until it has finished
+    ///
+    /// The job in flight completes, then sinks are flushed and closed and
+    /// progress is saved. A collector whose `run` has not started is
+    /// finished here, and `run` then returns at once.
+    pub async fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+        if self.claim() {
+            if let Err(e) = self.finish().await {
+                warn!("Failed to shut down the collector cleanly: {}", e);
+            }
+            self.phase.send_replace(Phase::Finished);
+        }
+        self.finished().await;
+    }
+
+    // Move from idle to running, false when another call already did
+    fn claim(&self) -> bool {
+        self.phase.send_if_modified(|phase| {
+            let idle = *phase == Phase::Idle;
+            if idle {
+                *phase = Phase::Running;
+            }
+            idle
+        })
+    }
+
+    async fn finished(&self) {
+        let mut phase = self.phase.subscribe();
+        // The sender lives as long as `self`: waiting cannot fail
+        let _ = phase.wait_for(|phase| *phase == Phase::Finished).await;
+    }
+
+    fn is_due(&self, job: &CollectionJob, now: DateTime<Utc>) -> bool {
+        match self.last_run(&job.name) {
+            Some(last) => (now - last)
+                .to_std()
+                .is_ok_and(|since| since >= self.interval),
+            None => true,
+        }
+    }
+
+    async fn collect(&self, job: &str, query: &EmissionQuery) -> Result<()> {
+        let emissions = self.source.query_emissions(query).await?;
+        debug!(
+            "Collection job {} returned {} emission(s)",
+            job,
+            emissions.len()
+        );
+        let mut sinks = self.sinks.lock().await;
+        for sink in sinks.iter_mut() {
+            sink.write(&emissions).await?;
+            sink.flush().await?;
+        }
+        Ok(())
+    }
+
+    // Queue another attempt at a collection that failed after `attempts` earlier ones
+    async fn retry_later(
+        &self,
+        job: &str,
+        period: &TimePeriod,
+        attempts: u32,
+        error: &CarbemError,
+    ) -> Result<()> {
+        let attempts = attempts + 1;
+        let delay = queue::retry_delay(self.retry_delay, attempts);
+        warn!(
+            "Collection job {} failed (attempt {}), retrying in {}s: {}",
+            job,
+            attempts,
+            delay.as_secs(),
+            error
+        );
+        self.queue
+            .schedule(PendingCollection {
+                job: job.to_string(),
+                period: period.clone(),
+                attempts,
+                next_attempt: Utc::now() + delay,
+                last_error: error.to_string(),
+            })
+            .await
+    }
+
+    // Close the sinks, save progress and let another replica collect
+    async fn finish(&self) -> Result<()> {
+        let mut result = Ok(());
+        if let Some(lock) = &self.lock {
+            result = lock.release().await;
+        }
+        for sink in self.sinks.lock().await.iter_mut() {
+            if let Err(e) = sink.close().await {
+                warn!("Failed to close sink {}: {}", sink.name(), e);
+                result = result.and(Err(e));
+            }
+        }
+        self.save_state().await.and(result)
+    }
+
+    async fn save_state(&self) -> Result<()> {
+        let Some(path) = &self.state_file else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(&*self.state.lock().expect("state lock poisoned"))?;
+        write_atomically(path, &json).await
+    }
+}
+
+impl std::fmt::Debug for Collector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Collector")
+            .field("jobs", &self.jobs)
+            .field("interval", &self.interval)
+            .field("state_file", &self.state_file)
+            .field("queue", &self.queue.name())
+            .field("lock", &self.lock.as_ref().map(|lock| lock.name()))
+            .finish_non_exhaustive()
+    }
+}
+
+// Replace `path` with `contents`, never leaving it half-written
+async fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    fs::write(&temporary, contents).await.map_err(io_error)?;
+    fs::rename(&temporary, path)
+        .await
+        .map_err(|e| CarbemError::Other(format!("Failed to save {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::CarbemClient;
+    use crate::models::CarbonEmission;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Clone, Default)]
+    struct RecordingSink {
+        written: Arc<Mutex<Vec<CarbonEmission>>>,
+        closed: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl EmissionSink for RecordingSink {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+            self.written.lock().unwrap().extend_from_slice(emissions);
+            Ok(())
+        }
+
+        async fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn close(&mut self) -> Result<()> {
+            self.closed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    // Fails its first query, like a provider during an outage
+    #[derive(Default)]
+    struct FlakySource {
+        failed: AtomicBool,
+    }
+
+    #[async_trait]
+    impl EmissionSource for FlakySource {
+        async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+            if !self.failed.swap(true, Ordering::SeqCst) {
+                return Err(CarbemError::Provider("outage".to_string()));
+            }
+            CarbemClient::demo().query_emissions(query).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_collections_are_retried() {
+        let query: EmissionQuery = serde_json::from_value(serde_json::json!({
+            "provider": "azure",
+            "regions": ["westeurope"],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"}
+        }))
+        .unwrap();
+        let sink = RecordingSink::default();
+        let collector = Collector::new(Arc::new(FlakySource::default()))
+            .with_job(CollectionJob::new("monthly", query))
+            .with_sink(Box::new(sink.clone()))
+            .with_retry_delay(Duration::ZERO);
+
+        assert!(collector.collect_once().await.is_err());
+        let pending = collector.job_queue().pending().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            (pending[0].attempts, pending[0].job.as_str()),
+            (1, "monthly")
+        );
+        assert!(pending[0].last_error.contains("outage"));
+
+        // The job is not due again, but its failed period is
+        assert_eq!(collector.collect_once().await.unwrap(), 1);
+        assert!(collector.job_queue().pending().await.unwrap().is_empty());
+        assert!(!sink.written.lock().unwrap().is_empty());
+    }
+
+    // Lock shared by collectors of one process, held by the collector of an id
+    struct SharedLock {
+        holder: Arc<Mutex<Option<u32>>>,
+        id: u32,
+    }
+
+    #[async_trait]
+    impl CollectionLock for SharedLock {
+        fn name(&self) -> &'static str {
+            "shared"
+        }
+
+        async fn try_acquire(&self) -> Result<bool> {
+            let mut holder = self.holder.lock().unwrap();
+            Ok(*holder.get_or_insert(self.id) == self.id)
+        }
+
+        async fn release(&self) -> Result<()> {
+            let mut holder = self.holder.lock().unwrap();
+            if *holder == Some(self.id) {
+                *holder = None;
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_one_replica_collects() {
+        let query: EmissionQuery = serde_json::from_value(serde_json::json!({
+            "provider": "azure",
+            "regions": ["westeurope"],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"}
+        }))
+        .unwrap();
+        let holder = Arc::new(Mutex::new(None));
+        let replica = |id| {
+            Collector::new(Arc::new(CarbemClient::demo()))
+                .with_job(CollectionJob::new("monthly", query.clone()))
+                .with_lock(Arc::new(SharedLock {
+                    holder: holder.clone(),
+                    id,
+                }))
+        };
+        let (first, second) = (replica(1), replica(2));
+
+        assert_eq!(first.collect_once().await.unwrap(), 1);
+        assert_eq!(second.collect_once().await.unwrap(), 0);
+        assert!(second.last_run("monthly").is_none());
+
+        // The second replica takes over once the first shut down
+        let running = first.run();
+        let (result, ()) = tokio::join!(running, first.shutdown());
+        result.unwrap();
+        assert_eq!(second.collect_once().await.unwrap(), 1);
+        assert_eq!(*holder.lock().unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown() {
+        let query: EmissionQuery = serde_json::from_value(serde_json::json!({
+            "provider": "azure",
+            "regions": ["westeurope"],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"}
+        }))
+        .unwrap();
+        let state_file =
+            std::env::temp_dir().join(format!("carbem-collector-{}.json", std::process::id()));
+        let sink = RecordingSink::default();
+        let collector = Arc::new(
+            Collector::new(Arc::new(CarbemClient::demo()))
+                .with_job(
+                    CollectionJob::new("monthly", query.clone())
+                        .with_period(ReportPeriod::PreviousMonths(1)),
+                )
+                .with_sink(Box::new(sink.clone()))
+                .with_state_file(&state_file)
+                .unwrap(),
+        );
+
+        let running = tokio::spawn({
+            let collector = collector.clone();
+            async move { collector.run().await }
+        });
+        while collector.last_run("monthly").is_none() {
+            tokio::task::yield_now().await;
+        }
+        collector.shutdown().await;
+        running.await.unwrap().unwrap();
+        assert!(sink.closed.load(Ordering::SeqCst));
+        assert!(!sink.written.lock().unwrap().is_empty());
+
+        // Shut down before `run` started, or without it: sinks are still closed
+        for spawn_run in [true, false] {
+            let sink = RecordingSink::default();
+            let collector = Arc::new(
+                Collector::new(Arc::new(CarbemClient::demo())).with_sink(Box::new(sink.clone())),
+            );
+            let running = spawn_run.then(|| {
+                tokio::spawn({
+                    let collector = collector.clone();
+                    async move { collector.run().await }
+                })
+            });
+            collector.shutdown().await;
+            assert!(sink.closed.load(Ordering::SeqCst));
+            if let Some(running) = running {
+                running.await.unwrap().unwrap();
+            }
+        }
+
+        // Progress survives a restart: the job is not due again yet
+        let restarted = Collector::new(Arc::new(CarbemClient::demo()))
+            .with_job(CollectionJob::new("monthly", query))
+            .with_state_file(&state_file)
+            .unwrap();
+        std::fs::remove_file(&state_file).unwrap();
+        assert_eq!(restarted.last_run("monthly"), collector.last_run("monthly"));
+        assert_eq!(restarted.collect_once().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,174 @@
+//! Queue of failed collections awaiting a retry
+//!
+//! When a collection fails, e.g. during a provider outage, the [`Collector`]
+//! records its job and period in a [`JobQueue`] and retries it on later runs,
+//! waiting longer after each failed attempt. With a durable queue such as
+//! [`SqliteJobQueue`](super::sqlite::SqliteJobQueue), retries survive
+//! restarts, so a night of downtime does not leave a permanent hole in the
+//! history.
+//!
+//! [`Collector`]: super::Collector
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::error::Result;
+use crate::models::TimePeriod;
+
+/// Delay before the first retry of a failed collection, doubled after each further failure
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(15 * 60);
+
+/// Longest delay between two attempts at a failed collection
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A failed collection awaiting a retry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingCollection {
+    /// Name of the job that failed
+    pub job: String,
+
+    /// Period the job was collecting
+    pub period: TimePeriod,
+
+    /// Failed attempts so far
+    pub attempts: u32,
+
+    /// Earliest time of the next attempt
+    pub next_attempt: DateTime<Utc>,
+
+    /// Error of the last attempt
+    pub last_error: String,
+}
+
+impl PendingCollection {
+    /// Whether this is the collection of `period` by `job`
+    pub fn is_for(&self, job: &str, period: &TimePeriod) -> bool {
+        self.job == job && self.period.start == period.start && self.period.end == period.end
+    }
+}
+
+/// Delay before retrying a collection that failed `attempts` times
+///
+/// `first` after the first failure, doubled after each further one, at most
+/// [`MAX_RETRY_DELAY`].
+pub fn retry_delay(first: Duration, attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    first.saturating_mul(factor).min(MAX_RETRY_DELAY.max(first))
+}
+
+/// Trait that all job queue backends implement
+#[async_trait]
+pub trait JobQueue: Send + Sync {
+    /// Get the queue name
+    fn name(&self) -> &'static str;
+
+    /// Add a failed collection, replacing the one of the same job and period
+    async fn schedule(&self, collection: PendingCollection) -> Result<()>;
+
+    /// Collections whose next attempt is due at `now`, earliest first
+    async fn due(&self, now: DateTime<Utc>) -> Result<Vec<PendingCollection>>;
+
+    /// Remove the collection of `period` by `job`, e.g. once it succeeded
+    async fn complete(&self, job: &str, period: &TimePeriod) -> Result<()>;
+
+    /// Every collection awaiting a retry, earliest first
+    async fn pending(&self) -> Result<Vec<PendingCollection>>;
+}
+
+/// Job queue kept in memory: retries are lost when the process exits
+#[derive(Debug, Default)]
+pub struct MemoryJobQueue {
+    collections: Mutex<Vec<PendingCollection>>,
+}
+
+impl MemoryJobQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl JobQueue for MemoryJobQueue {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn schedule(&self, collection: PendingCollection) -> Result<()> {
+        let mut collections = self.collections.lock().await;
+        collections.retain(|pending| !pending.is_for(&collection.job, &collection.period));
+        collections.push(collection);
+        collections.sort_by_key(|pending| pending.next_attempt);
+        Ok(())
+    }
+
+    async fn due(&self, now: DateTime<Utc>) -> Result<Vec<PendingCollection>> {
+        let collections = self.collections.lock().await;
+        Ok(collections
+            .iter()
+            .filter(|pending| pending.next_attempt <= now)
+            .cloned()
+            .collect())
+    }
+
+    async fn complete(&self, job: &str, period: &TimePeriod) -> Result<()> {
+        self.collections
+            .lock()
+            .await
+            .retain(|pending| !pending.is_for(job, period));
+        Ok(())
+    }
+
+    async fn pending(&self) -> Result<Vec<PendingCollection>> {
+        Ok(self.collections.lock().await.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_memory_queue_and_backoff() {
+        assert_eq!(retry_delay(DEFAULT_RETRY_DELAY, 1), DEFAULT_RETRY_DELAY);
+        assert_eq!(retry_delay(DEFAULT_RETRY_DELAY, 3), DEFAULT_RETRY_DELAY * 4);
+        assert_eq!(retry_delay(DEFAULT_RETRY_DELAY, 40), MAX_RETRY_DELAY);
+
+        let now = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        let period = TimePeriod {
+            start: Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap(),
+        };
+        let pending = |attempts, next_attempt| PendingCollection {
+            job: "monthly".to_string(),
+            period: period.clone(),
+            attempts,
+            next_attempt,
+            last_error: "outage".to_string(),
+        };
+        let queue = MemoryJobQueue::new();
+        queue.schedule(pending(1, now)).await.unwrap();
+        queue
+            .schedule(pending(2, now + DEFAULT_RETRY_DELAY * 2))
+            .await
+            .unwrap();
+        assert_eq!(queue.pending().await.unwrap()[0].attempts, 2);
+        assert!(queue.due(now).await.unwrap().is_empty());
+        assert_eq!(
+            queue
+                .due(now + DEFAULT_RETRY_DELAY * 2)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        queue.complete("monthly", &period).await.unwrap();
+        assert!(queue.pending().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+//! Redis collection lock
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use redis::Script;
+use redis::aio::MultiplexedConnection;
+
+use super::lock::CollectionLock;
+use crate::error::{CarbemError, Result};
+use crate::logging::{debug, warn};
+use crate::runtime::runtime;
+
+// Tells apart the locks of one process
+static INSTANCES: AtomicU64 = AtomicU64::new(0);
+
+// Extend the lock only while this instance holds it
+static RENEW: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"if redis.call("get", KEYS[1]) == ARGV[1] then
+            return redis.call("pexpire", KEYS[1], ARGV[2])
+        else
+            return 0
+        end"#,
+    )
+});
+
+// Delete the lock only while this instance holds it
+static RELEASE: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"if redis.call("get", KEYS[1]) == ARGV[1] then
+            return redis.call("del", KEYS[1])
+        else
+            return 0
+        end"#,
+    )
+});
+
+/// Collection lock stored in a Redis key, expiring unless renewed
+///
+/// The holder renews the lock every third of its time-to-live on a
+/// background task. If the holder crashes or loses its connection, the key
+/// expires and another replica acquires it on its next run. A holder whose
+/// last successful renewal is older than the time-to-live considers the lock
+/// lost as well, since another replica may hold it by then.
+pub struct RedisLock {
+    connection: MultiplexedConnection,
+    key: String,
+    // Identifies this instance as the holder in the key's value
+    token: String,
+    ttl: Duration,
+    // State of the current acquisition, shared with its renewal task
+    holding: Mutex<Arc<Holding>>,
+}
+
+// One acquisition of the lock
+struct Holding {
+    // Cleared on release or loss
+    held: AtomicBool,
+    // Start of the last request that pushed the key's expiry back
+    renewed: Mutex<Instant>,
+}
+
+impl Holding {
+    fn new(held: bool, renewed: Instant) -> Self {
+        Self {
+            held: AtomicBool::new(held),
+            renewed: Mutex::new(renewed),
+        }
+    }
+
+    // Whether the lock is held and its key cannot have expired since the last renewal
+    fn is_held(&self, ttl: Duration) -> bool {
+        self.held.load(Ordering::SeqCst)
+            && self.renewed.lock().expect("lock state poisoned").elapsed() < ttl
+    }
+}
+
+impl RedisLock {
+    /// Connect to the Redis server at `url` and use `key` as the lock
+    ///
+    /// `ttl` is how long the lock outlives a holder that stopped renewing it.
+    pub async fn connect(url: &str, key: impl Into<String>, ttl: Duration) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        let connection = client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(redis_error)?;
+        Ok(Self {
+            connection,
+            key: key.into(),
+            token: format!(
+                "{}:{}:{}",
+                std::process::id(),
+                Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+                INSTANCES.fetch_add(1, Ordering::Relaxed)
+            ),
+            ttl,
+            holding: Mutex::new(Arc::new(Holding::new(false, Instant::now()))),
+        })
+    }
+
+    fn ttl_millis(&self) -> u64 {
+        self.ttl.as_millis().max(1) as u64
+    }
+
+    // Renew the lock until it is released, refused or expired
+    fn keep_alive(&self, holding: Arc<Holding>) {
+        let mut connection = self.connection.clone();
+        let (key, token, ttl) = (self.key.clone(), self.token.clone(), self.ttl);
+        let ttl_millis = self.ttl_millis();
+        let interval = self.ttl / 3;
+        let sleeper = runtime();
+        runtime().spawn(Box::pin(async move {
+            loop {
+                sleeper.sleep(interval).await;
+                if !holding.held.load(Ordering::SeqCst) {
+                    return;
+                }
+                let attempt = Instant::now();
+                let renewed: redis::RedisResult<i64> = RENEW
+                    .key(&key)
+                    .arg(&token)
+                    .arg(ttl_millis)
+                    .invoke_async(&mut connection)
+                    .await;
+                match renewed {
+                    Ok(1) => *holding.renewed.lock().expect("lock state poisoned") = attempt,
+                    Ok(_) => {
+                        warn!("Lost collection lock {} to another instance", key);
+                        holding.held.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                    Err(e) if !holding.is_held(ttl) => {
+                        warn!(
+                            "Collection lock {} expired while it could not be renewed: {}",
+                            key, e
+                        );
+                        holding.held.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                    Err(e) => warn!("Failed to renew collection lock {}: {}", key, e),
+                }
+            }
+        }));
+    }
+}
+
+#[async_trait]
+impl CollectionLock for RedisLock {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn try_acquire(&self) -> Result<bool> {
+        let holding = self.holding.lock().expect("lock state poisoned").clone();
+        if holding.is_held(self.ttl) {
+            return Ok(true);
+        }
+        // Expired without renewal: stop its renewal task and compete again
+        holding.held.store(false, Ordering::SeqCst);
+
+        let attempt = Instant::now();
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(&self.key)
+            .arg(&self.token)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.ttl_millis())
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(redis_error)?;
+        if acquired.is_none() {
+            return Ok(false);
+        }
+        debug!("Acquired collection lock {}", self.key);
+        let holding = Arc::new(Holding::new(true, attempt));
+        *self.holding.lock().expect("lock state poisoned") = holding.clone();
+        self.keep_alive(holding);
+        Ok(true)
+    }
+
+    async fn release(&self) -> Result<()> {
+        let holding = self.holding.lock().expect("lock state poisoned").clone();
+        if !holding.held.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let _: i64 = RELEASE
+            .key(&self.key)
+            .arg(&self.token)
+            .invoke_async(&mut self.connection.clone())
+            .await
+            .map_err(redis_error)?;
+        debug!("Released collection lock {}", self.key);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for RedisLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisLock")
+            .field("key", &self.key)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+fn redis_error(error: redis::RedisError) -> CarbemError {
+    CarbemError::Other(format!("Redis error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_holding_expires_without_renewal() {
+        let ttl = Duration::from_secs(30);
+        assert!(Holding::new(true, Instant::now()).is_held(ttl));
+        assert!(!Holding::new(false, Instant::now()).is_held(ttl));
+        if let Some(stale) = Instant::now().checked_sub(ttl) {
+            assert!(!Holding::new(true, stale).is_held(ttl));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires a Redis server at REDIS_URL
+    async fn test_single_holder() {
+        if let Ok(url) = std::env::var("REDIS_URL") {
+            let key = format!("carbem-test-lock-{}", std::process::id());
+            let ttl = Duration::from_secs(5);
+            let first = RedisLock::connect(&url, &key, ttl).await.unwrap();
+            let second = RedisLock::connect(&url, &key, ttl).await.unwrap();
+
+            assert!(first.try_acquire().await.unwrap());
+            assert!(first.try_acquire().await.unwrap());
+            assert!(!second.try_acquire().await.unwrap());
+            first.release().await.unwrap();
+            assert!(second.try_acquire().await.unwrap());
+            second.release().await.unwrap();
+        }
+    }
+}
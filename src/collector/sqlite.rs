@@ -0,0 +1,219 @@
+//! SQLite job queue
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, Row, params};
+
+use super::queue::{JobQueue, PendingCollection};
+use crate::error::{CarbemError, Result};
+use crate::models::TimePeriod;
+use crate::runtime::spawn_blocking;
+
+/// Job queue kept in a SQLite database, so that retries survive restarts
+///
+/// Several collectors may share the database file; SQLite serializes their
+/// writes.
+#[derive(Debug, Clone)]
+pub struct SqliteJobQueue {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteJobQueue {
+    /// Open the queue stored at `path`, creating the database if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(Connection::open(path).map_err(sqlite_error)?)
+    }
+
+    /// A queue in a private in-memory database, e.g. for tests
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(sqlite_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS pending_collections (
+                    job TEXT NOT NULL,
+                    period_start TEXT NOT NULL,
+                    period_end TEXT NOT NULL,
+                    attempts INTEGER NOT NULL,
+                    next_attempt TEXT NOT NULL,
+                    last_error TEXT NOT NULL,
+                    PRIMARY KEY (job, period_start, period_end)
+                )",
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    // Run `statement` on a blocking thread
+    async fn run<T: Send + 'static>(
+        &self,
+        statement: impl FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let connection = self.connection.clone();
+        spawn_blocking(move || {
+            let connection = connection.lock().expect("SQLite connection lock poisoned");
+            statement(&connection)
+        })
+        .await?
+        .map_err(sqlite_error)
+    }
+
+    async fn select(&self, due_at: Option<DateTime<Utc>>) -> Result<Vec<PendingCollection>> {
+        let due_at = due_at.map(timestamp);
+        self.run(move |connection| {
+            let mut statement = connection.prepare(
+                "SELECT job, period_start, period_end, attempts, next_attempt, last_error
+                 FROM pending_collections
+                 WHERE ?1 IS NULL OR next_attempt <= ?1
+                 ORDER BY next_attempt",
+            )?;
+            statement
+                .query_map(params![due_at], pending_collection)?
+                .collect()
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl JobQueue for SqliteJobQueue {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn schedule(&self, collection: PendingCollection) -> Result<()> {
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO pending_collections
+                 (job, period_start, period_end, attempts, next_attempt, last_error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    collection.job,
+                    timestamp(collection.period.start),
+                    timestamp(collection.period.end),
+                    collection.attempts,
+                    timestamp(collection.next_attempt),
+                    collection.last_error,
+                ],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn due(&self, now: DateTime<Utc>) -> Result<Vec<PendingCollection>> {
+        self.select(Some(now)).await
+    }
+
+    async fn complete(&self, job: &str, period: &TimePeriod) -> Result<()> {
+        let (job, start, end) = (
+            job.to_string(),
+            timestamp(period.start),
+            timestamp(period.end),
+        );
+        self.run(move |connection| {
+            connection.execute(
+                "DELETE FROM pending_collections
+                 WHERE job = ?1 AND period_start = ?2 AND period_end = ?3",
+                params![job, start, end],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn pending(&self) -> Result<Vec<PendingCollection>> {
+        self.select(None).await
+    }
+}
+
+// Timestamps are stored in a fixed-width UTC form, so that they sort as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(row: &Row<'_>, index: usize) -> rusqlite::Result<DateTime<Utc>> {
+    let text: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&text)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+        })
+}
+
+fn pending_collection(row: &Row<'_>) -> rusqlite::Result<PendingCollection> {
+    Ok(PendingCollection {
+        job: row.get(0)?,
+        period: TimePeriod {
+            start: parse_timestamp(row, 1)?,
+            end: parse_timestamp(row, 2)?,
+        },
+        attempts: row.get(3)?,
+        next_attempt: parse_timestamp(row, 4)?,
+        last_error: row.get(5)?,
+    })
+}
+
+fn sqlite_error(error: rusqlite::Error) -> CarbemError {
+    CarbemError::Other(format!("SQLite error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_queue_survives_reopening() {
+        let path = std::env::temp_dir().join(format!("carbem-queue-{}.sqlite", std::process::id()));
+        let now = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        let period = TimePeriod {
+            start: Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap(),
+        };
+        let pending = PendingCollection {
+            job: "monthly".to_string(),
+            period: period.clone(),
+            attempts: 1,
+            next_attempt: now,
+            last_error: "outage".to_string(),
+        };
+
+        SqliteJobQueue::open(&path)
+            .unwrap()
+            .schedule(pending.clone())
+            .await
+            .unwrap();
+        let queue = SqliteJobQueue::open(&path).unwrap();
+        queue
+            .schedule(PendingCollection {
+                attempts: 2,
+                ..pending
+            })
+            .await
+            .unwrap();
+        let pending = queue.pending().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 2);
+        assert!(pending[0].is_for("monthly", &period));
+        assert_eq!(queue.due(now).await.unwrap().len(), 1);
+        assert!(
+            queue
+                .due(now - chrono::Duration::seconds(1))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        queue.complete("monthly", &period).await.unwrap();
+        assert!(queue.pending().await.unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
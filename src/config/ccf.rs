@@ -0,0 +1,220 @@
+//! Import of Cloud Carbon Footprint (CCF) configuration
+//!
+//! CCF is configured through environment variables, usually kept in a `.env`
+//! file. [`import_ccf`] reads either that `.env` format or a JSON object with
+//! the same keys and converts what carbem supports into a [`ClientConfig`].
+//! Everything that cannot be converted is listed in [`CcfImport::warnings`].
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::{AzureAccountConfig, ClientConfig};
+use crate::error::{CarbemError, Result};
+use crate::providers::azure::AzureConfig;
+
+/// Result of a CCF configuration import
+#[derive(Debug, Clone, Default)]
+pub struct CcfImport {
+    /// Converted configuration
+    pub config: ClientConfig,
+
+    /// Settings that were not converted and need attention
+    pub warnings: Vec<String>,
+}
+
+/// Convert a CCF configuration, in `.env` or JSON format, into a [`ClientConfig`]
+///
+/// Recognized settings:
+/// - `AZURE_USE_BILLING_DATA`, `AZURE_CLIENT_ID`, `AZURE_CLIENT_SECRET`,
+///   `AZURE_TENANT_ID`: enable an Azure account. carbem authenticates with a
+///   bearer token, read from `CARBEM_AZURE_ACCESS_TOKEN` or `AZURE_TOKEN` when
+///   present in the same file.
+/// - `AZURE_SUBSCRIPTIONS` and `AZURE_REGIONS`: JSON lists of IDs (or of
+///   `{"id": ...}` objects) or comma-separated values.
+/// - `AWS_*` and `GCP_*`: reported as unsupported.
+pub fn import_ccf(input: &str) -> Result<CcfImport> {
+    let settings = if input.trim_start().starts_with('{') {
+        parse_json(input)?
+    } else {
+        parse_env(input)
+    };
+
+    let mut import = CcfImport::default();
+    let setting = |key: &str| settings.get(key).filter(|value| !value.is_empty());
+
+    let azure_enabled = setting("AZURE_USE_BILLING_DATA").is_some_and(|value| is_true(value))
+        || setting("AZURE_CLIENT_ID").is_some()
+        || setting("AZURE_TENANT_ID").is_some();
+    if azure_enabled {
+        let access_token = setting("CARBEM_AZURE_ACCESS_TOKEN")
+            .or_else(|| setting("AZURE_TOKEN"))
+            .cloned();
+        if access_token.is_none() {
+            import.warnings.push(
+                "Azure: CCF service principal credentials cannot be used directly; set access_token to a bearer token obtained with them".to_string(),
+            );
+        }
+
+        let subscriptions = setting("AZURE_SUBSCRIPTIONS")
+            .map(|value| parse_list(value))
+            .unwrap_or_default();
+        if subscriptions.is_empty() {
+            import.warnings.push(
+                "Azure: CCF discovers subscriptions at runtime; list the subscriptions to query"
+                    .to_string(),
+            );
+        }
+
+        import.config.azure.push(AzureAccountConfig {
+            name: None,
+            auth: AzureConfig {
+                access_token: access_token.unwrap_or_default(),
+            },
+            subscriptions,
+            regions: setting("AZURE_REGIONS")
+                .map(|value| parse_list(value))
+                .unwrap_or_default(),
+        });
+
+        if setting("AZURE_RESOURCE_TAG_NAMES").is_some() {
+            import
+                .warnings
+                .push("Azure: resource tags are not supported and were ignored".to_string());
+        }
+    }
+
+    for (prefix, provider) in [("AWS_", "AWS"), ("GCP_", "GCP")] {
+        let keys: Vec<&str> = settings
+            .iter()
+            .filter(|(key, value)| key.starts_with(prefix) && !value.is_empty())
+            .map(|(key, _)| key.as_str())
+            .collect();
+        if !keys.is_empty() {
+            import.warnings.push(format!(
+                "{}: provider not supported by carbem, ignored {}",
+                provider,
+                keys.join(", ")
+            ));
+        }
+    }
+
+    Ok(import)
+}
+
+// Parse `KEY=VALUE` lines, ignoring comments, `export` prefixes and quotes
+fn parse_env(input: &str) -> BTreeMap<String, String> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            Some((key.trim().to_string(), unquote(value.trim()).to_string()))
+        })
+        .collect()
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+fn parse_json(input: &str) -> Result<BTreeMap<String, String>> {
+    let object: serde_json::Map<String, Value> = serde_json::from_str(input)
+        .map_err(|e| CarbemError::Config(format!("Invalid CCF JSON config: {}", e)))?;
+    Ok(object
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(value) => value,
+                other => other.to_string(),
+            };
+            (key, value)
+        })
+        .collect())
+}
+
+// A JSON list of IDs or `{"id": ...}` objects, or comma-separated values
+fn parse_list(value: &str) -> Vec<String> {
+    match serde_json::from_str::<Vec<Value>>(value) {
+        Ok(items) => items
+            .into_iter()
+            .filter_map(|item| match item {
+                Value::String(id) => Some(id),
+                Value::Object(object) => object.get("id")?.as_str().map(str::to_string),
+                _ => None,
+            })
+            .collect(),
+        Err(_) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+fn is_true(value: &str) -> bool {
+    value.eq_ignore_ascii_case("true") || value == "1"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_env_file() {
+        let input = r#"
+# CCF configuration
+AZURE_USE_BILLING_DATA=true
+AZURE_CLIENT_ID=client-id
+AZURE_CLIENT_SECRET="secret"
+export CARBEM_AZURE_ACCESS_TOKEN=token
+AZURE_SUBSCRIPTIONS=[{"id": "sub-1", "name": "prod"}, "sub-2"]
+AZURE_REGIONS=westeurope, northeurope
+AWS_USE_BILLING_DATA=true
+AWS_ACCOUNTS=[{"id":"123456789012","name":"aws"}]
+GCP_USE_BILLING_DATA=
+"#;
+
+        let import = import_ccf(input).unwrap();
+        let azure = &import.config.azure[0];
+        assert_eq!(azure.auth.access_token, "token");
+        assert_eq!(azure.subscriptions, vec!["sub-1", "sub-2"]);
+        assert_eq!(azure.regions, vec!["westeurope", "northeurope"]);
+        assert_eq!(
+            import.warnings,
+            vec![
+                "AWS: provider not supported by carbem, ignored AWS_ACCOUNTS, AWS_USE_BILLING_DATA"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_json_without_token() {
+        let input = r#"{"AZURE_USE_BILLING_DATA": true, "AZURE_TENANT_ID": "tenant"}"#;
+
+        let import = import_ccf(input).unwrap();
+        assert_eq!(import.config.azure.len(), 1);
+        assert!(import.config.azure[0].auth.access_token.is_empty());
+        assert_eq!(import.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_import_without_supported_provider() {
+        let import = import_ccf("GCP_PROJECTS=[{\"id\":\"p\"}]").unwrap();
+        assert!(import.config.is_empty());
+        assert_eq!(import.warnings.len(), 1);
+
+        assert!(import_ccf("{not json").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Client configuration, e.g. loaded from a file or imported from another tool
+//!
+//! A [`ClientConfig`] lists provider accounts with their credentials and the
+//! identifiers to query (subscriptions, enterprise, regions). It builds a
+//! [`CarbemClient`](crate::CarbemClient) with
+//! [`CarbemClient::from_config`](crate::CarbemClient::from_config).
+
+pub mod ccf;
+pub mod profiles;
+pub mod validate;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CarbemError, Result};
+use crate::providers::azure::AzureConfig;
+use crate::providers::config::ProviderQueryConfig;
+use crate::providers::ibm::IbmConfig;
+use crate::quota::Quota;
+use crate::report_definition::ReportDefinition;
+use crate::transport::{ProxyConfig, ReqwestTransport, Transport};
+
+/// Configuration of every provider account used by a client
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientConfig {
+    /// Azure accounts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub azure: Vec<AzureAccountConfig>,
+
+    /// IBM Cloud accounts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ibm: Vec<IbmAccountConfig>,
+
+    /// Fill missing fields in provider responses with defaults instead of failing
+    #[serde(default)]
+    pub lenient_parsing: bool,
+
+    /// Proxy of every provider request, instead of the `HTTPS_PROXY` environment variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+
+    /// JSON Lines file recording every provider request, see [`AuditLog`](crate::audit::AuditLog)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Application named in the `User-Agent` of provider requests, e.g. `emissions-dashboard/2.1`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_identifier: Option<String>,
+
+    /// Call quotas by provider name, see [`Quota`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub quotas: BTreeMap<String, Quota>,
+
+    /// Reports the client can run by name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reports: Vec<ReportDefinition>,
+
+    /// Provider configuration completing that of queries, at most one per provider,
+    /// see [`CarbemClientBuilder::with_query_defaults`](crate::CarbemClientBuilder::with_query_defaults)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query_defaults: Vec<ProviderQueryConfig>,
+}
+
+impl ClientConfig {
+    /// Parse a configuration from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| CarbemError::Config(format!("Invalid client config: {}", e)))
+    }
+
+    /// Whether no provider account is configured
+    pub fn is_empty(&self) -> bool {
+        self.azure.is_empty() && self.ibm.is_empty()
+    }
+
+    /// Transport through the configured proxy, `None` to use the default one
+    pub(crate) fn transport(&self) -> Result<Option<Arc<dyn Transport>>> {
+        let Some(proxy) = &self.proxy else {
+            return Ok(None);
+        };
+        Ok(Some(Arc::new(ReqwestTransport::with_proxy(proxy)?)))
+    }
+}
+
+/// An Azure account: credentials and what to query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AzureAccountConfig {
+    /// Instance name used in query routes, e.g. `prod` for `azure:prod`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Authentication
+    #[serde(flatten)]
+    pub auth: AzureConfig,
+
+    /// Subscription IDs to query
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subscriptions: Vec<String>,
+
+    /// Locations to query (all when empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<String>,
+}
+
+/// An IBM Cloud account: credentials and what to query
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IbmAccountConfig {
+    /// Instance name used in query routes, e.g. `prod` for `ibm:prod`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Authentication
+    #[serde(flatten)]
+    pub auth: IbmConfig,
+
+    /// Enterprise ID to query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enterprise_id: Option<String>,
+
+    /// Locations to query (all when empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<String>,
+}
+
+// Enterprise identifiers are redacted from debug output
+impl std::fmt::Debug for IbmAccountConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IbmAccountConfig")
+            .field("name", &self.name)
+            .field("auth", &self.auth)
+            .field(
+                "enterprise_id",
+                &self.enterprise_id.as_ref().map(|_| crate::redact::REDACTED),
+            )
+            .field("regions", &self.regions)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_config_from_json() {
+        let config = ClientConfig::from_json(
+            r#"{
+                "azure": [{"access_token": "token", "subscriptions": ["sub-1"]}],
+                "ibm": [{"api_key": "key", "enterprise_id": "ent-1"}]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.azure[0].auth.access_token, "token");
+        assert_eq!(config.azure[0].subscriptions, vec!["sub-1"]);
+        assert_eq!(config.ibm[0].enterprise_id.as_deref(), Some("ent-1"));
+        assert!(!config.lenient_parsing);
+        assert!(!format!("{:?}", config).contains("ent-1"));
+    }
+
+    #[test]
+    fn test_client_config_invalid_json() {
+        assert!(ClientConfig::from_json(r#"{"azure": [{}]}"#).is_err());
+        assert!(ClientConfig::from_json("{}").unwrap().is_empty());
+    }
+}
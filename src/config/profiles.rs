@@ -0,0 +1,127 @@
+//! Configuration file holding named profiles
+//!
+//! A [`ConfigFile`] maps profile names to [`ClientConfig`]s, so tools can keep
+//! several setups (e.g. `prod` and `staging`) in one JSON file. Setup flows
+//! typically build a [`ClientConfig`], check it with
+//! [`CarbemClient::probe_config`](crate::CarbemClient::probe_config) and save it
+//! with [`ConfigFile::set_profile`].
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::ClientConfig;
+use crate::error::{CarbemError, Result};
+use crate::sinks::jsonl::io_error;
+
+/// Name of the profile used when none is given
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Named client configurations stored in a JSON file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigFile {
+    /// Configurations by profile name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ClientConfig>,
+}
+
+impl ConfigFile {
+    /// Default location of the configuration file
+    ///
+    /// `CARBEM_CONFIG` when set, otherwise `carbem/config.json` in the user
+    /// configuration directory (`XDG_CONFIG_HOME`, `~/.config` or `%APPDATA%`).
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("CARBEM_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("carbem").join("config.json"))
+    }
+
+    /// Read the file at `path`; a missing file has no profile
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| {
+                CarbemError::Config(format!("Invalid config file {}: {}", path.display(), e))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    /// Write the file to `path`, creating its directory
+    ///
+    /// The file holds credentials, so on Unix it is only readable by its owner.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).map_err(io_error)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    /// Configuration of profile `name`
+    pub fn profile(&self, name: &str) -> Result<&ClientConfig> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| CarbemError::Config(format!("unknown profile '{}'", name)))
+    }
+
+    /// Add or replace profile `name`
+    pub fn set_profile(&mut self, name: &str, config: ClientConfig) {
+        self.profiles.insert(name.to_string(), config);
+    }
+
+    /// Remove profile `name`, returning its configuration
+    pub fn remove_profile(&mut self, name: &str) -> Option<ClientConfig> {
+        self.profiles.remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_profiles() {
+        let dir = std::env::temp_dir().join(format!("carbem-profiles-{}", std::process::id()));
+        let path = dir.join("nested").join("config.json");
+
+        assert!(ConfigFile::load(&path).unwrap().profiles.is_empty());
+
+        let mut file = ConfigFile::default();
+        file.set_profile(
+            DEFAULT_PROFILE,
+            ClientConfig::from_json(r#"{"ibm": [{"api_key": "key", "enterprise_id": "ent"}]}"#)
+                .unwrap(),
+        );
+        file.save(&path).unwrap();
+
+        let loaded = ConfigFile::load(&path).unwrap();
+        let config = loaded.profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!(config.ibm[0].auth.api_key, "key");
+        assert!(loaded.profile("staging").is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(ConfigFile::load(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
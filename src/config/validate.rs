@@ -0,0 +1,678 @@
+//! Validation of client configurations
+//!
+//! [`validate`] checks a [`ClientConfig`] without calling any provider: missing
+//! credentials, missing identifiers and malformed IDs. [`probe`] additionally
+//! runs a small query with every account that passed validation, which
+//! verifies the credentials and the access to the configured identifiers.
+//! [`preflight`] checks that the credentials hold the roles carbem needs and
+//! names the missing one, instead of a generic 403 in the middle of a query.
+
+use chrono::{Datelike, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::ClientConfig;
+use crate::error::CarbemError;
+use crate::models::{DateAlignment, EmissionQuery, TimePeriod};
+use crate::providers::azure::client::CARBON_READER_ROLE;
+use crate::providers::azure::{AzureProvider, AzureQueryConfig, AzureReportType};
+use crate::providers::config::ProviderQueryConfig;
+use crate::providers::ibm::IbmQueryConfig;
+use crate::providers::registry::ProviderRegistry;
+
+/// Severity of a configuration issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// The account cannot be queried
+    Error,
+
+    /// The account can be queried but the setting is likely a mistake
+    Warning,
+}
+
+/// A problem found in a configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    /// Severity of the issue
+    pub severity: IssueSeverity,
+
+    /// Path of the offending setting, e.g. `azure[0].subscriptions[1]`
+    pub field: String,
+
+    /// Description of the issue
+    pub message: String,
+}
+
+/// Result of a live query run with one account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthProbe {
+    /// Account probed, e.g. `ibm[0]`
+    pub account: String,
+
+    /// Error returned by the provider, `None` when the query succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuthProbe {
+    /// Whether the query succeeded
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// IBM Cloud access required to read carbon emissions of an enterprise
+pub const IBM_ENTERPRISE_VIEWER_ROLE: &str = "Viewer role on the Enterprise service";
+
+/// Outcome of a permission check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionStatus {
+    /// The credentials hold the required role
+    Granted,
+
+    /// The credentials lack the required role
+    Missing,
+
+    /// The check failed, e.g. rejected credentials or unreachable provider
+    Unverified,
+}
+
+/// Whether one account holds the role required on one scope
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionCheck {
+    /// Account checked, e.g. `azure[0]`
+    pub account: String,
+
+    /// Scope checked, e.g. `subscriptions/<id>`
+    pub scope: String,
+
+    /// Role carbem needs on the scope
+    pub required_role: String,
+
+    /// Outcome of the check
+    pub status: PermissionStatus,
+
+    /// Actions of the role that are not granted, when the provider lists them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_actions: Vec<String>,
+
+    /// Optional: why the check could not be completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for PermissionCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status {
+            PermissionStatus::Granted => write!(
+                f,
+                "{}: '{}' granted on {}",
+                self.account, self.required_role, self.scope
+            ),
+            PermissionStatus::Missing => {
+                write!(
+                    f,
+                    "{}: missing '{}' on {}",
+                    self.account, self.required_role, self.scope
+                )?;
+                if !self.missing_actions.is_empty() {
+                    write!(f, " (not granted: {})", self.missing_actions.join(", "))?;
+                }
+                Ok(())
+            }
+            PermissionStatus::Unverified => write!(
+                f,
+                "{}: could not verify '{}' on {}: {}",
+                self.account,
+                self.required_role,
+                self.scope,
+                self.error.as_deref().unwrap_or("unknown error")
+            ),
+        }
+    }
+}
+
+/// Issues found in a configuration and results of live probes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Issues found without calling providers
+    pub issues: Vec<ConfigIssue>,
+
+    /// Live probes, empty unless requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub probes: Vec<AuthProbe>,
+
+    /// Permission checks, empty unless requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<PermissionCheck>,
+}
+
+impl ValidationReport {
+    /// Whether there is no error and every probe and permission check succeeded
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+            && self.probes.iter().all(AuthProbe::succeeded)
+            && self
+                .permissions
+                .iter()
+                .all(|check| check.status == PermissionStatus::Granted)
+    }
+
+    /// Permission checks that found a missing role
+    pub fn missing_permissions(&self) -> impl Iterator<Item = &PermissionCheck> {
+        self.permissions
+            .iter()
+            .filter(|check| check.status == PermissionStatus::Missing)
+    }
+
+    /// Issues with [`IssueSeverity::Error`]
+    pub fn errors(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == IssueSeverity::Error)
+    }
+
+    fn push(&mut self, severity: IssueSeverity, field: String, message: &str) {
+        self.issues.push(ConfigIssue {
+            severity,
+            field,
+            message: message.to_string(),
+        });
+    }
+
+    fn has_errors_for(&self, account: &str) -> bool {
+        self.errors().any(|issue| {
+            issue.field == account || issue.field.starts_with(&format!("{}.", account))
+        })
+    }
+}
+
+/// Check every account of `config` without calling providers
+pub fn validate(config: &ClientConfig) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    if config.is_empty() {
+        report.push(
+            IssueSeverity::Error,
+            String::new(),
+            "no provider account is configured",
+        );
+    }
+
+    for (i, account) in config.azure.iter().enumerate() {
+        let path = format!("azure[{}]", i);
+        if account.auth.access_token.trim().is_empty() {
+            report.push(
+                IssueSeverity::Error,
+                format!("{}.access_token", path),
+                "access token is required",
+            );
+        }
+        if account.subscriptions.is_empty() {
+            report.push(
+                IssueSeverity::Error,
+                format!("{}.subscriptions", path),
+                "at least one subscription ID is required",
+            );
+        }
+        if account.regions.is_empty() {
+            report.push(
+                IssueSeverity::Error,
+                format!("{}.regions", path),
+                "at least one location is required for Azure queries",
+            );
+        }
+        for (j, subscription) in account.subscriptions.iter().enumerate() {
+            if !is_guid(subscription) {
+                report.push(
+                    IssueSeverity::Error,
+                    format!("{}.subscriptions[{}]", path, j),
+                    "subscription ID must be a GUID (xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx)",
+                );
+            } else if account.subscriptions[..j]
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(subscription))
+            {
+                report.push(
+                    IssueSeverity::Warning,
+                    format!("{}.subscriptions[{}]", path, j),
+                    "subscription ID is listed twice",
+                );
+            }
+        }
+    }
+
+    for (i, account) in config.ibm.iter().enumerate() {
+        let path = format!("ibm[{}]", i);
+        if account.auth.api_key.trim().is_empty() {
+            report.push(
+                IssueSeverity::Error,
+                format!("{}.api_key", path),
+                "API key is required",
+            );
+        }
+        match &account.enterprise_id {
+            None => report.push(
+                IssueSeverity::Error,
+                format!("{}.enterprise_id", path),
+                "enterprise ID is required",
+            ),
+            Some(id) if !is_enterprise_id(id) => report.push(
+                IssueSeverity::Error,
+                format!("{}.enterprise_id", path),
+                "enterprise ID must be 32 alphanumeric characters",
+            ),
+            Some(_) => {}
+        }
+    }
+
+    check_instance_names(
+        &mut report,
+        "azure",
+        config.azure.iter().map(|account| account.name.as_deref()),
+    );
+    check_instance_names(
+        &mut report,
+        "ibm",
+        config.ibm.iter().map(|account| account.name.as_deref()),
+    );
+
+    if let Some(Err(e)) = config.proxy.as_ref().map(|proxy| proxy.to_reqwest()) {
+        report.push(IssueSeverity::Error, "proxy".to_string(), &e.to_string());
+    }
+
+    for (i, definition) in config.reports.iter().enumerate() {
+        if config.reports[..i]
+            .iter()
+            .any(|other| other.name == definition.name)
+        {
+            report.push(
+                IssueSeverity::Error,
+                format!("reports[{}].name", i),
+                "report name is used by another report",
+            );
+        }
+    }
+
+    for (i, defaults) in config.query_defaults.iter().enumerate() {
+        if config.query_defaults[..i]
+            .iter()
+            .any(|other| other.provider() == defaults.provider())
+        {
+            report.push(
+                IssueSeverity::Error,
+                format!("query_defaults[{}]", i),
+                "query defaults are already set for this provider",
+            );
+        }
+    }
+
+    report
+}
+
+// Instance names must be unique per provider to be addressed by query routes
+fn check_instance_names<'a>(
+    report: &mut ValidationReport,
+    provider: &str,
+    names: impl Iterator<Item = Option<&'a str>>,
+) {
+    let mut seen = Vec::new();
+    for (i, name) in names.enumerate() {
+        let Some(name) = name else { continue };
+        let field = format!("{}[{}].name", provider, i);
+        if name.is_empty() || name.contains(':') {
+            report.push(
+                IssueSeverity::Error,
+                field,
+                "instance name must be non-empty and contain no ':'",
+            );
+        } else if seen.contains(&name) {
+            report.push(
+                IssueSeverity::Error,
+                field,
+                "instance name is used by another account",
+            );
+        } else {
+            seen.push(name);
+        }
+    }
+}
+
+/// Validate `config`, then run a query for the last full month with every valid account
+///
+/// Accounts with validation errors are not probed.
+pub async fn probe(config: &ClientConfig) -> ValidationReport {
+    let mut report = validate(config);
+    let Ok(transport) = config.transport() else {
+        return report;
+    };
+    let registry = ProviderRegistry::new();
+    let month = last_full_month();
+
+    let mut queries = Vec::new();
+    for (i, account) in config.azure.iter().enumerate() {
+        let provider_config = ProviderQueryConfig::Azure(AzureQueryConfig {
+            report_type: Some(AzureReportType::OverallSummaryReport),
+            subscription_list: Some(account.subscriptions.clone()),
+            ..Default::default()
+        });
+        queries.push((
+            format!("azure[{}]", i),
+            "azure",
+            json!(account.auth),
+            account.regions.clone(),
+            provider_config,
+        ));
+    }
+    for (i, account) in config.ibm.iter().enumerate() {
+        let provider_config = ProviderQueryConfig::Ibm(IbmQueryConfig {
+            enterprise_id: account.enterprise_id.clone(),
+            ..Default::default()
+        });
+        queries.push((
+            format!("ibm[{}]", i),
+            "ibm",
+            json!(account.auth),
+            account.regions.clone(),
+            provider_config,
+        ));
+    }
+
+    for (account, provider_name, auth, regions, provider_config) in queries {
+        if report.has_errors_for(&account) {
+            continue;
+        }
+        let query = probe_query(provider_name, regions, provider_config, &month);
+        let result = match registry.create_provider(provider_name, auth) {
+            Ok(mut provider) => {
+                if let Some(transport) = &transport {
+                    provider.set_transport(transport.clone());
+                }
+                provider.get_emissions(&query).await.map(|_| ())
+            }
+            Err(e) => Err(e),
+        };
+        report.probes.push(AuthProbe {
+            account,
+            error: result.err().map(|e| e.to_string()),
+        });
+    }
+
+    report
+}
+
+/// Validate `config`, then check the roles of every valid account
+///
+/// Azure accounts need [`CARBON_READER_ROLE`] on each subscription, checked
+/// with the Azure Authorization API. IBM Cloud has no equivalent API, so IBM
+/// accounts run a small query and a 403 is reported as a missing
+/// [`IBM_ENTERPRISE_VIEWER_ROLE`].
+pub async fn preflight(config: &ClientConfig) -> ValidationReport {
+    let mut report = validate(config);
+    let Ok(transport) = config.transport() else {
+        return report;
+    };
+
+    for (i, account) in config.azure.iter().enumerate() {
+        let path = format!("azure[{}]", i);
+        if report.has_errors_for(&path) {
+            continue;
+        }
+        let provider = match AzureProvider::new(account.auth.clone()) {
+            Ok(provider) => match &transport {
+                Some(transport) => provider.with_transport(transport.clone()),
+                None => provider,
+            },
+            Err(e) => {
+                report.push(IssueSeverity::Error, path, &e.to_string());
+                continue;
+            }
+        };
+        for subscription in &account.subscriptions {
+            let check = match provider.missing_permissions(subscription).await {
+                Ok(missing_actions) => PermissionCheck {
+                    account: path.clone(),
+                    scope: format!("subscriptions/{}", subscription),
+                    required_role: CARBON_READER_ROLE.to_string(),
+                    status: if missing_actions.is_empty() {
+                        PermissionStatus::Granted
+                    } else {
+                        PermissionStatus::Missing
+                    },
+                    missing_actions,
+                    error: None,
+                },
+                Err(e) => PermissionCheck {
+                    account: path.clone(),
+                    scope: format!("subscriptions/{}", subscription),
+                    required_role: CARBON_READER_ROLE.to_string(),
+                    status: PermissionStatus::Unverified,
+                    missing_actions: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            };
+            report.permissions.push(check);
+        }
+    }
+
+    let registry = ProviderRegistry::new();
+    let month = last_full_month();
+    for (i, account) in config.ibm.iter().enumerate() {
+        let path = format!("ibm[{}]", i);
+        if report.has_errors_for(&path) {
+            continue;
+        }
+        let query = probe_query(
+            "ibm",
+            account.regions.clone(),
+            ProviderQueryConfig::Ibm(IbmQueryConfig {
+                enterprise_id: account.enterprise_id.clone(),
+                ..Default::default()
+            }),
+            &month,
+        );
+        let result = match registry.create_provider("ibm", json!(account.auth)) {
+            Ok(mut provider) => {
+                if let Some(transport) = &transport {
+                    provider.set_transport(transport.clone());
+                }
+                provider.get_emissions(&query).await.map(|_| ())
+            }
+            Err(e) => Err(e),
+        };
+        let (status, error) = ibm_permission_status(result.err());
+        report.permissions.push(PermissionCheck {
+            account: path,
+            scope: "enterprise".to_string(),
+            required_role: IBM_ENTERPRISE_VIEWER_ROLE.to_string(),
+            status,
+            missing_actions: Vec::new(),
+            error,
+        });
+    }
+
+    report
+}
+
+// Permission status of an IBM account from the error of its probe query
+fn ibm_permission_status(error: Option<CarbemError>) -> (PermissionStatus, Option<String>) {
+    match error {
+        None => (PermissionStatus::Granted, None),
+        Some(CarbemError::Api(message)) if message.contains(" 403 ") => {
+            (PermissionStatus::Missing, None)
+        }
+        Some(e) => (PermissionStatus::Unverified, Some(e.to_string())),
+    }
+}
+
+// One-month query used to check an account
+fn probe_query(
+    provider: &str,
+    regions: Vec<String>,
+    provider_config: ProviderQueryConfig,
+    month: &TimePeriod,
+) -> EmissionQuery {
+    EmissionQuery {
+        provider: provider.to_string(),
+        regions,
+        time_period: month.clone(),
+        services: None,
+        resources: None,
+        provider_config: Some(provider_config),
+        raw_response: Default::default(),
+        date_alignment: DateAlignment::ExpandToFullMonths,
+        timezone: Default::default(),
+        dry_run: false,
+        route: Vec::new(),
+        tag_filters: Vec::new(),
+        strict: false,
+    }
+}
+
+// First and last instants of the month preceding the current one
+fn last_full_month() -> TimePeriod {
+    let now = Utc::now();
+    let this_month = Utc
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .expect("first instant of a month is valid in UTC");
+    let end = this_month - Duration::seconds(1);
+    TimePeriod {
+        start: Utc
+            .with_ymd_and_hms(end.year(), end.month(), 1, 0, 0, 0)
+            .single()
+            .expect("first instant of a month is valid in UTC"),
+        end,
+    }
+}
+
+fn is_guid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_enterprise_id(value: &str) -> bool {
+    value.len() == 32 && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reports_issues() {
+        let config = ClientConfig::from_json(
+            r#"{
+                "azure": [{
+                    "access_token": "token",
+                    "regions": ["westeurope"],
+                    "subscriptions": [
+                        "00000000-0000-0000-0000-000000000001",
+                        "not-a-guid",
+                        "00000000-0000-0000-0000-000000000001"
+                    ]
+                }],
+                "ibm": [
+                    {"name": "prod", "api_key": "", "enterprise_id": "x2x261x8x5x84xxxx49x4891xx077xx9"},
+                    {"name": "prod", "api_key": "key", "enterprise_id": "short"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let report = validate(&config);
+        let fields: Vec<(&str, IssueSeverity)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.field.as_str(), issue.severity))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("azure[0].subscriptions[1]", IssueSeverity::Error),
+                ("azure[0].subscriptions[2]", IssueSeverity::Warning),
+                ("ibm[0].api_key", IssueSeverity::Error),
+                ("ibm[1].enterprise_id", IssueSeverity::Error),
+                ("ibm[1].name", IssueSeverity::Error),
+            ]
+        );
+        assert!(!report.is_valid());
+        assert!(report.has_errors_for("ibm[1]"));
+        assert!(!report.has_errors_for("ibm[10]"));
+    }
+
+    #[test]
+    fn test_validate_valid_and_empty_configs() {
+        let config = ClientConfig::from_json(
+            r#"{"azure": [{
+                "access_token": "t",
+                "subscriptions": ["0A1b2c3d-0000-0000-0000-000000000000"],
+                "regions": ["westeurope"]
+            }]}"#,
+        )
+        .unwrap();
+        assert_eq!(validate(&config), ValidationReport::default());
+
+        let report = validate(&ClientConfig::default());
+        assert_eq!(report.errors().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_probe_skips_invalid_accounts() {
+        let config =
+            ClientConfig::from_json(r#"{"ibm": [{"api_key": "key", "enterprise_id": "short"}]}"#)
+                .unwrap();
+
+        let report = probe(&config).await;
+        assert!(report.probes.is_empty());
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_permission_checks() {
+        let (status, error) = ibm_permission_status(Some(CarbemError::Api(
+            "IBM API returned error 403 Forbidden: {}".to_string(),
+        )));
+        assert_eq!(status, PermissionStatus::Missing);
+        assert!(error.is_none());
+        assert_eq!(
+            ibm_permission_status(Some(CarbemError::Auth("expired".to_string()))).0,
+            PermissionStatus::Unverified
+        );
+
+        let check = PermissionCheck {
+            account: "azure[0]".to_string(),
+            scope: "subscriptions/00000000-0000-0000-0000-000000000001".to_string(),
+            required_role: CARBON_READER_ROLE.to_string(),
+            status: PermissionStatus::Missing,
+            missing_actions: vec!["Microsoft.Carbon/carbonEmissionReports/action".to_string()],
+            error: None,
+        };
+        assert_eq!(
+            check.to_string(),
+            "azure[0]: missing 'Carbon Optimization Reader' on \
+             subscriptions/00000000-0000-0000-0000-000000000001 \
+             (not granted: Microsoft.Carbon/carbonEmissionReports/action)"
+        );
+
+        let report = ValidationReport {
+            permissions: vec![check],
+            ..Default::default()
+        };
+        assert!(!report.is_valid());
+        assert_eq!(report.missing_permissions().count(), 1);
+    }
+
+    #[test]
+    fn test_last_full_month() {
+        let month = last_full_month();
+        assert_eq!(month.start.day(), 1);
+        assert!(month.end < Utc::now());
+        assert_eq!(month.start.month(), month.end.month());
+    }
+}
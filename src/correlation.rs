@@ -0,0 +1,127 @@
+//! Correlation IDs following a query through its requests
+//!
+//! Each [`CarbemClient::query_emissions`](crate::CarbemClient::query_emissions)
+//! call runs under a correlation ID, generated unless one is already set with
+//! [`with_correlation_id`], e.g. from an incoming request. The ID is sent
+//! with every provider request the query makes, retries, chunks and pages
+//! included: as `x-ms-client-request-id` to Azure and `X-Correlation-ID` to
+//! other providers. It is also recorded in the [audit log](crate::audit),
+//! named by failed queries' errors (see [`CarbemError::correlation_id`]) and,
+//! with the `tracing` feature, set on a `carbem_query` span around the query.
+//!
+//! [`CarbemError::correlation_id`]: crate::CarbemError::correlation_id
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+
+use reqwest::header::HeaderName;
+
+use crate::error::Result;
+
+tokio::task_local! {
+    // Correlation ID of the query being run by the current task
+    static CORRELATION_ID: String;
+}
+
+/// Header carrying the correlation ID in requests to providers other than Azure
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
+
+/// Header carrying the correlation ID in requests to Azure
+pub const AZURE_CORRELATION_HEADER: &str = "x-ms-client-request-id";
+
+/// Correlation ID of the query run by the current task, if any
+pub fn current() -> Option<String> {
+    CORRELATION_ID.try_with(Clone::clone).ok()
+}
+
+/// A new random correlation ID, in the form of a version 4 UUID
+pub fn generate() -> String {
+    // Each RandomState is seeded with fresh random keys
+    let random = |seed: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(seed);
+        hasher.finish()
+    };
+    let high = (random(0) & !0xf000) | 0x4000;
+    let low = (random(1) & !(0b11 << 62)) | (0b10 << 62);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// Run `future`, e.g. a query, under correlation ID `id`
+pub async fn with_correlation_id<F: Future>(id: impl Into<String>, future: F) -> F::Output {
+    let id = id.into();
+    #[cfg(feature = "tracing")]
+    let future = tracing::Instrument::instrument(
+        future,
+        tracing::info_span!("carbem_query", correlation_id = %id),
+    );
+    CORRELATION_ID.scope(id, future).await
+}
+
+// Run `future` under the current correlation ID, or a new one, naming it in errors
+pub(crate) async fn in_scope<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    match current() {
+        Some(id) => future.await.map_err(|e| e.with_correlation_id(&id)),
+        None => {
+            let id = generate();
+            with_correlation_id(id.clone(), future)
+                .await
+                .map_err(|e| e.with_correlation_id(&id))
+        }
+    }
+}
+
+// Header carrying the correlation ID in requests to `provider`
+pub(crate) fn header_name(provider: &str) -> HeaderName {
+    match provider {
+        "azure" => HeaderName::from_static(AZURE_CORRELATION_HEADER),
+        _ => HeaderName::from_static(CORRELATION_HEADER),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CarbemError;
+    use crate::exit::ExitStatus;
+
+    #[tokio::test]
+    async fn test_correlation_scopes() {
+        let id = generate();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert_ne!(id, generate());
+        assert_eq!(current(), None);
+
+        // Nested scopes keep the outer ID
+        let error = with_correlation_id("upstream-7", async {
+            in_scope(async {
+                assert_eq!(current().as_deref(), Some("upstream-7"));
+                in_scope(async { Err::<(), _>(CarbemError::RateLimit) }).await
+            })
+            .await
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(error.correlation_id(), Some("upstream-7"));
+        assert!(matches!(error.root(), CarbemError::RateLimit));
+        assert_eq!(ExitStatus::from(&error), ExitStatus::RateLimit);
+        assert_eq!(
+            error.to_string(),
+            "Rate limit exceeded (correlation ID: upstream-7)"
+        );
+
+        let error = in_scope(async { Err::<(), _>(CarbemError::RateLimit) })
+            .await
+            .unwrap_err();
+        assert_eq!(error.correlation_id().map(str::len), Some(36));
+    }
+}
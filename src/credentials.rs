@@ -0,0 +1,68 @@
+//! Provider credentials kept in the OS credential store
+//!
+//! Secrets are stored with the keyring service [`KEYRING_SERVICE`] under
+//! `<provider>:<profile>`, e.g. `azure:default`, in the macOS Keychain, the
+//! Windows Credential Manager or the Linux kernel keyring. They stay out of
+//! shell history, environment variables and configuration files.
+
+use crate::error::{CarbemError, Result};
+
+/// Keyring service under which carbem stores credentials
+pub const KEYRING_SERVICE: &str = "carbem";
+
+fn entry(provider: &str, profile: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("{}:{}", provider, profile))
+        .map_err(|e| keyring_error(provider, profile, e))
+}
+
+fn keyring_error(provider: &str, profile: &str, error: keyring::Error) -> CarbemError {
+    match error {
+        keyring::Error::NoEntry => CarbemError::Config(format!(
+            "no {} credential stored in the OS keyring for profile '{}'",
+            provider, profile
+        )),
+        other => CarbemError::Other(format!("OS keyring error: {}", other)),
+    }
+}
+
+/// Store the secret of `provider` for `profile`, replacing any previous one
+pub fn store_credential(provider: &str, profile: &str, secret: &str) -> Result<()> {
+    entry(provider, profile)?
+        .set_password(secret)
+        .map_err(|e| keyring_error(provider, profile, e))
+}
+
+/// Secret of `provider` stored for `profile`
+pub fn load_credential(provider: &str, profile: &str) -> Result<String> {
+    entry(provider, profile)?
+        .get_password()
+        .map_err(|e| keyring_error(provider, profile, e))
+}
+
+/// Remove the secret of `provider` stored for `profile`
+pub fn delete_credential(provider: &str, profile: &str) -> Result<()> {
+    entry(provider, profile)?
+        .delete_credential()
+        .map_err(|e| keyring_error(provider, profile, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_credential() {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+
+        let error = load_credential("azure", "missing").unwrap_err();
+        assert!(matches!(error, CarbemError::Config(_)));
+        assert!(error.to_string().contains("profile 'missing'"));
+
+        let error = keyring_error(
+            "ibm",
+            "default",
+            keyring::Error::NoStorageAccess("x".into()),
+        );
+        assert!(matches!(error, CarbemError::Other(_)));
+    }
+}
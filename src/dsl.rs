@@ -0,0 +1,527 @@
+//! Fluent DSL answering common questions in one expression
+//!
+//! [`emissions`] starts an [`EmissionsRequest`]: pick providers, a period
+//! relative to today, filters and a breakdown, then [`fetch`](EmissionsRequest::fetch)
+//! it from a [`CarbemClient`](crate::CarbemClient) or any other
+//! [`EmissionSource`]. The answer is a [`Breakdown`], ready to render as
+//! Markdown or HTML, or to turn into a [`Report`].
+//!
+//! ```rust,no_run
+//! # async fn run(client: &carbem::CarbemClient) -> carbem::Result<()> {
+//! let top = carbem::emissions()
+//!     .azure()
+//!     .last_quarter()
+//!     .by_service()
+//!     .top(10)
+//!     .fetch(client)
+//!     .await?;
+//! println!("{}", top.to_markdown());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests carry no provider configuration: set it once on the client with
+//! [`with_query_defaults`](crate::CarbemClientBuilder::with_query_defaults),
+//! or per request with [`EmissionsRequest::with_config`].
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::client::EmissionSource;
+use crate::error::{CarbemError, Result};
+use crate::i18n::Locale;
+use crate::models::{EmissionQuery, TagFilter, TimePeriod};
+use crate::providers::config::ProviderQueryConfig;
+use crate::report::{Report, ReportSection, dimension_name};
+
+/// Start an emissions request, for the last complete month until a period is chosen
+pub fn emissions() -> EmissionsRequest {
+    EmissionsRequest::default()
+}
+
+// Period of a request, resolved against its reference date when fetched
+#[derive(Debug, Clone, Default)]
+enum Period {
+    #[default]
+    LastMonth,
+    LastMonths(u32),
+    LastQuarter,
+    LastYear,
+    YearToDate,
+    Between(TimePeriod),
+}
+
+/// Grouping of the rows of a [`Breakdown`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grouping {
+    /// One row per value of a dimension, largest first
+    Dimension(Dimension),
+
+    /// One row per month, oldest first
+    Month,
+}
+
+/// An emissions question being put together, see [`emissions`]
+#[derive(Debug, Clone, Default)]
+pub struct EmissionsRequest {
+    providers: Vec<String>,
+    period: Period,
+    as_of: Option<DateTime<Utc>>,
+    regions: Vec<String>,
+    services: Option<Vec<String>>,
+    tag_filters: Vec<TagFilter>,
+    configs: Vec<ProviderQueryConfig>,
+    grouping: Option<Grouping>,
+    top: Option<usize>,
+    title: Option<String>,
+    locale: Locale,
+}
+
+impl EmissionsRequest {
+    /// Include Azure emissions
+    pub fn azure(self) -> Self {
+        self.provider("azure")
+    }
+
+    /// Include IBM Cloud emissions
+    pub fn ibm(self) -> Self {
+        self.provider("ibm")
+    }
+
+    /// Include the emissions of `provider`, e.g. `azure`
+    pub fn provider(mut self, provider: &str) -> Self {
+        if !self.providers.iter().any(|p| p == provider) {
+            self.providers.push(provider.to_string());
+        }
+        self
+    }
+
+    /// Cover the last complete calendar month
+    pub fn last_month(mut self) -> Self {
+        self.period = Period::LastMonth;
+        self
+    }
+
+    /// Cover the last `months` complete calendar months
+    pub fn last_months(mut self, months: u32) -> Self {
+        self.period = Period::LastMonths(months.max(1));
+        self
+    }
+
+    /// Cover the last complete calendar quarter
+    pub fn last_quarter(mut self) -> Self {
+        self.period = Period::LastQuarter;
+        self
+    }
+
+    /// Cover the last complete calendar year
+    pub fn last_year(mut self) -> Self {
+        self.period = Period::LastYear;
+        self
+    }
+
+    /// Cover the current calendar year up to today
+    pub fn year_to_date(mut self) -> Self {
+        self.period = Period::YearToDate;
+        self
+    }
+
+    /// Cover `period`
+    pub fn between(mut self, period: TimePeriod) -> Self {
+        self.period = Period::Between(period);
+        self
+    }
+
+    /// Resolve relative periods against `as_of` instead of now, e.g. in tests
+    pub fn as_of(mut self, as_of: DateTime<Utc>) -> Self {
+        self.as_of = Some(as_of);
+        self
+    }
+
+    /// Only include emissions of `regions`
+    pub fn regions<S: Into<String>>(mut self, regions: impl IntoIterator<Item = S>) -> Self {
+        self.regions.extend(regions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Only include emissions of `services`
+    pub fn services<S: Into<String>>(mut self, services: impl IntoIterator<Item = S>) -> Self {
+        self.services
+            .get_or_insert_with(Vec::new)
+            .extend(services.into_iter().map(Into::into));
+        self
+    }
+
+    /// Only include emissions tagged `key` with `value`
+    pub fn tagged(mut self, key: &str, value: &str) -> Self {
+        self.tag_filters.push(TagFilter::equals(key, value));
+        self
+    }
+
+    /// Query the provider of `config` with it, merged over the client query defaults
+    pub fn with_config(mut self, config: ProviderQueryConfig) -> Self {
+        self.configs.retain(|c| c.provider() != config.provider());
+        self.configs.push(config);
+        self
+    }
+
+    /// Break emissions down by provider
+    pub fn by_provider(self) -> Self {
+        self.grouped(Grouping::Dimension(Dimension::Provider))
+    }
+
+    /// Break emissions down by region
+    pub fn by_region(self) -> Self {
+        self.grouped(Grouping::Dimension(Dimension::Region))
+    }
+
+    /// Break emissions down by service
+    pub fn by_service(self) -> Self {
+        self.grouped(Grouping::Dimension(Dimension::Service))
+    }
+
+    /// Break emissions down by [service category](crate::taxonomy::ServiceCategory)
+    pub fn by_service_category(self) -> Self {
+        self.grouped(Grouping::Dimension(Dimension::ServiceCategory))
+    }
+
+    /// Break emissions down by month
+    pub fn by_month(self) -> Self {
+        self.grouped(Grouping::Month)
+    }
+
+    fn grouped(mut self, grouping: Grouping) -> Self {
+        self.grouping = Some(grouping);
+        self
+    }
+
+    /// Keep the `n` largest rows of the breakdown
+    ///
+    /// The total still covers every emission.
+    pub fn top(mut self, n: usize) -> Self {
+        self.top = Some(n);
+        self
+    }
+
+    /// Title of the breakdown, instead of one derived from the request
+    pub fn titled(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Label rendered breakdowns in `locale`
+    pub fn in_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Period covered by the request, with relative periods resolved
+    ///
+    /// Fails when the period starts before the earliest supported date.
+    pub fn period(&self) -> Result<TimePeriod> {
+        let today = self.as_of.unwrap_or_else(Utc::now).date_naive();
+        let this_month = today.with_day(1).unwrap_or(today);
+        let this_year = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap_or(today);
+        let months_before = |date: NaiveDate, months: u32| {
+            date.checked_sub_months(Months::new(months)).ok_or_else(|| {
+                CarbemError::Config(format!(
+                    "emissions request period starts {} months before {}, out of range",
+                    months, date
+                ))
+            })
+        };
+        let (start, end) = match &self.period {
+            Period::LastMonth => (months_before(this_month, 1)?, this_month),
+            Period::LastMonths(months) => (months_before(this_month, *months)?, this_month),
+            Period::LastQuarter => {
+                let quarter = months_before(this_month, this_month.month0() % 3)?;
+                (months_before(quarter, 3)?, quarter)
+            }
+            Period::LastYear => (months_before(this_year, 12)?, this_year),
+            Period::YearToDate => (this_year, today + Duration::days(1)),
+            Period::Between(period) => return Ok(period.clone()),
+        };
+        // Periods end on the last second of their last day, as parsed dates do
+        Ok(TimePeriod {
+            start: start.and_time(NaiveTime::MIN).and_utc(),
+            end: end.and_time(NaiveTime::MIN).and_utc() - Duration::seconds(1),
+        })
+    }
+
+    /// One query per provider of the request
+    pub fn to_queries(&self) -> Result<Vec<EmissionQuery>> {
+        self.queries_for(&self.period()?)
+    }
+
+    // One query per provider of the request, covering `time_period`
+    fn queries_for(&self, time_period: &TimePeriod) -> Result<Vec<EmissionQuery>> {
+        if self.providers.is_empty() {
+            return Err(CarbemError::Config(
+                "emissions request has no provider, add one with azure(), ibm() or provider()"
+                    .to_string(),
+            ));
+        }
+        Ok(self
+            .providers
+            .iter()
+            .map(|provider| EmissionQuery {
+                provider: provider.clone(),
+                regions: self.regions.clone(),
+                time_period: time_period.clone(),
+                services: self.services.clone(),
+                resources: None,
+                provider_config: self
+                    .configs
+                    .iter()
+                    .find(|config| config.provider() == provider)
+                    .cloned(),
+                raw_response: Default::default(),
+                date_alignment: Default::default(),
+                timezone: Default::default(),
+                dry_run: false,
+                route: Vec::new(),
+                tag_filters: self.tag_filters.clone(),
+                strict: false,
+            })
+            .collect())
+    }
+
+    /// Run the queries of the request against `source` and break the result down
+    pub async fn fetch<S: EmissionSource + ?Sized>(&self, source: &S) -> Result<Breakdown> {
+        // Resolved once, so the breakdown reports the period queried
+        let period = self.period()?;
+        let mut dataset = EmissionDataset::default();
+        for query in self.queries_for(&period)? {
+            dataset.extend(source.query_emissions(&query).await?);
+        }
+        Ok(self.breakdown(dataset, &period))
+    }
+
+    // Breakdown of `dataset`, covering `period`, as the request asks
+    fn breakdown(&self, dataset: EmissionDataset, period: &TimePeriod) -> Breakdown {
+        let total = dataset.total_kg_co2eq();
+        let mut rows: Vec<(String, f64)> = match self.grouping {
+            Some(Grouping::Dimension(dimension)) => {
+                let mut rows: Vec<_> = dataset.group_by_dimension(dimension).into_iter().collect();
+                rows.sort_by(|a, b| b.1.total_cmp(&a.1));
+                rows
+            }
+            Some(Grouping::Month) => dataset
+                .monthly_series()
+                .into_iter()
+                .map(|(month, kg)| (month.format("%Y-%m").to_string(), kg))
+                .collect(),
+            None => Vec::new(),
+        };
+        if let Some(n) = self.top {
+            if self.grouping == Some(Grouping::Month) {
+                // Largest months, still oldest first
+                let mut largest = rows.clone();
+                largest.sort_by(|a, b| b.1.total_cmp(&a.1));
+                largest.truncate(n);
+                rows.retain(|row| largest.contains(row));
+            } else {
+                rows.truncate(n);
+            }
+        }
+
+        Breakdown {
+            title: self.title.clone().unwrap_or_else(|| self.default_title()),
+            providers: self.providers.clone(),
+            period: (period.start, period.end),
+            grouping: self.grouping,
+            total_kg_co2eq: total,
+            rows: rows
+                .into_iter()
+                .map(|(key, kg)| BreakdownRow {
+                    share_percent: if total == 0.0 {
+                        0.0
+                    } else {
+                        kg / total * 100.0
+                    },
+                    key,
+                    kg_co2eq: kg,
+                })
+                .collect(),
+            locale: self.locale,
+            dataset,
+        }
+    }
+
+    // E.g. `azure emissions by service, top 10`
+    fn default_title(&self) -> String {
+        let mut title = format!("{} emissions", self.providers.join(" and "));
+        match self.grouping {
+            Some(Grouping::Dimension(dimension)) => {
+                title.push_str(&format!(
+                    " by {}",
+                    dimension_name(dimension).replace('-', " ")
+                ));
+            }
+            Some(Grouping::Month) => title.push_str(" by month"),
+            None => {}
+        }
+        if let Some(n) = self.top {
+            title.push_str(&format!(", top {}", n));
+        }
+        title
+    }
+}
+
+/// A row of a [`Breakdown`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BreakdownRow {
+    /// Value of the grouping, e.g. a service name or a `YYYY-MM` month
+    pub key: String,
+
+    /// Emissions in kg CO2e
+    pub kg_co2eq: f64,
+
+    /// Share of the total, in percent
+    pub share_percent: f64,
+}
+
+/// Answer to an [`EmissionsRequest`]: a total and its breakdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Breakdown {
+    /// Title, derived from the request unless one was given
+    pub title: String,
+
+    /// Providers queried
+    pub providers: Vec<String>,
+
+    /// Start and end of the period queried
+    pub period: (DateTime<Utc>, DateTime<Utc>),
+
+    /// Grouping of the rows, `None` for a total only
+    pub grouping: Option<Grouping>,
+
+    /// Total emissions of the period in kg CO2e, rows left out by `top` included
+    pub total_kg_co2eq: f64,
+
+    /// Rows of the breakdown
+    pub rows: Vec<BreakdownRow>,
+
+    /// Language of rendered labels and numbers
+    #[serde(default)]
+    pub locale: Locale,
+
+    /// Emissions the breakdown was computed from
+    #[serde(skip)]
+    pub dataset: EmissionDataset,
+}
+
+impl Breakdown {
+    /// The breakdown as a report with one section
+    pub fn to_report(&self) -> Report {
+        let locale = self.locale;
+        let name = match self.grouping {
+            Some(Grouping::Dimension(dimension)) => Some(dimension_name(dimension)),
+            Some(Grouping::Month) => Some("month"),
+            None => None,
+        };
+        let sections = name
+            .map(|name| ReportSection {
+                heading: locale.message(&format!("section-{}", name)),
+                columns: vec![
+                    match self.grouping {
+                        Some(Grouping::Month) => locale.message("report-month"),
+                        _ => locale.message(&format!("dimension-{}", name)),
+                    },
+                    locale.message("unit-kg-co2e"),
+                    locale.message("report-share"),
+                ],
+                rows: self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let share = locale.format(
+                            "report-share-value",
+                            &[("value", &locale.format_number(row.share_percent, 1))],
+                        );
+                        vec![
+                            row.key.clone(),
+                            locale.format_number(row.kg_co2eq, 2),
+                            share,
+                        ]
+                    })
+                    .collect(),
+            })
+            .into_iter()
+            .collect();
+        Report {
+            title: self.title.clone(),
+            period: Some(self.period),
+            total_kg_co2eq: self.total_kg_co2eq,
+            sections,
+            locale,
+        }
+    }
+
+    /// Render as GitHub-flavored Markdown
+    pub fn to_markdown(&self) -> String {
+        self.to_report().to_markdown()
+    }
+
+    /// Render as an XHTML fragment
+    pub fn to_html(&self) -> String {
+        self.to_report().to_html()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CarbemClient;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_top_services_of_last_quarter() {
+        let client = CarbemClient::demo();
+        let request = emissions()
+            .azure()
+            .last_quarter()
+            .as_of(Utc.with_ymd_and_hms(2024, 5, 15, 8, 0, 0).unwrap())
+            .by_service()
+            .top(2);
+
+        let period = request.period().unwrap();
+        assert_eq!(
+            period.start,
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            period.end,
+            Utc.with_ymd_and_hms(2024, 3, 31, 23, 59, 59).unwrap()
+        );
+
+        let top = request.fetch(&client).await.unwrap();
+        assert_eq!(top.title, "azure emissions by service, top 2");
+        assert_eq!(top.rows.len(), 2);
+        assert!(top.rows[0].kg_co2eq >= top.rows[1].kg_co2eq);
+        // The total covers the services left out
+        assert!((top.total_kg_co2eq - top.dataset.total_kg_co2eq()).abs() < 1e-9);
+        assert!(top.rows.iter().map(|row| row.kg_co2eq).sum::<f64>() < top.total_kg_co2eq);
+
+        let markdown = top.to_markdown();
+        assert!(markdown.starts_with("# azure emissions by service, top 2"));
+        assert!(markdown.contains(&top.rows[0].key));
+
+        let months = emissions()
+            .azure()
+            .ibm()
+            .last_months(3)
+            .as_of(Utc.with_ymd_and_hms(2024, 5, 15, 8, 0, 0).unwrap())
+            .by_month()
+            .fetch(&client)
+            .await
+            .unwrap();
+        let keys: Vec<&str> = months.rows.iter().map(|row| row.key.as_str()).collect();
+        assert_eq!(keys, vec!["2024-02", "2024-03", "2024-04"]);
+
+        assert!(emissions().last_month().to_queries().is_err());
+        assert!(emissions().azure().last_months(u32::MAX).period().is_err());
+    }
+}
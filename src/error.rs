@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::models::ConversionIssue;
+
 /// The main error type for the Carbem library.
 #[derive(Error, Debug)]
 pub enum CarbemError {
@@ -31,6 +33,26 @@ pub enum CarbemError {
     #[error("Rate limit exceeded")]
     RateLimit,
 
+    /// Provider call quota exhausted, see [`Quota`](crate::quota::Quota)
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    /// Caller of a carbem service without valid credentials
+    #[error("Unauthenticated: {0}")]
+    Unauthenticated(String),
+
+    /// Query outside the caller's [access policy](crate::policy::AccessPolicy)
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    /// Unknown or already released FFI client handle
+    #[error("Invalid client handle: {0}")]
+    InvalidHandle(u64),
+
+    /// Provider rows that a strict query could not convert without defaults
+    #[error("{} row(s) could not be converted: {}", .0.len(), describe(.0))]
+    LossyConversion(Vec<ConversionIssue>),
+
     /// API error (non-HTTP errors from cloud providers)
     #[error("API error: {0}")]
     Api(String),
@@ -38,6 +60,83 @@ pub enum CarbemError {
     /// Generic error
     #[error("An error occurred: {0}")]
     Other(String),
+
+    /// Error of the query with a [correlation ID](crate::correlation)
+    #[error("{source} (correlation ID: {correlation_id})")]
+    Correlated {
+        /// Correlation ID of the query
+        correlation_id: String,
+
+        /// The error itself
+        source: Box<CarbemError>,
+    },
+}
+
+impl CarbemError {
+    /// Correlation ID of the query that failed, if known
+    pub fn correlation_id(&self) -> Option<&str> {
+        match self {
+            CarbemError::Correlated { correlation_id, .. } => Some(correlation_id),
+            _ => None,
+        }
+    }
+
+    /// The error without its correlation ID
+    pub fn root(&self) -> &CarbemError {
+        match self {
+            CarbemError::Correlated { source, .. } => source,
+            other => other,
+        }
+    }
+
+    // The error with correlation ID `id`, unless it already has one
+    pub(crate) fn with_correlation_id(self, id: &str) -> Self {
+        match self {
+            CarbemError::Correlated { .. } => self,
+            other => CarbemError::Correlated {
+                correlation_id: id.to_string(),
+                source: Box::new(other),
+            },
+        }
+    }
+
+    // A copy of the error for another caller, e.g. of a coalesced query
+    //
+    // HTTP and JSON errors cannot be cloned: their copies keep the message
+    // and the class, as a provider and a configuration error respectively.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            CarbemError::Http(e) => CarbemError::Provider(format!("HTTP request failed: {}", e)),
+            CarbemError::Json(e) => CarbemError::Config(format!("JSON error: {}", e)),
+            CarbemError::Provider(m) => CarbemError::Provider(m.clone()),
+            CarbemError::UnsupportedProvider(m) => CarbemError::UnsupportedProvider(m.clone()),
+            CarbemError::Config(m) => CarbemError::Config(m.clone()),
+            CarbemError::Auth(m) => CarbemError::Auth(m.clone()),
+            CarbemError::RateLimit => CarbemError::RateLimit,
+            CarbemError::QuotaExceeded(m) => CarbemError::QuotaExceeded(m.clone()),
+            CarbemError::Unauthenticated(m) => CarbemError::Unauthenticated(m.clone()),
+            CarbemError::AccessDenied(m) => CarbemError::AccessDenied(m.clone()),
+            CarbemError::InvalidHandle(handle) => CarbemError::InvalidHandle(*handle),
+            CarbemError::LossyConversion(issues) => CarbemError::LossyConversion(issues.clone()),
+            CarbemError::Api(m) => CarbemError::Api(m.clone()),
+            CarbemError::Other(m) => CarbemError::Other(m.clone()),
+            CarbemError::Correlated {
+                correlation_id,
+                source,
+            } => CarbemError::Correlated {
+                correlation_id: correlation_id.clone(),
+                source: Box::new(source.duplicate()),
+            },
+        }
+    }
+}
+
+fn describe(issues: &[ConversionIssue]) -> String {
+    issues
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 /// Type alias for Result with CarbemError
@@ -0,0 +1,160 @@
+//! Cost estimates of emission queries
+//!
+//! [`CarbemClient::estimate_query`](crate::CarbemClient::estimate_query)
+//! sanity-checks a query, e.g. a multi-year backfill, before it is run: how
+//! many requests it sends, how many result pages it selects and roughly how
+//! long it takes. Nothing is sent; requests are counted from the query's
+//! [dry-run plan](crate::providers::CarbonProvider::plan_requests).
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::models::EmissionQuery;
+use crate::providers::DynCarbonProvider;
+use crate::providers::config::ProviderQueryConfig;
+
+/// Duration assumed for one provider request, response included
+pub const ASSUMED_REQUEST_LATENCY: Duration = Duration::from_secs(2);
+
+/// Expected cost of an emission query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryEstimate {
+    /// Provider answering the query, the first of its route
+    pub provider: String,
+
+    /// Months of data the query covers
+    pub months: u32,
+
+    /// Requests the query sends, discovery included
+    pub api_calls: usize,
+
+    /// Optional: result pages selected; pages after the first take one more query each
+    pub pages: Option<u64>,
+
+    /// Approximate time to retrieve every known page, at [`ASSUMED_REQUEST_LATENCY`] per request
+    pub approximate_duration: Duration,
+
+    /// What the estimate cannot account for
+    pub notes: Vec<String>,
+}
+
+impl QueryEstimate {
+    // Estimate `query` sent to `provider`
+    pub(crate) fn new(provider: &dyn DynCarbonProvider, query: &EmissionQuery) -> Result<Self> {
+        let months = query
+            .time_period
+            .month_count(query.date_alignment, &query.timezone)?;
+        let api_calls = provider.plan_requests(query)?.len();
+        let pages = provider.expected_pages(query);
+
+        let mut notes = Vec::new();
+        match pages {
+            Some(pages) if pages > 1 => notes.push(format!(
+                "each query retrieves one page: {} more queries, with an offset or skip token, retrieve the rest",
+                pages - 1
+            )),
+            Some(_) => {}
+            None => notes.push("the number of result pages depends on provider data".to_string()),
+        }
+        if let Some(ProviderQueryConfig::Ibm(config)) = &query.provider_config
+            && config.account_group_id.is_some()
+        {
+            notes.push("accounts of the group are discovered at query time and requested one by one, one request each".to_string());
+        }
+
+        let requests = api_calls as u64 + pages.unwrap_or(1).saturating_sub(1);
+        Ok(Self {
+            provider: provider.name().to_string(),
+            months,
+            api_calls,
+            pages,
+            approximate_duration: ASSUMED_REQUEST_LATENCY * requests as u32,
+            notes,
+        })
+    }
+}
+
+impl fmt::Display for QueryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} month(s), {} request(s), {} page(s), about {}s",
+            self.provider,
+            self.months,
+            self.api_calls,
+            self.pages
+                .map_or_else(|| "unknown".to_string(), |pages| pages.to_string()),
+            self.approximate_duration.as_secs()
+        )?;
+        for note in &self.notes {
+            write!(f, "\n- {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::CarbemClient;
+    use crate::providers::azure::AzureConfig;
+    use crate::providers::ibm::IbmConfig;
+
+    #[test]
+    fn test_estimate_backfills() {
+        let client = CarbemClient::builder()
+            .with_azure(AzureConfig {
+                access_token: "token".to_string(),
+            })
+            .unwrap()
+            .with_ibm(IbmConfig {
+                api_key: "key".to_string(),
+            })
+            .unwrap()
+            .build();
+        let estimate =
+            |query: &str| client.estimate_query(&EmissionQuery::from_query_string(query).unwrap());
+
+        // One report request covers every subscription and month
+        let subscriptions: Vec<String> = (0..50).map(|i| format!("sub-{}", i)).collect();
+        let azure = estimate(&format!(
+            "provider=azure&regions=westeurope&start=2022-01-01&end=2024-12-31\
+             &config.report_type=MonthlySummaryReport\
+             &config.subscription_list={}",
+            subscriptions.join(",")
+        ))
+        .unwrap();
+        assert_eq!(
+            (azure.months, azure.api_calls, azure.pages),
+            (36, 1, Some(1))
+        );
+        assert_eq!(azure.approximate_duration, ASSUMED_REQUEST_LATENCY);
+        assert!(azure.notes.is_empty());
+
+        // 36 monthly rows at 10 per page
+        let ibm = estimate(
+            "provider=ibm&start=2022-01-01&end=2024-12-31&config.enterprise_id=e\
+             &config.enterprise_account_id=a",
+        )
+        .unwrap();
+        assert_eq!((ibm.api_calls, ibm.pages), (1, Some(4)));
+        assert_eq!(ibm.approximate_duration, ASSUMED_REQUEST_LATENCY * 4);
+        assert!(
+            ibm.to_string()
+                .starts_with("ibm: 36 month(s), 1 request(s), 4 page(s), about 8s")
+        );
+
+        let grouped = estimate(
+            "provider=ibm&start=2024-01-01&end=2024-12-31&config.enterprise_id=e\
+             &config.account_group_id=g",
+        )
+        .unwrap();
+        assert_eq!((grouped.api_calls, grouped.pages), (3, None));
+        assert_eq!(grouped.notes.len(), 2);
+
+        assert!(estimate("provider=ibm&start=2024-01-01&end=2024-12-31").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Process exit statuses for tools automating carbem, e.g. cron jobs or CI steps
+//!
+//! Every failure class has its own stable code, so scripts can react to an
+//! expired credential differently from a rate limit or a budget breach.
+//!
+//! | Code | Status |
+//! |------|--------|
+//! | 0 | [`ExitStatus::Success`] |
+//! | 1 | [`ExitStatus::Error`] |
+//! | 2 | [`ExitStatus::Config`] |
+//! | 3 | [`ExitStatus::Auth`] |
+//! | 4 | [`ExitStatus::RateLimit`] |
+//! | 5 | [`ExitStatus::Provider`] |
+//! | 6 | [`ExitStatus::NoData`] |
+//! | 7 | [`ExitStatus::BudgetBreach`] |
+
+use crate::error::{CarbemError, Result};
+use crate::models::CarbonEmission;
+use crate::notify::Summary;
+
+/// Outcome of a run, mapped to a process exit code
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The run succeeded
+    Success = 0,
+
+    /// Unclassified failure
+    Error = 1,
+
+    /// Invalid configuration or query, or unknown provider
+    Config = 2,
+
+    /// Credentials were rejected or do not allow the query
+    Auth = 3,
+
+    /// The provider rate limit or call quota was exceeded; retrying later may succeed
+    RateLimit = 4,
+
+    /// The provider API failed or could not be reached
+    Provider = 5,
+
+    /// The query succeeded but returned no emissions
+    NoData = 6,
+
+    /// Emissions exceeded the configured budget
+    BudgetBreach = 7,
+}
+
+impl ExitStatus {
+    /// Numeric exit code
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Status of a query: its error class, or [`NoData`](Self::NoData) when no emission was returned
+    pub fn for_emissions(result: &Result<Vec<CarbonEmission>>) -> Self {
+        match result {
+            Ok(emissions) if emissions.is_empty() => ExitStatus::NoData,
+            Ok(_) => ExitStatus::Success,
+            Err(e) => e.into(),
+        }
+    }
+
+    /// [`BudgetBreach`](Self::BudgetBreach) when `summary` exceeds its budget
+    pub fn for_summary(summary: &Summary) -> Self {
+        if summary
+            .budget
+            .as_ref()
+            .is_some_and(|budget| budget.used_percent > 100.0)
+        {
+            ExitStatus::BudgetBreach
+        } else {
+            ExitStatus::Success
+        }
+    }
+}
+
+impl From<&CarbemError> for ExitStatus {
+    fn from(error: &CarbemError) -> Self {
+        match error {
+            CarbemError::Config(_)
+            | CarbemError::Json(_)
+            | CarbemError::UnsupportedProvider(_)
+            | CarbemError::InvalidHandle(_) => ExitStatus::Config,
+            CarbemError::Auth(_)
+            | CarbemError::Unauthenticated(_)
+            | CarbemError::AccessDenied(_) => ExitStatus::Auth,
+            CarbemError::RateLimit | CarbemError::QuotaExceeded(_) => ExitStatus::RateLimit,
+            CarbemError::Http(_)
+            | CarbemError::Provider(_)
+            | CarbemError::Api(_)
+            | CarbemError::LossyConversion(_) => ExitStatus::Provider,
+            CarbemError::Other(_) => ExitStatus::Error,
+            CarbemError::Correlated { source, .. } => ExitStatus::from(source.as_ref()),
+        }
+    }
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        std::process::ExitCode::from(status.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregation::EmissionDataset;
+    use crate::models::TimePeriod;
+    use crate::notify::SummaryPeriod;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_status_of_query_results() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let emission = CarbonEmission {
+            provider: "ibm".to_string(),
+            region: "dallas".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 10.0,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        };
+
+        assert_eq!(
+            ExitStatus::for_emissions(&Ok(vec![emission.clone()])),
+            ExitStatus::Success
+        );
+        assert_eq!(ExitStatus::for_emissions(&Ok(vec![])).code(), 6);
+        assert_eq!(
+            ExitStatus::for_emissions(&Err(CarbemError::RateLimit)).code(),
+            4
+        );
+        assert_eq!(
+            ExitStatus::from(&CarbemError::Auth("expired".to_string())),
+            ExitStatus::Auth
+        );
+
+        let as_of = Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap();
+        let summary = Summary::compute(
+            &EmissionDataset::new(vec![emission]),
+            SummaryPeriod::Monthly,
+            as_of,
+        );
+        assert_eq!(
+            ExitStatus::for_summary(&summary.clone().with_budget_kg_co2eq(5.0)),
+            ExitStatus::BudgetBreach
+        );
+        assert_eq!(
+            ExitStatus::for_summary(&summary.with_budget_kg_co2eq(50.0)),
+            ExitStatus::Success
+        );
+    }
+}
@@ -2,15 +2,27 @@
 //!
 //! This module provides simple JSON-based functions that can be easily
 //! called from Python using PyO3 or from TypeScript using NAPI-RS.
+//!
+//! Large item-level datasets are costly to pass as JSON. The `_encoded`
+//! functions take query payloads and return emissions in a
+//! [`PayloadFormat`] chosen by the binding: MessagePack with the `msgpack`
+//! feature, CBOR with the `cbor` feature, or JSON.
 
 use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
 
 use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde_json;
 
 use crate::client::CarbemClient;
 use crate::error::{CarbemError, Result};
-use crate::models::{CarbonEmission, EmissionQuery, TimePeriod};
+use crate::models::{
+    CarbonEmission, DateAlignment, EmissionQuery, EmissionResult, QueryTimezone, RawResponseMode,
+    TimePeriod,
+};
 use crate::providers::azure::AzureConfig;
 use crate::providers::config::ProviderQueryConfig;
 use crate::providers::ibm::IbmConfig;
@@ -44,6 +56,278 @@ pub async fn get_emissions(
     client.query_emissions(&query).await
 }
 
+/// FFI-friendly function to get emissions together with the raw provider responses
+///
+/// Takes the same parameters as [`get_emissions`]. The payload may set
+/// `"raw_response"` to `"alongside"` (default for this function) or `"only"`.
+pub async fn get_emissions_with_raw(
+    provider: &str,
+    json_config: &str,
+    json_payload: &str,
+) -> Result<EmissionResult> {
+    let client = create_client_from_json(provider, json_config)?;
+    let mut query = parse_emission_query_from_json(provider, json_payload)?;
+    if query.raw_response == RawResponseMode::None {
+        query.raw_response = RawResponseMode::Alongside;
+    }
+    client.query_emissions_with_raw(&query).await
+}
+
+/// Serialization of FFI payloads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// JSON text
+    #[default]
+    Json,
+
+    /// MessagePack, with struct fields as map keys
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+
+    /// CBOR (RFC 8949)
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl PayloadFormat {
+    /// Name of the format, as parsed by [`FromStr`]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadFormat::Json => "json",
+            #[cfg(feature = "msgpack")]
+            PayloadFormat::MessagePack => "msgpack",
+            #[cfg(feature = "cbor")]
+            PayloadFormat::Cbor => "cbor",
+        }
+    }
+
+    /// Serialize `value` in this format
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            PayloadFormat::Json => Ok(serde_json::to_vec(value)?),
+            #[cfg(feature = "msgpack")]
+            PayloadFormat::MessagePack => rmp_serde::to_vec_named(value)
+                .map_err(|e| CarbemError::Other(format!("MessagePack encoding failed: {}", e))),
+            #[cfg(feature = "cbor")]
+            PayloadFormat::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes)
+                    .map_err(|e| CarbemError::Other(format!("CBOR encoding failed: {}", e)))?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Deserialize a value from `bytes` in this format
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        match self {
+            PayloadFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            #[cfg(feature = "msgpack")]
+            PayloadFormat::MessagePack => rmp_serde::from_slice(bytes)
+                .map_err(|e| CarbemError::Config(format!("invalid MessagePack payload: {}", e))),
+            #[cfg(feature = "cbor")]
+            PayloadFormat::Cbor => ciborium::from_reader(bytes)
+                .map_err(|e| CarbemError::Config(format!("invalid CBOR payload: {}", e))),
+        }
+    }
+}
+
+impl FromStr for PayloadFormat {
+    type Err = CarbemError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(PayloadFormat::Json),
+            #[cfg(feature = "msgpack")]
+            "msgpack" | "messagepack" => Ok(PayloadFormat::MessagePack),
+            #[cfg(feature = "cbor")]
+            "cbor" => Ok(PayloadFormat::Cbor),
+            _ => Err(CarbemError::Config(format!(
+                "unsupported payload format '{}' (MessagePack and CBOR need the `msgpack` and `cbor` features)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Like [`get_emissions`], with the query payload and the emissions in `format`
+///
+/// The configuration stays JSON.
+pub async fn get_emissions_encoded(
+    provider: &str,
+    json_config: &str,
+    payload: &[u8],
+    format: PayloadFormat,
+) -> Result<Vec<u8>> {
+    let client = create_client_from_json(provider, json_config)?;
+    let query = parse_emission_query(provider, format.decode(payload)?)?;
+    format.encode(&client.query_emissions(&query).await?)
+}
+
+/// Opaque handle identifying a client stored in the FFI registry
+pub type ClientHandle = u64;
+
+/// Status codes returned to bindings by handle-based functions
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfiStatus {
+    Ok = 0,
+    InvalidHandle = 1,
+    Config = 2,
+    Auth = 3,
+    Provider = 4,
+    Other = 5,
+}
+
+impl From<&CarbemError> for FfiStatus {
+    fn from(error: &CarbemError) -> Self {
+        match error {
+            CarbemError::InvalidHandle(_) => FfiStatus::InvalidHandle,
+            CarbemError::Config(_) | CarbemError::Json(_) | CarbemError::UnsupportedProvider(_) => {
+                FfiStatus::Config
+            }
+            CarbemError::Auth(_)
+            | CarbemError::Unauthenticated(_)
+            | CarbemError::AccessDenied(_) => FfiStatus::Auth,
+            CarbemError::Http(_)
+            | CarbemError::Provider(_)
+            | CarbemError::Api(_)
+            | CarbemError::LossyConversion(_)
+            | CarbemError::RateLimit
+            | CarbemError::QuotaExceeded(_) => FfiStatus::Provider,
+            CarbemError::Other(_) => FfiStatus::Other,
+            CarbemError::Correlated { source, .. } => FfiStatus::from(source.as_ref()),
+        }
+    }
+}
+
+/// Registry of clients owned by bindings, keyed by integer handles.
+///
+/// Handles are allocated from a monotonic counter and never reused, so a
+/// released handle stays invalid forever: double-release and use-after-release
+/// return `CarbemError::InvalidHandle` instead of touching another client.
+struct ClientRegistry {
+    next_handle: ClientHandle,
+    clients: HashMap<ClientHandle, Arc<CarbemClient>>,
+}
+
+static CLIENT_REGISTRY: LazyLock<Mutex<ClientRegistry>> = LazyLock::new(|| {
+    Mutex::new(ClientRegistry {
+        next_handle: 1,
+        clients: HashMap::new(),
+    })
+});
+
+fn registry() -> MutexGuard<'static, ClientRegistry> {
+    // A panic while holding the lock cannot leave the map half-updated,
+    // so recovering from poisoning is safe
+    CLIENT_REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Create a client from JSON configuration and store it in the FFI registry
+///
+/// The returned handle must be released with [`release_client`] once the
+/// binding no longer needs it.
+pub fn create_client(provider: &str, json_config: &str) -> Result<ClientHandle> {
+    let client = create_client_from_json(provider, json_config)?;
+    Ok(store_client(client))
+}
+
+/// Store a [`CarbemClient::demo`] client in the FFI registry
+///
+/// Queries on the handle are answered from the bundled sample dataset as the
+/// `azure` provider. Release it with [`release_client`].
+pub fn create_demo_client() -> ClientHandle {
+    store_client(CarbemClient::demo())
+}
+
+fn store_client(client: CarbemClient) -> ClientHandle {
+    let mut registry = registry();
+    let handle = registry.next_handle;
+    registry.next_handle += 1;
+    registry.clients.insert(handle, Arc::new(client));
+    handle
+}
+
+/// Release a client previously returned by [`create_client`]
+///
+/// Queries already running on the client complete normally, as they hold
+/// their own reference to it.
+pub fn release_client(handle: ClientHandle) -> Result<()> {
+    registry()
+        .clients
+        .remove(&handle)
+        .map(|_| ())
+        .ok_or(CarbemError::InvalidHandle(handle))
+}
+
+/// Query emissions using a client stored in the FFI registry
+pub async fn get_emissions_with_client(
+    handle: ClientHandle,
+    json_payload: &str,
+) -> Result<Vec<CarbonEmission>> {
+    let payload = PayloadFormat::Json.decode(json_payload.as_bytes())?;
+    query_with_client(handle, payload).await
+}
+
+/// Like [`get_emissions_with_client`], with the query payload and the emissions in `format`
+pub async fn get_emissions_with_client_encoded(
+    handle: ClientHandle,
+    payload: &[u8],
+    format: PayloadFormat,
+) -> Result<Vec<u8>> {
+    let emissions = query_with_client(handle, format.decode(payload)?).await?;
+    format.encode(&emissions)
+}
+
+async fn query_with_client(
+    handle: ClientHandle,
+    payload: HashMap<String, serde_json::Value>,
+) -> Result<Vec<CarbonEmission>> {
+    // Clone the Arc so the lock is not held across the request
+    let client = registry()
+        .clients
+        .get(&handle)
+        .cloned()
+        .ok_or(CarbemError::InvalidHandle(handle))?;
+
+    let provider = client
+        .available_providers()
+        .first()
+        .map(|name| name.to_string())
+        .ok_or(CarbemError::InvalidHandle(handle))?;
+    let query = parse_emission_query(&provider, payload)?;
+    client.query_emissions(&query).await
+}
+
+/// Owned registry handle releasing its client when dropped
+///
+/// Intended for bindings whose host objects have deterministic destructors
+/// (e.g. NAPI finalizers), so the registry cannot leak clients.
+#[derive(Debug)]
+pub struct OwnedClientHandle(ClientHandle);
+
+impl OwnedClientHandle {
+    /// Create a client and wrap its handle
+    pub fn create(provider: &str, json_config: &str) -> Result<Self> {
+        create_client(provider, json_config).map(Self)
+    }
+
+    /// Raw handle value to pass across the FFI boundary
+    pub fn handle(&self) -> ClientHandle {
+        self.0
+    }
+}
+
+impl Drop for OwnedClientHandle {
+    fn drop(&mut self) {
+        // Already released handles are simply ignored
+        let _ = release_client(self.0);
+    }
+}
+
 /// Create a configured client from JSON configuration
 fn create_client_from_json(provider: &str, json_config: &str) -> Result<CarbemClient> {
     match provider {
@@ -64,9 +348,15 @@ fn create_client_from_json(provider: &str, json_config: &str) -> Result<CarbemCl
 
 /// Parse EmissionQuery from JSON payload
 fn parse_emission_query_from_json(provider: &str, json_payload: &str) -> Result<EmissionQuery> {
-    let payload: HashMap<String, serde_json::Value> =
-        serde_json::from_str(json_payload).map_err(CarbemError::Json)?;
+    let payload = serde_json::from_str(json_payload).map_err(CarbemError::Json)?;
+    parse_emission_query(provider, payload)
+}
 
+/// Parse EmissionQuery from decoded payload fields
+fn parse_emission_query(
+    provider: &str,
+    payload: HashMap<String, serde_json::Value>,
+) -> Result<EmissionQuery> {
     let start_date = match payload.get("start_date") {
         Some(value) => match value.as_str() {
             Some(date_str) => DateTime::parse_from_rfc3339(date_str)
@@ -133,6 +423,60 @@ fn parse_emission_query_from_json(provider: &str, json_payload: &str) -> Result<
                 .collect()
         });
 
+    let raw_response = match payload.get("raw_response") {
+        Some(value) => serde_json::from_value(value.clone()).map_err(|_| {
+            CarbemError::Config(format!(
+                "Invalid raw_response {}: expected \"none\", \"alongside\" or \"only\"",
+                value
+            ))
+        })?,
+        None => RawResponseMode::None,
+    };
+
+    let date_alignment = match payload.get("date_alignment") {
+        Some(value) => serde_json::from_value(value.clone()).map_err(|_| {
+            CarbemError::Config(format!(
+                "Invalid date_alignment {}: expected \"expand_to_full_months\", \"truncate\" or \"strict\"",
+                value
+            ))
+        })?,
+        None => DateAlignment::default(),
+    };
+
+    let timezone = match payload.get("timezone") {
+        Some(serde_json::Value::String(value)) => value.parse::<QueryTimezone>()?,
+        Some(serde_json::Value::Null) | None => QueryTimezone::default(),
+        Some(_) => {
+            return Err(CarbemError::Config("timezone must be a string".to_string()));
+        }
+    };
+
+    let dry_run = match payload.get("dry_run") {
+        Some(serde_json::Value::Bool(value)) => *value,
+        Some(serde_json::Value::Null) | None => false,
+        Some(_) => {
+            return Err(CarbemError::Config("dry_run must be a boolean".to_string()));
+        }
+    };
+
+    let strict = match payload.get("strict") {
+        Some(serde_json::Value::Bool(value)) => *value,
+        Some(serde_json::Value::Null) | None => false,
+        Some(_) => {
+            return Err(CarbemError::Config("strict must be a boolean".to_string()));
+        }
+    };
+
+    let tag_filters = match payload.get("tag_filters") {
+        Some(serde_json::Value::Null) | None => Vec::new(),
+        Some(value) => serde_json::from_value(value.clone()).map_err(|e| {
+            CarbemError::Config(format!(
+                "Invalid tag_filters: {} (expected a list of {{\"key\", \"value\", \"prefix\"}} objects)",
+                e
+            ))
+        })?,
+    };
+
     // Parse provider-specific configuration
     let provider_config = match provider {
         "azure" => {
@@ -176,6 +520,14 @@ fn parse_emission_query_from_json(provider: &str, json_payload: &str) -> Result<
         services,
         resources,
         provider_config,
+        raw_response,
+        date_alignment,
+        timezone,
+        dry_run,
+        // FFI clients hold a single provider instance, so there is nothing to route
+        route: Vec::new(),
+        tag_filters,
+        strict,
     })
 }
 
@@ -257,6 +609,153 @@ mod tests {
         assert_eq!(query.regions, vec!["eastus"]);
     }
 
+    #[test]
+    fn test_parse_emission_query_raw_response() {
+        let json = r#"{
+            "regions": ["eastus"],
+            "report_type": "MonthlySummaryReport",
+            "subscription_list": ["sub-1"],
+            "raw_response": "only"
+        }"#;
+        let query = parse_emission_query_from_json("azure", json).unwrap();
+        assert_eq!(query.raw_response, RawResponseMode::Only);
+
+        let json_invalid = r#"{"regions": ["eastus"], "report_type": "MonthlySummaryReport", "subscription_list": ["sub-1"], "raw_response": "all"}"#;
+        let result = parse_emission_query_from_json("azure", json_invalid);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid raw_response")
+        );
+    }
+
+    #[test]
+    fn test_parse_emission_query_dry_run() {
+        let json = r#"{"regions": ["eastus"], "report_type": "MonthlySummaryReport", "subscription_list": ["sub-1"], "dry_run": true}"#;
+        assert!(
+            parse_emission_query_from_json("azure", json)
+                .unwrap()
+                .dry_run
+        );
+
+        let json = r#"{"regions": ["eastus"], "report_type": "MonthlySummaryReport", "subscription_list": ["sub-1"]}"#;
+        assert!(
+            !parse_emission_query_from_json("azure", json)
+                .unwrap()
+                .dry_run
+        );
+
+        let json_invalid = r#"{"regions": ["eastus"], "report_type": "MonthlySummaryReport", "subscription_list": ["sub-1"], "dry_run": "yes"}"#;
+        assert!(parse_emission_query_from_json("azure", json_invalid).is_err());
+    }
+
+    #[test]
+    fn test_client_handle_release() {
+        let handle = create_client("azure", r#"{"access_token": "test"}"#).unwrap();
+
+        assert!(release_client(handle).is_ok());
+        // Double release must be reported, not undefined behavior
+        let result = release_client(handle);
+        assert!(matches!(result, Err(CarbemError::InvalidHandle(h)) if h == handle));
+    }
+
+    #[tokio::test]
+    async fn test_client_handle_use_after_release() {
+        let handle = create_client("ibm", r#"{"api_key": "test"}"#).unwrap();
+        release_client(handle).unwrap();
+
+        let result = get_emissions_with_client(handle, "{}").await;
+        let error = result.unwrap_err();
+        assert_eq!(FfiStatus::from(&error), FfiStatus::InvalidHandle);
+    }
+
+    #[tokio::test]
+    async fn test_demo_client_handle() {
+        let handle = create_demo_client();
+        let emissions = get_emissions_with_client(
+            handle,
+            r#"{
+                "start_date": "2024-01-01T00:00:00Z",
+                "end_date": "2024-02-01T00:00:00Z",
+                "regions": ["eastus"],
+                "report_type": "MonthlySummaryReport",
+                "subscription_list": ["00000000-0000-0000-0000-000000000000"],
+                "tag_filters": [{"key": "team", "value": "search"}]
+            }"#,
+        )
+        .await
+        .unwrap();
+        release_client(handle).unwrap();
+
+        assert!(!emissions.is_empty());
+        assert!(emissions.iter().all(|e| e.region == "eastus"));
+        assert!(
+            emissions
+                .iter()
+                .all(|e| e.service.as_deref() == Some("Virtual Machines"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encoded_payloads() {
+        let handle = create_demo_client();
+        let payload = serde_json::json!({
+            "start_date": "2024-01-01T00:00:00Z",
+            "end_date": "2024-03-01T00:00:00Z",
+            "report_type": "ItemDetailsReport",
+            "subscription_list": ["00000000-0000-0000-0000-000000000000"],
+            "strict": true
+        });
+        let mut expected = get_emissions_with_client(handle, &payload.to_string())
+            .await
+            .unwrap();
+        assert!(!expected.is_empty());
+        // Lineage records when each query ran
+        expected.iter_mut().for_each(|e| e.lineage = None);
+
+        let formats = [
+            PayloadFormat::Json,
+            #[cfg(feature = "msgpack")]
+            PayloadFormat::MessagePack,
+            #[cfg(feature = "cbor")]
+            PayloadFormat::Cbor,
+        ];
+        for format in formats {
+            let name = format.as_str();
+            assert_eq!(name.parse::<PayloadFormat>().unwrap(), format);
+            let bytes = get_emissions_with_client_encoded(
+                handle,
+                &format.encode(&payload).unwrap(),
+                format,
+            )
+            .await
+            .unwrap();
+            let mut emissions: Vec<CarbonEmission> = format.decode(&bytes).unwrap();
+            emissions.iter_mut().for_each(|e| e.lineage = None);
+            assert_eq!(
+                serde_json::to_value(&emissions).unwrap(),
+                serde_json::to_value(&expected).unwrap(),
+                "{}",
+                name
+            );
+        }
+        release_client(handle).unwrap();
+        assert!("yaml".parse::<PayloadFormat>().is_err());
+    }
+
+    #[test]
+    fn test_owned_client_handle_drop() {
+        let owned = OwnedClientHandle::create("azure", r#"{"access_token": "test"}"#).unwrap();
+        let handle = owned.handle();
+        let other = create_client("azure", r#"{"access_token": "test"}"#).unwrap();
+        assert_ne!(handle, other);
+
+        drop(owned);
+        assert!(release_client(handle).is_err());
+        assert!(release_client(other).is_ok());
+    }
+
     #[tokio::test]
     #[ignore] // Requires real Azure token
     async fn test_get_emissions_integration() {
@@ -0,0 +1,344 @@
+//! Hedged requests to providers with variable latency
+//!
+//! Some provider endpoints answer most requests quickly but a few very
+//! slowly. With a [`HedgePolicy`], a request still unanswered after the
+//! provider's recent p95 latency is sent a second time, and the first
+//! successful answer is used; the other attempt is cancelled. Provider
+//! requests only read data, so sending one twice is safe.
+//!
+//! Hedging is capped so that it cannot amplify load on a struggling
+//! provider: a request is hedged at most once, only a fraction of requests
+//! are hedged, only a few hedges run at once, and no request is hedged while
+//! the provider's [concurrency limits](crate::CarbemClientBuilder::with_provider_concurrency)
+//! have no free slot or its [quota](crate::quota) is exhausted. Hedges go
+//! through the limits and count against the quota like any other request.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::sync::Semaphore;
+
+use crate::error::Result;
+use crate::logging::debug;
+use crate::quota::QuotaTracker;
+use crate::runtime::runtime;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+
+/// Number of recent latencies the hedging delay is computed from
+const LATENCY_WINDOW: usize = 200;
+
+/// When and how often requests to a provider are hedged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HedgePolicy {
+    /// Latency percentile after which a request is hedged, e.g. `0.95`
+    pub percentile: f64,
+
+    /// Answered requests needed before hedging starts
+    pub min_samples: usize,
+
+    /// Shortest time to wait before hedging, whatever the percentile
+    pub min_delay: Duration,
+
+    /// Largest share of requests that are hedged, e.g. `0.05` for 5%
+    pub max_ratio: f64,
+
+    /// Most hedges in flight at once
+    pub max_in_flight: usize,
+}
+
+impl Default for HedgePolicy {
+    fn default() -> Self {
+        Self {
+            percentile: 0.95,
+            min_samples: 20,
+            min_delay: Duration::from_millis(50),
+            max_ratio: 0.05,
+            max_in_flight: 2,
+        }
+    }
+}
+
+impl HedgePolicy {
+    /// Hedge after the p95 latency, at most 5% of requests and 2 at once
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hedge after the `percentile` latency instead, between 0 and 1
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        self.percentile = percentile.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Start hedging once `samples` requests were answered
+    pub fn with_min_samples(mut self, samples: usize) -> Self {
+        self.min_samples = samples.max(1);
+        self
+    }
+
+    /// Never hedge before `delay`
+    pub fn with_min_delay(mut self, delay: Duration) -> Self {
+        self.min_delay = delay;
+        self
+    }
+
+    /// Hedge at most the `ratio` share of requests, between 0 and 1
+    pub fn with_max_ratio(mut self, ratio: f64) -> Self {
+        self.max_ratio = ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Run at most `hedges` hedges at once
+    pub fn with_max_in_flight(mut self, hedges: usize) -> Self {
+        self.max_in_flight = hedges;
+        self
+    }
+}
+
+/// Latencies and hedges of the requests to one provider
+#[derive(Debug)]
+pub(crate) struct Hedger {
+    policy: HedgePolicy,
+    latencies: Mutex<VecDeque<Duration>>,
+    requests: AtomicU64,
+    hedged: AtomicU64,
+    in_flight: AtomicUsize,
+}
+
+impl Hedger {
+    pub(crate) fn new(policy: HedgePolicy) -> Self {
+        Self {
+            policy,
+            latencies: Mutex::new(VecDeque::with_capacity(LATENCY_WINDOW)),
+            requests: AtomicU64::new(0),
+            hedged: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    // Time after which a request is hedged, `None` until enough were answered
+    fn delay(&self) -> Option<Duration> {
+        let latencies = self.latencies.lock().expect("hedger lock poisoned");
+        if latencies.len() < self.policy.min_samples {
+            return None;
+        }
+        let mut sorted: Vec<Duration> = latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() - 1) as f64 * self.policy.percentile).round() as usize;
+        Some(sorted[index].max(self.policy.min_delay))
+    }
+
+    fn record(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().expect("hedger lock poisoned");
+        if latencies.len() == LATENCY_WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    // Reserve a hedge if the caps allow one
+    fn try_hedge(&self) -> Option<InFlightHedge<'_>> {
+        let allowed = (self.requests.load(Ordering::SeqCst) as f64 * self.policy.max_ratio) as u64;
+        if self.hedged.load(Ordering::SeqCst) >= allowed {
+            return None;
+        }
+        self.in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |hedges| {
+                (hedges < self.policy.max_in_flight).then_some(hedges + 1)
+            })
+            .ok()?;
+        self.hedged.fetch_add(1, Ordering::SeqCst);
+        Some(InFlightHedge { hedger: self })
+    }
+}
+
+// Counts a hedge as in flight until dropped
+struct InFlightHedge<'a> {
+    hedger: &'a Hedger,
+}
+
+impl Drop for InFlightHedge<'_> {
+    fn drop(&mut self) {
+        self.hedger.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// [`Transport`] sending a second attempt of slow requests
+#[derive(Debug)]
+pub(crate) struct HedgedTransport {
+    inner: Arc<dyn Transport>,
+    hedger: Arc<Hedger>,
+    limits: Vec<Arc<Semaphore>>,
+    quota: Option<Arc<QuotaTracker>>,
+}
+
+impl HedgedTransport {
+    pub(crate) fn new(inner: Arc<dyn Transport>, hedger: Arc<Hedger>) -> Self {
+        Self {
+            inner,
+            hedger,
+            limits: Vec::new(),
+            quota: None,
+        }
+    }
+
+    /// Do not hedge while `semaphore`, limiting `inner`, has no free permit
+    pub(crate) fn with_limit(mut self, semaphore: Arc<Semaphore>) -> Self {
+        self.limits.push(semaphore);
+        self
+    }
+
+    /// Do not hedge while the quota of `tracker`, enforced by `inner`, is exhausted
+    pub(crate) fn with_quota(mut self, tracker: Arc<QuotaTracker>) -> Self {
+        self.quota = Some(tracker);
+        self
+    }
+
+    // Whether a hedge would wait for a slot or be refused
+    fn saturated(&self) -> bool {
+        self.limits
+            .iter()
+            .any(|semaphore| semaphore.available_permits() == 0)
+            || self
+                .quota
+                .as_ref()
+                .is_some_and(|tracker| tracker.status(Utc::now()).remaining() == Some(0))
+    }
+}
+
+// Whether `result` is an answer worth returning over the other attempt's
+fn settled(result: &Result<HttpResponse>) -> bool {
+    match result {
+        Ok(response) => !(response.status.is_server_error() || response.status.as_u16() == 429),
+        Err(_) => false,
+    }
+}
+
+#[async_trait]
+impl Transport for HedgedTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let started = Instant::now();
+        self.hedger.requests.fetch_add(1, Ordering::SeqCst);
+        let Some(delay) = self.hedger.delay() else {
+            let result = self.inner.send(request).await;
+            if result.is_ok() {
+                self.hedger.record(started.elapsed());
+            }
+            return result;
+        };
+
+        let primary = self.inner.send(request.clone());
+        tokio::pin!(primary);
+        tokio::select! {
+            result = &mut primary => {
+                if result.is_ok() {
+                    self.hedger.record(started.elapsed());
+                }
+                return result;
+            }
+            _ = runtime().sleep(delay) => {}
+        }
+
+        let hedge = if self.saturated() {
+            None
+        } else {
+            self.hedger.try_hedge()
+        };
+        let Some(_hedge) = hedge else {
+            let result = primary.await;
+            if result.is_ok() {
+                self.hedger.record(started.elapsed());
+            }
+            return result;
+        };
+        debug!(
+            "Hedging {} {} after {:?}",
+            request.method, request.url, delay
+        );
+        let secondary = self.inner.send(request);
+        tokio::pin!(secondary);
+        let result = tokio::select! {
+            result = &mut primary => {
+                if settled(&result) { result } else { secondary.await }
+            }
+            result = &mut secondary => {
+                if settled(&result) { result } else { primary.await }
+            }
+        };
+        // The slower attempt took at least as long, which is what the caller saw
+        if result.is_ok() {
+            self.hedger.record(started.elapsed());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Method;
+    use reqwest::header::HeaderMap;
+
+    // Answers every request quickly, except the `slow`th one
+    #[derive(Debug)]
+    struct OneSlow {
+        slow: usize,
+        requests: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Transport for OneSlow {
+        async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+            let request = self.requests.fetch_add(1, Ordering::SeqCst);
+            let latency = if request == self.slow { 5000 } else { 1 };
+            tokio::time::sleep(Duration::from_millis(latency)).await;
+            Ok(HttpResponse::new(reqwest::StatusCode::OK, "{}"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_is_hedged() {
+        let inner = Arc::new(OneSlow {
+            slow: 10,
+            requests: AtomicUsize::new(0),
+        });
+        let policy = HedgePolicy::new()
+            .with_min_samples(10)
+            .with_min_delay(Duration::from_millis(20))
+            .with_max_ratio(0.1);
+        let hedger = Arc::new(Hedger::new(policy));
+        let limit = Arc::new(Semaphore::new(4));
+        let transport =
+            HedgedTransport::new(inner.clone(), hedger.clone()).with_limit(limit.clone());
+        let request = || HttpRequest::new(Method::GET, "https://example.com", HeaderMap::new());
+
+        for _ in 0..10 {
+            transport.send(request()).await.unwrap();
+        }
+        assert_eq!(hedger.delay(), Some(Duration::from_millis(20)));
+
+        // The slow 11th request is answered by its hedge
+        let started = Instant::now();
+        transport.send(request()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(inner.requests.load(Ordering::SeqCst), 12);
+        assert_eq!(hedger.hedged.load(Ordering::SeqCst), 1);
+        assert_eq!(hedger.in_flight.load(Ordering::SeqCst), 0);
+
+        // No hedge while the limiter has no free slot, even within the ratio
+        hedger.requests.fetch_add(20, Ordering::SeqCst);
+        let _permits = limit.acquire_many(4).await.unwrap();
+        let slow = Arc::new(OneSlow {
+            slow: 0,
+            requests: AtomicUsize::new(0),
+        });
+        let transport = HedgedTransport::new(slow.clone(), hedger).with_limit(limit.clone());
+        let pending = tokio::time::timeout(Duration::from_millis(200), transport.send(request()));
+        assert!(pending.await.is_err());
+        assert_eq!(slow.requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,68 @@
+# Berichtsbeschriftungen
+report-period = Zeitraum
+report-period-range = { $start } bis { $end }
+report-total = Gesamt
+report-share = Anteil
+report-share-value = { $value } %
+report-month = Monat
+report-charts = Diagramme
+chart-monthly-by-service = Monatliche Emissionen nach Dienst
+chart-by-region = Emissionen nach Region
+
+# Abschnitte und ihre erste Spalte
+section-provider = Nach Anbieter
+section-region = Nach Region
+section-service = Nach Dienst
+section-service-category = Nach Dienstkategorie
+section-month = Nach Monat
+dimension-provider = Anbieter
+dimension-region = Region
+dimension-service = Dienst
+dimension-service-category = Dienstkategorie
+
+# Einheiten
+unit-kg-co2e = kg CO2e
+
+# Kommandozeilenwerkzeug
+cli-error = Fehler: { $message }
+cli-warning-no-data = Warnung: keine Emissionen gefunden
+cli-warning-budget = Warnung: das Budget ist überschritten
+cli-csv-unsupported = dieser Befehl gibt json oder eine Tabelle aus, kein csv
+cli-write-failed = Schreiben von { $path } fehlgeschlagen: { $error }
+cli-prompt-failed = Eingabe fehlgeschlagen: { $error }
+cli-azure-token = Azure-Zugriffstoken
+cli-ibm-api-key = IBM Cloud-API-Schlüssel
+cli-init-replace = Profil '{ $profile }' existiert in { $path }. Ersetzen?
+cli-init-providers = Abzufragende Anbieter (Leertaste zum Auswählen, Eingabe zum Bestätigen)
+cli-init-no-provider = kein Anbieter ausgewählt
+cli-init-subscriptions = Azure-Abonnement-IDs, durch Kommas getrennt
+cli-init-locations = Azure-Standorte, durch Kommas getrennt (z. B. westeurope)
+cli-init-enterprise = IBM Cloud-Enterprise-ID
+cli-init-regions = IBM Cloud-Regionen, durch Kommas getrennt, leer für alle
+cli-init-verify = Zugriff mit einer Testabfrage prüfen?
+cli-init-querying = Letzter vollständiger Monat wird abgefragt...
+cli-init-save-anyway = Profil trotzdem speichern?
+cli-init-keyring = Zugangsdaten im Schlüsselbund des Systems statt in der Konfigurationsdatei speichern?
+cli-init-saved = Profil '{ $profile }' in { $path } gespeichert
+cli-auth-signed-in = Bei Azure angemeldet, das Token des Profils '{ $profile }' liegt im Schlüsselbund des Systems
+cli-auth-stored = { $provider }-Zugangsdaten des Profils '{ $profile }' im Schlüsselbund des Systems gespeichert
+cli-auth-removed = { $provider }-Zugangsdaten des Profils '{ $profile }' aus dem Schlüsselbund des Systems entfernt
+cli-severity-error = Fehler
+cli-severity-warning = Warnung
+cli-validate-queried = ok: { $account } kann abgefragt werden
+cli-validate-query-failed = Fehler: Abfrage von { $account } fehlgeschlagen: { $error }
+cli-validate-no-issue = ok: kein Problem gefunden
+cli-support-no-profile = Warnung: Profil nicht geladen: { $error }
+cli-support-query-failed = Warnung: { $provider }-Abfrage fehlgeschlagen: { $error }
+cli-support-written = { $path } geschrieben
+cli-diff-no-change = keine Änderung
+cli-diff-net-change = Nettoänderung: { $value } kg
+cli-tui-total = Gesamt
+cli-tui-filters = Filter
+cli-tui-no-filter = keine
+cli-tui-value = Wert
+cli-tui-trend = Trend
+cli-tui-help = ↑↓ auswählen  Eingabe filtern  ⌫ zurück  c leeren  Tab Dimension  r aktualisieren  q beenden
+cli-tui-refreshing = Aktualisierung…
+cli-tui-refresh-failed = Aktualisierung fehlgeschlagen: { $error }
+cli-terminal-error = Terminalfehler: { $error }
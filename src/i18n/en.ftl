@@ -0,0 +1,68 @@
+# Report labels
+report-period = Period
+report-period-range = { $start } to { $end }
+report-total = Total
+report-share = Share
+report-share-value = { $value }%
+report-month = Month
+report-charts = Charts
+chart-monthly-by-service = Monthly emissions by service
+chart-by-region = Emissions by region
+
+# Sections and their first column
+section-provider = By provider
+section-region = By region
+section-service = By service
+section-service-category = By service category
+section-month = By month
+dimension-provider = Provider
+dimension-region = Region
+dimension-service = Service
+dimension-service-category = Service category
+
+# Units
+unit-kg-co2e = kg CO2e
+
+# Command-line tool
+cli-error = error: { $message }
+cli-warning-no-data = warning: no emissions found
+cli-warning-budget = warning: the budget is exceeded
+cli-csv-unsupported = this command prints json or table, not csv
+cli-write-failed = Failed to write { $path }: { $error }
+cli-prompt-failed = Prompt failed: { $error }
+cli-azure-token = Azure access token
+cli-ibm-api-key = IBM Cloud API key
+cli-init-replace = Profile '{ $profile }' exists in { $path }. Replace it?
+cli-init-providers = Providers to query (space to select, enter to confirm)
+cli-init-no-provider = no provider selected
+cli-init-subscriptions = Azure subscription IDs, comma-separated
+cli-init-locations = Azure locations, comma-separated (e.g. westeurope)
+cli-init-enterprise = IBM Cloud enterprise ID
+cli-init-regions = IBM Cloud regions, comma-separated, empty for all
+cli-init-verify = Verify access with a test query?
+cli-init-querying = Querying the last full month...
+cli-init-save-anyway = Save the profile anyway?
+cli-init-keyring = Store the credentials in the OS keyring instead of the configuration file?
+cli-init-saved = Saved profile '{ $profile }' to { $path }
+cli-auth-signed-in = Signed in to Azure, the token of profile '{ $profile }' is in the OS keyring
+cli-auth-stored = Stored the { $provider } credential of profile '{ $profile }' in the OS keyring
+cli-auth-removed = Removed the { $provider } credential of profile '{ $profile }' from the OS keyring
+cli-severity-error = error
+cli-severity-warning = warning
+cli-validate-queried = ok: { $account } can be queried
+cli-validate-query-failed = error: { $account } query failed: { $error }
+cli-validate-no-issue = ok: no issue found
+cli-support-no-profile = warning: profile not loaded: { $error }
+cli-support-query-failed = warning: { $provider } query failed: { $error }
+cli-support-written = Wrote { $path }
+cli-diff-no-change = no change
+cli-diff-net-change = net change: { $value } kg
+cli-tui-total = Total
+cli-tui-filters = Filters
+cli-tui-no-filter = none
+cli-tui-value = Value
+cli-tui-trend = Trend
+cli-tui-help = ↑↓ select  enter filter  ⌫ back  c clear  tab dimension  r refresh  q quit
+cli-tui-refreshing = Refreshing…
+cli-tui-refresh-failed = Refresh failed: { $error }
+cli-terminal-error = Terminal error: { $error }
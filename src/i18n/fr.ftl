@@ -0,0 +1,68 @@
+# Libellés des rapports
+report-period = Période
+report-period-range = du { $start } au { $end }
+report-total = Total
+report-share = Part
+report-share-value = { $value } %
+report-month = Mois
+report-charts = Graphiques
+chart-monthly-by-service = Émissions mensuelles par service
+chart-by-region = Émissions par région
+
+# Sections et leur première colonne
+section-provider = Par fournisseur
+section-region = Par région
+section-service = Par service
+section-service-category = Par catégorie de service
+section-month = Par mois
+dimension-provider = Fournisseur
+dimension-region = Région
+dimension-service = Service
+dimension-service-category = Catégorie de service
+
+# Unités
+unit-kg-co2e = kg éq. CO2
+
+# Outil en ligne de commande
+cli-error = erreur : { $message }
+cli-warning-no-data = avertissement : aucune émission trouvée
+cli-warning-budget = avertissement : le budget est dépassé
+cli-csv-unsupported = cette commande affiche du json ou un tableau, pas du csv
+cli-write-failed = Échec de l'écriture de { $path } : { $error }
+cli-prompt-failed = Échec de la saisie : { $error }
+cli-azure-token = Jeton d'accès Azure
+cli-ibm-api-key = Clé d'API IBM Cloud
+cli-init-replace = Le profil '{ $profile }' existe dans { $path }. Le remplacer ?
+cli-init-providers = Fournisseurs à interroger (espace pour sélectionner, entrée pour valider)
+cli-init-no-provider = aucun fournisseur sélectionné
+cli-init-subscriptions = Identifiants des abonnements Azure, séparés par des virgules
+cli-init-locations = Emplacements Azure, séparés par des virgules (par ex. westeurope)
+cli-init-enterprise = Identifiant d'entreprise IBM Cloud
+cli-init-regions = Régions IBM Cloud, séparées par des virgules, vide pour toutes
+cli-init-verify = Vérifier l'accès par une requête de test ?
+cli-init-querying = Interrogation du dernier mois complet...
+cli-init-save-anyway = Enregistrer le profil malgré tout ?
+cli-init-keyring = Conserver les identifiants dans le trousseau du système plutôt que dans le fichier de configuration ?
+cli-init-saved = Profil '{ $profile }' enregistré dans { $path }
+cli-auth-signed-in = Connecté à Azure, le jeton du profil '{ $profile }' est dans le trousseau du système
+cli-auth-stored = Identifiant { $provider } du profil '{ $profile }' enregistré dans le trousseau du système
+cli-auth-removed = Identifiant { $provider } du profil '{ $profile }' supprimé du trousseau du système
+cli-severity-error = erreur
+cli-severity-warning = avertissement
+cli-validate-queried = ok : { $account } peut être interrogé
+cli-validate-query-failed = erreur : échec de la requête de { $account } : { $error }
+cli-validate-no-issue = ok : aucun problème trouvé
+cli-support-no-profile = avertissement : profil non chargé : { $error }
+cli-support-query-failed = avertissement : échec de la requête { $provider } : { $error }
+cli-support-written = { $path } écrit
+cli-diff-no-change = aucun changement
+cli-diff-net-change = variation nette : { $value } kg
+cli-tui-total = Total
+cli-tui-filters = Filtres
+cli-tui-no-filter = aucun
+cli-tui-value = Valeur
+cli-tui-trend = Tendance
+cli-tui-help = ↑↓ choisir  entrée filtrer  ⌫ retour  c effacer  tab dimension  r actualiser  q quitter
+cli-tui-refreshing = Actualisation…
+cli-tui-refresh-failed = Échec de l'actualisation : { $error }
+cli-terminal-error = Erreur du terminal : { $error }
@@ -0,0 +1,168 @@
+//! Localization of report labels, units and command-line messages
+//!
+//! Labels are [Fluent](https://projectfluent.org/) messages bundled for each
+//! [`Locale`], in `src/i18n/<language>.ftl`. A message missing from a
+//! translation falls back to English. Reports are localized with
+//! [`Report::by_dimensions_in`](crate::Report::by_dimensions_in), and the
+//! messages of the `carbem` tool are the `cli-*` ones; machine readable
+//! outputs such as JSON and CSV are never localized.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+use crate::error::{CarbemError, Result};
+
+type Bundle = FluentBundle<FluentResource>;
+
+// Bundles are parsed on first use, indexed by `Locale as usize`
+static BUNDLES: [OnceLock<Bundle>; 3] = [const { OnceLock::new() }; 3];
+
+/// Language of report labels and number formatting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// English
+    #[default]
+    En,
+
+    /// French
+    Fr,
+
+    /// German
+    De,
+}
+
+impl Locale {
+    /// Every supported locale
+    pub const ALL: [Locale; 3] = [Locale::En, Locale::Fr, Locale::De];
+
+    /// Language code, e.g. `fr`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+            Locale::De => "de",
+        }
+    }
+
+    /// The message `id`, falling back to English, then to `id` itself
+    pub fn message(&self, id: &str) -> String {
+        self.format(id, &[])
+    }
+
+    /// The message `id` with its `{ $name }` placeables replaced by `args`
+    pub fn format(&self, id: &str, args: &[(&str, &str)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, *value);
+        }
+        for locale in [*self, Locale::En] {
+            let bundle = locale.bundle();
+            if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+                let mut errors = Vec::new();
+                return bundle
+                    .format_pattern(pattern, Some(&fluent_args), &mut errors)
+                    .into_owned();
+            }
+        }
+        id.to_string()
+    }
+
+    /// `value` with `decimals` digits and the locale's decimal separator
+    pub fn format_number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value);
+        match self {
+            Locale::En => formatted,
+            Locale::Fr | Locale::De => formatted.replace('.', ","),
+        }
+    }
+
+    fn bundle(&self) -> &'static Bundle {
+        BUNDLES[*self as usize].get_or_init(|| {
+            let source = match self {
+                Locale::En => include_str!("en.ftl"),
+                Locale::Fr => include_str!("fr.ftl"),
+                Locale::De => include_str!("de.ftl"),
+            };
+            let language: LanguageIdentifier = self.code().parse().expect("locale codes are valid");
+            let mut bundle = FluentBundle::new_concurrent(vec![language]);
+            // Labels end up in tables and files, not bidirectional text
+            bundle.set_use_isolating(false);
+            bundle
+                .add_resource(
+                    FluentResource::try_new(source.to_string())
+                        .expect("bundled messages are valid"),
+                )
+                .expect("bundled messages are unique");
+            bundle
+        })
+    }
+}
+
+impl FromStr for Locale {
+    type Err = CarbemError;
+
+    /// Parse a language tag, e.g. `fr` or `de-CH`; only the language is used
+    fn from_str(s: &str) -> Result<Self> {
+        let language = s
+            .parse::<LanguageIdentifier>()
+            .map_err(|e| CarbemError::Config(format!("invalid locale '{}': {}", s, e)))?
+            .language;
+        Locale::ALL
+            .into_iter()
+            .find(|locale| language.as_str() == locale.code())
+            .ok_or_else(|| {
+                CarbemError::Config(format!("unsupported locale '{}', expected en, fr or de", s))
+            })
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundles_translate_every_message() {
+        let ids: Vec<&str> = include_str!("en.ftl")
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(" = "))
+            .map(|(id, _)| id)
+            .collect();
+        assert!(ids.contains(&"unit-kg-co2e"));
+        for locale in Locale::ALL {
+            for id in &ids {
+                assert!(
+                    locale.bundle().has_message(id),
+                    "{} is missing {}",
+                    locale,
+                    id
+                );
+            }
+        }
+
+        assert_eq!(
+            Locale::Fr.format(
+                "report-period-range",
+                &[("start", "2024-01-01"), ("end", "2024-03-31")]
+            ),
+            "du 2024-01-01 au 2024-03-31"
+        );
+        assert_eq!(Locale::De.format_number(1234.5, 2), "1234,50");
+        assert_eq!(Locale::Fr.message("no-such-message"), "no-such-message");
+        assert_eq!("de-CH".parse::<Locale>().unwrap(), Locale::De);
+        assert!("es".parse::<Locale>().is_err());
+    }
+}
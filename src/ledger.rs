@@ -0,0 +1,250 @@
+//! Ledger of purchased carbon offsets and renewable energy certificates
+//!
+//! Entries record certificates retired against a reporting year. Netting
+//! reports gross and net emissions side by side for every year, as most
+//! disclosures require both figures.
+
+use std::collections::BTreeMap;
+
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::EmissionDataset;
+use crate::error::{CarbemError, Result};
+
+/// Kind of certificate held in the ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateKind {
+    /// Carbon offset, with a volume in tonnes CO2e
+    Offset,
+
+    /// Renewable energy certificate (REC, GO, I-REC), with a volume in MWh
+    RenewableEnergyCertificate,
+}
+
+/// A certificate purchase retired against a reporting year
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Kind of certificate
+    pub kind: CertificateKind,
+
+    /// Year the offset or renewable energy was generated
+    pub vintage: i32,
+
+    /// Tonnes CO2e for offsets, MWh for renewable energy certificates
+    pub volume: f64,
+
+    /// Registry the certificates are issued by, e.g. "Verra" or "M-RETS"
+    pub registry: String,
+
+    /// Year whose emissions the certificates are retired against
+    pub reporting_year: i32,
+
+    /// Optional: serial numbers or retirement reference
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+/// Gross and net emissions of a year
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NetEmissions {
+    /// Calendar year
+    pub year: i32,
+
+    /// Emissions before any certificate, in kg CO2e
+    pub gross_kg_co2eq: f64,
+
+    /// Emissions compensated by offsets, in kg CO2e
+    pub offsets_kg_co2eq: f64,
+
+    /// Emissions covered by renewable energy certificates, in kg CO2e
+    pub certificates_kg_co2eq: f64,
+
+    /// Gross emissions minus offsets and certificates; negative when over-compensated
+    pub net_kg_co2eq: f64,
+}
+
+/// Record of offsets and renewable energy certificates
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+
+    // Emissions avoided per MWh of renewable energy certificate
+    rec_emission_factor_kg_per_mwh: Option<f64>,
+}
+
+impl Ledger {
+    /// Create an empty ledger
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the emission factor used to convert certificate MWh to kg CO2e
+    ///
+    /// Usually the grid emission factor of the market the certificates cover.
+    pub fn with_rec_emission_factor(mut self, kg_co2eq_per_mwh: f64) -> Self {
+        self.rec_emission_factor_kg_per_mwh = Some(kg_co2eq_per_mwh);
+        self
+    }
+
+    /// Record a certificate purchase
+    pub fn record(&mut self, entry: LedgerEntry) -> Result<()> {
+        if !(entry.volume.is_finite() && entry.volume > 0.0) {
+            return Err(CarbemError::Config(format!(
+                "ledger entry volume must be positive, got {}",
+                entry.volume
+            )));
+        }
+        if entry.registry.trim().is_empty() {
+            return Err(CarbemError::Config(
+                "ledger entry registry is required".to_string(),
+            ));
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// All recorded entries
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// Gross and net emissions per year of `dataset` and of every reporting year in the ledger
+    ///
+    /// Fails when renewable energy certificates are recorded without an emission factor.
+    pub fn net(&self, dataset: &EmissionDataset) -> Result<Vec<NetEmissions>> {
+        let mut years: BTreeMap<i32, NetEmissions> = BTreeMap::new();
+
+        for (year, kg) in dataset.group_by(|e| e.time_period.start.year()) {
+            year_totals(&mut years, year).gross_kg_co2eq = kg;
+        }
+
+        for entry in &self.entries {
+            let totals = year_totals(&mut years, entry.reporting_year);
+            match entry.kind {
+                CertificateKind::Offset => totals.offsets_kg_co2eq += entry.volume * 1000.0,
+                CertificateKind::RenewableEnergyCertificate => {
+                    let factor = self.rec_emission_factor_kg_per_mwh.ok_or_else(|| {
+                        CarbemError::Config(
+                            "an emission factor is required to net renewable energy certificates"
+                                .to_string(),
+                        )
+                    })?;
+                    totals.certificates_kg_co2eq += entry.volume * factor;
+                }
+            }
+        }
+
+        Ok(years
+            .into_values()
+            .map(|mut totals| {
+                totals.net_kg_co2eq =
+                    totals.gross_kg_co2eq - totals.offsets_kg_co2eq - totals.certificates_kg_co2eq;
+                totals
+            })
+            .collect())
+    }
+}
+
+fn year_totals(years: &mut BTreeMap<i32, NetEmissions>, year: i32) -> &mut NetEmissions {
+    years.entry(year).or_insert(NetEmissions {
+        year,
+        gross_kg_co2eq: 0.0,
+        offsets_kg_co2eq: 0.0,
+        certificates_kg_co2eq: 0.0,
+        net_kg_co2eq: 0.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(year: i32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(year, 3, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "ibm".to_string(),
+            region: "dallas".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }
+    }
+
+    fn entry(kind: CertificateKind, volume: f64, reporting_year: i32) -> LedgerEntry {
+        LedgerEntry {
+            kind,
+            vintage: reporting_year - 1,
+            volume,
+            registry: "Verra".to_string(),
+            reporting_year,
+            reference: None,
+        }
+    }
+
+    #[test]
+    fn test_net_emissions() {
+        let mut ledger = Ledger::new().with_rec_emission_factor(400.0);
+        ledger
+            .record(entry(CertificateKind::Offset, 1.5, 2024))
+            .unwrap();
+        ledger
+            .record(entry(
+                CertificateKind::RenewableEnergyCertificate,
+                2.0,
+                2024,
+            ))
+            .unwrap();
+        ledger
+            .record(entry(CertificateKind::Offset, 1.0, 2025))
+            .unwrap();
+
+        let dataset = EmissionDataset::new(vec![emission(2023, 500.0), emission(2024, 5000.0)]);
+        let net = ledger.net(&dataset).unwrap();
+
+        assert_eq!(net.len(), 3);
+        assert_eq!(net[0].net_kg_co2eq, 500.0);
+        assert_eq!(net[1].gross_kg_co2eq, 5000.0);
+        assert_eq!(net[1].offsets_kg_co2eq, 1500.0);
+        assert_eq!(net[1].certificates_kg_co2eq, 800.0);
+        assert_eq!(net[1].net_kg_co2eq, 2700.0);
+        assert_eq!(net[2].net_kg_co2eq, -1000.0);
+    }
+
+    #[test]
+    fn test_rec_requires_emission_factor() {
+        let mut ledger = Ledger::new();
+        ledger
+            .record(entry(
+                CertificateKind::RenewableEnergyCertificate,
+                2.0,
+                2024,
+            ))
+            .unwrap();
+        assert!(ledger.net(&EmissionDataset::default()).is_err());
+    }
+
+    #[test]
+    fn test_record_rejects_invalid_entries() {
+        let mut ledger = Ledger::new();
+        assert!(
+            ledger
+                .record(entry(CertificateKind::Offset, 0.0, 2024))
+                .is_err()
+        );
+
+        let mut missing_registry = entry(CertificateKind::Offset, 1.0, 2024);
+        missing_registry.registry = " ".to_string();
+        assert!(ledger.record(missing_registry).is_err());
+        assert!(ledger.entries().is_empty());
+    }
+}
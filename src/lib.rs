@@ -27,10 +27,17 @@
 //!         services: None,
 //!         resources: None,
 //!         provider_config: Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-//!             report_type: AzureReportType::MonthlySummaryReport,
-//!             subscription_list: vec!["subscription-id".to_string()],
+//!             report_type: Some(AzureReportType::MonthlySummaryReport),
+//!             subscription_list: Some(vec!["subscription-id".to_string()]),
 //!             ..Default::default()
 //!         })),
+//!         raw_response: Default::default(),
+//!         date_alignment: Default::default(),
+//!         timezone: Default::default(),
+//!         dry_run: false,
+//!         route: Vec::new(),
+//!         tag_filters: Vec::new(),
+//!         strict: false,
 //!     };
 //!
 //!     let emissions = client.query_emissions(&query).await?;
@@ -52,32 +59,119 @@
 //!     Ok(())
 //! }
 //! ```
+
+// Every provider API is served over HTTPS
+#[cfg(not(any(feature = "rustls-tls", feature = "native-tls")))]
+compile_error!("enable a TLS backend: the `rustls-tls` (default) or `native-tls` feature");
+
 use pyo3::prelude::*;
-use pyo3::types::PyModule;
+#[cfg(feature = "tokio-runtime")]
+use pyo3::types::{PyBytes, PyModule};
 
+pub mod advisor;
+pub mod aggregation;
+pub mod allocation;
+pub mod audit;
+pub mod auth;
+#[cfg(feature = "signing")]
+pub mod bundle;
+pub mod capture;
 pub mod client;
+mod coalesce;
+pub mod collector;
+pub mod config;
+pub mod correlation;
+#[cfg(feature = "keyring")]
+pub mod credentials;
+pub mod dsl;
 pub mod error;
+pub mod estimate;
+pub mod exit;
 pub mod ffi;
+pub mod hedge;
+pub mod i18n;
+pub mod ledger;
+mod logging;
+#[cfg(feature = "mcp")]
+pub mod mcp;
 pub mod models;
+pub mod notify;
+pub mod organization;
+pub mod output;
+#[cfg(feature = "plot")]
+pub mod plot;
+pub mod policy;
+pub mod precision;
+pub mod progress;
 pub mod providers;
+mod query_string;
+pub mod quota;
+pub mod redact;
+pub mod report;
+pub mod report_definition;
+pub mod runtime;
+pub mod sandbox;
+pub mod schema;
+#[cfg(feature = "tower")]
+pub mod service;
+pub mod sinks;
+pub mod spill;
+pub mod store;
+pub mod subscription;
+pub mod support;
+pub mod targets;
+pub mod taxonomy;
+#[cfg(feature = "templates")]
+pub mod template;
+pub mod transport;
+#[cfg(feature = "axum")]
+pub mod web;
 
 // Export the main Rust API
 pub use client::*;
 
 // Export core types
+pub use aggregation::{
+    BusinessMetric, DataPointChange, DataQuality, DatasetDiff, DatasetSnapshot, Dimension,
+    EmissionDataset, FiscalCalendar, FiscalQuarter, IntensityPoint, StableSum,
+};
+pub use collector::{CollectionJob, Collector};
+pub use config::{AzureAccountConfig, ClientConfig, IbmAccountConfig};
+pub use dsl::{Breakdown, BreakdownRow, EmissionsRequest, Grouping, emissions};
 pub use error::{CarbemError, Result};
-pub use models::{CarbonEmission, EmissionMetadata, EmissionQuery, TimePeriod};
+pub use exit::ExitStatus;
+pub use i18n::Locale;
+pub use models::{
+    CarbonEmission, DateAlignment, EmissionMetadata, EmissionQuery, EmissionResult,
+    FlatEmissionRecord, Lineage, PlannedRequest, QueryTimezone, RawResponseMode, TimePeriod,
+    sort_emissions,
+};
+pub use notify::{SlackNotifier, Summary, SummaryPeriod};
+pub use output::OutputFormat;
+pub use precision::{PrecisionPolicy, RoundingMode};
+pub use progress::{Progress, ProgressHandler};
 pub use providers::azure::{
-    AzureCarbonScope, AzureConfig, AzureProvider, AzureQueryConfig, AzureReportType,
-    AzureSortDirection,
+    AzureCarbonScope, AzureCategoryType, AzureConfig, AzureProvider, AzureQueryConfig,
+    AzureReportType, AzureSortDirection,
 };
 pub use providers::config::ProviderQueryConfig;
 pub use providers::ibm::{IbmConfig, IbmGroupBy, IbmProvider, IbmQueryConfig};
+pub use providers::pager::{AzurePager, IbmPager, Pager, ProviderPager};
+pub use report::{Report, ReportSection};
+pub use report_definition::{ReportDefinition, ReportDestination, ReportFormat, ReportPeriod};
+pub use schema::{SchemaWarning, SchemaWarningKind};
+pub use subscription::{EmissionEvent, EmissionEvents};
+pub use taxonomy::ServiceCategory;
 
 // Export FFI functions for Python/TS bindings
-pub use ffi::get_emissions;
+pub use ffi::{
+    ClientHandle, FfiStatus, OwnedClientHandle, PayloadFormat, create_client, create_demo_client,
+    get_emissions, get_emissions_encoded, get_emissions_with_client,
+    get_emissions_with_client_encoded, get_emissions_with_raw, release_client,
+};
 
 /// Get carbon emissions from cloud providers (Python-compatible function)
+#[cfg(feature = "tokio-runtime")]
 #[pyfunction]
 pub fn get_emissions_py(provider: &str, config_json: &str, query_json: &str) -> PyResult<String> {
     // Use the existing FFI function with runtime block
@@ -105,9 +199,153 @@ pub fn get_emissions_py(provider: &str, config_json: &str, query_json: &str) ->
     }
 }
 
+/// Get carbon emissions as MessagePack, CBOR or JSON bytes (Python-compatible function)
+///
+/// `payload` is the query in `format` (`"msgpack"`, `"cbor"` or `"json"`),
+/// and the emissions are returned in the same format.
+#[cfg(feature = "tokio-runtime")]
+#[pyfunction]
+pub fn get_emissions_encoded_py<'py>(
+    py: Python<'py>,
+    provider: &str,
+    config_json: &str,
+    payload: &[u8],
+    format: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let format: PayloadFormat = format
+        .parse()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}", e)))?;
+    let rt = tokio::runtime::Runtime::new().map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to create runtime: {}",
+            e
+        ))
+    })?;
+
+    let bytes = rt
+        .block_on(get_emissions_encoded(
+            provider,
+            config_json,
+            payload,
+            format,
+        ))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}", e)))?;
+    Ok(PyBytes::new(py, &bytes))
+}
+
+/// Get carbon emissions and raw provider responses (Python-compatible function)
+///
+/// Returns a JSON object with `emissions` and `raw_responses` fields.
+#[cfg(feature = "tokio-runtime")]
+#[pyfunction]
+pub fn get_emissions_with_raw_py(
+    provider: &str,
+    config_json: &str,
+    query_json: &str,
+) -> PyResult<String> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to create runtime: {}",
+            e
+        ))
+    })?;
+
+    let result = rt
+        .block_on(get_emissions_with_raw(provider, config_json, query_json))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}", e)))?;
+
+    serde_json::to_string(&result).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Serialization error: {}", e))
+    })
+}
+
+/// Create a client and return its registry handle (Python-compatible function)
+#[pyfunction]
+pub fn create_client_py(provider: &str, config_json: &str) -> PyResult<u64> {
+    create_client(provider, config_json)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}", e)))
+}
+
+/// Create a demo client answering from bundled sample data and return its handle (Python-compatible function)
+#[pyfunction]
+pub fn create_demo_client_py() -> u64 {
+    create_demo_client()
+}
+
+/// Release a client handle, returning an `FfiStatus` code (Python-compatible function)
+#[pyfunction]
+pub fn release_client_py(handle: u64) -> i32 {
+    match release_client(handle) {
+        Ok(()) => FfiStatus::Ok as i32,
+        Err(e) => FfiStatus::from(&e) as i32,
+    }
+}
+
+/// Get carbon emissions using a registered client (Python-compatible function)
+#[cfg(feature = "tokio-runtime")]
+#[pyfunction]
+pub fn get_emissions_with_client_py(handle: u64, query_json: &str) -> PyResult<String> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to create runtime: {}",
+            e
+        ))
+    })?;
+
+    let emissions = rt
+        .block_on(get_emissions_with_client(handle, query_json))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}", e)))?;
+
+    serde_json::to_string(&emissions).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Serialization error: {}", e))
+    })
+}
+
+/// Get carbon emissions as MessagePack, CBOR or JSON bytes using a registered client (Python-compatible function)
+#[cfg(feature = "tokio-runtime")]
+#[pyfunction]
+pub fn get_emissions_with_client_encoded_py<'py>(
+    py: Python<'py>,
+    handle: u64,
+    payload: &[u8],
+    format: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let format: PayloadFormat = format
+        .parse()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}", e)))?;
+    let rt = tokio::runtime::Runtime::new().map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to create runtime: {}",
+            e
+        ))
+    })?;
+
+    let bytes = rt
+        .block_on(get_emissions_with_client_encoded(handle, payload, format))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{}", e)))?;
+    Ok(PyBytes::new(py, &bytes))
+}
+
+/// List registered providers with their required fields and auth style as JSON (Python-compatible function)
+#[pyfunction]
+pub fn list_providers_py() -> PyResult<String> {
+    serde_json::to_string(&providers::registry::ProviderRegistry::new().available()).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Serialization error: {}", e))
+    })
+}
+
 /// Python module
+#[cfg(feature = "tokio-runtime")]
 #[pymodule]
 fn carbem(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(get_emissions_py, m)?)?;
+    m.add_function(wrap_pyfunction!(get_emissions_encoded_py, m)?)?;
+    m.add_function(wrap_pyfunction!(get_emissions_with_raw_py, m)?)?;
+    m.add_function(wrap_pyfunction!(create_client_py, m)?)?;
+    m.add_function(wrap_pyfunction!(create_demo_client_py, m)?)?;
+    m.add_function(wrap_pyfunction!(release_client_py, m)?)?;
+    m.add_function(wrap_pyfunction!(get_emissions_with_client_py, m)?)?;
+    m.add_function(wrap_pyfunction!(get_emissions_with_client_encoded_py, m)?)?;
+    m.add_function(wrap_pyfunction!(list_providers_py, m)?)?;
     Ok(())
 }
@@ -0,0 +1,10 @@
+//! Internal logging facade
+//!
+//! Logs are emitted through the `log` crate by default, or through `tracing`
+//! when the `tracing` feature is enabled. Library code imports the macros from
+//! here so that the backend choice stays in a single place.
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use log::{debug, warn};
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, warn};
@@ -0,0 +1,446 @@
+//! Model Context Protocol server exposing emissions tools
+//!
+//! [`McpServer`] lets AI assistants answer questions such as "what were our
+//! Azure emissions last quarter?" from a configured [`CarbemClient`]. It
+//! speaks [MCP](https://modelcontextprotocol.io/) over JSON-RPC, one message
+//! per line, e.g. on stdio with [`McpServer::serve_stdio`], and offers three
+//! read-only tools:
+//!
+//! - `list_regions`: the providers and regions the server may query
+//! - `query_emissions`: emissions of a provider for a period, as flat records
+//! - `summarize_period`: a Markdown summary of a period, by a dimension
+//!
+//! Assistants never send raw queries: each tool call runs one of the
+//! approved scopes added with [`McpServer::with_scope`], with only its period
+//! replaced and its regions and services narrowed.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::client::CarbemClient;
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, EmissionQuery, TimePeriod};
+use crate::output::OutputFormat;
+use crate::report::Report;
+
+/// MCP revision implemented by the server
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// MCP server answering tool calls with a client
+#[derive(Clone)]
+pub struct McpServer {
+    client: CarbemClient,
+    scopes: Vec<EmissionQuery>,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct ListRegionsArgs {
+    provider: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct QueryArgs {
+    provider: String,
+    start: String,
+    end: String,
+    #[serde(default)]
+    regions: Vec<String>,
+    services: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct SummarizeArgs {
+    provider: Option<String>,
+    start: String,
+    end: String,
+    group_by: Option<Dimension>,
+}
+
+impl McpServer {
+    /// Server querying `client`, with no approved scope yet
+    pub fn new(client: CarbemClient) -> Self {
+        Self {
+            client,
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Approve `query` as a scope: tools run it for other periods
+    ///
+    /// Its regions are the most a tool call can ask for; empty regions allow
+    /// any. Its provider configuration is never exposed.
+    pub fn with_scope(mut self, query: EmissionQuery) -> Self {
+        self.scopes.push(query);
+        self
+    }
+
+    /// Serve messages read from stdin, answering on stdout, until stdin closes
+    #[cfg(feature = "tokio-runtime")]
+    pub async fn serve_stdio(&self) -> Result<()> {
+        self.serve(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await
+    }
+
+    /// Serve messages read from `input`, one per line, until it ends
+    pub async fn serve(
+        &self,
+        input: impl AsyncBufRead + Unpin,
+        output: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        self.serve_with_graceful_shutdown(input, output, std::future::pending())
+            .await
+    }
+
+    /// Serve messages read from `input` until it ends or `signal` completes
+    ///
+    /// On shutdown, the message being handled is answered before returning;
+    /// no further message is read.
+    pub async fn serve_with_graceful_shutdown(
+        &self,
+        input: impl AsyncBufRead + Unpin,
+        mut output: impl AsyncWrite + Unpin,
+        signal: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let io_error = |e: std::io::Error| CarbemError::Other(format!("MCP transport: {}", e));
+        let mut lines = input.lines();
+        let mut signal = std::pin::pin!(signal);
+        loop {
+            let line = tokio::select! {
+                biased;
+                _ = &mut signal => break,
+                line = lines.next_line() => line.map_err(io_error)?,
+            };
+            let Some(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str(&line) {
+                Ok(message) => self.handle(message).await,
+                Err(e) => Some(error(Value::Null, PARSE_ERROR, &e.to_string())),
+            };
+            if let Some(response) = response {
+                let mut bytes = serde_json::to_vec(&response)?;
+                bytes.push(b'\n');
+                output.write_all(&bytes).await.map_err(io_error)?;
+                output.flush().await.map_err(io_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer one JSON-RPC message, `None` for notifications
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let method = message.get("method").and_then(Value::as_str);
+        let Some(id) = message.get("id").cloned() else {
+            // Notifications, such as notifications/initialized, get no answer
+            return None;
+        };
+        let Some(method) = method else {
+            return Some(error(id, INVALID_REQUEST, "missing method"));
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "carbem", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({"tools": tools()})),
+            "tools/call" => match serde_json::from_value::<ToolCall>(params) {
+                Ok(call) => self.call(call).await,
+                Err(e) => Err((INVALID_PARAMS, e.to_string())),
+            },
+            other => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", other))),
+        };
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error(id, code, &message),
+        })
+    }
+
+    // Run a tool; failures of the tool itself are results flagged as errors
+    async fn call(&self, call: ToolCall) -> std::result::Result<Value, (i64, String)> {
+        let invalid = |e: serde_json::Error| (INVALID_PARAMS, e.to_string());
+        let outcome = match call.name.as_str() {
+            "list_regions" => {
+                let args: ListRegionsArgs =
+                    serde_json::from_value(call.arguments).map_err(invalid)?;
+                Ok(self.list_regions(args.provider.as_deref()))
+            }
+            "query_emissions" => {
+                let args: QueryArgs = serde_json::from_value(call.arguments).map_err(invalid)?;
+                self.query_emissions(args).await
+            }
+            "summarize_period" => {
+                let args: SummarizeArgs =
+                    serde_json::from_value(call.arguments).map_err(invalid)?;
+                self.summarize_period(args).await
+            }
+            other => return Err((INVALID_PARAMS, format!("unknown tool '{}'", other))),
+        };
+        Ok(match outcome {
+            Ok(text) => json!({"content": [{"type": "text", "text": text}], "isError": false}),
+            Err(e) => {
+                json!({"content": [{"type": "text", "text": e.to_string()}], "isError": true})
+            }
+        })
+    }
+
+    fn list_regions(&self, provider: Option<&str>) -> String {
+        let scopes: Vec<Value> = self
+            .scopes_of(provider)
+            .map(|scope| json!({"provider": scope.provider, "regions": scope.regions}))
+            .collect();
+        serde_json::to_string_pretty(&scopes).expect("scopes always serialize")
+    }
+
+    async fn query_emissions(&self, args: QueryArgs) -> Result<String> {
+        let period = TimePeriod::parse(&args.start, &args.end)?;
+        let mut emissions = Vec::new();
+        let mut scopes = self.scopes_of(Some(&args.provider)).peekable();
+        if scopes.peek().is_none() {
+            return Err(not_approved(&format!("provider '{}'", args.provider)));
+        }
+        for scope in scopes {
+            let mut query = scope.clone();
+            query.time_period = period.clone();
+            if !args.regions.is_empty() {
+                if let Some(region) = args
+                    .regions
+                    .iter()
+                    .find(|region| !scope.regions.is_empty() && !scope.regions.contains(region))
+                {
+                    return Err(not_approved(&format!("region '{}'", region)));
+                }
+                query.regions = args.regions.clone();
+            }
+            if let Some(services) = &args.services {
+                query.services = Some(services.clone());
+            }
+            emissions.extend(self.run(&query).await?);
+        }
+        OutputFormat::Json.render(&emissions)
+    }
+
+    async fn summarize_period(&self, args: SummarizeArgs) -> Result<String> {
+        let period = TimePeriod::parse(&args.start, &args.end)?;
+        let mut dataset = EmissionDataset::default();
+        for scope in self.scopes_of(args.provider.as_deref()) {
+            let mut query = scope.clone();
+            query.time_period = period.clone();
+            dataset.extend(self.run(&query).await?);
+        }
+        let title = format!(
+            "Emissions from {} to {}",
+            period.start.format("%Y-%m-%d"),
+            period.end.format("%Y-%m-%d")
+        );
+        let dimension = args.group_by.unwrap_or(Dimension::Service);
+        Ok(Report::by_dimensions(title, &dataset, &[dimension]).to_markdown())
+    }
+
+    fn scopes_of<'a>(
+        &'a self,
+        provider: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a EmissionQuery> {
+        self.scopes
+            .iter()
+            .filter(move |scope| provider.is_none_or(|provider| scope.provider == provider))
+    }
+
+    async fn run(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        self.client.query_emissions(query).await
+    }
+}
+
+fn not_approved(what: &str) -> CarbemError {
+    CarbemError::Config(format!("{} is not in an approved scope", what))
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn tools() -> Value {
+    let date = |what: &str| json!({"type": "string", "description": format!("{} date, YYYY-MM-DD or RFC 3339", what)});
+    let strings = |description: &str| json!({"type": "array", "items": {"type": "string"}, "description": description});
+    json!([
+        {
+            "name": "list_regions",
+            "description": "List the cloud providers and regions whose emissions can be queried. An empty region list means every region of the provider.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "provider": {"type": "string", "description": "Only this provider, e.g. azure"},
+                },
+            },
+            "annotations": {"readOnlyHint": true},
+        },
+        {
+            "name": "query_emissions",
+            "description": "Carbon emissions of a cloud provider over a period, in kg CO2e, as JSON records with region, service and month.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "provider": {"type": "string", "description": "Provider from list_regions, e.g. azure"},
+                    "start": date("First"),
+                    "end": date("Last"),
+                    "regions": strings("Only these regions, from list_regions"),
+                    "services": strings("Only these services"),
+                },
+                "required": ["provider", "start", "end"],
+            },
+            "annotations": {"readOnlyHint": true},
+        },
+        {
+            "name": "summarize_period",
+            "description": "Markdown summary of carbon emissions over a period: total, breakdown by a dimension and by month.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "provider": {"type": "string", "description": "Only this provider (defaults to all)"},
+                    "start": date("First"),
+                    "end": date("Last"),
+                    "group_by": {
+                        "enum": ["provider", "region", "service", "service_category"],
+                        "description": "Breakdown dimension (defaults to service)",
+                    },
+                },
+                "required": ["start", "end"],
+            },
+            "annotations": {"readOnlyHint": true},
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> McpServer {
+        let scope = serde_json::from_value(json!({
+            "provider": "azure",
+            "regions": ["westeurope", "northeurope"],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"}
+        }))
+        .unwrap();
+        McpServer::new(CarbemClient::demo()).with_scope(scope)
+    }
+
+    async fn call(server: &McpServer, name: &str, arguments: Value) -> Value {
+        server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "tools/call",
+                "params": {"name": name, "arguments": arguments},
+            }))
+            .await
+            .unwrap()["result"]
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_tools() {
+        let server = server();
+
+        let query = call(
+            &server,
+            "query_emissions",
+            json!({"provider": "azure", "start": "2024-02-01", "end": "2024-03-31", "regions": ["westeurope"]}),
+        )
+        .await;
+        assert_eq!(query["isError"], false);
+        let records: Vec<Value> =
+            serde_json::from_str(query["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert!(!records.is_empty());
+        assert!(records.iter().all(|record| record["region"] == "westeurope"
+            && record["period_start"].as_str().unwrap() >= "2024-02"));
+
+        let denied = call(
+            &server,
+            "query_emissions",
+            json!({"provider": "azure", "start": "2024-02-01", "end": "2024-03-31", "regions": ["eastus"]}),
+        )
+        .await;
+        assert_eq!(denied["isError"], true);
+        assert!(
+            denied["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("'eastus'")
+        );
+        let other = call(
+            &server,
+            "query_emissions",
+            json!({"provider": "ibm", "start": "2024-02-01", "end": "2024-03-31"}),
+        )
+        .await;
+        assert_eq!(other["isError"], true);
+
+        let summary = call(
+            &server,
+            "summarize_period",
+            json!({"start": "2024-01-01", "end": "2024-03-31", "group_by": "region"}),
+        )
+        .await;
+        let text = summary["content"][0]["text"].as_str().unwrap();
+        assert!(text.starts_with("# Emissions from 2024-01-01 to 2024-03-31"));
+        assert!(text.contains("## By region"));
+
+        let regions = call(&server, "list_regions", json!({})).await;
+        assert!(
+            regions["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("northeurope")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_protocol() {
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18"}}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"resources/list"}"#,
+            "not json",
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        server().serve(input.as_bytes(), &mut output).await.unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert_eq!(responses[1]["result"]["tools"].as_array().unwrap().len(), 3);
+        assert_eq!(responses[2]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[3]["error"]["code"], PARSE_ERROR);
+    }
+}
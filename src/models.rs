@@ -1,6 +1,15 @@
+use crate::error::{CarbemError, Result};
 use crate::providers::config::ProviderQueryConfig;
-use chrono::{DateTime, Utc};
+use crate::taxonomy::ServiceCategory;
+use crate::transport::{HttpRequest, request_hash};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 
 /// Represents carbon emission data from a cloud provider
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +23,10 @@ pub struct CarbonEmission {
     /// The service or resource type
     pub service: Option<String>,
 
+    /// Provider-neutral category of the service, see [`ServiceCategory::classify`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_category: Option<ServiceCategory>,
+
     /// Carbon emissions in kilograms of CO2 equivalent
     pub emissions_kg_co2eq: f64,
 
@@ -22,6 +35,150 @@ pub struct CarbonEmission {
 
     /// Additional metadata
     pub metadata: Option<EmissionMetadata>,
+
+    /// Provider call that returned the emission, see [`Lineage`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lineage: Option<Lineage>,
+}
+
+/// Where an emission comes from, to trace a reported figure back to its source call
+///
+/// Set by providers on every emission they return. The request hash is the
+/// one of the [`AuditRecord`](crate::audit::AuditRecord) of the call, when an
+/// audit log is kept. Emissions computed by carbem from several others, e.g.
+/// rollups, have no lineage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lineage {
+    /// HTTP method and URL of the provider endpoint, without query string,
+    /// e.g. `GET https://api.carbon-calculator.cloud.ibm.com/v1/carbon_emissions`
+    pub endpoint: String,
+
+    /// [`request_hash`](crate::transport::request_hash) of the provider request
+    pub request_hash: String,
+
+    /// When the provider request was sent
+    pub retrieved_at: DateTime<Utc>,
+
+    /// Version of carbem that retrieved the emission
+    pub carbem_version: String,
+}
+
+impl Lineage {
+    /// Lineage of emissions returned by `request`, sent now
+    pub fn of_request(request: &HttpRequest) -> Self {
+        Self {
+            endpoint: format!(
+                "{} {}",
+                request.method,
+                request.url.split(['?', '#']).next().unwrap_or_default()
+            ),
+            request_hash: request_hash(request),
+            retrieved_at: Utc::now(),
+            carbem_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// Sort emissions in carbem's canonical order
+///
+/// Emissions are ordered by provider, region, service (emissions without a
+/// service first), period start, period end and finally by value, so the
+/// order never depends on the order a provider returned them in.
+pub fn sort_emissions(emissions: &mut [CarbonEmission]) {
+    emissions.sort_by(|a, b| {
+        a.provider
+            .cmp(&b.provider)
+            .then_with(|| a.region.cmp(&b.region))
+            .then_with(|| a.service.cmp(&b.service))
+            .then_with(|| a.time_period.start.cmp(&b.time_period.start))
+            .then_with(|| a.time_period.end.cmp(&b.time_period.end))
+            .then_with(|| a.emissions_kg_co2eq.total_cmp(&b.emissions_kg_co2eq))
+    });
+}
+
+/// A carbon emission with its period and metadata flattened into columns
+///
+/// Meant for tabular exports (CSV, Parquet, SQL): every field is a scalar and
+/// every record has the same columns. Provider-specific data, whose shape
+/// varies by provider, is kept as a JSON-encoded string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlatEmissionRecord {
+    /// The cloud provider
+    pub provider: String,
+
+    /// The region where the emissions occurred
+    pub region: String,
+
+    /// The service or resource type
+    pub service: Option<String>,
+
+    /// Carbon emissions in kilograms of CO2 equivalent
+    pub emissions_kg_co2eq: f64,
+
+    /// Start of the measurement period
+    pub period_start: DateTime<Utc>,
+
+    /// End of the measurement period
+    pub period_end: DateTime<Utc>,
+
+    /// Energy consumption in kWh
+    pub energy_kwh: Option<f64>,
+
+    /// Grid carbon intensity (gCO2eq/kWh)
+    pub grid_carbon_intensity: Option<f64>,
+
+    /// Renewable energy percentage
+    pub renewable_percentage: Option<f64>,
+
+    /// Date alignment applied to the query
+    pub date_alignment: Option<DateAlignment>,
+
+    /// Provider-specific data, JSON-encoded
+    pub provider_data: Option<String>,
+
+    /// Provider endpoint of the [`Lineage`]
+    pub lineage_endpoint: Option<String>,
+
+    /// Request hash of the [`Lineage`]
+    pub lineage_request_hash: Option<String>,
+
+    /// Retrieval time of the [`Lineage`]
+    pub lineage_retrieved_at: Option<DateTime<Utc>>,
+
+    /// Version of carbem of the [`Lineage`]
+    pub lineage_carbem_version: Option<String>,
+}
+
+impl From<&CarbonEmission> for FlatEmissionRecord {
+    fn from(emission: &CarbonEmission) -> Self {
+        let metadata = emission.metadata.as_ref();
+        let lineage = emission.lineage.as_ref();
+        Self {
+            provider: emission.provider.clone(),
+            region: emission.region.clone(),
+            service: emission.service.clone(),
+            emissions_kg_co2eq: emission.emissions_kg_co2eq,
+            period_start: emission.time_period.start,
+            period_end: emission.time_period.end,
+            energy_kwh: metadata.and_then(|m| m.energy_kwh),
+            grid_carbon_intensity: metadata.and_then(|m| m.grid_carbon_intensity),
+            renewable_percentage: metadata.and_then(|m| m.renewable_percentage),
+            date_alignment: metadata.and_then(|m| m.date_alignment),
+            provider_data: metadata
+                .and_then(|m| m.provider_data.as_ref())
+                .map(|data| data.to_string()),
+            lineage_endpoint: lineage.map(|l| l.endpoint.clone()),
+            lineage_request_hash: lineage.map(|l| l.request_hash.clone()),
+            lineage_retrieved_at: lineage.map(|l| l.retrieved_at),
+            lineage_carbem_version: lineage.map(|l| l.carbem_version.clone()),
+        }
+    }
+}
+
+impl From<CarbonEmission> for FlatEmissionRecord {
+    fn from(emission: CarbonEmission) -> Self {
+        Self::from(&emission)
+    }
 }
 
 /// Time period for carbon emission measurements
@@ -34,6 +191,287 @@ pub struct TimePeriod {
     pub end: DateTime<Utc>,
 }
 
+impl TimePeriod {
+    /// Parse a period from UTC dates, e.g. `2024-01-01`, or RFC 3339 timestamps
+    ///
+    /// A date `end` includes the whole day.
+    pub fn parse(start: &str, end: &str) -> Result<Self> {
+        let parse = |value: &str, end_of_day: bool| -> Result<DateTime<Utc>> {
+            if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+                return Ok(date.with_timezone(&Utc));
+            }
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+                CarbemError::Config(format!("invalid date '{}', expected YYYY-MM-DD", value))
+            })?;
+            let start = date.and_time(NaiveTime::MIN).and_utc();
+            Ok(if end_of_day {
+                start + Duration::days(1) - Duration::seconds(1)
+            } else {
+                start
+            })
+        };
+        let period = Self {
+            start: parse(start, false)?,
+            end: parse(end, true)?,
+        };
+        if period.end < period.start {
+            return Err(CarbemError::Config(
+                "time_period end must not be before start".to_string(),
+            ));
+        }
+        Ok(period)
+    }
+
+    /// Build a period from local date times, e.g. `DateTime<FixedOffset>`
+    pub fn from_local<Tz: TimeZone>(start: DateTime<Tz>, end: DateTime<Tz>) -> Self {
+        Self {
+            start: start.with_timezone(&Utc),
+            end: end.with_timezone(&Utc),
+        }
+    }
+
+    /// Map this period onto whole UTC months according to `alignment`
+    ///
+    /// The returned period starts at the first instant of the first month and
+    /// ends at the first instant of the last month, which is how providers
+    /// express inclusive monthly ranges.
+    pub fn align(&self, alignment: DateAlignment) -> Result<TimePeriod> {
+        self.align_in(alignment, &QueryTimezone::Utc)
+    }
+
+    /// Map this period onto whole months of `timezone` according to `alignment`
+    ///
+    /// Month boundaries are evaluated in `timezone`; the returned instants are
+    /// still expressed in UTC. Use [`QueryTimezone::local_date`] to get the
+    /// local calendar dates expected by providers.
+    pub fn align_in(
+        &self,
+        alignment: DateAlignment,
+        timezone: &QueryTimezone,
+    ) -> Result<TimePeriod> {
+        if self.end < self.start {
+            return Err(CarbemError::Config(
+                "time_period end must not be before start".to_string(),
+            ));
+        }
+
+        let month_start = |date| month_start(date, timezone);
+        let next_month_start = |month| next_month_start(month, timezone);
+        let previous_month_start = |month| previous_month_start(month, timezone);
+
+        let first_touched = month_start(self.start);
+        let last_touched = month_start(self.end);
+
+        let (first, last) = match alignment {
+            DateAlignment::ExpandToFullMonths => (first_touched, last_touched),
+            DateAlignment::Truncate | DateAlignment::Strict => {
+                let start_aligned = self.start == first_touched;
+                // An end on a month's first instant excludes that month
+                let end_is_month_start = self.end == last_touched;
+                let end_is_month_end =
+                    self.end >= next_month_start(last_touched) - Duration::seconds(1);
+
+                if alignment == DateAlignment::Strict
+                    && !(start_aligned && (end_is_month_start || end_is_month_end))
+                {
+                    return Err(CarbemError::Config(format!(
+                        "time_period {} to {} is not aligned on month boundaries (strict date alignment)",
+                        self.start.to_rfc3339(),
+                        self.end.to_rfc3339()
+                    )));
+                }
+
+                let first = if start_aligned {
+                    first_touched
+                } else {
+                    next_month_start(first_touched)
+                };
+                let last = if end_is_month_end {
+                    last_touched
+                } else {
+                    previous_month_start(last_touched)
+                };
+
+                if last < first {
+                    return Err(CarbemError::Config(format!(
+                        "time_period {} to {} does not cover any full month",
+                        self.start.to_rfc3339(),
+                        self.end.to_rfc3339()
+                    )));
+                }
+                (first, last)
+            }
+        };
+
+        Ok(TimePeriod {
+            start: first,
+            end: last,
+        })
+    }
+
+    /// Half-open range covering the months selected by [`align_in`](Self::align_in)
+    ///
+    /// Starts at the first instant of the first month and ends at the first
+    /// instant after the last month.
+    pub fn month_range_in(
+        &self,
+        alignment: DateAlignment,
+        timezone: &QueryTimezone,
+    ) -> Result<TimePeriod> {
+        let aligned = self.align_in(alignment, timezone)?;
+        Ok(TimePeriod {
+            start: aligned.start,
+            end: next_month_start(aligned.end, timezone),
+        })
+    }
+
+    /// Number of months of `timezone` this period spans once aligned with `alignment`
+    pub fn month_count(&self, alignment: DateAlignment, timezone: &QueryTimezone) -> Result<u32> {
+        let range = self.month_range_in(alignment, timezone)?;
+        let mut count = 0;
+        let mut month = range.start;
+        while month < range.end {
+            count += 1;
+            month = next_month_start(month, timezone);
+        }
+        Ok(count)
+    }
+}
+
+// First instant of the month containing `date`, in `timezone`
+fn month_start(date: DateTime<Utc>, timezone: &QueryTimezone) -> DateTime<Utc> {
+    let local = timezone.local_date(date);
+    let day = NaiveDate::from_ymd_opt(local.year(), local.month(), 1)
+        .expect("first day of an existing month is valid");
+    timezone.to_utc(day.and_time(NaiveTime::MIN))
+}
+
+// First instant of the month following the month starting at `month`
+pub(crate) fn next_month_start(month: DateTime<Utc>, timezone: &QueryTimezone) -> DateTime<Utc> {
+    month_start(month + Duration::days(32), timezone)
+}
+
+// First instant of the month preceding the month starting at `month`
+fn previous_month_start(month: DateTime<Utc>, timezone: &QueryTimezone) -> DateTime<Utc> {
+    month_start(month - Duration::days(1), timezone)
+}
+
+/// Timezone in which month boundaries of a query are evaluated
+///
+/// Parsed from `"UTC"`, a fixed offset such as `"+02:00"`, or an IANA zone
+/// name such as `"Europe/Paris"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum QueryTimezone {
+    /// Coordinated Universal Time
+    #[default]
+    Utc,
+
+    /// Fixed offset from UTC
+    Fixed(FixedOffset),
+
+    /// IANA timezone, following daylight saving time changes
+    Named(chrono_tz::Tz),
+}
+
+impl QueryTimezone {
+    /// Local calendar date of `instant` in this timezone
+    pub fn local_date(&self, instant: DateTime<Utc>) -> NaiveDate {
+        self.to_local(instant).date()
+    }
+
+    fn to_local(self, instant: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            QueryTimezone::Utc => instant.naive_utc(),
+            QueryTimezone::Fixed(offset) => instant.with_timezone(&offset).naive_local(),
+            QueryTimezone::Named(tz) => instant.with_timezone(&tz).naive_local(),
+        }
+    }
+
+    // Earliest UTC instant of a local time; local times skipped by a DST
+    // change resolve to the first instant after the gap
+    fn to_utc(self, local: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            QueryTimezone::Utc => Utc.from_utc_datetime(&local),
+            QueryTimezone::Fixed(offset) => Utc.from_utc_datetime(&(local - offset)),
+            QueryTimezone::Named(tz) => {
+                // Gaps span up to a day, as when Pacific/Apia skipped 2011-12-30
+                let mut candidate = local;
+                while candidate - local <= Duration::days(2) {
+                    if let Some(instant) = tz.from_local_datetime(&candidate).earliest() {
+                        return instant.with_timezone(&Utc);
+                    }
+                    candidate += Duration::minutes(15);
+                }
+                // No zone has longer gaps; fall back to the offset in force at `local` as UTC
+                let offset = tz.offset_from_utc_datetime(&local).fix();
+                Utc.from_utc_datetime(&(local - offset))
+            }
+        }
+    }
+}
+
+impl FromStr for QueryTimezone {
+    type Err = CarbemError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if value.eq_ignore_ascii_case("utc") || value == "Z" {
+            return Ok(QueryTimezone::Utc);
+        }
+        if let Ok(offset) = value.parse::<FixedOffset>() {
+            return Ok(QueryTimezone::Fixed(offset));
+        }
+        value.parse::<chrono_tz::Tz>().map(QueryTimezone::Named).map_err(|_| {
+            CarbemError::Config(format!(
+                "Invalid timezone '{}': expected \"UTC\", an offset like \"+02:00\" or an IANA name like \"Europe/Paris\"",
+                value
+            ))
+        })
+    }
+}
+
+impl TryFrom<String> for QueryTimezone {
+    type Error = CarbemError;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<QueryTimezone> for String {
+    fn from(timezone: QueryTimezone) -> Self {
+        timezone.to_string()
+    }
+}
+
+impl fmt::Display for QueryTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryTimezone::Utc => f.write_str("UTC"),
+            QueryTimezone::Fixed(offset) => write!(f, "{}", offset),
+            QueryTimezone::Named(tz) => f.write_str(tz.name()),
+        }
+    }
+}
+
+/// How a query's time period is mapped onto the monthly granularity of providers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateAlignment {
+    /// Include every month the period touches, e.g. March 15 – April 15
+    /// returns March and April in full
+    #[default]
+    ExpandToFullMonths,
+
+    /// Only include months entirely inside the period, e.g. March 15 – May 20
+    /// returns April only; fails when no month is fully covered
+    Truncate,
+
+    /// Fail unless the period starts on a month's first instant and ends
+    /// either on a month's first instant (excluded) or its last second
+    Strict,
+}
+
 /// Additional metadata for carbon emissions
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmissionMetadata {
@@ -46,8 +484,109 @@ pub struct EmissionMetadata {
     // Renewable energy percentage
     pub renewable_percentage: Option<f64>,
 
+    // Date alignment applied to the query that produced this emission
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_alignment: Option<DateAlignment>,
+
     // Additional provider-specific data
     pub provider_data: Option<serde_json::Value>,
+
+    // Resource tags (labels) the emission is attributed to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+
+    // Whether the value is an estimate (e.g. sample data) rather than reported
+    // by the provider, unknown when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated: Option<bool>,
+}
+
+/// A provider row that could only be converted by defaulting a value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversionIssue {
+    /// Provider of the row
+    pub provider: String,
+
+    /// Index of the row in the provider response
+    pub row: usize,
+
+    /// Provider field that could not be converted, e.g. `location`
+    pub field: String,
+
+    /// What is wrong with the field and the default that would be used
+    pub message: String,
+}
+
+impl ConversionIssue {
+    pub(crate) fn new(provider: &str, row: usize, field: &str, message: String) -> Self {
+        Self {
+            provider: provider.to_string(),
+            row,
+            field: field.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ConversionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} row {}, {}: {}",
+            self.provider, self.row, self.field, self.message
+        )
+    }
+}
+
+/// Condition on a tag (label) of the resources an emission is attributed to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagFilter {
+    /// Tag key, matched exactly
+    pub key: String,
+
+    /// Expected tag value, or its beginning when `prefix` is set
+    pub value: String,
+
+    /// Match values starting with `value` instead of equal to it
+    #[serde(default)]
+    pub prefix: bool,
+}
+
+impl TagFilter {
+    /// Match emissions tagged `key` with exactly `value`
+    pub fn equals(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            prefix: false,
+        }
+    }
+
+    /// Match emissions tagged `key` with a value starting with `prefix`
+    pub fn prefix(key: impl Into<String>, prefix: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: prefix.into(),
+            prefix: true,
+        }
+    }
+
+    /// Whether `emission` carries a matching tag
+    pub fn matches(&self, emission: &CarbonEmission) -> bool {
+        emission
+            .metadata
+            .as_ref()
+            .is_some_and(|metadata| self.matches_tags(&metadata.tags))
+    }
+
+    /// Whether `tags` holds a matching tag
+    pub fn matches_tags(&self, tags: &BTreeMap<String, String>) -> bool {
+        match tags.get(&self.key) {
+            Some(value) if self.prefix => value.starts_with(&self.value),
+            Some(value) => *value == self.value,
+            None => false,
+        }
+    }
 }
 
 /// Configuration for querying carbon emissions
@@ -71,4 +610,436 @@ pub struct EmissionQuery {
     /// Optional: provider-specific configuration (type-safe)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub provider_config: Option<ProviderQueryConfig>,
+
+    /// Whether to return the raw provider responses (defaults to parsed data only)
+    #[serde(default)]
+    pub raw_response: RawResponseMode,
+
+    /// How the time period is mapped onto whole months (defaults to expanding)
+    #[serde(default)]
+    pub date_alignment: DateAlignment,
+
+    /// Timezone in which month boundaries are evaluated (defaults to UTC)
+    #[serde(default)]
+    pub timezone: QueryTimezone,
+
+    /// Plan the provider requests without sending them (defaults to querying)
+    ///
+    /// The plan is returned in [`EmissionResult::planned_requests`]; no
+    /// emission is returned.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Provider instances to query in order, failing over on error
+    ///
+    /// Entries are `provider` or `provider:instance`, e.g.
+    /// `["azure:prod", "azure:dr"]`; the next entry is tried when one fails.
+    /// Defaults to the first configured instance of `provider`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route: Vec<String>,
+
+    /// Only include emissions matching every filter (defaults to no filter)
+    ///
+    /// Providers able to filter by tag do it in their request; the results
+    /// of the others are filtered after the response. Emissions without tags
+    /// never match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tag_filters: Vec<TagFilter>,
+
+    /// Fail with [`CarbemError::LossyConversion`] instead of defaulting values
+    /// of rows that cannot be fully converted (defaults to lenient mapping)
+    ///
+    /// E.g. IBM rows without a location are otherwise reported in region
+    /// `unknown`, and rows with an unparsable month over the query period.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl EmissionQuery {
+    /// Whether `emission` matches every tag filter of the query
+    pub fn matches_tags(&self, emission: &CarbonEmission) -> bool {
+        self.tag_filters
+            .iter()
+            .all(|filter| filter.matches(emission))
+    }
+}
+
+/// Controls whether raw provider responses are returned with query results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawResponseMode {
+    /// Return parsed emissions only
+    #[default]
+    None,
+
+    /// Return parsed emissions and the raw provider responses
+    Alongside,
+
+    /// Return the raw provider responses without mapping them to emissions
+    Only,
+}
+
+/// Result of a query, including raw provider responses when requested
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmissionResult {
+    /// Parsed emissions (empty with `RawResponseMode::Only`)
+    pub emissions: Vec<CarbonEmission>,
+
+    /// Raw JSON responses as returned by the provider, one per API call
+    pub raw_responses: Vec<serde_json::Value>,
+
+    /// Requests a dry run would have sent, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub planned_requests: Vec<PlannedRequest>,
+}
+
+/// A provider request planned by a dry run, with credentials redacted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedRequest {
+    /// Provider sending the request
+    pub provider: String,
+
+    /// HTTP method
+    pub method: String,
+
+    /// Full URL, including query parameters
+    pub url: String,
+
+    /// HTTP headers
+    pub headers: BTreeMap<String, String>,
+
+    /// Optional: JSON body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<serde_json::Value>,
+}
+
+impl PlannedRequest {
+    /// Describe a request from its parts, redacting secrets known to `redactor`
+    pub(crate) fn new(
+        provider: &str,
+        method: &str,
+        url: &str,
+        headers: &reqwest::header::HeaderMap,
+        body: Option<serde_json::Value>,
+        redactor: &crate::redact::Redactor,
+    ) -> Self {
+        Self {
+            provider: provider.to_string(),
+            method: method.to_string(),
+            url: redactor.redact(url),
+            headers: headers
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes());
+                    (name.to_string(), redactor.redact(&value))
+                })
+                .collect(),
+            body: body.map(|body| {
+                serde_json::from_str(&redactor.redact(&body.to_string())).unwrap_or(body)
+            }),
+        }
+    }
+}
+
+impl EmissionResult {
+    /// Record `lineage` on every emission
+    pub(crate) fn record_lineage(&mut self, lineage: &Lineage) {
+        for emission in &mut self.emissions {
+            emission.lineage = Some(lineage.clone());
+        }
+    }
+
+    /// Record the date alignment used by the query on every emission
+    pub(crate) fn record_date_alignment(&mut self, alignment: DateAlignment) {
+        for emission in &mut self.emissions {
+            if let Some(metadata) = &mut emission.metadata {
+                metadata.date_alignment = Some(alignment);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn period(start: (i32, u32, u32), end: (i32, u32, u32, u32, u32, u32)) -> TimePeriod {
+        TimePeriod {
+            start: Utc
+                .with_ymd_and_hms(start.0, start.1, start.2, 0, 0, 0)
+                .unwrap(),
+            end: Utc
+                .with_ymd_and_hms(end.0, end.1, end.2, end.3, end.4, end.5)
+                .unwrap(),
+        }
+    }
+
+    fn month(year: i32, month: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_align_expand_to_full_months() {
+        let aligned = period((2024, 3, 15), (2024, 4, 15, 0, 0, 0))
+            .align(DateAlignment::ExpandToFullMonths)
+            .unwrap();
+        assert_eq!(aligned.start, month(2024, 3));
+        assert_eq!(aligned.end, month(2024, 4));
+    }
+
+    #[test]
+    fn test_align_truncate() {
+        let aligned = period((2024, 3, 15), (2024, 5, 20, 0, 0, 0))
+            .align(DateAlignment::Truncate)
+            .unwrap();
+        assert_eq!(aligned.start, month(2024, 4));
+        assert_eq!(aligned.end, month(2024, 4));
+
+        // December end of month rolls over correctly
+        let aligned = period((2024, 11, 1), (2024, 12, 31, 23, 59, 59))
+            .align(DateAlignment::Truncate)
+            .unwrap();
+        assert_eq!(aligned.start, month(2024, 11));
+        assert_eq!(aligned.end, month(2024, 12));
+
+        let result = period((2024, 3, 15), (2024, 4, 15, 0, 0, 0)).align(DateAlignment::Truncate);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("does not cover any full month")
+        );
+    }
+
+    #[test]
+    fn test_align_in_timezone() {
+        let paris: QueryTimezone = "Europe/Paris".parse().unwrap();
+        // 2024-02-29T23:30Z is already March 1st in Paris
+        let period = TimePeriod {
+            start: Utc.with_ymd_and_hms(2024, 2, 29, 23, 30, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 31, 22, 30, 0).unwrap(),
+        };
+
+        let aligned = period
+            .align_in(DateAlignment::ExpandToFullMonths, &paris)
+            .unwrap();
+        assert_eq!(
+            aligned.start,
+            Utc.with_ymd_and_hms(2024, 2, 29, 23, 0, 0).unwrap()
+        );
+        assert_eq!(paris.local_date(aligned.start).to_string(), "2024-03-01");
+        // 2024-03-31T22:30Z is April 1st 00:30 in Paris (summer time)
+        assert_eq!(paris.local_date(aligned.end).to_string(), "2024-04-01");
+
+        let utc_aligned = period.align(DateAlignment::ExpandToFullMonths).unwrap();
+        assert_eq!(utc_aligned.start, month(2024, 2));
+        assert_eq!(utc_aligned.end, month(2024, 3));
+
+        // Samoa skipped 2011-12-30 entirely: its midnight resolves to the next day's
+        let apia: QueryTimezone = "Pacific/Apia".parse().unwrap();
+        let skipped = NaiveDate::from_ymd_opt(2011, 12, 30).unwrap();
+        assert_eq!(
+            apia.to_utc(skipped.and_time(NaiveTime::MIN)),
+            Utc.with_ymd_and_hms(2011, 12, 30, 10, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_time_period_from_local() {
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        let period = TimePeriod::from_local(
+            offset.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap(),
+            offset.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(
+            period.start,
+            Utc.with_ymd_and_hms(2024, 3, 31, 22, 0, 0).unwrap()
+        );
+
+        let aligned = period
+            .align_in(DateAlignment::Strict, &QueryTimezone::Fixed(offset))
+            .unwrap();
+        assert_eq!(aligned.start, period.start);
+    }
+
+    #[test]
+    fn test_time_period_parse() {
+        let parsed = TimePeriod::parse("2024-01-01", "2024-01-31").unwrap();
+        let expected = period((2024, 1, 1), (2024, 1, 31, 23, 59, 59));
+        assert_eq!((parsed.start, parsed.end), (expected.start, expected.end));
+        let parsed = TimePeriod::parse("2024-01-01T10:00:00+02:00", "2024-01-02").unwrap();
+        assert_eq!(
+            parsed.start,
+            Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0).unwrap()
+        );
+        assert!(TimePeriod::parse("2024-02-01", "2024-01-01").is_err());
+        assert!(TimePeriod::parse("01/02/2024", "2024-03-01").is_err());
+    }
+
+    #[test]
+    fn test_query_timezone_parsing() {
+        assert_eq!("UTC".parse::<QueryTimezone>().unwrap(), QueryTimezone::Utc);
+        assert_eq!(
+            "+05:30".parse::<QueryTimezone>().unwrap().to_string(),
+            "+05:30"
+        );
+        assert_eq!(
+            "America/New_York"
+                .parse::<QueryTimezone>()
+                .unwrap()
+                .to_string(),
+            "America/New_York"
+        );
+        assert!("Mars/Olympus".parse::<QueryTimezone>().is_err());
+
+        let timezone: QueryTimezone = serde_json::from_str("\"Europe/Paris\"").unwrap();
+        assert_eq!(
+            serde_json::to_string(&timezone).unwrap(),
+            "\"Europe/Paris\""
+        );
+    }
+
+    #[test]
+    fn test_sort_emissions() {
+        let emission = |region: &str, service: Option<&str>, month: u32| CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: service.map(str::to_string),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.0,
+            time_period: period((2024, month, 1), (2024, month + 1, 1, 0, 0, 0)),
+            metadata: None,
+        };
+        let mut emissions = vec![
+            emission("westeurope", Some("Storage"), 2),
+            emission("westeurope", Some("Storage"), 1),
+            emission("eastus", Some("Compute"), 1),
+            emission("westeurope", None, 3),
+        ];
+
+        sort_emissions(&mut emissions);
+        let keys: Vec<(&str, Option<&str>, u32)> = emissions
+            .iter()
+            .map(|e| {
+                (
+                    e.region.as_str(),
+                    e.service.as_deref(),
+                    e.time_period.start.month(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("eastus", Some("Compute"), 1),
+                ("westeurope", None, 3),
+                ("westeurope", Some("Storage"), 1),
+                ("westeurope", Some("Storage"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tag_filters() {
+        let mut tagged = CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.0,
+            time_period: period((2024, 1, 1), (2024, 1, 31, 23, 59, 59)),
+            metadata: None,
+        };
+        let team = TagFilter::equals("team", "search");
+        assert!(!team.matches(&tagged));
+
+        tagged.metadata = Some(EmissionMetadata {
+            energy_kwh: None,
+            grid_carbon_intensity: None,
+            renewable_percentage: None,
+            date_alignment: None,
+            provider_data: None,
+            tags: BTreeMap::from([
+                ("team".to_string(), "search".to_string()),
+                ("env".to_string(), "prod-eu".to_string()),
+            ]),
+            estimated: None,
+        });
+        assert!(team.matches(&tagged));
+        assert!(TagFilter::prefix("env", "prod").matches(&tagged));
+        assert!(!TagFilter::equals("env", "prod").matches(&tagged));
+
+        let query: EmissionQuery = serde_json::from_value(serde_json::json!({
+            "provider": "azure",
+            "regions": [],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-01-31T00:00:00Z"},
+            "tag_filters": [
+                {"key": "team", "value": "search"},
+                {"key": "env", "value": "prod-", "prefix": true}
+            ]
+        }))
+        .unwrap();
+        assert!(query.matches_tags(&tagged));
+    }
+
+    #[test]
+    fn test_flat_emission_record() {
+        let emission = CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.5,
+            time_period: period((2024, 1, 1), (2024, 2, 1, 0, 0, 0)),
+            metadata: Some(EmissionMetadata {
+                energy_kwh: Some(10.0),
+                grid_carbon_intensity: None,
+                renewable_percentage: None,
+                date_alignment: Some(DateAlignment::Strict),
+                provider_data: Some(serde_json::json!({"dataType": "MonthlySummaryData"})),
+                tags: Default::default(),
+                estimated: None,
+            }),
+        };
+
+        let record = FlatEmissionRecord::from(&emission);
+        assert_eq!(record.energy_kwh, Some(10.0));
+        assert_eq!(
+            record.provider_data.as_deref(),
+            Some(r#"{"dataType":"MonthlySummaryData"}"#)
+        );
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["period_start"], "2024-01-01T00:00:00Z");
+        assert_eq!(json["date_alignment"], "strict");
+        assert!(json["grid_carbon_intensity"].is_null());
+
+        let without_metadata = FlatEmissionRecord::from(CarbonEmission {
+            metadata: None,
+            ..emission
+        });
+        assert_eq!(without_metadata.energy_kwh, None);
+        assert_eq!(without_metadata.provider_data, None);
+    }
+
+    #[test]
+    fn test_align_strict() {
+        // Exclusive end on the first instant of May
+        let aligned = period((2024, 3, 1), (2024, 5, 1, 0, 0, 0))
+            .align(DateAlignment::Strict)
+            .unwrap();
+        assert_eq!(aligned.start, month(2024, 3));
+        assert_eq!(aligned.end, month(2024, 4));
+
+        let result = period((2024, 3, 15), (2024, 5, 1, 0, 0, 0)).align(DateAlignment::Strict);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("not aligned on month boundaries")
+        );
+    }
 }
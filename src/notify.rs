@@ -0,0 +1,331 @@
+//! Periodic summaries posted to chat tools
+//!
+//! A [`Summary`] compares the last complete week or month to the one before:
+//! total, change, services that moved the most and, optionally, the share of a
+//! carbon budget used. [`SlackNotifier`] posts it to a Slack incoming webhook.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::error::{CarbemError, Result};
+
+/// Number of top movers listed in a summary
+const TOP_MOVERS: usize = 3;
+
+/// Period covered by a summary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryPeriod {
+    /// The last complete week, Monday to Sunday
+    Weekly,
+
+    /// The last complete calendar month
+    #[default]
+    Monthly,
+}
+
+/// A service whose emissions changed between two periods
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mover {
+    /// Service name
+    pub service: String,
+
+    /// Emissions in the summarized period, in kg CO2e
+    pub current_kg_co2eq: f64,
+
+    /// Emissions in the period before, in kg CO2e
+    pub previous_kg_co2eq: f64,
+}
+
+impl Mover {
+    /// Change in kg CO2e (negative when emissions decreased)
+    pub fn change_kg_co2eq(&self) -> f64 {
+        self.current_kg_co2eq - self.previous_kg_co2eq
+    }
+}
+
+/// Emissions of a period against a carbon budget
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    /// Budget of the period in kg CO2e
+    pub budget_kg_co2eq: f64,
+
+    /// Percent of the budget used
+    pub used_percent: f64,
+}
+
+/// Summary of a period compared to the period before
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    /// Summarized period
+    pub period: SummaryPeriod,
+
+    /// Start of the summarized period (inclusive)
+    pub start: DateTime<Utc>,
+
+    /// End of the summarized period (exclusive)
+    pub end: DateTime<Utc>,
+
+    /// Emissions in the period, in kg CO2e
+    pub total_kg_co2eq: f64,
+
+    /// Emissions in the period before, in kg CO2e
+    pub previous_kg_co2eq: f64,
+
+    /// Services with the largest absolute change
+    pub top_movers: Vec<Mover>,
+
+    /// Budget status, when a budget is given
+    pub budget: Option<BudgetStatus>,
+}
+
+impl Summary {
+    /// Summarize the last complete `period` before `as_of`
+    ///
+    /// Emissions are assigned to the period their time period starts in.
+    pub fn compute(dataset: &EmissionDataset, period: SummaryPeriod, as_of: DateTime<Utc>) -> Self {
+        let today = as_of.date_naive();
+        let (start, previous_start) = match period {
+            SummaryPeriod::Weekly => {
+                let this_week =
+                    today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+                (
+                    this_week - Duration::days(7),
+                    this_week - Duration::days(14),
+                )
+            }
+            SummaryPeriod::Monthly => {
+                let this_month = today.with_day(1).unwrap_or(today);
+                (this_month - Months::new(1), this_month - Months::new(2))
+            }
+        };
+        let end = match period {
+            SummaryPeriod::Weekly => start + Duration::days(7),
+            SummaryPeriod::Monthly => start + Months::new(1),
+        };
+
+        let window = |from: NaiveDate, to: NaiveDate| {
+            dataset
+                .emissions()
+                .iter()
+                .filter(|e| {
+                    let day = e.time_period.start.date_naive();
+                    day >= from && day < to
+                })
+                .cloned()
+                .collect::<EmissionDataset>()
+        };
+        let current = window(start, end);
+        let previous = window(previous_start, start);
+
+        let current_by_service = current.group_by_dimension(Dimension::Service);
+        let previous_by_service = previous.group_by_dimension(Dimension::Service);
+        let services: BTreeSet<&String> = current_by_service
+            .keys()
+            .chain(previous_by_service.keys())
+            .collect();
+        let mut top_movers: Vec<Mover> = services
+            .into_iter()
+            .map(|service| Mover {
+                service: service.clone(),
+                current_kg_co2eq: current_by_service.get(service).copied().unwrap_or(0.0),
+                previous_kg_co2eq: previous_by_service.get(service).copied().unwrap_or(0.0),
+            })
+            .filter(|mover| mover.change_kg_co2eq() != 0.0)
+            .collect();
+        top_movers.sort_by(|a, b| {
+            b.change_kg_co2eq()
+                .abs()
+                .total_cmp(&a.change_kg_co2eq().abs())
+        });
+        top_movers.truncate(TOP_MOVERS);
+
+        Self {
+            period,
+            start: Utc.from_utc_datetime(&start.and_hms_opt(0, 0, 0).unwrap_or_default()),
+            end: Utc.from_utc_datetime(&end.and_hms_opt(0, 0, 0).unwrap_or_default()),
+            total_kg_co2eq: current.total_kg_co2eq(),
+            previous_kg_co2eq: previous.total_kg_co2eq(),
+            top_movers,
+            budget: None,
+        }
+    }
+
+    /// Compare the summarized period to a budget in kg CO2e
+    pub fn with_budget_kg_co2eq(mut self, budget_kg_co2eq: f64) -> Self {
+        self.budget = (budget_kg_co2eq > 0.0).then(|| BudgetStatus {
+            budget_kg_co2eq,
+            used_percent: self.total_kg_co2eq / budget_kg_co2eq * 100.0,
+        });
+        self
+    }
+
+    /// Change from the period before in percent, `None` when it had no emissions
+    pub fn change_percent(&self) -> Option<f64> {
+        (self.previous_kg_co2eq != 0.0).then(|| {
+            (self.total_kg_co2eq - self.previous_kg_co2eq) / self.previous_kg_co2eq * 100.0
+        })
+    }
+
+    /// Render as Slack `mrkdwn` text
+    pub fn to_slack_text(&self) -> String {
+        let label = match self.period {
+            SummaryPeriod::Weekly => format!("Week of {}", self.start.format("%Y-%m-%d")),
+            SummaryPeriod::Monthly => self.start.format("%B %Y").to_string(),
+        };
+        let change = self
+            .change_percent()
+            .map(|change| format!(" ({:+.1}%)", change))
+            .unwrap_or_default();
+
+        let mut text = format!(
+            "*Cloud carbon emissions: {}*\nTotal: *{:.2} kg CO2e*{}",
+            label, self.total_kg_co2eq, change
+        );
+        if !self.top_movers.is_empty() {
+            text.push_str("\nTop movers:");
+            for mover in &self.top_movers {
+                text.push_str(&format!(
+                    "\n• {}: {:+.2} kg CO2e",
+                    mover.service,
+                    mover.change_kg_co2eq()
+                ));
+            }
+        }
+        if let Some(budget) = self.budget {
+            let status = if budget.used_percent > 100.0 {
+                ":red_circle: over budget"
+            } else {
+                ":large_green_circle: within budget"
+            };
+            text.push_str(&format!(
+                "\nBudget: {:.0}% of {:.2} kg CO2e used, {}",
+                budget.used_percent, budget.budget_kg_co2eq, status
+            ));
+        }
+        text
+    }
+}
+
+/// Posts summaries to a Slack incoming webhook
+pub struct SlackNotifier {
+    webhook_url: String,
+    http_client: reqwest::Client,
+}
+
+impl SlackNotifier {
+    /// Create a notifier posting to `webhook_url`
+    pub fn new(webhook_url: impl Into<String>) -> Self {
+        Self {
+            webhook_url: webhook_url.into(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Post `summary` to the webhook
+    pub async fn notify(&self, summary: &Summary) -> Result<()> {
+        let response = self
+            .http_client
+            .post(&self.webhook_url)
+            .json(&json!({ "text": summary.to_slack_text() }))
+            .send()
+            .await
+            .map_err(CarbemError::Http)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(CarbemError::Other(format!(
+                "Slack webhook failed with status {}: {}",
+                status, body
+            )));
+        }
+        Ok(())
+    }
+}
+
+// The webhook URL embeds its secret
+impl std::fmt::Debug for SlackNotifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlackNotifier")
+            .field("webhook_url", &crate::redact::REDACTED)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+
+    fn emission(service: &str, year: i32, month: u32, day: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: Some(service.to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + Duration::days(1),
+            },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_monthly_summary() {
+        let dataset = EmissionDataset::new(vec![
+            emission("Storage", 2024, 4, 1, 100.0),
+            emission("Compute", 2024, 4, 1, 50.0),
+            emission("Storage", 2024, 5, 1, 90.0),
+            emission("Network", 2024, 5, 1, 30.0),
+            emission("Storage", 2024, 6, 1, 500.0),
+        ]);
+        let as_of = Utc.with_ymd_and_hms(2024, 6, 10, 0, 0, 0).unwrap();
+
+        let summary =
+            Summary::compute(&dataset, SummaryPeriod::Monthly, as_of).with_budget_kg_co2eq(100.0);
+        assert_eq!(
+            summary.start,
+            Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(summary.total_kg_co2eq, 120.0);
+        assert_eq!(summary.previous_kg_co2eq, 150.0);
+        assert_eq!(summary.change_percent(), Some(-20.0));
+        let movers: Vec<&str> = summary
+            .top_movers
+            .iter()
+            .map(|m| m.service.as_str())
+            .collect();
+        assert_eq!(movers, vec!["Compute", "Network", "Storage"]);
+
+        let text = summary.to_slack_text();
+        assert!(text.starts_with("*Cloud carbon emissions: May 2024*"));
+        assert!(text.contains("(-20.0%)"));
+        assert!(text.contains("• Compute: -50.00 kg CO2e"));
+        assert!(text.contains("120% of 100.00 kg CO2e used, :red_circle: over budget"));
+    }
+
+    #[test]
+    fn test_weekly_summary() {
+        let dataset = EmissionDataset::new(vec![
+            emission("Storage", 2024, 6, 3, 10.0),
+            emission("Storage", 2024, 6, 9, 5.0),
+            emission("Storage", 2024, 6, 10, 7.0),
+        ]);
+        // Wednesday 12 June: last complete week is 3 to 9 June
+        let as_of = Utc.with_ymd_and_hms(2024, 6, 12, 8, 0, 0).unwrap();
+
+        let summary = Summary::compute(&dataset, SummaryPeriod::Weekly, as_of);
+        assert_eq!(summary.total_kg_co2eq, 15.0);
+        assert_eq!(summary.change_percent(), None);
+        assert!(summary.to_slack_text().contains("Week of 2024-06-03"));
+    }
+}
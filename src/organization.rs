@@ -0,0 +1,257 @@
+//! Organizational hierarchy rollups
+//!
+//! An [`OrgHierarchy`] describes the org structure (team → department → org)
+//! and maps emissions to its nodes with allocation [`Selector`]s on
+//! subscriptions, accounts, regions or provider data. Rolling up a dataset
+//! gives every node the emissions of its own resources and of all its
+//! descendants, ready for per-node totals and trends.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::aggregation::EmissionDataset;
+use crate::allocation::{Allocation, AllocationKey, Allocator, Selector};
+use crate::error::{CarbemError, Result};
+
+/// Organization structure and mapping of emissions to its nodes
+#[derive(Debug, Clone, Default)]
+pub struct OrgHierarchy {
+    // Parent of every node, `None` for roots
+    parents: BTreeMap<String, Option<String>>,
+    allocator: Allocator,
+}
+
+impl OrgHierarchy {
+    /// Create an empty hierarchy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a top-level node, e.g. the organization itself
+    pub fn with_root(mut self, name: impl Into<String>) -> Self {
+        self.parents.insert(name.into(), None);
+        self
+    }
+
+    /// Add a node below `parent`
+    pub fn with_node(mut self, name: impl Into<String>, parent: impl Into<String>) -> Self {
+        self.parents.insert(name.into(), Some(parent.into()));
+        self
+    }
+
+    /// Attribute selected emissions to `node`
+    pub fn map(mut self, selector: Selector, node: impl Into<String>) -> Self {
+        self.allocator = self.allocator.assign(selector, node);
+        self
+    }
+
+    /// Split selected emissions across nodes according to `key`
+    pub fn map_shared(mut self, selector: Selector, key: AllocationKey) -> Self {
+        self.allocator = self.allocator.share(selector, key);
+        self
+    }
+
+    /// Parent of `node`, if it is not a root
+    pub fn parent(&self, node: &str) -> Option<&str> {
+        self.parents.get(node)?.as_deref()
+    }
+
+    /// Direct children of `node`
+    pub fn children(&self, node: &str) -> Vec<&str> {
+        self.parents
+            .iter()
+            .filter(|(_, parent)| parent.as_deref() == Some(node))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Check that every parent exists and that the hierarchy has no cycle
+    pub fn validate(&self) -> Result<()> {
+        for (node, parent) in &self.parents {
+            if let Some(parent) = parent
+                && !self.parents.contains_key(parent)
+            {
+                return Err(CarbemError::Config(format!(
+                    "org node '{}' has unknown parent '{}'",
+                    node, parent
+                )));
+            }
+            self.ancestors(node)?;
+        }
+        Ok(())
+    }
+
+    /// Roll the emissions of `dataset` up the hierarchy
+    pub fn rollup(&self, dataset: &EmissionDataset) -> Result<OrgRollup> {
+        let allocation = self.allocator.allocate(dataset)?;
+        self.rollup_allocation(allocation)
+    }
+
+    /// Roll an existing allocation up the hierarchy, using its teams as nodes
+    pub fn rollup_allocation(&self, allocation: Allocation) -> Result<OrgRollup> {
+        self.validate()?;
+
+        let mut nodes: BTreeMap<String, EmissionDataset> = self
+            .parents
+            .keys()
+            .map(|name| (name.clone(), EmissionDataset::default()))
+            .collect();
+
+        for (team, dataset) in allocation.teams {
+            if !self.parents.contains_key(&team) {
+                return Err(CarbemError::Config(format!(
+                    "emissions are mapped to unknown org node '{}'",
+                    team
+                )));
+            }
+            for node in self.ancestors(&team)? {
+                if let Some(node_dataset) = nodes.get_mut(&node) {
+                    node_dataset.extend(dataset.emissions().iter().cloned());
+                }
+            }
+        }
+
+        Ok(OrgRollup {
+            nodes,
+            unmapped: allocation.unallocated,
+        })
+    }
+
+    // `node` followed by its ancestors up to the root
+    fn ancestors(&self, node: &str) -> Result<Vec<String>> {
+        let mut path = vec![node.to_string()];
+        let mut seen = BTreeSet::from([node]);
+        let mut current = node;
+        while let Some(parent) = self.parent(current) {
+            if !seen.insert(parent) {
+                return Err(CarbemError::Config(format!(
+                    "org hierarchy has a cycle through '{}'",
+                    parent
+                )));
+            }
+            path.push(parent.to_string());
+            current = parent;
+        }
+        Ok(path)
+    }
+}
+
+/// Emissions of every node of an org hierarchy, including its descendants
+#[derive(Debug, Clone, Default)]
+pub struct OrgRollup {
+    /// Dataset of each node
+    pub nodes: BTreeMap<String, EmissionDataset>,
+
+    /// Emissions not mapped to any node
+    pub unmapped: EmissionDataset,
+}
+
+impl OrgRollup {
+    /// Dataset of `node`, e.g. to compute its trends
+    pub fn node(&self, node: &str) -> Option<&EmissionDataset> {
+        self.nodes.get(node)
+    }
+
+    /// Total emissions in kg CO2e per node
+    pub fn totals(&self) -> BTreeMap<String, f64> {
+        self.nodes
+            .iter()
+            .map(|(name, dataset)| (name.clone(), dataset.total_kg_co2eq()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregation::Dimension;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(region: &str, month: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(28),
+            },
+            metadata: None,
+        }
+    }
+
+    fn region(value: &str) -> Selector {
+        Selector::Dimension {
+            dimension: Dimension::Region,
+            value: value.to_string(),
+        }
+    }
+
+    fn hierarchy() -> OrgHierarchy {
+        OrgHierarchy::new()
+            .with_root("acme")
+            .with_node("engineering", "acme")
+            .with_node("search", "engineering")
+            .with_node("ads", "engineering")
+            .with_node("finance", "acme")
+            .map(region("westeurope"), "search")
+            .map(region("eastus"), "ads")
+            .map(region("northeurope"), "finance")
+    }
+
+    #[test]
+    fn test_rollup_totals() {
+        let dataset = EmissionDataset::new(vec![
+            emission("westeurope", 1, 10.0),
+            emission("westeurope", 2, 20.0),
+            emission("eastus", 1, 5.0),
+            emission("northeurope", 1, 1.0),
+            emission("japaneast", 1, 2.0),
+        ]);
+
+        let rollup = hierarchy().rollup(&dataset).unwrap();
+        let totals = rollup.totals();
+        assert_eq!(totals["search"], 30.0);
+        assert_eq!(totals["engineering"], 35.0);
+        assert_eq!(totals["acme"], 36.0);
+        assert_eq!(rollup.unmapped.total_kg_co2eq(), 2.0);
+
+        // Node datasets keep their time dimension for trends
+        let engineering = rollup.node("engineering").unwrap();
+        let monthly: Vec<f64> = engineering
+            .monthly_series()
+            .into_iter()
+            .map(|(_, kg)| kg)
+            .collect();
+        assert_eq!(monthly, vec![15.0, 20.0]);
+    }
+
+    #[test]
+    fn test_children() {
+        let hierarchy = hierarchy();
+        assert_eq!(hierarchy.children("engineering"), vec!["ads", "search"]);
+        assert_eq!(hierarchy.parent("search"), Some("engineering"));
+        assert_eq!(hierarchy.parent("acme"), None);
+    }
+
+    #[test]
+    fn test_invalid_hierarchy() {
+        let dataset = EmissionDataset::default();
+
+        let unknown_parent = OrgHierarchy::new().with_node("search", "engineering");
+        assert!(unknown_parent.rollup(&dataset).is_err());
+
+        let cycle = OrgHierarchy::new().with_node("a", "b").with_node("b", "a");
+        assert!(cycle.validate().unwrap_err().to_string().contains("cycle"));
+
+        let unknown_node = OrgHierarchy::new()
+            .with_root("acme")
+            .map(region("westeurope"), "search");
+        let dataset = EmissionDataset::new(vec![emission("westeurope", 1, 1.0)]);
+        assert!(unknown_node.rollup(&dataset).is_err());
+    }
+}
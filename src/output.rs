@@ -0,0 +1,310 @@
+//! Machine-readable and human-readable rendering of emissions
+//!
+//! [`OutputFormat`] renders emissions as JSON, CSV or an aligned text table.
+//! JSON and CSV both use the columns of [`FlatEmissionRecord`], described by
+//! [`emission_record_schema`], so scripts consuming them do not depend on the
+//! nested shape of [`CarbonEmission`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, FlatEmissionRecord};
+use crate::precision::PrecisionPolicy;
+
+/// Identifier of the JSON Schema returned by [`emission_record_schema`]
+///
+/// Changes to the record columns that could break consumers get a new identifier.
+pub const EMISSION_RECORD_SCHEMA_ID: &str = "urn:carbem:emission-record:v2";
+
+// Columns of FlatEmissionRecord, in output order
+const COLUMNS: [&str; 15] = [
+    "provider",
+    "region",
+    "service",
+    "emissions_kg_co2eq",
+    "period_start",
+    "period_end",
+    "energy_kwh",
+    "grid_carbon_intensity",
+    "renewable_percentage",
+    "date_alignment",
+    "provider_data",
+    "lineage_endpoint",
+    "lineage_request_hash",
+    "lineage_retrieved_at",
+    "lineage_carbem_version",
+];
+
+// Columns shown by the text table
+const TABLE_COLUMNS: [&str; 5] = ["provider", "region", "service", "month", "kg_co2eq"];
+
+/// Format in which emissions are rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// JSON array of flat emission records
+    #[default]
+    Json,
+
+    /// Aligned text table for terminals
+    Table,
+
+    /// CSV with a header row, one flat emission record per line
+    Csv,
+}
+
+impl OutputFormat {
+    /// Render `emissions` in this format
+    pub fn render(&self, emissions: &[CarbonEmission]) -> Result<String> {
+        let records: Vec<FlatEmissionRecord> = emissions.iter().map(Into::into).collect();
+        self.render_records(&records, 3)
+    }
+
+    /// Render `emissions` with their emissions and energy rounded by `policy`
+    pub fn render_with(
+        &self,
+        emissions: &[CarbonEmission],
+        policy: &PrecisionPolicy,
+    ) -> Result<String> {
+        let records: Vec<FlatEmissionRecord> = emissions
+            .iter()
+            .map(|emission| {
+                let mut record = FlatEmissionRecord::from(emission);
+                record.emissions_kg_co2eq = policy.round(record.emissions_kg_co2eq);
+                record.energy_kwh = record.energy_kwh.map(|kwh| policy.round(kwh));
+                record
+            })
+            .collect();
+        self.render_records(&records, policy.decimal_places as usize)
+    }
+
+    fn render_records(&self, records: &[FlatEmissionRecord], decimals: usize) -> Result<String> {
+        match self {
+            OutputFormat::Json => Ok(serde_json::to_string_pretty(records)?),
+            OutputFormat::Csv => render_csv(records),
+            OutputFormat::Table => Ok(render_table(records, decimals)),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = CarbemError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            other => Err(CarbemError::Config(format!(
+                "unknown output format '{}', expected json, table or csv",
+                other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Table => "table",
+            OutputFormat::Csv => "csv",
+        })
+    }
+}
+
+/// JSON Schema of the records rendered by [`OutputFormat::Json`]
+pub fn emission_record_schema() -> Value {
+    let nullable = |kind: &str| json!({ "type": [kind, "null"] });
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": EMISSION_RECORD_SCHEMA_ID,
+        "title": "Emission record",
+        "type": "object",
+        "required": COLUMNS,
+        "additionalProperties": false,
+        "properties": {
+            "provider": { "type": "string" },
+            "region": { "type": "string" },
+            "service": nullable("string"),
+            "emissions_kg_co2eq": { "type": "number" },
+            "period_start": { "type": "string", "format": "date-time" },
+            "period_end": { "type": "string", "format": "date-time" },
+            "energy_kwh": nullable("number"),
+            "grid_carbon_intensity": nullable("number"),
+            "renewable_percentage": nullable("number"),
+            "date_alignment": {
+                "enum": ["expand_to_full_months", "truncate", "strict", null]
+            },
+            "provider_data": nullable("string"),
+            "lineage_endpoint": nullable("string"),
+            "lineage_request_hash": nullable("string"),
+            "lineage_retrieved_at": { "type": ["string", "null"], "format": "date-time" },
+            "lineage_carbem_version": nullable("string"),
+        },
+    })
+}
+
+fn render_csv(records: &[FlatEmissionRecord]) -> Result<String> {
+    let mut csv = COLUMNS.join(",");
+    csv.push('\n');
+    for record in records {
+        let Value::Object(fields) = serde_json::to_value(record)? else {
+            unreachable!("records serialize to objects");
+        };
+        let row: Vec<String> = COLUMNS
+            .iter()
+            .map(|column| match &fields[*column] {
+                Value::Null => String::new(),
+                Value::String(text) => csv_escape(text),
+                other => other.to_string(),
+            })
+            .collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    Ok(csv)
+}
+
+fn csv_escape(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn render_table(records: &[FlatEmissionRecord], decimals: usize) -> String {
+    let rows: Vec<[String; 5]> = records
+        .iter()
+        .map(|record| {
+            [
+                record.provider.clone(),
+                record.region.clone(),
+                record.service.clone().unwrap_or_else(|| "-".to_string()),
+                record.period_start.format("%Y-%m").to_string(),
+                format!("{:.*}", decimals, record.emissions_kg_co2eq),
+            ]
+        })
+        .collect();
+
+    let mut widths = TABLE_COLUMNS.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: [&str; 5]| {
+        let mut line = String::new();
+        for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            // Right-align the numeric column
+            if i == cells.len() - 1 {
+                line.push_str(&format!("{:>width$}", cell, width = width));
+            } else {
+                line.push_str(&format!("{:<width$}", cell, width = width));
+            }
+        }
+        line.trim_end().to_string() + "\n"
+    };
+
+    let mut table = format_row(TABLE_COLUMNS);
+    for row in &rows {
+        table.push_str(&format_row(row.each_ref().map(String::as_str)));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EmissionMetadata, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(service: Option<&str>, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: service.map(str::to_string),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let mut with_data = emission(Some("Storage, hot"), 1.5);
+        with_data.metadata = Some(EmissionMetadata {
+            energy_kwh: None,
+            grid_carbon_intensity: None,
+            renewable_percentage: None,
+            date_alignment: None,
+            provider_data: Some(json!({"note": "a \"quoted\" value"})),
+            tags: Default::default(),
+            estimated: None,
+        });
+
+        let csv = OutputFormat::Csv
+            .render(&[emission(None, 2.0), with_data])
+            .unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            "azure,westeurope,,2.0,2024-03-01T00:00:00Z,2024-04-01T00:00:00Z,,,,,,,,,"
+        );
+        assert!(lines[2].starts_with("azure,westeurope,\"Storage, hot\",1.5,"));
+        assert!(lines[2].ends_with(r#","{""note"":""a \""quoted\"" value""}",,,,"#));
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = OutputFormat::Table
+            .render(&[emission(Some("Compute"), 12.5), emission(None, 0.25)])
+            .unwrap();
+        assert_eq!(
+            table,
+            "provider  region      service  month    kg_co2eq\n\
+             azure     westeurope  Compute  2024-03    12.500\n\
+             azure     westeurope  -        2024-03     0.250\n"
+        );
+
+        let rounded = OutputFormat::Table
+            .render_with(&[emission(None, 0.125)], &PrecisionPolicy::new(2))
+            .unwrap();
+        assert!(rounded.ends_with("2024-03      0.12\n"));
+    }
+
+    #[test]
+    fn test_json_matches_schema_columns() {
+        let json = OutputFormat::Json.render(&[emission(None, 1.0)]).unwrap();
+        let records: Vec<Value> = serde_json::from_str(&json).unwrap();
+        let schema = emission_record_schema();
+        let properties = schema["properties"].as_object().unwrap();
+
+        let fields = records[0].as_object().unwrap();
+        assert_eq!(fields.len(), properties.len());
+        assert!(fields.keys().all(|key| properties.contains_key(key)));
+    }
+
+    #[test]
+    fn test_parse_output_format() {
+        assert_eq!("CSV".parse::<OutputFormat>().unwrap(), OutputFormat::Csv);
+        assert_eq!(OutputFormat::Table.to_string(), "table");
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+}
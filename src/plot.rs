@@ -0,0 +1,279 @@
+//! Quick SVG charts of an emission dataset (requires the `plot` feature)
+//!
+//! Charts are rendered to SVG strings, ready to be written to a file or
+//! embedded inline in an HTML report. Text is drawn with the viewer's
+//! sans-serif font.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use plotters::prelude::*;
+
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::error::{CarbemError, Result};
+
+// Colors cycled through series and bars
+const PALETTE: [RGBColor; 8] = [
+    RGBColor(46, 125, 50),
+    RGBColor(21, 101, 192),
+    RGBColor(239, 108, 0),
+    RGBColor(106, 27, 154),
+    RGBColor(0, 131, 143),
+    RGBColor(198, 40, 40),
+    RGBColor(158, 157, 36),
+    RGBColor(93, 64, 55),
+];
+
+/// Size and title of a chart
+#[derive(Debug, Clone)]
+pub struct ChartOptions {
+    /// Width in pixels
+    pub width: u32,
+
+    /// Height in pixels
+    pub height: u32,
+
+    /// Optional: caption drawn above the chart
+    pub title: Option<String>,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 480,
+            title: None,
+        }
+    }
+}
+
+/// Monthly emissions stacked by service, as an SVG document
+pub fn stacked_area_by_service(
+    dataset: &EmissionDataset,
+    options: &ChartOptions,
+) -> Result<String> {
+    let months: Vec<NaiveDate> = dataset
+        .monthly_series()
+        .into_iter()
+        .map(|(month, _)| month)
+        .collect();
+    let services = dataset.group_by_dimension(Dimension::Service);
+
+    // Cumulative totals: layer i is the sum of services 0..=i
+    let by_service_month: BTreeMap<(String, NaiveDate), f64> = dataset.group_by(|e| {
+        let start = e.time_period.start;
+        let month = NaiveDate::from_ymd_opt(start.year(), start.month(), 1)
+            .expect("first day of an existing month is valid");
+        (Dimension::Service.key(e), month)
+    });
+    let mut layers: Vec<(String, Vec<f64>)> = Vec::new();
+    for service in services.keys() {
+        let below = layers
+            .last()
+            .map(|(_, values)| values.clone())
+            .unwrap_or_else(|| vec![0.0; months.len()]);
+        let values = months
+            .iter()
+            .zip(below)
+            .map(|(month, below)| {
+                below
+                    + by_service_month
+                        .get(&(service.clone(), *month))
+                        .copied()
+                        .unwrap_or(0.0)
+            })
+            .collect();
+        layers.push((service.clone(), values));
+    }
+    let max = layers
+        .last()
+        .and_then(|(_, values)| values.iter().copied().reduce(f64::max))
+        .unwrap_or(0.0);
+
+    let mut svg = String::new();
+    {
+        let root =
+            SVGBackend::with_string(&mut svg, (options.width, options.height)).into_drawing_area();
+        root.fill(&WHITE).map_err(plot_error)?;
+
+        let mut builder = ChartBuilder::on(&root);
+        builder
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(70);
+        if let Some(title) = &options.title {
+            builder.caption(title, ("sans-serif", 20));
+        }
+        let x_max = months.len().saturating_sub(1).max(1);
+        let mut chart = builder
+            .build_cartesian_2d(0..x_max, 0.0..nice_max(max))
+            .map_err(plot_error)?;
+        chart
+            .configure_mesh()
+            .x_labels(months.len().clamp(2, 12))
+            .x_label_formatter(&|index| {
+                months
+                    .get(*index)
+                    .map(|month| month.format("%Y-%m").to_string())
+                    .unwrap_or_default()
+            })
+            .y_desc("kg CO2e")
+            .draw()
+            .map_err(plot_error)?;
+
+        // Draw the tallest layer first so lower layers stay visible
+        for (index, (service, values)) in layers.iter().enumerate().rev() {
+            let color = PALETTE[index % PALETTE.len()];
+            chart
+                .draw_series(AreaSeries::new(
+                    values.iter().copied().enumerate(),
+                    0.0,
+                    color.mix(0.8),
+                ))
+                .map_err(plot_error)?
+                .label(service.as_str())
+                .legend(move |(x, y)| {
+                    Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
+                });
+        }
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .map_err(plot_error)?;
+        root.present().map_err(plot_error)?;
+    }
+    Ok(svg)
+}
+
+/// Total emissions per region, largest first, as an SVG bar chart
+pub fn bar_by_region(dataset: &EmissionDataset, options: &ChartOptions) -> Result<String> {
+    let mut regions: Vec<(String, f64)> = dataset
+        .group_by_dimension(Dimension::Region)
+        .into_iter()
+        .collect();
+    regions.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let max = regions.first().map(|(_, kg)| *kg).unwrap_or(0.0);
+
+    let mut svg = String::new();
+    {
+        let root =
+            SVGBackend::with_string(&mut svg, (options.width, options.height)).into_drawing_area();
+        root.fill(&WHITE).map_err(plot_error)?;
+
+        let mut builder = ChartBuilder::on(&root);
+        builder
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(70);
+        if let Some(title) = &options.title {
+            builder.caption(title, ("sans-serif", 20));
+        }
+        let mut chart = builder
+            .build_cartesian_2d(
+                (0..regions.len().max(1)).into_segmented(),
+                0.0..nice_max(max),
+            )
+            .map_err(plot_error)?;
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(regions.len().max(1))
+            .x_label_formatter(&|segment| match segment {
+                SegmentValue::CenterOf(index) => regions
+                    .get(*index)
+                    .map(|(region, _)| region.clone())
+                    .unwrap_or_default(),
+                _ => String::new(),
+            })
+            .y_desc("kg CO2e")
+            .draw()
+            .map_err(plot_error)?;
+
+        chart
+            .draw_series(regions.iter().enumerate().map(|(index, (_, kg))| {
+                let mut bar = Rectangle::new(
+                    [
+                        (SegmentValue::Exact(index), 0.0),
+                        (SegmentValue::Exact(index + 1), *kg),
+                    ],
+                    PALETTE[index % PALETTE.len()].filled(),
+                );
+                bar.set_margin(0, 0, 8, 8);
+                bar
+            }))
+            .map_err(plot_error)?;
+        root.present().map_err(plot_error)?;
+    }
+    Ok(svg)
+}
+
+// Upper bound of the value axis, leaving headroom above the largest value
+fn nice_max(max: f64) -> f64 {
+    if max > 0.0 { max * 1.1 } else { 1.0 }
+}
+
+fn plot_error<E: std::error::Error + Send + Sync>(error: DrawingAreaErrorKind<E>) -> CarbemError {
+    CarbemError::Other(format!("Failed to render chart: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(region: &str, service: &str, month: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: Some(service.to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(28),
+            },
+            metadata: None,
+        }
+    }
+
+    fn dataset() -> EmissionDataset {
+        EmissionDataset::new(vec![
+            emission("westeurope", "Storage", 1, 10.0),
+            emission("westeurope", "Compute", 1, 5.0),
+            emission("eastus", "Storage", 2, 20.0),
+            emission("eastus", "Compute", 3, 8.0),
+        ])
+    }
+
+    #[test]
+    fn test_stacked_area_by_service() {
+        let options = ChartOptions {
+            title: Some("Emissions by service".to_string()),
+            ..Default::default()
+        };
+        let svg = stacked_area_by_service(&dataset(), &options).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Emissions by service"));
+        assert!(svg.contains("Storage"));
+        assert!(svg.contains("2024-02"));
+    }
+
+    #[test]
+    fn test_bar_by_region() {
+        let svg = bar_by_region(&dataset(), &ChartOptions::default()).unwrap();
+        assert!(svg.contains("eastus"));
+        assert!(svg.contains("westeurope"));
+    }
+
+    #[test]
+    fn test_empty_dataset() {
+        let dataset = EmissionDataset::default();
+        assert!(stacked_area_by_service(&dataset, &ChartOptions::default()).is_ok());
+        assert!(bar_by_region(&dataset, &ChartOptions::default()).is_ok());
+    }
+}
@@ -0,0 +1,203 @@
+//! Row-level access policies for shared deployments
+//!
+//! When one carbem service answers several teams, each caller gets an
+//! [`AccessPolicy`] restricting the providers, regions and tags it may query.
+//! [`AccessPolicy::authorize`] is applied to every query before it is sent:
+//! queries for other providers or regions are denied, and queries without
+//! regions or tag filters are narrowed to the allowed ones. Returned
+//! emissions are checked again with [`AccessPolicy::allows`], since not every
+//! provider filters by region or tag itself.
+//!
+//! [`ApiKeyPolicies`] maps API keys to policies, e.g. for the
+//! [`web`](crate::web) endpoints.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, EmissionQuery, TagFilter};
+use crate::redact::REDACTED;
+
+/// Providers, regions and tags a caller may query
+///
+/// Empty lists do not restrict: the default policy allows everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessPolicy {
+    /// Providers that may be queried, e.g. `["azure"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<String>,
+
+    /// Regions that may be queried
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub regions: Vec<String>,
+
+    /// Tag filters every returned emission must match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tag_filters: Vec<TagFilter>,
+}
+
+impl AccessPolicy {
+    /// Policy allowing every query
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    /// Only allow the providers `providers`
+    pub fn with_providers<S: Into<String>>(
+        mut self,
+        providers: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.providers = providers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only allow the regions `regions`
+    pub fn with_regions<S: Into<String>>(mut self, regions: impl IntoIterator<Item = S>) -> Self {
+        self.regions = regions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only allow emissions matching `filter`, in addition to earlier filters
+    pub fn with_tag_filter(mut self, filter: TagFilter) -> Self {
+        self.tag_filters.push(filter);
+        self
+    }
+
+    /// `query` restricted to what the policy allows
+    ///
+    /// Fails with [`CarbemError::AccessDenied`] when the query names a
+    /// provider, route entry or region the policy does not allow. A query
+    /// without regions is narrowed to the allowed regions, and the policy's
+    /// tag filters are added to the query's.
+    pub fn authorize(&self, query: &EmissionQuery) -> Result<EmissionQuery> {
+        let providers = std::iter::once(query.provider.as_str()).chain(
+            query
+                .route
+                .iter()
+                .map(|entry| entry.split(':').next().unwrap_or_default()),
+        );
+        for provider in providers {
+            if !self.allows_provider(provider) {
+                return Err(CarbemError::AccessDenied(format!(
+                    "provider '{}' is not allowed",
+                    provider
+                )));
+            }
+        }
+        let mut query = query.clone();
+        if !self.regions.is_empty() {
+            if let Some(region) = query.regions.iter().find(|r| !self.regions.contains(r)) {
+                return Err(CarbemError::AccessDenied(format!(
+                    "region '{}' is not allowed",
+                    region
+                )));
+            }
+            if query.regions.is_empty() {
+                query.regions = self.regions.clone();
+            }
+        }
+        query.tag_filters.extend(self.tag_filters.iter().cloned());
+        Ok(query)
+    }
+
+    /// Whether the policy allows returning `emission`
+    pub fn allows(&self, emission: &CarbonEmission) -> bool {
+        self.allows_provider(&emission.provider)
+            && (self.regions.is_empty() || self.regions.contains(&emission.region))
+            && self
+                .tag_filters
+                .iter()
+                .all(|filter| filter.matches(emission))
+    }
+
+    fn allows_provider(&self, provider: &str) -> bool {
+        self.providers.is_empty() || self.providers.iter().any(|p| p == provider)
+    }
+}
+
+/// Access policies of API keys
+#[derive(Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ApiKeyPolicies {
+    keys: HashMap<String, AccessPolicy>,
+}
+
+impl ApiKeyPolicies {
+    /// Create an empty set, rejecting every key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `key`, restricted by `policy`
+    pub fn with_key(mut self, key: impl Into<String>, policy: AccessPolicy) -> Self {
+        self.keys.insert(key.into(), policy);
+        self
+    }
+
+    /// Policy of `key`, or `None` if the key is unknown
+    pub fn policy(&self, key: &str) -> Option<&AccessPolicy> {
+        // Compare with every key in constant time, so that timing does not
+        // tell how close a guess is
+        let mut found = None;
+        for (candidate, policy) in &self.keys {
+            if constant_time_eq(candidate.as_bytes(), key.as_bytes()) {
+                found = Some(policy);
+            }
+        }
+        found
+    }
+}
+
+impl std::fmt::Debug for ApiKeyPolicies {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.keys.values().map(|policy| (REDACTED, policy)))
+            .finish()
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_restricts_queries() {
+        let policy = AccessPolicy::unrestricted()
+            .with_providers(["azure"])
+            .with_regions(["westeurope", "northeurope"])
+            .with_tag_filter(TagFilter::equals("team", "payments"));
+        let query = EmissionQuery::from_query_string(
+            "provider=azure&start=2024-01-01&end=2024-01-31&tags=env:prod",
+        )
+        .unwrap();
+
+        let authorized = policy.authorize(&query).unwrap();
+        assert_eq!(authorized.regions, ["westeurope", "northeurope"]);
+        assert_eq!(authorized.tag_filters.len(), 2);
+
+        let mut other = query.clone();
+        other.regions = vec!["eastus".to_string()];
+        assert!(matches!(
+            policy.authorize(&other),
+            Err(CarbemError::AccessDenied(m)) if m.contains("eastus")
+        ));
+        other.regions.clear();
+        other.route = vec!["azure:prod".to_string(), "ibm".to_string()];
+        assert!(matches!(
+            policy.authorize(&other),
+            Err(CarbemError::AccessDenied(m)) if m.contains("ibm")
+        ));
+        assert!(AccessPolicy::unrestricted().authorize(&other).is_ok());
+
+        let keys: ApiKeyPolicies =
+            serde_json::from_str(r#"{"k-payments": {"providers": ["azure"]}}"#).unwrap();
+        assert_eq!(keys.policy("k-payments").unwrap().providers, ["azure"]);
+        assert!(keys.policy("k-payment").is_none());
+        assert!(!format!("{:?}", keys).contains("k-payments"));
+    }
+}
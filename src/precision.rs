@@ -0,0 +1,154 @@
+//! Rounding and precision of emission values
+//!
+//! A [`PrecisionPolicy`] fixes the decimal places and rounding mode of the
+//! totals of an [`EmissionDataset`](crate::EmissionDataset) (see
+//! [`with_precision`](crate::EmissionDataset::with_precision)) and of
+//! exported values (see [`OutputFormat::render_with`](crate::OutputFormat::render_with)),
+//! so that every report and export of a dataset shows the same numbers.
+//!
+//! Values are summed in `f64`, with compensation (see
+//! [`StableSum`](crate::aggregation::StableSum)), and rounded once, on the total. With the
+//! `decimal` feature, they are summed and rounded in fixed-point decimal
+//! arithmetic instead, which makes totals exact and independent of the
+//! summation order.
+
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::stable_sum;
+
+/// How values are rounded to the decimal places of a policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Round to the nearest value, ties to the even digit (banker's rounding)
+    #[default]
+    HalfEven,
+
+    /// Round to the nearest value, ties away from zero
+    HalfAwayFromZero,
+
+    /// Drop the extra digits
+    TowardZero,
+}
+
+/// Decimal places and rounding mode of emission values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PrecisionPolicy {
+    /// Digits kept after the decimal point
+    pub decimal_places: u32,
+
+    /// Rounding mode (defaults to half even)
+    #[serde(default)]
+    pub rounding: RoundingMode,
+}
+
+impl Default for PrecisionPolicy {
+    /// Three decimal places (grams of CO2e), half even
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl PrecisionPolicy {
+    /// Round to `decimal_places`, ties to even
+    pub fn new(decimal_places: u32) -> Self {
+        Self {
+            decimal_places,
+            rounding: RoundingMode::default(),
+        }
+    }
+
+    /// Use `rounding` instead of half even
+    pub fn with_rounding(mut self, rounding: RoundingMode) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// `value` rounded according to the policy
+    pub fn round(&self, value: f64) -> f64 {
+        #[cfg(feature = "decimal")]
+        if let Some(rounded) = decimal::round(self, value) {
+            return rounded;
+        }
+
+        let scale = 10f64.powi(self.decimal_places as i32);
+        let scaled = value * scale;
+        let rounded = match self.rounding {
+            RoundingMode::HalfEven => scaled.round_ties_even(),
+            RoundingMode::HalfAwayFromZero => scaled.round(),
+            RoundingMode::TowardZero => scaled.trunc(),
+        };
+        if rounded.is_finite() {
+            rounded / scale
+        } else {
+            value
+        }
+    }
+
+    /// Sum of `values`, rounded according to the policy
+    pub fn sum(&self, values: impl IntoIterator<Item = f64>) -> f64 {
+        let values: Vec<f64> = values.into_iter().collect();
+
+        #[cfg(feature = "decimal")]
+        if let Some(total) = decimal::sum(self, &values) {
+            return total;
+        }
+
+        self.round(stable_sum(values))
+    }
+}
+
+// Fixed-point arithmetic, falling back to f64 for values out of its range
+#[cfg(feature = "decimal")]
+mod decimal {
+    use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+    use rust_decimal::{Decimal, RoundingStrategy};
+
+    use super::{PrecisionPolicy, RoundingMode};
+
+    pub(super) fn round(policy: &PrecisionPolicy, value: f64) -> Option<f64> {
+        rounded(policy, Decimal::from_f64(value)?).to_f64()
+    }
+
+    pub(super) fn sum(policy: &PrecisionPolicy, values: &[f64]) -> Option<f64> {
+        let total = values.iter().try_fold(Decimal::ZERO, |total, value| {
+            total.checked_add(Decimal::from_f64(*value)?)
+        })?;
+        rounded(policy, total).to_f64()
+    }
+
+    fn rounded(policy: &PrecisionPolicy, value: Decimal) -> Decimal {
+        let strategy = match policy.rounding {
+            RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::HalfAwayFromZero => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::TowardZero => RoundingStrategy::ToZero,
+        };
+        value.round_dp_with_strategy(policy.decimal_places, strategy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding_modes() {
+        let policy = PrecisionPolicy::new(1);
+        assert_eq!(policy.round(0.25), 0.2);
+        assert_eq!(policy.round(-0.45), -0.4);
+        let away = policy.with_rounding(RoundingMode::HalfAwayFromZero);
+        assert_eq!(away.round(0.25), 0.3);
+        let truncate = policy.with_rounding(RoundingMode::TowardZero);
+        assert_eq!(truncate.round(0.29), 0.2);
+        assert_eq!(truncate.round(-0.29), -0.2);
+        assert_eq!(PrecisionPolicy::new(2).round(f64::MAX), f64::MAX);
+    }
+
+    #[test]
+    fn test_sum_is_rounded_once() {
+        let policy = PrecisionPolicy::new(2);
+        assert_eq!(policy.sum([0.1, 0.2, 0.3]), 0.6);
+        assert_eq!(policy.sum([0.004, 0.004]), 0.01);
+        assert_eq!(policy.sum([]), 0.0);
+    }
+}
@@ -0,0 +1,94 @@
+//! Progress reporting for long-running queries
+//!
+//! Collecting years of emissions can take hours. A [`ProgressHandler`] is
+//! told when each chunk of the work, e.g. a range of months fetched by a
+//! [`CompositeClient`](crate::store::CompositeClient) or an account of an IBM
+//! account group, and each provider result page is done, so that callers can
+//! render progress bars.
+//!
+//! [`report_to`] reports the progress of any query future to a handler:
+//!
+//! ```ignore
+//! let emissions = carbem::progress::report_to(handler, client.query_emissions(&query)).await?;
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+
+tokio::task_local! {
+    // Handler receiving the progress of the current task
+    static HANDLER: Arc<dyn ProgressHandler>;
+}
+
+/// Progress through the units of an operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// What was just done, e.g. `azure 2024-01..2024-03`
+    pub label: String,
+
+    /// Units done so far, the last one included
+    pub completed: u64,
+
+    /// Optional: units in total, when known in advance
+    pub total: Option<u64>,
+}
+
+impl Progress {
+    /// Completed share of the total, from 0 to 1, when the total is known
+    pub fn fraction(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.completed as f64 / total as f64).min(1.0))
+    }
+}
+
+/// Receives progress of long-running queries
+///
+/// Callbacks run on the querying task: they should return quickly, e.g. by
+/// updating a progress bar or sending to a channel.
+pub trait ProgressHandler: Send + Sync {
+    /// A chunk of the operation is done, e.g. a range of months or an account
+    fn on_chunk(&self, _progress: &Progress) {}
+
+    /// A page of provider results was received
+    fn on_page(&self, _progress: &Progress) {}
+}
+
+/// Run `future`, reporting its progress to `handler`
+///
+/// A handler set by an inner call, e.g. with
+/// [`CompositeClient::with_progress`](crate::store::CompositeClient::with_progress),
+/// takes precedence for the operations it wraps.
+pub async fn report_to<F: Future>(handler: Arc<dyn ProgressHandler>, future: F) -> F::Output {
+    HANDLER.scope(handler, future).await
+}
+
+// Report a finished chunk to the handler of the current task, if any
+pub(crate) fn chunk(label: impl FnOnce() -> String, completed: u64, total: Option<u64>) {
+    notify(label, completed, total, |handler, progress| {
+        handler.on_chunk(progress)
+    });
+}
+
+// Report a received page to the handler of the current task, if any
+pub(crate) fn page(label: impl FnOnce() -> String, completed: u64, total: Option<u64>) {
+    notify(label, completed, total, |handler, progress| {
+        handler.on_page(progress)
+    });
+}
+
+fn notify(
+    label: impl FnOnce() -> String,
+    completed: u64,
+    total: Option<u64>,
+    callback: impl FnOnce(&dyn ProgressHandler, &Progress),
+) {
+    let _ = HANDLER.try_with(|handler| {
+        let progress = Progress {
+            label: label(),
+            completed,
+            total,
+        };
+        callback(handler.as_ref(), &progress);
+    });
+}
@@ -1,46 +1,99 @@
-use async_trait::async_trait;
+use std::sync::Arc;
+
 use chrono::{DateTime, Datelike, TimeZone, Utc};
 use reqwest::{
-    Client,
+    Method, StatusCode,
     header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue},
 };
 
+use crate::auth::TokenCache;
+use crate::capture::{CapturedExchange, ExchangeLog};
 use crate::error::{CarbemError, Result};
-use crate::models::{CarbonEmission, EmissionMetadata, EmissionQuery, TimePeriod};
+use crate::logging::{debug, warn};
+use crate::models::{
+    CarbonEmission, ConversionIssue, EmissionMetadata, EmissionQuery, EmissionResult, Lineage,
+    PlannedRequest, QueryTimezone, RawResponseMode, TimePeriod, next_month_start,
+};
+use crate::progress;
 use crate::providers::CarbonProvider;
 use crate::providers::config::ProviderQueryConfig;
+use crate::providers::pager::{AzurePager, PageSource};
+use crate::redact::Redactor;
+use crate::schema::{self, SchemaLog, SchemaWarning, inspect_object};
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
 
 use super::models::*;
 
 // Azure Management API base URL
 const AZURE_MANAGEMENT_BASE_URL: &str = "https://management.azure.com";
 const CARBON_API_VERSION: &str = "2025-04-01";
+const AUTHORIZATION_API_VERSION: &str = "2022-04-01";
+const SUBSCRIPTIONS_API_VERSION: &str = "2022-12-01";
+
+/// Azure built-in role granting access to carbon emission reports
+pub const CARBON_READER_ROLE: &str = "Carbon Optimization Reader";
+
+/// Actions of [`CARBON_READER_ROLE`] used by carbem
+pub const CARBON_REPORT_ACTIONS: &[&str] = &["Microsoft.Carbon/carbonEmissionReports/action"];
 
 // Azure Carbon Optimization provider
 #[derive(Debug, Clone)]
 pub struct AzureProvider {
     config: AzureConfig,
-    http_client: Client,
+    transport: Arc<dyn Transport>,
+    lenient_parsing: bool,
+    schema_log: SchemaLog,
+    exchange_log: ExchangeLog,
+    token_cache: Option<Arc<TokenCache>>,
 }
 
 impl AzureProvider {
     // Create a new Azure provider instance with configuration
     pub fn new(config: AzureConfig) -> Result<Self> {
-        let http_client = Client::new();
         Ok(Self {
             config,
-            http_client,
+            transport: Arc::new(ReqwestTransport::default()),
+            lenient_parsing: false,
+            schema_log: SchemaLog::default(),
+            exchange_log: ExchangeLog::default(),
+            token_cache: None,
         })
     }
 
+    /// Send requests through `transport` instead of the default reqwest client
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Take access tokens from `cache`, refreshed before they expire
+    ///
+    /// Clones of the provider share the cache, so concurrent queries refresh
+    /// an expired token once.
+    pub fn with_token_cache(mut self, cache: Arc<TokenCache>) -> Self {
+        self.token_cache = Some(cache);
+        self
+    }
+
     // Convert EmissionQuery to Azure-specific request format
     fn convert_emission_query_to_azure_request(
         &self,
         query: &EmissionQuery,
     ) -> Result<AzureCarbonEmissionReportRequest> {
-        // Convert time period to Azure date format (YYYY-MM-DD)
-        let start_date = query.time_period.start.format("%Y-%m-%d").to_string();
-        let end_date = query.time_period.end.format("%Y-%m-%d").to_string();
+        // Align the time period on local months and convert it to Azure date format (YYYY-MM-DD)
+        let aligned_period = query
+            .time_period
+            .align_in(query.date_alignment, &query.timezone)?;
+        let start_date = query
+            .timezone
+            .local_date(aligned_period.start)
+            .format("%Y-%m-%d")
+            .to_string();
+        let end_date = query
+            .timezone
+            .local_date(aligned_period.end)
+            .format("%Y-%m-%d")
+            .to_string();
 
         let date_range = AzureDateRange {
             start: start_date.clone(),
@@ -67,7 +120,7 @@ impl AzureProvider {
         azure_config.validate().map_err(CarbemError::Config)?;
 
         // Validate single-month requirement for certain report types
-        let report_type_enum = &azure_config.report_type;
+        let report_type_enum = azure_config.report_type();
         if matches!(
             report_type_enum,
             AzureReportType::ItemDetailsReport | AzureReportType::TopItemsSummaryReport
@@ -79,26 +132,32 @@ impl AzureProvider {
             )));
         }
 
-        // Extract report type (mandatory field)
-        let report_type = azure_config.report_type.as_str().to_string();
+        let report_type = report_type_enum.as_str().to_string();
+
+        // Selected emission scopes, all of them when not set (duplicates removed)
+        let mut carbon_scope_list: Vec<String> = Vec::new();
+        for scope in azure_config.carbon_scope_list.unwrap_or_else(|| {
+            vec![
+                AzureCarbonScope::Scope1,
+                AzureCarbonScope::Scope2,
+                AzureCarbonScope::Scope3,
+            ]
+        }) {
+            let scope = scope.as_str().to_string();
+            if !carbon_scope_list.contains(&scope) {
+                carbon_scope_list.push(scope);
+            }
+        }
 
         Ok(AzureCarbonEmissionReportRequest {
             report_type,
-            subscription_list: azure_config.subscription_list.clone(),
-            carbon_scope_list: azure_config
-                .carbon_scope_list
-                .unwrap_or_else(|| {
-                    vec![
-                        AzureCarbonScope::Scope1,
-                        AzureCarbonScope::Scope2,
-                        AzureCarbonScope::Scope3,
-                    ]
-                })
-                .iter()
-                .map(|scope| scope.as_str().to_string())
-                .collect(),
+            subscription_list: azure_config.subscription_list.clone().unwrap_or_default(),
+            carbon_scope_list,
             date_range,
-            category_type: azure_config.category_type,
+            category_type: azure_config
+                .category_type
+                .as_ref()
+                .map(|ct| ct.as_str().to_string()),
             top_items: azure_config.top_items,
             order_by: azure_config.order_by,
             sort_direction: azure_config
@@ -117,12 +176,194 @@ impl AzureProvider {
         })
     }
 
+    // Carbon emission reports endpoint
+    fn endpoint_url() -> String {
+        format!(
+            "{}/providers/Microsoft.Carbon/carbonEmissionReports?api-version={}",
+            AZURE_MANAGEMENT_BASE_URL, CARBON_API_VERSION
+        )
+    }
+
+    /// Actions of [`CARBON_REPORT_ACTIONS`] the credentials lack on `subscription_id`
+    ///
+    /// An empty list means the credentials can query the subscription.
+    pub async fn missing_permissions(&self, subscription_id: &str) -> Result<Vec<String>> {
+        let url = format!(
+            "{}/subscriptions/{}/providers/Microsoft.Authorization/permissions?api-version={}",
+            AZURE_MANAGEMENT_BASE_URL,
+            urlencoding::encode(subscription_id),
+            AUTHORIZATION_API_VERSION
+        );
+        let access_token = self.access_token().await?;
+        let response = self
+            .transport
+            .send(HttpRequest::new(
+                Method::GET,
+                url,
+                self.build_headers(&access_token)?,
+            ))
+            .await?;
+
+        let status = response.status;
+        let body = self.redactor(&access_token).redact(&response.text());
+        match status.as_u16() {
+            200..=299 => {}
+            401 => {
+                self.invalidate_token();
+                return Err(CarbemError::Auth(format!(
+                    "Azure rejected the access token: {}",
+                    body
+                )));
+            }
+            // The caller cannot even read its permissions on the subscription
+            403 | 404 => {
+                return Ok(CARBON_REPORT_ACTIONS
+                    .iter()
+                    .map(|action| action.to_string())
+                    .collect());
+            }
+            _ => {
+                return Err(CarbemError::Provider(format!(
+                    "Azure permissions request failed with status {}: {}",
+                    status, body
+                )));
+            }
+        }
+
+        let permissions: AzurePermissionListResponse = serde_json::from_str(&body)?;
+        Ok(missing_actions(&permissions.value, CARBON_REPORT_ACTIONS))
+    }
+
+    /// Subscriptions of the tenant the credentials can read
+    ///
+    /// Follows the pagination of the ARM subscriptions API. Queries with
+    /// `all_subscriptions` set in their [`AzureQueryConfig`] use the active
+    /// subscriptions of this list, so it never goes stale.
+    pub async fn discover_subscriptions(&self) -> Result<Vec<AzureSubscription>> {
+        let access_token = self.access_token().await?;
+        let redactor = self.redactor(&access_token);
+        let mut subscriptions = Vec::new();
+        let mut next_url = Some(Self::subscriptions_url());
+
+        while let Some(url) = next_url {
+            let response = self
+                .transport
+                .send(HttpRequest::new(
+                    Method::GET,
+                    url,
+                    self.build_headers(&access_token)?,
+                ))
+                .await?;
+
+            let status = response.status;
+            let body = redactor.redact(&response.text());
+            if status == StatusCode::UNAUTHORIZED {
+                self.invalidate_token();
+                return Err(CarbemError::Auth(format!(
+                    "Azure rejected the access token: {}",
+                    body
+                )));
+            }
+            if !response.is_success() {
+                return Err(CarbemError::Provider(format!(
+                    "Azure subscriptions request failed with status {}: {}",
+                    status, body
+                )));
+            }
+
+            let page: AzureSubscriptionListResponse = serde_json::from_str(&body)?;
+            subscriptions.extend(page.value);
+            next_url = page.next_link;
+        }
+
+        debug!("Discovered {} Azure subscription(s)", subscriptions.len());
+        Ok(subscriptions)
+    }
+
+    // ARM subscriptions list endpoint
+    fn subscriptions_url() -> String {
+        format!(
+            "{}/subscriptions?api-version={}",
+            AZURE_MANAGEMENT_BASE_URL, SUBSCRIPTIONS_API_VERSION
+        )
+    }
+
+    // Fill the subscription list of queries asking for all subscriptions
+    async fn resolve_subscriptions(&self, query: &EmissionQuery) -> Result<Option<EmissionQuery>> {
+        let Some(ProviderQueryConfig::Azure(config)) = &query.provider_config else {
+            return Ok(None);
+        };
+        if config.all_subscriptions != Some(true) {
+            return Ok(None);
+        }
+
+        let subscription_list: Vec<String> = self
+            .discover_subscriptions()
+            .await?
+            .into_iter()
+            .filter(AzureSubscription::is_active)
+            .map(|subscription| subscription.subscription_id)
+            .collect();
+        if subscription_list.is_empty() {
+            return Err(CarbemError::Auth(
+                "the Azure credentials cannot read any active subscription".to_string(),
+            ));
+        }
+
+        let mut resolved = query.clone();
+        resolved.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(subscription_list),
+            all_subscriptions: Some(false),
+            ..config.clone()
+        }));
+        Ok(Some(resolved))
+    }
+
+    // Check the query targets Azure and selects locations, then convert it
+    fn prepare_request(&self, query: &EmissionQuery) -> Result<AzureCarbonEmissionReportRequest> {
+        if query.provider != "azure" {
+            return Err(CarbemError::Config(
+                "Query provider must be 'azure' for AzureProvider".to_string(),
+            ));
+        }
+
+        if query.regions.is_empty() {
+            return Err(CarbemError::Config(
+                "At least one subscription ID must be specified in the query".to_string(),
+            ));
+        }
+
+        self.convert_emission_query_to_azure_request(query)
+    }
+
+    // Redactor aware of this provider's credentials
+    fn redactor(&self, access_token: &str) -> Redactor {
+        Redactor::new()
+            .with_secret(self.config.access_token.clone())
+            .with_secret(access_token.to_string())
+    }
+
+    // Token for the next request: from the token cache when set, else the configured one
+    async fn access_token(&self) -> Result<String> {
+        match &self.token_cache {
+            Some(cache) => cache.token().await,
+            None => Ok(self.config.access_token.clone()),
+        }
+    }
+
+    // Drop a cached token the API rejected, so the next request refreshes it
+    fn invalidate_token(&self) {
+        if let Some(cache) = &self.token_cache {
+            cache.invalidate();
+        }
+    }
+
     // Build authorization headers for Azure API requests
-    fn build_headers(&self) -> Result<HeaderMap> {
+    fn build_headers(&self, access_token: &str) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
 
         // Add authorization header
-        let auth_value = format!("Bearer {}", self.config.access_token);
+        let auth_value = format!("Bearer {}", access_token);
         headers.insert(
             AUTHORIZATION,
             HeaderValue::from_str(&auth_value)
@@ -144,12 +385,14 @@ impl AzureProvider {
         return query.clone();
     }
 
-    ///Convert Azure emission data to carbem CarbonEmission
+    ///Convert Azure emission data to carbem CarbonEmission, recording defaulted values in `issues`
     fn convert_to_carbon_emission(
         &self,
         data: &AzureEmissionData,
         subscription_id: &str,
         date_range: &AzureDateRange,
+        row: usize,
+        issues: &mut Vec<ConversionIssue>,
     ) -> CarbonEmission {
         // Create metadata with Azure-specific information
         let mut provider_data = serde_json::Map::new();
@@ -217,6 +460,16 @@ impl AzureProvider {
                     TimePeriod { start, end }
                 }
                 Err(_) => {
+                    warn!(
+                        "Unparsable date '{}' in Azure {} row, falling back to the query date range",
+                        date, data.data_type
+                    );
+                    issues.push(ConversionIssue::new(
+                        "azure",
+                        row,
+                        "date",
+                        format!("unparsable date '{}', would use the query date range", date),
+                    ));
                     // Fallback: convert DateRange to TimePeriod using start and end
                     let start = DateTime::parse_from_str(
                         &format!("{}T00:00:00+00:00", date_range.start),
@@ -240,7 +493,11 @@ impl AzureProvider {
                             .map(|dt| dt.with_timezone(&Utc))
                             .unwrap_or_else(|_| Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap())
                     });
-                    TimePeriod { start, end }
+                    // The date range includes its end month
+                    TimePeriod {
+                        start,
+                        end: next_month_start(end, &QueryTimezone::Utc),
+                    }
                 }
             }
         } else {
@@ -267,14 +524,21 @@ impl AzureProvider {
                     .map(|dt| dt.with_timezone(&Utc))
                     .unwrap_or_else(|_| Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap())
             });
-            TimePeriod { start, end }
+            // The date range includes its end month
+            TimePeriod {
+                start,
+                end: next_month_start(end, &QueryTimezone::Utc),
+            }
         };
 
         let metadata = EmissionMetadata {
             energy_kwh: None,                             // Not provided by Azure API
             grid_carbon_intensity: data.carbon_intensity, // Use Azure's carbon intensity
             renewable_percentage: None,                   // Not provided by Azure API
+            date_alignment: None,                         // Recorded once the query completes
             provider_data: Some(serde_json::Value::Object(provider_data)),
+            tags: Default::default(), // Not provided by Azure API
+            estimated: Some(false),
         };
 
         // Use item_name as region if available (for location-based reports), otherwise use subscription_id
@@ -295,46 +559,148 @@ impl AzureProvider {
             provider: "azure".to_string(),
             region,
             service,
+            service_category: None,
+            lineage: None,
             emissions_kg_co2eq: data.latest_month_emissions,
             time_period: specific_time_period,
             metadata: Some(metadata),
         }
     }
 
+    // Parse a report response, recording schema drift before deserializing
+    fn parse_response(&self, body: &str) -> Result<AzureCarbonEmissionReportResponse> {
+        let mut value = schema::parse_body("azure", body)?;
+        let mut warnings = Vec::new();
+        let lenient = self.lenient_parsing;
+
+        inspect_object(
+            "azure",
+            "",
+            &mut value,
+            AZURE_RESPONSE_FIELDS,
+            lenient,
+            &mut warnings,
+        );
+        if let Some(rows) = value.get_mut("value").and_then(|v| v.as_array_mut()) {
+            for row in rows {
+                inspect_object(
+                    "azure",
+                    "value[]",
+                    row,
+                    AZURE_EMISSION_DATA_FIELDS,
+                    lenient,
+                    &mut warnings,
+                );
+            }
+        }
+        if let Some(decisions) = value
+            .get_mut("subscriptionAccessDecisionList")
+            .and_then(|v| v.as_array_mut())
+        {
+            for decision in decisions {
+                inspect_object(
+                    "azure",
+                    "subscriptionAccessDecisionList[]",
+                    decision,
+                    AZURE_ACCESS_DECISION_FIELDS,
+                    lenient,
+                    &mut warnings,
+                );
+            }
+        }
+
+        self.schema_log.record(warnings);
+        schema::deserialize("azure", value)
+    }
+
     #[allow(clippy::redundant_closure)]
     async fn request_carbon_emissions(
         &self,
         query: &AzureCarbonEmissionReportRequest,
-    ) -> Result<Vec<CarbonEmission>> {
-        let url = format!(
-            "{}/providers/Microsoft.Carbon/carbonEmissionReports?api-version={}",
-            AZURE_MANAGEMENT_BASE_URL, CARBON_API_VERSION
-        );
+        raw_mode: RawResponseMode,
+        strict: bool,
+    ) -> Result<(EmissionResult, Option<String>)> {
+        let url = Self::endpoint_url();
 
-        let headers = self.build_headers()?;
+        let access_token = self.access_token().await?;
+        let headers = self.build_headers(&access_token)?;
         let payload = self.build_request_payload(query);
 
-        let response = self
-            .http_client
-            .post(&url)
-            .headers(headers)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(CarbemError::Http)?;
+        debug!(
+            "Requesting Azure {} for {} subscription(s) from {} to {}",
+            payload.report_type,
+            payload.subscription_list.len(),
+            payload.date_range.start,
+            payload.date_range.end
+        );
+
+        let redactor = self.redactor(&access_token);
+        let exchange = CapturedExchange::new(PlannedRequest::new(
+            self.name(),
+            "POST",
+            &url,
+            &headers,
+            Some(serde_json::to_value(&payload)?),
+            &redactor,
+        ));
+
+        let request =
+            HttpRequest::new(Method::POST, &url, headers).with_body(serde_json::to_vec(&payload)?);
+        let lineage = Lineage::of_request(&request);
+        let response = match self.transport.send(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.exchange_log
+                    .record(exchange.with_error(redactor.redact(&e.to_string())));
+                return Err(e);
+            }
+        };
 
         // Check if request was successful
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
+        if !response.is_success() {
+            let status = response.status;
+            let body = redactor.redact(&response.text());
+            self.exchange_log
+                .record(exchange.with_response(status.as_u16(), &body));
+            if status == StatusCode::UNAUTHORIZED {
+                self.invalidate_token();
+            }
             return Err(CarbemError::Provider(format!(
                 "Azure API request failed with status {}: {}",
                 status, body
             )));
         }
 
-        let azure_response: AzureCarbonEmissionReportResponse =
-            response.json().await.map_err(CarbemError::Http)?;
+        let status = response.status.as_u16();
+        let body = response.text();
+        self.exchange_log
+            .record(exchange.with_response(status, &redactor.redact(&body)));
+
+        // Keep the response untouched by lenient parsing when it is requested raw
+        let raw_responses: Vec<serde_json::Value> = match raw_mode {
+            RawResponseMode::None => Vec::new(),
+            RawResponseMode::Alongside | RawResponseMode::Only => {
+                vec![serde_json::from_str(&body)?]
+            }
+        };
+        if raw_mode == RawResponseMode::Only {
+            let skip_token = raw_responses[0]
+                .get("skipToken")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string);
+            let result = EmissionResult {
+                emissions: Vec::new(),
+                raw_responses,
+                planned_requests: Vec::new(),
+            };
+            return Ok((result, skip_token));
+        }
+
+        // Deserialization errors can quote response values
+        let azure_response = self.parse_response(&body).map_err(|e| match e {
+            CarbemError::Api(message) => CarbemError::Api(redactor.redact(&message)),
+            e => e,
+        })?;
 
         // Check for access decisions and collect denied subscriptions info
         let mut allowed_subscriptions = Vec::new();
@@ -370,63 +736,241 @@ impl AzureProvider {
                 )));
             }
             // Otherwise, we can continue with the allowed subscriptions
+            warn!(
+                "Azure denied access to some subscriptions, their emissions are not included: {}",
+                denied_subscriptions.join(", ")
+            );
         }
 
+        // The total is unknown when pages precede or follow this one
+        let last_page = azure_response.skip_token.is_none() && query.skip_token.is_none();
+        progress::page(|| self.name().to_string(), 1, last_page.then_some(1));
+
         // Convert Azure response to carbem format
         let mut emissions = Vec::new();
-        for data in azure_response.value {
+        let mut issues = Vec::new();
+        for (row, data) in azure_response.value.iter().enumerate() {
+            // Rows are repeated per subscription: report their issues once
+            let mut row_issues = Vec::new();
             for subscription_id in &allowed_subscriptions {
-                let emission =
-                    self.convert_to_carbon_emission(&data, subscription_id, &query.date_range);
+                row_issues.clear();
+                let emission = self.convert_to_carbon_emission(
+                    data,
+                    subscription_id,
+                    &query.date_range,
+                    row,
+                    &mut row_issues,
+                );
                 emissions.push(emission);
             }
+            issues.extend(row_issues);
+        }
+        if strict && !issues.is_empty() {
+            return Err(CarbemError::LossyConversion(issues));
         }
 
         // Sort emissions by date if available (newest first)
-        emissions.sort_by(|a, b| b.time_period.start.cmp(&a.time_period.start));
+        emissions.sort_by_key(|e| std::cmp::Reverse(e.time_period.start));
+
+        let mut result = EmissionResult {
+            emissions,
+            raw_responses,
+            planned_requests: Vec::new(),
+        };
+        result.record_lineage(&lineage);
+        Ok((result, azure_response.skip_token))
+    }
 
-        Ok(emissions)
+    /// Pager over the pages of `query`, fetched one by one on request
+    ///
+    /// Dry runs yield their planned requests as a single page.
+    pub fn pager<'a>(&'a self, query: &EmissionQuery) -> AzurePager<'a> {
+        AzurePager::new(self, query.clone())
+    }
+}
+
+impl PageSource for AzureProvider {
+    // Fetch the page selected by `query`, with the query of the next page if any
+    async fn fetch_page(
+        &self,
+        query: &EmissionQuery,
+    ) -> Result<(EmissionResult, Option<EmissionQuery>)> {
+        if query.dry_run {
+            let result = EmissionResult {
+                planned_requests: self.plan_requests(query)?,
+                ..Default::default()
+            };
+            return Ok((result, None));
+        }
+
+        let resolved = self.resolve_subscriptions(query).await?;
+        let query = resolved.as_ref().unwrap_or(query);
+
+        // Convert EmissionQuery to Azure request format
+        let azure_request = self.prepare_request(query)?;
+
+        let (mut result, skip_token) = self
+            .request_carbon_emissions(&azure_request, query.raw_response, query.strict)
+            .await?;
+        result.record_date_alignment(query.date_alignment);
+
+        // Subscriptions stay resolved so following pages skip the discovery
+        let next = skip_token.map(|skip_token| {
+            let mut next = query.clone();
+            if let Some(ProviderQueryConfig::Azure(config)) = &mut next.provider_config {
+                config.skip_token = Some(skip_token);
+            }
+            next
+        });
+        Ok((result, next))
     }
 }
 
-#[async_trait]
 impl CarbonProvider for AzureProvider {
     fn name(&self) -> &'static str {
         "azure"
     }
 
     async fn get_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
-        if query.provider != "azure" {
-            return Err(CarbemError::Config(
-                "Query provider must be 'azure' for AzureProvider".to_string(),
-            ));
-        }
+        Ok(self.get_emissions_with_raw(query).await?.emissions)
+    }
 
-        if query.regions.is_empty() {
-            return Err(CarbemError::Config(
-                "At least one subscription ID must be specified in the query".to_string(),
-            ));
+    async fn get_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        let (result, next) = self.fetch_page(query).await?;
+        if next.is_some() {
+            warn!(
+                "Azure returned more pages than were fetched; set skip_token in AzureQueryConfig or use AzureProvider::pager to retrieve the next page"
+            );
         }
+        Ok(result)
+    }
 
-        // Convert EmissionQuery to Azure request format
-        let azure_request = self.convert_emission_query_to_azure_request(query)?;
+    fn is_configured(&self) -> bool {
+        !self.config.access_token.is_empty() || self.token_cache.is_some()
+    }
 
-        self.request_carbon_emissions(&azure_request).await
+    async fn prewarm(&self) -> Result<()> {
+        // Refreshes a cached token now rather than on the first query
+        self.access_token().await?;
+        // Any response leaves a pooled TLS connection to the API
+        self.transport
+            .send(HttpRequest::new(
+                Method::HEAD,
+                AZURE_MANAGEMENT_BASE_URL,
+                HeaderMap::new(),
+            ))
+            .await
+            .map(|_| ())
     }
 
-    fn is_configured(&self) -> bool {
-        !self.config.access_token.is_empty()
+    fn set_lenient_parsing(&mut self, lenient: bool) {
+        self.lenient_parsing = lenient;
     }
 
-    fn clone_provider(&self) -> Box<dyn CarbonProvider + Send + Sync> {
-        Box::new(self.clone())
+    fn set_transport(&mut self, transport: Arc<dyn Transport>) {
+        self.transport = transport;
     }
+
+    fn schema_warnings(&self) -> Vec<SchemaWarning> {
+        self.schema_log.snapshot()
+    }
+
+    fn recent_exchanges(&self) -> Vec<CapturedExchange> {
+        self.exchange_log.snapshot()
+    }
+
+    fn plan_requests(&self, query: &EmissionQuery) -> Result<Vec<PlannedRequest>> {
+        let azure_request = self.prepare_request(query)?;
+        let payload = self.build_request_payload(&azure_request);
+        let headers = self.build_headers(&self.config.access_token)?;
+        let redactor = self.redactor(&self.config.access_token);
+
+        let mut planned = Vec::new();
+        // The subscription list of the report request is only known after discovery
+        if matches!(
+            &query.provider_config,
+            Some(ProviderQueryConfig::Azure(config)) if config.all_subscriptions == Some(true)
+        ) {
+            planned.push(PlannedRequest::new(
+                self.name(),
+                "GET",
+                &Self::subscriptions_url(),
+                &headers,
+                None,
+                &redactor,
+            ));
+        }
+        planned.push(PlannedRequest::new(
+            self.name(),
+            "POST",
+            &Self::endpoint_url(),
+            &headers,
+            Some(serde_json::to_value(&payload)?),
+            &redactor,
+        ));
+        Ok(planned)
+    }
+
+    fn expected_pages(&self, query: &EmissionQuery) -> Option<u64> {
+        // Only item details are paginated, over resources carbem cannot count
+        match &query.provider_config {
+            Some(ProviderQueryConfig::Azure(config)) => {
+                (config.report_type() != AzureReportType::ItemDetailsReport).then_some(1)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Actions of `required` allowed by none of `permissions`
+fn missing_actions(permissions: &[AzurePermission], required: &[&str]) -> Vec<String> {
+    required
+        .iter()
+        .filter(|action| {
+            !permissions.iter().any(|permission| {
+                permission
+                    .actions
+                    .iter()
+                    .any(|pattern| action_matches(pattern, action))
+                    && !permission
+                        .not_actions
+                        .iter()
+                        .any(|pattern| action_matches(pattern, action))
+            })
+        })
+        .map(|action| action.to_string())
+        .collect()
+}
+
+// Case-insensitive match of an action against a pattern where `*` matches any text
+fn action_matches(pattern: &str, action: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let action = action.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = action.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard: the whole action must match
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{EmissionQuery, TimePeriod};
+    use crate::models::{DateAlignment, EmissionQuery, QueryTimezone, TimePeriod};
+    use crate::schema::SchemaWarningKind;
+    use async_trait::async_trait;
     use chrono::{TimeZone, Utc};
 
     fn create_test_provider() -> AzureProvider {
@@ -447,9 +991,48 @@ mod tests {
             services: None,
             resources: None,
             provider_config: None, // Use defaults
+            raw_response: RawResponseMode::None,
+            date_alignment: DateAlignment::ExpandToFullMonths,
+            timezone: QueryTimezone::Utc,
+            dry_run: false,
+            route: Vec::new(),
+            tag_filters: Vec::new(),
+            strict: false,
         }
     }
 
+    #[test]
+    fn test_missing_actions() {
+        let permissions = |actions: &[&str], not_actions: &[&str]| {
+            vec![AzurePermission {
+                actions: actions.iter().map(|a| a.to_string()).collect(),
+                not_actions: not_actions.iter().map(|a| a.to_string()).collect(),
+            }]
+        };
+
+        assert!(missing_actions(&permissions(&["*"], &[]), CARBON_REPORT_ACTIONS).is_empty());
+        assert!(
+            missing_actions(
+                &permissions(&["microsoft.carbon/*"], &[]),
+                CARBON_REPORT_ACTIONS
+            )
+            .is_empty()
+        );
+        // Reader grants only read actions
+        assert_eq!(
+            missing_actions(&permissions(&["*/read"], &[]), CARBON_REPORT_ACTIONS),
+            CARBON_REPORT_ACTIONS
+        );
+        assert_eq!(
+            missing_actions(
+                &permissions(&["*"], &["Microsoft.Carbon/*/action"]),
+                CARBON_REPORT_ACTIONS
+            ),
+            CARBON_REPORT_ACTIONS
+        );
+        assert!(!action_matches("Microsoft.Carbon", "Microsoft.Carbon/x"));
+    }
+
     #[test]
     fn test_azure_provider_creation() {
         let config = AzureConfig {
@@ -461,6 +1044,158 @@ mod tests {
         assert!(provider.is_configured());
     }
 
+    #[tokio::test]
+    async fn test_dry_run_plans_redacted_request() {
+        let provider = create_test_provider();
+        let mut query = create_test_emission_query();
+        query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            ..Default::default()
+        }));
+        query.dry_run = true;
+
+        let result = provider.get_emissions_with_raw(&query).await.unwrap();
+        assert!(result.emissions.is_empty());
+        let request = &result.planned_requests[0];
+        assert_eq!(request.provider, "azure");
+        assert_eq!(request.method, "POST");
+        assert!(
+            request
+                .url
+                .ends_with("carbonEmissionReports?api-version=2025-04-01")
+        );
+        assert!(!request.headers["authorization"].contains("test-token"));
+        let body = request.body.as_ref().unwrap();
+        assert_eq!(body["reportType"], "MonthlySummaryReport");
+        assert_eq!(body["dateRange"]["end"], "2024-05-01");
+
+        // Invalid queries fail the same way as when sent
+        query.provider_config = None;
+        assert!(provider.plan_requests(&query).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_conformance() {
+        use crate::providers::test_kit::ConformanceSuite;
+
+        let suite = ConformanceSuite::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/providers/azure/conformance"
+        ))
+        .unwrap();
+        suite.run(&create_test_provider()).await.assert_passed();
+    }
+
+    #[tokio::test]
+    async fn test_get_emissions_through_transport() {
+        use crate::transport::HttpResponse;
+        use std::sync::Mutex;
+
+        #[derive(Debug, Default)]
+        struct InMemory {
+            requests: Mutex<Vec<HttpRequest>>,
+        }
+
+        #[async_trait]
+        impl Transport for InMemory {
+            async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+                self.requests.lock().unwrap().push(request);
+                Ok(HttpResponse::new(
+                    StatusCode::OK,
+                    r#"{"value": [{
+                        "dataType": "MonthlySummaryData",
+                        "latestMonthEmissions": 12.5,
+                        "previousMonthEmissions": 10.0,
+                        "monthOverMonthEmissionsChangeRatio": 0.25,
+                        "monthlyEmissionsChangeValue": 2.5,
+                        "date": "2024-03-01"
+                    }]}"#,
+                ))
+            }
+        }
+
+        let transport = Arc::new(InMemory::default());
+        let provider = create_test_provider().with_transport(transport.clone());
+        let mut query = create_test_emission_query();
+        query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            ..Default::default()
+        }));
+
+        let emissions = provider.get_emissions(&query).await.unwrap();
+        assert_eq!(emissions.len(), 1);
+        assert_eq!(emissions[0].emissions_kg_co2eq, 12.5);
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].headers[AUTHORIZATION], "Bearer test-token");
+        let body: serde_json::Value =
+            serde_json::from_slice(requests[0].body.as_ref().unwrap()).unwrap();
+        assert_eq!(body["reportType"], "MonthlySummaryReport");
+        assert_eq!(provider.recent_exchanges()[0].status, Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_all_subscriptions_are_discovered() {
+        use crate::transport::HttpResponse;
+        use std::sync::Mutex;
+
+        #[derive(Debug, Default)]
+        struct Tenant {
+            report_bodies: Mutex<Vec<serde_json::Value>>,
+        }
+
+        #[async_trait]
+        impl Transport for Tenant {
+            async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+                let body = if request.url.contains("skiptoken") {
+                    r#"{"value": [
+                        {"subscriptionId": "00000000-0000-0000-0000-000000000002", "displayName": "Legacy", "state": "Disabled"}
+                    ]}"#
+                } else if request.method == Method::GET {
+                    r#"{"value": [
+                        {"subscriptionId": "00000000-0000-0000-0000-000000000001", "displayName": "Production", "state": "Enabled", "tenantId": "tenant"}
+                    ], "nextLink": "https://management.azure.com/subscriptions?api-version=2022-12-01&$skiptoken=page2"}"#
+                } else {
+                    self.report_bodies
+                        .lock()
+                        .unwrap()
+                        .push(serde_json::from_slice(request.body.as_ref().unwrap()).unwrap());
+                    r#"{"value": []}"#
+                };
+                Ok(HttpResponse::new(StatusCode::OK, body))
+            }
+        }
+
+        let transport = Arc::new(Tenant::default());
+        let provider = create_test_provider().with_transport(transport.clone());
+
+        let subscriptions = provider.discover_subscriptions().await.unwrap();
+        assert_eq!(subscriptions.len(), 2);
+        assert_eq!(subscriptions[0].display_name, "Production");
+        assert!(!subscriptions[1].is_active());
+
+        let mut query = create_test_emission_query();
+        query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            all_subscriptions: Some(true),
+            ..Default::default()
+        }));
+        provider.get_emissions(&query).await.unwrap();
+        assert_eq!(
+            transport.report_bodies.lock().unwrap()[0]["subscriptionList"],
+            serde_json::json!(["00000000-0000-0000-0000-000000000001"])
+        );
+
+        query.dry_run = true;
+        let planned = provider
+            .get_emissions_with_raw(&query)
+            .await
+            .unwrap()
+            .planned_requests;
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].method, "GET");
+    }
+
     #[test]
     fn test_azure_provider_not_configured_with_empty_token() {
         let config = AzureConfig {
@@ -471,6 +1206,37 @@ mod tests {
         assert!(!provider.is_configured());
     }
 
+    #[tokio::test]
+    async fn test_token_cache_supplies_access_token() {
+        struct Fixed;
+
+        #[async_trait]
+        impl crate::auth::TokenRefresher for Fixed {
+            async fn refresh(&self) -> Result<crate::auth::AccessToken> {
+                Ok(crate::auth::AccessToken {
+                    secret: "fresh-token".to_string(),
+                    expires_at: Utc::now() + chrono::Duration::hours(1),
+                })
+            }
+        }
+
+        let provider = AzureProvider::new(AzureConfig {
+            access_token: String::new(),
+        })
+        .unwrap()
+        .with_token_cache(Arc::new(TokenCache::new(Arc::new(Fixed))));
+
+        assert!(provider.is_configured());
+        let access_token = provider.access_token().await.unwrap();
+        assert_eq!(access_token, "fresh-token");
+        assert_eq!(
+            provider
+                .redactor(&access_token)
+                .redact("Bearer fresh-token"),
+            "Bearer [REDACTED]"
+        );
+    }
+
     #[test]
     fn test_convert_emission_query_to_azure_request() {
         let provider = create_test_provider();
@@ -478,7 +1244,7 @@ mod tests {
 
         // Provide required Azure configuration with defaults
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
             ..Default::default()
         }));
 
@@ -504,6 +1270,37 @@ mod tests {
         assert_eq!(azure_request.page_size, None);
     }
 
+    #[test]
+    fn test_convert_emission_query_aligns_mid_month_dates() {
+        let provider = create_test_provider();
+        let mut query = create_test_emission_query();
+        query.time_period = TimePeriod {
+            start: Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 5, 20, 0, 0, 0).unwrap(),
+        };
+        query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            ..Default::default()
+        }));
+
+        let azure_request = provider
+            .convert_emission_query_to_azure_request(&query)
+            .unwrap();
+        assert_eq!(azure_request.date_range.start, "2024-03-01");
+        assert_eq!(azure_request.date_range.end, "2024-05-01");
+
+        query.date_alignment = DateAlignment::Truncate;
+        let azure_request = provider
+            .convert_emission_query_to_azure_request(&query)
+            .unwrap();
+        assert_eq!(azure_request.date_range.start, "2024-04-01");
+        assert_eq!(azure_request.date_range.end, "2024-04-01");
+
+        query.date_alignment = DateAlignment::Strict;
+        let result = provider.convert_emission_query_to_azure_request(&query);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_convert_emission_query_with_provider_config() {
         let provider = create_test_provider();
@@ -511,10 +1308,11 @@ mod tests {
 
         // Add provider-specific configuration for ItemDetailsReport using type-safe config
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::ItemDetailsReport,
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            report_type: Some(AzureReportType::ItemDetailsReport),
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            all_subscriptions: None,
             carbon_scope_list: None, // Will use defaults
-            category_type: Some("Location".to_string()),
+            category_type: Some(AzureCategoryType::Location),
             order_by: Some("emissions".to_string()),
             page_size: Some(100),
             sort_direction: Some(AzureSortDirection::Desc),
@@ -558,8 +1356,9 @@ mod tests {
 
         // Missing required fields for ItemDetailsReport
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::ItemDetailsReport,
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            report_type: Some(AzureReportType::ItemDetailsReport),
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            all_subscriptions: None,
             carbon_scope_list: None,
             category_type: None,  // Missing (required)
             order_by: None,       // Missing (required)
@@ -588,10 +1387,11 @@ mod tests {
 
         // Multi-month query (should fail for ItemDetailsReport)
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::ItemDetailsReport,
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            report_type: Some(AzureReportType::ItemDetailsReport),
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            all_subscriptions: None,
             carbon_scope_list: None,
-            category_type: Some("Location".to_string()),
+            category_type: Some(AzureCategoryType::Location),
             order_by: Some("emissions".to_string()),
             page_size: Some(100),
             sort_direction: Some(AzureSortDirection::Desc),
@@ -619,8 +1419,9 @@ mod tests {
 
         // Missing required fields for TopItemsSummaryReport
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::TopItemsSummaryReport,
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            report_type: Some(AzureReportType::TopItemsSummaryReport),
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            all_subscriptions: None,
             carbon_scope_list: None,
             category_type: None, // Missing (required)
             order_by: None,
@@ -649,10 +1450,11 @@ mod tests {
 
         // TopItemsMonthlySummaryReport with optional filters
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::TopItemsMonthlySummaryReport,
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            report_type: Some(AzureReportType::TopItemsMonthlySummaryReport),
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            all_subscriptions: None,
             carbon_scope_list: Some(vec![AzureCarbonScope::Scope1]),
-            category_type: Some("Location".to_string()),
+            category_type: Some(AzureCategoryType::Location),
             order_by: None,
             page_size: None,
             sort_direction: None,
@@ -686,6 +1488,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_carbon_scope_selection() {
+        let provider = create_test_provider();
+        let mut query = create_test_emission_query();
+
+        query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            carbon_scope_list: Some(vec![
+                AzureCarbonScope::Scope3,
+                AzureCarbonScope::Scope1,
+                AzureCarbonScope::Scope3,
+            ]),
+            ..Default::default()
+        }));
+
+        let azure_request = provider
+            .convert_emission_query_to_azure_request(&query)
+            .unwrap();
+        assert_eq!(azure_request.carbon_scope_list, vec!["Scope3", "Scope1"]);
+    }
+
+    #[test]
+    fn test_carbon_scope_validation() {
+        let provider = create_test_provider();
+        let mut query = create_test_emission_query();
+
+        query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            carbon_scope_list: Some(vec![AzureCarbonScope::Scope1, AzureCarbonScope::Location]),
+            ..Default::default()
+        }));
+        let result = provider.convert_emission_query_to_azure_request(&query);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("carbon_scope_list only accepts Scope1, Scope2 and Scope3")
+        );
+
+        query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            carbon_scope_list: Some(vec![]),
+            ..Default::default()
+        }));
+        let result = provider.convert_emission_query_to_azure_request(&query);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("carbon_scope_list cannot be empty")
+        );
+    }
+
     #[test]
     fn test_page_size_validation() {
         let provider = create_test_provider();
@@ -694,10 +1549,11 @@ mod tests {
 
         // Invalid page_size (too large)
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::ItemDetailsReport,
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            report_type: Some(AzureReportType::ItemDetailsReport),
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            all_subscriptions: None,
             carbon_scope_list: None,
-            category_type: Some("Location".to_string()),
+            category_type: Some(AzureCategoryType::Location),
             order_by: Some("emissions".to_string()),
             page_size: Some(6000), // > 5000 (max)
             sort_direction: Some(AzureSortDirection::Desc),
@@ -725,10 +1581,11 @@ mod tests {
 
         // Invalid top_items (too large)
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            report_type: AzureReportType::TopItemsSummaryReport,
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            report_type: Some(AzureReportType::TopItemsSummaryReport),
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
+            all_subscriptions: None,
             carbon_scope_list: None,
-            category_type: Some("Location".to_string()),
+            category_type: Some(AzureCategoryType::Location),
             order_by: None,
             page_size: None,
             sort_direction: None,
@@ -751,7 +1608,9 @@ mod tests {
     #[test]
     fn test_build_headers() {
         let provider = create_test_provider();
-        let headers = provider.build_headers().unwrap();
+        let headers = provider
+            .build_headers(&provider.config.access_token)
+            .unwrap();
 
         assert!(headers.contains_key("authorization"));
         assert!(headers.contains_key("content-type"));
@@ -763,6 +1622,14 @@ mod tests {
         assert_eq!(content_type, "application/json");
     }
 
+    #[test]
+    fn test_debug_output_redacts_access_token() {
+        let provider = create_test_provider();
+        let debug = format!("{:?}", provider);
+        assert!(!debug.contains("test-token"));
+        assert!(debug.contains("[REDACTED]"));
+    }
+
     #[test]
     fn test_build_headers_invalid_token() {
         let config = AzureConfig {
@@ -770,7 +1637,7 @@ mod tests {
         };
         let provider = AzureProvider::new(config).unwrap();
 
-        let result = provider.build_headers();
+        let result = provider.build_headers(&provider.config.access_token);
         assert!(result.is_err());
         assert!(
             result
@@ -802,7 +1669,7 @@ mod tests {
 
         // Provide required Azure configuration with defaults
         query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
-            subscription_list: vec!["00000000-0000-0000-0000-000000000000".to_string()],
+            subscription_list: Some(vec!["00000000-0000-0000-0000-000000000000".to_string()]),
             ..Default::default()
         }));
 
@@ -839,8 +1706,13 @@ mod tests {
             category_type: None,
         };
 
-        let emission =
-            provider.convert_to_carbon_emission(&azure_data, "test-subscription", &date_range);
+        let emission = provider.convert_to_carbon_emission(
+            &azure_data,
+            "test-subscription",
+            &date_range,
+            0,
+            &mut Vec::new(),
+        );
 
         assert_eq!(emission.provider, "azure");
         assert_eq!(emission.region, "test-subscription");
@@ -880,8 +1752,13 @@ mod tests {
             category_type: Some("Location".to_string()),
         };
 
-        let emission =
-            provider.convert_to_carbon_emission(&azure_data, "test-subscription", &date_range);
+        let emission = provider.convert_to_carbon_emission(
+            &azure_data,
+            "test-subscription",
+            &date_range,
+            0,
+            &mut Vec::new(),
+        );
 
         assert_eq!(emission.provider, "azure");
         assert_eq!(emission.region, "east us"); // Should use item_name as region
@@ -914,8 +1791,13 @@ mod tests {
             category_type: None,
         };
 
-        let emission =
-            provider.convert_to_carbon_emission(&azure_data, "test-subscription", &date_range);
+        let emission = provider.convert_to_carbon_emission(
+            &azure_data,
+            "test-subscription",
+            &date_range,
+            0,
+            &mut Vec::new(),
+        );
 
         // Check that the time period was created from the date (May 1 to June 1)
         let expected_start = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
@@ -981,8 +1863,13 @@ mod tests {
             category_type: None,
         };
 
-        let emission =
-            provider.convert_to_carbon_emission(&azure_data, "test-subscription", &date_range);
+        let emission = provider.convert_to_carbon_emission(
+            &azure_data,
+            "test-subscription",
+            &date_range,
+            0,
+            &mut Vec::new(),
+        );
 
         // December should roll over to January of the next year
         let expected_start = Utc.with_ymd_and_hms(2024, 12, 1, 0, 0, 0).unwrap();
@@ -1012,17 +1899,22 @@ mod tests {
             category_type: None,
         };
 
-        let emission =
-            provider.convert_to_carbon_emission(&azure_data, "test-subscription", &date_range);
+        let emission = provider.convert_to_carbon_emission(
+            &azure_data,
+            "test-subscription",
+            &date_range,
+            0,
+            &mut Vec::new(),
+        );
 
         assert_eq!(emission.provider, "azure");
         assert_eq!(emission.region, "test-subscription"); // Should use subscription_id when no item_name
         assert_eq!(emission.service, Some("overall".to_string())); // Should default to "overall"
         assert_eq!(emission.emissions_kg_co2eq, 0.1);
 
-        // Should use the original date range when no specific date is provided
+        // Should use the original date range, end month included, when no specific date is provided
         let expected_start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
-        let expected_end = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let expected_end = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
 
         assert_eq!(emission.time_period.start, expected_start);
         assert_eq!(emission.time_period.end, expected_end);
@@ -1032,6 +1924,152 @@ mod tests {
         assert_eq!(provider_data["dataType"], "OverallSummaryData");
     }
 
+    #[test]
+    fn test_parse_response_records_schema_drift() {
+        let provider = create_test_provider();
+        let body = r#"{
+            "value": [{
+                "dataType": "MonthlySummaryData",
+                "latestMonthEmissions": 0.1,
+                "previousMonthEmissions": 0.05,
+                "monthOverMonthEmissionsChangeRatio": 1.0,
+                "monthlyEmissionsChangeValue": 0.05,
+                "date": "2024-05-01",
+                "waterUsage": 3.2
+            }]
+        }"#;
+
+        let response = provider.parse_response(body).unwrap();
+        assert_eq!(response.value.len(), 1);
+
+        let warnings = provider.schema_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "value[].waterUsage");
+        assert_eq!(warnings[0].kind, SchemaWarningKind::UnknownField);
+    }
+
+    #[test]
+    fn test_parse_response_lenient_missing_field() {
+        let mut provider = create_test_provider();
+        let body = r#"{
+            "value": [{
+                "dataType": "OverallSummaryData",
+                "latestMonthEmissions": 0.1,
+                "previousMonthEmissions": 0.05,
+                "monthlyEmissionsChangeValue": 0.05
+            }]
+        }"#;
+
+        // Strict parsing fails on the missing field, but still reports it
+        assert!(provider.parse_response(body).is_err());
+        assert_eq!(
+            provider.schema_warnings()[0].path,
+            "value[].monthOverMonthEmissionsChangeRatio"
+        );
+
+        provider.set_lenient_parsing(true);
+        let response = provider.parse_response(body).unwrap();
+        assert_eq!(
+            response.value[0].month_over_month_emissions_change_ratio,
+            0.0
+        );
+        assert_eq!(provider.schema_warnings().len(), 1);
+    }
+
+    mod properties {
+        use super::*;
+        use crate::schema::strategies;
+        use proptest::prelude::*;
+        use serde_json::json;
+
+        // Answers every request with the same body
+        #[derive(Debug)]
+        struct Answering(String);
+
+        #[async_trait]
+        impl Transport for Answering {
+            async fn send(&self, _request: HttpRequest) -> Result<crate::transport::HttpResponse> {
+                Ok(crate::transport::HttpResponse::new(
+                    StatusCode::OK,
+                    self.0.clone(),
+                ))
+            }
+        }
+
+        fn valid_row() -> serde_json::Value {
+            json!({
+                "dataType": "MonthlySummaryData",
+                "latestMonthEmissions": 12.5,
+                "previousMonthEmissions": 10.0,
+                "monthOverMonthEmissionsChangeRatio": 0.25,
+                "monthlyEmissionsChangeValue": 2.5,
+                "date": "2024-03-01",
+                "carbonIntensity": 0.4
+            })
+        }
+
+        fn query(subscriptions: Vec<String>) -> EmissionQuery {
+            let mut query = create_test_emission_query();
+            query.provider_config = Some(ProviderQueryConfig::Azure(AzureQueryConfig {
+                subscription_list: Some(subscriptions),
+                ..Default::default()
+            }));
+            query
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            // Malformed responses fail with an error naming the problem, never a panic
+            #[test]
+            fn malformed_responses_are_reported(
+                rows in prop::collection::vec(
+                    strategies::mutated(valid_row(), AZURE_EMISSION_DATA_FIELDS),
+                    0..4,
+                ),
+                extra in strategies::json(),
+            ) {
+                let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+                for body in [json!({"value": rows}).to_string(), extra.to_string()] {
+                    let provider = create_test_provider().with_transport(Arc::new(Answering(body)));
+                    let query = query(vec!["00000000-0000-0000-0000-000000000000".to_string()]);
+                    if let Err(e) = runtime.block_on(provider.get_emissions(&query)) {
+                        prop_assert!(
+                            matches!(e, CarbemError::Api(_) | CarbemError::Auth(_)),
+                            "unexpected error: {}",
+                            e
+                        );
+                    }
+                }
+            }
+
+            // Request bodies carry the subscriptions and the months of the query
+            #[test]
+            fn request_bodies_follow_queries(
+                subscriptions in prop::collection::vec("[0-9a-zA-Z-]{1,36}", 1..5),
+                start in 0i64..1_000_000,
+                months in 0u32..36,
+            ) {
+                let provider = create_test_provider();
+                let mut query = query(subscriptions.clone());
+                query.time_period.start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap()
+                    + chrono::Duration::hours(start);
+                query.time_period.end = query.time_period.start + chrono::Months::new(months);
+
+                let request = provider.prepare_request(&query).unwrap();
+                let body: serde_json::Value = serde_json::from_slice(
+                    &serde_json::to_vec(&provider.build_request_payload(&request)).unwrap(),
+                )
+                .unwrap();
+                prop_assert_eq!(&body["subscriptionList"], &json!(subscriptions));
+                let start = body["dateRange"]["start"].as_str().unwrap();
+                let end = body["dateRange"]["end"].as_str().unwrap();
+                prop_assert!(start.ends_with("-01") && end.ends_with("-01"));
+                prop_assert!(start <= end, "{} after {}", start, end);
+            }
+        }
+    }
+
     #[tokio::test]
     #[ignore] // Ignore by default as this requires a real Azure token
     async fn test_get_emissions_integration() {
@@ -1051,6 +2089,13 @@ mod tests {
                 services: None,
                 resources: None,
                 provider_config: None, // Use defaults
+                raw_response: RawResponseMode::None,
+                date_alignment: DateAlignment::ExpandToFullMonths,
+                timezone: QueryTimezone::Utc,
+                dry_run: false,
+                route: Vec::new(),
+                tag_filters: Vec::new(),
+                strict: false,
             };
 
             let result = provider.get_emissions(&query).await;
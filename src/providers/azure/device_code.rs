@@ -0,0 +1,405 @@
+//! Interactive Azure sign-in with the OAuth 2.0 device code flow
+//!
+//! [`DeviceCodeFlow::start`] returns a [`DeviceCodeChallenge`] whose message
+//! asks the user to open a page and enter a code; [`DeviceCodeFlow::poll`] then
+//! waits until the sign-in completes. The resulting [`AzureToken`] carries a
+//! refresh token, so later runs can get a fresh access token with
+//! [`DeviceCodeFlow::refresh`] without signing in again.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{
+    Method,
+    header::{CONTENT_TYPE, HeaderMap, HeaderValue},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{AccessToken, TokenRefresher};
+use crate::error::{CarbemError, Result};
+use crate::runtime::runtime;
+use crate::transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport};
+
+/// Microsoft identity platform authority
+const AZURE_AUTHORITY_URL: &str = "https://login.microsoftonline.com";
+
+/// Public client ID of the Azure CLI, usable without registering an application
+pub const AZURE_CLI_CLIENT_ID: &str = "04b07795-8ddb-461a-bbee-02f9e1bf7b46";
+
+// Scopes of the Azure Management API, with a refresh token
+const MANAGEMENT_SCOPE: &str = "https://management.azure.com/.default offline_access";
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Code the user enters to complete the sign-in
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceCodeChallenge {
+    /// Code to enter on the verification page
+    pub user_code: String,
+
+    /// Page where the code is entered
+    pub verification_uri: String,
+
+    /// Instructions to display to the user
+    pub message: String,
+
+    /// Seconds before the code expires
+    pub expires_in: u64,
+
+    /// Seconds to wait between polls
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+
+    // Secret code exchanged for the token, never shown to the user
+    device_code: String,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// Tokens obtained from a sign-in or a refresh
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AzureToken {
+    /// Bearer token for the Azure Management API
+    pub access_token: String,
+
+    /// Optional: token to get a new access token without signing in again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+
+    /// When the access token expires
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AzureToken {
+    /// Whether the access token expires within `margin`
+    pub fn expires_within(&self, margin: chrono::Duration) -> bool {
+        self.expires_at - margin <= Utc::now()
+    }
+}
+
+impl From<AzureToken> for AccessToken {
+    fn from(token: AzureToken) -> Self {
+        AccessToken {
+            secret: token.access_token,
+            expires_at: token.expires_at,
+        }
+    }
+}
+
+// Successful token endpoint response
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    expires_in: i64,
+}
+
+// Token endpoint error response (RFC 8628, section 3.5)
+#[derive(Debug, Deserialize)]
+struct TokenError {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+// What to do after a poll of the token endpoint
+#[derive(Debug, PartialEq)]
+enum PollOutcome {
+    Pending,
+    SlowDown,
+    Failed(String),
+}
+
+impl From<TokenError> for PollOutcome {
+    fn from(error: TokenError) -> Self {
+        match error.error.as_str() {
+            "authorization_pending" => PollOutcome::Pending,
+            "slow_down" => PollOutcome::SlowDown,
+            "authorization_declined" => PollOutcome::Failed("sign-in was declined".to_string()),
+            "expired_token" => {
+                PollOutcome::Failed("device code expired before sign-in".to_string())
+            }
+            _ => PollOutcome::Failed(
+                error
+                    .error_description
+                    .unwrap_or_else(|| error.error.clone()),
+            ),
+        }
+    }
+}
+
+/// Device code sign-in against a Microsoft Entra ID tenant
+#[derive(Debug, Clone)]
+pub struct DeviceCodeFlow {
+    tenant: String,
+    client_id: String,
+    authority_url: String,
+    transport: Arc<dyn Transport>,
+}
+
+impl Default for DeviceCodeFlow {
+    fn default() -> Self {
+        Self::new("organizations")
+    }
+}
+
+impl DeviceCodeFlow {
+    /// Flow for `tenant` (ID, domain or `organizations`) with the Azure CLI client ID
+    pub fn new(tenant: impl Into<String>) -> Self {
+        Self {
+            tenant: tenant.into(),
+            client_id: AZURE_CLI_CLIENT_ID.to_string(),
+            authority_url: AZURE_AUTHORITY_URL.to_string(),
+            transport: Arc::new(ReqwestTransport::default()),
+        }
+    }
+
+    /// Use the client ID of your own public application registration
+    pub fn with_client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = client_id.into();
+        self
+    }
+
+    /// Use another authority, e.g. for national clouds
+    pub fn with_authority_url(mut self, authority_url: impl Into<String>) -> Self {
+        self.authority_url = authority_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Send requests through `transport` instead of the default reqwest client
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    fn endpoint(&self, name: &str) -> String {
+        format!(
+            "{}/{}/oauth2/v2.0/{}",
+            self.authority_url,
+            urlencoding::encode(&self.tenant),
+            name
+        )
+    }
+
+    async fn post_form(&self, name: &str, params: &[(&str, &str)]) -> Result<HttpResponse> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        let request = HttpRequest::new(Method::POST, self.endpoint(name), headers)
+            .with_body(form_body(params));
+        self.transport.send(request).await
+    }
+
+    /// Request a device code; display its [`message`](DeviceCodeChallenge::message) to the user
+    pub async fn start(&self) -> Result<DeviceCodeChallenge> {
+        let response = self
+            .post_form(
+                "devicecode",
+                &[("client_id", &self.client_id), ("scope", MANAGEMENT_SCOPE)],
+            )
+            .await?;
+        if !response.is_success() {
+            return Err(CarbemError::Auth(format!(
+                "device code request failed with status {}: {}",
+                response.status,
+                response.text()
+            )));
+        }
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    /// Wait until the user completes the sign-in of `challenge`
+    pub async fn poll(&self, challenge: &DeviceCodeChallenge) -> Result<AzureToken> {
+        let deadline = Utc::now() + chrono::Duration::seconds(challenge.expires_in as i64);
+        let mut interval = challenge.interval.max(1);
+        loop {
+            runtime().sleep(Duration::from_secs(interval)).await;
+            match self
+                .request_token(&[
+                    ("grant_type", DEVICE_CODE_GRANT),
+                    ("client_id", &self.client_id),
+                    ("device_code", &challenge.device_code),
+                ])
+                .await?
+            {
+                Ok(token) => return Ok(token),
+                Err(PollOutcome::Pending) => {}
+                // RFC 8628: increase the interval by 5 seconds
+                Err(PollOutcome::SlowDown) => interval += 5,
+                Err(PollOutcome::Failed(message)) => return Err(CarbemError::Auth(message)),
+            }
+            if Utc::now() >= deadline {
+                return Err(CarbemError::Auth(
+                    "device code expired before sign-in".to_string(),
+                ));
+            }
+        }
+    }
+
+    /// Get a new access token from a refresh token
+    pub async fn refresh(&self, refresh_token: &str) -> Result<AzureToken> {
+        match self
+            .request_token(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", &self.client_id),
+                ("refresh_token", refresh_token),
+                ("scope", MANAGEMENT_SCOPE),
+            ])
+            .await?
+        {
+            Ok(token) => Ok(token),
+            Err(PollOutcome::Failed(message)) => Err(CarbemError::Auth(message)),
+            Err(_) => Err(CarbemError::Auth("token refresh failed".to_string())),
+        }
+    }
+
+    async fn request_token(
+        &self,
+        params: &[(&str, &str)],
+    ) -> Result<std::result::Result<AzureToken, PollOutcome>> {
+        let response = self.post_form("token", params).await?;
+        let text = response.text();
+        if response.is_success() {
+            let token: TokenResponse = serde_json::from_str(&text)?;
+            return Ok(Ok(AzureToken {
+                access_token: token.access_token,
+                refresh_token: token.refresh_token,
+                expires_at: Utc::now() + chrono::Duration::seconds(token.expires_in),
+            }));
+        }
+        match serde_json::from_str::<TokenError>(&text) {
+            Ok(error) => Ok(Err(error.into())),
+            Err(_) => Err(CarbemError::Auth(format!(
+                "unexpected token endpoint response: {}",
+                text
+            ))),
+        }
+    }
+}
+
+/// [`TokenRefresher`] using a refresh token, replaced by the rotated one after each refresh
+///
+/// Pair it with a [`TokenCache`](crate::auth::TokenCache) so concurrent
+/// queries share a single refresh.
+pub struct RefreshTokenRefresher {
+    flow: DeviceCodeFlow,
+    refresh_token: Mutex<String>,
+}
+
+impl RefreshTokenRefresher {
+    /// Refresh with `refresh_token` against the tenant of `flow`
+    pub fn new(flow: DeviceCodeFlow, refresh_token: impl Into<String>) -> Self {
+        Self {
+            flow,
+            refresh_token: Mutex::new(refresh_token.into()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, String> {
+        self.refresh_token
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl TokenRefresher for RefreshTokenRefresher {
+    async fn refresh(&self) -> Result<AccessToken> {
+        let refresh_token = self.lock().clone();
+        let token = self.flow.refresh(&refresh_token).await?;
+        if let Some(rotated) = &token.refresh_token {
+            *self.lock() = rotated.clone();
+        }
+        Ok(token.into())
+    }
+}
+
+fn form_body(params: &[(&str, &str)]) -> String {
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(feature = "keyring")]
+impl DeviceCodeFlow {
+    // Keyring entry holding the refresh token, next to the access token entry
+    const KEYRING_PROVIDER: &str = "azure-refresh";
+
+    /// Store the refresh token of `token` in the OS keyring for `profile`
+    pub fn save_to_keyring(token: &AzureToken, profile: &str) -> Result<()> {
+        let refresh_token = token.refresh_token.as_deref().ok_or_else(|| {
+            CarbemError::Auth("the sign-in did not return a refresh token".to_string())
+        })?;
+        crate::credentials::store_credential(Self::KEYRING_PROVIDER, profile, refresh_token)
+    }
+
+    /// Remove the refresh token stored for `profile`
+    pub fn delete_from_keyring(profile: &str) -> Result<()> {
+        crate::credentials::delete_credential(Self::KEYRING_PROVIDER, profile)
+    }
+
+    /// Refresh the token stored for `profile`, storing the rotated refresh token
+    pub async fn refresh_from_keyring(&self, profile: &str) -> Result<AzureToken> {
+        let refresh_token = crate::credentials::load_credential(Self::KEYRING_PROVIDER, profile)?;
+        let token = self.refresh(&refresh_token).await?;
+        if token.refresh_token.is_some() {
+            Self::save_to_keyring(&token, profile)?;
+        }
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_outcomes() {
+        let outcome =
+            |json: &str| PollOutcome::from(serde_json::from_str::<TokenError>(json).unwrap());
+
+        assert_eq!(
+            outcome(r#"{"error": "authorization_pending"}"#),
+            PollOutcome::Pending
+        );
+        assert_eq!(outcome(r#"{"error": "slow_down"}"#), PollOutcome::SlowDown);
+        assert_eq!(
+            outcome(r#"{"error": "invalid_grant", "error_description": "AADSTS70000: bad grant"}"#),
+            PollOutcome::Failed("AADSTS70000: bad grant".to_string())
+        );
+        assert!(matches!(
+            outcome(r#"{"error": "expired_token"}"#),
+            PollOutcome::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_endpoints_and_form_body() {
+        let flow = DeviceCodeFlow::new("contoso.onmicrosoft.com")
+            .with_authority_url("https://login.microsoftonline.us/");
+        assert_eq!(
+            flow.endpoint("devicecode"),
+            "https://login.microsoftonline.us/contoso.onmicrosoft.com/oauth2/v2.0/devicecode"
+        );
+        assert_eq!(
+            form_body(&[("client_id", "abc"), ("scope", MANAGEMENT_SCOPE)]),
+            "client_id=abc&scope=https%3A%2F%2Fmanagement.azure.com%2F.default%20offline_access"
+        );
+
+        let challenge: DeviceCodeChallenge = serde_json::from_str(
+            r#"{"user_code": "ABCD1234", "device_code": "secret", "verification_uri": "https://microsoft.com/devicelogin", "expires_in": 900, "message": "To sign in..."}"#,
+        )
+        .unwrap();
+        assert_eq!(challenge.interval, 5);
+        assert_eq!(challenge.device_code, "secret");
+    }
+}
@@ -1,8 +1,11 @@
 pub mod client;
+pub mod device_code;
 pub mod models;
 
 // Limit export to what is necessary
 pub use client::AzureProvider;
+pub use device_code::{AzureToken, DeviceCodeChallenge, DeviceCodeFlow, RefreshTokenRefresher};
 pub use models::{
-    AzureCarbonScope, AzureConfig, AzureQueryConfig, AzureReportType, AzureSortDirection,
+    AzureCarbonScope, AzureCategoryType, AzureConfig, AzureQueryConfig, AzureReportType,
+    AzureSortDirection, AzureSubscription,
 };
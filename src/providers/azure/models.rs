@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::schema::{FieldDefault, FieldSpec};
+
 // ============================================================================
 // Generic structs
 // ============================================================================
@@ -12,7 +14,7 @@ pub struct AzureDateRange {
 }
 
 // Azure report types
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "PascalCase")]
 pub enum AzureReportType {
     OverallSummaryReport,
@@ -36,7 +38,10 @@ impl AzureReportType {
 }
 
 // Azure carbon scopes
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Only Scope1, Scope2 and Scope3 are emission scopes accepted by the API.
+// Location and Service are kept for compatibility and rejected by validation:
+// use AzureCategoryType to break emissions down by location instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum AzureCarbonScope {
     Scope1,
@@ -56,6 +61,37 @@ impl AzureCarbonScope {
             AzureCarbonScope::Service => "Service",
         }
     }
+
+    // Whether the API accepts this value in carbonScopeList
+    pub fn is_emission_scope(&self) -> bool {
+        matches!(
+            self,
+            AzureCarbonScope::Scope1 | AzureCarbonScope::Scope2 | AzureCarbonScope::Scope3
+        )
+    }
+}
+
+// Azure category types used to break down top items and item details reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum AzureCategoryType {
+    Location,
+    Resource,
+    ResourceGroup,
+    ResourceType,
+    Subscription,
+}
+
+impl AzureCategoryType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AzureCategoryType::Location => "Location",
+            AzureCategoryType::Resource => "Resource",
+            AzureCategoryType::ResourceGroup => "ResourceGroup",
+            AzureCategoryType::ResourceType => "ResourceType",
+            AzureCategoryType::Subscription => "Subscription",
+        }
+    }
 }
 
 // Azure sort direction
@@ -79,28 +115,40 @@ impl AzureSortDirection {
 // Provider Configuration Types
 // ============================================================================
 // Configuration for Azure provider
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct AzureConfig {
     pub access_token: String,
 }
 
+// Never expose the access token in debug output
+impl std::fmt::Debug for AzureConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AzureConfig")
+            .field("access_token", &crate::redact::REDACTED)
+            .finish()
+    }
+}
+
 // ============================================================================
 // Query Configuration Types
 // ============================================================================
 
 // Azure query configuration for Carbon Emissions API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AzureQueryConfig {
     // Report type for Azure Carbon Emissions API
-    pub report_type: AzureReportType,
+    // Unset, it takes the client's query defaults, then MonthlySummaryReport
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report_type: Option<AzureReportType>,
 
-    // Carbon scope list for emissions calculation
+    // Carbon scope list for emissions calculation (Scope1, Scope2 and/or Scope3)
+    // Unset, it takes the client's query defaults, then all three scopes
     #[serde(skip_serializing_if = "Option::is_none")]
     pub carbon_scope_list: Option<Vec<AzureCarbonScope>>,
 
-    // Category type for certain report types
+    // Category used to break down top items and item details reports
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub category_type: Option<String>,
+    pub category_type: Option<AzureCategoryType>,
 
     // Order by field for ItemDetailsReport
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -120,7 +168,14 @@ pub struct AzureQueryConfig {
 
     // Mandatory subscription list - different from location_list
     // Format: List of subscription IDs (e.g., ["sub-id-1", "sub-id-2"])
-    pub subscription_list: Vec<String>,
+    // May be left unset when all_subscriptions is set
+    // Unset fields take the client's query defaults, see ProviderQueryConfig::merged_over
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_list: Option<Vec<String>>,
+
+    // Query every subscription the credentials can read, discovered at query time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub all_subscriptions: Option<bool>,
 
     // Optional filters - applicable to all report types
 
@@ -137,38 +192,39 @@ pub struct AzureQueryConfig {
     pub skip_token: Option<String>,
 }
 
-impl Default for AzureQueryConfig {
-    fn default() -> Self {
-        Self {
-            report_type: AzureReportType::default(),
-            subscription_list: vec![],
-            carbon_scope_list: Some(vec![
-                AzureCarbonScope::Scope1,
-                AzureCarbonScope::Scope2,
-                AzureCarbonScope::Scope3,
-            ]),
-            category_type: None,
-            order_by: None,
-            page_size: None,
-            sort_direction: None,
-            top_items: None,
-            resource_group_url_list: None,
-            resource_type_list: None,
-            skip_token: None,
-        }
+impl AzureQueryConfig {
+    // Report type to request, MonthlySummaryReport when not set
+    pub fn report_type(&self) -> AzureReportType {
+        self.report_type.unwrap_or_default()
     }
-}
 
-impl AzureQueryConfig {
     // Validates that all required fields for the specified report type are present
     pub fn validate(&self) -> Result<(), String> {
         // Validate mandatory subscription_list
-        if self.subscription_list.is_empty() {
-            return Err("subscription_list is required and cannot be empty".to_string());
+        let no_subscriptions = self.subscription_list.as_ref().is_none_or(Vec::is_empty);
+        if no_subscriptions && self.all_subscriptions != Some(true) {
+            return Err(
+                "subscription_list is required and cannot be empty unless all_subscriptions is set"
+                    .to_string(),
+            );
         }
 
-        // report_type is now mandatory (not Option), so it's always present
-        match &self.report_type {
+        // Validate the carbon scope selection when provided
+        if let Some(scopes) = &self.carbon_scope_list {
+            if scopes.is_empty() {
+                return Err(
+                    "carbon_scope_list cannot be empty, omit it to include all scopes".to_string(),
+                );
+            }
+            if let Some(scope) = scopes.iter().find(|s| !s.is_emission_scope()) {
+                return Err(format!(
+                    "carbon_scope_list only accepts Scope1, Scope2 and Scope3, got {} (use category_type to break down emissions by location)",
+                    scope.as_str()
+                ));
+            }
+        }
+
+        match self.report_type() {
             AzureReportType::ItemDetailsReport => {
                 // ItemDetailsReport requires: category_type, order_by, page_size, sort_direction
                 if self.category_type.is_none() {
@@ -311,4 +367,98 @@ pub struct AzureCarbonEmissionReportResponse {
     #[serde(default)]
     pub(super) subscription_access_decision_list: Option<Vec<AzureSubscriptionAccessDecision>>,
     pub(super) value: Vec<AzureEmissionData>,
+    // Pagination token for ItemDetailsReport, present when more pages are available
+    #[serde(default)]
+    pub(super) skip_token: Option<String>,
 }
+
+// Permissions granted to the caller on a scope, from the Authorization API
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AzurePermission {
+    #[serde(default)]
+    pub(super) actions: Vec<String>,
+    #[serde(default)]
+    pub(super) not_actions: Vec<String>,
+}
+
+// Azure Authorization API response listing the caller permissions
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzurePermissionListResponse {
+    #[serde(default)]
+    pub(super) value: Vec<AzurePermission>,
+}
+
+/// A subscription visible to the credentials, from the ARM subscriptions API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureSubscription {
+    /// Subscription ID (GUID)
+    pub subscription_id: String,
+
+    /// Name shown in the Azure portal
+    #[serde(default)]
+    pub display_name: String,
+
+    /// Tenant the subscription belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+
+    /// Subscription state, e.g. `Enabled`, `Warned` or `Disabled`
+    #[serde(default)]
+    pub state: String,
+}
+
+impl AzureSubscription {
+    /// Whether emissions can still be reported for the subscription
+    pub fn is_active(&self) -> bool {
+        !matches!(self.state.as_str(), "Disabled" | "Deleted")
+    }
+}
+
+// One page of the ARM subscriptions API response
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureSubscriptionListResponse {
+    #[serde(default)]
+    pub(super) value: Vec<AzureSubscription>,
+    pub(super) next_link: Option<String>,
+}
+
+// ============================================================================
+// Expected response schema (used for drift detection)
+// ============================================================================
+
+// Top-level fields of the carbon emission reports response
+pub(super) const AZURE_RESPONSE_FIELDS: &[FieldSpec] = &[
+    FieldSpec::required("value", FieldDefault::EmptyArray),
+    FieldSpec::optional("subscriptionAccessDecisionList"),
+    FieldSpec::optional("skipToken"),
+];
+
+// Fields of an emission data row, across all report types
+pub(super) const AZURE_EMISSION_DATA_FIELDS: &[FieldSpec] = &[
+    FieldSpec::required("dataType", FieldDefault::EmptyString),
+    FieldSpec::required("latestMonthEmissions", FieldDefault::Zero),
+    FieldSpec::required("previousMonthEmissions", FieldDefault::Zero),
+    FieldSpec::required("monthOverMonthEmissionsChangeRatio", FieldDefault::Zero),
+    FieldSpec::required("monthlyEmissionsChangeValue", FieldDefault::Zero),
+    FieldSpec::optional("date"),
+    FieldSpec::optional("carbonIntensity"),
+    FieldSpec::optional("itemName"),
+    FieldSpec::optional("categoryType"),
+    // Item details fields, documented but not mapped yet
+    FieldSpec::optional("subscriptionId"),
+    FieldSpec::optional("resourceGroup"),
+    FieldSpec::optional("resourceGroupUrl"),
+    FieldSpec::optional("resourceId"),
+    FieldSpec::optional("resourceType"),
+    FieldSpec::optional("location"),
+];
+
+// Fields of a subscription access decision
+pub(super) const AZURE_ACCESS_DECISION_FIELDS: &[FieldSpec] = &[
+    FieldSpec::required("subscriptionId", FieldDefault::EmptyString),
+    FieldSpec::required("decision", FieldDefault::EmptyString),
+    FieldSpec::optional("denialReason"),
+];
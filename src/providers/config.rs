@@ -1,6 +1,8 @@
+use crate::error::{CarbemError, Result};
 use crate::providers::azure::AzureQueryConfig;
 use crate::providers::ibm::IbmQueryConfig;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 /// Provider-specific configuration enum
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,3 +16,147 @@ pub enum ProviderQueryConfig {
     #[serde(rename = "ibm")]
     Ibm(IbmQueryConfig),
 }
+
+impl ProviderQueryConfig {
+    /// Name of the provider the configuration is for, e.g. `ibm`
+    pub fn provider(&self) -> &'static str {
+        match self {
+            ProviderQueryConfig::Azure(_) => "azure",
+            ProviderQueryConfig::Ibm(_) => "ibm",
+        }
+    }
+
+    /// This configuration merged field by field over `defaults`
+    ///
+    /// Unset fields of `self` (`None`, or absent from the JSON it was parsed
+    /// from) keep the value of `defaults`, the others replace it, explicit
+    /// `false`, `""` and `[]` included, so a partial configuration only sets
+    /// what it gives. Configurations of different providers are not merged:
+    /// `self` is returned as is.
+    pub fn merged_over(&self, defaults: &ProviderQueryConfig) -> Result<ProviderQueryConfig> {
+        if self.provider() != defaults.provider() {
+            return Ok(self.clone());
+        }
+        let mut merged = serde_json::to_value(defaults)?;
+        merge_json(&mut merged, serde_json::to_value(self)?);
+        serde_json::from_value(merged).map_err(|e| {
+            CarbemError::Config(format!(
+                "Invalid {} configuration after merging query defaults: {}",
+                self.provider(),
+                e
+            ))
+        })
+    }
+}
+
+// Merge `overrides` into `base`, recursing into objects and skipping unset values
+fn merge_json(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (_, Value::Null) => {}
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::azure::{AzureCarbonScope, AzureReportType};
+    use crate::providers::ibm::IbmGroupBy;
+
+    #[test]
+    fn test_partial_config_merges_over_defaults() {
+        let defaults = ProviderQueryConfig::Ibm(IbmQueryConfig {
+            enterprise_id: Some("e1".to_string()),
+            limit: Some(100),
+            ..Default::default()
+        });
+        let partial: ProviderQueryConfig = serde_json::from_str(
+            r#"{"provider": "ibm", "config": {"group_by": "service", "offset": 100}}"#,
+        )
+        .unwrap();
+
+        let ProviderQueryConfig::Ibm(merged) = partial.merged_over(&defaults).unwrap() else {
+            panic!("merged an IBM configuration into another provider's");
+        };
+        assert_eq!(merged.enterprise_id.as_deref(), Some("e1"));
+        assert!(matches!(merged.group_by, Some(IbmGroupBy::Service)));
+        assert_eq!(merged.limit, Some(100));
+        assert_eq!(merged.offset, Some(100));
+
+        // The query wins over the defaults
+        let other = ProviderQueryConfig::Ibm(IbmQueryConfig {
+            enterprise_id: Some("e2".to_string()),
+            ..Default::default()
+        });
+        let ProviderQueryConfig::Ibm(merged) = other.merged_over(&defaults).unwrap() else {
+            panic!("merged an IBM configuration into another provider's");
+        };
+        assert_eq!(merged.enterprise_id.as_deref(), Some("e2"));
+
+        let azure = ProviderQueryConfig::Azure(AzureQueryConfig::default());
+        assert_eq!(azure.merged_over(&defaults).unwrap().provider(), "azure");
+    }
+
+    #[test]
+    fn test_explicit_false_overrides_default() {
+        let defaults = ProviderQueryConfig::Azure(AzureQueryConfig {
+            all_subscriptions: Some(true),
+            ..Default::default()
+        });
+        let partial: ProviderQueryConfig = serde_json::from_str(
+            r#"{"provider": "azure", "config": {"all_subscriptions": false, "subscription_list": ["s1"]}}"#,
+        )
+        .unwrap();
+        let ProviderQueryConfig::Azure(merged) = partial.merged_over(&defaults).unwrap() else {
+            panic!("merged an Azure configuration into another provider's");
+        };
+        assert_eq!(merged.all_subscriptions, Some(false));
+        assert_eq!(merged.subscription_list, Some(vec!["s1".to_string()]));
+
+        // Absent, the default applies
+        let partial: ProviderQueryConfig =
+            serde_json::from_str(r#"{"provider": "azure", "config": {}}"#).unwrap();
+        let ProviderQueryConfig::Azure(merged) = partial.merged_over(&defaults).unwrap() else {
+            panic!("merged an Azure configuration into another provider's");
+        };
+        assert_eq!(merged.all_subscriptions, Some(true));
+    }
+
+    #[test]
+    fn test_unset_report_type_and_scopes_keep_defaults() {
+        let defaults = ProviderQueryConfig::Azure(AzureQueryConfig {
+            report_type: Some(AzureReportType::ItemDetailsReport),
+            carbon_scope_list: Some(vec![AzureCarbonScope::Scope1]),
+            ..Default::default()
+        });
+        let partial = ProviderQueryConfig::Azure(AzureQueryConfig {
+            subscription_list: Some(vec!["s1".to_string()]),
+            ..Default::default()
+        });
+        let ProviderQueryConfig::Azure(merged) = partial.merged_over(&defaults).unwrap() else {
+            panic!("merged an Azure configuration into another provider's");
+        };
+        assert_eq!(merged.report_type(), AzureReportType::ItemDetailsReport);
+        assert_eq!(
+            merged.carbon_scope_list,
+            Some(vec![AzureCarbonScope::Scope1])
+        );
+        assert_eq!(merged.subscription_list, Some(vec!["s1".to_string()]));
+
+        // Without defaults, the monthly summary of all scopes is requested
+        assert_eq!(
+            AzureQueryConfig::default().report_type(),
+            AzureReportType::MonthlySummaryReport
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Demo provider answering queries from a bundled sample dataset
+//!
+//! [`DemoProvider`] needs no credentials and sends no request: it answers
+//! any query with realistic monthly emissions built from the series in
+//! `dataset.json`, so the query, aggregation and export pipeline can be tried
+//! before having cloud access. Values are deterministic: a monthly baseline
+//! per region and service, a seasonal swing, a slow downward trend and a
+//! small fixed jitter.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::sync::LazyLock;
+
+use chrono::{Datelike, Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::audit::query_hash;
+use crate::error::Result;
+use crate::models::{
+    CarbonEmission, EmissionMetadata, EmissionQuery, EmissionResult, Lineage, PlannedRequest,
+    TimePeriod, next_month_start,
+};
+use crate::providers::CarbonProvider;
+
+// Endpoint recorded in the lineage of demo emissions
+const DEMO_ENDPOINT: &str = "demo dataset.json";
+
+// Months are counted from this year to compute the trend
+const TREND_ORIGIN_YEAR: i32 = 2023;
+
+// Monthly reduction of emissions, compounded
+const MONTHLY_TREND: f64 = 0.004;
+
+// Relative amplitude of the seasonal swing, highest in January
+const SEASONAL_AMPLITUDE: f64 = 0.08;
+
+// One region and service of the sample dataset
+#[derive(Debug, Deserialize)]
+struct DemoSeries {
+    provider: String,
+    region: String,
+    service: String,
+    monthly_kg_co2eq: f64,
+    grid_carbon_intensity: f64,
+    renewable_percentage: f64,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+static DATASET: LazyLock<Vec<DemoSeries>> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("dataset.json")).expect("bundled demo dataset is valid")
+});
+
+/// Provider serving the bundled sample dataset under the name of a real provider
+#[derive(Debug, Clone)]
+pub struct DemoProvider {
+    name: &'static str,
+}
+
+impl DemoProvider {
+    /// Demo provider answering queries for `name`, e.g. `azure` or `ibm`
+    pub fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+
+    /// Names of the providers covered by the sample dataset
+    pub fn provider_names() -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = Vec::new();
+        for name in ["azure", "ibm"] {
+            if DATASET.iter().any(|series| series.provider == name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    fn emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        let aligned = query
+            .time_period
+            .align_in(query.date_alignment, &query.timezone)?;
+
+        let series: Vec<(usize, &DemoSeries)> = DATASET
+            .iter()
+            .enumerate()
+            .filter(|(_, series)| series.provider == self.name)
+            .filter(|(_, series)| {
+                query.regions.is_empty() || query.regions.contains(&series.region)
+            })
+            .filter(|(_, series)| {
+                query
+                    .services
+                    .as_ref()
+                    .is_none_or(|services| services.contains(&series.service))
+            })
+            .filter(|(_, series)| {
+                query
+                    .tag_filters
+                    .iter()
+                    .all(|filter| filter.matches_tags(&series.tags))
+            })
+            .collect();
+
+        // No call is made: trace the emissions to the dataset and the query
+        let lineage = Lineage {
+            endpoint: DEMO_ENDPOINT.to_string(),
+            request_hash: query_hash(query),
+            retrieved_at: Utc::now(),
+            carbem_version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+
+        let mut emissions = Vec::new();
+        let mut month = aligned.start;
+        while month <= aligned.end {
+            let next_month = next_month_start(month, &query.timezone);
+            let local = query.timezone.local_date(month);
+            let index = (local.year() - TREND_ORIGIN_YEAR) * 12 + local.month0() as i32;
+
+            for (position, series) in &series {
+                let seasonal =
+                    1.0 + SEASONAL_AMPLITUDE * (2.0 * PI * local.month0() as f64 / 12.0).cos();
+                let trend = (1.0 - MONTHLY_TREND).powi(index);
+                let jitter =
+                    ((index * 7 + *position as i32 * 13).rem_euclid(11) as f64 - 5.0) / 100.0;
+                let kg = round3(series.monthly_kg_co2eq * seasonal * trend * (1.0 + jitter));
+
+                emissions.push(CarbonEmission {
+                    provider: self.name.to_string(),
+                    region: series.region.clone(),
+                    service: Some(series.service.clone()),
+                    service_category: None,
+                    lineage: Some(lineage.clone()),
+                    emissions_kg_co2eq: kg,
+                    time_period: TimePeriod {
+                        start: month,
+                        end: next_month - Duration::seconds(1),
+                    },
+                    metadata: Some(EmissionMetadata {
+                        energy_kwh: Some(round3(kg * 1000.0 / series.grid_carbon_intensity)),
+                        grid_carbon_intensity: Some(series.grid_carbon_intensity),
+                        renewable_percentage: Some(series.renewable_percentage),
+                        date_alignment: Some(query.date_alignment),
+                        provider_data: Some(json!({ "demo": true })),
+                        tags: series.tags.clone(),
+                        estimated: Some(true),
+                    }),
+                });
+            }
+            month = next_month;
+        }
+        Ok(emissions)
+    }
+}
+
+fn round3(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+impl CarbonProvider for DemoProvider {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn get_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        self.emissions(query)
+    }
+
+    async fn get_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        // Nothing would be sent, so a dry run plans no request
+        let emissions = if query.dry_run {
+            Vec::new()
+        } else {
+            self.emissions(query)?
+        };
+        Ok(EmissionResult {
+            emissions,
+            raw_responses: Vec::new(),
+            planned_requests: Vec::new(),
+        })
+    }
+
+    fn is_configured(&self) -> bool {
+        true
+    }
+
+    fn filters_tags(&self) -> bool {
+        true
+    }
+
+    fn plan_requests(&self, _query: &EmissionQuery) -> Result<Vec<PlannedRequest>> {
+        Ok(Vec::new())
+    }
+
+    fn expected_pages(&self, _query: &EmissionQuery) -> Option<u64> {
+        Some(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DateAlignment;
+    use chrono::TimeZone;
+
+    fn query(provider: &str, regions: &[&str]) -> EmissionQuery {
+        EmissionQuery {
+            provider: provider.to_string(),
+            regions: regions.iter().map(|r| r.to_string()).collect(),
+            time_period: TimePeriod {
+                start: Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap(),
+                end: Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap(),
+            },
+            services: None,
+            resources: None,
+            provider_config: None,
+            raw_response: Default::default(),
+            date_alignment: DateAlignment::ExpandToFullMonths,
+            timezone: Default::default(),
+            dry_run: false,
+            route: Vec::new(),
+            tag_filters: Vec::new(),
+            strict: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_demo_emissions() {
+        let provider = DemoProvider::new("azure");
+        let emissions = provider
+            .get_emissions(&query("azure", &["westeurope"]))
+            .await
+            .unwrap();
+
+        // Three months of the three westeurope services
+        assert_eq!(emissions.len(), 9);
+        assert!(emissions.iter().all(|e| e.region == "westeurope"));
+        assert_eq!(
+            emissions[0].time_period.end,
+            Utc.with_ymd_and_hms(2024, 1, 31, 23, 59, 59).unwrap()
+        );
+        assert!(emissions.iter().all(|e| e.emissions_kg_co2eq > 0.0));
+
+        // Same query, same values
+        let again = provider
+            .get_emissions(&query("azure", &["westeurope"]))
+            .await
+            .unwrap();
+        assert_eq!(emissions[0].emissions_kg_co2eq, again[0].emissions_kg_co2eq);
+
+        let mut services_query = query("ibm", &[]);
+        services_query.services = Some(vec!["Virtual Server for VPC".to_string()]);
+        let ibm = DemoProvider::new("ibm")
+            .get_emissions(&services_query)
+            .await
+            .unwrap();
+        assert_eq!(ibm.len(), 6);
+        assert_eq!(DemoProvider::provider_names(), vec!["azure", "ibm"]);
+    }
+}
@@ -1,13 +1,23 @@
-use async_trait::async_trait;
+use std::sync::Arc;
 
+use crate::capture::{CapturedExchange, ExchangeLog};
 use crate::error::{CarbemError, Result};
-use crate::models::{CarbonEmission, EmissionMetadata, EmissionQuery, TimePeriod};
+use crate::logging::{debug, warn};
+use crate::models::{
+    CarbonEmission, ConversionIssue, EmissionMetadata, EmissionQuery, EmissionResult, Lineage,
+    PlannedRequest, QueryTimezone, RawResponseMode, TimePeriod,
+};
+use crate::progress;
 use crate::providers::CarbonProvider;
 use crate::providers::config::ProviderQueryConfig;
+use crate::providers::pager::{IbmPager, PageSource};
+use crate::redact::Redactor;
+use crate::schema::{self, SchemaLog, SchemaWarning, inspect_object};
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
 
 use chrono::{Datelike, NaiveDate, TimeZone, Utc};
 use reqwest::{
-    Client,
+    Method,
     header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue},
 };
 
@@ -16,24 +26,94 @@ use super::models::*;
 // IBM Carbon Calculator API base URL
 const IBM_CARBON_API_BASE_URL: &str = "https://api.carbon-calculator.cloud.ibm.com";
 const IBM_API_VERSION: &str = "v1";
+// Results per page when the query sets no limit
+const IBM_DEFAULT_LIMIT: i32 = 10;
+
+// IBM Cloud Enterprise Management API base URL
+const IBM_ENTERPRISE_API_BASE_URL: &str = "https://enterprise.cloud.ibm.com";
 
 // IBM Cloud provider
 #[derive(Debug, Clone)]
 pub struct IbmProvider {
     config: IbmConfig,
-    http_client: Client,
+    transport: Arc<dyn Transport>,
+    lenient_parsing: bool,
+    schema_log: SchemaLog,
+    exchange_log: ExchangeLog,
 }
 
 impl IbmProvider {
     // Create a new IBM provider instance with configuration
     pub fn new(config: IbmConfig) -> Result<Self> {
-        let http_client = Client::new();
         Ok(Self {
             config,
-            http_client,
+            transport: Arc::new(ReqwestTransport::default()),
+            lenient_parsing: false,
+            schema_log: SchemaLog::default(),
+            exchange_log: ExchangeLog::default(),
         })
     }
 
+    /// Send requests through `transport` instead of the default reqwest client
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    // Parse a carbon emissions response, recording schema drift before deserializing
+    fn parse_response(&self, body: &str) -> Result<IbmCarbonEmissionResponse> {
+        let mut value = schema::parse_body("ibm", body)?;
+        let mut warnings = Vec::new();
+        let lenient = self.lenient_parsing;
+
+        inspect_object(
+            "ibm",
+            "",
+            &mut value,
+            IBM_RESPONSE_FIELDS,
+            lenient,
+            &mut warnings,
+        );
+        if let Some(rows) = value
+            .get_mut("carbon_emissions")
+            .and_then(|v| v.as_array_mut())
+        {
+            for row in rows {
+                inspect_object(
+                    "ibm",
+                    "carbon_emissions[]",
+                    row,
+                    IBM_EMISSION_DATA_FIELDS,
+                    lenient,
+                    &mut warnings,
+                );
+                if let Some(month) = row.get_mut("month") {
+                    inspect_object(
+                        "ibm",
+                        "carbon_emissions[].month",
+                        month,
+                        IBM_MONTH_FIELDS,
+                        lenient,
+                        &mut warnings,
+                    );
+                }
+                if let Some(group_by) = row.get_mut("group_by") {
+                    inspect_object(
+                        "ibm",
+                        "carbon_emissions[].group_by",
+                        group_by,
+                        IBM_GROUP_BY_FIELDS,
+                        lenient,
+                        &mut warnings,
+                    );
+                }
+            }
+        }
+
+        self.schema_log.record(warnings);
+        schema::deserialize("ibm", value)
+    }
+
     // Convert EmissionQuery to IBM Carbon API request
     fn convert_emission_query_to_ibm_request(
         &self,
@@ -58,11 +138,14 @@ impl IbmProvider {
         ibm_config.validate().map_err(CarbemError::Config)?;
 
         // Convert time_period to month filters (format: "gte:2023-01", "lte:2023-03")
-        let month_filters = self.build_month_filters(&query.time_period);
+        let aligned_period = query
+            .time_period
+            .align_in(query.date_alignment, &query.timezone)?;
+        let month_filters = self.build_month_filters(&aligned_period, &query.timezone);
 
         // Build the request
         Ok(IbmCarbonEmissionRequest {
-            enterprise_id: ibm_config.enterprise_id.clone(),
+            enterprise_id: ibm_config.enterprise_id.clone().unwrap_or_default(),
             month: if month_filters.is_empty() {
                 None
             } else {
@@ -81,21 +164,42 @@ impl IbmProvider {
         })
     }
 
-    // Build month filters from time period
-    fn build_month_filters(&self, time_period: &TimePeriod) -> Vec<String> {
+    // Build month filters from time period, using the months of the query timezone
+    fn build_month_filters(
+        &self,
+        time_period: &TimePeriod,
+        timezone: &QueryTimezone,
+    ) -> Vec<String> {
         let mut filters = Vec::new();
 
         // Start month filter (gte:YYYY-MM)
-        let start_month = time_period.start.format("%Y-%m").to_string();
+        let start_month = timezone
+            .local_date(time_period.start)
+            .format("%Y-%m")
+            .to_string();
         filters.push(format!("gte:{}", start_month));
 
         // End month filter (lte:YYYY-MM)
-        let end_month = time_period.end.format("%Y-%m").to_string();
+        let end_month = timezone
+            .local_date(time_period.end)
+            .format("%Y-%m")
+            .to_string();
         filters.push(format!("lte:{}", end_month));
 
         filters
     }
 
+    // Redactor aware of the API key and the enterprise identifiers of a request
+    fn redactor(&self, request: &IbmCarbonEmissionRequest) -> Redactor {
+        let redactor = Redactor::new()
+            .with_secret(self.config.api_key.clone())
+            .with_secret(request.enterprise_id.clone());
+        match &request.enterprise_account_id {
+            Some(account_id) => redactor.with_secret(account_id.clone()),
+            None => redactor,
+        }
+    }
+
     // Build authorization headers for IBM API requests
     fn build_headers(&self) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
@@ -173,16 +277,315 @@ impl IbmProvider {
         format!("{}?{}", base_url, query_params.join("&"))
     }
 
-    // Convert IBM emission data to carbem CarbonEmission
+    /// Account groups and accounts of `enterprise_id`, following pagination
+    ///
+    /// Queries with `account_group_id` set in their [`IbmQueryConfig`] use this
+    /// tree to request every account below the group.
+    pub async fn discover_accounts(&self, enterprise_id: &str) -> Result<IbmAccountTree> {
+        let mut nodes = self
+            .list_enterprise("account-groups", enterprise_id)
+            .await?
+            .into_iter()
+            .map(|resource| resource.into_node(IbmEnterpriseNodeKind::AccountGroup))
+            .collect::<Vec<_>>();
+        nodes.extend(
+            self.list_enterprise("accounts", enterprise_id)
+                .await?
+                .into_iter()
+                .map(|resource| resource.into_node(IbmEnterpriseNodeKind::Account)),
+        );
+
+        debug!("Discovered {} IBM enterprise node(s)", nodes.len());
+        Ok(IbmAccountTree { nodes })
+    }
+
+    // Enterprise Management API list endpoint for `collection` of an enterprise
+    fn enterprise_url(collection: &str, enterprise_id: &str) -> String {
+        format!(
+            "{}/{}/{}?enterprise_id={}",
+            IBM_ENTERPRISE_API_BASE_URL,
+            IBM_API_VERSION,
+            collection,
+            urlencoding::encode(enterprise_id)
+        )
+    }
+
+    // Every page of an Enterprise Management API list
+    async fn list_enterprise(
+        &self,
+        collection: &str,
+        enterprise_id: &str,
+    ) -> Result<Vec<IbmEnterpriseResource>> {
+        let redactor = Redactor::new()
+            .with_secret(self.config.api_key.clone())
+            .with_secret(enterprise_id.to_string());
+        let mut resources = Vec::new();
+        let mut next_url = Some(Self::enterprise_url(collection, enterprise_id));
+
+        while let Some(url) = next_url {
+            let response = self
+                .transport
+                .send(HttpRequest::new(Method::GET, url, self.build_headers()?))
+                .await
+                .map_err(|e| {
+                    CarbemError::Api(format!(
+                        "IBM Enterprise API request failed: {}",
+                        redactor.redact(&e.to_string())
+                    ))
+                })?;
+            if !response.is_success() {
+                return Err(CarbemError::Api(format!(
+                    "IBM Enterprise API returned error {}: {}",
+                    response.status,
+                    redactor.redact(&response.text())
+                )));
+            }
+
+            let page: IbmEnterpriseListResponse = serde_json::from_str(&response.text())?;
+            resources.extend(page.resources);
+            // Next pages are given relative to the API host
+            next_url = page.next_url.map(|next| {
+                if next.starts_with("http") {
+                    next
+                } else {
+                    format!("{}{}", IBM_ENTERPRISE_API_BASE_URL, next)
+                }
+            });
+        }
+        Ok(resources)
+    }
+
+    // Request every account below an account group, naming the account of each emission
+    async fn request_account_group(
+        &self,
+        query: &EmissionQuery,
+        ibm_request: &IbmCarbonEmissionRequest,
+        group_id: &str,
+    ) -> Result<EmissionResult> {
+        let tree = self.discover_accounts(&ibm_request.enterprise_id).await?;
+        let accounts = tree.accounts_under(group_id);
+        if accounts.is_empty() {
+            return Err(CarbemError::Config(
+                "account_group_id matches no account group with accounts in the enterprise"
+                    .to_string(),
+            ));
+        }
+
+        let mut result = EmissionResult::default();
+        let total = accounts.len() as u64;
+        for (index, account) in accounts.into_iter().enumerate() {
+            let request = IbmCarbonEmissionRequest {
+                enterprise_account_id: Some(account.id.clone()),
+                ..ibm_request.clone()
+            };
+            let (mut part, next_offset) = self.request_emissions(query, &request).await?;
+            if next_offset.is_some() {
+                warn!(
+                    "IBM returned more results than the limit for account {}; increase limit in IbmQueryConfig to retrieve them",
+                    account.name
+                );
+            }
+            for emission in &mut part.emissions {
+                if let Some(serde_json::Value::Object(data)) = emission
+                    .metadata
+                    .as_mut()
+                    .and_then(|metadata| metadata.provider_data.as_mut())
+                {
+                    data.insert(
+                        "account_name".to_string(),
+                        serde_json::Value::String(account.name.clone()),
+                    );
+                }
+            }
+            result.emissions.extend(part.emissions);
+            result.raw_responses.extend(part.raw_responses);
+            progress::chunk(
+                || format!("{} account {}", self.name(), account.name),
+                index as u64 + 1,
+                Some(total),
+            );
+        }
+        Ok(result)
+    }
+
+    // Request the emissions selected by one IBM request, with the offset of the next page if any
+    async fn request_emissions(
+        &self,
+        query: &EmissionQuery,
+        ibm_request: &IbmCarbonEmissionRequest,
+    ) -> Result<(EmissionResult, Option<i32>)> {
+        // Build URL and headers
+        let url = self.build_endpoint_url(ibm_request);
+        let headers = self.build_headers()?;
+        let redactor = self.redactor(ibm_request);
+
+        debug!(
+            "Requesting IBM carbon emissions with {} month filter(s)",
+            ibm_request.month.as_ref().map_or(0, |m| m.len())
+        );
+
+        let exchange = CapturedExchange::new(PlannedRequest::new(
+            self.name(),
+            "GET",
+            &url,
+            &headers,
+            None,
+            &redactor,
+        ));
+
+        // Make API request
+        let request = HttpRequest::new(Method::GET, &url, headers);
+        let lineage = Lineage::of_request(&request);
+        let response = match self.transport.send(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                let error = redactor.redact(&e.to_string());
+                self.exchange_log.record(exchange.with_error(error.clone()));
+                return Err(CarbemError::Api(format!(
+                    "IBM API request failed: {}",
+                    error
+                )));
+            }
+        };
+
+        // Check response status
+        if !response.is_success() {
+            let status = response.status;
+            let error_body = redactor.redact(&response.text());
+            self.exchange_log
+                .record(exchange.with_response(status.as_u16(), &error_body));
+            return Err(CarbemError::Api(format!(
+                "IBM API returned error {}: {}",
+                status, error_body
+            )));
+        }
+
+        // Parse response
+        let status = response.status.as_u16();
+        let body = response.text();
+        self.exchange_log
+            .record(exchange.with_response(status, &redactor.redact(&body)));
+
+        // Keep the response untouched by lenient parsing when it is requested raw
+        let raw_responses: Vec<serde_json::Value> = match query.raw_response {
+            RawResponseMode::None => Vec::new(),
+            RawResponseMode::Alongside | RawResponseMode::Only => {
+                vec![serde_json::from_str(&body)?]
+            }
+        };
+        if query.raw_response == RawResponseMode::Only {
+            let raw = &raw_responses[0];
+            let page_field = |field: &str| {
+                raw.get(field)
+                    .and_then(serde_json::Value::as_i64)
+                    .map(|value| value as i32)
+            };
+            let next_offset = next_offset(
+                raw.get("next").is_some_and(|next| !next.is_null()),
+                page_field("offset").or(ibm_request.offset),
+                page_field("limit").or(ibm_request.limit),
+            );
+            let result = EmissionResult {
+                emissions: Vec::new(),
+                raw_responses,
+                planned_requests: Vec::new(),
+            };
+            return Ok((result, next_offset));
+        }
+
+        let ibm_response = self.parse_response(&body).map_err(|e| match e {
+            CarbemError::Api(message) => CarbemError::Api(redactor.redact(&message)),
+            e => e,
+        })?;
+
+        let next_offset = next_offset(
+            ibm_response.next.is_some(),
+            ibm_response.offset.or(ibm_request.offset),
+            ibm_response.limit.or(ibm_request.limit),
+        );
+        let limit = ibm_response
+            .limit
+            .or(ibm_request.limit)
+            .unwrap_or(IBM_DEFAULT_LIMIT)
+            .max(1) as u64;
+        let offset = ibm_response
+            .offset
+            .or(ibm_request.offset)
+            .unwrap_or(0)
+            .max(0) as u64;
+        progress::page(
+            || self.name().to_string(),
+            offset / limit + 1,
+            ibm_response
+                .total_count
+                .map(|count| (count.max(1) as u64).div_ceil(limit)),
+        );
+
+        // Convert to CarbonEmission
+        let mut issues = Vec::new();
+        let emissions: Vec<CarbonEmission> = ibm_response
+            .carbon_emissions
+            .iter()
+            .enumerate()
+            .map(|(row, data)| {
+                self.convert_to_carbon_emission(data, &query.time_period, row, &mut issues)
+            })
+            .collect();
+        if query.strict && !issues.is_empty() {
+            return Err(CarbemError::LossyConversion(issues));
+        }
+
+        let mut result = EmissionResult {
+            emissions,
+            raw_responses,
+            planned_requests: Vec::new(),
+        };
+        result.record_lineage(&lineage);
+        Ok((result, next_offset))
+    }
+
+    /// Pager over the pages of `query`, fetched one by one on request
+    ///
+    /// Dry runs yield their planned requests as a single page. Account
+    /// group queries are rejected, page accounts one by one with
+    /// `enterprise_account_id` instead.
+    pub fn pager<'a>(&'a self, query: &EmissionQuery) -> Result<IbmPager<'a>> {
+        if account_group_id(query).is_some() {
+            return Err(CarbemError::Config(
+                "account_group_id queries cannot be paged; page each account with enterprise_account_id instead"
+                    .to_string(),
+            ));
+        }
+        Ok(IbmPager::new(self, query.clone()))
+    }
+
+    // Convert IBM emission data to carbem CarbonEmission, recording defaulted values in `issues`
     fn convert_to_carbon_emission(
         &self,
         data: &IbmEmissionData,
         query_time_period: &TimePeriod,
+        row: usize,
+        issues: &mut Vec<ConversionIssue>,
     ) -> CarbonEmission {
         // Parse the month to create time period
         let emission_time_period = self
             .parse_month_to_time_period(&data.month.value)
-            .unwrap_or_else(|| query_time_period.clone());
+            .unwrap_or_else(|| {
+                warn!(
+                    "Unparsable month '{}' in IBM row, falling back to the query time period",
+                    data.month.value
+                );
+                issues.push(ConversionIssue::new(
+                    "ibm",
+                    row,
+                    "month",
+                    format!(
+                        "unparsable month '{}', would use the query time period",
+                        data.month.value
+                    ),
+                ));
+                query_time_period.clone()
+            });
 
         // Create provider-specific data
         let mut provider_data = serde_json::Map::new();
@@ -217,7 +620,16 @@ impl IbmProvider {
                     }
                 })
             })
-            .unwrap_or_else(|| "unknown".to_string());
+            .unwrap_or_else(|| {
+                warn!("IBM row has no location information, using region 'unknown'");
+                issues.push(ConversionIssue::new(
+                    "ibm",
+                    row,
+                    "location",
+                    "no location, would use region 'unknown'".to_string(),
+                ));
+                "unknown".to_string()
+            });
 
         // Determine service from service field or group_by value
         let service = data.service.clone().or_else(|| {
@@ -237,6 +649,8 @@ impl IbmProvider {
             provider: "ibm".to_string(),
             region,
             service,
+            service_category: None,
+            lineage: None,
             // API returns grams, convert to kg
             emissions_kg_co2eq: data.carbon_emission / 1000.0,
             time_period: emission_time_period,
@@ -244,7 +658,10 @@ impl IbmProvider {
                 energy_kwh: Some(energy_kwh),
                 grid_carbon_intensity: None,
                 renewable_percentage: None,
+                date_alignment: None,
                 provider_data: Some(serde_json::Value::Object(provider_data)),
+                tags: Default::default(),
+                estimated: Some(false),
             }),
         }
     }
@@ -269,70 +686,165 @@ impl IbmProvider {
     }
 }
 
-#[async_trait]
+impl PageSource for IbmProvider {
+    // Fetch the page selected by `query`, with the query of the next page if any
+    //
+    // Account groups span one request per account and are fetched whole.
+    async fn fetch_page(
+        &self,
+        query: &EmissionQuery,
+    ) -> Result<(EmissionResult, Option<EmissionQuery>)> {
+        if query.dry_run {
+            let result = EmissionResult {
+                planned_requests: self.plan_requests(query)?,
+                ..Default::default()
+            };
+            return Ok((result, None));
+        }
+
+        // Convert query to IBM format
+        let ibm_request = self.convert_emission_query_to_ibm_request(query)?;
+
+        let (mut result, next_offset) = match account_group_id(query) {
+            Some(group_id) => {
+                let result = self
+                    .request_account_group(query, &ibm_request, group_id)
+                    .await?;
+                (result, None)
+            }
+            None => self.request_emissions(query, &ibm_request).await?,
+        };
+        result.record_date_alignment(query.date_alignment);
+
+        let next = next_offset.map(|offset| {
+            let mut next = query.clone();
+            if let Some(ProviderQueryConfig::Ibm(config)) = &mut next.provider_config {
+                config.offset = Some(offset);
+            }
+            next
+        });
+        Ok((result, next))
+    }
+}
+
 impl CarbonProvider for IbmProvider {
     fn name(&self) -> &'static str {
         "ibm"
     }
 
     async fn get_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
-        // Convert query to IBM format
-        let ibm_request = self.convert_emission_query_to_ibm_request(query)?;
+        Ok(self.get_emissions_with_raw(query).await?.emissions)
+    }
 
-        // Build URL and headers
-        let url = self.build_endpoint_url(&ibm_request);
-        let headers = self.build_headers()?;
+    async fn get_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        let (result, next) = self.fetch_page(query).await?;
+        if next.is_some() {
+            warn!(
+                "IBM returned {} results and more remain; increase limit or set offset in IbmQueryConfig, or use IbmProvider::pager, to retrieve the remaining pages",
+                result.emissions.len()
+            );
+        }
+        Ok(result)
+    }
 
-        // Make API request
-        let response = self
-            .http_client
-            .get(&url)
-            .headers(headers)
-            .send()
+    fn is_configured(&self) -> bool {
+        !self.config.api_key.is_empty()
+    }
+
+    async fn prewarm(&self) -> Result<()> {
+        // The API key is sent as is: only the connection needs warming up
+        self.transport
+            .send(HttpRequest::new(
+                Method::HEAD,
+                IBM_CARBON_API_BASE_URL,
+                HeaderMap::new(),
+            ))
             .await
-            .map_err(|e| CarbemError::Api(format!("IBM API request failed: {}", e)))?;
+            .map(|_| ())
+    }
 
-        // Check response status
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(CarbemError::Api(format!(
-                "IBM API returned error {}: {}",
-                status, error_body
-            )));
-        }
+    fn set_lenient_parsing(&mut self, lenient: bool) {
+        self.lenient_parsing = lenient;
+    }
 
-        // Parse response
-        let ibm_response: IbmCarbonEmissionResponse = response
-            .json()
-            .await
-            .map_err(|e| CarbemError::Api(format!("Failed to parse IBM API response: {}", e)))?;
+    fn set_transport(&mut self, transport: Arc<dyn Transport>) {
+        self.transport = transport;
+    }
 
-        // Convert to CarbonEmission
-        let emissions: Vec<CarbonEmission> = ibm_response
-            .carbon_emissions
-            .iter()
-            .map(|data| self.convert_to_carbon_emission(data, &query.time_period))
-            .collect();
+    fn recent_exchanges(&self) -> Vec<CapturedExchange> {
+        self.exchange_log.snapshot()
+    }
 
-        Ok(emissions)
+    fn plan_requests(&self, query: &EmissionQuery) -> Result<Vec<PlannedRequest>> {
+        let ibm_request = self.convert_emission_query_to_ibm_request(query)?;
+        let headers = self.build_headers()?;
+        let redactor = self.redactor(&ibm_request);
+
+        let mut planned = Vec::new();
+        // Accounts of the group are only known after discovery, then requested one by one
+        if account_group_id(query).is_some() {
+            for collection in ["account-groups", "accounts"] {
+                planned.push(PlannedRequest::new(
+                    self.name(),
+                    "GET",
+                    &Self::enterprise_url(collection, &ibm_request.enterprise_id),
+                    &headers,
+                    None,
+                    &redactor,
+                ));
+            }
+        }
+        planned.push(PlannedRequest::new(
+            self.name(),
+            "GET",
+            &self.build_endpoint_url(&ibm_request),
+            &headers,
+            None,
+            &redactor,
+        ));
+        Ok(planned)
     }
 
-    fn is_configured(&self) -> bool {
-        !self.config.api_key.is_empty()
+    fn expected_pages(&self, query: &EmissionQuery) -> Option<u64> {
+        let Some(ProviderQueryConfig::Ibm(config)) = &query.provider_config else {
+            return None;
+        };
+        // Rows are only predictable for one account, one per month
+        let one_account =
+            config.enterprise_account_id.is_some() && config.account_group_id.is_none();
+        if !one_account || !matches!(config.group_by, None | Some(IbmGroupBy::Month)) {
+            return None;
+        }
+        let months = query
+            .time_period
+            .month_count(query.date_alignment, &query.timezone)
+            .ok()?;
+        let limit = config.limit.unwrap_or(IBM_DEFAULT_LIMIT).max(1) as u64;
+        Some(u64::from(months).div_ceil(limit).max(1))
     }
 
-    fn clone_provider(&self) -> Box<dyn CarbonProvider + Send + Sync> {
-        Box::new(self.clone())
+    fn schema_warnings(&self) -> Vec<SchemaWarning> {
+        self.schema_log.snapshot()
+    }
+}
+
+// Offset of the page after the one at `offset`, when the response links to one
+fn next_offset(has_next: bool, offset: Option<i32>, limit: Option<i32>) -> Option<i32> {
+    has_next.then(|| offset.unwrap_or(0).max(0) + limit.unwrap_or(IBM_DEFAULT_LIMIT).max(1))
+}
+
+// Account group whose sub-tree the query covers
+fn account_group_id(query: &EmissionQuery) -> Option<&str> {
+    match &query.provider_config {
+        Some(ProviderQueryConfig::Ibm(config)) => config.account_group_id.as_deref(),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::DateAlignment;
     use chrono::TimeZone;
 
     fn create_test_config() -> IbmConfig {
@@ -355,12 +867,20 @@ mod tests {
             ]),
             resources: None,
             provider_config: Some(ProviderQueryConfig::Ibm(IbmQueryConfig {
-                enterprise_id: "x2x261x8x5x84xxxx49x4891xx077xx9".to_string(),
+                enterprise_id: Some("x2x261x8x5x84xxxx49x4891xx077xx9".to_string()),
                 group_by: Some(IbmGroupBy::Month),
                 enterprise_account_id: None,
+                account_group_id: None,
                 limit: Some(10),
                 offset: None,
             })),
+            raw_response: RawResponseMode::None,
+            date_alignment: DateAlignment::ExpandToFullMonths,
+            timezone: QueryTimezone::Utc,
+            dry_run: false,
+            route: Vec::new(),
+            tag_filters: Vec::new(),
+            strict: false,
         }
     }
 
@@ -448,9 +968,10 @@ mod tests {
         let provider = IbmProvider::new(config).unwrap();
         let mut query = create_test_emission_query();
         query.provider_config = Some(ProviderQueryConfig::Ibm(IbmQueryConfig {
-            enterprise_id: "".to_string(),
+            enterprise_id: Some("".to_string()),
             group_by: None,
             enterprise_account_id: None,
+            account_group_id: None,
             limit: None,
             offset: None,
         }));
@@ -491,6 +1012,41 @@ mod tests {
         assert!(url.contains("limit=10"));
     }
 
+    #[tokio::test]
+    async fn test_dry_run_plans_redacted_request() {
+        let provider = IbmProvider::new(create_test_config()).unwrap();
+        let mut query = create_test_emission_query();
+        query.dry_run = true;
+
+        let result = provider.get_emissions_with_raw(&query).await.unwrap();
+        assert!(result.emissions.is_empty());
+        let request = &result.planned_requests[0];
+        assert_eq!(result.planned_requests.len(), 1);
+        assert_eq!(request.method, "GET");
+        assert!(request.url.contains("month=gte%3A2023-01"));
+        assert!(!request.url.contains("x2x261x8x5x84xxxx49x4891xx077xx9"));
+        assert!(!request.headers["authorization"].contains("test-api-key"));
+        assert!(request.body.is_none());
+    }
+
+    #[test]
+    fn test_redactor_hides_credentials_and_enterprise_id() {
+        let config = create_test_config();
+        let provider = IbmProvider::new(config).unwrap();
+        let query = create_test_emission_query();
+        let ibm_request = provider
+            .convert_emission_query_to_ibm_request(&query)
+            .unwrap();
+
+        let body = "key test-api-key cannot access x2x261x8x5x84xxxx49x4891xx077xx9";
+        let redacted = provider.redactor(&ibm_request).redact(body);
+        assert_eq!(redacted, "key [REDACTED] cannot access [REDACTED]");
+
+        let debug = format!("{:?} {:?}", provider, query);
+        assert!(!debug.contains("test-api-key"));
+        assert!(!debug.contains("x2x261x8x5x84xxxx49x4891xx077xx9"));
+    }
+
     #[test]
     fn test_build_month_filters() {
         let config = create_test_config();
@@ -501,12 +1057,28 @@ mod tests {
             end: Utc.with_ymd_and_hms(2023, 3, 20, 23, 59, 59).unwrap(),
         };
 
-        let filters = provider.build_month_filters(&time_period);
+        let filters = provider.build_month_filters(&time_period, &QueryTimezone::Utc);
         assert_eq!(filters.len(), 2);
         assert_eq!(filters[0], "gte:2023-01");
         assert_eq!(filters[1], "lte:2023-03");
     }
 
+    #[test]
+    fn test_build_month_filters_in_timezone() {
+        let config = create_test_config();
+        let provider = IbmProvider::new(config).unwrap();
+
+        // 2023-01-31T23:00Z is already February in Tokyo
+        let time_period = TimePeriod {
+            start: Utc.with_ymd_and_hms(2023, 1, 31, 23, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2023, 3, 31, 16, 0, 0).unwrap(),
+        };
+
+        let tokyo: QueryTimezone = "Asia/Tokyo".parse().unwrap();
+        let filters = provider.build_month_filters(&time_period, &tokyo);
+        assert_eq!(filters, vec!["gte:2023-02", "lte:2023-04"]);
+    }
+
     #[test]
     fn test_parse_month_to_time_period() {
         let config = create_test_config();
@@ -548,10 +1120,13 @@ mod tests {
             end: Utc.with_ymd_and_hms(2023, 3, 31, 23, 59, 59).unwrap(),
         };
 
-        let emission = provider.convert_to_carbon_emission(&data, &time_period);
+        let mut issues = Vec::new();
+        let emission = provider.convert_to_carbon_emission(&data, &time_period, 0, &mut issues);
 
         assert_eq!(emission.provider, "ibm");
         assert_eq!(emission.region, "unknown"); // No location in this case
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "location");
         assert_eq!(emission.service, None);
         // 2000 grams = 2 kg
         assert_eq!(emission.emissions_kg_co2eq, 2.0);
@@ -574,6 +1149,273 @@ mod tests {
         assert_eq!(provider_data.get("group_by_value").unwrap(), "2023-01");
     }
 
+    #[tokio::test]
+    async fn test_account_group_queries_its_sub_tree() {
+        use crate::transport::HttpResponse;
+        use async_trait::async_trait;
+        use std::sync::Mutex;
+
+        #[derive(Debug, Default)]
+        struct Enterprise {
+            emission_urls: Mutex<Vec<String>>,
+        }
+
+        #[async_trait]
+        impl Transport for Enterprise {
+            async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+                let url = request.url;
+                let body = if url.contains("/v1/account-groups?") {
+                    r#"{"resources": [
+                        {"id": "g1", "name": "Research", "parent": "crn:v1:bluemix:public:enterprise::a/e1::enterprise:e1", "enterprise_path": "enterprise:e1/account-group:g1", "state": "ACTIVE"},
+                        {"id": "g2", "name": "Labs", "parent": "crn:v1:bluemix:public:enterprise::a/e1::account-group:g1", "enterprise_path": "enterprise:e1/account-group:g1/account-group:g2", "state": "ACTIVE"}
+                    ]}"#
+                } else if url.contains("next_docid") {
+                    r#"{"resources": [
+                        {"id": "a2", "name": "Billing", "parent": "crn:v1:bluemix:public:enterprise::a/e1::enterprise:e1", "enterprise_path": "enterprise:e1/account:a2", "state": "ACTIVE"}
+                    ]}"#
+                } else if url.contains("/v1/accounts?") {
+                    r#"{"resources": [
+                        {"id": "a1", "name": "Lab A", "parent": "crn:v1:bluemix:public:enterprise::a/e1::account-group:g2", "enterprise_path": "enterprise:e1/account-group:g1/account-group:g2/account:a1", "state": "ACTIVE"}
+                    ], "next_url": "/v1/accounts?enterprise_id=e1&next_docid=2"}"#
+                } else {
+                    self.emission_urls.lock().unwrap().push(url);
+                    r#"{"carbon_emissions": [{
+                        "account_id": "a1",
+                        "carbon_emission": 12.0,
+                        "energy_consumption": 30.0,
+                        "month": {"value": "2024-01"}
+                    }]}"#
+                };
+                Ok(HttpResponse::new(reqwest::StatusCode::OK, body))
+            }
+        }
+
+        let transport = Arc::new(Enterprise::default());
+        let provider = IbmProvider::new(create_test_config())
+            .unwrap()
+            .with_transport(transport.clone());
+
+        let tree = provider.discover_accounts("e1").await.unwrap();
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(tree.children("e1").len(), 2);
+        assert_eq!(tree.get("a1").unwrap().parent_id(), "g2");
+        let under_g1: Vec<&str> = tree
+            .accounts_under("g1")
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect();
+        assert_eq!(under_g1, vec!["a1"]);
+
+        let mut query = create_test_emission_query();
+        query.provider_config = Some(ProviderQueryConfig::Ibm(IbmQueryConfig {
+            enterprise_id: Some("e1".to_string()),
+            account_group_id: Some("g1".to_string()),
+            ..Default::default()
+        }));
+        let emissions = provider.get_emissions(&query).await.unwrap();
+        assert_eq!(emissions.len(), 1);
+        let provider_data = emissions[0]
+            .metadata
+            .as_ref()
+            .unwrap()
+            .provider_data
+            .as_ref()
+            .unwrap();
+        assert_eq!(provider_data["account_name"], "Lab A");
+        let urls = transport.emission_urls.lock().unwrap();
+        assert_eq!(urls.len(), 1);
+        assert!(urls[0].contains("enterprise_account_id=a1"));
+
+        // Lineage traces the emission to the request that returned it
+        let lineage = emissions[0].lineage.as_ref().unwrap();
+        assert_eq!(
+            lineage.endpoint,
+            "GET https://api.carbon-calculator.cloud.ibm.com/v1/carbon_emissions"
+        );
+        assert_eq!(
+            lineage.request_hash,
+            crate::transport::request_hash(&HttpRequest::new(
+                Method::GET,
+                &urls[0],
+                Default::default()
+            ))
+        );
+        assert_eq!(lineage.carbem_version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn test_conformance() {
+        use crate::providers::test_kit::ConformanceSuite;
+
+        let suite = ConformanceSuite::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/providers/ibm/conformance"
+        ))
+        .unwrap();
+        let provider = IbmProvider::new(create_test_config()).unwrap();
+        suite.run(&provider).await.assert_passed();
+    }
+
+    #[tokio::test]
+    async fn test_strict_query_rejects_defaulted_rows() {
+        use crate::transport::HttpResponse;
+        use async_trait::async_trait;
+
+        #[derive(Debug)]
+        struct Rows;
+
+        #[async_trait]
+        impl Transport for Rows {
+            async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+                let body = r#"{"carbon_emissions": [
+                    {"account_id": "a1", "carbon_emission": 1.0, "energy_consumption": 1.0, "month": {"value": "2024-01"}, "location": "Dallas"},
+                    {"account_id": "a1", "carbon_emission": 2.0, "energy_consumption": 2.0, "month": {"value": "Jan 2024"}}
+                ]}"#;
+                Ok(HttpResponse::new(reqwest::StatusCode::OK, body))
+            }
+        }
+
+        let provider = IbmProvider::new(create_test_config())
+            .unwrap()
+            .with_transport(Arc::new(Rows));
+        let mut query = create_test_emission_query();
+        assert_eq!(provider.get_emissions(&query).await.unwrap().len(), 2);
+
+        query.strict = true;
+        match provider.get_emissions(&query).await {
+            Err(CarbemError::LossyConversion(issues)) => {
+                let fields: Vec<(usize, &str)> = issues
+                    .iter()
+                    .map(|issue| (issue.row, issue.field.as_str()))
+                    .collect();
+                assert_eq!(fields, vec![(1, "month"), (1, "location")]);
+            }
+            other => panic!("expected a lossy conversion error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_response_schema_drift() {
+        let config = create_test_config();
+        let mut provider = IbmProvider::new(config).unwrap();
+        provider.set_lenient_parsing(true);
+
+        let body = r#"{
+            "carbon_emissions": [{
+                "account_id": "test-account",
+                "carbon_emission": 1500.0,
+                "month": {"value": "2023-02"},
+                "scope": "3"
+            }],
+            "total_count": 1
+        }"#;
+
+        let response = provider.parse_response(body).unwrap();
+        assert_eq!(response.carbon_emissions[0].energy_consumption, 0.0);
+
+        let paths: Vec<String> = provider
+            .schema_warnings()
+            .into_iter()
+            .map(|w| w.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "carbon_emissions[].scope",
+                "carbon_emissions[].energy_consumption"
+            ]
+        );
+    }
+
+    mod properties {
+        use super::*;
+        use crate::schema::strategies;
+        use crate::transport::HttpResponse;
+        use async_trait::async_trait;
+        use proptest::prelude::*;
+        use serde_json::json;
+
+        // Answers every request with the same body
+        #[derive(Debug)]
+        struct Answering(String);
+
+        #[async_trait]
+        impl Transport for Answering {
+            async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+                Ok(HttpResponse::new(reqwest::StatusCode::OK, self.0.clone()))
+            }
+        }
+
+        fn valid_row() -> serde_json::Value {
+            json!({
+                "account_id": "a1",
+                "carbon_emission": 1.5,
+                "energy_consumption": 3.0,
+                "month": {"value": "2023-02"},
+                "location": "Dallas",
+                "service": "Cloud Object Storage"
+            })
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            // Malformed responses fail with an error naming the problem, never a panic
+            #[test]
+            fn malformed_responses_are_reported(
+                rows in prop::collection::vec(
+                    strategies::mutated(valid_row(), IBM_EMISSION_DATA_FIELDS),
+                    0..4,
+                ),
+                extra in strategies::json(),
+            ) {
+                let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+                for body in [json!({"carbon_emissions": rows}).to_string(), extra.to_string()] {
+                    let provider = IbmProvider::new(create_test_config())
+                        .unwrap()
+                        .with_transport(Arc::new(Answering(body)));
+                    let query = create_test_emission_query();
+                    if let Err(e) = runtime.block_on(provider.get_emissions(&query)) {
+                        prop_assert!(matches!(e, CarbemError::Api(_)), "unexpected error: {}", e);
+                    }
+                }
+            }
+
+            // Filters survive URL encoding whatever characters they contain
+            #[test]
+            fn urls_carry_query_filters(
+                enterprise_id in ".{1,40}",
+                regions in prop::collection::vec(".{1,20}", 0..4),
+                services in prop::collection::vec(".{1,20}", 0..4),
+            ) {
+                let provider = IbmProvider::new(create_test_config()).unwrap();
+                let mut query = create_test_emission_query();
+                query.regions = regions.clone();
+                query.services = (!services.is_empty()).then(|| services.clone());
+                if let Some(ProviderQueryConfig::Ibm(config)) = &mut query.provider_config {
+                    config.enterprise_id = Some(enterprise_id.clone());
+                }
+
+                let request = provider.convert_emission_query_to_ibm_request(&query).unwrap();
+                let url = reqwest::Url::parse(&provider.build_endpoint_url(&request)).unwrap();
+                prop_assert_eq!(url.host_str(), Some("api.carbon-calculator.cloud.ibm.com"));
+                let param = |name: &str| -> Vec<String> {
+                    url.query_pairs()
+                        .filter(|(key, _)| key == name)
+                        .map(|(_, value)| value.into_owned())
+                        .collect()
+                };
+                prop_assert_eq!(param("enterprise_id"), vec![enterprise_id]);
+                prop_assert_eq!(param("month"), vec!["gte:2023-01", "lte:2023-03"]);
+                let joined = |values: Vec<String>| {
+                    if values.is_empty() { vec![] } else { vec![values.join(", ")] }
+                };
+                prop_assert_eq!(param("locations"), joined(regions));
+                prop_assert_eq!(param("services"), joined(services));
+            }
+        }
+    }
+
     #[test]
     fn test_convert_to_carbon_emission_with_location() {
         let config = create_test_config();
@@ -601,9 +1443,11 @@ mod tests {
             end: Utc.with_ymd_and_hms(2023, 2, 28, 23, 59, 59).unwrap(),
         };
 
-        let emission = provider.convert_to_carbon_emission(&data, &time_period);
+        let mut issues = Vec::new();
+        let emission = provider.convert_to_carbon_emission(&data, &time_period, 0, &mut issues);
 
         assert_eq!(emission.provider, "ibm");
+        assert!(issues.is_empty());
         assert_eq!(emission.region, "Dallas");
         assert_eq!(emission.service, Some("Cloud Object Storage".to_string()));
         assert_eq!(emission.emissions_kg_co2eq, 1.5); // 1500g = 1.5kg
@@ -3,4 +3,6 @@ pub mod models;
 
 // Limit export to what is necessary
 pub use client::IbmProvider;
-pub use models::{IbmConfig, IbmGroupBy, IbmQueryConfig};
+pub use models::{
+    IbmAccountTree, IbmConfig, IbmEnterpriseNode, IbmEnterpriseNodeKind, IbmGroupBy, IbmQueryConfig,
+};
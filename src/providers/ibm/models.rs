@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::schema::{FieldDefault, FieldSpec};
+
 // ============================================================================
 // Generic structs
 // ============================================================================
@@ -31,20 +33,31 @@ impl IbmGroupBy {
 // ============================================================================
 
 // Configuration for IBM Cloud provider (authentication)
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct IbmConfig {
     pub api_key: String,
 }
 
+// Never expose the API key in debug output
+impl std::fmt::Debug for IbmConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IbmConfig")
+            .field("api_key", &crate::redact::REDACTED)
+            .finish()
+    }
+}
+
 // ============================================================================
 // Query Configuration Types
 // ============================================================================
 
 // IBM query configuration for Carbon Calculator API
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct IbmQueryConfig {
     // Mandatory enterprise ID for IBM Cloud
-    pub enterprise_id: String,
+    // May be left unset in queries when the client's query defaults set it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enterprise_id: Option<String>,
 
     // Grouping option for results
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -55,6 +68,11 @@ pub struct IbmQueryConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enterprise_account_id: Option<String>,
 
+    // Account group whose whole sub-tree is queried, one account at a time (optional)
+    // Accounts are discovered at query time; exclusive with enterprise_account_id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_group_id: Option<String>,
+
     // Pagination limit (optional, default is 10)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<i32>,
@@ -64,12 +82,39 @@ pub struct IbmQueryConfig {
     pub offset: Option<i32>,
 }
 
+// Enterprise identifiers are redacted from debug output
+impl std::fmt::Debug for IbmQueryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IbmQueryConfig")
+            .field("enterprise_id", &crate::redact::REDACTED)
+            .field("group_by", &self.group_by)
+            .field(
+                "enterprise_account_id",
+                &self
+                    .enterprise_account_id
+                    .as_ref()
+                    .map(|_| crate::redact::REDACTED),
+            )
+            .field(
+                "account_group_id",
+                &self
+                    .account_group_id
+                    .as_ref()
+                    .map(|_| crate::redact::REDACTED),
+            )
+            .field("limit", &self.limit)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
 impl Default for IbmQueryConfig {
     fn default() -> Self {
         Self {
-            enterprise_id: String::new(),
+            enterprise_id: None,
             group_by: Some(IbmGroupBy::Month),
             enterprise_account_id: None,
+            account_group_id: None,
             limit: None,
             offset: None,
         }
@@ -80,9 +125,14 @@ impl IbmQueryConfig {
     // Validates that all required fields are present
     pub fn validate(&self) -> Result<(), String> {
         // Validate mandatory enterprise_id
-        if self.enterprise_id.is_empty() {
+        if self.enterprise_id.as_ref().is_none_or(String::is_empty) {
             return Err("enterprise_id is required and cannot be empty".to_string());
         }
+        if self.enterprise_account_id.is_some() && self.account_group_id.is_some() {
+            return Err(
+                "enterprise_account_id and account_group_id cannot be set together".to_string(),
+            );
+        }
 
         Ok(())
     }
@@ -93,7 +143,7 @@ impl IbmQueryConfig {
 // ============================================================================
 
 // IBM Carbon Calculator API query parameters
-#[derive(Debug, Clone, Serialize)]
+#[derive(Clone, Serialize)]
 pub struct IbmCarbonEmissionRequest {
     pub(super) enterprise_id: String,
 
@@ -126,6 +176,28 @@ pub struct IbmCarbonEmissionRequest {
     pub(super) offset: Option<i32>,
 }
 
+// Enterprise identifiers are redacted from debug output
+impl std::fmt::Debug for IbmCarbonEmissionRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IbmCarbonEmissionRequest")
+            .field("enterprise_id", &crate::redact::REDACTED)
+            .field("month", &self.month)
+            .field("locations", &self.locations)
+            .field("services", &self.services)
+            .field(
+                "enterprise_account_id",
+                &self
+                    .enterprise_account_id
+                    .as_ref()
+                    .map(|_| crate::redact::REDACTED),
+            )
+            .field("group_by", &self.group_by)
+            .field("limit", &self.limit)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
 // ============================================================================
 // API Response Types
 // ============================================================================
@@ -217,3 +289,165 @@ pub struct IbmCarbonEmissionResponse {
     #[serde(default)]
     pub(super) next: Option<IbmPaginationLink>,
 }
+
+// ============================================================================
+// Enterprise Management Types
+// ============================================================================
+
+/// Kind of node in an IBM Cloud enterprise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IbmEnterpriseNodeKind {
+    /// Account group, containing accounts and other account groups
+    AccountGroup,
+
+    /// Account, where resources and their emissions live
+    Account,
+}
+
+/// An account or account group of an IBM Cloud enterprise
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IbmEnterpriseNode {
+    /// Account or account group ID
+    pub id: String,
+
+    /// Display name
+    pub name: String,
+
+    /// Whether this is an account or an account group
+    pub kind: IbmEnterpriseNodeKind,
+
+    /// CRN of the parent: the enterprise or an account group
+    pub parent: String,
+
+    /// Path from the enterprise, e.g. `enterprise:e1/account-group:g1/account:a1`
+    pub enterprise_path: String,
+
+    /// State, e.g. `ACTIVE` or `SUSPENDED`
+    pub state: String,
+}
+
+impl IbmEnterpriseNode {
+    /// ID of the parent account group or enterprise, taken from its CRN
+    pub fn parent_id(&self) -> &str {
+        self.parent
+            .rsplit_once(':')
+            .map_or(self.parent.as_str(), |(_, id)| id)
+    }
+
+    /// Whether the node is `group_id` or sits below it
+    pub fn is_under(&self, group_id: &str) -> bool {
+        self.id == group_id
+            || self
+                .enterprise_path
+                .split('/')
+                .any(|segment| segment == format!("account-group:{}", group_id))
+    }
+}
+
+/// Accounts and account groups of an IBM Cloud enterprise
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IbmAccountTree {
+    /// Every account group and account, account groups first
+    pub nodes: Vec<IbmEnterpriseNode>,
+}
+
+impl IbmAccountTree {
+    /// Node with the given ID
+    pub fn get(&self, id: &str) -> Option<&IbmEnterpriseNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Direct children of an account group, or of the enterprise for its ID
+    pub fn children(&self, parent_id: &str) -> Vec<&IbmEnterpriseNode> {
+        self.nodes
+            .iter()
+            .filter(|node| node.parent_id() == parent_id)
+            .collect()
+    }
+
+    /// Accounts of the whole sub-tree below `group_id`
+    pub fn accounts_under(&self, group_id: &str) -> Vec<&IbmEnterpriseNode> {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind == IbmEnterpriseNodeKind::Account && node.is_under(group_id))
+            .collect()
+    }
+}
+
+// Account or account group as listed by the Enterprise Management API
+#[derive(Debug, Clone, Deserialize)]
+pub struct IbmEnterpriseResource {
+    pub(super) id: String,
+    #[serde(default)]
+    pub(super) name: String,
+    #[serde(default)]
+    pub(super) parent: String,
+    #[serde(default)]
+    pub(super) enterprise_path: String,
+    #[serde(default)]
+    pub(super) state: String,
+}
+
+impl IbmEnterpriseResource {
+    pub(super) fn into_node(self, kind: IbmEnterpriseNodeKind) -> IbmEnterpriseNode {
+        IbmEnterpriseNode {
+            id: self.id,
+            name: self.name,
+            kind,
+            parent: self.parent,
+            enterprise_path: self.enterprise_path,
+            state: self.state,
+        }
+    }
+}
+
+// One page of the Enterprise Management API list responses
+#[derive(Debug, Clone, Deserialize)]
+pub struct IbmEnterpriseListResponse {
+    #[serde(default)]
+    pub(super) resources: Vec<IbmEnterpriseResource>,
+    #[serde(default)]
+    pub(super) next_url: Option<String>,
+}
+
+// ============================================================================
+// Expected response schema (used for drift detection)
+// ============================================================================
+
+// Top-level fields of the carbon emissions response
+pub(super) const IBM_RESPONSE_FIELDS: &[FieldSpec] = &[
+    FieldSpec::required("carbon_emissions", FieldDefault::EmptyArray),
+    FieldSpec::optional("total_emission"),
+    FieldSpec::optional("offset"),
+    FieldSpec::optional("limit"),
+    FieldSpec::optional("total_count"),
+    FieldSpec::optional("first"),
+    FieldSpec::optional("last"),
+    FieldSpec::optional("previous"),
+    FieldSpec::optional("next"),
+];
+
+// Fields of a carbon emission data point
+pub(super) const IBM_EMISSION_DATA_FIELDS: &[FieldSpec] = &[
+    FieldSpec::required("account_id", FieldDefault::EmptyString),
+    FieldSpec::required("carbon_emission", FieldDefault::Zero),
+    FieldSpec::required("energy_consumption", FieldDefault::Zero),
+    FieldSpec::required("month", FieldDefault::EmptyObject),
+    FieldSpec::optional("group_by"),
+    FieldSpec::optional("location"),
+    FieldSpec::optional("service"),
+];
+
+// Fields of the month information
+pub(super) const IBM_MONTH_FIELDS: &[FieldSpec] = &[
+    FieldSpec::required("value", FieldDefault::EmptyString),
+    FieldSpec::optional("min"),
+    FieldSpec::optional("max"),
+];
+
+// Fields of the group by information
+pub(super) const IBM_GROUP_BY_FIELDS: &[FieldSpec] = &[
+    FieldSpec::required("type", FieldDefault::EmptyString),
+    FieldSpec::required("value", FieldDefault::EmptyString),
+];
@@ -2,25 +2,206 @@
 
 pub mod azure;
 pub mod config;
+pub mod demo;
 pub mod ibm;
+pub mod pager;
 pub mod registry;
+#[cfg(any(test, feature = "test-kit"))]
+pub mod test_kit;
 
-use crate::error::Result;
-use crate::models::{CarbonEmission, EmissionQuery};
+use crate::capture::CapturedExchange;
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, EmissionQuery, EmissionResult, PlannedRequest};
+use crate::schema::SchemaWarning;
+use crate::transport::Transport;
 use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
 
 /// Trait that all carbon emission providers must implement
-#[async_trait]
+///
+/// Query methods are native `async fn`s, so calls on a concrete provider are
+/// not boxed. The trait is not dyn-compatible: registries and clients hold
+/// providers as [`DynCarbonProvider`] trait objects, implemented for every
+/// `Clone` provider.
 pub trait CarbonProvider: Send + Sync {
     /// Get the provider name
     fn name(&self) -> &'static str;
 
     /// Query carbon emissions for the given parameters
-    async fn get_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>>;
+    fn get_emissions(
+        &self,
+        query: &EmissionQuery,
+    ) -> impl Future<Output = Result<Vec<CarbonEmission>>> + Send;
+
+    /// Query carbon emissions, also returning raw responses as set by `query.raw_response`
+    ///
+    /// Providers that cannot expose their raw responses return parsed emissions only.
+    fn get_emissions_with_raw(
+        &self,
+        query: &EmissionQuery,
+    ) -> impl Future<Output = Result<EmissionResult>> + Send {
+        async move {
+            Ok(EmissionResult {
+                emissions: self.get_emissions(query).await?,
+                raw_responses: Vec::new(),
+                planned_requests: Vec::new(),
+            })
+        }
+    }
 
     /// Check if the provider is properly configured
     fn is_configured(&self) -> bool;
 
+    /// Authenticate and open a connection ahead of the first query
+    ///
+    /// Saves the handshakes of the first query of short-lived processes, such
+    /// as serverless functions. Providers with nothing to warm up do nothing.
+    fn prewarm(&self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Whether the provider applies `query.tag_filters` itself
+    ///
+    /// Results of providers that do not are filtered by the client.
+    fn filters_tags(&self) -> bool {
+        false
+    }
+
+    /// Fill missing required response fields with defaults instead of failing
+    fn set_lenient_parsing(&mut self, _lenient: bool) {}
+
+    /// Send requests through `transport` instead of the default reqwest client
+    fn set_transport(&mut self, _transport: Arc<dyn Transport>) {}
+
+    /// Schema differences observed in this provider's responses so far
+    fn schema_warnings(&self) -> Vec<SchemaWarning> {
+        Vec::new()
+    }
+
+    /// Last requests sent by this provider and their responses, oldest first
+    fn recent_exchanges(&self) -> Vec<CapturedExchange> {
+        Vec::new()
+    }
+
+    /// Requests a query would send, in order, with credentials redacted
+    ///
+    /// Used for dry runs (`query.dry_run`); nothing is sent.
+    fn plan_requests(&self, _query: &EmissionQuery) -> Result<Vec<PlannedRequest>> {
+        Err(CarbemError::Provider(format!(
+            "provider {} does not support dry runs",
+            self.name()
+        )))
+    }
+
+    /// Result pages needed to retrieve everything `query` selects, when predictable
+    ///
+    /// A query retrieves its first page; each further page takes another
+    /// query, e.g. with an offset. `None` when the count depends on data only
+    /// the provider knows.
+    fn expected_pages(&self, _query: &EmissionQuery) -> Option<u64> {
+        None
+    }
+}
+
+/// Dyn-compatible form of [`CarbonProvider`], used to hold providers of different types
+///
+/// Implemented for every `Clone` [`CarbonProvider`]; implement
+/// [`CarbonProvider`] rather than this trait. Query futures are boxed here
+/// only, at the dynamic dispatch boundary.
+#[async_trait]
+pub trait DynCarbonProvider: Send + Sync {
+    /// See [`CarbonProvider::name`]
+    fn name(&self) -> &'static str;
+
+    /// See [`CarbonProvider::get_emissions`]
+    async fn get_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>>;
+
+    /// See [`CarbonProvider::get_emissions_with_raw`]
+    async fn get_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult>;
+
+    /// See [`CarbonProvider::is_configured`]
+    fn is_configured(&self) -> bool;
+
+    /// See [`CarbonProvider::prewarm`]
+    async fn prewarm(&self) -> Result<()>;
+
+    /// See [`CarbonProvider::filters_tags`]
+    fn filters_tags(&self) -> bool;
+
     /// Clone the provider (required for CarbemClient cloning)
-    fn clone_provider(&self) -> Box<dyn CarbonProvider + Send + Sync>;
+    fn clone_provider(&self) -> Box<dyn DynCarbonProvider>;
+
+    /// See [`CarbonProvider::set_lenient_parsing`]
+    fn set_lenient_parsing(&mut self, lenient: bool);
+
+    /// See [`CarbonProvider::set_transport`]
+    fn set_transport(&mut self, transport: Arc<dyn Transport>);
+
+    /// See [`CarbonProvider::schema_warnings`]
+    fn schema_warnings(&self) -> Vec<SchemaWarning>;
+
+    /// See [`CarbonProvider::recent_exchanges`]
+    fn recent_exchanges(&self) -> Vec<CapturedExchange>;
+
+    /// See [`CarbonProvider::plan_requests`]
+    fn plan_requests(&self, query: &EmissionQuery) -> Result<Vec<PlannedRequest>>;
+
+    /// See [`CarbonProvider::expected_pages`]
+    fn expected_pages(&self, query: &EmissionQuery) -> Option<u64>;
+}
+
+#[async_trait]
+impl<P: CarbonProvider + Clone + 'static> DynCarbonProvider for P {
+    fn name(&self) -> &'static str {
+        CarbonProvider::name(self)
+    }
+
+    async fn get_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        CarbonProvider::get_emissions(self, query).await
+    }
+
+    async fn get_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        CarbonProvider::get_emissions_with_raw(self, query).await
+    }
+
+    fn is_configured(&self) -> bool {
+        CarbonProvider::is_configured(self)
+    }
+
+    async fn prewarm(&self) -> Result<()> {
+        CarbonProvider::prewarm(self).await
+    }
+
+    fn filters_tags(&self) -> bool {
+        CarbonProvider::filters_tags(self)
+    }
+
+    fn clone_provider(&self) -> Box<dyn DynCarbonProvider> {
+        Box::new(self.clone())
+    }
+
+    fn set_lenient_parsing(&mut self, lenient: bool) {
+        CarbonProvider::set_lenient_parsing(self, lenient)
+    }
+
+    fn set_transport(&mut self, transport: Arc<dyn Transport>) {
+        CarbonProvider::set_transport(self, transport)
+    }
+
+    fn schema_warnings(&self) -> Vec<SchemaWarning> {
+        CarbonProvider::schema_warnings(self)
+    }
+
+    fn recent_exchanges(&self) -> Vec<CapturedExchange> {
+        CarbonProvider::recent_exchanges(self)
+    }
+
+    fn plan_requests(&self, query: &EmissionQuery) -> Result<Vec<PlannedRequest>> {
+        CarbonProvider::plan_requests(self, query)
+    }
+
+    fn expected_pages(&self, query: &EmissionQuery) -> Option<u64> {
+        CarbonProvider::expected_pages(self, query)
+    }
 }
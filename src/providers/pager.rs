@@ -0,0 +1,198 @@
+//! Page-by-page access to provider results
+//!
+//! A [`ProviderPager`] fetches one page of a query per
+//! [`next_page`](ProviderPager::next_page) call, so the caller decides when
+//! each request is sent: backing off between pages, rate limiting or
+//! persisting progress. The query of the next page is a plain
+//! [`EmissionQuery`], selecting the page through `skip_token` for Azure and
+//! `offset` for IBM Cloud: store it and create a new pager from it to resume
+//! paging in another process. Client queries return the page their query
+//! selects and warn when more remain.
+
+use std::future::Future;
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::models::{EmissionQuery, EmissionResult};
+use crate::providers::azure::AzureProvider;
+use crate::providers::ibm::IbmProvider;
+
+/// Explicit, resumable paging over the results of one query
+#[async_trait]
+pub trait ProviderPager: Send {
+    /// Fetch the next page, or `None` once every page was fetched
+    ///
+    /// A failed page is not skipped: calling again retries it.
+    async fn next_page(&mut self) -> Result<Option<EmissionResult>>;
+
+    /// Query of the next page, or `None` once every page was fetched
+    fn next_query(&self) -> Option<&EmissionQuery>;
+
+    /// Number of pages fetched so far
+    fn pages_fetched(&self) -> usize;
+
+    /// Fetch the remaining pages, merged into one result
+    async fn try_collect(mut self) -> Result<EmissionResult>
+    where
+        Self: Sized,
+    {
+        let mut result = EmissionResult::default();
+        while let Some(page) = self.next_page().await? {
+            result.emissions.extend(page.emissions);
+            result.raw_responses.extend(page.raw_responses);
+            result.planned_requests.extend(page.planned_requests);
+        }
+        Ok(result)
+    }
+}
+
+// Provider fetching one page of a query, with the query of the next page if any
+pub(crate) trait PageSource: Sync {
+    fn fetch_page(
+        &self,
+        query: &EmissionQuery,
+    ) -> impl Future<Output = Result<(EmissionResult, Option<EmissionQuery>)>> + Send;
+}
+
+/// Pager over the result pages of a provider, created by its `pager` method
+///
+/// Subscriptions of Azure `all_subscriptions` queries are discovered with
+/// the first page and listed in the following page queries.
+#[derive(Debug)]
+pub struct Pager<'a, P> {
+    provider: &'a P,
+    next: Option<EmissionQuery>,
+    pages: usize,
+}
+
+/// Pager over Azure report pages, created by [`AzureProvider::pager`]
+pub type AzurePager<'a> = Pager<'a, AzureProvider>;
+
+/// Pager over IBM Cloud result pages, created by [`IbmProvider::pager`]
+pub type IbmPager<'a> = Pager<'a, IbmProvider>;
+
+impl<'a, P> Pager<'a, P> {
+    pub(crate) fn new(provider: &'a P, query: EmissionQuery) -> Self {
+        Self {
+            provider,
+            next: Some(query),
+            pages: 0,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: PageSource> ProviderPager for Pager<'_, P> {
+    async fn next_page(&mut self) -> Result<Option<EmissionResult>> {
+        let Some(query) = self.next.take() else {
+            return Ok(None);
+        };
+        match self.provider.fetch_page(&query).await {
+            Ok((page, next)) => {
+                self.next = next;
+                self.pages += 1;
+                Ok(Some(page))
+            }
+            Err(e) => {
+                // Kept for a retry
+                self.next = Some(query);
+                Err(e)
+            }
+        }
+    }
+
+    fn next_query(&self) -> Option<&EmissionQuery> {
+        self.next.as_ref()
+    }
+
+    fn pages_fetched(&self) -> usize {
+        self.pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::azure::AzureConfig;
+    use crate::providers::config::ProviderQueryConfig;
+    use crate::providers::ibm::IbmConfig;
+    use crate::sandbox::{SANDBOX_CREDENTIAL, SandboxTransport};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_ibm_pager_pages_and_resumes() {
+        let provider = IbmProvider::new(IbmConfig {
+            api_key: SANDBOX_CREDENTIAL.to_string(),
+        })
+        .unwrap()
+        .with_transport(Arc::new(SandboxTransport::new()));
+        let query = EmissionQuery::from_query_string(
+            "provider=ibm&regions=us-south&start=2024-01-01&end=2024-01-31\
+             &config.enterprise_id=sandbox-enterprise&config.group_by=service&config.limit=1",
+        )
+        .unwrap();
+
+        let mut pager = provider.pager(&query).unwrap();
+        let first = pager.next_page().await.unwrap().unwrap();
+        assert_eq!(first.emissions.len(), 1);
+        assert_eq!(pager.pages_fetched(), 1);
+
+        // The next page query resumes paging in a new pager
+        let next = pager.next_query().unwrap().clone();
+        let Some(ProviderQueryConfig::Ibm(config)) = &next.provider_config else {
+            panic!("the next page query lost its IBM configuration");
+        };
+        assert_eq!(config.offset, Some(1));
+        let resumed = provider.pager(&next).unwrap().try_collect().await.unwrap();
+        assert_eq!(resumed.emissions.len(), 2);
+
+        let all = provider.pager(&query).unwrap().try_collect().await.unwrap();
+        let services = |result: &EmissionResult| {
+            result
+                .emissions
+                .iter()
+                .map(|e| e.service.clone())
+                .collect::<Vec<_>>()
+        };
+        let mut paged = services(&first);
+        paged.extend(services(&resumed));
+        assert_eq!(services(&all), paged);
+    }
+
+    #[tokio::test]
+    async fn test_azure_pager_follows_skip_tokens() {
+        let provider = AzureProvider::new(AzureConfig {
+            access_token: SANDBOX_CREDENTIAL.to_string(),
+        })
+        .unwrap()
+        .with_transport(Arc::new(SandboxTransport::new()));
+        let query = EmissionQuery::from_query_string(
+            "provider=azure&regions=westeurope&start=2024-01-01&end=2024-01-31\
+             &config.report_type=ItemDetailsReport&config.category_type=Resource\
+             &config.order_by=ItemName&config.sort_direction=Asc&config.page_size=1\
+             &config.all_subscriptions=true",
+        )
+        .unwrap();
+
+        let mut pager = provider.pager(&query);
+        let mut skip_tokens = Vec::new();
+        let mut paged = Vec::new();
+        while let Some(page) = pager.next_page().await.unwrap() {
+            assert_eq!(page.emissions.len(), 1);
+            paged.extend(page.emissions);
+            // Following pages reuse the subscriptions discovered for the first
+            if let Some(Some(ProviderQueryConfig::Azure(config))) =
+                pager.next_query().map(|next| &next.provider_config)
+            {
+                assert_eq!(config.all_subscriptions, Some(false));
+                assert_eq!(config.subscription_list.as_ref().map(Vec::len), Some(1));
+                skip_tokens.push(config.skip_token.clone().unwrap());
+            }
+        }
+        assert!(paged.len() > 1);
+        assert_eq!(pager.pages_fetched(), paged.len());
+        assert_eq!(skip_tokens.len(), paged.len() - 1);
+        assert!(pager.next_page().await.unwrap().is_none());
+    }
+}
@@ -2,18 +2,77 @@
 
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 use crate::error::{CarbemError, Result};
-use crate::providers::CarbonProvider;
+use crate::providers::DynCarbonProvider;
 use crate::providers::azure::{AzureConfig, AzureProvider};
 use crate::providers::ibm::{IbmConfig, IbmProvider};
 
 /// Type alias for provider factory functions
 type ProviderFactory =
-    Box<dyn Fn(serde_json::Value) -> Result<Box<dyn CarbonProvider + Send + Sync>> + Send + Sync>;
+    Box<dyn Fn(serde_json::Value) -> Result<Box<dyn DynCarbonProvider>> + Send + Sync>;
+
+/// How a provider authenticates with its API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthStyle {
+    /// A bearer token obtained beforehand, e.g. an Azure access token
+    BearerToken,
+
+    /// An API key exchanged for short-lived tokens by the provider
+    ApiKey,
+
+    /// Not described by the provider's registration
+    Unspecified,
+}
+
+/// Description of a registered provider, e.g. to generate configuration forms
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderInfo {
+    /// Name used to create the provider
+    pub name: String,
+
+    /// Fields required in the provider configuration
+    pub required_config_fields: Vec<String>,
+
+    /// Fields required in the query `provider_config`
+    pub required_query_fields: Vec<String>,
+
+    /// How the provider authenticates
+    pub auth_style: AuthStyle,
+}
+
+impl ProviderInfo {
+    /// Describe a provider without any required field
+    pub fn new(name: &str, auth_style: AuthStyle) -> Self {
+        Self {
+            name: name.to_string(),
+            required_config_fields: Vec::new(),
+            required_query_fields: Vec::new(),
+            auth_style,
+        }
+    }
+
+    /// Add fields required in the provider configuration
+    pub fn with_config_fields(mut self, fields: &[&str]) -> Self {
+        self.required_config_fields
+            .extend(fields.iter().map(|field| field.to_string()));
+        self
+    }
+
+    /// Add fields required in the query `provider_config`
+    pub fn with_query_fields(mut self, fields: &[&str]) -> Self {
+        self.required_query_fields
+            .extend(fields.iter().map(|field| field.to_string()));
+        self
+    }
+}
 
 /// Registry for carbon emission providers
 pub struct ProviderRegistry {
     factories: HashMap<String, ProviderFactory>,
+    infos: HashMap<String, ProviderInfo>,
 }
 
 impl ProviderRegistry {
@@ -21,6 +80,7 @@ impl ProviderRegistry {
     pub fn new() -> Self {
         let mut registry = Self {
             factories: HashMap::new(),
+            infos: HashMap::new(),
         };
 
         // Register built-in providers
@@ -37,10 +97,15 @@ impl ProviderRegistry {
                 .map_err(|e| CarbemError::Config(format!("Invalid Azure config: {}", e)))?;
 
             let provider = AzureProvider::new(config)?;
-            Ok(Box::new(provider) as Box<dyn CarbonProvider + Send + Sync>)
+            Ok(Box::new(provider) as Box<dyn DynCarbonProvider>)
         });
 
         self.factories.insert("azure".to_string(), factory);
+        self.infos.insert(
+            "azure".to_string(),
+            ProviderInfo::new("azure", AuthStyle::BearerToken)
+                .with_config_fields(&["access_token"]),
+        );
     }
 
     /// Register IBM provider factory
@@ -50,21 +115,37 @@ impl ProviderRegistry {
                 .map_err(|e| CarbemError::Config(format!("Invalid IBM config: {}", e)))?;
 
             let provider = IbmProvider::new(config)?;
-            Ok(Box::new(provider) as Box<dyn CarbonProvider + Send + Sync>)
+            Ok(Box::new(provider) as Box<dyn DynCarbonProvider>)
         });
 
         self.factories.insert("ibm".to_string(), factory);
+        self.infos.insert(
+            "ibm".to_string(),
+            ProviderInfo::new("ibm", AuthStyle::ApiKey)
+                .with_config_fields(&["api_key"])
+                .with_query_fields(&["enterprise_id"]),
+        );
     }
 
     /// Register a custom provider factory
+    ///
+    /// The provider is listed by [`available`](Self::available) without
+    /// metadata; use [`register_provider_with_info`](Self::register_provider_with_info)
+    /// to describe it.
     pub fn register_provider<F>(&mut self, name: &str, factory: F)
     where
-        F: Fn(serde_json::Value) -> Result<Box<dyn CarbonProvider + Send + Sync>>
-            + Send
-            + Sync
-            + 'static,
+        F: Fn(serde_json::Value) -> Result<Box<dyn DynCarbonProvider>> + Send + Sync + 'static,
+    {
+        self.register_provider_with_info(ProviderInfo::new(name, AuthStyle::Unspecified), factory);
+    }
+
+    /// Register a custom provider factory described by `info`
+    pub fn register_provider_with_info<F>(&mut self, info: ProviderInfo, factory: F)
+    where
+        F: Fn(serde_json::Value) -> Result<Box<dyn DynCarbonProvider>> + Send + Sync + 'static,
     {
-        self.factories.insert(name.to_string(), Box::new(factory));
+        self.factories.insert(info.name.clone(), Box::new(factory));
+        self.infos.insert(info.name.clone(), info);
     }
 
     /// Create a provider instance
@@ -72,7 +153,7 @@ impl ProviderRegistry {
         &self,
         name: &str,
         config: serde_json::Value,
-    ) -> Result<Box<dyn CarbonProvider + Send + Sync>> {
+    ) -> Result<Box<dyn DynCarbonProvider>> {
         let factory = self
             .factories
             .get(name)
@@ -85,6 +166,18 @@ impl ProviderRegistry {
     pub fn available_providers(&self) -> Vec<String> {
         self.factories.keys().cloned().collect()
     }
+
+    /// Description of every registered provider, sorted by name
+    pub fn available(&self) -> Vec<ProviderInfo> {
+        let mut infos: Vec<ProviderInfo> = self.infos.values().cloned().collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    /// Description of the provider registered as `name`
+    pub fn info(&self, name: &str) -> Option<&ProviderInfo> {
+        self.infos.get(name)
+    }
 }
 
 impl Default for ProviderRegistry {
@@ -124,6 +217,28 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_available_provider_info() {
+        let mut registry = ProviderRegistry::new();
+        registry.register_provider("custom", |_| {
+            Err(CarbemError::Config("not implemented".to_string()))
+        });
+
+        let infos = registry.available();
+        let names: Vec<&str> = infos.iter().map(|info| info.name.as_str()).collect();
+        assert_eq!(names, vec!["azure", "custom", "ibm"]);
+        assert_eq!(infos[0].required_config_fields, vec!["access_token"]);
+        assert_eq!(infos[1].auth_style, AuthStyle::Unspecified);
+        assert_eq!(
+            registry.info("ibm").unwrap().required_query_fields,
+            vec!["enterprise_id"]
+        );
+        assert_eq!(
+            serde_json::to_value(&infos[2]).unwrap()["auth_style"],
+            json!("api_key")
+        );
+    }
+
     #[test]
     fn test_unknown_provider() {
         let registry = ProviderRegistry::new();
@@ -0,0 +1,738 @@
+//! Conformance test kit for provider implementations
+//!
+//! Every provider must give queries, units, pages and errors the same
+//! meaning, whatever its API looks like. A [`ConformanceSuite`] runs golden
+//! fixtures, one JSON file per [`ConformanceCase`], against a provider whose
+//! HTTP requests are answered from the fixture. Each case checks the requests
+//! the provider sent, the emissions or the error it returned, and the pages
+//! it expected, and every case also checks the invariants shared by all
+//! providers:
+//!
+//! - each recorded response is requested, and nothing more;
+//! - emissions are named after the provider and given in kg CO2e, never
+//!   negative;
+//! - emission periods are non-empty;
+//! - emissions record the lineage of the request that returned them.
+//!
+//! A suite must cover every [`ConformanceCategory`]. New providers add their
+//! fixtures next to their module and run them from a test:
+//!
+//! ```rust,ignore
+//! let suite = ConformanceSuite::load("src/providers/acme/conformance")?;
+//! suite.run(&AcmeProvider::new(config)?).await.assert_passed();
+//! ```
+//!
+//! With `CARBEM_UPDATE_GOLDEN=1`, failing cases have their expectations
+//! rewritten with what the provider returned instead, to review with
+//! `git diff`. Expectations leave lineage out, as it records when the
+//! emissions were retrieved. Available with the `test-kit` feature.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, EmissionQuery, sort_emissions};
+use crate::providers::CarbonProvider;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+
+/// Environment variable rewriting the expectations of failing cases
+pub const UPDATE_GOLDEN_ENV: &str = "CARBEM_UPDATE_GOLDEN";
+
+/// Relative difference tolerated between expected and returned numbers
+const TOLERANCE: f64 = 1e-9;
+
+/// What a conformance case is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConformanceCategory {
+    /// Queries turned into provider requests
+    QueryConversion,
+
+    /// Provider figures converted to kg CO2e over carbem periods
+    UnitConversion,
+
+    /// Responses with further pages, and the pages expected beforehand
+    Pagination,
+
+    /// Provider failures mapped to [`CarbemError`] variants
+    ErrorMapping,
+}
+
+impl ConformanceCategory {
+    /// Every category a suite must cover
+    pub const ALL: [ConformanceCategory; 4] = [
+        ConformanceCategory::QueryConversion,
+        ConformanceCategory::UnitConversion,
+        ConformanceCategory::Pagination,
+        ConformanceCategory::ErrorMapping,
+    ];
+}
+
+/// A response of the provider API, answering the next request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// HTTP status code
+    #[serde(default = "default_status")]
+    pub status: u16,
+
+    /// Response body, as JSON or as a string sent verbatim
+    pub body: Value,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// A request the provider is expected to send
+///
+/// Headers are not compared: they carry credentials.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpectedRequest {
+    /// HTTP method, e.g. `GET`
+    pub method: String,
+
+    /// Full URL, including query parameters
+    pub url: String,
+
+    /// Optional: JSON body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// An error the provider is expected to return
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpectedError {
+    /// [`CarbemError`] variant, e.g. `Api`, see [`error_kind`]
+    pub kind: String,
+
+    /// Optional: text the error message must contain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+}
+
+/// What a provider must do with a case, each field checked when set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Expectation {
+    /// Optional: requests sent, in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests: Option<Vec<ExpectedRequest>>,
+
+    /// Optional: emissions returned, in any order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emissions: Option<Vec<Value>>,
+
+    /// Optional: error returned instead of emissions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ExpectedError>,
+
+    /// Optional: result pages expected before querying, `null` when unpredictable
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expected_pages: Option<Option<u64>>,
+}
+
+// Tells a field set to `null` from a missing one
+fn present<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// A golden fixture: a query, the provider's answers, and the expected outcome
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConformanceCase {
+    /// Case name, e.g. `monthly_summary`
+    pub name: String,
+
+    /// What the case is about
+    pub category: ConformanceCategory,
+
+    /// Query in the query string format of [`EmissionQuery::from_query_string`]
+    pub query: String,
+
+    /// Answers to the provider's requests, in order
+    #[serde(default)]
+    pub responses: Vec<RecordedResponse>,
+
+    /// Expected outcome
+    #[serde(default)]
+    pub expect: Expectation,
+
+    // File the case was loaded from, rewritten by golden updates
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+/// Golden fixtures every implementation of a provider must pass
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceSuite {
+    cases: Vec<ConformanceCase>,
+}
+
+impl ConformanceSuite {
+    /// Suite of `cases`, e.g. built in code
+    pub fn new(cases: Vec<ConformanceCase>) -> Self {
+        Self { cases }
+    }
+
+    /// Suite of the `*.json` cases in `dir`, in file name order
+    ///
+    /// Fails if a file cannot be parsed or a category has no case.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            CarbemError::Config(format!(
+                "Failed to read conformance cases {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let mut cases = Vec::new();
+        for path in paths {
+            let text = std::fs::read_to_string(&path).map_err(|e| {
+                CarbemError::Config(format!(
+                    "Failed to read conformance case {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            let mut case: ConformanceCase = serde_json::from_str(&text).map_err(|e| {
+                CarbemError::Config(format!(
+                    "Invalid conformance case {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            case.path = Some(path);
+            cases.push(case);
+        }
+
+        let suite = Self { cases };
+        let missing = suite.missing_categories();
+        if !missing.is_empty() {
+            return Err(CarbemError::Config(format!(
+                "Conformance cases in {} do not cover {:?}",
+                dir.display(),
+                missing
+            )));
+        }
+        Ok(suite)
+    }
+
+    /// The cases of the suite
+    pub fn cases(&self) -> &[ConformanceCase] {
+        &self.cases
+    }
+
+    /// Categories without any case
+    pub fn missing_categories(&self) -> Vec<ConformanceCategory> {
+        ConformanceCategory::ALL
+            .into_iter()
+            .filter(|category| !self.cases.iter().any(|case| case.category == *category))
+            .collect()
+    }
+
+    /// Run every case against a copy of `provider` answered from the case
+    pub async fn run<P>(&self, provider: &P) -> ConformanceReport
+    where
+        P: CarbonProvider + Clone,
+    {
+        let update = std::env::var(UPDATE_GOLDEN_ENV).is_ok_and(|value| value == "1");
+        let mut report = ConformanceReport::default();
+        for case in &self.cases {
+            let outcome = Outcome::of(case, provider).await;
+            let mut failures = outcome.failures(case);
+            if !failures.is_empty() && update {
+                match outcome.update(case) {
+                    Ok(()) => failures.clear(),
+                    Err(e) => failures.push(e.to_string()),
+                }
+            }
+            // Golden updates cannot accept a broken invariant
+            if let Ok(emissions) = &outcome.result {
+                failures.extend(invariant_failures(provider.name(), emissions));
+            }
+            if failures.is_empty() {
+                report.passed += 1;
+            }
+            report
+                .failures
+                .extend(failures.into_iter().map(|message| CaseFailure {
+                    case: case.name.clone(),
+                    category: case.category,
+                    message,
+                }));
+        }
+        report
+    }
+}
+
+/// A check a conformance case failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseFailure {
+    /// Name of the case
+    pub case: String,
+
+    /// Category of the case
+    pub category: ConformanceCategory,
+
+    /// What differed from the expectation
+    pub message: String,
+}
+
+/// Results of running a [`ConformanceSuite`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConformanceReport {
+    /// Cases that passed every check
+    pub passed: usize,
+
+    /// Failed checks, in case order
+    pub failures: Vec<CaseFailure>,
+}
+
+impl ConformanceReport {
+    /// Whether every case passed
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Panic with every failure unless every case passed, for tests
+    #[track_caller]
+    pub fn assert_passed(&self) {
+        if !self.is_success() {
+            let failures: Vec<String> = self
+                .failures
+                .iter()
+                .map(|failure| format!("{}: {}", failure.case, failure.message))
+                .collect();
+            panic!(
+                "{} conformance check(s) failed:\n{}",
+                failures.len(),
+                failures.join("\n")
+            );
+        }
+    }
+}
+
+/// Name of the [`CarbemError`] variant of `error`, ignoring correlation IDs
+pub fn error_kind(error: &CarbemError) -> &'static str {
+    match error.root() {
+        CarbemError::Http(_) => "Http",
+        CarbemError::Json(_) => "Json",
+        CarbemError::Provider(_) => "Provider",
+        CarbemError::UnsupportedProvider(_) => "UnsupportedProvider",
+        CarbemError::Config(_) => "Config",
+        CarbemError::Auth(_) => "Auth",
+        CarbemError::RateLimit => "RateLimit",
+        CarbemError::QuotaExceeded(_) => "QuotaExceeded",
+        CarbemError::Unauthenticated(_) => "Unauthenticated",
+        CarbemError::AccessDenied(_) => "AccessDenied",
+        CarbemError::InvalidHandle(_) => "InvalidHandle",
+        CarbemError::LossyConversion(_) => "LossyConversion",
+        CarbemError::Api(_) => "Api",
+        CarbemError::Other(_) => "Other",
+        CarbemError::Correlated { .. } => "Correlated",
+    }
+}
+
+// Answers requests from the recorded responses and records them
+#[derive(Debug)]
+struct Replay {
+    responses: Mutex<VecDeque<RecordedResponse>>,
+    requests: Mutex<Vec<ExpectedRequest>>,
+}
+
+#[async_trait]
+impl Transport for Replay {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let body = request.body.as_ref().map(|body| {
+            serde_json::from_slice(body)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()))
+        });
+        self.requests
+            .lock()
+            .expect("replay lock poisoned")
+            .push(ExpectedRequest {
+                method: request.method.to_string(),
+                url: request.url.clone(),
+                body,
+            });
+        let Some(response) = self
+            .responses
+            .lock()
+            .expect("replay lock poisoned")
+            .pop_front()
+        else {
+            return Err(CarbemError::Other(format!(
+                "no recorded response left for {} {}",
+                request.method, request.url
+            )));
+        };
+        let status = StatusCode::from_u16(response.status).map_err(|e| {
+            CarbemError::Config(format!(
+                "Invalid recorded status {}: {}",
+                response.status, e
+            ))
+        })?;
+        let body = match response.body {
+            Value::String(body) => body.into_bytes(),
+            body => serde_json::to_vec(&body)?,
+        };
+        Ok(HttpResponse::new(status, body))
+    }
+}
+
+// What a provider did with a case
+struct Outcome {
+    query: Result<EmissionQuery>,
+    result: Result<Vec<CarbonEmission>>,
+    requests: Vec<ExpectedRequest>,
+    unanswered: usize,
+    expected_pages: Option<u64>,
+}
+
+impl Outcome {
+    async fn of<P: CarbonProvider + Clone>(case: &ConformanceCase, provider: &P) -> Self {
+        let replay = Arc::new(Replay {
+            responses: Mutex::new(case.responses.iter().cloned().collect()),
+            requests: Mutex::new(Vec::new()),
+        });
+        let mut provider = provider.clone();
+        provider.set_transport(replay.clone());
+
+        let query = EmissionQuery::from_query_string(&case.query);
+        let (result, expected_pages) = match &query {
+            Ok(query) => (
+                provider.get_emissions(query).await,
+                provider.expected_pages(query),
+            ),
+            Err(e) => (Err(e.duplicate()), None),
+        };
+        let requests = std::mem::take(&mut *replay.requests.lock().expect("replay lock poisoned"));
+        let unanswered = replay.responses.lock().expect("replay lock poisoned").len();
+        Self {
+            query,
+            result: result.map(|mut emissions| {
+                sort_emissions(&mut emissions);
+                emissions
+            }),
+            requests,
+            unanswered,
+            expected_pages,
+        }
+    }
+
+    // Differences from the case expectation
+    fn failures(&self, case: &ConformanceCase) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Err(e) = &self.query {
+            failures.push(format!("invalid query {:?}: {}", case.query, e));
+            return failures;
+        }
+        if self.unanswered > 0 {
+            failures.push(format!(
+                "{} recorded response(s) were never requested",
+                self.unanswered
+            ));
+        }
+
+        let expect = &case.expect;
+        if let Some(requests) = &expect.requests {
+            if requests.len() != self.requests.len() {
+                failures.push(format!(
+                    "sent {} request(s), expected {}",
+                    self.requests.len(),
+                    requests.len()
+                ));
+            }
+            for (index, (expected, sent)) in requests.iter().zip(&self.requests).enumerate() {
+                if expected.method != sent.method || expected.url != sent.url {
+                    failures.push(format!(
+                        "request {} is {} {}, expected {} {}",
+                        index, sent.method, sent.url, expected.method, expected.url
+                    ));
+                }
+                if expected.body != sent.body {
+                    failures.push(format!(
+                        "request {} body is {}, expected {}",
+                        index,
+                        json_or_none(&sent.body),
+                        json_or_none(&expected.body)
+                    ));
+                }
+            }
+        }
+        if let Some(pages) = expect.expected_pages
+            && pages != self.expected_pages
+        {
+            failures.push(format!(
+                "expects {:?} page(s), expected {:?}",
+                self.expected_pages, pages
+            ));
+        }
+
+        match (&self.result, &expect.error) {
+            (Ok(emissions), None) => {
+                if let Some(expected) = &expect.emissions {
+                    failures.extend(emission_failures(expected, emissions));
+                }
+            }
+            (Ok(emissions), Some(error)) => failures.push(format!(
+                "returned {} emission(s), expected a {} error",
+                emissions.len(),
+                error.kind
+            )),
+            (Err(e), None) => failures.push(format!("failed: {}", e)),
+            (Err(e), Some(error)) => {
+                if error_kind(e) != error.kind {
+                    failures.push(format!(
+                        "failed with a {} error, expected {}: {}",
+                        error_kind(e),
+                        error.kind,
+                        e
+                    ));
+                }
+                if let Some(text) = &error.contains
+                    && !e.to_string().contains(text.as_str())
+                {
+                    failures.push(format!(
+                        "error {:?} does not contain {:?}",
+                        e.to_string(),
+                        text
+                    ));
+                }
+            }
+        }
+        failures
+    }
+
+    // Rewrite the case file with this outcome as its expectation
+    fn update(&self, case: &ConformanceCase) -> Result<()> {
+        let Some(path) = &case.path else {
+            return Err(CarbemError::Config(format!(
+                "Case {} was not loaded from a file",
+                case.name
+            )));
+        };
+        let mut expect = case.expect.clone();
+        if expect.requests.is_some() {
+            expect.requests = Some(self.requests.clone());
+        }
+        if expect.expected_pages.is_some() {
+            expect.expected_pages = Some(self.expected_pages);
+        }
+        match &self.result {
+            Ok(emissions) => {
+                expect.error = None;
+                expect.emissions = Some(
+                    emissions
+                        .iter()
+                        .map(golden_value)
+                        .collect::<std::result::Result<_, _>>()?,
+                );
+            }
+            Err(e) => {
+                expect.emissions = None;
+                let contains = expect.error.and_then(|error| error.contains);
+                expect.error = Some(ExpectedError {
+                    kind: error_kind(e).to_string(),
+                    contains: contains.filter(|text| e.to_string().contains(text.as_str())),
+                });
+            }
+        }
+        let case = ConformanceCase {
+            expect,
+            ..case.clone()
+        };
+        let mut text = serde_json::to_string_pretty(&case)?;
+        text.push('\n');
+        std::fs::write(path, text).map_err(|e| {
+            CarbemError::Config(format!(
+                "Failed to update conformance case {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+}
+
+// Semantics every provider shares, whatever the case expects
+fn invariant_failures(provider: &str, emissions: &[CarbonEmission]) -> Vec<String> {
+    let mut failures = Vec::new();
+    for (index, emission) in emissions.iter().enumerate() {
+        if emission.provider != provider {
+            failures.push(format!(
+                "emission {} is from provider {:?}, expected {:?}",
+                index, emission.provider, provider
+            ));
+        }
+        if !emission.emissions_kg_co2eq.is_finite() || emission.emissions_kg_co2eq < 0.0 {
+            failures.push(format!(
+                "emission {} is {} kg CO2e, expected a finite non-negative amount",
+                index, emission.emissions_kg_co2eq
+            ));
+        }
+        if emission.time_period.start >= emission.time_period.end {
+            failures.push(format!(
+                "emission {} covers an empty period {} to {}",
+                index, emission.time_period.start, emission.time_period.end
+            ));
+        }
+        if emission
+            .lineage
+            .as_ref()
+            .is_none_or(|lineage| lineage.endpoint.is_empty() || lineage.request_hash.is_empty())
+        {
+            failures.push(format!("emission {} records no lineage", index));
+        }
+    }
+    failures
+}
+
+// Emission as compared to expectations, without its lineage
+fn golden_value(emission: &CarbonEmission) -> serde_json::Result<Value> {
+    let mut value = serde_json::to_value(emission)?;
+    if let Value::Object(fields) = &mut value {
+        fields.remove("lineage");
+    }
+    Ok(value)
+}
+
+// Differences between expected emissions, in canonical order, and returned ones
+fn emission_failures(expected: &[Value], emissions: &[CarbonEmission]) -> Vec<String> {
+    let mut failures = Vec::new();
+    if expected.len() != emissions.len() {
+        failures.push(format!(
+            "returned {} emission(s), expected {}",
+            emissions.len(),
+            expected.len()
+        ));
+    }
+    for (index, (expected, emission)) in expected.iter().zip(emissions).enumerate() {
+        let returned = match golden_value(emission) {
+            Ok(returned) => returned,
+            Err(e) => {
+                failures.push(format!("emission {} cannot be serialized: {}", index, e));
+                continue;
+            }
+        };
+        if let Some(path) = difference(expected, &returned, String::new()) {
+            failures.push(format!(
+                "emission {} differs at {}: returned {}, expected {}",
+                index,
+                if path.is_empty() { "." } else { &path },
+                returned,
+                expected
+            ));
+        }
+    }
+    failures
+}
+
+// Path of the first difference between `expected` and `returned`, numbers
+// compared with a relative tolerance
+fn difference(expected: &Value, returned: &Value, path: String) -> Option<String> {
+    match (expected, returned) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64()?, b.as_f64()?);
+            ((a - b).abs() > TOLERANCE * a.abs().max(b.abs()).max(1.0)).then_some(path)
+        }
+        (Value::Object(a), Value::Object(b)) => a
+            .keys()
+            .chain(b.keys().filter(|key| !a.contains_key(*key)))
+            .find_map(|key| {
+                let path = format!("{}.{}", path, key);
+                match (a.get(key), b.get(key)) {
+                    (Some(a), Some(b)) => difference(a, b, path),
+                    _ => Some(path),
+                }
+            }),
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => a
+            .iter()
+            .zip(b)
+            .enumerate()
+            .find_map(|(index, (a, b))| difference(a, b, format!("{}[{}]", path, index))),
+        (a, b) => (a != b).then_some(path),
+    }
+}
+
+fn json_or_none(value: &Option<Value>) -> String {
+    value
+        .as_ref()
+        .map_or_else(|| "none".to_string(), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AzureConfig, AzureProvider};
+
+    #[tokio::test]
+    async fn test_failures_are_reported_per_case() {
+        let provider = AzureProvider::new(AzureConfig {
+            access_token: "token".to_string(),
+        })
+        .unwrap();
+        let case = |name: &str, category, expect| ConformanceCase {
+            name: name.to_string(),
+            category,
+            query: "provider=azure&regions=westeurope&start=2024-01-01&end=2024-01-31\
+                    &config.report_type=MonthlySummaryReport&config.subscription_list=sub-1"
+                .to_string(),
+            responses: vec![RecordedResponse {
+                status: 429,
+                body: Value::String("slow down".to_string()),
+            }],
+            expect,
+            path: None,
+        };
+        let suite = ConformanceSuite::new(vec![
+            case(
+                "throttled",
+                ConformanceCategory::ErrorMapping,
+                Expectation {
+                    error: Some(ExpectedError {
+                        kind: "Provider".to_string(),
+                        contains: Some("429".to_string()),
+                    }),
+                    ..Default::default()
+                },
+            ),
+            case(
+                "wrong_kind",
+                ConformanceCategory::ErrorMapping,
+                Expectation {
+                    error: Some(ExpectedError {
+                        kind: "RateLimit".to_string(),
+                        contains: None,
+                    }),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(suite.missing_categories().len(), 3);
+
+        let report = suite.run(&provider).await;
+        assert_eq!(report.passed, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].case, "wrong_kind");
+        assert!(report.failures[0].message.contains("Provider error"));
+    }
+}
@@ -0,0 +1,346 @@
+//! Emission queries from URL query strings
+//!
+//! [`EmissionQuery::from_query_string`] deserializes parameters with serde:
+//! each parameter is parsed as the type of the query field it names, lists
+//! are comma-separated or repeated, and `config.`-prefixed parameters fill
+//! the provider configuration of the query's provider.
+
+use std::fmt::Display;
+
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::de::{Deserializer, Error as _, IntoDeserializer, Visitor};
+use serde::{Deserialize, forward_to_deserialize_any};
+
+use crate::error::{CarbemError, Result};
+use crate::models::{
+    DateAlignment, EmissionQuery, QueryTimezone, RawResponseMode, TagFilter, TimePeriod,
+};
+use crate::providers::azure::AzureQueryConfig;
+use crate::providers::config::ProviderQueryConfig;
+use crate::providers::ibm::IbmQueryConfig;
+
+// Prefix of provider configuration parameters
+const CONFIG_PREFIX: &str = "config.";
+
+#[derive(Deserialize)]
+struct Params {
+    provider: String,
+    start: String,
+    end: String,
+    #[serde(default)]
+    regions: Vec<String>,
+    services: Option<Vec<String>>,
+    resources: Option<Vec<String>>,
+    #[serde(default)]
+    raw_response: RawResponseMode,
+    #[serde(default)]
+    date_alignment: DateAlignment,
+    #[serde(default)]
+    timezone: QueryTimezone,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    route: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    strict: bool,
+}
+
+impl EmissionQuery {
+    /// Parse a query from a URL query string, with or without its leading `?`
+    ///
+    /// `provider`, `start` and `end` are required. Dates are UTC days
+    /// (`2024-01-31`, the end day included) or RFC 3339 timestamps. Lists
+    /// (`regions`, `services`, `resources`, `route`, `tags`) are comma-separated
+    /// or repeated. Tags are `key:value`, or `key:prefix*` to match values
+    /// starting with `prefix`. Other fields take their serialized form, e.g.
+    /// `date_alignment=truncate` or `timezone=Europe/Paris`.
+    ///
+    /// Provider configuration fields are prefixed with `config.`, e.g.
+    /// `config.subscription_list=sub-1,sub-2&config.report_type=MonthlySummaryReport`.
+    /// Unknown parameters are rejected.
+    pub fn from_query_string(query: &str) -> Result<Self> {
+        let mut params: Vec<(String, Vec<String>)> = Vec::new();
+        for pair in query.trim_start_matches('?').split('&') {
+            if pair.is_empty() {
+                continue;
+            }
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let (key, value) = (decode(key)?, decode(value)?);
+            match params.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, values)) => values.push(value),
+                None => params.push((key, vec![value])),
+            }
+        }
+        let (config, params): (Vec<_>, Vec<_>) = params
+            .into_iter()
+            .partition(|(key, _)| key.starts_with(CONFIG_PREFIX));
+
+        let params: Params = deserialize(params)?;
+        let provider_config = if config.is_empty() {
+            None
+        } else {
+            let config = config
+                .into_iter()
+                .map(|(key, values)| (key[CONFIG_PREFIX.len()..].to_string(), values));
+            Some(match params.provider.as_str() {
+                "azure" => ProviderQueryConfig::Azure(deserialize::<AzureQueryConfig>(config)?),
+                "ibm" => ProviderQueryConfig::Ibm(deserialize::<IbmQueryConfig>(config)?),
+                other => {
+                    return Err(CarbemError::Config(format!(
+                        "provider '{}' takes no config parameters",
+                        other
+                    )));
+                }
+            })
+        };
+
+        Ok(EmissionQuery {
+            time_period: TimePeriod::parse(&params.start, &params.end)?,
+            provider: params.provider,
+            regions: params.regions,
+            services: params.services,
+            resources: params.resources,
+            provider_config,
+            raw_response: params.raw_response,
+            date_alignment: params.date_alignment,
+            timezone: params.timezone,
+            dry_run: params.dry_run,
+            route: params.route,
+            tag_filters: params
+                .tags
+                .iter()
+                .map(|tag| tag_filter(tag))
+                .collect::<Result<_>>()?,
+            strict: params.strict,
+        })
+    }
+}
+
+fn decode(value: &str) -> Result<String> {
+    urlencoding::decode(&value.replace('+', " "))
+        .map(|value| value.into_owned())
+        .map_err(|_| CarbemError::Config(format!("'{}' is not valid percent-encoded UTF-8", value)))
+}
+
+fn tag_filter(tag: &str) -> Result<TagFilter> {
+    let (key, value) = tag.split_once(':').ok_or_else(|| {
+        CarbemError::Config(format!(
+            "invalid tag '{}', expected key:value or key:prefix*",
+            tag
+        ))
+    })?;
+    Ok(match value.strip_suffix('*') {
+        Some(prefix) => TagFilter {
+            prefix: true,
+            ..TagFilter::equals(key, prefix)
+        },
+        None => TagFilter::equals(key, value),
+    })
+}
+
+fn deserialize<'de, T: Deserialize<'de>>(
+    params: impl IntoIterator<Item = (String, Vec<String>)>,
+) -> Result<T> {
+    let params = params.into_iter().map(|(key, values)| {
+        let value = Param {
+            key: key.clone(),
+            values,
+        };
+        (key, value)
+    });
+    T::deserialize(MapDeserializer::<_, Error>::new(params))
+        .map_err(|e| CarbemError::Config(format!("invalid query string: {}", e)))
+}
+
+// Values of one parameter, deserialized as the type of the field it names
+struct Param {
+    key: String,
+    values: Vec<String>,
+}
+
+impl Param {
+    fn error(&self, message: impl Display) -> Error {
+        Error::custom(format!("`{}`: {}", self.key, message))
+    }
+
+    // The value of a parameter that cannot be repeated
+    fn single(&self) -> std::result::Result<&str, Error> {
+        match self.values.as_slice() {
+            [value] => Ok(value),
+            _ => Err(self.error("expected a single value")),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> std::result::Result<T, Error> {
+        let value = self.single()?;
+        value
+            .trim()
+            .parse()
+            .map_err(|_| self.error(format!("invalid value '{}', expected {}", value, expected)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Param {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Error> {
+                let value = self.parse("a number")?;
+                visitor.$visit::<Error>(value).map_err(|e| self.error(e))
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Param {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Error> {
+        let value = self.single()?.to_string();
+        visitor
+            .visit_string::<Error>(value)
+            .map_err(|e| self.error(e))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Error> {
+        let value = match self.single()? {
+            "" | "true" | "1" => true,
+            "false" | "0" => false,
+            other => {
+                return Err(
+                    self.error(format!("invalid value '{}', expected true or false", other))
+                );
+            }
+        };
+        visitor.visit_bool(value)
+    }
+
+    deserialize_number!(
+        deserialize_i8 => visit_i8, deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32, deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8, deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32, deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32, deserialize_f64 => visit_f64
+    );
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Error> {
+        let items: Vec<String> = self
+            .values
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect();
+        let items = SeqDeserializer::<_, Error>::new(items.into_iter());
+        visitor.visit_seq(items).map_err(|e| self.error(e))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        let value: StringDeserializer<Error> = self.single()?.to_string().into_deserializer();
+        value
+            .deserialize_enum(name, variants, visitor)
+            .map_err(|e| self.error(e))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        Err(self.error("unknown parameter"))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> std::result::Result<V::Value, Error> {
+        Err(self.error("nested values are not supported"))
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct tuple tuple_struct struct identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::azure::{AzureCarbonScope, AzureReportType};
+
+    #[test]
+    fn test_from_query_string() {
+        let query = EmissionQuery::from_query_string(
+            "?provider=azure&start=2024-01-01&end=2024-03-31&regions=westeurope,+northeurope\
+             &regions=eastus&date_alignment=truncate&timezone=Europe%2FParis&strict\
+             &tags=team:data,env:prod*&config.subscription_list=sub-1\
+             &config.carbon_scope_list=Scope1,Scope3&config.page_size=50\
+             &config.report_type=MonthlySummaryReport",
+        )
+        .unwrap();
+        assert_eq!(query.regions, ["westeurope", "northeurope", "eastus"]);
+        assert_eq!(query.date_alignment, DateAlignment::Truncate);
+        assert_eq!(query.timezone, "Europe/Paris".parse().unwrap());
+        assert!(query.strict);
+        assert_eq!(query.tag_filters[0], TagFilter::equals("team", "data"));
+        assert!(query.tag_filters[1].prefix);
+        let Some(ProviderQueryConfig::Azure(config)) = query.provider_config else {
+            panic!("expected an Azure config");
+        };
+        assert_eq!(config.subscription_list, Some(vec!["sub-1".to_string()]));
+        assert_eq!(
+            config.carbon_scope_list,
+            Some(vec![AzureCarbonScope::Scope1, AzureCarbonScope::Scope3])
+        );
+        assert_eq!(config.page_size, Some(50));
+        assert_eq!(
+            config.report_type,
+            Some(AzureReportType::MonthlySummaryReport)
+        );
+
+        let error = |query: &str| {
+            EmissionQuery::from_query_string(query)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error("provider=azure&start=2024-01-01").contains("missing field `end`"));
+        assert!(
+            error("provider=azure&start=2024-01-01&end=2024-01-31&regoins=x")
+                .contains("`regoins`: unknown parameter")
+        );
+        assert!(
+            error("provider=azure&start=2024-01-01&end=2024-01-31&config.page_size=many")
+                .contains("`page_size`: invalid value 'many', expected a number")
+        );
+        assert!(
+            error("provider=ibm&start=2024-01-01&end=2024-01-31&config.group_by=planet")
+                .contains("`group_by`: unknown variant `planet`")
+        );
+        assert!(error("provider=azure&start=2024-01-31&end=2024-01-01").contains("before start"));
+    }
+}
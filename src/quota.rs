@@ -0,0 +1,310 @@
+//! Quotas of provider API calls per hour and per day
+//!
+//! Provider rate limits are often shared by every tool of an organization.
+//! A [`Quota`] caps the calls carbem makes to a provider in the current UTC
+//! hour and day, so that a backfill cannot exhaust the limit other tools
+//! rely on. Calls beyond the quota are refused with
+//! [`CarbemError::QuotaExceeded`], or wait for the next window with
+//! [`QuotaAction::Wait`]. Every request sent counts, retries and result
+//! pages included; instances of one provider share its quota.
+//!
+//! [`CarbemClient::quota_status`](crate::CarbemClient::quota_status) reports
+//! the calls made in the current windows.
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CarbemError, Result};
+use crate::logging::debug;
+use crate::runtime::runtime;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+
+/// What happens to calls beyond a quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Fail the call with [`CarbemError::QuotaExceeded`]
+    #[default]
+    Reject,
+
+    /// Wait until the window with the exhausted quota ends
+    Wait,
+}
+
+/// Calls allowed to one provider per UTC hour and day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// Optional: calls allowed per hour
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_hour: Option<u32>,
+
+    /// Optional: calls allowed per day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_day: Option<u32>,
+
+    /// What happens to calls beyond the quota (defaults to rejecting them)
+    #[serde(default)]
+    pub when_exhausted: QuotaAction,
+}
+
+impl Quota {
+    /// Allow `calls` per hour
+    pub fn per_hour(calls: u32) -> Self {
+        Self::default().with_per_hour(calls)
+    }
+
+    /// Allow `calls` per day
+    pub fn per_day(calls: u32) -> Self {
+        Self::default().with_per_day(calls)
+    }
+
+    /// Also allow at most `calls` per hour
+    pub fn with_per_hour(mut self, calls: u32) -> Self {
+        self.per_hour = Some(calls);
+        self
+    }
+
+    /// Also allow at most `calls` per day
+    pub fn with_per_day(mut self, calls: u32) -> Self {
+        self.per_day = Some(calls);
+        self
+    }
+
+    /// Make calls beyond the quota wait for the next window instead of failing
+    pub fn waiting(mut self) -> Self {
+        self.when_exhausted = QuotaAction::Wait;
+        self
+    }
+}
+
+/// Calls made to a provider in the current windows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaStatus {
+    /// Provider name, e.g. `azure`
+    pub provider: String,
+
+    /// Calls made in the current hour
+    pub calls_this_hour: u32,
+
+    /// Optional: calls allowed per hour
+    pub hourly_limit: Option<u32>,
+
+    /// Calls made in the current day
+    pub calls_today: u32,
+
+    /// Optional: calls allowed per day
+    pub daily_limit: Option<u32>,
+
+    /// Start of the next hour, when the hourly count resets
+    pub hour_resets_at: DateTime<Utc>,
+
+    /// Start of the next day, when the daily count resets
+    pub day_resets_at: DateTime<Utc>,
+}
+
+impl QuotaStatus {
+    /// Calls left before a quota is exhausted, `None` without quota
+    pub fn remaining(&self) -> Option<u32> {
+        let hour = self
+            .hourly_limit
+            .map(|limit| limit.saturating_sub(self.calls_this_hour));
+        let day = self
+            .daily_limit
+            .map(|limit| limit.saturating_sub(self.calls_today));
+        hour.into_iter().chain(day).min()
+    }
+}
+
+/// Counts the calls made to one provider against its quota
+#[derive(Debug)]
+pub(crate) struct QuotaTracker {
+    provider: String,
+    quota: Quota,
+    windows: Mutex<Windows>,
+}
+
+#[derive(Debug, Default)]
+struct Windows {
+    hour_start: Option<DateTime<Utc>>,
+    calls_this_hour: u32,
+    day_start: Option<DateTime<Utc>>,
+    calls_today: u32,
+}
+
+impl QuotaTracker {
+    pub(crate) fn new(provider: &str, quota: Quota) -> Self {
+        Self {
+            provider: provider.to_string(),
+            quota,
+            windows: Mutex::new(Windows::default()),
+        }
+    }
+
+    // Count a call at `now`, or return when the exhausted window ends
+    fn try_acquire(&self, now: DateTime<Utc>) -> std::result::Result<(), DateTime<Utc>> {
+        let mut windows = self.windows.lock().expect("quota lock poisoned");
+        windows.roll(now);
+        let (hour_end, day_end) = window_ends(now);
+        if self
+            .quota
+            .per_hour
+            .is_some_and(|limit| windows.calls_this_hour >= limit)
+        {
+            return Err(hour_end);
+        }
+        if self
+            .quota
+            .per_day
+            .is_some_and(|limit| windows.calls_today >= limit)
+        {
+            return Err(day_end);
+        }
+        windows.calls_this_hour += 1;
+        windows.calls_today += 1;
+        Ok(())
+    }
+
+    pub(crate) fn status(&self, now: DateTime<Utc>) -> QuotaStatus {
+        let mut windows = self.windows.lock().expect("quota lock poisoned");
+        windows.roll(now);
+        let (hour_resets_at, day_resets_at) = window_ends(now);
+        QuotaStatus {
+            provider: self.provider.clone(),
+            calls_this_hour: windows.calls_this_hour,
+            hourly_limit: self.quota.per_hour,
+            calls_today: windows.calls_today,
+            daily_limit: self.quota.per_day,
+            hour_resets_at,
+            day_resets_at,
+        }
+    }
+}
+
+impl Windows {
+    // Reset the counts of windows that ended before `now`
+    fn roll(&mut self, now: DateTime<Utc>) {
+        let hour_start = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+        if self.hour_start != Some(hour_start) {
+            self.hour_start = Some(hour_start);
+            self.calls_this_hour = 0;
+        }
+        let day_start = now.duration_trunc(Duration::days(1)).unwrap_or(now);
+        if self.day_start != Some(day_start) {
+            self.day_start = Some(day_start);
+            self.calls_today = 0;
+        }
+    }
+}
+
+// Ends of the hour and day containing `now`
+fn window_ends(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let hour = now.duration_trunc(Duration::hours(1)).unwrap_or(now) + Duration::hours(1);
+    let day = now.duration_trunc(Duration::days(1)).unwrap_or(now) + Duration::days(1);
+    (hour, day)
+}
+
+/// [`Transport`] counting requests against a provider quota
+#[derive(Debug)]
+pub(crate) struct QuotaTransport {
+    inner: Arc<dyn Transport>,
+    tracker: Arc<QuotaTracker>,
+}
+
+impl QuotaTransport {
+    pub(crate) fn new(inner: Arc<dyn Transport>, tracker: Arc<QuotaTracker>) -> Self {
+        Self { inner, tracker }
+    }
+}
+
+#[async_trait]
+impl Transport for QuotaTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        loop {
+            let now = Utc::now();
+            let Err(resets_at) = self.tracker.try_acquire(now) else {
+                break;
+            };
+            match self.tracker.quota.when_exhausted {
+                QuotaAction::Reject => {
+                    return Err(CarbemError::QuotaExceeded(format!(
+                        "{} call quota exhausted until {}",
+                        self.tracker.provider,
+                        resets_at.to_rfc3339()
+                    )));
+                }
+                QuotaAction::Wait => {
+                    debug!(
+                        "{} call quota exhausted, waiting until {}",
+                        self.tracker.provider, resets_at
+                    );
+                    let wait = (resets_at - now).to_std().unwrap_or_default();
+                    runtime().sleep(wait).await;
+                }
+            }
+        }
+        self.inner.send(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_quota_windows() {
+        let tracker = QuotaTracker::new("ibm", Quota::per_hour(2).with_per_day(3));
+        let at = |hour, minute| Utc.with_ymd_and_hms(2024, 5, 2, hour, minute, 0).unwrap();
+
+        assert_eq!(tracker.try_acquire(at(9, 0)), Ok(()));
+        assert_eq!(tracker.try_acquire(at(9, 30)), Ok(()));
+        assert_eq!(tracker.try_acquire(at(9, 59)), Err(at(10, 0)));
+        let status = tracker.status(at(9, 59));
+        assert_eq!((status.calls_this_hour, status.calls_today), (2, 2));
+        assert_eq!(status.remaining(), Some(0));
+
+        // The hour resets, the day does not
+        assert_eq!(tracker.try_acquire(at(10, 0)), Ok(()));
+        let midnight = Utc.with_ymd_and_hms(2024, 5, 3, 0, 0, 0).unwrap();
+        assert_eq!(tracker.try_acquire(at(11, 0)), Err(midnight));
+        let status = tracker.status(at(11, 0));
+        assert_eq!((status.calls_this_hour, status.calls_today), (0, 3));
+        assert_eq!(status.day_resets_at, midnight);
+        assert_eq!(tracker.try_acquire(midnight), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_calls_beyond_quota() {
+        #[derive(Debug)]
+        struct Reachable;
+
+        #[async_trait]
+        impl Transport for Reachable {
+            async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+                Ok(HttpResponse::new(reqwest::StatusCode::NOT_FOUND, ""))
+            }
+        }
+
+        let client = crate::CarbemClient::builder()
+            .with_azure(crate::AzureConfig {
+                access_token: "token".to_string(),
+            })
+            .unwrap()
+            .with_transport(Arc::new(Reachable))
+            .with_provider_quota("azure", Quota::per_hour(1))
+            .build();
+
+        client.prewarm().await.unwrap();
+        assert!(matches!(
+            client.prewarm().await,
+            Err(CarbemError::QuotaExceeded(m)) if m.contains("azure")
+        ));
+        let status = client.quota_status();
+        assert_eq!(status[0].provider, "azure");
+        assert_eq!(status[0].calls_this_hour, 1);
+        assert_eq!(status[0].remaining(), Some(0));
+    }
+}
@@ -0,0 +1,362 @@
+//! Summary reports of an emission dataset, rendered as Markdown or HTML
+//!
+//! A [`Report`] holds the total, the covered period and breakdowns by
+//! provider, region, service and month. The HTML rendering only uses plain
+//! XHTML tables so that it can be embedded in wikis, e-mails or dashboards.
+//! Labels and numbers follow the report's [`Locale`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::i18n::Locale;
+
+// Breakdowns of a report when none are chosen
+pub(crate) const DEFAULT_DIMENSIONS: [Dimension; 3] =
+    [Dimension::Provider, Dimension::Region, Dimension::Service];
+
+// Name of `dimension` in message identifiers, e.g. `service` in `section-service`
+pub(crate) fn dimension_name(dimension: Dimension) -> &'static str {
+    match dimension {
+        Dimension::Provider => "provider",
+        Dimension::Region => "region",
+        Dimension::Service => "service",
+        Dimension::ServiceCategory => "service-category",
+    }
+}
+
+/// A table of a report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportSection {
+    /// Section heading
+    pub heading: String,
+
+    /// Column headers
+    pub columns: Vec<String>,
+
+    /// Formatted cell values
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Summary report of an emission dataset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    /// Report title
+    pub title: String,
+
+    /// Start of the earliest and end of the latest emission period
+    pub period: Option<(DateTime<Utc>, DateTime<Utc>)>,
+
+    /// Total emissions in kg CO2e
+    pub total_kg_co2eq: f64,
+
+    /// Breakdown tables
+    pub sections: Vec<ReportSection>,
+
+    /// Language of the labels and numbers
+    #[serde(default)]
+    pub locale: Locale,
+}
+
+impl Report {
+    /// Summarize `dataset` by provider, region, service and month
+    pub fn from_dataset(title: impl Into<String>, dataset: &EmissionDataset) -> Self {
+        Self::by_dimensions(title, dataset, &DEFAULT_DIMENSIONS)
+    }
+
+    /// Summarize `dataset` by each of `dimensions`, then by month
+    pub fn by_dimensions(
+        title: impl Into<String>,
+        dataset: &EmissionDataset,
+        dimensions: &[Dimension],
+    ) -> Self {
+        Self::by_dimensions_in(title, dataset, dimensions, Locale::En)
+    }
+
+    /// Summarize `dataset` by each of `dimensions`, then by month, labelled in `locale`
+    pub fn by_dimensions_in(
+        title: impl Into<String>,
+        dataset: &EmissionDataset,
+        dimensions: &[Dimension],
+        locale: Locale,
+    ) -> Self {
+        let unit = locale.message("unit-kg-co2e");
+        let emissions = dataset.emissions();
+        let period = emissions
+            .iter()
+            .map(|e| e.time_period.start)
+            .min()
+            .zip(emissions.iter().map(|e| e.time_period.end).max());
+
+        let mut sections: Vec<ReportSection> = dimensions
+            .iter()
+            .map(|&dimension| {
+                let name = dimension_name(dimension);
+                let shares = dataset.share_of_total(dimension);
+                let mut totals: Vec<(String, f64)> =
+                    dataset.group_by_dimension(dimension).into_iter().collect();
+                totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+                ReportSection {
+                    heading: locale.message(&format!("section-{}", name)),
+                    columns: vec![
+                        locale.message(&format!("dimension-{}", name)),
+                        unit.clone(),
+                        locale.message("report-share"),
+                    ],
+                    rows: totals
+                        .into_iter()
+                        .map(|(key, kg)| {
+                            let share = shares.get(&key).copied().unwrap_or(0.0);
+                            let share = locale.format(
+                                "report-share-value",
+                                &[("value", &locale.format_number(share, 1))],
+                            );
+                            vec![key, locale.format_number(kg, 2), share]
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        sections.push(ReportSection {
+            heading: locale.message("section-month"),
+            columns: vec![locale.message("report-month"), unit],
+            rows: dataset
+                .monthly_series()
+                .into_iter()
+                .map(|(month, kg)| {
+                    vec![
+                        month.format("%Y-%m").to_string(),
+                        locale.format_number(kg, 2),
+                    ]
+                })
+                .collect(),
+        });
+
+        Self {
+            title: title.into(),
+            period,
+            total_kg_co2eq: dataset.total_kg_co2eq(),
+            sections,
+            locale,
+        }
+    }
+
+    /// Render as GitHub-flavored Markdown
+    pub fn to_markdown(&self) -> String {
+        let mut output = format!("# {}\n\n", self.title);
+        if let Some(period) = self.period_label() {
+            output.push_str(&format!(
+                "{}: {}\n\n",
+                self.locale.message("report-period"),
+                period
+            ));
+        }
+        output.push_str(&format!(
+            "{}: **{}**\n",
+            self.locale.message("report-total"),
+            self.total_label()
+        ));
+
+        for section in &self.sections {
+            output.push_str(&format!("\n## {}\n\n", section.heading));
+            output.push_str(&format!("| {} |\n", section.columns.join(" | ")));
+            output.push_str(&format!(
+                "|{}\n",
+                section.columns.iter().map(|_| "---|").collect::<String>()
+            ));
+            for row in &section.rows {
+                let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+                output.push_str(&format!("| {} |\n", cells.join(" | ")));
+            }
+        }
+        output
+    }
+
+    /// Render as an XHTML fragment
+    pub fn to_html(&self) -> String {
+        let mut output = format!("<h1>{}</h1>\n", escape_html(&self.title));
+        if let Some(period) = self.period_label() {
+            output.push_str(&format!(
+                "<p>{}: {}</p>\n",
+                escape_html(&self.locale.message("report-period")),
+                escape_html(&period)
+            ));
+        }
+        output.push_str(&format!(
+            "<p>{}: <strong>{}</strong></p>\n",
+            escape_html(&self.locale.message("report-total")),
+            escape_html(&self.total_label())
+        ));
+
+        for section in &self.sections {
+            output.push_str(&format!(
+                "<h2>{}</h2>\n<table>\n<tr>",
+                escape_html(&section.heading)
+            ));
+            for column in &section.columns {
+                output.push_str(&format!("<th>{}</th>", escape_html(column)));
+            }
+            output.push_str("</tr>\n");
+            for row in &section.rows {
+                output.push_str("<tr>");
+                for cell in row {
+                    output.push_str(&format!("<td>{}</td>", escape_html(cell)));
+                }
+                output.push_str("</tr>\n");
+            }
+            output.push_str("</table>\n");
+        }
+        output
+    }
+
+    /// Render as an XHTML fragment followed by inline SVG charts of `dataset`
+    ///
+    /// `dataset` should be the one the report was built from.
+    #[cfg(feature = "plot")]
+    pub fn to_html_with_charts(&self, dataset: &EmissionDataset) -> crate::error::Result<String> {
+        use crate::plot::{ChartOptions, bar_by_region, stacked_area_by_service};
+
+        let mut output = self.to_html();
+        output.push_str(&format!(
+            "<h2>{}</h2>\n",
+            escape_html(&self.locale.message("report-charts"))
+        ));
+        output.push_str(&stacked_area_by_service(
+            dataset,
+            &ChartOptions {
+                title: Some(self.locale.message("chart-monthly-by-service")),
+                ..Default::default()
+            },
+        )?);
+        output.push_str(&bar_by_region(
+            dataset,
+            &ChartOptions {
+                title: Some(self.locale.message("chart-by-region")),
+                ..Default::default()
+            },
+        )?);
+        Ok(output)
+    }
+
+    fn period_label(&self) -> Option<String> {
+        self.period.map(|(start, end)| {
+            self.locale.format(
+                "report-period-range",
+                &[
+                    ("start", &start.format("%Y-%m-%d").to_string()),
+                    ("end", &end.format("%Y-%m-%d").to_string()),
+                ],
+            )
+        })
+    }
+
+    fn total_label(&self) -> String {
+        format!(
+            "{} {}",
+            self.locale.format_number(self.total_kg_co2eq, 2),
+            self.locale.message("unit-kg-co2e")
+        )
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::TimeZone;
+
+    fn emission(provider: &str, service: &str, month: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: provider.to_string(),
+            region: "westeurope".to_string(),
+            service: Some(service.to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(28),
+            },
+            metadata: None,
+        }
+    }
+
+    fn dataset() -> EmissionDataset {
+        EmissionDataset::new(vec![
+            emission("azure", "Storage", 1, 10.0),
+            emission("ibm", "Kubernetes <IKS>", 3, 30.0),
+        ])
+    }
+
+    #[test]
+    fn test_report_sections() {
+        let report = Report::from_dataset("Monthly report", &dataset());
+
+        assert_eq!(report.total_kg_co2eq, 40.0);
+        assert_eq!(report.sections.len(), 4);
+        assert_eq!(
+            report.sections[0].rows[0],
+            vec!["ibm".to_string(), "30.00".to_string(), "75.0%".to_string()]
+        );
+        assert_eq!(report.sections[3].rows.len(), 3);
+        assert_eq!(report.sections[3].rows[1][1], "0.00");
+    }
+
+    #[test]
+    fn test_render_markdown_and_html() {
+        let report = Report::from_dataset("Monthly report", &dataset());
+
+        let markdown = report.to_markdown();
+        assert!(markdown.starts_with("# Monthly report\n"));
+        assert!(markdown.contains("Period: 2024-01-01 to 2024-03-29"));
+        assert!(markdown.contains("| Provider | kg CO2e | Share |\n|---|---|---|\n"));
+
+        let html = report.to_html();
+        assert!(html.contains("<td>Kubernetes &lt;IKS&gt;</td>"));
+        assert!(html.contains("<strong>40.00 kg CO2e</strong>"));
+    }
+
+    #[test]
+    fn test_render_localized() {
+        let report =
+            Report::by_dimensions_in("Bilan", &dataset(), &[Dimension::Provider], Locale::Fr);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("Période: du 2024-01-01 au 2024-03-29"));
+        assert!(markdown.contains("**40,00 kg éq. CO2**"));
+        assert!(markdown.contains("## Par fournisseur\n\n| Fournisseur | kg éq. CO2 | Part |"));
+        assert!(markdown.contains("| ibm | 30,00 | 75,0 % |"));
+        assert!(markdown.contains("## Par mois"));
+    }
+
+    #[cfg(feature = "plot")]
+    #[test]
+    fn test_render_html_with_charts() {
+        let dataset = dataset();
+        let html = Report::from_dataset("Monthly report", &dataset)
+            .to_html_with_charts(&dataset)
+            .unwrap();
+        assert_eq!(html.matches("<svg").count(), 2);
+    }
+
+    #[test]
+    fn test_empty_report() {
+        let report = Report::from_dataset("Empty", &EmissionDataset::default());
+        assert_eq!(report.period, None);
+        assert!(
+            report
+                .sections
+                .iter()
+                .all(|section| section.rows.is_empty())
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Saved queries and named report definitions
+//!
+//! A [`ReportDefinition`] bundles a query, the dimensions to break it down
+//! by, an output format and an optional destination under a name, so that
+//! recurring reports live in configuration rather than code. Definitions are
+//! listed in [`ClientConfig::reports`](crate::config::ClientConfig::reports)
+//! and run with [`CarbemClient::run_report`](crate::CarbemClient::run_report),
+//! or against any [`EmissionSource`] with [`ReportDefinition::run`].
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::client::EmissionSource;
+use crate::error::{CarbemError, Result};
+use crate::i18n::Locale;
+use crate::models::{CarbonEmission, EmissionQuery, TimePeriod};
+use crate::output::OutputFormat;
+use crate::precision::PrecisionPolicy;
+use crate::report::{DEFAULT_DIMENSIONS, Report};
+use crate::runtime::fs;
+use crate::sinks::SinkRegistry;
+
+/// A named, reusable report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportDefinition {
+    /// Name the report is run by, e.g. `monthly-eu`
+    pub name: String,
+
+    /// Report title (defaults to the name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// Query of the report
+    pub query: EmissionQuery,
+
+    /// Period relative to the run, replacing the query's time period when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<ReportPeriod>,
+
+    /// Breakdowns of the Markdown and HTML formats (defaults to provider, region and service)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub group_by: Vec<Dimension>,
+
+    /// Output format
+    #[serde(default)]
+    pub format: ReportFormat,
+
+    /// Language of the Markdown and HTML formats (defaults to English)
+    #[serde(default)]
+    pub locale: Locale,
+
+    /// Rounding of the totals and exported values (unrounded when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precision: Option<PrecisionPolicy>,
+
+    /// Template file replacing the built-in Markdown or HTML layout
+    ///
+    /// Loaded at every run with [`ReportTemplate::from_file`](crate::template::ReportTemplate::from_file).
+    #[cfg(feature = "templates")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<PathBuf>,
+
+    /// Where to deliver the report, in addition to returning it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<ReportDestination>,
+}
+
+/// Time period of a report, relative to when it runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportPeriod {
+    /// The given number of full months before the current one
+    PreviousMonths(u32),
+
+    /// From the first of January of the current year until now
+    YearToDate,
+}
+
+impl ReportPeriod {
+    /// The period for a report run at `now`, in UTC
+    pub fn resolve(&self, now: DateTime<Utc>) -> TimePeriod {
+        match self {
+            ReportPeriod::PreviousMonths(months) => {
+                let this_month = first_of_month(now.year(), now.month());
+                let months_back = now.year() * 12 + now.month0() as i32 - (*months).max(1) as i32;
+                TimePeriod {
+                    start: first_of_month(
+                        months_back.div_euclid(12),
+                        months_back.rem_euclid(12) as u32 + 1,
+                    ),
+                    end: this_month - Duration::seconds(1),
+                }
+            }
+            ReportPeriod::YearToDate => TimePeriod {
+                start: first_of_month(now.year(), 1),
+                end: now,
+            },
+        }
+    }
+}
+
+fn first_of_month(year: i32, month: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .single()
+        .expect("first instant of a month is valid in UTC")
+}
+
+/// Format in which a report is rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// Summary [`Report`] as Markdown
+    #[default]
+    Markdown,
+
+    /// Summary [`Report`] as an XHTML fragment
+    Html,
+
+    /// Emissions as JSON, see [`OutputFormat::Json`]
+    Json,
+
+    /// Emissions as CSV, see [`OutputFormat::Csv`]
+    Csv,
+
+    /// Emissions as a text table, see [`OutputFormat::Table`]
+    Table,
+}
+
+/// Where a report is delivered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum ReportDestination {
+    /// Write the rendered report to a file, replacing it
+    File {
+        /// Path of the file
+        path: PathBuf,
+    },
+
+    /// Export the queried emissions to a sink of the [`SinkRegistry`]
+    Sink {
+        /// Sink name, e.g. `jsonl`
+        sink: String,
+
+        /// Sink configuration
+        #[serde(default)]
+        config: Value,
+    },
+}
+
+impl ReportDefinition {
+    /// Definition of a Markdown report of `query`, not delivered anywhere
+    pub fn new(name: impl Into<String>, query: EmissionQuery) -> Self {
+        Self {
+            name: name.into(),
+            title: None,
+            query,
+            period: None,
+            group_by: Vec::new(),
+            format: ReportFormat::default(),
+            locale: Locale::default(),
+            precision: None,
+            #[cfg(feature = "templates")]
+            template: None,
+            destination: None,
+        }
+    }
+
+    /// The query run at `now`, with the relative period resolved
+    pub fn query_at(&self, now: DateTime<Utc>) -> EmissionQuery {
+        let mut query = self.query.clone();
+        if let Some(period) = &self.period {
+            query.time_period = period.resolve(now);
+        }
+        query
+    }
+
+    /// Query `source`, render the report and deliver it to the destination
+    ///
+    /// Returns the rendered report.
+    pub async fn run(&self, source: &dyn EmissionSource) -> Result<String> {
+        let emissions = source.query_emissions(&self.query_at(Utc::now())).await?;
+        let title = self.title.as_deref().unwrap_or(&self.name);
+        let rendered = match self.format {
+            ReportFormat::Markdown | ReportFormat::Html => {
+                let mut dataset = EmissionDataset::new(emissions.clone());
+                if let Some(policy) = self.precision {
+                    dataset = dataset.with_precision(policy);
+                }
+                let dimensions = if self.group_by.is_empty() {
+                    &DEFAULT_DIMENSIONS[..]
+                } else {
+                    &self.group_by
+                };
+                let report = Report::by_dimensions_in(title, &dataset, dimensions, self.locale);
+                #[cfg(feature = "templates")]
+                if let Some(path) = &self.template {
+                    crate::template::ReportTemplate::from_file(path)?.render(&report, &dataset)?
+                } else if self.format == ReportFormat::Html {
+                    report.to_html()
+                } else {
+                    report.to_markdown()
+                }
+                #[cfg(not(feature = "templates"))]
+                if self.format == ReportFormat::Html {
+                    report.to_html()
+                } else {
+                    report.to_markdown()
+                }
+            }
+            ReportFormat::Json => self.render_emissions(OutputFormat::Json, &emissions)?,
+            ReportFormat::Csv => self.render_emissions(OutputFormat::Csv, &emissions)?,
+            ReportFormat::Table => self.render_emissions(OutputFormat::Table, &emissions)?,
+        };
+
+        match &self.destination {
+            None => {}
+            Some(ReportDestination::File { path }) => {
+                fs::write(path, rendered.as_bytes()).await.map_err(|e| {
+                    CarbemError::Other(format!(
+                        "Failed to write report '{}' to {}: {}",
+                        self.name,
+                        path.display(),
+                        e
+                    ))
+                })?;
+            }
+            Some(ReportDestination::Sink { sink, config }) => {
+                let mut sink = SinkRegistry::new().create_sink(sink, config.clone())?;
+                sink.write(&emissions).await?;
+                sink.close().await?;
+            }
+        }
+        Ok(rendered)
+    }
+
+    fn render_emissions(
+        &self,
+        format: OutputFormat,
+        emissions: &[CarbonEmission],
+    ) -> Result<String> {
+        match &self.precision {
+            Some(policy) => format.render_with(emissions, policy),
+            None => format.render(emissions),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CarbemClient;
+
+    fn monthly_eu() -> ReportDefinition {
+        serde_json::from_value(serde_json::json!({
+            "name": "monthly-eu",
+            "query": {
+                "provider": "azure",
+                "regions": ["westeurope"],
+                "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-03-31T00:00:00Z"}
+            },
+            "group_by": ["region"]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_relative_periods() {
+        let now = Utc.with_ymd_and_hms(2025, 2, 14, 9, 30, 0).unwrap();
+
+        let period = ReportPeriod::PreviousMonths(3).resolve(now);
+        assert_eq!(
+            period.start,
+            Utc.with_ymd_and_hms(2024, 11, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            period.end,
+            Utc.with_ymd_and_hms(2025, 1, 31, 23, 59, 59).unwrap()
+        );
+
+        let period = ReportPeriod::YearToDate.resolve(now);
+        assert_eq!(
+            period.start,
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(period.end, now);
+
+        let mut definition = monthly_eu();
+        definition.period = Some(ReportPeriod::PreviousMonths(1));
+        assert_eq!(
+            definition.query_at(now).time_period.start,
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_report_by_name() {
+        let path = std::env::temp_dir().join(format!("carbem-report-{}.md", std::process::id()));
+        let mut definition = monthly_eu();
+        definition.destination = Some(ReportDestination::File { path: path.clone() });
+
+        let client = CarbemClient::demo().with_report(definition).unwrap();
+        assert_eq!(
+            client
+                .reports()
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["monthly-eu"]
+        );
+
+        let markdown = client.run_report("monthly-eu").await.unwrap();
+        assert!(markdown.starts_with("# monthly-eu\n"));
+        assert!(markdown.contains("## By region"));
+        assert!(!markdown.contains("## By provider"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), markdown);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(client.run_report("weekly").await.is_err());
+        assert!(
+            CarbemClient::demo()
+                .with_report(monthly_eu())
+                .unwrap()
+                .with_report(monthly_eu())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Async runtime used for timers, background and blocking tasks
+//!
+//! Carbem goes through the process-wide [`Runtime`] to sleep (device code
+//! polling, retries), to run background tasks
+//! ([`CarbemClient::subscribe`](crate::CarbemClient::subscribe)) and to run
+//! blocking work off the async threads: file access, SQLite statements and
+//! Kafka flushes. The default is Tokio, behind the default `tokio-runtime`
+//! feature. Without it, install a runtime with [`set_runtime`] at startup,
+//! before any of the above is used.
+//!
+//! This does not make carbem executor-agnostic: the default
+//! [`ReqwestTransport`](crate::transport::ReqwestTransport) runs on a Tokio
+//! reactor whatever the runtime, so replace it with a
+//! [`Transport`](crate::transport::Transport) built on the executor's HTTP
+//! client. The `jsonl` file sink of the
+//! [`SinkRegistry`](crate::sinks::SinkRegistry), the MCP stdio server and the
+//! Python bindings also require the `tokio-runtime` feature.
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::error::{CarbemError, Result};
+
+/// A boxed future run by a [`Runtime`]
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// A boxed blocking task run by a [`Runtime`]
+pub type BlockingTask = Box<dyn FnOnce() + Send + 'static>;
+
+static RUNTIME: OnceLock<Arc<dyn Runtime>> = OnceLock::new();
+
+/// Executor running carbem's timers, background and blocking tasks
+pub trait Runtime: Send + Sync + Debug {
+    /// Run `future` to completion in the background, detached
+    fn spawn(&self, future: BoxFuture);
+
+    /// Run `task` on a thread where blocking is allowed, detached
+    fn spawn_blocking(&self, task: BlockingTask);
+
+    /// A future completing after `duration`
+    fn sleep(&self, duration: Duration) -> BoxFuture;
+}
+
+/// The Tokio runtime the caller runs in
+#[cfg(feature = "tokio-runtime")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio-runtime")]
+impl Runtime for TokioRuntime {
+    fn spawn(&self, future: BoxFuture) {
+        tokio::spawn(future);
+    }
+
+    fn spawn_blocking(&self, task: BlockingTask) {
+        tokio::task::spawn_blocking(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Use `runtime` instead of Tokio, for the rest of the process
+///
+/// Fails if a runtime was already set.
+pub fn set_runtime(runtime: Arc<dyn Runtime>) -> Result<()> {
+    RUNTIME
+        .set(runtime)
+        .map_err(|_| CarbemError::Config("the async runtime is already set".to_string()))
+}
+
+/// The runtime set with [`set_runtime`], or [`TokioRuntime`]
+///
+/// # Panics
+///
+/// Without the `tokio-runtime` feature, panics if no runtime was set.
+pub fn runtime() -> Arc<dyn Runtime> {
+    if let Some(runtime) = RUNTIME.get() {
+        return runtime.clone();
+    }
+    #[cfg(feature = "tokio-runtime")]
+    return Arc::new(TokioRuntime);
+    #[cfg(not(feature = "tokio-runtime"))]
+    panic!("no async runtime: call carbem::set_runtime or enable the tokio-runtime feature");
+}
+
+// Run `task` through `runtime().spawn_blocking`, awaiting its result
+pub(crate) async fn spawn_blocking<T: Send + 'static>(
+    task: impl FnOnce() -> T + Send + 'static,
+) -> Result<T> {
+    let (sender, receiver) = oneshot::channel();
+    runtime().spawn_blocking(Box::new(move || {
+        // The caller may have stopped waiting
+        let _ = sender.send(task());
+    }));
+    receiver
+        .await
+        .map_err(|_| CarbemError::Other("blocking task panicked".to_string()))
+}
+
+// File access on blocking threads of the runtime, mirroring `std::fs`
+pub(crate) mod fs {
+    use std::io;
+    use std::path::Path;
+
+    use super::spawn_blocking;
+
+    async fn run<T: Send + 'static>(
+        operation: impl FnOnce() -> io::Result<T> + Send + 'static,
+    ) -> io::Result<T> {
+        spawn_blocking(operation).await.map_err(io::Error::other)?
+    }
+
+    pub(crate) async fn read(path: &Path) -> io::Result<Vec<u8>> {
+        let path = path.to_path_buf();
+        run(move || std::fs::read(path)).await
+    }
+
+    pub(crate) async fn write(path: &Path, contents: impl Into<Vec<u8>>) -> io::Result<()> {
+        let (path, contents) = (path.to_path_buf(), contents.into());
+        run(move || std::fs::write(path, contents)).await
+    }
+
+    pub(crate) async fn rename(from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (from.to_path_buf(), to.to_path_buf());
+        run(move || std::fs::rename(from, to)).await
+    }
+
+    pub(crate) async fn create_dir_all(path: &Path) -> io::Result<()> {
+        let path = path.to_path_buf();
+        run(move || std::fs::create_dir_all(path)).await
+    }
+}
@@ -0,0 +1,286 @@
+// Azure Carbon Optimization API answered from the bundled subscription
+
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use chrono::{Months, NaiveDate};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{error, month_index, months, respond, round};
+use crate::transport::HttpResponse;
+
+pub(super) const HOST: &str = "management.azure.com";
+pub(super) const REPORTS_PATH: &str = "/providers/Microsoft.Carbon/carbonEmissionReports";
+
+// Share of each emission scope in the fixture values
+const SCOPE_SHARES: [(&str, f64); 3] = [("Scope1", 0.02), ("Scope2", 0.71), ("Scope3", 0.27)];
+
+// Rows per page of item details when the request sets no page size
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct AzureFixture {
+    subscription: Value,
+    first_month: NaiveDate,
+    resources: Vec<Resource>,
+}
+
+// A resource of the subscription and its monthly emissions, null before it
+// was created or after it was deleted
+#[derive(Debug, Deserialize)]
+struct Resource {
+    name: String,
+    location: String,
+    resource_group: String,
+    resource_type: String,
+    monthly_kg_co2eq: Vec<Option<f64>>,
+}
+
+static FIXTURE: LazyLock<AzureFixture> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("azure.json")).expect("bundled Azure fixture is valid")
+});
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportRequest {
+    report_type: String,
+    subscription_list: Vec<String>,
+    carbon_scope_list: Vec<String>,
+    date_range: DateRange,
+    category_type: Option<String>,
+    top_items: Option<usize>,
+    order_by: Option<String>,
+    sort_direction: Option<String>,
+    page_size: Option<usize>,
+    location_list: Option<Vec<String>>,
+    resource_group_url_list: Option<Vec<String>>,
+    resource_type_list: Option<Vec<String>>,
+    skip_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DateRange {
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+impl ReportRequest {
+    fn selects(&self, resource: &Resource) -> bool {
+        let listed = |list: &Option<Vec<String>>, value: &str| {
+            list.as_ref()
+                .is_none_or(|list| list.iter().any(|item| item.eq_ignore_ascii_case(value)))
+        };
+        listed(&self.location_list, &resource.location)
+            && listed(&self.resource_type_list, &resource.resource_type)
+            && self.resource_group_url_list.as_ref().is_none_or(|urls| {
+                urls.iter().any(|url| {
+                    url.rsplit('/')
+                        .next()
+                        .is_some_and(|group| group.eq_ignore_ascii_case(&resource.resource_group))
+                })
+            })
+    }
+
+    // Fraction of the emissions in the requested scopes
+    fn scope_factor(&self) -> f64 {
+        SCOPE_SHARES
+            .iter()
+            .filter(|(scope, _)| self.carbon_scope_list.iter().any(|s| s == scope))
+            .map(|(_, share)| share)
+            .sum()
+    }
+
+    // Emissions of the selected resources in `month` by item of `category`,
+    // empty for months outside the fixture
+    fn items(&self, category: &str, month: NaiveDate) -> BTreeMap<String, f64> {
+        let mut items = BTreeMap::new();
+        let Some(index) = month_index(FIXTURE.first_month, month) else {
+            return items;
+        };
+        for resource in FIXTURE.resources.iter().filter(|r| self.selects(r)) {
+            let Some(Some(kg)) = resource.monthly_kg_co2eq.get(index) else {
+                continue;
+            };
+            let item = match category {
+                "Location" => resource.location.clone(),
+                "Resource" => resource.name.clone(),
+                "ResourceGroup" => resource.resource_group.clone(),
+                "ResourceType" => resource.resource_type.clone(),
+                _ => FIXTURE.subscription["subscriptionId"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            *items.entry(item).or_insert(0.0) += kg * self.scope_factor();
+        }
+        items
+    }
+
+    fn total(&self, month: NaiveDate) -> Option<f64> {
+        let items = self.items("Subscription", month);
+        (!items.is_empty()).then(|| items.values().sum())
+    }
+
+    // Items of `month` with their emissions the month before, largest first
+    fn item_rows(&self, category: &str, month: NaiveDate) -> Vec<(String, f64, f64)> {
+        let previous = self.items(category, month - Months::new(1));
+        let mut rows: Vec<(String, f64, f64)> = self
+            .items(category, month)
+            .into_iter()
+            .map(|(item, kg)| {
+                let before = previous.get(&item).copied().unwrap_or(0.0);
+                (item, kg, before)
+            })
+            .collect();
+        rows.sort_by(|a, b| b.1.total_cmp(&a.1));
+        rows
+    }
+}
+
+// Emission row of every report type
+fn row(data_type: &str, latest: f64, previous: f64) -> Value {
+    let ratio = if previous > 0.0 {
+        (latest - previous) / previous
+    } else {
+        0.0
+    };
+    json!({
+        "dataType": data_type,
+        "latestMonthEmissions": round(latest, 3),
+        "previousMonthEmissions": round(previous, 3),
+        "monthOverMonthEmissionsChangeRatio": round(ratio, 4),
+        "monthlyEmissionsChangeValue": round(latest - previous, 3),
+    })
+}
+
+fn item_row(data_type: &str, category: &str, item: &str, latest: f64, previous: f64) -> Value {
+    let mut row = row(data_type, latest, previous);
+    row["itemName"] = json!(item);
+    row["categoryType"] = json!(category);
+    row
+}
+
+// Answer a carbon emission report request
+pub(super) fn report(body: &[u8]) -> Option<HttpResponse> {
+    let request: ReportRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return Some(error(StatusCode::BAD_REQUEST, &e.to_string())),
+    };
+    let months = months(request.date_range.start, request.date_range.end);
+    let category = request.category_type.as_deref().unwrap_or("Subscription");
+
+    let mut skip_token = None;
+    let value: Vec<Value> = match request.report_type.as_str() {
+        "OverallSummaryReport" => {
+            // Compared to as many months before the range
+            let earlier = months.len() as u32;
+            let latest: f64 = months.iter().filter_map(|&m| request.total(m)).sum();
+            let previous: f64 = months
+                .iter()
+                .filter_map(|&m| request.total(m - Months::new(earlier)))
+                .sum();
+            vec![row("OverallSummaryData", latest, previous)]
+        }
+        "MonthlySummaryReport" => months
+            .iter()
+            .filter_map(|&month| {
+                let latest = request.total(month)?;
+                let previous = request.total(month - Months::new(1)).unwrap_or(0.0);
+                let mut row = row("MonthlySummaryData", latest, previous);
+                row["date"] = json!(month.format("%Y-%m-%d").to_string());
+                Some(row)
+            })
+            .collect(),
+        "TopItemsSummaryReport" | "TopItemsMonthlySummaryReport" => {
+            let monthly = request.report_type == "TopItemsMonthlySummaryReport";
+            let data_type = if monthly {
+                "TopItemsMonthlySummaryData"
+            } else {
+                "TopItemsSummaryData"
+            };
+            months
+                .iter()
+                .flat_map(|&month| {
+                    let mut rows = request.item_rows(category, month);
+                    rows.truncate(request.top_items.unwrap_or(5));
+                    rows.into_iter().map(move |(item, latest, previous)| {
+                        let mut row = item_row(data_type, category, &item, latest, previous);
+                        if monthly {
+                            row["date"] = json!(month.format("%Y-%m-%d").to_string());
+                        }
+                        row
+                    })
+                })
+                .collect()
+        }
+        "ItemDetailsReport" => {
+            let mut rows = months
+                .first()
+                .map(|&month| request.item_rows(category, month))
+                .unwrap_or_default();
+            let descending = request.sort_direction.as_deref() != Some("Asc");
+            rows.sort_by(|a, b| {
+                let order = match request.order_by.as_deref() {
+                    Some("ItemName") => a.0.cmp(&b.0),
+                    Some("PreviousMonthEmissions") => a.2.total_cmp(&b.2),
+                    Some("MonthlyEmissionsChangeValue") => (a.1 - a.2).total_cmp(&(b.1 - b.2)),
+                    _ => a.1.total_cmp(&b.1),
+                };
+                if descending { order.reverse() } else { order }
+            });
+            let offset: usize = request
+                .skip_token
+                .as_deref()
+                .and_then(|token| token.parse().ok())
+                .unwrap_or(0);
+            let page_size = request.page_size.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+            if offset + page_size < rows.len() {
+                skip_token = Some((offset + page_size).to_string());
+            }
+            let data_type = format!("{}ItemDetailsData", category);
+            rows.into_iter()
+                .skip(offset)
+                .take(page_size)
+                .map(|(item, latest, previous)| {
+                    item_row(&data_type, category, &item, latest, previous)
+                })
+                .collect()
+        }
+        other => {
+            return Some(error(
+                StatusCode::BAD_REQUEST,
+                &format!("unknown reportType {}", other),
+            ));
+        }
+    };
+
+    let decisions: Vec<Value> = request
+        .subscription_list
+        .iter()
+        .map(|id| json!({ "subscriptionId": id, "decision": "Allowed" }))
+        .collect();
+    let mut body = json!({ "subscriptionAccessDecisionList": decisions, "value": value });
+    if let Some(token) = skip_token {
+        body["skipToken"] = json!(token);
+    }
+    Some(respond(StatusCode::OK, &body))
+}
+
+// Answer the subscription list and permission requests
+pub(super) fn get(path: &str) -> Option<HttpResponse> {
+    if path == "/subscriptions" {
+        return Some(respond(
+            StatusCode::OK,
+            &json!({ "value": [FIXTURE.subscription], "nextLink": null }),
+        ));
+    }
+    path.ends_with("/providers/Microsoft.Authorization/permissions")
+        .then(|| {
+            respond(
+                StatusCode::OK,
+                &json!({ "value": [{ "actions": ["*"], "notActions": [] }] }),
+            )
+        })
+}
@@ -0,0 +1,222 @@
+// IBM Cloud Carbon Calculator and Enterprise Management APIs answered from
+// the bundled enterprise
+
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use chrono::NaiveDate;
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{error, month_index, months, respond, round};
+use crate::transport::HttpResponse;
+
+pub(super) const CARBON_HOST: &str = "api.carbon-calculator.cloud.ibm.com";
+pub(super) const EMISSIONS_PATH: &str = "/v1/carbon_emissions";
+pub(super) const ENTERPRISE_HOST: &str = "enterprise.cloud.ibm.com";
+
+// Results per page when the request sets no limit, as the API does
+const DEFAULT_LIMIT: usize = 10;
+
+#[derive(Debug, Deserialize)]
+struct IbmFixture {
+    account_groups: Vec<Value>,
+    accounts: Vec<Value>,
+    first_month: String,
+    series: Vec<Series>,
+}
+
+// Monthly emissions (g CO2e) and energy (Wh) of a service of an account in a location
+#[derive(Debug, Deserialize)]
+struct Series {
+    account_id: String,
+    location: String,
+    service: String,
+    carbon_emission_g: Vec<Option<f64>>,
+    energy_consumption_wh: Vec<Option<f64>>,
+}
+
+static FIXTURE: LazyLock<IbmFixture> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("ibm.json")).expect("bundled IBM fixture is valid")
+});
+
+fn first_month() -> NaiveDate {
+    NaiveDate::parse_from_str(&format!("{}-01", FIXTURE.first_month), "%Y-%m-%d")
+        .expect("bundled IBM fixture starts on a valid month")
+}
+
+// Comma-separated filter values of parameter `name`
+fn list(url: &Url, name: &str) -> Option<Vec<String>> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.split(',').map(|v| v.trim().to_string()).collect())
+}
+
+fn param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+// Month range of the `month=gte:YYYY-MM` and `month=lte:YYYY-MM` filters,
+// the whole fixture when not set
+fn month_range(url: &Url) -> Option<(NaiveDate, NaiveDate)> {
+    let first = first_month();
+    let mut start = first;
+    let mut end = first + chrono::Months::new(FIXTURE.series[0].carbon_emission_g.len() as u32 - 1);
+    for (key, value) in url.query_pairs() {
+        if key != "month" {
+            continue;
+        }
+        let (operator, month) = value.split_once(':')?;
+        let month = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").ok()?;
+        match operator {
+            "gte" => start = month,
+            "lte" => end = month,
+            _ => return None,
+        }
+    }
+    Some((start, end))
+}
+
+// Answer a carbon emissions request
+pub(super) fn emissions(url: &Url) -> Option<HttpResponse> {
+    if param(url, "enterprise_id").is_none() {
+        return Some(error(StatusCode::BAD_REQUEST, "enterprise_id is required"));
+    }
+    let Some((start, end)) = month_range(url) else {
+        return Some(error(StatusCode::BAD_REQUEST, "invalid month filter"));
+    };
+    let account = param(url, "enterprise_account_id");
+    let locations = list(url, "locations");
+    let services = list(url, "services");
+    let group_by = param(url, "group_by");
+    if group_by
+        .as_deref()
+        .is_some_and(|g| !["month", "location", "service", "account"].contains(&g))
+    {
+        return Some(error(StatusCode::BAD_REQUEST, "invalid group_by"));
+    }
+
+    let selected = FIXTURE.series.iter().filter(|series| {
+        account.as_ref().is_none_or(|id| *id == series.account_id)
+            && locations
+                .as_ref()
+                .is_none_or(|locations| locations.contains(&series.location))
+            && services
+                .as_ref()
+                .is_none_or(|services| services.contains(&series.service))
+    });
+
+    // Rows by month and group, summed when grouped
+    let mut rows: BTreeMap<(String, String), Value> = BTreeMap::new();
+    for series in selected {
+        for month in months(start, end) {
+            let Some(index) = month_index(first_month(), month) else {
+                continue;
+            };
+            let (Some(Some(grams)), Some(Some(wh))) = (
+                series.carbon_emission_g.get(index),
+                series.energy_consumption_wh.get(index),
+            ) else {
+                continue;
+            };
+            let month = month.format("%Y-%m").to_string();
+            let group = match group_by.as_deref() {
+                Some("month") => month.clone(),
+                Some("location") => series.location.clone(),
+                Some("service") => series.service.clone(),
+                Some(_) => series.account_id.clone(),
+                None => format!(
+                    "{}/{}/{}",
+                    series.account_id, series.location, series.service
+                ),
+            };
+            let row = rows
+                .entry((month.clone(), group.clone()))
+                .or_insert_with(|| {
+                    let mut row = json!({
+                        "account_id": account.as_deref().unwrap_or(&series.account_id),
+                        "carbon_emission": 0.0,
+                        "energy_consumption": 0.0,
+                        "month": { "value": month },
+                    });
+                    match group_by.as_deref() {
+                        Some(group_type) => {
+                            row["group_by"] = json!({ "type": group_type, "value": group });
+                            if group_type == "account" {
+                                row["account_id"] = json!(group);
+                            }
+                        }
+                        None => {
+                            row["location"] = json!(series.location);
+                            row["service"] = json!(series.service);
+                        }
+                    }
+                    row
+                });
+            row["carbon_emission"] = json!(round(
+                row["carbon_emission"].as_f64().unwrap_or(0.0) + grams,
+                1
+            ));
+            row["energy_consumption"] = json!(round(
+                row["energy_consumption"].as_f64().unwrap_or(0.0) + wh,
+                1
+            ));
+        }
+    }
+
+    let total_count = rows.len();
+    let total_emission: f64 = rows
+        .values()
+        .filter_map(|row| row["carbon_emission"].as_f64())
+        .sum();
+    let limit = param(url, "limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_LIMIT)
+        .max(1);
+    let offset: usize = param(url, "offset")
+        .and_then(|offset| offset.parse().ok())
+        .unwrap_or(0);
+    let page: Vec<Value> = rows.into_values().skip(offset).take(limit).collect();
+
+    let link = |offset: usize| {
+        let mut link = url.clone();
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, _)| key != "offset")
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        link.query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair("offset", &offset.to_string());
+        json!({ "href": link.as_str() })
+    };
+    let mut body = json!({
+        "carbon_emissions": page,
+        "total_emission": round(total_emission, 1),
+        "offset": offset,
+        "limit": limit,
+        "total_count": total_count,
+        "first": link(0),
+    });
+    if offset + limit < total_count {
+        body["next"] = link(offset + limit);
+    }
+    Some(respond(StatusCode::OK, &body))
+}
+
+// Answer the account group and account lists of the enterprise
+pub(super) fn enterprise(path: &str) -> Option<HttpResponse> {
+    let resources = match path {
+        "/v1/account-groups" => &FIXTURE.account_groups,
+        "/v1/accounts" => &FIXTURE.accounts,
+        _ => return None,
+    };
+    Some(respond(
+        StatusCode::OK,
+        &json!({ "resources": resources, "next_url": null }),
+    ))
+}
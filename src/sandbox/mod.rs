@@ -0,0 +1,212 @@
+//! Sandbox mode answering provider requests from bundled fixtures
+//!
+//! With `CARBEM_SANDBOX=1`, every client sends its provider requests to a
+//! [`SandboxTransport`] instead of the network. The transport answers them
+//! the way the Azure and IBM Cloud APIs would, from fixtures bundled with
+//! carbem: 24 months (2023 and 2024) of an Azure subscription and of an IBM
+//! Cloud enterprise with two accounts. Unlike [`CarbemClient::demo`], the
+//! providers still build their requests, parse the responses and convert
+//! the rows, so demos, integration tests and the CI of bindings exercise the
+//! real code paths, deterministically and without credentials.
+//!
+//! Any credential is accepted, but requests must carry one, as they would
+//! against the real APIs. In sandbox mode, providers configured from the
+//! environment fall back to [`SANDBOX_CREDENTIAL`] when theirs is not set.
+//!
+//! [`CarbemClient::demo`]: crate::CarbemClient::demo
+
+mod azure;
+mod ibm;
+
+use async_trait::async_trait;
+use chrono::{Datelike, Months, NaiveDate};
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, StatusCode, Url};
+use serde_json::{Value, json};
+
+use crate::error::{CarbemError, Result};
+use crate::logging::debug;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+
+/// Environment variable enabling sandbox mode when set to `1`, `true` or `yes`
+pub const SANDBOX_ENV: &str = "CARBEM_SANDBOX";
+
+/// Credential of providers configured from an environment without one, in sandbox mode
+pub const SANDBOX_CREDENTIAL: &str = "carbem-sandbox";
+
+/// Whether [`SANDBOX_ENV`] enables sandbox mode
+pub fn sandbox_enabled() -> bool {
+    std::env::var(SANDBOX_ENV).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes"
+        )
+    })
+}
+
+/// Transport answering Azure and IBM Cloud requests from the bundled fixtures
+///
+/// Used by every client in sandbox mode; it can also be passed to
+/// [`CarbemClientBuilder::with_transport`](crate::CarbemClientBuilder::with_transport)
+/// to sandbox a single client. Requests to other hosts get a 404 response.
+#[derive(Debug, Clone, Default)]
+pub struct SandboxTransport;
+
+impl SandboxTransport {
+    /// Create a sandbox transport
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Transport for SandboxTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let url = Url::parse(&request.url)
+            .map_err(|e| CarbemError::Config(format!("Invalid URL {}: {}", request.url, e)))?;
+        debug!("Sandbox answering {} {}", request.method, url.path());
+
+        // Connection warm-ups need no credentials
+        if request.method == Method::HEAD {
+            return Ok(HttpResponse::new(StatusCode::OK, Vec::new()));
+        }
+        if !request.headers.contains_key(AUTHORIZATION) {
+            return Ok(error(
+                StatusCode::UNAUTHORIZED,
+                "the request carries no credentials",
+            ));
+        }
+
+        let host = url.host_str().unwrap_or_default();
+        Ok(match (request.method.clone(), host) {
+            (Method::POST, azure::HOST) if url.path() == azure::REPORTS_PATH => {
+                azure::report(request.body.as_deref().unwrap_or_default())
+            }
+            (Method::GET, azure::HOST) => azure::get(url.path()),
+            (Method::GET, ibm::CARBON_HOST) if url.path() == ibm::EMISSIONS_PATH => {
+                ibm::emissions(&url)
+            }
+            (Method::GET, ibm::ENTERPRISE_HOST) => ibm::enterprise(url.path()),
+            _ => None,
+        }
+        .unwrap_or_else(|| {
+            error(
+                StatusCode::NOT_FOUND,
+                &format!("no sandbox fixture for {} {}", request.method, url.as_str()),
+            )
+        }))
+    }
+}
+
+// JSON response with `status`
+fn respond(status: StatusCode, body: &Value) -> HttpResponse {
+    HttpResponse::new(status, body.to_string())
+}
+
+// Error response shaped like the ones of both APIs
+fn error(status: StatusCode, message: &str) -> HttpResponse {
+    respond(
+        status,
+        &json!({ "error": { "code": status.as_u16(), "message": message } }),
+    )
+}
+
+// Position of the month of `date` in a fixture starting on `first_month`
+fn month_index(first_month: NaiveDate, date: NaiveDate) -> Option<usize> {
+    let months = (date.year() - first_month.year()) * 12 + date.month0() as i32
+        - first_month.month0() as i32;
+    usize::try_from(months).ok()
+}
+
+// First days of the months from `start` to `end`, both included
+fn months(start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+    let mut months = Vec::new();
+    let mut month = start.with_day(1).unwrap_or(start);
+    while month <= end {
+        months.push(month);
+        month = month + Months::new(1);
+    }
+    months
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CarbemClient;
+    use crate::models::EmissionQuery;
+    use std::sync::Arc;
+
+    async fn sandboxed(provider: &str, query: &str) -> Result<Vec<crate::CarbonEmission>> {
+        let transport: Arc<dyn Transport> = Arc::new(SandboxTransport::new());
+        let builder = CarbemClient::builder().with_transport(transport);
+        let client = match provider {
+            "azure" => builder
+                .with_azure(crate::AzureConfig {
+                    access_token: SANDBOX_CREDENTIAL.to_string(),
+                })?
+                .build(),
+            _ => builder
+                .with_ibm(crate::IbmConfig {
+                    api_key: SANDBOX_CREDENTIAL.to_string(),
+                })?
+                .build(),
+        };
+        client
+            .query_emissions(&EmissionQuery::from_query_string(query)?)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_providers_convert_sandbox_responses() {
+        let monthly = sandboxed(
+            "azure",
+            "provider=azure&regions=westeurope&start=2024-01-01&end=2024-06-30&config.report_type=MonthlySummaryReport&config.subscription_list=00000000-0000-0000-0000-000000000000",
+        )
+        .await
+        .unwrap();
+        assert_eq!(monthly.len(), 6);
+        assert!(monthly.iter().all(|e| e.emissions_kg_co2eq > 0.0));
+
+        let locations = sandboxed(
+            "azure",
+            "provider=azure&regions=westeurope&start=2024-03-01&end=2024-03-31&config.report_type=ItemDetailsReport&config.category_type=Location&config.order_by=LatestMonthEmissions&config.page_size=10&config.sort_direction=Desc&config.subscription_list=00000000-0000-0000-0000-000000000000",
+        )
+        .await
+        .unwrap();
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].region, "westeurope");
+        let march = monthly
+            .iter()
+            .find(|e| e.time_period.start.month() == 3)
+            .unwrap();
+        assert!((locations[0].emissions_kg_co2eq - march.emissions_kg_co2eq).abs() < 1e-6);
+
+        let ibm = sandboxed(
+            "ibm",
+            "provider=ibm&regions=us-south&start=2023-11-01&end=2024-02-29&config.enterprise_id=sandbox-enterprise&config.group_by=service&config.limit=100",
+        )
+        .await
+        .unwrap();
+        // Three services in us-south over four months
+        assert_eq!(ibm.len(), 12);
+        assert!(
+            ibm.iter()
+                .all(|e| e.metadata.as_ref().unwrap().energy_kwh > Some(0.0))
+        );
+
+        let unauthorized = SandboxTransport::new()
+            .send(HttpRequest::new(
+                Method::GET,
+                "https://api.carbon-calculator.cloud.ibm.com/v1/carbon_emissions",
+                Default::default(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status, StatusCode::UNAUTHORIZED);
+    }
+}
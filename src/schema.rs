@@ -0,0 +1,309 @@
+//! Detection of provider response schema drift
+//!
+//! Provider responses are inspected against the fields carbem knows about
+//! before being deserialized. Unknown fields and missing expected fields are
+//! recorded as [`SchemaWarning`]s, available from
+//! [`CarbemClient::schema_warnings`](crate::CarbemClient::schema_warnings).
+//! In lenient mode, missing required fields are filled with neutral defaults
+//! instead of failing the whole query. Responses that still cannot be
+//! deserialized fail with an error naming the offending field.
+
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{CarbemError, Result};
+use crate::logging::warn;
+
+/// Kind of difference between a provider response and the expected schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaWarningKind {
+    /// The response contains a field carbem does not know about
+    UnknownField,
+    /// The response lacks a field carbem expects
+    MissingField,
+}
+
+/// A single schema difference observed in a provider response
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SchemaWarning {
+    /// Provider that returned the response
+    pub provider: String,
+
+    /// Path of the field, e.g. `value[].latestMonthEmissions`
+    pub path: String,
+
+    /// Kind of difference
+    pub kind: SchemaWarningKind,
+}
+
+/// Value inserted by lenient parsing in place of a missing required field
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum FieldDefault {
+    Zero,
+    EmptyString,
+    EmptyArray,
+    EmptyObject,
+}
+
+impl FieldDefault {
+    fn value(self) -> Value {
+        match self {
+            FieldDefault::Zero => Value::from(0.0),
+            FieldDefault::EmptyString => Value::String(String::new()),
+            FieldDefault::EmptyArray => Value::Array(Vec::new()),
+            FieldDefault::EmptyObject => Value::Object(serde_json::Map::new()),
+        }
+    }
+}
+
+/// A field expected in a provider response object
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FieldSpec {
+    pub(crate) name: &'static str,
+    pub(crate) required: Option<FieldDefault>,
+}
+
+impl FieldSpec {
+    pub(crate) const fn optional(name: &'static str) -> Self {
+        Self {
+            name,
+            required: None,
+        }
+    }
+
+    pub(crate) const fn required(name: &'static str, default: FieldDefault) -> Self {
+        Self {
+            name,
+            required: Some(default),
+        }
+    }
+}
+
+/// Compare a response object against its expected fields
+///
+/// Non-object values are left untouched, deserialization reports them.
+pub(crate) fn inspect_object(
+    provider: &str,
+    path: &str,
+    value: &mut Value,
+    fields: &[FieldSpec],
+    lenient: bool,
+    warnings: &mut Vec<SchemaWarning>,
+) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+
+    let field_path = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+
+    for key in object.keys() {
+        if !fields.iter().any(|field| field.name == key) {
+            warnings.push(SchemaWarning {
+                provider: provider.to_string(),
+                path: field_path(key),
+                kind: SchemaWarningKind::UnknownField,
+            });
+        }
+    }
+
+    for field in fields {
+        let Some(default) = field.required else {
+            continue;
+        };
+        if object.get(field.name).is_none_or(Value::is_null) {
+            warnings.push(SchemaWarning {
+                provider: provider.to_string(),
+                path: field_path(field.name),
+                kind: SchemaWarningKind::MissingField,
+            });
+            if lenient {
+                object.insert(field.name.to_string(), default.value());
+            }
+        }
+    }
+}
+
+/// Parse a response body of `provider` as JSON
+pub(crate) fn parse_body(provider: &str, body: &str) -> Result<Value> {
+    serde_json::from_str(body).map_err(|e| {
+        CarbemError::Api(format!(
+            "{} response is not valid JSON ({} bytes): {}",
+            provider,
+            body.len(),
+            e
+        ))
+    })
+}
+
+/// Deserialize an inspected response of `provider`, naming the offending field on failure
+///
+/// E.g. `value[2].latestMonthEmissions: invalid type: string "n/a", expected f64`
+/// instead of serde's bare message.
+pub(crate) fn deserialize<T: DeserializeOwned>(provider: &str, value: Value) -> Result<T> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        CarbemError::Api(format!(
+            "unexpected {} response at {}: {}",
+            provider,
+            e.path(),
+            e.inner()
+        ))
+    })
+}
+
+/// Deduplicated, shareable record of schema warnings seen by a provider
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SchemaLog {
+    warnings: Arc<Mutex<Vec<SchemaWarning>>>,
+}
+
+impl SchemaLog {
+    /// Record new warnings, logging each distinct one the first time it is seen
+    pub(crate) fn record(&self, warnings: Vec<SchemaWarning>) {
+        let mut recorded = self
+            .warnings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for warning in warnings {
+            if !recorded.contains(&warning) {
+                warn!(
+                    "{} response schema drift: {:?} at '{}'",
+                    warning.provider, warning.kind, warning.path
+                );
+                recorded.push(warning);
+            }
+        }
+    }
+
+    /// All distinct warnings recorded so far
+    pub(crate) fn snapshot(&self) -> Vec<SchemaWarning> {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+/// Proptest generators of malformed provider responses
+#[cfg(test)]
+pub(crate) mod strategies {
+    use proptest::prelude::*;
+    use serde_json::Value;
+
+    use super::FieldSpec;
+
+    /// Any JSON value, up to a few levels deep
+    pub(crate) fn json() -> impl Strategy<Value = Value> {
+        scalar().prop_recursive(3, 24, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+                prop::collection::btree_map("[a-zA-Z]{1,12}", inner, 0..4)
+                    .prop_map(|fields| Value::Object(fields.into_iter().collect())),
+            ]
+        })
+    }
+
+    // Scalars, with the dates, numbers and text providers send
+    fn scalar() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::from),
+            any::<i64>().prop_map(Value::from),
+            (-1e12f64..1e12).prop_map(Value::from),
+            "[0-9]{1,4}-[0-9]{1,2}(-[0-9]{1,2})?".prop_map(Value::from),
+            ".{0,16}".prop_map(Value::from),
+        ]
+    }
+
+    /// `valid` with some of its `fields` removed or replaced by any JSON value
+    pub(crate) fn mutated(
+        valid: Value,
+        fields: &'static [FieldSpec],
+    ) -> impl Strategy<Value = Value> {
+        let change = prop::option::weighted(0.3, prop::option::of(json()));
+        prop::collection::vec(change, fields.len()).prop_map(move |changes| {
+            let mut value = valid.clone();
+            let object = value.as_object_mut().expect("valid rows are objects");
+            for (field, change) in fields.iter().zip(changes) {
+                match change {
+                    None => {}
+                    Some(None) => {
+                        object.remove(field.name);
+                    }
+                    Some(Some(replacement)) => {
+                        object.insert(field.name.to_string(), replacement);
+                    }
+                }
+            }
+            value
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const FIELDS: &[FieldSpec] = &[
+        FieldSpec::required("emissions", FieldDefault::Zero),
+        FieldSpec::optional("date"),
+    ];
+
+    #[test]
+    fn test_inspect_object_reports_unknown_and_missing_fields() {
+        let mut value = json!({"date": "2024-01-01", "newField": 1});
+        let mut warnings = Vec::new();
+
+        inspect_object("azure", "value[]", &mut value, FIELDS, false, &mut warnings);
+
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.contains(&SchemaWarning {
+            provider: "azure".to_string(),
+            path: "value[].newField".to_string(),
+            kind: SchemaWarningKind::UnknownField,
+        }));
+        assert!(warnings.contains(&SchemaWarning {
+            provider: "azure".to_string(),
+            path: "value[].emissions".to_string(),
+            kind: SchemaWarningKind::MissingField,
+        }));
+        // Strict mode leaves the value untouched
+        assert!(value.get("emissions").is_none());
+    }
+
+    #[test]
+    fn test_inspect_object_lenient_fills_defaults() {
+        let mut value = json!({"date": "2024-01-01"});
+        let mut warnings = Vec::new();
+
+        inspect_object("ibm", "", &mut value, FIELDS, true, &mut warnings);
+
+        assert_eq!(warnings[0].path, "emissions");
+        assert_eq!(value["emissions"], 0.0);
+    }
+
+    #[test]
+    fn test_schema_log_deduplicates() {
+        let log = SchemaLog::default();
+        let warning = SchemaWarning {
+            provider: "ibm".to_string(),
+            path: "carbon_emissions[].extra".to_string(),
+            kind: SchemaWarningKind::UnknownField,
+        };
+
+        log.record(vec![warning.clone(), warning.clone()]);
+        log.clone().record(vec![warning]);
+
+        assert_eq!(log.snapshot().len(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+//! [`tower::Service`](tower_service::Service) adapter for the client
+//!
+//! With the `tower` feature, [`CarbemClient`] implements
+//! `Service<EmissionQuery>`, answering each query with
+//! [`query_emissions`](CarbemClient::query_emissions). It composes with
+//! standard tower middleware (retry, rate limit, timeout, tracing), which
+//! can replace the client's own equivalents:
+//!
+//! ```ignore
+//! use std::time::Duration;
+//! use tower::{ServiceBuilder, ServiceExt};
+//!
+//! let mut service = ServiceBuilder::new()
+//!     .timeout(Duration::from_secs(30))
+//!     .rate_limit(10, Duration::from_secs(1))
+//!     .service(client);
+//! let emissions = service.ready().await?.call(query).await?;
+//! ```
+//!
+//! The client is always ready: concurrency limits set on the builder still
+//! apply inside `call`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tower_service::Service;
+
+use crate::client::CarbemClient;
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, EmissionQuery};
+
+/// Future returned by the [`Service`] implementation of [`CarbemClient`]
+pub type EmissionsFuture = Pin<Box<dyn Future<Output = Result<Vec<CarbonEmission>>> + Send>>;
+
+impl Service<EmissionQuery> for CarbemClient {
+    type Response = Vec<CarbonEmission>;
+    type Error = CarbemError;
+    type Future = EmissionsFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, query: EmissionQuery) -> Self::Future {
+        let client = self.clone();
+        Box::pin(async move { client.query_emissions(&query).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tower::{ServiceBuilder, ServiceExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_client_under_tower_middleware() {
+        let query: EmissionQuery = serde_json::from_value(serde_json::json!({
+            "provider": "azure",
+            "regions": ["westeurope"],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-02-29T00:00:00Z"}
+        }))
+        .unwrap();
+        let expected = CarbemClient::demo().query_emissions(&query).await.unwrap();
+
+        let service = ServiceBuilder::new()
+            .timeout(Duration::from_secs(5))
+            .concurrency_limit(1)
+            .service(CarbemClient::demo());
+        let emissions = service.oneshot(query).await.unwrap();
+        assert_eq!(emissions.len(), expected.len());
+        assert!(!emissions.is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+//! Compression of file exports
+//!
+//! JSON Lines and CSV files are compressed as a whole, while Parquet files
+//! compress their pages with the matching codec.
+
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::jsonl::io_error;
+use crate::error::Result;
+
+/// Compression applied to exported files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    /// Uncompressed output
+    #[default]
+    None,
+
+    /// gzip, readable by virtually every tool
+    Gzip,
+
+    /// Zstandard, smaller and faster than gzip
+    Zstd,
+}
+
+impl Compression {
+    /// Compression implied by the extension of `path`: `.gz` or `.zst`
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".gz") {
+            Compression::Gzip
+        } else if path.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// File extension of compressed files, including the dot, or `""`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Wrap `writer` so that everything written to it is compressed
+    ///
+    /// The compressed stream is only complete once the writer is shut down,
+    /// e.g. by [`EmissionSink::close`](super::EmissionSink::close).
+    pub fn encoder<'a, W>(self, writer: W) -> Box<dyn AsyncWrite + Unpin + Send + 'a>
+    where
+        W: AsyncWrite + Unpin + Send + 'a,
+    {
+        match self {
+            Compression::None => Box::new(writer),
+            Compression::Gzip => Box::new(GzipEncoder::new(writer)),
+            Compression::Zstd => Box::new(ZstdEncoder::new(writer)),
+        }
+    }
+
+    /// Compress `data` in memory
+    pub async fn compress(self, data: Vec<u8>) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let mut encoder = self.encoder(&mut output);
+        encoder.write_all(&data).await.map_err(io_error)?;
+        encoder.shutdown().await.map_err(io_error)?;
+        drop(encoder);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Compression::from_path("out.jsonl.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("out.jsonl.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_path("out.jsonl"), Compression::None);
+        assert_eq!(Compression::Zstd.extension(), ".zst");
+    }
+
+    #[tokio::test]
+    async fn test_compress_round_trip() {
+        let data = b"{\"region\":\"westeurope\"}\n".repeat(100);
+
+        let gzip = Compression::Gzip.compress(data.clone()).await.unwrap();
+        assert!(gzip.len() < data.len());
+        let mut decoded = Vec::new();
+        GzipDecoder::new(&gzip[..])
+            .read_to_end(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        let zstd = Compression::Zstd.compress(data.clone()).await.unwrap();
+        let mut decoded = Vec::new();
+        ZstdDecoder::new(&zstd[..])
+            .read_to_end(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        assert_eq!(
+            Compression::None.compress(data.clone()).await.unwrap(),
+            data
+        );
+    }
+}
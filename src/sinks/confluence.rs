@@ -0,0 +1,212 @@
+//! Confluence sink publishing summary reports (requires the `confluence` feature)
+//!
+//! Emissions are buffered and, on flush, rendered as a [`Report`] published
+//! as a new Confluence page, e.g. by a monthly job.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::EmissionSink;
+use crate::aggregation::EmissionDataset;
+use crate::error::{CarbemError, Result};
+use crate::models::CarbonEmission;
+use crate::redact::Redactor;
+use crate::report::Report;
+
+/// Configuration of the Confluence sink
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ConfluenceSinkConfig {
+    /// Site URL, e.g. `https://example.atlassian.net`
+    pub base_url: String,
+
+    /// Key of the space pages are created in
+    pub space_key: String,
+
+    /// Optional: ID of the parent page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_page_id: Option<String>,
+
+    /// Page title; the covered period is appended to keep titles unique
+    #[serde(default = "default_title")]
+    pub title: String,
+
+    /// Account e-mail
+    pub email: String,
+
+    /// API token of the account
+    pub api_token: String,
+}
+
+fn default_title() -> String {
+    "Cloud carbon emissions".to_string()
+}
+
+// Never expose the API token in debug output
+impl std::fmt::Debug for ConfluenceSinkConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfluenceSinkConfig")
+            .field("base_url", &self.base_url)
+            .field("space_key", &self.space_key)
+            .field("parent_page_id", &self.parent_page_id)
+            .field("title", &self.title)
+            .field("email", &self.email)
+            .field("api_token", &crate::redact::REDACTED)
+            .finish()
+    }
+}
+
+/// Publishes a report page to Confluence on every flush
+pub struct ConfluenceSink {
+    config: ConfluenceSinkConfig,
+    http_client: reqwest::Client,
+    buffer: Vec<CarbonEmission>,
+}
+
+impl ConfluenceSink {
+    /// Create a sink publishing to the space of `config`
+    pub fn new(config: ConfluenceSinkConfig) -> Result<Self> {
+        if config.base_url.trim().is_empty() || config.space_key.trim().is_empty() {
+            return Err(CarbemError::Config(
+                "Confluence base_url and space_key are required".to_string(),
+            ));
+        }
+        Ok(Self {
+            config,
+            http_client: reqwest::Client::new(),
+            buffer: Vec::new(),
+        })
+    }
+
+    // Request body creating a page with the report in storage format
+    fn page_body(&self, report: &Report) -> Value {
+        let title = match report.period {
+            Some((start, end)) => format!(
+                "{} ({} to {})",
+                self.config.title,
+                start.format("%Y-%m-%d"),
+                end.format("%Y-%m-%d")
+            ),
+            None => self.config.title.clone(),
+        };
+        let mut body = json!({
+            "type": "page",
+            "title": title,
+            "space": { "key": self.config.space_key },
+            "body": {
+                "storage": { "value": report.to_html(), "representation": "storage" }
+            },
+        });
+        if let Some(parent) = &self.config.parent_page_id {
+            body["ancestors"] = json!([{ "id": parent }]);
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl EmissionSink for ConfluenceSink {
+    fn name(&self) -> &'static str {
+        "confluence"
+    }
+
+    async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+        self.buffer.extend_from_slice(emissions);
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let dataset = EmissionDataset::new(self.buffer.clone());
+        let report = Report::from_dataset(self.config.title.clone(), &dataset);
+        let url = format!(
+            "{}/wiki/rest/api/content",
+            self.config.base_url.trim_end_matches('/')
+        );
+        let response = self
+            .http_client
+            .post(&url)
+            .basic_auth(&self.config.email, Some(&self.config.api_token))
+            .json(&self.page_body(&report))
+            .send()
+            .await
+            .map_err(CarbemError::Http)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            let redactor = Redactor::new().with_secret(self.config.api_token.clone());
+            return Err(CarbemError::Other(format!(
+                "Confluence page creation failed with status {}: {}",
+                status,
+                redactor.redact(&body)
+            )));
+        }
+
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::{TimeZone, Utc};
+
+    fn config() -> ConfluenceSinkConfig {
+        serde_json::from_value(json!({
+            "base_url": "https://example.atlassian.net",
+            "space_key": "GREEN",
+            "parent_page_id": "42",
+            "email": "ops@example.com",
+            "api_token": "secret-token"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_page_body() {
+        let sink = ConfluenceSink::new(config()).unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let dataset = EmissionDataset::new(vec![CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.0,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }]);
+
+        let body = sink.page_body(&Report::from_dataset("Report", &dataset));
+        assert_eq!(
+            body["title"],
+            "Cloud carbon emissions (2024-01-01 to 2024-02-01)"
+        );
+        assert_eq!(body["space"]["key"], "GREEN");
+        assert_eq!(body["ancestors"][0]["id"], "42");
+        assert!(
+            body["body"]["storage"]["value"]
+                .as_str()
+                .unwrap()
+                .contains("<table>")
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        assert!(!format!("{:?}", config()).contains("secret-token"));
+
+        let mut missing_space = config();
+        missing_space.space_key = String::new();
+        assert!(ConfluenceSink::new(missing_space).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! CSV sink: a header row, then one flat emission record per line
+
+use async_trait::async_trait;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+
+use super::EmissionSink;
+use super::jsonl::io_error;
+use crate::error::Result;
+use crate::models::CarbonEmission;
+use crate::output::OutputFormat;
+
+/// Writes emissions as CSV to any async writer, with the columns of
+/// [`OutputFormat::Csv`]
+pub struct CsvSink<W: AsyncWrite + Unpin + Send> {
+    writer: BufWriter<W>,
+    header_written: bool,
+}
+
+impl<W: AsyncWrite + Unpin + Send> CsvSink<W> {
+    /// Create a sink writing to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+            header_written: false,
+        }
+    }
+
+    /// Close the output and return the underlying writer
+    pub async fn into_inner(mut self) -> Result<W> {
+        self.close().await?;
+        Ok(self.writer.into_inner())
+    }
+}
+
+#[async_trait]
+impl<W: AsyncWrite + Unpin + Send> EmissionSink for CsvSink<W> {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+        let csv = OutputFormat::Csv.render(emissions)?;
+        // Every batch renders the header, only the first one keeps it
+        let rows = if self.header_written {
+            csv.split_once('\n').map_or("", |(_, rows)| rows)
+        } else {
+            &csv
+        };
+        self.writer
+            .write_all(rows.as_bytes())
+            .await
+            .map_err(io_error)?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.writer.flush().await.map_err(io_error)
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.writer.shutdown().await.map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::{TimeZone, Utc};
+
+    fn emission() -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: Some("Storage".to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.5,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_csv() {
+        let mut sink = CsvSink::new(Vec::new());
+        sink.write(&[emission()]).await.unwrap();
+        sink.write(&[]).await.unwrap();
+        sink.write(&[emission()]).await.unwrap();
+
+        let output = String::from_utf8(sink.into_inner().await.unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("provider,region,service,"));
+        assert_eq!(lines[1], lines[2]);
+    }
+}
@@ -0,0 +1,187 @@
+//! Google Sheets sink appending monthly summaries (requires the `google-sheets` feature)
+//!
+//! Emissions are buffered and, on flush, appended to a sheet as one row per
+//! month, provider and service: `month | provider | service | kg CO2e`.
+
+use async_trait::async_trait;
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::EmissionSink;
+use crate::aggregation::{Dimension, EmissionDataset};
+use crate::error::{CarbemError, Result};
+use crate::models::CarbonEmission;
+use crate::redact::Redactor;
+
+const SHEETS_API_BASE_URL: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
+/// Configuration of the Google Sheets sink
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GoogleSheetsSinkConfig {
+    /// ID of the spreadsheet, as found in its URL
+    pub spreadsheet_id: String,
+
+    /// Sheet (tab) name or A1 range rows are appended after
+    #[serde(default = "default_range")]
+    pub range: String,
+
+    /// OAuth 2.0 access token with the `spreadsheets` scope
+    pub access_token: String,
+}
+
+fn default_range() -> String {
+    "Sheet1".to_string()
+}
+
+// Never expose the access token in debug output
+impl std::fmt::Debug for GoogleSheetsSinkConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GoogleSheetsSinkConfig")
+            .field("spreadsheet_id", &self.spreadsheet_id)
+            .field("range", &self.range)
+            .field("access_token", &crate::redact::REDACTED)
+            .finish()
+    }
+}
+
+/// Appends monthly emission summaries to a Google Sheet
+pub struct GoogleSheetsSink {
+    config: GoogleSheetsSinkConfig,
+    http_client: reqwest::Client,
+    buffer: Vec<CarbonEmission>,
+}
+
+impl GoogleSheetsSink {
+    /// Create a sink appending to the spreadsheet of `config`
+    pub fn new(config: GoogleSheetsSinkConfig) -> Result<Self> {
+        if config.spreadsheet_id.trim().is_empty() {
+            return Err(CarbemError::Config(
+                "Google Sheets spreadsheet_id is required".to_string(),
+            ));
+        }
+        Ok(Self {
+            config,
+            http_client: reqwest::Client::new(),
+            buffer: Vec::new(),
+        })
+    }
+}
+
+/// Summary rows of `emissions`: month (`YYYY-MM`), provider, service and kg CO2e
+pub fn summary_rows(emissions: &[CarbonEmission]) -> Vec<Value> {
+    EmissionDataset::new(emissions.to_vec())
+        .group_by(|e| {
+            let start = e.time_period.start;
+            (
+                format!("{:04}-{:02}", start.year(), start.month()),
+                e.provider.clone(),
+                Dimension::Service.key(e),
+            )
+        })
+        .into_iter()
+        .map(|((month, provider, service), kg)| json!([month, provider, service, kg]))
+        .collect()
+}
+
+#[async_trait]
+impl EmissionSink for GoogleSheetsSink {
+    fn name(&self) -> &'static str {
+        "google_sheets"
+    }
+
+    async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+        self.buffer.extend_from_slice(emissions);
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let url = format!(
+            "{}/{}/values/{}:append?valueInputOption=RAW&insertDataOption=INSERT_ROWS",
+            SHEETS_API_BASE_URL,
+            urlencoding::encode(&self.config.spreadsheet_id),
+            urlencoding::encode(&self.config.range)
+        );
+        let response = self
+            .http_client
+            .post(&url)
+            .bearer_auth(&self.config.access_token)
+            .json(&json!({ "values": summary_rows(&self.buffer) }))
+            .send()
+            .await
+            .map_err(CarbemError::Http)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            let redactor = Redactor::new().with_secret(self.config.access_token.clone());
+            return Err(CarbemError::Other(format!(
+                "Google Sheets append failed with status {}: {}",
+                status,
+                redactor.redact(&body)
+            )));
+        }
+
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::{TimeZone, Utc};
+
+    fn emission(service: Option<&str>, day: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 5, day, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: service.map(str::to_string),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(1),
+            },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_summary_rows() {
+        let rows = summary_rows(&[
+            emission(Some("Storage"), 1, 1.0),
+            emission(Some("Storage"), 2, 2.0),
+            emission(None, 3, 0.5),
+        ]);
+
+        assert_eq!(
+            rows,
+            vec![
+                json!(["2024-05", "azure", "Storage", 3.0]),
+                json!(["2024-05", "azure", "unspecified", 0.5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let config: GoogleSheetsSinkConfig =
+            serde_json::from_value(json!({"spreadsheet_id": "abc", "access_token": "secret"}))
+                .unwrap();
+        assert_eq!(config.range, "Sheet1");
+        assert!(!format!("{:?}", config).contains("secret"));
+
+        let mut missing_id = config.clone();
+        missing_id.spreadsheet_id = String::new();
+        assert!(GoogleSheetsSink::new(missing_id).is_err());
+        assert!(GoogleSheetsSink::new(config).is_ok());
+    }
+}
@@ -0,0 +1,95 @@
+//! JSON Lines sink: one JSON-encoded emission per line
+
+use async_trait::async_trait;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+
+use super::EmissionSink;
+use crate::error::{CarbemError, Result};
+use crate::models::CarbonEmission;
+
+/// Writes emissions as JSON Lines to any async writer
+pub struct JsonLinesSink<W: AsyncWrite + Unpin + Send> {
+    writer: BufWriter<W>,
+}
+
+impl<W: AsyncWrite + Unpin + Send> JsonLinesSink<W> {
+    /// Create a sink writing to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    /// Close the output and return the underlying writer
+    pub async fn into_inner(mut self) -> Result<W> {
+        self.close().await?;
+        Ok(self.writer.into_inner())
+    }
+}
+
+#[async_trait]
+impl<W: AsyncWrite + Unpin + Send> EmissionSink for JsonLinesSink<W> {
+    fn name(&self) -> &'static str {
+        "jsonl"
+    }
+
+    async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+        for emission in emissions {
+            let mut line = serde_json::to_vec(emission)?;
+            line.push(b'\n');
+            self.writer.write_all(&line).await.map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.writer.flush().await.map_err(io_error)
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.writer.shutdown().await.map_err(io_error)
+    }
+}
+
+pub(crate) fn io_error(error: std::io::Error) -> CarbemError {
+    CarbemError::Other(format!("I/O error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::{TimeZone, Utc};
+
+    fn emission(region: &str) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.5,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_json_lines() {
+        let mut sink = JsonLinesSink::new(Vec::new());
+        sink.write(&[emission("westeurope"), emission("eastus")])
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(sink.into_inner().await.unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let parsed: CarbonEmission = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(parsed.region, "eastus");
+    }
+}
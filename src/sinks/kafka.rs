@@ -0,0 +1,365 @@
+//! Kafka sink streaming emissions as events (requires the `kafka` feature)
+//!
+//! Values are JSON, JSON in a Kafka Connect envelope, or Avro records of
+//! [`avro_schema`]. Avro values are framed for a schema registry when the
+//! schema id is configured, and with the Avro single-object encoding otherwise.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rdkafka::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use rdkafka::util::Timeout;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::EmissionSink;
+use crate::error::{CarbemError, Result};
+use crate::models::{CarbonEmission, FlatEmissionRecord};
+use crate::runtime::spawn_blocking;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Encoding of the Kafka message values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaPayloadFormat {
+    /// The emission serialized as JSON
+    #[default]
+    Json,
+
+    /// A Kafka Connect `{"schema", "payload"}` envelope with a
+    /// [`FlatEmissionRecord`], for sink connectors that require a schema
+    JsonWithSchema,
+
+    /// A [`FlatEmissionRecord`] in Avro binary encoding, see [`avro_schema`]
+    Avro,
+}
+
+/// Configuration of the Kafka sink
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KafkaSinkConfig {
+    /// Comma-separated list of bootstrap brokers
+    pub brokers: String,
+
+    /// Topic the emissions are produced to
+    pub topic: String,
+
+    /// Encoding of message values
+    #[serde(default)]
+    pub format: KafkaPayloadFormat,
+
+    /// Optional: id of [`avro_schema`] in the schema registry, for the `avro`
+    /// format
+    ///
+    /// Values then start with the Confluent wire format header. Without it,
+    /// they use the Avro single-object encoding, which carries the schema
+    /// fingerprint instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_id: Option<u32>,
+
+    /// Additional librdkafka properties, e.g. `security.protocol`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Produces one Kafka message per emission, keyed by `provider:region`
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    format: KafkaPayloadFormat,
+    schema_id: Option<u32>,
+}
+
+impl KafkaSink {
+    /// Create a producer for `config`
+    pub fn new(config: KafkaSinkConfig) -> Result<Self> {
+        let mut client_config = ClientConfig::new();
+        client_config.set("bootstrap.servers", &config.brokers);
+        for (key, value) in &config.properties {
+            client_config.set(key, value);
+        }
+        let producer = client_config
+            .create()
+            .map_err(|e| CarbemError::Config(format!("Invalid Kafka sink config: {}", e)))?;
+
+        Ok(Self {
+            producer,
+            topic: config.topic,
+            format: config.format,
+            schema_id: config.schema_id,
+        })
+    }
+}
+
+#[async_trait]
+impl EmissionSink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+        for emission in emissions {
+            let payload = encode(emission, self.format, self.schema_id)?;
+            let key = format!("{}:{}", emission.provider, emission.region);
+            let record = FutureRecord::to(&self.topic).payload(&payload).key(&key);
+            self.producer
+                .send(record, Timeout::After(SEND_TIMEOUT))
+                .await
+                .map_err(|(e, _)| CarbemError::Other(format!("Kafka delivery failed: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        let producer = self.producer.clone();
+        spawn_blocking(move || producer.flush(Timeout::After(SEND_TIMEOUT)))
+            .await?
+            .map_err(|e| CarbemError::Other(format!("Kafka flush failed: {}", e)))
+    }
+}
+
+// Message value of an emission
+fn encode(
+    emission: &CarbonEmission,
+    format: KafkaPayloadFormat,
+    schema_id: Option<u32>,
+) -> Result<Vec<u8>> {
+    let value = match format {
+        KafkaPayloadFormat::Json => json!(emission),
+        KafkaPayloadFormat::JsonWithSchema => json!({
+            "schema": connect_schema(),
+            "payload": FlatEmissionRecord::from(emission),
+        }),
+        KafkaPayloadFormat::Avro => {
+            let mut payload = match schema_id {
+                // Magic byte and big-endian schema id
+                Some(id) => [&[0][..], &id.to_be_bytes()].concat(),
+                // Marker and little-endian CRC-64-AVRO fingerprint
+                None => [
+                    &[0xc3, 0x01][..],
+                    &fingerprint(&avro_schema_json(false)).to_le_bytes(),
+                ]
+                .concat(),
+            };
+            payload.extend(avro_datum(emission)?);
+            return Ok(payload);
+        }
+    };
+    Ok(serde_json::to_vec(&value)?)
+}
+
+// Kafka Connect schema of `FlatEmissionRecord`
+fn connect_schema() -> Value {
+    let field = |name: &str, field_type: &str, optional: bool| json!({"field": name, "type": field_type, "optional": optional});
+    json!({
+        "type": "struct",
+        "name": "carbem.CarbonEmission",
+        "optional": false,
+        "fields": [
+            field("provider", "string", false),
+            field("region", "string", false),
+            field("service", "string", true),
+            field("emissions_kg_co2eq", "double", false),
+            field("period_start", "string", false),
+            field("period_end", "string", false),
+            field("energy_kwh", "double", true),
+            field("grid_carbon_intensity", "double", true),
+            field("renewable_percentage", "double", true),
+            field("date_alignment", "string", true),
+            field("provider_data", "string", true),
+            field("lineage_endpoint", "string", true),
+            field("lineage_request_hash", "string", true),
+            field("lineage_retrieved_at", "string", true),
+            field("lineage_carbem_version", "string", true),
+        ],
+    })
+}
+
+// Avro fields of `FlatEmissionRecord`, in order: name, type and whether the
+// field is nullable. Timestamps are RFC 3339 strings, as in the JSON formats.
+const AVRO_FIELDS: [(&str, &str, bool); 15] = [
+    ("provider", "string", false),
+    ("region", "string", false),
+    ("service", "string", true),
+    ("emissions_kg_co2eq", "double", false),
+    ("period_start", "string", false),
+    ("period_end", "string", false),
+    ("energy_kwh", "double", true),
+    ("grid_carbon_intensity", "double", true),
+    ("renewable_percentage", "double", true),
+    ("date_alignment", "string", true),
+    ("provider_data", "string", true),
+    ("lineage_endpoint", "string", true),
+    ("lineage_request_hash", "string", true),
+    ("lineage_retrieved_at", "string", true),
+    ("lineage_carbem_version", "string", true),
+];
+
+/// Avro schema of the values produced in the [`KafkaPayloadFormat::Avro`]
+/// format, e.g. to register in a schema registry
+pub fn avro_schema() -> String {
+    avro_schema_json(true)
+}
+
+// The schema, without the defaults of nullable fields in the Parsing
+// Canonical Form that fingerprints are computed over
+fn avro_schema_json(defaults: bool) -> String {
+    let fields: Vec<String> = AVRO_FIELDS
+        .iter()
+        .map(|(name, avro_type, nullable)| match (nullable, defaults) {
+            (false, _) => format!(r#"{{"name":"{}","type":"{}"}}"#, name, avro_type),
+            (true, false) => format!(r#"{{"name":"{}","type":["null","{}"]}}"#, name, avro_type),
+            (true, true) => format!(
+                r#"{{"name":"{}","type":["null","{}"],"default":null}}"#,
+                name, avro_type
+            ),
+        })
+        .collect();
+    format!(
+        r#"{{"name":"carbem.CarbonEmission","type":"record","fields":[{}]}}"#,
+        fields.join(",")
+    )
+}
+
+// Avro binary encoding of the flat record of an emission
+fn avro_datum(emission: &CarbonEmission) -> Result<Vec<u8>> {
+    let Value::Object(record) = serde_json::to_value(FlatEmissionRecord::from(emission))? else {
+        unreachable!("records serialize to objects");
+    };
+    let mut datum = Vec::new();
+    for (name, avro_type, nullable) in AVRO_FIELDS {
+        let value = &record[name];
+        if nullable {
+            // Branch of the ["null", type] union
+            write_long(&mut datum, i64::from(!value.is_null()));
+            if value.is_null() {
+                continue;
+            }
+        }
+        match (avro_type, value) {
+            ("double", Value::Number(number)) => {
+                datum.extend(number.as_f64().unwrap_or_default().to_le_bytes())
+            }
+            ("string", Value::String(text)) => {
+                write_long(&mut datum, text.len() as i64);
+                datum.extend(text.as_bytes());
+            }
+            // Non-finite numbers serialize to null
+            _ => {
+                return Err(CarbemError::Other(format!(
+                    "Cannot encode {} of {} as Avro",
+                    value, name
+                )));
+            }
+        }
+    }
+    Ok(datum)
+}
+
+// Zig-zag variable-length encoding of Avro ints and longs
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    while zigzag >= 0x80 {
+        out.push(zigzag as u8 | 0x80);
+        zigzag >>= 7;
+    }
+    out.push(zigzag as u8);
+}
+
+// CRC-64-AVRO fingerprint of a schema in Parsing Canonical Form
+fn fingerprint(schema: &str) -> u64 {
+    const EMPTY: u64 = 0xc15d_213a_a4d7_a795;
+    schema.bytes().fold(EMPTY, |fp, byte| {
+        let mut entry = (fp ^ u64::from(byte)) & 0xff;
+        for _ in 0..8 {
+            entry = (entry >> 1) ^ (EMPTY & (entry & 1).wrapping_neg());
+        }
+        (fp >> 8) ^ entry
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::{TimeZone, Utc};
+
+    fn emission() -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "ibm".to_string(),
+            region: "dallas".to_string(),
+            service: Some("Kubernetes Service".to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 2.5,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        }
+    }
+
+    fn encode_json(format: KafkaPayloadFormat) -> Value {
+        serde_json::from_slice(&encode(&emission(), format, None).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_json() {
+        let value = encode_json(KafkaPayloadFormat::Json);
+        assert_eq!(value["region"], "dallas");
+        assert_eq!(value["time_period"]["start"], "2024-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_encode_json_with_schema() {
+        let value = encode_json(KafkaPayloadFormat::JsonWithSchema);
+        let fields = value["schema"]["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), value["payload"].as_object().unwrap().len());
+        assert_eq!(value["payload"]["period_start"], "2024-01-01T00:00:00Z");
+        assert_eq!(value["payload"]["provider_data"], Value::Null);
+    }
+
+    #[test]
+    fn test_encode_avro() {
+        let datum = avro_datum(&emission()).unwrap();
+        // "ibm", "dallas", then the second branch of the service union
+        assert!(datum.starts_with(b"\x06ibm\x0cdallas\x02\x24Kubernetes Service"));
+        let kg = &datum[1 + 3 + 1 + 6 + 2 + 18..][..8];
+        assert_eq!(f64::from_le_bytes(kg.try_into().unwrap()), 2.5);
+
+        let framed = encode(&emission(), KafkaPayloadFormat::Avro, Some(7)).unwrap();
+        assert_eq!(framed[..5], [0, 0, 0, 0, 7]);
+        assert_eq!(framed[5..], datum);
+        let single = encode(&emission(), KafkaPayloadFormat::Avro, None).unwrap();
+        assert_eq!(single[..2], [0xc3, 0x01]);
+        assert_eq!(single[10..], datum);
+
+        let schema: Value = serde_json::from_str(&avro_schema()).unwrap();
+        assert_eq!(schema["fields"].as_array().unwrap().len(), 15);
+        assert_eq!(schema["fields"][2]["default"], Value::Null);
+    }
+
+    #[test]
+    fn test_avro_primitives() {
+        let mut out = Vec::new();
+        for value in [0, -1, 1, 64, -65] {
+            write_long(&mut out, value);
+        }
+        assert_eq!(out, [0x00, 0x01, 0x02, 0x80, 0x01, 0x81, 0x01]);
+        // Test vector of the Avro specification
+        assert_eq!(fingerprint(r#""null""#), 0x63dd_24e7_cc25_8f8a);
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: KafkaSinkConfig =
+            serde_json::from_value(json!({"brokers": "localhost:9092", "topic": "emissions"}))
+                .unwrap();
+        assert_eq!(config.format, KafkaPayloadFormat::Json);
+        assert!(KafkaSink::new(config).is_ok());
+    }
+}
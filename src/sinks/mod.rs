@@ -0,0 +1,58 @@
+//! Sinks exporting carbon emissions to files and external systems
+
+pub mod compression;
+#[cfg(feature = "confluence")]
+pub mod confluence;
+pub mod csv;
+#[cfg(feature = "google-sheets")]
+pub mod google_sheets;
+pub mod jsonl;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "object-store")]
+pub mod object_storage;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod registry;
+
+use crate::error::Result;
+use crate::models::CarbonEmission;
+use async_trait::async_trait;
+
+pub use compression::Compression;
+#[cfg(feature = "confluence")]
+pub use confluence::{ConfluenceSink, ConfluenceSinkConfig};
+pub use csv::CsvSink;
+#[cfg(feature = "google-sheets")]
+pub use google_sheets::{GoogleSheetsSink, GoogleSheetsSinkConfig};
+pub use jsonl::JsonLinesSink;
+#[cfg(feature = "kafka")]
+pub use kafka::{KafkaPayloadFormat, KafkaSink, KafkaSinkConfig, avro_schema};
+#[cfg(feature = "object-store")]
+pub use object_storage::{ObjectFileFormat, ObjectStoreSink, ObjectStoreSinkConfig};
+pub use registry::SinkRegistry;
+
+/// Trait that all emission exporters implement
+///
+/// Custom sinks can be registered in a [`SinkRegistry`] to ship emissions to
+/// proprietary systems.
+#[async_trait]
+pub trait EmissionSink: Send {
+    /// Get the sink name
+    fn name(&self) -> &'static str;
+
+    /// Write a batch of emissions
+    ///
+    /// Sinks may buffer; data is only guaranteed to be persisted after `flush`.
+    async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()>;
+
+    /// Persist any buffered emissions
+    async fn flush(&mut self) -> Result<()>;
+
+    /// Flush and finalize the output, e.g. write the trailer of a compressed file
+    ///
+    /// Nothing may be written after closing a sink.
+    async fn close(&mut self) -> Result<()> {
+        self.flush().await
+    }
+}
@@ -0,0 +1,323 @@
+//! Object storage sink writing Hive-style partitioned files (requires the `object-store` feature)
+//!
+//! Emissions are buffered and written on flush as JSON Lines or, with the
+//! `parquet` feature, Parquet files, gzip compressed by default, under
+//! `<prefix>/provider=<provider>/year=<yyyy>/month=<mm>/`, a layout Athena,
+//! Trino or Spark can query as a partitioned table without any ETL.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{Datelike, Utc};
+use object_store::ObjectStore;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+
+use super::{Compression, EmissionSink};
+use crate::error::{CarbemError, Result};
+use crate::models::CarbonEmission;
+#[cfg(feature = "parquet")]
+use crate::models::FlatEmissionRecord;
+
+/// Configuration of an S3-compatible bucket
+///
+/// Credentials are read from the standard `AWS_*` environment variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectStoreSinkConfig {
+    /// Bucket name
+    pub bucket: String,
+
+    /// Optional: key prefix of the table, e.g. "carbem/emissions"
+    #[serde(default)]
+    pub prefix: String,
+
+    /// Optional: endpoint of S3-compatible stores (MinIO, IBM COS, R2...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    /// Optional: bucket region
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// Allow plain HTTP endpoints, e.g. a local MinIO
+    #[serde(default)]
+    pub allow_http: bool,
+
+    /// Compression of the written files (defaults to gzip)
+    #[serde(default = "default_compression")]
+    pub compression: Compression,
+
+    /// Format of the written files (defaults to JSON Lines)
+    #[serde(default)]
+    pub format: ObjectFileFormat,
+}
+
+/// Format of the files written by [`ObjectStoreSink`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectFileFormat {
+    /// JSON Lines of emissions, the whole file compressed
+    #[default]
+    Jsonl,
+
+    /// Parquet of flat emission records, its pages compressed (requires the
+    /// `parquet` feature)
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+fn default_compression() -> Compression {
+    Compression::Gzip
+}
+
+/// Writes emissions to object storage, one file per partition and flush
+pub struct ObjectStoreSink {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    compression: Compression,
+    format: ObjectFileFormat,
+    buffer: Vec<CarbonEmission>,
+    flushes: u64,
+}
+
+impl ObjectStoreSink {
+    /// Create a sink writing uncompressed files under `prefix` of `store`
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> Self {
+        Self {
+            store,
+            prefix: prefix.trim_matches('/').to_string(),
+            compression: Compression::None,
+            format: ObjectFileFormat::Jsonl,
+            buffer: Vec::new(),
+            flushes: 0,
+        }
+    }
+
+    /// Compress the written files
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Write files in `format`
+    pub fn with_format(mut self, format: ObjectFileFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Create a sink writing to an S3-compatible bucket
+    pub fn s3(config: ObjectStoreSinkConfig) -> Result<Self> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&config.bucket)
+            .with_allow_http(config.allow_http);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(region) = &config.region {
+            builder = builder.with_region(region);
+        }
+        let store = builder
+            .build()
+            .map_err(|e| CarbemError::Config(format!("Invalid object store config: {}", e)))?;
+
+        Ok(Self::new(Arc::new(store), &config.prefix)
+            .with_compression(config.compression)
+            .with_format(config.format))
+    }
+}
+
+/// Hive-style partition of an emission: `provider=<provider>/year=<yyyy>/month=<mm>`
+///
+/// The month is the one the emission's period starts in, in UTC.
+pub fn partition_path(emission: &CarbonEmission) -> String {
+    let start = emission.time_period.start;
+    format!(
+        "provider={}/year={:04}/month={:02}",
+        urlencoding::encode(&emission.provider),
+        start.year(),
+        start.month()
+    )
+}
+
+impl ObjectStoreSink {
+    // Content of the file of a partition
+    async fn encode(&self, emissions: &[&CarbonEmission]) -> Result<Vec<u8>> {
+        match self.format {
+            ObjectFileFormat::Jsonl => {
+                let mut content = Vec::new();
+                for emission in emissions {
+                    serde_json::to_writer(&mut content, emission)?;
+                    content.push(b'\n');
+                }
+                self.compression.compress(content).await
+            }
+            #[cfg(feature = "parquet")]
+            ObjectFileFormat::Parquet => {
+                let records: Vec<FlatEmissionRecord> = emissions
+                    .iter()
+                    .map(|emission| (*emission).into())
+                    .collect();
+                super::parquet::encode(&records, self.compression)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EmissionSink for ObjectStoreSink {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+        self.buffer.extend_from_slice(emissions);
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let mut partitions: BTreeMap<String, Vec<&CarbonEmission>> = BTreeMap::new();
+        for emission in &self.buffer {
+            partitions
+                .entry(partition_path(emission))
+                .or_default()
+                .push(emission);
+        }
+
+        // Parquet compresses pages, so its files keep their plain extension
+        let extension = match self.format {
+            ObjectFileFormat::Jsonl => format!("jsonl{}", self.compression.extension()),
+            #[cfg(feature = "parquet")]
+            ObjectFileFormat::Parquet => "parquet".to_string(),
+        };
+        // Unique per sink and flush so that runs never overwrite each other
+        let file_name = format!(
+            "part-{}-{:04}.{}",
+            Utc::now().format("%Y%m%dT%H%M%S%3fZ"),
+            self.flushes,
+            extension
+        );
+        for (partition, emissions) in partitions {
+            let key = if self.prefix.is_empty() {
+                format!("{}/{}", partition, file_name)
+            } else {
+                format!("{}/{}/{}", self.prefix, partition, file_name)
+            };
+            let content = self.encode(&emissions).await?;
+            let path = Path::parse(&key)
+                .map_err(|e| CarbemError::Config(format!("Invalid object key {}: {}", key, e)))?;
+            self.store.put(&path, content.into()).await.map_err(|e| {
+                CarbemError::Other(format!("Failed to write object {}: {}", key, e))
+            })?;
+        }
+
+        self.buffer.clear();
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::TimeZone;
+    use object_store::memory::InMemory;
+
+    fn emission(provider: &str, month: u32) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: provider.to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.0,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(28),
+            },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_partition_path() {
+        assert_eq!(
+            partition_path(&emission("azure", 3)),
+            "provider=azure/year=2024/month=03"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush_writes_partitioned_files() {
+        let store = Arc::new(InMemory::new());
+        let mut sink = ObjectStoreSink::new(store.clone(), "/emissions/");
+        sink.write(&[
+            emission("azure", 1),
+            emission("azure", 1),
+            emission("ibm", 2),
+        ])
+        .await
+        .unwrap();
+        sink.flush().await.unwrap();
+        sink.flush().await.unwrap();
+
+        let listed = store.list_with_delimiter(None).await.unwrap();
+        assert_eq!(listed.common_prefixes, vec![Path::from("emissions")]);
+
+        let prefix = Path::from("emissions/provider=azure/year=2024/month=01");
+        let objects = store.list_with_delimiter(Some(&prefix)).await.unwrap();
+        assert_eq!(objects.objects.len(), 1);
+
+        let content = store
+            .get(&objects.objects[0].location)
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(content.split(|b| *b == b'\n').count(), 3);
+
+        let prefix = Path::from("emissions/provider=ibm/year=2024/month=02");
+        let objects = store.list_with_delimiter(Some(&prefix)).await.unwrap();
+        assert_eq!(objects.objects.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_compressed_files() {
+        let store = Arc::new(InMemory::new());
+        let mut sink = ObjectStoreSink::new(store.clone(), "").with_compression(Compression::Gzip);
+        sink.write(&[emission("azure", 1)]).await.unwrap();
+        sink.flush().await.unwrap();
+
+        let prefix = Path::from("provider=azure/year=2024/month=01");
+        let objects = store.list_with_delimiter(Some(&prefix)).await.unwrap();
+        assert!(objects.objects[0].location.as_ref().ends_with(".jsonl.gz"));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_flush_parquet_files() {
+        let store = Arc::new(InMemory::new());
+        let mut sink = ObjectStoreSink::new(store.clone(), "")
+            .with_compression(Compression::Zstd)
+            .with_format(ObjectFileFormat::Parquet);
+        sink.write(&[emission("azure", 1), emission("azure", 1)])
+            .await
+            .unwrap();
+        sink.flush().await.unwrap();
+
+        let prefix = Path::from("provider=azure/year=2024/month=01");
+        let objects = store.list_with_delimiter(Some(&prefix)).await.unwrap();
+        let location = &objects.objects[0].location;
+        assert!(location.as_ref().ends_with(".parquet"));
+        let content = store.get(location).await.unwrap().bytes().await.unwrap();
+        assert!(content.starts_with(b"PAR1") && content.ends_with(b"PAR1"));
+    }
+}
@@ -0,0 +1,212 @@
+//! Parquet encoding of emissions (requires the `parquet` feature)
+//!
+//! Files hold one row per [`FlatEmissionRecord`], with the columns of the
+//! JSON and CSV outputs. Periods and retrieval times are UTC timestamps in
+//! milliseconds, and pages are compressed with the file's [`Compression`].
+
+use std::sync::Arc;
+
+use ::parquet::basic::{Compression as Codec, GzipLevel, ZstdLevel};
+use ::parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use ::parquet::schema::parser::parse_message_type;
+
+use super::Compression;
+use crate::error::{CarbemError, Result};
+use crate::models::FlatEmissionRecord;
+
+/// Parquet schema of the written files
+pub const PARQUET_SCHEMA: &str = "
+message carbon_emission {
+    required binary provider (STRING);
+    required binary region (STRING);
+    optional binary service (STRING);
+    required double emissions_kg_co2eq;
+    required int64 period_start (TIMESTAMP(MILLIS, true));
+    required int64 period_end (TIMESTAMP(MILLIS, true));
+    optional double energy_kwh;
+    optional double grid_carbon_intensity;
+    optional double renewable_percentage;
+    optional binary date_alignment (STRING);
+    optional binary provider_data (JSON);
+    optional binary lineage_endpoint (STRING);
+    optional binary lineage_request_hash (STRING);
+    optional int64 lineage_retrieved_at (TIMESTAMP(MILLIS, true));
+    optional binary lineage_carbem_version (STRING);
+}
+";
+
+// Values of a column in schema order, `None` for nulls
+enum Column {
+    Text(Vec<Option<String>>),
+    Double(Vec<Option<f64>>),
+    Timestamp(Vec<Option<i64>>),
+}
+
+/// Encode `records` as a Parquet file with a single row group
+pub fn encode(records: &[FlatEmissionRecord], compression: Compression) -> Result<Vec<u8>> {
+    let codec = match compression {
+        Compression::None => Codec::UNCOMPRESSED,
+        Compression::Gzip => Codec::GZIP(GzipLevel::default()),
+        Compression::Zstd => Codec::ZSTD(ZstdLevel::default()),
+    };
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA).map_err(parquet_error)?);
+    let properties = Arc::new(WriterProperties::builder().set_compression(codec).build());
+    let mut writer =
+        SerializedFileWriter::new(Vec::new(), schema, properties).map_err(parquet_error)?;
+
+    let mut row_group = writer.next_row_group().map_err(parquet_error)?;
+    let mut columns = columns(records).into_iter();
+    while let Some(mut writer) = row_group.next_column().map_err(parquet_error)? {
+        let column = columns
+            .next()
+            .expect("every column of the schema has values");
+        write_column(&mut writer, column).map_err(parquet_error)?;
+        writer.close().map_err(parquet_error)?;
+    }
+    row_group.close().map_err(parquet_error)?;
+    writer.into_inner().map_err(parquet_error)
+}
+
+fn columns(records: &[FlatEmissionRecord]) -> Vec<Column> {
+    let text = |field: fn(&FlatEmissionRecord) -> Option<String>| {
+        Column::Text(records.iter().map(field).collect())
+    };
+    let double = |field: fn(&FlatEmissionRecord) -> Option<f64>| {
+        Column::Double(records.iter().map(field).collect())
+    };
+    let timestamp = |field: fn(&FlatEmissionRecord) -> Option<i64>| {
+        Column::Timestamp(records.iter().map(field).collect())
+    };
+    vec![
+        text(|r| Some(r.provider.clone())),
+        text(|r| Some(r.region.clone())),
+        text(|r| r.service.clone()),
+        double(|r| Some(r.emissions_kg_co2eq)),
+        timestamp(|r| Some(r.period_start.timestamp_millis())),
+        timestamp(|r| Some(r.period_end.timestamp_millis())),
+        double(|r| r.energy_kwh),
+        double(|r| r.grid_carbon_intensity),
+        double(|r| r.renewable_percentage),
+        text(|r| {
+            r.date_alignment
+                .and_then(|alignment| serde_json::to_value(alignment).ok())
+                .and_then(|value| value.as_str().map(str::to_string))
+        }),
+        text(|r| r.provider_data.clone()),
+        text(|r| r.lineage_endpoint.clone()),
+        text(|r| r.lineage_request_hash.clone()),
+        timestamp(|r| r.lineage_retrieved_at.map(|at| at.timestamp_millis())),
+        text(|r| r.lineage_carbem_version.clone()),
+    ]
+}
+
+fn write_column(
+    writer: &mut SerializedColumnWriter,
+    column: Column,
+) -> std::result::Result<(), ParquetError> {
+    // Definition levels are ignored for required columns
+    fn levels<T>(values: &[Option<T>]) -> Vec<i16> {
+        values
+            .iter()
+            .map(|value| i16::from(value.is_some()))
+            .collect()
+    }
+    match column {
+        Column::Text(values) => {
+            let levels = levels(&values);
+            let values: Vec<ByteArray> = values
+                .into_iter()
+                .flatten()
+                .map(|text| ByteArray::from(text.into_bytes()))
+                .collect();
+            writer
+                .typed::<ByteArrayType>()
+                .write_batch(&values, Some(&levels), None)?;
+        }
+        Column::Double(values) => {
+            let levels = levels(&values);
+            let values: Vec<f64> = values.into_iter().flatten().collect();
+            writer
+                .typed::<DoubleType>()
+                .write_batch(&values, Some(&levels), None)?;
+        }
+        Column::Timestamp(values) => {
+            let levels = levels(&values);
+            let values: Vec<i64> = values.into_iter().flatten().collect();
+            writer
+                .typed::<Int64Type>()
+                .write_batch(&values, Some(&levels), None)?;
+        }
+    }
+    Ok(())
+}
+
+fn parquet_error(error: ParquetError) -> CarbemError {
+    CarbemError::Other(format!("Failed to encode Parquet: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+    use ::parquet::record::Field;
+    use chrono::{TimeZone, Utc};
+
+    fn record(service: Option<&str>) -> FlatEmissionRecord {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        FlatEmissionRecord::from(CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: service.map(str::to_string),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.5,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        })
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let records = vec![record(Some("Storage")), record(None)];
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let path = std::env::temp_dir().join(format!(
+                "carbem-parquet-{:?}-{}.parquet",
+                compression,
+                std::process::id()
+            ));
+            std::fs::write(&path, encode(&records, compression).unwrap()).unwrap();
+            let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+            let rows: Vec<_> = reader
+                .get_row_iter(None)
+                .unwrap()
+                .map(|row| row.unwrap())
+                .collect();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(rows.len(), 2);
+            let fields: Vec<(&String, &Field)> = rows[1].get_column_iter().collect();
+            assert_eq!(fields.len(), 15);
+            assert_eq!(
+                fields[0],
+                (
+                    &"provider".to_string(),
+                    &Field::Str(records[1].provider.clone())
+                )
+            );
+            assert_eq!(*fields[2].1, Field::Null);
+            assert_eq!(*fields[3].1, Field::Double(records[1].emissions_kg_co2eq));
+            assert_eq!(
+                *fields[4].1,
+                Field::TimestampMillis(records[1].period_start.timestamp_millis())
+            );
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Sink Registry Pattern for dynamic exporter management
+
+use std::collections::HashMap;
+
+#[cfg(feature = "tokio-runtime")]
+use serde::Deserialize;
+
+use super::EmissionSink;
+#[cfg(feature = "tokio-runtime")]
+use super::{Compression, CsvSink, JsonLinesSink, jsonl::io_error};
+use crate::error::{CarbemError, Result};
+
+/// Type alias for sink factory functions
+type SinkFactory = Box<dyn Fn(serde_json::Value) -> Result<Box<dyn EmissionSink>> + Send + Sync>;
+
+/// Configuration of the built-in file sinks
+#[cfg(feature = "tokio-runtime")]
+#[derive(Debug, Deserialize)]
+struct FileSinkConfig {
+    path: String,
+
+    // Defaults to the compression implied by the file extension
+    #[serde(default)]
+    compression: Option<Compression>,
+}
+
+/// Registry for emission sinks
+pub struct SinkRegistry {
+    factories: HashMap<String, SinkFactory>,
+}
+
+impl SinkRegistry {
+    /// Create a new sink registry
+    pub fn new() -> Self {
+        // Every built-in sink is behind a feature
+        #[allow(unused_mut)]
+        let mut registry = Self {
+            factories: HashMap::new(),
+        };
+
+        // Register built-in sinks
+        #[cfg(feature = "tokio-runtime")]
+        registry.register_jsonl();
+        #[cfg(feature = "tokio-runtime")]
+        registry.register_csv();
+        #[cfg(feature = "kafka")]
+        registry.register_kafka();
+        #[cfg(feature = "object-store")]
+        registry.register_s3();
+        #[cfg(feature = "google-sheets")]
+        registry.register_google_sheets();
+        #[cfg(feature = "confluence")]
+        registry.register_confluence();
+
+        registry
+    }
+
+    /// Register JSON Lines file sink factory
+    #[cfg(feature = "tokio-runtime")]
+    fn register_jsonl(&mut self) {
+        let factory: SinkFactory = Box::new(|config_json| {
+            let config: FileSinkConfig = serde_json::from_value(config_json)
+                .map_err(|e| CarbemError::Config(format!("Invalid jsonl sink config: {}", e)))?;
+
+            let compression = config
+                .compression
+                .unwrap_or_else(|| Compression::from_path(&config.path));
+            let file = std::fs::File::create(&config.path).map_err(io_error)?;
+            let sink = JsonLinesSink::new(compression.encoder(tokio::fs::File::from_std(file)));
+            Ok(Box::new(sink) as Box<dyn EmissionSink>)
+        });
+
+        self.factories.insert("jsonl".to_string(), factory);
+    }
+
+    /// Register CSV file sink factory
+    #[cfg(feature = "tokio-runtime")]
+    fn register_csv(&mut self) {
+        let factory: SinkFactory = Box::new(|config_json| {
+            let config: FileSinkConfig = serde_json::from_value(config_json)
+                .map_err(|e| CarbemError::Config(format!("Invalid csv sink config: {}", e)))?;
+
+            let compression = config
+                .compression
+                .unwrap_or_else(|| Compression::from_path(&config.path));
+            let file = std::fs::File::create(&config.path).map_err(io_error)?;
+            let sink = CsvSink::new(compression.encoder(tokio::fs::File::from_std(file)));
+            Ok(Box::new(sink) as Box<dyn EmissionSink>)
+        });
+
+        self.factories.insert("csv".to_string(), factory);
+    }
+
+    /// Register Kafka sink factory
+    #[cfg(feature = "kafka")]
+    fn register_kafka(&mut self) {
+        let factory: SinkFactory = Box::new(|config_json| {
+            let config: super::KafkaSinkConfig = serde_json::from_value(config_json)
+                .map_err(|e| CarbemError::Config(format!("Invalid kafka sink config: {}", e)))?;
+
+            Ok(Box::new(super::KafkaSink::new(config)?) as Box<dyn EmissionSink>)
+        });
+
+        self.factories.insert("kafka".to_string(), factory);
+    }
+
+    /// Register S3 object storage sink factory
+    #[cfg(feature = "object-store")]
+    fn register_s3(&mut self) {
+        let factory: SinkFactory = Box::new(|config_json| {
+            let config: super::ObjectStoreSinkConfig = serde_json::from_value(config_json)
+                .map_err(|e| CarbemError::Config(format!("Invalid s3 sink config: {}", e)))?;
+
+            Ok(Box::new(super::ObjectStoreSink::s3(config)?) as Box<dyn EmissionSink>)
+        });
+
+        self.factories.insert("s3".to_string(), factory);
+    }
+
+    /// Register Google Sheets sink factory
+    #[cfg(feature = "google-sheets")]
+    fn register_google_sheets(&mut self) {
+        let factory: SinkFactory = Box::new(|config_json| {
+            let config: super::GoogleSheetsSinkConfig = serde_json::from_value(config_json)
+                .map_err(|e| {
+                    CarbemError::Config(format!("Invalid google_sheets sink config: {}", e))
+                })?;
+
+            Ok(Box::new(super::GoogleSheetsSink::new(config)?) as Box<dyn EmissionSink>)
+        });
+
+        self.factories.insert("google_sheets".to_string(), factory);
+    }
+
+    /// Register Confluence report sink factory
+    #[cfg(feature = "confluence")]
+    fn register_confluence(&mut self) {
+        let factory: SinkFactory = Box::new(|config_json| {
+            let config: super::ConfluenceSinkConfig =
+                serde_json::from_value(config_json).map_err(|e| {
+                    CarbemError::Config(format!("Invalid confluence sink config: {}", e))
+                })?;
+
+            Ok(Box::new(super::ConfluenceSink::new(config)?) as Box<dyn EmissionSink>)
+        });
+
+        self.factories.insert("confluence".to_string(), factory);
+    }
+
+    /// Register a custom sink factory
+    pub fn register_sink<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(serde_json::Value) -> Result<Box<dyn EmissionSink>> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+    }
+
+    /// Create a sink instance
+    pub fn create_sink(
+        &self,
+        name: &str,
+        config: serde_json::Value,
+    ) -> Result<Box<dyn EmissionSink>> {
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| CarbemError::Config(format!("Unknown sink: {}", name)))?;
+
+        factory(config)
+    }
+
+    /// Get list of available sinks
+    pub fn available_sinks(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+}
+
+impl Default for SinkRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CarbonEmission;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    struct CountingSink {
+        written: Arc<Mutex<usize>>,
+    }
+
+    #[async_trait]
+    impl EmissionSink for CountingSink {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn write(&mut self, emissions: &[CarbonEmission]) -> Result<()> {
+            *self.written.lock().unwrap() += emissions.len();
+            Ok(())
+        }
+
+        async fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_registry_creation() {
+        let registry = SinkRegistry::new();
+        assert!(registry.available_sinks().contains(&"jsonl".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_custom_sink() {
+        let written = Arc::new(Mutex::new(0));
+        let mut registry = SinkRegistry::new();
+        let counter = written.clone();
+        registry.register_sink("counting", move |_config| {
+            Ok(Box::new(CountingSink {
+                written: counter.clone(),
+            }) as Box<dyn EmissionSink>)
+        });
+
+        let mut sink = registry.create_sink("counting", json!({})).unwrap();
+        sink.write(&[]).await.unwrap();
+        sink.flush().await.unwrap();
+        assert_eq!(sink.name(), "counting");
+        assert_eq!(*written.lock().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_jsonl_sink() {
+        let path = std::env::temp_dir().join(format!("carbem-{}.jsonl.gz", std::process::id()));
+        let registry = SinkRegistry::new();
+        let mut sink = registry
+            .create_sink("jsonl", json!({ "path": path.to_str().unwrap() }))
+            .unwrap();
+        sink.write(&[]).await.unwrap();
+        sink.close().await.unwrap();
+
+        // An empty gzip stream still has a header and a trailer
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&content[..2], &[0x1f, 0x8b]);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_csv_sink() {
+        let path = std::env::temp_dir().join(format!("carbem-{}.csv", std::process::id()));
+        let registry = SinkRegistry::new();
+        let mut sink = registry
+            .create_sink(
+                "csv",
+                json!({ "path": path.to_str().unwrap(), "compression": "zstd" }),
+            )
+            .unwrap();
+        sink.write(&[]).await.unwrap();
+        sink.close().await.unwrap();
+
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&content[..4], &[0x28, 0xb5, 0x2f, 0xfd]);
+    }
+
+    #[test]
+    fn test_invalid_sink() {
+        let registry = SinkRegistry::new();
+        assert!(registry.create_sink("unknown", json!({})).is_err());
+        assert!(registry.create_sink("jsonl", json!({})).is_err());
+    }
+}
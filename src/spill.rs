@@ -0,0 +1,300 @@
+//! Memory-bounded results of large backfills
+//!
+//! Backfilling years of item-level emissions can exceed the memory of a small
+//! collector instance. [`CarbemClient::query_emissions_spilled`] splits the
+//! query period into windows of [`SpillOptions::window_months`] months, queries them one after another
+//! and writes the emissions of each window, a page, to a temporary JSON Lines
+//! file before querying the next. The returned [`SpilledEmissions`] reads
+//! them back one at a time through [`SpilledEmissions::iter`], so peak memory
+//! is bounded by the largest page rather than by the whole backfill. The
+//! files are deleted when the [`SpilledEmissions`] is dropped.
+//!
+//! [`CarbemClient::query_emissions_spilled`]: crate::CarbemClient::query_emissions_spilled
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::Duration;
+
+use crate::error::{CarbemError, Result};
+use crate::logging::{debug, warn};
+use crate::models::{CarbonEmission, EmissionQuery, TimePeriod, next_month_start};
+use crate::runtime::{fs, spawn_blocking};
+
+// Distinguishes the spill directories of one process
+static SPILLS: AtomicU64 = AtomicU64::new(0);
+
+/// Where and in which windows [`query_emissions_spilled`] spills pages
+///
+/// The default spills one page per month to the system temporary directory.
+///
+/// [`query_emissions_spilled`]: crate::CarbemClient::query_emissions_spilled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillOptions {
+    /// Optional: directory the temporary files are created in, the system
+    /// temporary directory by default
+    pub dir: Option<PathBuf>,
+
+    /// Months of the query period fetched and spilled as one page
+    pub window_months: u32,
+}
+
+impl Default for SpillOptions {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            window_months: 1,
+        }
+    }
+}
+
+impl SpillOptions {
+    /// Spill one page per month to the system temporary directory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the temporary files in `dir`, e.g. on a larger data volume
+    pub fn in_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Fetch and spill `months` months per page
+    pub fn with_window_months(mut self, months: u32) -> Self {
+        self.window_months = months.max(1);
+        self
+    }
+
+    // Half-open periods of the pages of `query`, in order, on month boundaries
+    pub(crate) fn windows(&self, query: &EmissionQuery) -> Result<Vec<TimePeriod>> {
+        let range = query
+            .time_period
+            .month_range_in(query.date_alignment, &query.timezone)?;
+        let mut windows = Vec::new();
+        let mut start = range.start;
+        while start < range.end {
+            let mut end = start;
+            for _ in 0..self.window_months {
+                end = next_month_start(end, &query.timezone);
+            }
+            let end = end.min(range.end);
+            windows.push(TimePeriod { start, end });
+            start = end;
+        }
+        Ok(windows)
+    }
+}
+
+/// Emissions spilled to temporary files, one per page
+///
+/// Emissions are iterated page after page, i.e. by window of the query
+/// period, and in canonical order within a page.
+#[derive(Debug)]
+pub struct SpilledEmissions {
+    dir: PathBuf,
+    pages: Vec<SpilledPage>,
+}
+
+#[derive(Debug)]
+struct SpilledPage {
+    period: TimePeriod,
+    path: PathBuf,
+    emissions: usize,
+}
+
+impl SpilledEmissions {
+    // Empty spill in a new directory under `options.dir`
+    pub(crate) async fn create(options: &SpillOptions) -> Result<Self> {
+        let parent = options.dir.clone().unwrap_or_else(std::env::temp_dir);
+        let dir = parent.join(format!(
+            "carbem-spill-{}-{}",
+            std::process::id(),
+            SPILLS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| io_error(&dir, e))?;
+        Ok(Self {
+            dir,
+            pages: Vec::new(),
+        })
+    }
+
+    // Write the emissions of `period` as the next page
+    pub(crate) async fn push_page(
+        &mut self,
+        period: TimePeriod,
+        emissions: Vec<CarbonEmission>,
+    ) -> Result<()> {
+        let path = self.dir.join(format!("page-{:05}.jsonl", self.pages.len()));
+        let count = emissions.len();
+        let written = path.clone();
+        spawn_blocking(move || -> Result<()> {
+            let file = File::create(&written).map_err(|e| io_error(&written, e))?;
+            let mut writer = BufWriter::new(file);
+            for emission in &emissions {
+                serde_json::to_writer(&mut writer, emission)?;
+                writer.write_all(b"\n").map_err(|e| io_error(&written, e))?;
+            }
+            writer.flush().map_err(|e| io_error(&written, e))
+        })
+        .await??;
+        debug!("Spilled {} emissions to {}", count, path.display());
+        self.pages.push(SpilledPage {
+            period,
+            path,
+            emissions: count,
+        });
+        Ok(())
+    }
+
+    /// Directory holding the page files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Number of spilled emissions
+    pub fn len(&self) -> usize {
+        self.pages.iter().map(|page| page.emissions).sum()
+    }
+
+    /// Whether no emission was spilled
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Periods of the pages and their number of emissions, in order
+    pub fn pages(&self) -> impl Iterator<Item = (&TimePeriod, usize)> {
+        self.pages.iter().map(|page| (&page.period, page.emissions))
+    }
+
+    /// Emissions read back from disk, one line at a time
+    ///
+    /// Each item is an error if its file cannot be read or its line parsed.
+    pub fn iter(&self) -> SpilledIter<'_> {
+        SpilledIter {
+            pages: self.pages.iter(),
+            current: None,
+        }
+    }
+}
+
+impl Drop for SpilledEmissions {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            warn!(
+                "Failed to delete spilled emissions {}: {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a SpilledEmissions {
+    type Item = Result<CarbonEmission>;
+    type IntoIter = SpilledIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over [`SpilledEmissions`], reading one line at a time
+#[derive(Debug)]
+pub struct SpilledIter<'a> {
+    pages: std::slice::Iter<'a, SpilledPage>,
+    current: Option<(&'a Path, Lines<BufReader<File>>)>,
+}
+
+impl Iterator for SpilledIter<'_> {
+    type Item = Result<CarbonEmission>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((path, lines)) = &mut self.current {
+                match lines.next() {
+                    Some(Ok(line)) => return Some(serde_json::from_str(&line).map_err(Into::into)),
+                    Some(Err(e)) => return Some(Err(io_error(path, e))),
+                    None => self.current = None,
+                }
+            }
+            let page = self.pages.next()?;
+            match File::open(&page.path) {
+                Ok(file) => self.current = Some((&page.path, BufReader::new(file).lines())),
+                Err(e) => return Some(Err(io_error(&page.path, e))),
+            }
+        }
+    }
+}
+
+// Query of the page of `query` covering the months of `window`
+pub(crate) fn page_query(query: &EmissionQuery, window: &TimePeriod) -> EmissionQuery {
+    EmissionQuery {
+        // Up to the last instant of the window's last month, which every
+        // date alignment includes
+        time_period: TimePeriod {
+            start: window.start,
+            end: window.end - Duration::seconds(1),
+        },
+        ..query.clone()
+    }
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> CarbemError {
+    CarbemError::Other(format!(
+        "Failed to access spilled emissions {}: {}",
+        path.display(),
+        error
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CarbemClient;
+    use chrono::{TimeZone, Utc};
+
+    #[tokio::test]
+    async fn test_backfill_is_spilled_by_window() {
+        let client = CarbemClient::demo();
+        let query = EmissionQuery::from_query_string(
+            "provider=azure&start=2024-01-01&end=2024-06-01\
+             &config.report_type=MonthlySummaryReport\
+             &config.subscription_list=00000000-0000-0000-0000-000000000000",
+        )
+        .unwrap();
+        let mut expected = client.query_emissions(&query).await.unwrap();
+        assert!(!expected.is_empty());
+        // Lineage differs per window query
+        expected.iter_mut().for_each(|e| e.lineage = None);
+
+        let options = SpillOptions::new().with_window_months(2);
+        let spilled = client
+            .query_emissions_spilled(&query, &options)
+            .await
+            .unwrap();
+        let windows: Vec<_> = spilled.pages().map(|(period, _)| period.end).collect();
+        assert_eq!(windows.len(), 3);
+        assert_eq!(
+            windows[2],
+            Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(spilled.len(), expected.len());
+        let mut emissions: Vec<CarbonEmission> = spilled.iter().collect::<Result<_>>().unwrap();
+        crate::models::sort_emissions(&mut emissions);
+        emissions.iter_mut().for_each(|e| e.lineage = None);
+        assert_eq!(
+            serde_json::to_value(&emissions).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+
+        let dir = spilled.dir().to_path_buf();
+        assert!(dir.exists());
+        drop(spilled);
+        assert!(!dir.exists());
+    }
+}
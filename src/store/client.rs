@@ -0,0 +1,153 @@
+//! Client answering emission queries from a store
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::{EmissionStore, StoreFilter};
+use crate::client::EmissionSource;
+use crate::error::Result;
+use crate::models::{CarbonEmission, EmissionQuery, EmissionResult, sort_emissions};
+
+/// Queries stored emissions with the same API as [`CarbemClient`](crate::CarbemClient)
+///
+/// The query period is expanded to whole months following `query.date_alignment`
+/// and `query.timezone`, like providers do, so a query returns the same months
+/// whether it is answered live or from the store.
+#[derive(Clone)]
+pub struct StoredClient {
+    store: Arc<dyn EmissionStore>,
+}
+
+impl StoredClient {
+    /// Create a client reading from `store`
+    pub fn new(store: Arc<dyn EmissionStore>) -> Self {
+        Self { store }
+    }
+
+    /// The underlying store
+    pub fn store(&self) -> &Arc<dyn EmissionStore> {
+        &self.store
+    }
+
+    /// Query stored emissions
+    pub async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        let mut filter = StoreFilter::from(query);
+        filter.period = Some(
+            query
+                .time_period
+                .month_range_in(query.date_alignment, &query.timezone)?,
+        );
+
+        let mut emissions = self.store.query(&filter).await?;
+        emissions.retain(|emission| query.matches_tags(emission));
+        sort_emissions(&mut emissions);
+        Ok(emissions)
+    }
+
+    /// Query stored emissions; there are never raw responses
+    pub async fn query_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        Ok(EmissionResult {
+            emissions: self.query_emissions(query).await?,
+            raw_responses: Vec::new(),
+            planned_requests: Vec::new(),
+        })
+    }
+}
+
+impl std::fmt::Debug for StoredClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoredClient")
+            .field("store", &self.store.name())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl EmissionSource for StoredClient {
+    async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        StoredClient::query_emissions(self, query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DateAlignment, TimePeriod};
+    use crate::store::MemoryStore;
+    use chrono::{TimeZone, Utc};
+
+    fn emission(region: &str, month: u32) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "ibm".to_string(),
+            region: region.to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.0,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Months::new(1),
+            },
+            metadata: None,
+        }
+    }
+
+    fn query(start: (u32, u32), end: (u32, u32)) -> EmissionQuery {
+        EmissionQuery {
+            provider: "ibm".to_string(),
+            regions: vec!["dallas".to_string()],
+            time_period: TimePeriod {
+                start: Utc
+                    .with_ymd_and_hms(2024, start.0, start.1, 0, 0, 0)
+                    .unwrap(),
+                end: Utc.with_ymd_and_hms(2024, end.0, end.1, 0, 0, 0).unwrap(),
+            },
+            services: None,
+            resources: None,
+            provider_config: None,
+            raw_response: Default::default(),
+            date_alignment: Default::default(),
+            timezone: Default::default(),
+            dry_run: false,
+            route: Vec::new(),
+            tag_filters: Vec::new(),
+            strict: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_stored_emissions() {
+        let store = Arc::new(MemoryStore::new());
+        store
+            .insert(&[
+                emission("dallas", 4),
+                emission("dallas", 2),
+                emission("dallas", 3),
+                emission("frankfurt", 2),
+            ])
+            .await
+            .unwrap();
+        let client = StoredClient::new(store);
+
+        // Mid-month dates are expanded to February and March
+        let emissions = client
+            .query_emissions(&query((2, 15), (3, 10)))
+            .await
+            .unwrap();
+        let months: Vec<u32> = emissions
+            .iter()
+            .map(|e| chrono::Datelike::month(&e.time_period.start))
+            .collect();
+        assert_eq!(months, vec![2, 3]);
+
+        let mut truncated = query((2, 15), (4, 10));
+        truncated.date_alignment = DateAlignment::Truncate;
+        let source: &dyn EmissionSource = &client;
+        assert_eq!(source.query_emissions(&truncated).await.unwrap().len(), 1);
+
+        truncated.date_alignment = DateAlignment::Strict;
+        assert!(client.query_emissions(&truncated).await.is_err());
+    }
+}
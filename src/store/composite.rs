@@ -0,0 +1,466 @@
+//! Client answering from a store first and fetching missing months live
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use super::{EmissionStore, StoreFilter};
+use crate::client::EmissionSource;
+use crate::error::Result;
+use crate::models::{
+    CarbonEmission, DateAlignment, EmissionQuery, EmissionResult, TimePeriod, next_month_start,
+    sort_emissions,
+};
+use crate::progress::{self, ProgressHandler};
+use crate::subscription::{EmissionEvent, diff};
+
+/// Answers queries from a store and fetches the months it does not cover yet
+///
+/// Months are tracked per query scope: provider, regions, services, resources,
+/// provider configuration, tag filters and timezone. When every month of the query period
+/// is covered for that scope, no provider is called. Otherwise each missing
+/// range of months is queried from `live`, with the period expanded to whole
+/// months, and written back to the store before answering.
+///
+/// Months compacted by the store's retention policy are answered from its
+/// monthly rollups, which are shared by all scopes.
+///
+/// [`invalidate_cache`](Self::invalidate_cache) forces months to be fetched
+/// again, e.g. after a provider published corrected data. When fetching
+/// months again returns restated values, the same months of the provider's
+/// other scopes are invalidated as well.
+///
+/// Each missing range fetched is a chunk of progress, reported to the handler
+/// set with [`with_progress`](Self::with_progress) or by
+/// [`progress::report_to`].
+#[derive(Clone)]
+pub struct CompositeClient {
+    live: Arc<dyn EmissionSource>,
+    store: Arc<dyn EmissionStore>,
+    progress: Option<Arc<dyn ProgressHandler>>,
+}
+
+impl CompositeClient {
+    /// Create a client reading from `store` and falling back to `live`
+    pub fn new(live: Arc<dyn EmissionSource>, store: Arc<dyn EmissionStore>) -> Self {
+        Self {
+            live,
+            store,
+            progress: None,
+        }
+    }
+
+    /// Report the progress of every query, e.g. a backfill, to `handler`
+    pub fn with_progress(mut self, handler: Arc<dyn ProgressHandler>) -> Self {
+        self.progress = Some(handler);
+        self
+    }
+
+    /// The underlying store
+    pub fn store(&self) -> &Arc<dyn EmissionStore> {
+        &self.store
+    }
+
+    /// Query emissions, fetching months missing from the store
+    ///
+    /// Dry runs are passed to `live` as they are: they return no data to
+    /// store, and must not mark months as covered.
+    pub async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        if query.dry_run {
+            return self.live.query_emissions(query).await;
+        }
+        match &self.progress {
+            Some(handler) => progress::report_to(handler.clone(), self.fetch(query)).await,
+            None => self.fetch(query).await,
+        }
+    }
+
+    async fn fetch(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        let period = query
+            .time_period
+            .month_range_in(query.date_alignment, &query.timezone)?;
+        let scope = scope_key(query);
+
+        let covered = self.store.covered_months(&scope).await?;
+        let mut months = Vec::new();
+        let mut month = period.start;
+        while month < period.end {
+            months.push(month);
+            month = next_month_start(month, &query.timezone);
+        }
+
+        let missing = missing_ranges(&months, |month| covered.contains(month));
+        let chunks = missing.len() as u64;
+        for (chunk, missing) in missing.into_iter().enumerate() {
+            let first = missing[0];
+            let last = missing[missing.len() - 1];
+            let mut live_query = query.clone();
+            live_query.time_period = TimePeriod {
+                start: first,
+                end: last,
+            };
+            live_query.date_alignment = DateAlignment::ExpandToFullMonths;
+
+            let emissions = self.live.query_emissions(&live_query).await?;
+            let fetched = TimePeriod {
+                start: first,
+                end: next_month_start(last, &query.timezone),
+            };
+            // Emissions kept from invalidated months, to detect restatements
+            let previous = StoreFilter {
+                scope: Some(scope.clone()),
+                period: Some(fetched.clone()),
+                ..Default::default()
+            };
+            let previous = self.store.query(&previous).await?;
+            self.store
+                .replace_scope(&scope, &fetched, missing, &emissions)
+                .await?;
+            for event in diff(&previous, &emissions) {
+                if let EmissionEvent::Restated { current, .. } = event {
+                    self.invalidate(&current.provider, &current.time_period, Some(&scope))
+                        .await?;
+                }
+            }
+            progress::chunk(
+                || {
+                    format!(
+                        "{} {}..{}",
+                        query.provider,
+                        first.format("%Y-%m"),
+                        last.format("%Y-%m")
+                    )
+                },
+                chunk as u64 + 1,
+                Some(chunks),
+            );
+        }
+
+        // The scope already selects what the provider returned for the query
+        let filter = StoreFilter {
+            scope: Some(scope),
+            provider: Some(query.provider.clone()),
+            period: Some(period),
+            ..Default::default()
+        };
+        let mut emissions = self.store.query(&filter).await?;
+        sort_emissions(&mut emissions);
+        Ok(emissions)
+    }
+
+    /// Fetch the months of `provider` overlapping `period` again on their next query
+    ///
+    /// Returns the number of months invalidated, over all scopes.
+    pub async fn invalidate_cache(&self, provider: &str, period: &TimePeriod) -> Result<usize> {
+        self.invalidate(provider, period, None).await
+    }
+
+    /// Invalidate the months of the restatements among `events`, e.g. from
+    /// [`CarbemClient::subscribe`](crate::CarbemClient::subscribe)
+    ///
+    /// Returns the number of months invalidated, over all scopes.
+    pub async fn invalidate_restated(&self, events: &[EmissionEvent]) -> Result<usize> {
+        let mut invalidated = 0;
+        for event in events {
+            if let EmissionEvent::Restated { current, .. } = event {
+                invalidated += self
+                    .invalidate_cache(&current.provider, &current.time_period)
+                    .await?;
+            }
+        }
+        Ok(invalidated)
+    }
+
+    // Invalidate the months of every scope of `provider` but `except`
+    async fn invalidate(
+        &self,
+        provider: &str,
+        period: &TimePeriod,
+        except: Option<&str>,
+    ) -> Result<usize> {
+        let mut invalidated = 0;
+        for scope in self.store.scopes().await? {
+            if except != Some(scope.as_str()) && scope_provider(&scope).as_deref() == Some(provider)
+            {
+                invalidated += self.store.uncover(&scope, period).await?;
+            }
+        }
+        Ok(invalidated)
+    }
+
+    /// Query emissions; raw responses are not kept by the store
+    pub async fn query_emissions_with_raw(&self, query: &EmissionQuery) -> Result<EmissionResult> {
+        Ok(EmissionResult {
+            emissions: self.query_emissions(query).await?,
+            raw_responses: Vec::new(),
+            planned_requests: Vec::new(),
+        })
+    }
+}
+
+impl std::fmt::Debug for CompositeClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompositeClient")
+            .field("store", &self.store.name())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl EmissionSource for CompositeClient {
+    async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+        CompositeClient::query_emissions(self, query).await
+    }
+}
+
+// Identifies the data a query fetches, whatever its period
+fn scope_key(query: &EmissionQuery) -> String {
+    let mut regions = query.regions.clone();
+    regions.sort();
+    json!({
+        "provider": query.provider,
+        "regions": regions,
+        "services": query.services,
+        "resources": query.resources,
+        "provider_config": query.provider_config,
+        // Tag-filtered queries store only the matching rows
+        "tag_filters": query.tag_filters,
+        "timezone": query.timezone,
+    })
+    .to_string()
+}
+
+// Provider of a scope built by `scope_key`
+fn scope_provider(scope: &str) -> Option<String> {
+    let scope: serde_json::Value = serde_json::from_str(scope).ok()?;
+    scope["provider"].as_str().map(str::to_string)
+}
+
+// Contiguous runs of `months` that are not covered
+fn missing_ranges(
+    months: &[DateTime<Utc>],
+    is_covered: impl Fn(&DateTime<Utc>) -> bool,
+) -> Vec<&[DateTime<Utc>]> {
+    months
+        .split(is_covered)
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TagFilter;
+    use crate::progress::Progress;
+    use crate::store::MemoryStore;
+    use chrono::{Datelike, TimeZone};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Returns one emission per month of the query and records the queries
+    #[derive(Default)]
+    struct FakeSource {
+        queries: Mutex<Vec<TimePeriod>>,
+        restated: AtomicBool,
+    }
+
+    #[async_trait]
+    impl EmissionSource for FakeSource {
+        async fn query_emissions(&self, query: &EmissionQuery) -> Result<Vec<CarbonEmission>> {
+            self.queries.lock().unwrap().push(query.time_period.clone());
+            let period = query
+                .time_period
+                .month_range_in(query.date_alignment, &query.timezone)?;
+            let mut emissions = Vec::new();
+            let mut month = period.start;
+            while month < period.end {
+                let end = next_month_start(month, &query.timezone);
+                emissions.push(CarbonEmission {
+                    provider: query.provider.clone(),
+                    region: "dallas".to_string(),
+                    service: None,
+                    service_category: None,
+                    lineage: None,
+                    emissions_kg_co2eq: f64::from(month.month())
+                        * if self.restated.load(Ordering::SeqCst) {
+                            1.5
+                        } else {
+                            1.0
+                        },
+                    time_period: TimePeriod { start: month, end },
+                    metadata: None,
+                });
+                month = end;
+            }
+            Ok(emissions)
+        }
+    }
+
+    fn query(start_month: u32, end_month: u32) -> EmissionQuery {
+        EmissionQuery {
+            provider: "ibm".to_string(),
+            regions: vec!["dallas".to_string()],
+            time_period: TimePeriod {
+                start: Utc
+                    .with_ymd_and_hms(2024, start_month, 10, 0, 0, 0)
+                    .unwrap(),
+                end: Utc.with_ymd_and_hms(2024, end_month, 10, 0, 0, 0).unwrap(),
+            },
+            services: None,
+            resources: None,
+            provider_config: None,
+            raw_response: Default::default(),
+            date_alignment: Default::default(),
+            timezone: Default::default(),
+            dry_run: false,
+            route: Vec::new(),
+            tag_filters: Vec::new(),
+            strict: false,
+        }
+    }
+
+    fn months(emissions: &[CarbonEmission]) -> Vec<u32> {
+        emissions
+            .iter()
+            .map(|e| e.time_period.start.month())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_fetches_only_missing_months() {
+        let live = Arc::new(FakeSource::default());
+        let client = CompositeClient::new(live.clone(), Arc::new(MemoryStore::new()));
+
+        assert_eq!(
+            months(&client.query_emissions(&query(3, 4)).await.unwrap()),
+            vec![3, 4]
+        );
+        // Fully covered: answered from the store
+        assert_eq!(
+            months(&client.query_emissions(&query(3, 3)).await.unwrap()),
+            vec![3]
+        );
+        assert_eq!(live.queries.lock().unwrap().len(), 1);
+
+        // Only the months around the covered range are fetched
+        let emissions = client.query_emissions(&query(1, 6)).await.unwrap();
+        assert_eq!(months(&emissions), vec![1, 2, 3, 4, 5, 6]);
+        let queries = live.queries.lock().unwrap();
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[1].start.month(), 1);
+        assert_eq!(queries[1].end.month(), 2);
+        assert_eq!(queries[2].start.month(), 5);
+        assert_eq!(queries[2].end.month(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_dry_runs_leave_the_store_untouched() {
+        let live = Arc::new(FakeSource::default());
+        let store = Arc::new(MemoryStore::new());
+        let client = CompositeClient::new(live.clone(), store.clone());
+
+        let dry_run = EmissionQuery {
+            dry_run: true,
+            ..query(3, 4)
+        };
+        client.query_emissions(&dry_run).await.unwrap();
+        assert!(store.scopes().await.unwrap().is_empty());
+
+        // The months are still fetched by the next real query
+        client.query_emissions(&query(3, 4)).await.unwrap();
+        assert_eq!(live.queries.lock().unwrap().len(), 2);
+    }
+
+    #[derive(Default)]
+    struct RecordingHandler(Mutex<Vec<Progress>>);
+
+    impl ProgressHandler for RecordingHandler {
+        fn on_chunk(&self, progress: &Progress) {
+            self.0.lock().unwrap().push(progress.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_progress_per_missing_range() {
+        let handler = Arc::new(RecordingHandler::default());
+        let client = CompositeClient::new(
+            Arc::new(FakeSource::default()),
+            Arc::new(MemoryStore::new()),
+        )
+        .with_progress(handler.clone());
+
+        client.query_emissions(&query(3, 4)).await.unwrap();
+        handler.0.lock().unwrap().clear();
+        client.query_emissions(&query(1, 6)).await.unwrap();
+        let chunks = handler.0.lock().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].label, "ibm 2024-01..2024-02");
+        assert_eq!((chunks[1].completed, chunks[1].total), (2, Some(2)));
+        assert_eq!(chunks[1].fraction(), Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_scopes_are_independent() {
+        let live = Arc::new(FakeSource::default());
+        let client = CompositeClient::new(live.clone(), Arc::new(MemoryStore::new()));
+
+        client.query_emissions(&query(1, 2)).await.unwrap();
+        let mut other_regions = query(1, 2);
+        other_regions.regions = vec!["frankfurt".to_string()];
+        let emissions = client.query_emissions(&other_regions).await.unwrap();
+
+        // Same months fetched again for the other scope, without duplicates
+        assert_eq!(months(&emissions), vec![1, 2]);
+        assert_eq!(live.queries.lock().unwrap().len(), 2);
+
+        // Rows stored for a tag-filtered query do not cover unfiltered ones
+        let mut tagged = query(3, 3);
+        tagged.tag_filters = vec![TagFilter::equals("team", "search")];
+        client.query_emissions(&tagged).await.unwrap();
+        client.query_emissions(&query(3, 3)).await.unwrap();
+        assert_eq!(live.queries.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_invalidation() {
+        let live = Arc::new(FakeSource::default());
+        let client = CompositeClient::new(live.clone(), Arc::new(MemoryStore::new()));
+        let mut other_regions = query(1, 3);
+        other_regions.regions = vec!["frankfurt".to_string()];
+        client.query_emissions(&query(1, 3)).await.unwrap();
+        client.query_emissions(&other_regions).await.unwrap();
+        let queries = || live.queries.lock().unwrap().len();
+        assert_eq!(queries(), 2);
+
+        // February of both scopes is fetched again
+        let february = query(2, 2).time_period;
+        assert_eq!(
+            client.invalidate_cache("azure", &february).await.unwrap(),
+            0
+        );
+        assert_eq!(client.invalidate_cache("ibm", &february).await.unwrap(), 2);
+        let emissions = client.query_emissions(&query(1, 3)).await.unwrap();
+        assert_eq!(months(&emissions), vec![1, 2, 3]);
+        assert_eq!(queries(), 3);
+        assert_eq!(live.queries.lock().unwrap()[2].start.month(), 2);
+
+        // A restatement found by one scope invalidates the others
+        live.restated.store(true, Ordering::SeqCst);
+        let march = query(3, 3).time_period;
+        client
+            .store()
+            .uncover(&scope_key(&query(1, 3)), &march)
+            .await
+            .unwrap();
+        let emissions = client.query_emissions(&query(1, 3)).await.unwrap();
+        assert_eq!(emissions[2].emissions_kg_co2eq, 4.5);
+        assert_eq!(queries(), 4);
+        // February, still invalidated, and March are fetched together
+        client.query_emissions(&other_regions).await.unwrap();
+        assert_eq!(queries(), 5);
+        assert_eq!(live.queries.lock().unwrap()[4].start.month(), 2);
+        assert_eq!(live.queries.lock().unwrap()[4].end.month(), 3);
+    }
+}
@@ -0,0 +1,276 @@
+//! In-memory emission store
+
+use std::collections::{BTreeSet, HashMap};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Months, Utc};
+use tokio::sync::RwLock;
+
+use super::{EmissionStore, MonthlyRollups, PruneReport, RetentionPolicy, StoreFilter};
+use crate::error::Result;
+use crate::models::{CarbonEmission, TimePeriod};
+
+/// Emission store keeping everything in memory, e.g. for tests or short-lived processes
+///
+/// Monthly rollups are maintained on insert.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    retention: RetentionPolicy,
+    data: RwLock<StoredData>,
+}
+
+#[derive(Debug, Default)]
+struct StoredData {
+    // Emissions with the scope they were stored for
+    detail: Vec<(Option<String>, CarbonEmission)>,
+    rollups: MonthlyRollups,
+
+    // Months covered per scope
+    coverage: HashMap<String, BTreeSet<DateTime<Utc>>>,
+
+    // Detail starting before this instant was compacted; only rollups remain
+    compacted_before: Option<DateTime<Utc>>,
+}
+
+impl StoredData {
+    fn is_compacted(&self, emission: &CarbonEmission) -> bool {
+        self.compacted_before
+            .is_some_and(|cutoff| emission.time_period.start < cutoff)
+    }
+
+    fn add(&mut self, scope: Option<&str>, emissions: &[CarbonEmission]) {
+        self.rollups.add(emissions);
+        // Late data for compacted months only contributes to rollups
+        let detail: Vec<(Option<String>, CarbonEmission)> = emissions
+            .iter()
+            .filter(|e| !self.is_compacted(e))
+            .map(|e| (scope.map(str::to_string), e.clone()))
+            .collect();
+        self.detail.extend(detail);
+    }
+}
+
+impl MemoryStore {
+    /// Create an empty store keeping everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply `retention` when pruning
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+#[async_trait]
+impl EmissionStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn insert(&self, emissions: &[CarbonEmission]) -> Result<()> {
+        self.data.write().await.add(None, emissions);
+        Ok(())
+    }
+
+    async fn query(&self, filter: &StoreFilter) -> Result<Vec<CarbonEmission>> {
+        let data = self.data.read().await;
+        let mut emissions: Vec<CarbonEmission> = data
+            .detail
+            .iter()
+            .filter(|(scope, e)| filter.matches_scope(scope.as_deref()) && filter.matches(e))
+            .map(|(_, e)| e.clone())
+            .collect();
+        // Rollups of compacted months belong to no scope
+        emissions.extend(
+            data.rollups
+                .emissions(|e| data.is_compacted(e) && filter.matches(e)),
+        );
+        Ok(emissions)
+    }
+
+    async fn replace_scope(
+        &self,
+        scope: &str,
+        period: &TimePeriod,
+        months: &[DateTime<Utc>],
+        emissions: &[CarbonEmission],
+    ) -> Result<()> {
+        let mut data = self.data.write().await;
+        let (replaced, kept) = std::mem::take(&mut data.detail)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(stored_scope, e)| {
+                stored_scope.as_deref() == Some(scope)
+                    && e.time_period.start >= period.start
+                    && e.time_period.start < period.end
+            });
+        data.detail = kept;
+        let replaced: Vec<CarbonEmission> = replaced.into_iter().map(|(_, e)| e).collect();
+        data.rollups.subtract(&replaced);
+
+        data.add(Some(scope), emissions);
+        data.coverage
+            .entry(scope.to_string())
+            .or_default()
+            .extend(months.iter().copied());
+        Ok(())
+    }
+
+    async fn covered_months(&self, scope: &str) -> Result<BTreeSet<DateTime<Utc>>> {
+        Ok(self
+            .data
+            .read()
+            .await
+            .coverage
+            .get(scope)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn scopes(&self) -> Result<Vec<String>> {
+        Ok(self.data.read().await.coverage.keys().cloned().collect())
+    }
+
+    async fn uncover(&self, scope: &str, period: &TimePeriod) -> Result<usize> {
+        let mut data = self.data.write().await;
+        let Some(months) = data.coverage.get_mut(scope) else {
+            return Ok(0);
+        };
+        let before = months.len();
+        months.retain(|month| *month >= period.end || *month + Months::new(1) <= period.start);
+        Ok(before - months.len())
+    }
+
+    async fn monthly_rollups(&self, filter: &StoreFilter) -> Result<Vec<CarbonEmission>> {
+        Ok(self
+            .data
+            .read()
+            .await
+            .rollups
+            .emissions(|e| filter.matches(e)))
+    }
+
+    async fn prune(&self, now: DateTime<Utc>) -> Result<PruneReport> {
+        let mut data = self.data.write().await;
+        let mut report = PruneReport::default();
+
+        if let Some(cutoff) = self.retention.detail_cutoff(now) {
+            let before = data.detail.len();
+            data.detail.retain(|(_, e)| e.time_period.start >= cutoff);
+            report.compacted = before - data.detail.len();
+            data.compacted_before = data.compacted_before.max(Some(cutoff));
+        }
+
+        if let Some(cutoff) = self.retention.rollup_cutoff(now) {
+            report.rollups_deleted = data.rollups.remove_before(cutoff);
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregation::{Dimension, EmissionDataset};
+    use crate::models::TimePeriod;
+    use chrono::TimeZone;
+
+    fn emission(service: &str, year: i32, month: u32, day: u32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: Some(service.to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(1),
+            },
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prune_compacts_and_deletes() {
+        let store = MemoryStore::new().with_retention(
+            RetentionPolicy::keep_all()
+                .with_detail_months(2)
+                .with_rollup_months(12),
+        );
+        store
+            .insert(&[
+                emission("Storage", 2023, 1, 5, 1.0),
+                emission("Storage", 2024, 1, 5, 1.0),
+                emission("Storage", 2024, 1, 20, 2.0),
+                emission("Compute", 2024, 1, 20, 4.0),
+                emission("Storage", 2024, 5, 1, 8.0),
+            ])
+            .await
+            .unwrap();
+
+        let now = Utc.with_ymd_and_hms(2024, 6, 10, 0, 0, 0).unwrap();
+        let report = store.prune(now).await.unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                compacted: 4,
+                rollups_deleted: 1,
+            }
+        );
+
+        let stored = EmissionDataset::new(store.query(&StoreFilter::default()).await.unwrap());
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored.total_kg_co2eq(), 15.0);
+        assert_eq!(
+            stored.group_by_dimension(Dimension::Service)["Storage"],
+            11.0
+        );
+
+        // Pruning again is a no-op
+        assert_eq!(store.prune(now).await.unwrap(), PruneReport::default());
+
+        // Late data for a compacted month is rolled up, not kept as detail
+        store
+            .insert(&[emission("Compute", 2024, 1, 25, 1.0)])
+            .await
+            .unwrap();
+        let stored = EmissionDataset::new(store.query(&StoreFilter::default()).await.unwrap());
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored.total_kg_co2eq(), 16.0);
+    }
+
+    #[tokio::test]
+    async fn test_rollups_maintained_on_insert() {
+        let store = MemoryStore::new();
+        store
+            .insert(&[
+                emission("Storage", 2024, 1, 1, 1.0),
+                emission("Storage", 2024, 1, 2, 2.0),
+            ])
+            .await
+            .unwrap();
+        store
+            .insert(&[emission("Storage", 2024, 1, 3, 3.0)])
+            .await
+            .unwrap();
+
+        let rollups = store
+            .monthly_rollups(&StoreFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups[0].emissions_kg_co2eq, 6.0);
+        assert_eq!(
+            rollups[0].time_period.end,
+            Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+        );
+
+        // Detail is untouched without a retention policy
+        store.prune(Utc::now()).await.unwrap();
+        assert_eq!(store.query(&StoreFilter::default()).await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,330 @@
+//! Storage of collected emissions
+//!
+//! An [`EmissionStore`] keeps the history of collected emissions so that it
+//! can be queried without calling provider APIs again. Stores apply a
+//! [`RetentionPolicy`] when [`prune`](EmissionStore::prune) is called, e.g.
+//! after every collection run.
+
+pub mod client;
+pub mod composite;
+pub mod memory;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Months, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::models::{CarbonEmission, EmissionQuery, TimePeriod};
+
+pub use client::StoredClient;
+pub use composite::CompositeClient;
+pub use memory::MemoryStore;
+
+/// Trait that all emission storage backends implement
+#[async_trait]
+pub trait EmissionStore: Send + Sync {
+    /// Get the store name
+    fn name(&self) -> &'static str;
+
+    /// Store emissions
+    async fn insert(&self, emissions: &[CarbonEmission]) -> Result<()>;
+
+    /// Stored emissions matching `filter`
+    async fn query(&self, filter: &StoreFilter) -> Result<Vec<CarbonEmission>>;
+
+    /// Replace the emissions of `scope` starting within `period` and mark `months` as covered
+    ///
+    /// A scope identifies a set of fetched data, e.g. a query's provider,
+    /// regions and configuration. Emissions stored with [`insert`](Self::insert)
+    /// belong to no scope.
+    async fn replace_scope(
+        &self,
+        scope: &str,
+        period: &TimePeriod,
+        months: &[DateTime<Utc>],
+        emissions: &[CarbonEmission],
+    ) -> Result<()>;
+
+    /// First instants of the months covered for `scope`
+    async fn covered_months(&self, scope: &str) -> Result<BTreeSet<DateTime<Utc>>>;
+
+    /// Scopes with covered months
+    async fn scopes(&self) -> Result<Vec<String>>;
+
+    /// Mark the months of `scope` overlapping `period` as not covered, returning how many were
+    ///
+    /// Their emissions are kept until the months are replaced.
+    async fn uncover(&self, scope: &str, period: &TimePeriod) -> Result<usize>;
+
+    /// Monthly totals per provider, region and service of the emissions matching `filter`
+    ///
+    /// Rollups have no metadata and span whole calendar months. Backends that
+    /// materialize them on insert answer without scanning the detail; the
+    /// default implementation aggregates [`query`](Self::query).
+    async fn monthly_rollups(&self, filter: &StoreFilter) -> Result<Vec<CarbonEmission>> {
+        let mut rollups = MonthlyRollups::default();
+        rollups.add(&self.query(filter).await?);
+        Ok(rollups.emissions(|_| true))
+    }
+
+    /// Apply the retention policy of the store as of `now`
+    async fn prune(&self, now: DateTime<Utc>) -> Result<PruneReport>;
+}
+
+/// Selection of stored emissions
+///
+/// Empty lists match everything. An emission matches `period` when its own
+/// period starts within it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreFilter {
+    /// Optional: only emissions stored for this scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+
+    /// Optional: provider name
+    pub provider: Option<String>,
+
+    /// Regions to include
+    pub regions: Vec<String>,
+
+    /// Services to include
+    pub services: Vec<String>,
+
+    /// Optional: time period emissions start in
+    pub period: Option<TimePeriod>,
+}
+
+impl StoreFilter {
+    /// Whether emissions stored for `scope` match the filter
+    pub fn matches_scope(&self, scope: Option<&str>) -> bool {
+        self.scope.is_none() || self.scope.as_deref() == scope
+    }
+
+    /// Whether `emission` matches the filter, scope aside
+    pub fn matches(&self, emission: &CarbonEmission) -> bool {
+        self.provider
+            .as_ref()
+            .is_none_or(|provider| *provider == emission.provider)
+            && (self.regions.is_empty() || self.regions.contains(&emission.region))
+            && (self.services.is_empty()
+                || emission
+                    .service
+                    .as_ref()
+                    .is_some_and(|service| self.services.contains(service)))
+            && self.period.as_ref().is_none_or(|period| {
+                emission.time_period.start >= period.start
+                    && emission.time_period.start < period.end
+            })
+    }
+}
+
+impl From<&EmissionQuery> for StoreFilter {
+    fn from(query: &EmissionQuery) -> Self {
+        Self {
+            scope: None,
+            provider: Some(query.provider.clone()),
+            regions: query.regions.clone(),
+            services: query.services.clone().unwrap_or_default(),
+            period: Some(query.time_period.clone()),
+        }
+    }
+}
+
+/// How long stored emissions are kept
+///
+/// Detail is every emission as collected. Once older than `detail_months`,
+/// detail is compacted into monthly rollups per provider, region and service,
+/// which are deleted once older than `rollup_months`. `None` keeps data
+/// forever. Ages count whole calendar months before the current one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Months of detail to keep
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail_months: Option<u32>,
+
+    /// Months of monthly rollups to keep
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollup_months: Option<u32>,
+}
+
+impl RetentionPolicy {
+    /// Keep everything
+    pub fn keep_all() -> Self {
+        Self::default()
+    }
+
+    /// Keep `months` months of detail
+    pub fn with_detail_months(mut self, months: u32) -> Self {
+        self.detail_months = Some(months);
+        self
+    }
+
+    /// Keep `months` months of rollups
+    pub fn with_rollup_months(mut self, months: u32) -> Self {
+        self.rollup_months = Some(months);
+        self
+    }
+
+    /// Detail starting before this instant is compacted
+    pub fn detail_cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.detail_months.map(|months| months_before(now, months))
+    }
+
+    /// Rollups starting before this instant are deleted
+    pub fn rollup_cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.rollup_months.map(|months| months_before(now, months))
+    }
+}
+
+// Start of the month `months` months before the month of `now`
+fn months_before(now: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    month_start(now) - Months::new(months)
+}
+
+fn month_start(instant: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(instant.year(), instant.month(), 1, 0, 0, 0)
+        .single()
+        .expect("first day of an existing month is valid")
+}
+
+/// Outcome of applying a retention policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    /// Detailed emissions removed, now only available as rollups
+    pub compacted: usize,
+
+    /// Rollups deleted
+    pub rollups_deleted: usize,
+}
+
+// Key of a monthly rollup: provider, region, service and month start
+type RollupKey = (String, String, Option<String>, DateTime<Utc>);
+
+fn rollup_key(emission: &CarbonEmission) -> RollupKey {
+    (
+        emission.provider.clone(),
+        emission.region.clone(),
+        emission.service.clone(),
+        month_start(emission.time_period.start),
+    )
+}
+
+/// Monthly totals per provider, region and service, updated incrementally
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MonthlyRollups {
+    // Total and number of emissions of each rollup
+    totals: BTreeMap<RollupKey, (f64, usize)>,
+}
+
+impl MonthlyRollups {
+    /// Add emissions to the totals of their month
+    pub(crate) fn add(&mut self, emissions: &[CarbonEmission]) {
+        for emission in emissions {
+            let (total, count) = self.totals.entry(rollup_key(emission)).or_insert((0.0, 0));
+            *total += emission.emissions_kg_co2eq;
+            *count += 1;
+        }
+    }
+
+    /// Remove previously added emissions from the totals of their month
+    pub(crate) fn subtract(&mut self, emissions: &[CarbonEmission]) {
+        for emission in emissions {
+            let key = rollup_key(emission);
+            if let Some((total, count)) = self.totals.get_mut(&key) {
+                *total -= emission.emissions_kg_co2eq;
+                *count -= 1;
+                if *count == 0 {
+                    self.totals.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Delete rollups of months starting before `cutoff`, returning how many were deleted
+    pub(crate) fn remove_before(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let before = self.totals.len();
+        self.totals.retain(|(_, _, _, month), _| *month >= cutoff);
+        before - self.totals.len()
+    }
+
+    /// Rollups as emissions, keeping those matching `predicate`
+    pub(crate) fn emissions(
+        &self,
+        predicate: impl Fn(&CarbonEmission) -> bool,
+    ) -> Vec<CarbonEmission> {
+        self.totals
+            .iter()
+            .map(
+                |((provider, region, service, month), (kg, _))| CarbonEmission {
+                    provider: provider.clone(),
+                    region: region.clone(),
+                    service: service.clone(),
+                    service_category: None,
+                    lineage: None,
+                    emissions_kg_co2eq: *kg,
+                    time_period: TimePeriod {
+                        start: *month,
+                        end: *month + Months::new(1),
+                    },
+                    metadata: None,
+                },
+            )
+            .filter(|e| predicate(e))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_cutoffs() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 15, 12, 0, 0).unwrap();
+        let policy = RetentionPolicy::keep_all().with_detail_months(13);
+
+        assert_eq!(
+            policy.detail_cutoff(now),
+            Some(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(policy.rollup_cutoff(now), None);
+    }
+
+    #[test]
+    fn test_store_filter() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let emission = CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 1.0,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(31),
+            },
+            metadata: None,
+        };
+
+        assert!(StoreFilter::default().matches(&emission));
+        let filter = StoreFilter {
+            provider: Some("azure".to_string()),
+            period: Some(TimePeriod {
+                start,
+                end: start + chrono::Duration::days(1),
+            }),
+            ..Default::default()
+        };
+        assert!(filter.matches(&emission));
+        assert!(
+            !StoreFilter {
+                services: vec!["Storage".to_string()],
+                ..Default::default()
+            }
+            .matches(&emission)
+        );
+    }
+}
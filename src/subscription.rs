@@ -0,0 +1,211 @@
+//! Change events from repeated emission queries
+//!
+//! [`CarbemClient::subscribe`](crate::CarbemClient::subscribe) runs a query on
+//! an interval and compares each result with the previous one. Emissions for a
+//! period not seen before, such as a newly published month, are reported as
+//! [`EmissionEvent::Published`]; emissions whose value changed since the
+//! previous poll, as providers restate past months, as
+//! [`EmissionEvent::Restated`]. [`diff`] applies the same comparison to any
+//! two results.
+
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+
+use crate::aggregation::{DataPointChange, DataPointKey, EmissionDataset, data_point_key};
+use crate::client::CarbemClient;
+use crate::error::Result;
+use crate::models::{CarbonEmission, EmissionQuery};
+use crate::runtime::runtime;
+
+/// Events buffered for a slow consumer before polling pauses
+const EVENT_BUFFER: usize = 256;
+
+/// A change between two results of the same query
+// Events are few and short-lived: keep both emissions inline for matching
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum EmissionEvent {
+    /// An emission for a provider, region, service and period not seen before
+    Published(CarbonEmission),
+
+    /// An emission whose value changed since the previous poll
+    Restated {
+        /// The emission as previously reported
+        previous: CarbonEmission,
+
+        /// The emission as now reported
+        current: CarbonEmission,
+    },
+}
+
+/// Events turning `previous` into `current`
+///
+/// Emissions are matched by data point, as in [`EmissionDataset::diff`].
+/// Data points missing from `current` produce no event, as a provider may
+/// drop them from a single response without retracting them.
+pub fn diff(previous: &[CarbonEmission], current: &[CarbonEmission]) -> Vec<EmissionEvent> {
+    let previous = EmissionDataset::new(previous.to_vec());
+    previous
+        .diff(&EmissionDataset::new(current.to_vec()))
+        .changes
+        .into_iter()
+        .filter_map(|change| match change {
+            DataPointChange::Added { current } => Some(EmissionEvent::Published(current)),
+            DataPointChange::Changed { previous, current } => {
+                Some(EmissionEvent::Restated { previous, current })
+            }
+            DataPointChange::Removed { .. } => None,
+        })
+        .collect()
+}
+
+// Events for `current` against every emission seen so far, which it updates
+fn changes(
+    known: &mut BTreeMap<DataPointKey, CarbonEmission>,
+    current: &[CarbonEmission],
+) -> Vec<EmissionEvent> {
+    let mut events = Vec::new();
+    for emission in current {
+        match known.insert(data_point_key(emission), emission.clone()) {
+            None => events.push(EmissionEvent::Published(emission.clone())),
+            Some(previous) if previous.emissions_kg_co2eq != emission.emissions_kg_co2eq => {
+                events.push(EmissionEvent::Restated {
+                    previous,
+                    current: emission.clone(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    events
+}
+
+/// Stream of change events from a subscribed query
+///
+/// The first poll publishes every emission of the result. Later polls are
+/// compared with every emission seen so far. Failed polls are yielded as
+/// errors and do not end the stream. Polling stops when the stream is
+/// dropped.
+#[derive(Debug)]
+pub struct EmissionEvents {
+    receiver: mpsc::Receiver<Result<EmissionEvent>>,
+}
+
+impl EmissionEvents {
+    // Run `query` with `client` every `interval` on a background task
+    pub(crate) fn spawn(client: CarbemClient, query: EmissionQuery, interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel(EVENT_BUFFER);
+        let runtime = runtime();
+        let sleeper = runtime.clone();
+        runtime.spawn(Box::pin(async move {
+            let poll = async {
+                let mut known = BTreeMap::new();
+                loop {
+                    let started = Instant::now();
+                    let events = match client.query_emissions(&query).await {
+                        Ok(current) => changes(&mut known, &current).into_iter().map(Ok).collect(),
+                        Err(e) => vec![Err(e)],
+                    };
+                    for event in events {
+                        if sender.send(event).await.is_err() {
+                            return;
+                        }
+                    }
+                    sleeper
+                        .sleep(interval.saturating_sub(started.elapsed()))
+                        .await;
+                }
+            };
+            // Stop as soon as the stream is dropped, even mid-query
+            tokio::select! {
+                _ = sender.closed() => {}
+                _ = poll => {}
+            }
+        }));
+        Self { receiver }
+    }
+}
+
+impl Stream for EmissionEvents {
+    type Item = Result<EmissionEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimePeriod;
+    use chrono::{Datelike, TimeZone, Utc};
+
+    fn emission(month: u32, kg: f64) -> CarbonEmission {
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: Some("Virtual Machines".to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start: Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap(),
+                end: Utc.with_ymd_and_hms(2024, month + 1, 1, 0, 0, 0).unwrap(),
+            },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_reports_new_and_restated_emissions() {
+        let previous = vec![emission(1, 10.0), emission(2, 20.0)];
+        let current = vec![emission(1, 10.0), emission(2, 21.5), emission(3, 30.0)];
+
+        let events = diff(&previous, &current);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            EmissionEvent::Restated { previous, current }
+                if previous.emissions_kg_co2eq == 20.0 && current.emissions_kg_co2eq == 21.5
+        ));
+        assert!(matches!(
+            &events[1],
+            EmissionEvent::Published(e) if e.time_period.start.month() == 3
+        ));
+        assert!(diff(&current, &previous[..1]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_publishes_changes_only() {
+        use tokio_stream::StreamExt;
+
+        let client = CarbemClient::demo();
+        let query: EmissionQuery = serde_json::from_value(serde_json::json!({
+            "provider": "azure",
+            "regions": ["westeurope"],
+            "time_period": {"start": "2024-01-01T00:00:00Z", "end": "2024-02-28T00:00:00Z"}
+        }))
+        .unwrap();
+        let expected = client.query_emissions(&query).await.unwrap();
+
+        let mut events = client.subscribe(query, Duration::from_millis(5));
+        for emission in expected {
+            let event = events.next().await.unwrap().unwrap();
+            assert!(matches!(
+                event,
+                EmissionEvent::Published(e)
+                    if e.emissions_kg_co2eq == emission.emissions_kg_co2eq
+                        && e.time_period.start == emission.time_period.start
+            ));
+        }
+
+        // Later polls return the same values: nothing to report
+        let next = tokio::time::timeout(Duration::from_millis(50), events.next()).await;
+        assert!(next.is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! Support bundles for reporting provider issues
+//!
+//! A [`SupportBundle`] gathers what is needed to investigate a problem in a
+//! single JSON document: versions, enabled features, registered providers,
+//! the configuration and the last provider exchanges of a client. Credentials
+//! and identifiers are redacted, so bundles can be attached to issues.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::capture::CapturedExchange;
+use crate::client::CarbemClient;
+use crate::config::ClientConfig;
+use crate::error::Result;
+use crate::providers::config::ProviderQueryConfig;
+use crate::providers::registry::{ProviderInfo, ProviderRegistry};
+use crate::redact::Redactor;
+use crate::runtime::fs;
+use crate::schema::SchemaWarning;
+use crate::sinks::Compression;
+use crate::sinks::jsonl::io_error;
+use crate::transport::ProxyConfig;
+
+/// Everything needed to investigate an issue, with secrets redacted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupportBundle {
+    /// When the bundle was collected
+    pub generated_at: DateTime<Utc>,
+
+    /// carbem version
+    pub carbem_version: String,
+
+    /// Operating system and architecture, e.g. `linux-x86_64`
+    pub platform: String,
+
+    /// Enabled cargo features
+    pub features: Vec<String>,
+
+    /// Registered providers
+    pub providers: Vec<ProviderInfo>,
+
+    /// Optional: client configuration, redacted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<Value>,
+
+    /// Schema differences observed in provider responses
+    pub schema_warnings: Vec<SchemaWarning>,
+
+    /// Last provider requests and responses, oldest first
+    pub exchanges: Vec<CapturedExchange>,
+}
+
+impl SupportBundle {
+    /// Collect a bundle, with the state of `client` and `config` when given
+    pub fn collect(client: Option<&CarbemClient>, config: Option<&ClientConfig>) -> Result<Self> {
+        Ok(Self {
+            generated_at: Utc::now(),
+            carbem_version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            features: enabled_features(),
+            providers: ProviderRegistry::new().available(),
+            config: config.map(redacted_config).transpose()?,
+            schema_warnings: client.map(|c| c.schema_warnings()).unwrap_or_default(),
+            exchanges: client.map(|c| c.recent_exchanges()).unwrap_or_default(),
+        })
+    }
+
+    /// Write the bundle as JSON to `path`, compressed according to its extension (`.gz`, `.zst`)
+    pub async fn write_to(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let data = Compression::from_path(&path.to_string_lossy())
+            .compress(json)
+            .await?;
+        fs::write(path, data).await.map_err(io_error)
+    }
+}
+
+// Configuration with credentials and account identifiers redacted
+fn redacted_config(config: &ClientConfig) -> Result<Value> {
+    let mut redactor = Redactor::new();
+    for account in &config.azure {
+        redactor = redactor.with_secret(account.auth.access_token.clone());
+    }
+    for account in &config.ibm {
+        redactor = redactor.with_secret(account.auth.api_key.clone());
+        if let Some(enterprise_id) = &account.enterprise_id {
+            redactor = redactor.with_secret(enterprise_id.clone());
+        }
+    }
+    for defaults in &config.query_defaults {
+        if let ProviderQueryConfig::Ibm(defaults) = defaults {
+            let ids = [
+                &defaults.enterprise_id,
+                &defaults.enterprise_account_id,
+                &defaults.account_group_id,
+            ];
+            for id in ids.into_iter().flatten() {
+                redactor = redactor.with_secret(id.clone());
+            }
+        }
+    }
+    for secret in config.proxy.iter().flat_map(ProxyConfig::secrets) {
+        redactor = redactor.with_secret(secret);
+    }
+    let json = redactor.redact(&serde_json::to_string(config)?);
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn enabled_features() -> Vec<String> {
+    [
+        ("rustls-tls", cfg!(feature = "rustls-tls")),
+        ("native-tls", cfg!(feature = "native-tls")),
+        ("native-tls-vendored", cfg!(feature = "native-tls-vendored")),
+        ("tracing", cfg!(feature = "tracing")),
+        ("kafka", cfg!(feature = "kafka")),
+        ("object-store", cfg!(feature = "object-store")),
+        ("google-sheets", cfg!(feature = "google-sheets")),
+        ("confluence", cfg!(feature = "confluence")),
+        ("plot", cfg!(feature = "plot")),
+        ("signing", cfg!(feature = "signing")),
+        ("templates", cfg!(feature = "templates")),
+        ("decimal", cfg!(feature = "decimal")),
+        ("mcp", cfg!(feature = "mcp")),
+        ("axum", cfg!(feature = "axum")),
+        ("tower", cfg!(feature = "tower")),
+        ("sqlite", cfg!(feature = "sqlite")),
+        ("redis", cfg!(feature = "redis")),
+        ("jwt", cfg!(feature = "jwt")),
+        ("msgpack", cfg!(feature = "msgpack")),
+        ("cbor", cfg!(feature = "cbor")),
+        ("test-kit", cfg!(feature = "test-kit")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collect_redacts_config() {
+        let config = ClientConfig::from_json(
+            r#"{
+                "azure": [{"access_token": "secret-token", "subscriptions": ["sub-1"]}],
+                "ibm": [{"api_key": "secret-key", "enterprise_id": "ent-12345"}]
+            }"#,
+        )
+        .unwrap();
+        let client = CarbemClient::from_config(&config).unwrap();
+
+        let bundle = SupportBundle::collect(Some(&client), Some(&config)).unwrap();
+        assert_eq!(bundle.carbem_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(bundle.providers.len(), 2);
+        assert!(bundle.exchanges.is_empty());
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        for secret in ["secret-token", "secret-key", "ent-12345"] {
+            assert!(!json.contains(secret));
+        }
+        assert_eq!(
+            bundle.config.unwrap()["azure"][0]["subscriptions"][0],
+            "sub-1"
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("carbem-support-{}.json.gz", std::process::id()));
+        SupportBundle::collect(None, None)
+            .unwrap()
+            .write_to(&path)
+            .await
+            .unwrap();
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!(&written[..2], &[0x1f, 0x8b]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+//! Reduction target tracking (SBTi-style trajectories)
+//!
+//! A [`ReductionTarget`] defines a baseline year and a reduction to reach by a
+//! target year, e.g. −42% by 2030 from 2020. The required trajectory is linear
+//! between the baseline emissions and the target emissions; tracking compares
+//! it to the yearly totals of a dataset and flags years above the trajectory.
+
+use serde::{Deserialize, Serialize};
+
+use crate::aggregation::{EmissionDataset, FiscalCalendar};
+use crate::error::{CarbemError, Result};
+
+/// A reduction target relative to a baseline year
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReductionTarget {
+    baseline_year: i32,
+    target_year: i32,
+    reduction_percent: f64,
+    baseline_kg_co2eq: Option<f64>,
+    tolerance_percent: f64,
+    calendar: FiscalCalendar,
+}
+
+impl ReductionTarget {
+    /// Reduce emissions by `reduction_percent` between `baseline_year` and `target_year`
+    pub fn new(baseline_year: i32, target_year: i32, reduction_percent: f64) -> Result<Self> {
+        if target_year <= baseline_year {
+            return Err(CarbemError::Config(format!(
+                "target year {} must be after baseline year {}",
+                target_year, baseline_year
+            )));
+        }
+        if !(0.0..=100.0).contains(&reduction_percent) {
+            return Err(CarbemError::Config(format!(
+                "reduction must be between 0 and 100 percent, got {}",
+                reduction_percent
+            )));
+        }
+        Ok(Self {
+            baseline_year,
+            target_year,
+            reduction_percent,
+            baseline_kg_co2eq: None,
+            tolerance_percent: 0.0,
+            calendar: FiscalCalendar::calendar_year(),
+        })
+    }
+
+    /// Use a known baseline instead of the baseline year total of the tracked dataset
+    pub fn with_baseline_kg_co2eq(mut self, baseline_kg_co2eq: f64) -> Self {
+        self.baseline_kg_co2eq = Some(baseline_kg_co2eq);
+        self
+    }
+
+    /// Only flag years exceeding the trajectory by more than `tolerance_percent`
+    pub fn with_tolerance_percent(mut self, tolerance_percent: f64) -> Self {
+        self.tolerance_percent = tolerance_percent;
+        self
+    }
+
+    /// Count years with a fiscal calendar instead of calendar years
+    pub fn with_calendar(mut self, calendar: FiscalCalendar) -> Self {
+        self.calendar = calendar;
+        self
+    }
+
+    /// Emissions allowed in `year` by a linear trajectory from `baseline_kg_co2eq`
+    ///
+    /// Years after the target year keep the target level.
+    pub fn required_kg_co2eq(&self, year: i32, baseline_kg_co2eq: f64) -> f64 {
+        let span = f64::from(self.target_year - self.baseline_year);
+        let elapsed =
+            f64::from((year - self.baseline_year).clamp(0, self.target_year - self.baseline_year));
+        let reduction = self.reduction_percent / 100.0 * elapsed / span;
+        baseline_kg_co2eq * (1.0 - reduction)
+    }
+
+    /// Compare the yearly totals of `dataset` to the required trajectory
+    ///
+    /// Fails when no baseline was given and `dataset` has no emissions in the
+    /// baseline year.
+    pub fn track(&self, dataset: &EmissionDataset) -> Result<TargetProgress> {
+        let actuals = dataset.group_by_fiscal_year(&self.calendar);
+        let baseline_kg_co2eq = match self.baseline_kg_co2eq {
+            Some(baseline) => baseline,
+            None => *actuals.get(&self.baseline_year).ok_or_else(|| {
+                CarbemError::Config(format!(
+                    "dataset has no emissions in baseline year {}",
+                    self.baseline_year
+                ))
+            })?,
+        };
+
+        let last_year = actuals
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(self.target_year)
+            .max(self.target_year);
+        let trajectory = (self.baseline_year..=last_year)
+            .map(|year| {
+                let required_kg_co2eq = self.required_kg_co2eq(year, baseline_kg_co2eq);
+                let actual_kg_co2eq = actuals.get(&year).copied();
+                let divergence_percent = actual_kg_co2eq
+                    .filter(|_| required_kg_co2eq > 0.0)
+                    .map(|actual| (actual - required_kg_co2eq) / required_kg_co2eq * 100.0);
+                let off_track = match (actual_kg_co2eq, divergence_percent) {
+                    (_, Some(divergence)) => divergence > self.tolerance_percent,
+                    (Some(actual), None) => actual > 0.0,
+                    (None, _) => false,
+                };
+                TrajectoryPoint {
+                    year,
+                    required_kg_co2eq,
+                    actual_kg_co2eq,
+                    divergence_percent,
+                    off_track,
+                }
+            })
+            .collect();
+
+        Ok(TargetProgress {
+            baseline_kg_co2eq,
+            target_kg_co2eq: self.required_kg_co2eq(self.target_year, baseline_kg_co2eq),
+            trajectory,
+        })
+    }
+}
+
+/// Required and actual emissions for one year
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrajectoryPoint {
+    /// Calendar or fiscal year
+    pub year: i32,
+
+    /// Emissions allowed by the trajectory in kg CO2e
+    pub required_kg_co2eq: f64,
+
+    /// Actual emissions in kg CO2e, `None` when the dataset has no data for the year
+    pub actual_kg_co2eq: Option<f64>,
+
+    /// Percent by which actual emissions exceed the trajectory (negative when below)
+    pub divergence_percent: Option<f64>,
+
+    /// Whether actual emissions exceed the trajectory beyond the tolerance
+    pub off_track: bool,
+}
+
+/// Progress of a dataset against a reduction target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetProgress {
+    /// Emissions of the baseline year in kg CO2e
+    pub baseline_kg_co2eq: f64,
+
+    /// Emissions to reach by the target year in kg CO2e
+    pub target_kg_co2eq: f64,
+
+    /// Trajectory from the baseline year to the target year or the last year with data
+    pub trajectory: Vec<TrajectoryPoint>,
+}
+
+impl TargetProgress {
+    /// Years whose emissions exceed the trajectory
+    pub fn off_track_years(&self) -> Vec<i32> {
+        self.trajectory
+            .iter()
+            .filter(|point| point.off_track)
+            .map(|point| point.year)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn emission(year: i32, kg: f64) -> CarbonEmission {
+        let start = Utc.with_ymd_and_hms(year, 6, 1, 0, 0, 0).unwrap();
+        CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: None,
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: kg,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(30),
+            },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_track_linear_trajectory() {
+        let target = ReductionTarget::new(2020, 2030, 42.0)
+            .unwrap()
+            .with_tolerance_percent(1.0);
+        let dataset = EmissionDataset::new(vec![
+            emission(2020, 1000.0),
+            emission(2021, 950.0),
+            emission(2022, 960.0),
+        ]);
+
+        let progress = target.track(&dataset).unwrap();
+        assert_eq!(progress.baseline_kg_co2eq, 1000.0);
+        assert!((progress.target_kg_co2eq - 580.0).abs() < 1e-9);
+        assert_eq!(progress.trajectory.len(), 11);
+
+        let year_2022 = progress.trajectory[2];
+        assert!((year_2022.required_kg_co2eq - 916.0).abs() < 1e-9);
+        assert_eq!(year_2022.actual_kg_co2eq, Some(960.0));
+        assert!(year_2022.off_track);
+        assert_eq!(progress.off_track_years(), vec![2022]);
+        assert_eq!(progress.trajectory[5].actual_kg_co2eq, None);
+    }
+
+    #[test]
+    fn test_track_requires_baseline() {
+        let target = ReductionTarget::new(2019, 2030, 50.0).unwrap();
+        let dataset = EmissionDataset::new(vec![emission(2020, 100.0)]);
+        assert!(target.track(&dataset).is_err());
+
+        let progress = target
+            .with_baseline_kg_co2eq(110.0)
+            .track(&dataset)
+            .unwrap();
+        assert_eq!(progress.off_track_years(), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_invalid_target() {
+        assert!(ReductionTarget::new(2030, 2020, 42.0).is_err());
+        assert!(ReductionTarget::new(2020, 2030, 142.0).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Provider-neutral service categories
+//!
+//! Each provider names its services differently: Azure reports "Virtual
+//! Machines" or `microsoft.compute/virtualmachines`, IBM Cloud "Virtual
+//! Server for VPC". [`ServiceCategory::classify`] maps these names onto a
+//! small shared taxonomy, so multi-cloud reports can group e.g. compute
+//! across all clouds with [`Dimension::ServiceCategory`](crate::Dimension).
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::CarbonEmission;
+
+/// Category of a cloud service, shared by all providers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceCategory {
+    /// Virtual machines, containers, serverless and application hosting
+    Compute,
+
+    /// Object, block and file storage, backups
+    Storage,
+
+    /// Networking, load balancing, gateways and content delivery
+    Network,
+
+    /// Managed databases and caches
+    Database,
+
+    /// Machine learning and AI services
+    Ai,
+}
+
+// Name fragments of each category, checked in order on lowercase service names:
+// specific categories come first, e.g. "SQL Database" is a database, not storage
+const RULES: &[(ServiceCategory, &[&str])] = &[
+    (
+        ServiceCategory::Ai,
+        &[
+            "machine learning",
+            "machinelearning",
+            "cognitive",
+            "openai",
+            "watson",
+            "ai services",
+            "bot service",
+        ],
+    ),
+    (
+        ServiceCategory::Database,
+        &[
+            "database",
+            "sql",
+            "cosmos",
+            "documentdb",
+            "dbfor",
+            "postgres",
+            "mysql",
+            "mariadb",
+            "mongodb",
+            "redis",
+            "cloudant",
+            "db2",
+        ],
+    ),
+    (
+        ServiceCategory::Network,
+        &[
+            "network",
+            "load balancer",
+            "loadbalancer",
+            "gateway",
+            "vpn",
+            "dns",
+            "cdn",
+            "front door",
+            "frontdoor",
+            "bandwidth",
+            "expressroute",
+            "direct link",
+            "firewall",
+            "traffic manager",
+        ],
+    ),
+    (
+        ServiceCategory::Storage,
+        &["storage", "disk", "backup", "blob", "archive", "data lake"],
+    ),
+    (
+        ServiceCategory::Compute,
+        &[
+            "virtual machine",
+            "virtualmachine",
+            "virtual server",
+            "compute",
+            "kubernetes",
+            "container",
+            "app service",
+            "web app",
+            "functions",
+            "bare metal",
+            "code engine",
+            "batch",
+            "cloud foundry",
+            "vmware",
+        ],
+    ),
+];
+
+impl ServiceCategory {
+    /// Category of a provider service name, `None` when it is not recognized
+    pub fn classify(service: &str) -> Option<Self> {
+        let service = service.to_lowercase();
+        RULES
+            .iter()
+            .find(|(_, fragments)| fragments.iter().any(|f| service.contains(f)))
+            .map(|(category, _)| *category)
+    }
+
+    /// Snake case name, as serialized
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceCategory::Compute => "compute",
+            ServiceCategory::Storage => "storage",
+            ServiceCategory::Network => "network",
+            ServiceCategory::Database => "database",
+            ServiceCategory::Ai => "ai",
+        }
+    }
+}
+
+impl fmt::Display for ServiceCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Set the category of emissions that have a service but no category yet
+pub fn categorize_emissions(emissions: &mut [CarbonEmission]) {
+    for emission in emissions {
+        if emission.service_category.is_none() {
+            emission.service_category = emission
+                .service
+                .as_deref()
+                .and_then(ServiceCategory::classify);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_provider_services() {
+        let cases = [
+            ("Virtual Machines", Some(ServiceCategory::Compute)),
+            (
+                "microsoft.compute/virtualmachines",
+                Some(ServiceCategory::Compute),
+            ),
+            ("Virtual Server for VPC", Some(ServiceCategory::Compute)),
+            ("Azure Kubernetes Service", Some(ServiceCategory::Compute)),
+            ("Cloud Object Storage", Some(ServiceCategory::Storage)),
+            (
+                "microsoft.storage/storageaccounts",
+                Some(ServiceCategory::Storage),
+            ),
+            ("SQL Database", Some(ServiceCategory::Database)),
+            ("Databases for PostgreSQL", Some(ServiceCategory::Database)),
+            ("Virtual Network", Some(ServiceCategory::Network)),
+            ("Azure Machine Learning", Some(ServiceCategory::Ai)),
+            ("watsonx.ai", Some(ServiceCategory::Ai)),
+            ("overall", None),
+        ];
+        for (service, expected) in cases {
+            assert_eq!(ServiceCategory::classify(service), expected, "{}", service);
+        }
+        assert_eq!(
+            serde_json::to_string(&ServiceCategory::Ai).unwrap(),
+            "\"ai\""
+        );
+    }
+}
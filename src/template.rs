@@ -0,0 +1,157 @@
+//! User-supplied report templates
+//!
+//! A [`ReportTemplate`] renders a [`Report`] with a
+//! [Tera](https://keats.github.io/tera/docs/) template instead of the built-in
+//! layout, so organizations can brand and restructure reports. Templates see:
+//!
+//! - `report`: the [`Report`], with `title`, `period` (start and end, or
+//!   null), `total_kg_co2eq` and `sections` (each with `heading`, `columns`
+//!   and `rows`)
+//! - `emissions`: every emission of the dataset as a
+//!   [`FlatEmissionRecord`], e.g. to build custom breakdowns
+//!
+//! HTML templates escape every inserted value; Markdown templates insert them
+//! as is.
+
+use std::error::Error as _;
+use std::path::Path;
+
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::aggregation::EmissionDataset;
+use crate::error::{CarbemError, Result};
+use crate::models::FlatEmissionRecord;
+use crate::report::Report;
+
+// Name of the single template, its extension selects autoescaping
+const HTML_TEMPLATE: &str = "report.html";
+const MARKDOWN_TEMPLATE: &str = "report.md";
+
+/// A Tera template rendering reports
+#[derive(Debug, Clone)]
+pub struct ReportTemplate {
+    tera: Tera,
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+struct TemplateData<'a> {
+    report: &'a Report,
+    emissions: Vec<FlatEmissionRecord>,
+}
+
+impl ReportTemplate {
+    /// Template producing HTML, with inserted values escaped
+    pub fn html(source: &str) -> Result<Self> {
+        Self::new(HTML_TEMPLATE, source)
+    }
+
+    /// Template producing Markdown
+    pub fn markdown(source: &str) -> Result<Self> {
+        Self::new(MARKDOWN_TEMPLATE, source)
+    }
+
+    /// Load a template file, as HTML if its extension is `html` or `htm`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            CarbemError::Config(format!(
+                "Failed to read report template {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("html" | "htm") => Self::html(&source),
+            _ => Self::markdown(&source),
+        }
+    }
+
+    fn new(name: &'static str, source: &str) -> Result<Self> {
+        let mut tera = Tera::default();
+        tera.add_raw_template(name, source).map_err(|e| {
+            CarbemError::Config(format!("Invalid report template: {}", details(&e)))
+        })?;
+        Ok(Self { tera, name })
+    }
+
+    /// Render `report`, built from `dataset`
+    pub fn render(&self, report: &Report, dataset: &EmissionDataset) -> Result<String> {
+        let data = TemplateData {
+            report,
+            emissions: dataset.emissions().iter().map(Into::into).collect(),
+        };
+        let context = Context::from_serialize(data)
+            .map_err(|e| CarbemError::Other(format!("Invalid template data: {}", details(&e))))?;
+        self.tera.render(self.name, &context).map_err(|e| {
+            CarbemError::Other(format!("Failed to render report template: {}", details(&e)))
+        })
+    }
+}
+
+// Tera errors only name the template; the cause is in their sources
+fn details(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CarbonEmission, TimePeriod};
+    use chrono::{TimeZone, Utc};
+
+    fn dataset() -> EmissionDataset {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        EmissionDataset::new(vec![CarbonEmission {
+            provider: "azure".to_string(),
+            region: "westeurope".to_string(),
+            service: Some("<VM>".to_string()),
+            service_category: None,
+            lineage: None,
+            emissions_kg_co2eq: 12.5,
+            time_period: TimePeriod {
+                start,
+                end: start + chrono::Duration::days(30),
+            },
+            metadata: None,
+        }])
+    }
+
+    #[test]
+    fn test_render_templates() {
+        let dataset = dataset();
+        let report = Report::from_dataset("ACME footprint", &dataset);
+
+        let markdown = ReportTemplate::markdown(
+            "# {{ report.title }}\n{% for e in emissions %}- {{ e.service }}: {{ e.emissions_kg_co2eq }}\n{% endfor %}",
+        )
+        .unwrap()
+        .render(&report, &dataset)
+        .unwrap();
+        assert_eq!(markdown, "# ACME footprint\n- <VM>: 12.5\n");
+
+        let html = ReportTemplate::html(
+            "{% for s in report.sections %}<h2>{{ s.heading }}</h2>{% for r in s.rows %}<p>{{ r.0 }}</p>{% endfor %}{% endfor %}",
+        )
+        .unwrap()
+        .render(&report, &dataset)
+        .unwrap();
+        assert!(html.contains("<h2>By service</h2><p>&lt;VM&gt;</p>"));
+
+        assert!(matches!(
+            ReportTemplate::markdown("{% for %}"),
+            Err(CarbemError::Config(_))
+        ));
+        let error = ReportTemplate::markdown("{{ report.missing }}")
+            .unwrap()
+            .render(&report, &dataset)
+            .unwrap_err();
+        assert!(error.to_string().contains("missing"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Authentication of callers of the endpoints
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::Request;
+use axum::http::HeaderMap;
+use axum::http::header::AUTHORIZATION;
+use axum::middleware::{self, Next};
+use axum::response::Response;
+
+use super::ApiError;
+#[cfg(feature = "jwt")]
+use super::jwt::JwtValidator;
+use crate::error::{CarbemError, Result};
+use crate::policy::{AccessPolicy, ApiKeyPolicies};
+
+/// Header carrying the API key, as an alternative to `Authorization: Bearer`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Accepted credentials and the access policies they grant
+///
+/// API keys are read from the `X-API-Key` header or an `Authorization:
+/// Bearer` header. With a [`JwtValidator`](super::JwtValidator), bearer
+/// tokens that are not API keys are validated as JWTs.
+#[derive(Debug, Clone, Default)]
+pub struct Authentication {
+    api_keys: ApiKeyPolicies,
+    #[cfg(feature = "jwt")]
+    jwt: Option<Arc<JwtValidator>>,
+}
+
+impl Authentication {
+    /// Authentication accepting no credentials until some are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept the API keys of `keys`
+    pub fn with_api_keys(mut self, keys: ApiKeyPolicies) -> Self {
+        self.api_keys = keys;
+        self
+    }
+
+    /// Accept bearer JWTs valid for `validator`
+    #[cfg(feature = "jwt")]
+    pub fn with_jwt(mut self, validator: JwtValidator) -> Self {
+        self.jwt = Some(Arc::new(validator));
+        self
+    }
+
+    /// Access policy granted by the credentials in `headers`
+    ///
+    /// Fails with [`CarbemError::Unauthenticated`] without valid credentials,
+    /// and with [`CarbemError::AccessDenied`] for valid credentials granting
+    /// no policy.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<AccessPolicy> {
+        if let Some(key) = headers.get(API_KEY_HEADER) {
+            let key = key.to_str().unwrap_or_default();
+            return self.api_key_policy(key);
+        }
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| CarbemError::Unauthenticated("missing credentials".to_string()))?;
+        #[cfg(feature = "jwt")]
+        if let Some(jwt) = &self.jwt
+            && self.api_keys.policy(token).is_none()
+        {
+            return jwt.validate(token).await;
+        }
+        self.api_key_policy(token)
+    }
+
+    fn api_key_policy(&self, key: &str) -> Result<AccessPolicy> {
+        self.api_keys
+            .policy(key)
+            .cloned()
+            .ok_or_else(|| CarbemError::Unauthenticated("unknown API key".to_string()))
+    }
+}
+
+/// `router` only answering requests authenticated by `authentication`
+///
+/// Each request is scoped by the [`AccessPolicy`] its credentials grant.
+pub fn require_auth(router: Router, authentication: Authentication) -> Router {
+    let authentication = Arc::new(authentication);
+    router.layer(middleware::from_fn(move |request, next| {
+        authenticate(authentication.clone(), request, next)
+    }))
+}
+
+/// `router` only answering requests with a key of `keys`, scoped by its policy
+pub fn require_api_key(router: Router, keys: ApiKeyPolicies) -> Router {
+    require_auth(router, Authentication::new().with_api_keys(keys))
+}
+
+async fn authenticate(
+    authentication: Arc<Authentication>,
+    mut request: Request,
+    next: Next,
+) -> std::result::Result<Response, ApiError> {
+    let policy = authentication.authenticate(request.headers()).await?;
+    request.extensions_mut().insert(Arc::new(policy));
+    Ok(next.run(request).await)
+}
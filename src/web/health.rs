@@ -0,0 +1,230 @@
+//! Liveness and readiness endpoints
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::client::CarbemClient;
+
+/// Default time provider probe results are reused for
+pub const DEFAULT_PROBE_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// Result of probing one provider instance
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderHealth {
+    /// Instance name, e.g. `azure:prod`, or provider name
+    pub provider: String,
+
+    /// Whether the provider authenticated and connected
+    pub healthy: bool,
+
+    /// Optional: why the probe failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// When the probe ran
+    pub checked_at: DateTime<Utc>,
+}
+
+/// `/healthz` and `/readyz` endpoints, e.g. for Kubernetes probes
+///
+/// `/healthz` answers `200` while the process serves requests. `/readyz`
+/// answers `200` too, unless provider probes are enabled with
+/// [`with_provider_probes`](Self::with_provider_probes): it then answers
+/// `503 Service Unavailable` while any provider fails to authenticate or
+/// connect. Probe results are cached, so frequent readiness checks do not
+/// reach the providers each time.
+#[derive(Clone)]
+pub struct HealthChecks {
+    client: CarbemClient,
+    probes: Option<Arc<Probes>>,
+}
+
+struct Probes {
+    max_age: Duration,
+    // Last results and when they were obtained; locked during probes, so
+    // that concurrent checks share one
+    last: Mutex<Option<(Vec<ProviderHealth>, Instant)>>,
+}
+
+impl HealthChecks {
+    /// Health checks of a service querying `client`, without provider probes
+    pub fn new(client: CarbemClient) -> Self {
+        Self {
+            client,
+            probes: None,
+        }
+    }
+
+    /// Include provider probes in readiness, reusing results for `max_age`
+    ///
+    /// See [`CarbemClient::probe_providers`] and [`DEFAULT_PROBE_MAX_AGE`].
+    pub fn with_provider_probes(mut self, max_age: Duration) -> Self {
+        self.probes = Some(Arc::new(Probes {
+            max_age,
+            last: Mutex::new(None),
+        }));
+        self
+    }
+
+    /// Provider probe results, probing again if the cached ones are too old
+    ///
+    /// Empty without provider probes.
+    pub async fn provider_health(&self) -> Vec<ProviderHealth> {
+        let Some(probes) = &self.probes else {
+            return Vec::new();
+        };
+        let mut last = probes.last.lock().await;
+        if let Some((health, checked)) = last.as_ref()
+            && checked.elapsed() < probes.max_age
+        {
+            return health.clone();
+        }
+        let checked_at = Utc::now();
+        let health: Vec<ProviderHealth> = self
+            .client
+            .probe_providers()
+            .await
+            .into_iter()
+            .map(|(provider, result)| ProviderHealth {
+                provider,
+                healthy: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+                checked_at,
+            })
+            .collect();
+        *last = Some((health.clone(), Instant::now()));
+        health
+    }
+
+    /// Router serving `/healthz` and `/readyz`, to merge into the service's
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .with_state(self)
+    }
+}
+
+impl std::fmt::Debug for HealthChecks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HealthChecks")
+            .field("probe_max_age", &self.probes.as_ref().map(|p| p.max_age))
+            .finish_non_exhaustive()
+    }
+}
+
+async fn healthz() -> Json<serde_json::Value> {
+    Json(json!({"status": "ok"}))
+}
+
+async fn readyz(State(checks): State<HealthChecks>) -> Response {
+    let providers = checks.provider_health().await;
+    let ready = providers.iter().all(|provider| provider.healthy);
+    let (status, label) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+    (
+        status,
+        Json(json!({"status": label, "providers": providers})),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::AzureConfig;
+    use crate::error::{CarbemError, Result};
+    use crate::transport::{HttpRequest, HttpResponse, Transport};
+
+    // Fails every request
+    #[derive(Debug, Default)]
+    struct Unreachable {
+        requests: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Transport for Unreachable {
+        async fn send(&self, _request: HttpRequest) -> Result<HttpResponse> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Err(CarbemError::Other("connection refused".to_string()))
+        }
+    }
+
+    async fn get_json(router: Router, uri: &str) -> (StatusCode, Value) {
+        let response = router
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_failing_providers() {
+        let transport = Arc::new(Unreachable::default());
+        let client = CarbemClient::builder()
+            .with_azure(AzureConfig {
+                access_token: "token".to_string(),
+            })
+            .unwrap()
+            .with_instance_name("prod")
+            .unwrap()
+            .with_transport(transport.clone())
+            .build();
+
+        let router = HealthChecks::new(client.clone()).router();
+        assert_eq!(get_json(router.clone(), "/healthz").await.0, StatusCode::OK);
+        assert_eq!(get_json(router, "/readyz").await.0, StatusCode::OK);
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 0);
+
+        let router = HealthChecks::new(client)
+            .with_provider_probes(DEFAULT_PROBE_MAX_AGE)
+            .router();
+        let (status, body) = get_json(router.clone(), "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["providers"][0]["provider"], "azure:prod");
+        assert!(
+            body["providers"][0]["error"]
+                .as_str()
+                .unwrap()
+                .contains("connection refused")
+        );
+        assert_eq!(
+            get_json(router, "/readyz").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 1);
+
+        let (status, body) = get_json(
+            HealthChecks::new(CarbemClient::demo())
+                .with_provider_probes(DEFAULT_PROBE_MAX_AGE)
+                .router(),
+            "/readyz",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+    }
+}
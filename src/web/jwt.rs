@@ -0,0 +1,318 @@
+//! JWT validation against an OpenID Connect issuer
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use reqwest::Method;
+use reqwest::header::HeaderMap;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+
+use crate::error::{CarbemError, Result};
+use crate::logging::debug;
+use crate::policy::AccessPolicy;
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
+
+/// Shortest time between two fetches of the issuer's keys
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Validates bearer JWTs and maps their claims to access policies
+///
+/// Tokens must be signed by the issuer, be issued by it (`iss`), be meant for
+/// the audience (`aud`) and not be expired (`exp`). The policy of a token is
+/// that of the first [`with_claim_policy`](Self::with_claim_policy) mapping
+/// it matches, or the [default policy](Self::with_default_policy); tokens
+/// matching none are denied.
+#[derive(Debug)]
+pub struct JwtValidator {
+    issuer: String,
+    audience: String,
+    keys: Keys,
+    policies: Vec<ClaimPolicy>,
+    default_policy: Option<AccessPolicy>,
+}
+
+enum Keys {
+    // Shared secret of HMAC-signed tokens
+    Secret(DecodingKey),
+    // Public keys published by the issuer, refetched for unknown key IDs
+    Jwks {
+        url: String,
+        transport: Arc<dyn Transport>,
+        cache: RwLock<(JwkSet, Instant)>,
+    },
+}
+
+impl std::fmt::Debug for Keys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Keys::Secret(_) => f.write_str("Secret"),
+            Keys::Jwks { url, .. } => f.debug_struct("Jwks").field("url", url).finish(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ClaimPolicy {
+    claim: String,
+    value: String,
+    policy: AccessPolicy,
+}
+
+impl JwtValidator {
+    /// Validator of tokens issued by `issuer` for `audience`
+    ///
+    /// The issuer's signing keys are found through its OpenID Connect
+    /// discovery document, `{issuer}/.well-known/openid-configuration`.
+    pub async fn discover(issuer: &str, audience: &str) -> Result<Self> {
+        Self::discover_with(Arc::new(ReqwestTransport::default()), issuer, audience).await
+    }
+
+    /// Like [`discover`](Self::discover), fetching the keys with `transport`
+    pub async fn discover_with(
+        transport: Arc<dyn Transport>,
+        issuer: &str,
+        audience: &str,
+    ) -> Result<Self> {
+        let issuer = issuer.trim_end_matches('/');
+        let discovery: Value = fetch_json(
+            transport.as_ref(),
+            &format!("{}/.well-known/openid-configuration", issuer),
+        )
+        .await?;
+        let url = discovery["jwks_uri"]
+            .as_str()
+            .ok_or_else(|| {
+                CarbemError::Config(format!("issuer {} does not publish a jwks_uri", issuer))
+            })?
+            .to_string();
+        let jwks = fetch_json(transport.as_ref(), &url).await?;
+        Ok(Self::new(
+            issuer,
+            audience,
+            Keys::Jwks {
+                url,
+                transport,
+                cache: RwLock::new((jwks, Instant::now())),
+            },
+        ))
+    }
+
+    /// Validator of HMAC-signed (`HS256`, `HS384` or `HS512`) tokens
+    pub fn from_secret(issuer: &str, audience: &str, secret: &[u8]) -> Self {
+        Self::new(
+            issuer,
+            audience,
+            Keys::Secret(DecodingKey::from_secret(secret)),
+        )
+    }
+
+    fn new(issuer: &str, audience: &str, keys: Keys) -> Self {
+        Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            audience: audience.to_string(),
+            keys,
+            policies: Vec::new(),
+            default_policy: None,
+        }
+    }
+
+    /// Grant `policy` to tokens whose claim `claim` holds `value`
+    ///
+    /// The claim holds the value if it is equal to it, lists it in an array,
+    /// or names it in a space-separated string, as `scope` does. E.g.
+    /// `with_claim_policy("groups", "payments", policy)`.
+    pub fn with_claim_policy(
+        mut self,
+        claim: impl Into<String>,
+        value: impl Into<String>,
+        policy: AccessPolicy,
+    ) -> Self {
+        self.policies.push(ClaimPolicy {
+            claim: claim.into(),
+            value: value.into(),
+            policy,
+        });
+        self
+    }
+
+    /// Grant `policy` to valid tokens matching no claim mapping
+    pub fn with_default_policy(mut self, policy: AccessPolicy) -> Self {
+        self.default_policy = Some(policy);
+        self
+    }
+
+    /// Access policy granted by `token`
+    ///
+    /// Fails with [`CarbemError::Unauthenticated`] if the token is invalid,
+    /// and with [`CarbemError::AccessDenied`] if it grants no policy.
+    pub async fn validate(&self, token: &str) -> Result<AccessPolicy> {
+        let header = decode_header(token).map_err(invalid_token)?;
+        let key = match &self.keys {
+            Keys::Secret(key) => {
+                if !matches!(
+                    header.alg,
+                    Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+                ) {
+                    return Err(invalid_token(format!(
+                        "unexpected algorithm {:?}",
+                        header.alg
+                    )));
+                }
+                key.clone()
+            }
+            Keys::Jwks { .. } => self.public_key(header.kid.as_deref()).await?,
+        };
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        let claims = decode::<Map<String, Value>>(token, &key, &validation)
+            .map_err(invalid_token)?
+            .claims;
+        self.policies
+            .iter()
+            .find(|mapping| {
+                claims
+                    .get(&mapping.claim)
+                    .is_some_and(|claim| holds(claim, &mapping.value))
+            })
+            .map(|mapping| &mapping.policy)
+            .or(self.default_policy.as_ref())
+            .cloned()
+            .ok_or_else(|| CarbemError::AccessDenied("no access policy for this token".to_string()))
+    }
+
+    // Public key `kid` of the issuer, refetching the keys if it is unknown
+    async fn public_key(&self, kid: Option<&str>) -> Result<DecodingKey> {
+        let Keys::Jwks {
+            url,
+            transport,
+            cache,
+        } = &self.keys
+        else {
+            unreachable!("only called for published keys");
+        };
+        if let Some(key) = find_key(&cache.read().await.0, kid)? {
+            return Ok(key);
+        }
+        let mut cache = cache.write().await;
+        if cache.1.elapsed() >= JWKS_REFRESH_INTERVAL {
+            debug!("Refetching the signing keys of {}", self.issuer);
+            *cache = (fetch_json(transport.as_ref(), url).await?, Instant::now());
+        }
+        find_key(&cache.0, kid)?
+            .ok_or_else(|| invalid_token(format!("unknown key ID {:?}", kid.unwrap_or_default())))
+    }
+}
+
+// Key `kid` of `jwks`, or its only key for tokens without a key ID
+fn find_key(jwks: &JwkSet, kid: Option<&str>) -> Result<Option<DecodingKey>> {
+    let jwk = match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    };
+    jwk.map(|jwk| DecodingKey::from_jwk(jwk).map_err(invalid_token))
+        .transpose()
+}
+
+// Whether `claim` is `value`, lists it or names it in a space-separated string
+fn holds(claim: &Value, value: &str) -> bool {
+    match claim {
+        Value::String(text) => text.split(' ').any(|word| word == value),
+        Value::Array(items) => items.iter().any(|item| item.as_str() == Some(value)),
+        _ => false,
+    }
+}
+
+async fn fetch_json<T: DeserializeOwned>(transport: &dyn Transport, url: &str) -> Result<T> {
+    let response = transport
+        .send(HttpRequest::new(Method::GET, url, HeaderMap::new()))
+        .await?;
+    if !response.is_success() {
+        return Err(CarbemError::Api(format!(
+            "{} returned {}",
+            url, response.status
+        )));
+    }
+    Ok(serde_json::from_slice(&response.body)?)
+}
+
+fn invalid_token(error: impl std::fmt::Display) -> CarbemError {
+    CarbemError::Unauthenticated(format!("invalid token: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::CarbemClient;
+    use crate::web::{Authentication, require_auth, router};
+
+    #[tokio::test]
+    async fn test_tokens_grant_claim_policies() {
+        let validator =
+            JwtValidator::from_secret("https://login.example.com/", "carbem", b"secret")
+                .with_claim_policy(
+                    "groups",
+                    "payments",
+                    AccessPolicy::unrestricted().with_regions(["westeurope"]),
+                );
+        let token = |claims: Value| {
+            encode(
+                &Header::default(),
+                &claims,
+                &EncodingKey::from_secret(b"secret"),
+            )
+            .unwrap()
+        };
+        let exp = jsonwebtoken::get_current_timestamp() + 600;
+        let claims = json!({"iss": "https://login.example.com", "aud": "carbem", "exp": exp, "groups": ["payments"]});
+
+        let policy = validator.validate(&token(claims.clone())).await.unwrap();
+        assert_eq!(policy.regions, ["westeurope"]);
+
+        let mut other = claims.clone();
+        other["aud"] = json!("billing");
+        assert!(matches!(
+            validator.validate(&token(other)).await,
+            Err(CarbemError::Unauthenticated(_))
+        ));
+        let mut other = claims.clone();
+        other["groups"] = json!(["platform"]);
+        assert!(matches!(
+            validator.validate(&token(other)).await,
+            Err(CarbemError::AccessDenied(_))
+        ));
+        let forged = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"guess"),
+        )
+        .unwrap();
+        assert!(validator.validate(&forged).await.is_err());
+
+        let app = require_auth(
+            router(CarbemClient::demo()),
+            Authentication::new().with_jwt(validator),
+        );
+        let response = app
+            .oneshot(
+                Request::get("/emissions?provider=azure&start=2024-01-01&end=2024-01-31")
+                    .header("Authorization", format!("Bearer {}", token(claims)))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
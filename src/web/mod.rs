@@ -0,0 +1,221 @@
+//! axum handlers and extractors for emissions endpoints
+//!
+//! With the `axum` feature, services mount carbem endpoints into their
+//! existing router. [`emissions`] answers `GET` requests with the flat JSON
+//! records of [`FlatEmissionRecord`], for a query read from the URL by
+//! [`EmissionsQuery`]:
+//!
+//! ```text
+//! GET /emissions?provider=azure&start=2024-01-01&end=2024-03-31&regions=westeurope,northeurope
+//! ```
+//!
+//! The client comes from the router state, directly or through
+//! [`FromRef`](axum::extract::FromRef) for a larger application state, and
+//! errors become JSON responses with a matching status (see [`ApiError`]).
+//! [`router`] builds a ready-made router with the endpoint.
+//!
+//! Without `config.` parameters, the client's provider configuration and
+//! defaults apply.
+//!
+//! [`HealthChecks`] serves `/healthz` and `/readyz` for orchestrators,
+//! optionally probing the providers for readiness.
+//!
+//! [`require_auth`] restricts a router to authenticated callers, with API
+//! keys or, with the `jwt` feature, tokens of an OpenID Connect issuer. Each
+//! caller is scoped by an [`AccessPolicy`]: [`emissions`] authorizes every
+//! query against the policy of the request and only returns the emissions it
+//! allows.
+
+mod auth;
+mod health;
+#[cfg(feature = "jwt")]
+mod jwt;
+
+use std::sync::Arc;
+
+use axum::extract::{FromRequestParts, State};
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde_json::json;
+
+use crate::client::CarbemClient;
+use crate::error::CarbemError;
+use crate::exit::ExitStatus;
+use crate::models::{EmissionQuery, FlatEmissionRecord};
+use crate::policy::AccessPolicy;
+
+pub use auth::{API_KEY_HEADER, Authentication, require_api_key, require_auth};
+pub use health::{DEFAULT_PROBE_MAX_AGE, HealthChecks, ProviderHealth};
+#[cfg(feature = "jwt")]
+pub use jwt::JwtValidator;
+
+/// Extractor of an [`EmissionQuery`] from the URL, see [`EmissionQuery::from_query_string`]
+#[derive(Debug, Clone)]
+pub struct EmissionsQuery(pub EmissionQuery);
+
+impl<S: Send + Sync> FromRequestParts<S> for EmissionsQuery {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+        Ok(Self(EmissionQuery::from_query_string(query)?))
+    }
+}
+
+/// A [`CarbemError`] as a JSON error response
+///
+/// The body is `{"error": <class>, "message": <error>}`. Invalid queries are
+/// `400 Bad Request`, missing or invalid credentials `401 Unauthorized`, queries
+/// outside the caller's policy `403 Forbidden`, exceeded provider rate
+/// limits `429 Too Many Requests`, provider and credential failures
+/// `502 Bad Gateway` and other errors `500 Internal Server Error`.
+#[derive(Debug)]
+pub struct ApiError(pub CarbemError);
+
+impl From<CarbemError> for ApiError {
+    fn from(error: CarbemError) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, class) = match (self.0.root(), ExitStatus::from(&self.0)) {
+            (CarbemError::Unauthenticated(_), _) => (StatusCode::UNAUTHORIZED, "unauthenticated"),
+            (CarbemError::AccessDenied(_), _) => (StatusCode::FORBIDDEN, "forbidden"),
+            (_, ExitStatus::Config) => (StatusCode::BAD_REQUEST, "invalid_query"),
+            (_, ExitStatus::RateLimit) => (StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+            (_, ExitStatus::Auth) => (StatusCode::BAD_GATEWAY, "provider_auth"),
+            (_, ExitStatus::Provider) => (StatusCode::BAD_GATEWAY, "provider"),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+        };
+        let body = json!({"error": class, "message": self.0.to_string()});
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Handler answering an emissions request with flat JSON records
+///
+/// With an [`AccessPolicy`] in the request extensions, e.g. set by
+/// [`require_auth`], the query is authorized against it and only the
+/// emissions it allows are returned.
+pub async fn emissions(
+    State(client): State<CarbemClient>,
+    policy: Option<Extension<Arc<AccessPolicy>>>,
+    EmissionsQuery(query): EmissionsQuery,
+) -> Result<Json<Vec<FlatEmissionRecord>>, ApiError> {
+    let records = match policy {
+        Some(Extension(policy)) => client
+            .query_emissions(&policy.authorize(&query)?)
+            .await?
+            .iter()
+            .filter(|emission| policy.allows(emission))
+            .map(FlatEmissionRecord::from)
+            .collect(),
+        None => client
+            .query_emissions(&query)
+            .await?
+            .iter()
+            .map(FlatEmissionRecord::from)
+            .collect(),
+    };
+    Ok(Json(records))
+}
+
+/// Router serving [`emissions`] at `/emissions`, to nest or merge
+pub fn router(client: CarbemClient) -> Router {
+    Router::new()
+        .route("/emissions", get(emissions))
+        .with_state(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::policy::ApiKeyPolicies;
+
+    async fn get_json(uri: &str) -> (StatusCode, Value) {
+        send(router(CarbemClient::demo()), Request::get(uri)).await
+    }
+
+    async fn send(router: Router, request: axum::http::request::Builder) -> (StatusCode, Value) {
+        let response = router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_emissions_endpoint() {
+        let (status, body) = get_json(
+            "/emissions?provider=azure&start=2024-01-01&end=2024-02-29&regions=westeurope",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let records = body.as_array().unwrap();
+        assert!(!records.is_empty());
+        assert!(
+            records
+                .iter()
+                .all(|record| record["region"] == "westeurope")
+        );
+
+        let (status, body) = get_json("/emissions?provider=azure&start=2024-03-01").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_query");
+
+        let (status, body) =
+            get_json("/emissions?provider=aws&start=2024-01-01&end=2024-01-31").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["message"].as_str().unwrap().contains("aws"));
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_scope_queries() {
+        let keys = ApiKeyPolicies::new().with_key(
+            "k-payments",
+            AccessPolicy::unrestricted()
+                .with_providers(["azure"])
+                .with_regions(["westeurope"]),
+        );
+        let router = require_api_key(router(CarbemClient::demo()), keys);
+        let uri = "/emissions?provider=azure&start=2024-01-01&end=2024-02-29";
+
+        let (status, body) = send(router.clone(), Request::get(uri)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "unauthenticated");
+
+        let (status, body) = send(
+            router.clone(),
+            Request::get(uri).header("Authorization", "Bearer k-payments"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let records = body.as_array().unwrap();
+        assert!(!records.is_empty());
+        assert!(
+            records
+                .iter()
+                .all(|record| record["region"] == "westeurope")
+        );
+
+        let (status, body) = send(
+            router,
+            Request::get(format!("{}&regions=eastus", uri)).header(API_KEY_HEADER, "k-payments"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body["message"].as_str().unwrap().contains("eastus"));
+    }
+}
@@ -0,0 +1,60 @@
+//! A custom runtime installed with `set_runtime`
+//!
+//! The runtime is process-wide, so this runs in its own test binary.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use carbem::runtime::{BlockingTask, BoxFuture, Runtime, TokioRuntime, runtime, set_runtime};
+use carbem::{DatasetSnapshot, EmissionDataset};
+
+// Delegates to Tokio, counting what goes through it
+#[derive(Debug, Default)]
+struct Counting {
+    spawned: AtomicUsize,
+    blocking: AtomicUsize,
+    slept: AtomicUsize,
+}
+
+impl Runtime for Counting {
+    fn spawn(&self, future: BoxFuture) {
+        self.spawned.fetch_add(1, Ordering::SeqCst);
+        TokioRuntime.spawn(future);
+    }
+
+    fn spawn_blocking(&self, task: BlockingTask) {
+        self.blocking.fetch_add(1, Ordering::SeqCst);
+        TokioRuntime.spawn_blocking(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture {
+        self.slept.fetch_add(1, Ordering::SeqCst);
+        TokioRuntime.sleep(duration)
+    }
+}
+
+#[tokio::test]
+async fn test_custom_runtime() {
+    let counting = Arc::new(Counting::default());
+    set_runtime(counting.clone()).unwrap();
+    assert!(set_runtime(Arc::new(TokioRuntime)).is_err());
+
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    runtime().spawn(Box::pin(async move {
+        runtime().sleep(Duration::from_millis(1)).await;
+        sender.send(()).unwrap();
+    }));
+    receiver.await.unwrap();
+    assert!(counting.spawned.load(Ordering::SeqCst) >= 1);
+    assert!(counting.slept.load(Ordering::SeqCst) >= 1);
+
+    // File access runs as blocking tasks
+    let path = std::env::temp_dir().join(format!("carbem-runtime-{}.json", std::process::id()));
+    let snapshot = EmissionDataset::new(Vec::new()).snapshot();
+    snapshot.write_to(&path).await.unwrap();
+    let read = DatasetSnapshot::read_from(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(read.content_hash, snapshot.content_hash);
+    assert_eq!(counting.blocking.load(Ordering::SeqCst), 2);
+}